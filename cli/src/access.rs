@@ -0,0 +1,305 @@
+//! Access control of `mina-wallet serve`
+//!
+//! Without an access file, every endpoint is open to whoever can reach the
+//! server. With `--access FILE`, each endpoint is a method that is either
+//! public or allowed to some API tokens, sent as `Authorization: Bearer
+//! <token>`:
+//!
+//! ```toml
+//! # Methods anyone can call
+//! public = ["validate", "openapi"]
+//!
+//! [[tokens]]
+//! name = "checkout"
+//! # SHA-256 of the token, e.g. from `printf %s "$TOKEN" | sha256sum`
+//! sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//! methods = ["sign-payment", "broadcast"]
+//! ```
+//!
+//! Only hashes of the tokens are kept, so the file does not leak them. The
+//! methods are `address`, `validate`, `sign-payment`, `broadcast`, `openapi`
+//! and `metrics`; `*` stands for all of them.
+
+use mina_web_wallet_core::secret;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Methods of the API, as named in access files
+const METHODS: [&str; 6] = [
+    "address",
+    "validate",
+    "sign-payment",
+    "broadcast",
+    "openapi",
+    "metrics",
+];
+
+/// Stands for every method in an access file
+const ALL_METHODS: &str = "*";
+
+/// Who may call which method
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessControl {
+    /// Methods that need no token
+    #[serde(default)]
+    public: Vec<String>,
+    #[serde(default)]
+    tokens: Vec<ApiToken>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiToken {
+    /// Shown in logs instead of the token
+    name: String,
+    /// Hex SHA-256 of the token
+    sha256: String,
+    methods: Vec<String>,
+}
+
+/// Why a request is refused
+pub enum Denied {
+    /// No token, or one that is not in the access file
+    Unauthenticated,
+    /// The token is known but not allowed to call the method
+    Forbidden { token: String },
+}
+
+impl AccessControl {
+    /// Every method is public, as without an access file
+    pub fn open() -> Self {
+        Self {
+            public: vec![ALL_METHODS.to_string()],
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Read and check an access file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let access: Self =
+            toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        access.validate()?;
+        Ok(access)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let methods = self
+            .public
+            .iter()
+            .chain(self.tokens.iter().flat_map(|t| &t.methods));
+        for method in methods {
+            if method != ALL_METHODS && !METHODS.contains(&method.as_str()) {
+                return Err(format!(
+                    "Unknown method '{}': expected {} or {}",
+                    method,
+                    METHODS.join(", "),
+                    ALL_METHODS
+                ));
+            }
+        }
+        for (i, token) in self.tokens.iter().enumerate() {
+            let valid_hash =
+                token.sha256.len() == 64 && token.sha256.chars().all(|c| c.is_ascii_hexdigit());
+            if !valid_hash {
+                return Err(format!(
+                    "The sha256 of token '{}' is not 64 hex characters",
+                    token.name
+                ));
+            }
+            if self.tokens[..i].iter().any(|t| t.name == token.name) {
+                return Err(format!("Two tokens are named '{}'", token.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether some method needs a token
+    pub fn requires_token(&self) -> bool {
+        METHODS.iter().any(|method| !allows(&self.public, method))
+    }
+
+    /// Refuse to serve on `addr` what anyone reaching it could abuse: signing
+    /// with the key of the server without a token, or tokens sent in clear
+    /// beyond loopback
+    pub fn check_exposure(
+        &self,
+        addr: &SocketAddr,
+        serves_key: bool,
+        tls: bool,
+        insecure: bool,
+    ) -> Result<(), String> {
+        if addr.ip().is_loopback() {
+            return Ok(());
+        }
+        if serves_key && allows(&self.public, "sign-payment") {
+            return Err(format!(
+                "Anyone who can reach {} could sign payments with the key: \
+                 require a token for sign-payment with --access, or listen on a loopback address",
+                addr
+            ));
+        }
+        if self.requires_token() && !tls && !insecure {
+            return Err(
+                "API tokens would be sent in clear over HTTP: serve HTTPS with \
+                 --tls-cert and --tls-key, or pass --insecure"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that a request with the bearer token `bearer` may call `method`
+    pub fn authorize(&self, method: &str, bearer: Option<&str>) -> Result<(), Denied> {
+        if allows(&self.public, method) {
+            return Ok(());
+        }
+        let Some(bearer) = bearer else {
+            return Err(Denied::Unauthenticated);
+        };
+        let hash = hex::encode(Sha256::digest(bearer.as_bytes()));
+        // Compare with every token, so that timing does not tell which matched
+        let mut found = None;
+        for token in &self.tokens {
+            let matches = secret::ct_eq(hash.as_bytes(), token.sha256.to_lowercase().as_bytes());
+            if matches && found.is_none() {
+                found = Some(token);
+            }
+        }
+        match found {
+            None => Err(Denied::Unauthenticated),
+            Some(token) if allows(&token.methods, method) => Ok(()),
+            Some(token) => Err(Denied::Forbidden {
+                token: token.name.clone(),
+            }),
+        }
+    }
+}
+
+fn allows(methods: &[String], method: &str) -> bool {
+    methods.iter().any(|m| m == ALL_METHODS || m == method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of "test"
+    const TEST_HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    /// SHA-256 of "admin"
+    const ADMIN_HASH: &str = "8c6976e5b5410415bde908bd4dee15dfb167a9c873fc4bb8a81f6f2ab448a918";
+
+    fn parse(text: &str) -> Result<AccessControl, String> {
+        let access: AccessControl = toml::from_str(text).map_err(|e| e.to_string())?;
+        access.validate()?;
+        Ok(access)
+    }
+
+    fn token(name: &str, sha256: &str, methods: &str) -> String {
+        format!(
+            "[[tokens]]\nname = \"{}\"\nsha256 = \"{}\"\nmethods = {}\n",
+            name, sha256, methods
+        )
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(parse("public = [\"validate\", \"openapi\"]").is_ok());
+        assert!(parse("public = [\"*\"]").is_ok());
+        assert!(
+            parse("public = [\"sign\"]")
+                .unwrap_err()
+                .contains("Unknown method 'sign'")
+        );
+        assert!(
+            parse(&token("a", TEST_HASH, "[\"delete\"]"))
+                .unwrap_err()
+                .contains("Unknown method")
+        );
+
+        let duplicate = token("a", TEST_HASH, "[\"*\"]") + &token("a", ADMIN_HASH, "[\"*\"]");
+        assert!(parse(&duplicate).unwrap_err().contains("Two tokens"));
+
+        let bad_hashes = [
+            TEST_HASH[..63].to_string(),
+            format!("{}z", &TEST_HASH[..63]),
+            String::new(),
+        ];
+        for bad in &bad_hashes {
+            assert!(
+                parse(&token("a", bad, "[\"*\"]"))
+                    .unwrap_err()
+                    .contains("not 64 hex characters")
+            );
+        }
+        assert!(parse(&token("a", &TEST_HASH.to_uppercase(), "[\"*\"]")).is_ok());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let access = parse(
+            &("public = [\"validate\"]\n".to_string()
+                + &token("checkout", TEST_HASH, "[\"sign-payment\"]")
+                + &token("admin", &ADMIN_HASH.to_uppercase(), "[\"*\"]")),
+        )
+        .unwrap();
+        assert!(access.requires_token());
+
+        // Public methods need no token, and ignore a wrong one
+        assert!(access.authorize("validate", None).is_ok());
+        assert!(access.authorize("validate", Some("wrong")).is_ok());
+
+        assert!(matches!(
+            access.authorize("sign-payment", None),
+            Err(Denied::Unauthenticated)
+        ));
+        assert!(matches!(
+            access.authorize("sign-payment", Some("wrong")),
+            Err(Denied::Unauthenticated)
+        ));
+        assert!(access.authorize("sign-payment", Some("test")).is_ok());
+        assert!(matches!(
+            access.authorize("broadcast", Some("test")),
+            Err(Denied::Forbidden { token }) if token == "checkout"
+        ));
+
+        // Hashes are compared case-insensitively
+        assert!(access.authorize("broadcast", Some("admin")).is_ok());
+        assert!(access.authorize("metrics", Some("admin")).is_ok());
+
+        let open = AccessControl::open();
+        assert!(!open.requires_token());
+        assert!(open.authorize("sign-payment", None).is_ok());
+    }
+
+    #[test]
+    fn test_check_exposure() {
+        let public: SocketAddr = "0.0.0.0:3000".parse().unwrap();
+        let loopback: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        // Signing is public even though not every method is
+        let partial = parse("public = [\"sign-payment\"]").unwrap();
+        assert!(partial.requires_token());
+        assert!(partial.check_exposure(&public, true, true, false).is_err());
+        assert!(
+            partial
+                .check_exposure(&loopback, true, false, false)
+                .is_ok()
+        );
+        assert!(partial.check_exposure(&public, false, true, false).is_ok());
+
+        let open = AccessControl::open();
+        assert!(open.check_exposure(&public, true, true, true).is_err());
+        assert!(open.check_exposure(&public, false, false, false).is_ok());
+
+        // Tokens need TLS, unless --insecure
+        let guarded = parse(&token("checkout", TEST_HASH, "[\"sign-payment\"]")).unwrap();
+        assert!(guarded.check_exposure(&public, true, false, false).is_err());
+        assert!(guarded.check_exposure(&public, true, false, true).is_ok());
+        assert!(guarded.check_exposure(&public, true, true, false).is_ok());
+    }
+}
@@ -0,0 +1,309 @@
+//! Session agent holding unlocked keystore keys
+//!
+//! `unlock` starts a background agent, as ssh-agent does, that keeps the keys
+//! derived from keystore passphrases in memory, so that later commands sign
+//! without asking for the passphrase again. Commands reach it over a Unix
+//! socket in a directory of the keystore that only the user can open. Each
+//! key is wiped when its timeout expires or on `lock`, and the agent exits
+//! once it holds none. Secret keys themselves never reach the agent.
+
+use mina_web_wallet_core::keystore::KEY_LEN;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// How long a command waits for the agent to answer
+#[cfg(unix)]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the agent checks for expired keys
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(not(unix))]
+const AGENT_UNSUPPORTED: &str = "The session agent needs Unix sockets, which this platform lacks";
+
+/// A key derived from a keystore passphrase
+pub type DerivedKey = Zeroizing<[u8; KEY_LEN]>;
+
+/// A key held by the agent
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockedKey {
+    pub address: String,
+    /// Seconds until the key is wiped
+    pub expires_in: u64,
+}
+
+#[cfg(unix)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum Request {
+    Add {
+        address: String,
+        key: HexKey,
+        timeout: u64,
+    },
+    Get {
+        address: String,
+    },
+    List,
+    Lock,
+}
+
+#[cfg(unix)]
+#[derive(Default, Serialize, Deserialize)]
+struct Response {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<HexKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keys: Vec<UnlockedKey>,
+}
+
+/// A derived key in hex, wiped when dropped
+#[cfg(unix)]
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(transparent)]
+struct HexKey(String);
+
+/// Socket of the agent of the keystore in `keystore_dir`
+pub fn socket_path(keystore_dir: &Path) -> PathBuf {
+    keystore_dir.join("agent").join("agent.sock")
+}
+
+/// Hand a key to the agent for `timeout` seconds, starting the agent if needed
+#[cfg(unix)]
+pub fn add(socket: &Path, address: &str, key: &[u8; KEY_LEN], timeout: u64) -> Result<(), String> {
+    if UnixStream::connect(socket).is_err() {
+        spawn(socket)?;
+    }
+    request(
+        socket,
+        &Request::Add {
+            address: address.to_string(),
+            key: HexKey(hex::encode(key)),
+            timeout,
+        },
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(unix))]
+pub fn add(
+    _socket: &Path,
+    _address: &str,
+    _key: &[u8; KEY_LEN],
+    _timeout: u64,
+) -> Result<(), String> {
+    Err(AGENT_UNSUPPORTED.to_string())
+}
+
+/// Key of `address` held by the agent, if an agent runs and holds it
+#[cfg(unix)]
+pub fn get(socket: &Path, address: &str) -> Option<DerivedKey> {
+    let response = request(
+        socket,
+        &Request::Get {
+            address: address.to_string(),
+        },
+    )
+    .ok()?;
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    hex::decode_to_slice(response.key.as_ref()?.0.as_bytes(), &mut key[..]).ok()?;
+    Some(key)
+}
+
+#[cfg(not(unix))]
+pub fn get(_socket: &Path, _address: &str) -> Option<DerivedKey> {
+    None
+}
+
+/// Keys held by the agent; none if no agent runs
+#[cfg(unix)]
+pub fn list(socket: &Path) -> Vec<UnlockedKey> {
+    request(socket, &Request::List)
+        .map(|response| response.keys)
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+pub fn list(_socket: &Path) -> Vec<UnlockedKey> {
+    Vec::new()
+}
+
+/// Wipe every key and stop the agent; false if no agent ran
+#[cfg(unix)]
+pub fn lock(socket: &Path) -> bool {
+    request(socket, &Request::Lock).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn lock(_socket: &Path) -> bool {
+    false
+}
+
+/// Serve requests on `socket` until every key has expired or `lock`
+#[cfg(unix)]
+pub fn run(socket: &Path) -> Result<(), String> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let dir = socket.parent().ok_or("Invalid agent socket path")?;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    // A socket left by an agent that was killed
+    let _ = std::fs::remove_file(socket);
+    let listener = UnixListener::bind(socket)
+        .map_err(|e| format!("Cannot listen on {}: {}", socket.display(), e))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Agent socket: {}", e))?;
+
+    let started = Instant::now();
+    // Boxed, so that growing the map does not leave copies of the keys behind
+    let mut keys: HashMap<String, (Box<DerivedKey>, Instant)> = HashMap::new();
+    loop {
+        let now = Instant::now();
+        keys.retain(|_, (_, expiry)| *expiry > now);
+        // Leave `unlock` time to hand over the first key
+        if keys.is_empty() && now - started > REQUEST_TIMEOUT {
+            break;
+        }
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        if serve(stream, &mut keys) {
+            break;
+        }
+    }
+    drop(keys);
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run(_socket: &Path) -> Result<(), String> {
+    Err(AGENT_UNSUPPORTED.to_string())
+}
+
+/// Answer one request; true on `lock`
+#[cfg(unix)]
+fn serve(stream: UnixStream, keys: &mut HashMap<String, (Box<DerivedKey>, Instant)>) -> bool {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err()
+    {
+        return false;
+    }
+    let mut line = Zeroizing::new(String::new());
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return false;
+    }
+    let Ok(request) = serde_json::from_str::<Request>(&line) else {
+        return false;
+    };
+
+    let mut response = Response::default();
+    let locked = match request {
+        Request::Add {
+            address,
+            key,
+            timeout,
+        } => {
+            let mut derived = Box::new(Zeroizing::new([0u8; KEY_LEN]));
+            if hex::decode_to_slice(key.0.as_bytes(), &mut derived[..]).is_err() {
+                return false;
+            }
+            let expiry = Instant::now() + Duration::from_secs(timeout);
+            keys.insert(address, (derived, expiry));
+            false
+        }
+        Request::Get { address } => {
+            response.key = keys
+                .get(&address)
+                .map(|(key, _)| HexKey(hex::encode(&key[..])));
+            false
+        }
+        Request::List => {
+            let now = Instant::now();
+            response.keys = keys
+                .iter()
+                .map(|(address, (_, expiry))| UnlockedKey {
+                    address: address.clone(),
+                    expires_in: expiry.saturating_duration_since(now).as_secs(),
+                })
+                .collect();
+            false
+        }
+        Request::Lock => {
+            keys.clear();
+            true
+        }
+    };
+    let json = Zeroizing::new(serde_json::to_string(&response).expect("responses serialize"));
+    let _ = (&stream).write_all(json.as_bytes());
+    locked
+}
+
+/// Send one request to the agent and read its answer
+#[cfg(unix)]
+fn request(socket: &Path, request: &Request) -> Result<Response, String> {
+    let stream = UnixStream::connect(socket).map_err(|e| format!("No agent running: {}", e))?;
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut json = Zeroizing::new(serde_json::to_string(request).expect("requests serialize"));
+    json.push('\n');
+    (&stream)
+        .write_all(json.as_bytes())
+        .map_err(|e| format!("Agent: {}", e))?;
+    let mut answer = Zeroizing::new(String::new());
+    BufReader::new(&stream)
+        .read_line(&mut answer)
+        .map_err(|e| format!("Agent: {}", e))?;
+    serde_json::from_str(&answer).map_err(|e| format!("Agent: {}", e))
+}
+
+/// Start an agent in the background, detached from the terminal
+#[cfg(unix)]
+fn spawn(socket: &Path) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let exe = std::env::current_exe().map_err(|e| format!("Cannot start the agent: {}", e))?;
+    std::process::Command::new(exe)
+        .arg("agent")
+        .arg(socket)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        // Its own process group, so that Ctrl-C in the terminal does not stop it
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Cannot start the agent: {}", e))?;
+
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    while Instant::now() < deadline {
+        if UnixStream::connect(socket).is_ok() {
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Err("The agent did not start".to_string())
+}
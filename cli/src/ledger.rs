@@ -0,0 +1,107 @@
+//! Ledger hardware wallets over USB HID
+//!
+//! The Mina app of a Ledger device signs payments with keys that never leave
+//! the device, once the user confirms them on its screen. The messages are
+//! built and checked by `mina_web_wallet_core::ledger`; this module only
+//! carries them to the device, and needs the `ledger` feature.
+
+use mina_signer::NetworkId;
+use mina_web_wallet_core::ledger;
+use mina_web_wallet_core::transaction::{SignedTransaction, Transaction};
+
+#[cfg(feature = "ledger")]
+use mina_web_wallet_core::ledger::{Deframer, HID_PACKET_LEN, LEDGER_VENDOR_ID};
+
+/// Usage page of the HID interface of Ledger devices
+#[cfg(feature = "ledger")]
+const LEDGER_USAGE_PAGE: u16 = 0xffa0;
+
+/// How long the user has to confirm on the device, in milliseconds
+#[cfg(feature = "ledger")]
+const CONFIRMATION_TIMEOUT_MS: i32 = 5 * 60 * 1000;
+
+#[cfg(not(feature = "ledger"))]
+const LEDGER_UNSUPPORTED: &str =
+    "This build has no Ledger support; rebuild with `--features ledger`";
+
+/// A connected Ledger device
+#[cfg_attr(not(feature = "ledger"), allow(dead_code))] // `open` always fails without the feature
+pub struct Ledger {
+    #[cfg(feature = "ledger")]
+    device: hidapi::HidDevice,
+}
+
+impl Ledger {
+    /// Open the first Ledger device found
+    #[cfg(feature = "ledger")]
+    pub fn open() -> Result<Self, String> {
+        let api = hidapi::HidApi::new().map_err(|e| format!("Cannot list USB devices: {}", e))?;
+        let info = api
+            .device_list()
+            .find(|d| {
+                d.vendor_id() == LEDGER_VENDOR_ID
+                    && (d.usage_page() == LEDGER_USAGE_PAGE || d.interface_number() == 0)
+            })
+            .ok_or("No Ledger device found: connect and unlock it, and open the Mina app")?;
+        let device = info
+            .open_device(&api)
+            .map_err(|e| format!("Cannot open the Ledger device: {}", e))?;
+        Ok(Self { device })
+    }
+
+    #[cfg(not(feature = "ledger"))]
+    pub fn open() -> Result<Self, String> {
+        Err(LEDGER_UNSUPPORTED.to_string())
+    }
+
+    /// Address of account `index`
+    pub fn address(&self, index: u32) -> Result<String, String> {
+        let response = self.exchange(&ledger::get_address_apdu(index))?;
+        ledger::parse_address(&response).map_err(|e| e.to_string())
+    }
+
+    /// Sign a transaction with account `index`, once confirmed on the device
+    pub fn sign(
+        &self,
+        transaction: &Transaction,
+        index: u32,
+        network: NetworkId,
+    ) -> Result<SignedTransaction, String> {
+        let apdu = ledger::sign_transaction_apdu(transaction, index, &network)
+            .map_err(|e| e.to_string())?;
+        let response = self.exchange(&apdu)?;
+        ledger::parse_signature(&response, transaction, network).map_err(|e| e.to_string())
+    }
+
+    /// Send an APDU and wait for the answer
+    #[cfg(feature = "ledger")]
+    fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, String> {
+        let error = |e: hidapi::HidError| format!("Ledger device: {}", e);
+        for packet in ledger::frame(apdu) {
+            // hidapi takes the report ID, always 0, before the report
+            let mut report = [0u8; HID_PACKET_LEN + 1];
+            report[1..].copy_from_slice(&packet);
+            self.device.write(&report).map_err(error)?;
+        }
+
+        let mut deframer = Deframer::new();
+        loop {
+            let mut packet = [0u8; HID_PACKET_LEN];
+            let read = self
+                .device
+                .read_timeout(&mut packet, CONFIRMATION_TIMEOUT_MS)
+                .map_err(error)?;
+            if read == 0 {
+                return Err("Timed out waiting for the Ledger device".to_string());
+            }
+            if let Some(response) = deframer.push(&packet[..read]).map_err(|e| e.to_string())? {
+                return Ok(response);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "ledger"))]
+    fn exchange(&self, _apdu: &[u8]) -> Result<Vec<u8>, String> {
+        Err(LEDGER_UNSUPPORTED.to_string())
+    }
+}
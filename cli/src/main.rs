@@ -1,18 +1,189 @@
 //! Mina Wallet CLI
 //!
 //! Command-line interface for Mina wallet operations including:
-//! - Generating new wallets
-//! - Importing existing wallets
+//! - Generating new wallets, one at a time or in bulk to an encrypted file,
+//!   optionally from dice rolls mixed with the OS randomness
+//! - Importing existing wallets, and keys exported by Auro and Clorio
 //! - Displaying wallet information
+//! - Managing secret keys in an encrypted keystore, unlocked for a session
+//!   by a background agent
+//! - Splitting secret keys into Shamir backup shares
+//! - Exporting paper wallets
+//! - Converting keys, field elements and memos between encodings
+//! - Generating test vectors for other Mina signers
+//! - Generating vanity addresses on every CPU core, into the keystore
+//! - Encrypting messages to Mina addresses
+//! - Signing and verifying messages
+//! - Signing oracle data and field arrays for o1js zkApps
+//! - Querying balances from a Mina node
+//! - Signing payments, with nonces fetched from a node
+//! - Broadcasting signed payments
+//! - Decoding signed transactions from other wallets and the daemon
+//! - Tracking transaction confirmations
+//! - Inspecting pending transactions for nonce gaps and low fees
+//! - Exporting transaction history, as CSV or OFX valued in fiat for bookkeeping
+//! - Watching an address for new transactions, with signed webhook notifications
+//! - Checking delegation and staking status
+//! - Estimating staking rewards
+//! - Checking node health
+//! - Reading the state of zkApp accounts
+//! - Checking zkApp verification keys
+//! - Converting between slots, epochs and time
+//! - Signing payment requests read from stdin, for pipelines
+//! - Running without prompts from cron jobs and CI, with secrets from the environment
+//! - Paying the rows of a CSV file, resuming after failures
+//! - Serving an HTTP API with an OpenAPI description and Prometheus metrics,
+//!   over HTTPS and with per-endpoint API tokens
+//! - Signing payments on an offline machine, with `tx create`, `tx sign` and
+//!   `tx broadcast`, and previewing them with `tx preview`
+//! - Signing payments on a Ledger hardware wallet
+//! - Recording every signed transaction in a hash-chained audit log
+//!
+//! Defaults for the network, nodes, fee, account and output format can be set
+//! in `~/.config/mina-wallet/config.toml`, or the file given with `--config`.
+//! Named profiles (`profile add`, `--profile`) switch between chains, each
+//! with its own network, nodes and keystore.
+//!
+//! With `--json`, commands print JSON on stdout, and errors as
+//! `{"error": {"code": 1, "message": "..."}}`. Exit codes are 0 on success,
+//! 1 on error, and 2 when a looked up transaction is unknown.
 
-use clap::{Parser, Subcommand};
+mod access;
+mod agent;
+mod ledger;
+mod metrics;
+mod profile;
+mod serve;
+mod tls;
+
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use ledger::Ledger;
 use mina_signer::NetworkId;
-use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::address_book::{AddressBook, AddressBookError, CONTACT_PREFIX, Contact};
+use mina_web_wallet_core::audit::AuditLog;
+use mina_web_wallet_core::backup::{BackupArchive, BackupContents};
+use mina_web_wallet_core::batch::{BatchProgress, BatchRow, RowStatus};
+use mina_web_wallet_core::consensus::ConsensusConstants;
+use mina_web_wallet_core::convert;
+use mina_web_wallet_core::encryption;
+use mina_web_wallet_core::entropy::{self, EntropyError, EntropySource, UserEntropy};
+use mina_web_wallet_core::envelope::{EnvelopePayload, TransactionEnvelope};
+use mina_web_wallet_core::fields::Fp;
+use mina_web_wallet_core::import::{self, ExportFormat, ImportError};
+use mina_web_wallet_core::inspect::{self, TransactionFormat};
+use mina_web_wallet_core::keystore::{EncryptedSecret, KdfCost, KeyEntry, Keystore};
+use mina_web_wallet_core::message::{SignedMessage, verify_message};
+use mina_web_wallet_core::network::accounting::{self, AccountingRow};
+use mina_web_wallet_core::network::archive::{self, Direction};
+use mina_web_wallet_core::network::staking;
+use mina_web_wallet_core::network::{
+    self, AccountWatcher, ArchiveClient, Backend, BackendKind, GraphQlClient, HistoryPage,
+    RetryPolicy, TxStatus, TxTracker, WatchEvent, Webhook, WebhookPayload,
+};
+use mina_web_wallet_core::nonce::NonceManager;
+use mina_web_wallet_core::oracle;
+use mina_web_wallet_core::paper::PaperWallet;
+use mina_web_wallet_core::qr::{self, QrFormat};
+use mina_web_wallet_core::queue::{BroadcastQueue, FlushOutcome};
+use mina_web_wallet_core::rewards::{self, RewardParameters};
+use mina_web_wallet_core::secret;
+use mina_web_wallet_core::signing::{SignatureJson, SignatureScheme};
+use mina_web_wallet_core::storage::FileStorage;
+use mina_web_wallet_core::test_vectors;
+use mina_web_wallet_core::time;
+use mina_web_wallet_core::transaction::{
+    self, PaymentPreview, SignedTransaction, Transaction, TransactionKind,
+};
+use mina_web_wallet_core::ur;
+use mina_web_wallet_core::vanity::{self, CancellationToken, VanityPosition};
+use mina_web_wallet_core::verification_key::VerificationKey;
+use mina_web_wallet_core::{Amount, Wallet, sss};
+use profile::{Profile, Profiles};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use zeroize::Zeroizing;
+
+/// Exit code of a failed command
+const EXIT_ERROR: i32 = 1;
+
+/// Exit code when the transaction looked up is unknown to the node
+const EXIT_NOT_FOUND: i32 = 2;
+
+/// Set by the global --json flag
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set by the global --non-interactive flag: prompts fail instead
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Loaded from the configuration file at startup
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Environment variable read instead of prompting for the keystore passphrase
+const PASSPHRASE_ENV: &str = "MINA_WALLET_PASSPHRASE";
+
+/// Environment variable naming a file, or `/dev/fd/N`, holding the passphrase
+const PASSPHRASE_FILE_ENV: &str = "MINA_WALLET_PASSPHRASE_FILE";
+
+/// Environment variable naming the keystore key used when a command is given none
+const KEY_ENV: &str = "MINA_WALLET_KEY";
+
+/// Environment variable enabling --non-interactive when set to 1 or true
+const NON_INTERACTIVE_ENV: &str = "MINA_WALLET_NON_INTERACTIVE";
+
+/// Environment variable holding the HMAC secret of `watch --webhook`
+const WEBHOOK_SECRET_ENV: &str = "MINA_WALLET_WEBHOOK_SECRET";
+
+/// Times a webhook delivery is attempted before it is given up
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Consecutive polls a watched transaction may be unknown before
+/// `transaction.confirmed` is given up for it
+const WEBHOOK_MAX_UNKNOWN_POLLS: u32 = 3;
+
+/// File of the data directory recording every signed transaction
+const AUDIT_LOG_FILE: &str = "audit.jsonl";
+
+/// How long each part of an animated UR QR code is shown, in milliseconds
+const UR_FRAME_MS: u64 = 400;
+
+/// Service under which passphrases are saved in the OS keychain
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "mina-wallet";
+
+#[cfg(not(feature = "keyring"))]
+const KEYRING_UNSUPPORTED: &str =
+    "This build has no OS keychain support; rebuild with `--features keyring`";
 
 #[derive(Parser)]
 #[command(name = "mina-wallet")]
 #[command(author, version, about = "Mina wallet CLI tool", long_about = None)]
 struct Cli {
+    /// Directory for wallet state (defaults to $MINA_WALLET_DIR, then ~/.mina-wallet)
+    #[arg(long, global = true)]
+    data_dir: Option<std::path::PathBuf>,
+
+    /// Print JSON on stdout, including errors, whatever the --format of the command
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Never prompt, failing instead, and print JSON as with --json; for cron
+    /// jobs and CI (also set by $MINA_WALLET_NON_INTERACTIVE=1)
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Configuration file (defaults to ~/.config/mina-wallet/config.toml)
+    #[arg(long, global = true)]
+    #[allow(dead_code)] // read by config_path, before the arguments are parsed
+    config: Option<std::path::PathBuf>,
+
+    /// Profile to use (defaults to the one chosen with `profile use`)
+    #[arg(long, global = true)]
+    #[allow(dead_code)] // read by main, before the arguments are parsed
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -28,12 +199,37 @@ enum Commands {
         /// Output format: text or json
         #[arg(short, long, default_value = "text")]
         format: String,
+
+        /// Number of wallets to generate, in parallel (requires --output)
+        #[arg(long, requires = "output")]
+        count: Option<u32>,
+
+        /// Write the addresses and passphrase-encrypted secret keys to a new
+        /// CSV file, or JSON lines if the name ends in .jsonl
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+
+        /// Show the address as a QR code
+        #[arg(long, conflicts_with = "output")]
+        qr: bool,
+
+        /// Mix entropy typed in, from dice, coin or hex, with the OS randomness
+        #[arg(long, value_name = "SOURCE", conflicts_with = "output")]
+        entropy_from: Option<String>,
     },
 
-    /// Import a wallet from a secret key
+    /// Import a wallet from a secret key, or from the export of another wallet
     Import {
-        /// Secret key in hex or base58 format
-        secret_key: String,
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Import a key exported by another wallet instead: auro or clorio
+        #[arg(long, value_name = "WALLET", conflicts_with_all = ["secret_key", "stdin"])]
+        from: Option<String>,
+
+        /// Export file to import with --from (default: stdin)
+        #[arg(long, requires = "from")]
+        file: Option<std::path::PathBuf>,
 
         /// Network: mainnet or testnet
         #[arg(short, long, default_value = "mainnet")]
@@ -52,132 +248,5787 @@ enum Commands {
 
     /// Get address from a secret key (without showing the secret)
     Address {
-        /// Secret key in hex or base58 format
-        secret_key: String,
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Show the address as a QR code
+        #[arg(long)]
+        qr: bool,
     },
-}
 
-fn parse_network(network: &str) -> Result<NetworkId, String> {
-    match network.to_lowercase().as_str() {
-        "mainnet" => Ok(NetworkId::MAINNET),
-        "testnet" => Ok(NetworkId::TESTNET),
-        _ => Err(format!(
-            "Invalid network '{}'. Use 'mainnet' or 'testnet'.",
-            network
-        )),
-    }
-}
+    /// Generate a wallet whose address matches a pattern
+    Vanity {
+        /// Pattern to match right after the B62q header
+        #[arg(long, conflicts_with = "suffix", required_unless_present = "suffix")]
+        prefix: Option<String>,
 
-fn import_wallet(secret_key: &str, network: NetworkId) -> Result<Wallet, String> {
-    // Try hex format first
-    if let Ok(wallet) = Wallet::from_secret_key_hex(secret_key, network.clone()) {
-        return Ok(wallet);
-    }
+        /// Pattern to match at the end of the address
+        #[arg(long)]
+        suffix: Option<String>,
 
-    // Try base58 format
-    if let Ok(wallet) = Wallet::from_secret_key_base58(secret_key, network) {
-        return Ok(wallet);
-    }
+        /// Worker threads (defaults to the number of CPUs)
+        #[arg(long)]
+        threads: Option<usize>,
 
-    Err("Invalid secret key format. Expected hex (64 chars) or base58 (52 chars).".to_string())
-}
+        /// Label of the key in the keystore
+        #[arg(short, long, conflicts_with = "print")]
+        label: Option<String>,
 
-fn print_wallet_text(wallet: &Wallet) {
-    println!("Wallet Generated Successfully!");
-    println!("==============================");
-    println!("Address:          {}", wallet.address());
-    println!("Secret Key (Hex): {}", wallet.secret_key_hex());
-    println!("Secret Key (B58): {}", wallet.secret_key_base58());
-    println!("Network:          {:?}", wallet.network());
-    println!();
-    println!(
-        "WARNING: Store your secret key securely! Anyone with access to it can control your funds."
-    );
-}
+        /// Print the wallet, secret key included, instead of adding it to the keystore
+        #[arg(long)]
+        print: bool,
 
-fn print_wallet_json(wallet: &Wallet) {
-    let json = serde_json::json!({
-        "address": wallet.address(),
-        "secret_key_hex": wallet.secret_key_hex(),
-        "secret_key_base58": wallet.secret_key_base58(),
-        "network": format!("{:?}", wallet.network()).to_lowercase(),
-    });
-    println!("{}", serde_json::to_string_pretty(&json).unwrap());
-}
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
 
-fn main() {
-    let cli = Cli::parse();
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
 
-    match cli.command {
-        Commands::Generate { network, format } => {
-            let network_id = match parse_network(&network) {
-                Ok(n) => n,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            };
+    /// Encrypt a message so that only the owner of an address can read it
+    Encrypt {
+        /// Recipient Mina address
+        #[arg(long)]
+        to: String,
 
-            let wallet = match Wallet::new(network_id) {
-                Ok(w) => w,
-                Err(e) => {
-                    eprintln!("Error generating wallet: {}", e);
-                    std::process::exit(1);
-                }
-            };
+        /// Message to encrypt
+        message: String,
+    },
 
-            match format.as_str() {
-                "json" => print_wallet_json(&wallet),
-                _ => print_wallet_text(&wallet),
-            }
-        }
+    /// Decrypt a message encrypted to your address
+    Decrypt {
+        #[command(flatten)]
+        secret: SecretArgs,
 
-        Commands::Import {
-            secret_key,
-            network,
-            format,
-        } => {
-            let network_id = match parse_network(&network) {
-                Ok(n) => n,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            };
+        /// Hex-encoded ciphertext (read from stdin if omitted)
+        ciphertext: Option<String>,
+    },
 
-            match import_wallet(&secret_key, network_id) {
-                Ok(wallet) => match format.as_str() {
-                    "json" => print_wallet_json(&wallet),
-                    _ => print_wallet_text(&wallet),
-                },
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
-        }
+    /// Sign a message to prove ownership of an address
+    SignMessage {
+        #[command(flatten)]
+        secret: SecretArgs,
 
-        Commands::Validate { address } => match mina_web_wallet_core::address_to_pubkey(&address) {
-            Ok(_) => {
-                println!("Address is valid: {}", address);
-            }
-            Err(e) => {
-                eprintln!("Invalid address: {:?}", e);
-                std::process::exit(1);
-            }
-        },
+        /// Message to sign (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        message: Option<String>,
 
-        Commands::Address { secret_key } => {
-            // Default to mainnet for address derivation
-            match import_wallet(&secret_key, NetworkId::MAINNET) {
-                Ok(wallet) => {
-                    println!("{}", wallet.address());
-                }
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
-        }
+        /// Read the message from a file, signed byte for byte
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// Show the signed message as a QR code
+        #[arg(long)]
+        qr: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Verify that a message was signed by the owner of an address
+    VerifyMessage {
+        /// The signed message (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        message: Option<String>,
+
+        /// Read the message from a file
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// Address of the signer
+        #[arg(long)]
+        address: String,
+
+        /// Base58 signature, as printed by sign-message
+        #[arg(long)]
+        signature: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+    },
+
+    /// Sign JSON data as an o1js-compatible oracle response
+    SignOracle {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// JSON data to sign (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        data: Option<String>,
+
+        /// Read the JSON data from a file
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+    },
+
+    /// Sign an array of field elements, as o1js `signFields`
+    SignFields {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// JSON array of fields, as decimal strings or integers, e.g. '["1","2"]'
+        /// (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        fields: Option<String>,
+
+        /// Read the JSON array from a file
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+    },
+
+    /// Decode a signed transaction from this wallet, Auro, mina-signer or a
+    /// daemon GraphQL request, and find the network its signature is for
+    Inspect {
+        /// Signed transaction JSON (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        json: Option<String>,
+
+        /// Read the JSON from a file
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Convert keys, field elements and memos between their encodings
+    Convert {
+        #[command(subcommand)]
+        command: ConvertCommands,
+    },
+
+    /// Render an address, payment URI or signed transaction as a QR code image
+    Qr {
+        /// The data to encode
+        data: String,
+
+        /// Image format: svg or png
+        #[arg(short, long, default_value = "svg")]
+        format: String,
+
+        /// File to write, required for png (default: stdout)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Print deterministic keypairs, messages and transactions with their
+    /// signatures as JSON, for testing other Mina signers against this one
+    TestVectors {
+        /// Number of vectors
+        #[arg(long, default_value_t = 5)]
+        count: u32,
+
+        /// Hashing of the signatures: legacy or kimchi
+        #[arg(long, default_value = "legacy")]
+        scheme: String,
+
+        /// Seed the keys are derived from
+        #[arg(long, default_value = "mina-web-wallet")]
+        seed: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "testnet")]
+        network: String,
+    },
+
+    /// Export a wallet for offline storage
+    Export {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Produce a printable paper wallet
+        #[arg(long, required = true)]
+        paper: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the balance of an address
+    Balance {
+        /// The Mina address to query
+        address: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Endpoints of the node, comma-separated for failover (defaults to a public GraphQL node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// API exposed by the node: graphql or rosetta (rosetta requires --node)
+        #[arg(long, default_value = "graphql")]
+        backend: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the nonces of an address, or override the next nonce this wallet uses
+    Nonce {
+        /// Address, or @label of a contact
+        address: String,
+
+        /// Forget the nonces recorded by this wallet, e.g. after a broadcast
+        /// transaction was dropped from the transaction pool
+        #[arg(long, conflicts_with = "reserve")]
+        reset: bool,
+
+        /// Use N as the next nonce when the node's inferred nonce is lower,
+        /// e.g. while transactions signed on another machine are not in its pool
+        #[arg(long, value_name = "N")]
+        reserve: Option<u32>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Sign a payment
+    SignPayment {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        #[command(flatten)]
+        payment: PaymentArgs,
+    },
+
+    /// Sign a payment, optionally broadcast it to a node and wait for inclusion
+    Send {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        #[command(flatten)]
+        payment: PaymentArgs,
+
+        /// Submit the signed payment to the node
+        #[arg(long)]
+        broadcast: bool,
+
+        /// Broadcast the payment and poll until it is final
+        #[arg(long)]
+        wait: bool,
+
+        /// Confirmations after which the payment is final, with --wait
+        #[arg(long, default_value_t = network::tracker::DEFAULT_CONFIRMATIONS)]
+        confirmations: u32,
+
+        /// Queue the payment for `queue flush`: right away, or with --broadcast
+        /// if the node cannot be reached
+        #[arg(long)]
+        queue: bool,
+    },
+
+    /// Sign payment requests read from stdin, one JSON object per line, such as
+    /// {"to": "B62q...", "amount": "1.5", "fee": "0.1", "memo": "invoice 42"}
+    ///
+    /// Nonces are assigned in order. One JSON result per request is printed on
+    /// stdout, with the signed payment or its hash, or an error object.
+    Batch {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Nonce of the first payment (fetched from the node if omitted)
+        #[arg(long)]
+        nonce: Option<u32>,
+
+        /// Fee of requests without one, in MINA
+        #[arg(long, default_value = "0.1")]
+        fee: String,
+
+        /// Broadcast each payment once signed, stopping at the first failure
+        #[arg(long)]
+        broadcast: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Sign and broadcast even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Pay every row of a CSV file with the columns to, amount (in MINA),
+    /// and optionally memo and fee, as for a payroll or an airdrop
+    ///
+    /// Nonces are assigned in order and each payment is broadcast once signed.
+    /// Sent rows are recorded next to the file: after a failure, the same
+    /// command resumes with the first row that was not sent.
+    SendBatch {
+        /// CSV file of payments, with a header row
+        file: std::path::PathBuf,
+
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Fee of rows without one, in MINA
+        #[arg(long, default_value = "0.1")]
+        fee: String,
+
+        /// File recording the sent rows (defaults to the CSV file name
+        /// followed by .progress.json)
+        #[arg(long)]
+        progress: Option<std::path::PathBuf>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Broadcast even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show the status of a transaction
+    Status {
+        /// Transaction hash
+        hash: String,
+
+        /// Confirmations after which the transaction is final
+        #[arg(long, default_value_t = network::tracker::DEFAULT_CONFIRMATIONS)]
+        confirmations: u32,
+
+        /// Keep polling until the transaction is final
+        #[arg(long)]
+        wait: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// List the pending transactions of an address and look for stuck ones
+    Pending {
+        /// The Mina address to query
+        address: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the transaction history of an address
+    History {
+        /// The Mina address to query
+        address: String,
+
+        /// Number of transactions per page
+        #[arg(long, default_value_t = archive::DEFAULT_PAGE_SIZE)]
+        limit: u32,
+
+        /// Cursor of the page to fetch, as printed after the previous page
+        #[arg(long)]
+        before: Option<u64>,
+
+        /// GraphQL endpoints of the archive indexer, comma-separated for failover
+        #[arg(long)]
+        archive: Option<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Output format: text, json or csv
+        #[arg(short, long, default_value = "text")]
+        format: String,
+
+        /// Export every page of the history for bookkeeping: csv or ofx
+        #[arg(long, value_name = "FORMAT")]
+        export: Option<String>,
+
+        /// Value exported transactions in fiat with daily prices from
+        /// coingecko, or the URL of a CoinGecko-compatible API
+        #[arg(long, value_name = "API", requires = "export")]
+        price_source: Option<String>,
+
+        /// Fiat currency of --price-source
+        #[arg(long, default_value = "usd")]
+        currency: String,
+    },
+
+    /// Print transactions from or to an address as new blocks include them,
+    /// until interrupted
+    ///
+    /// With --format json, one JSON object is printed per line, for pipelines
+    /// and monitoring scripts.
+    ///
+    /// With --webhook, incoming payments and finalized transactions are also
+    /// POSTed as JSON, signed with HMAC-SHA256 under the secret in
+    /// $MINA_WALLET_WEBHOOK_SECRET or --webhook-secret-file. The signature of
+    /// `<timestamp>.<body>` is sent in X-Mina-Wallet-Signature and the
+    /// timestamp in X-Mina-Wallet-Timestamp.
+    Watch {
+        /// The Mina address to watch
+        address: String,
+
+        /// Seconds between two polls of the node
+        #[arg(long, default_value_t = network::watch::DEFAULT_WATCH_INTERVAL.as_secs())]
+        interval: u64,
+
+        /// Send payment.received and transaction.confirmed notifications to
+        /// this URL; repeat for several receivers
+        #[arg(long, value_name = "URL")]
+        webhook: Vec<String>,
+
+        /// File holding the webhook secret, instead of $MINA_WALLET_WEBHOOK_SECRET
+        #[arg(long, value_name = "PATH", requires = "webhook")]
+        webhook_secret_file: Option<std::path::PathBuf>,
+
+        /// Confirmations after which transaction.confirmed is sent
+        #[arg(long, default_value_t = network::tracker::DEFAULT_CONFIRMATIONS)]
+        confirmations: u32,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the delegate, delegators and staking ledger entry of an address
+    StakingInfo {
+        /// The Mina address to query
+        address: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// GraphQL endpoints of the archive indexer, for the staking ledger
+        #[arg(long)]
+        archive: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Delegate the stake of a key to a block producer and broadcast the delegation
+    Delegate {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Address of the block producer, or @label of a contact
+        #[arg(long)]
+        to: String,
+
+        /// Fee, in MINA
+        #[arg(long, default_value = "0.1")]
+        fee: String,
+
+        /// Nonce of the delegation (fetched from the node if omitted)
+        #[arg(long)]
+        nonce: Option<u32>,
+
+        /// URL of a pool directory, a JSON array of {"address", "name", "website"},
+        /// to check that the block producer is a known pool
+        #[arg(long)]
+        pool_directory: Option<String>,
+
+        /// Delegate even if the block producer does not look like one
+        #[arg(long)]
+        yes: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Sign and broadcast even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Estimate the staking rewards of a stake delegated to a block producer
+    RewardsEstimate {
+        /// Stake, in MINA
+        #[arg(long)]
+        stake: String,
+
+        /// Fee of the delegate, in percent (e.g. 5 or 7.5)
+        #[arg(long, default_value = "0")]
+        fee: String,
+
+        /// The stake is still under a vesting schedule
+        #[arg(long)]
+        locked: bool,
+
+        /// Assume a doubled coinbase for unlocked stake, as before the Berkeley upgrade
+        #[arg(long)]
+        supercharged: bool,
+
+        /// Total stake, in MINA (fetched from the node if omitted)
+        #[arg(long)]
+        total_stake: Option<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the on-chain state, URI and permissions of a zkApp account
+    ZkappState {
+        /// The zkApp address to query
+        address: String,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Hash a zkApp verification key, optionally comparing it with a deployed one
+    #[command(alias = "vk")]
+    VerificationKey {
+        /// Base64 verification key, as in o1js `VerificationKey.data` (read from stdin if omitted)
+        #[arg(conflicts_with = "file")]
+        data: Option<String>,
+
+        /// Read the verification key from a file
+        #[arg(long)]
+        file: Option<std::path::PathBuf>,
+
+        /// zkApp address whose deployed verification key must match
+        #[arg(long)]
+        address: Option<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the current slot and epoch, or those of a given slot or time
+    Epoch {
+        /// Global slot to describe instead of the current one
+        #[arg(long, conflicts_with = "at")]
+        slot: Option<u32>,
+
+        /// Time to describe instead of now, as YYYY-MM-DDTHH:MM:SSZ
+        #[arg(long)]
+        at: Option<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Check that a node is reachable, synced and on the expected chain
+    #[command(alias = "doctor")]
+    NodeStatus {
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show transaction fees, or suggest one from the node's transaction pool
+    Fee {
+        /// Suggest fees from the fees of pending transactions
+        #[arg(long)]
+        suggest: bool,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Create, sign and broadcast payments in separate steps, to sign on an
+    /// offline machine
+    Tx {
+        #[command(subcommand)]
+        command: TxCommands,
+    },
+
+    /// Serve an HTTP API for address derivation, validation, payment signing
+    /// and broadcasting, described at /v1/openapi.json, with Prometheus
+    /// metrics at /metrics
+    ///
+    /// Before exposing the server beyond localhost, restrict the endpoints to
+    /// API tokens with --access and serve HTTPS with --tls-cert and --tls-key.
+    /// The access file is TOML: `public` lists the endpoints anyone can call,
+    /// and each `[[tokens]]` entry has a `name`, the hex `sha256` of the
+    /// token and the `methods` it may call, among address, validate,
+    /// sign-payment, broadcast, openapi, metrics, or * for all.
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        http: std::net::SocketAddr,
+
+        /// TOML file of the API tokens and the endpoints they may call;
+        /// without it, every endpoint is open
+        #[arg(long, value_name = "FILE")]
+        access: Option<std::path::PathBuf>,
+
+        /// Largest request body accepted, in bytes
+        #[arg(long, default_value_t = serve::DEFAULT_MAX_BODY_SIZE)]
+        max_body_size: usize,
+
+        /// PEM certificate chain to serve HTTPS with
+        #[arg(long, value_name = "PATH", requires = "tls_key")]
+        tls_cert: Option<std::path::PathBuf>,
+
+        /// PEM private key of --tls-cert
+        #[arg(long, value_name = "PATH", requires = "tls_cert")]
+        tls_key: Option<std::path::PathBuf>,
+
+        /// Label or address of the keystore key that signs payments; without
+        /// it, /v1/sign-payment is disabled
+        #[arg(long)]
+        key: Option<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Fetch nonces and broadcast even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+
+        /// Accept API tokens over plain HTTP on a non-loopback address
+        #[arg(long)]
+        insecure: bool,
+    },
+
+    /// Manage signed transactions waiting to be broadcast
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommands,
+    },
+
+    /// Manage secret keys in the encrypted keystore
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommands,
+    },
+
+    /// Keep a keystore key unlocked for a while, so that commands do not ask
+    /// for its passphrase
+    Unlock {
+        /// Label or address of the key (defaults to the default key)
+        key: Option<String>,
+
+        /// How long the key stays unlocked, e.g. 90s, 10m or 2h
+        #[arg(long, default_value = "10m")]
+        timeout: String,
+    },
+
+    /// Wipe every key unlocked with `unlock`
+    Lock,
+
+    /// Run the session agent of `unlock` (started by `unlock` itself)
+    #[command(hide = true)]
+    Agent {
+        /// Socket to listen on
+        socket: std::path::PathBuf,
+    },
+
+    /// Manage labeled addresses, usable as `--to @label`
+    Contacts {
+        #[command(subcommand)]
+        command: ContactsCommands,
+    },
+
+    /// Back up secret keys as Shamir secret shares or an encrypted archive
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommands,
+    },
+
+    /// Manage profiles, each with its own network, nodes and keystore
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+
+    /// Use a Ledger hardware wallet with the Mina app open
+    Ledger {
+        #[command(subcommand)]
+        command: LedgerCommands,
+    },
+
+    /// Show or verify the log of every transaction signed by this wallet
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommands,
+    },
+}
+
+/// Payment built by sign-payment, send and ledger sign-payment
+#[derive(Args)]
+struct PaymentArgs {
+    /// Receiver address, or @label of a contact
+    #[arg(long)]
+    to: String,
+
+    /// Amount to send, in MINA (e.g. 1.5)
+    #[arg(long)]
+    amount: String,
+
+    /// Fee, in MINA, or slow, standard or fast to use a fee suggested from
+    /// the node's transaction pool
+    #[arg(long, default_value = "0.1")]
+    fee: String,
+
+    /// Nonce of the transaction
+    #[arg(
+        long,
+        conflicts_with = "auto_nonce",
+        required_unless_present = "auto_nonce"
+    )]
+    nonce: Option<u32>,
+
+    /// Fetch the nonce from the node
+    #[arg(long)]
+    auto_nonce: bool,
+
+    /// Memo, up to 32 bytes
+    #[arg(long, default_value = "")]
+    memo: String,
+
+    /// Last global slot at which the payment can be included
+    #[arg(long, conflicts_with = "valid_for")]
+    valid_until: Option<u32>,
+
+    /// Minutes during which the payment can be included, converted to --valid-until
+    #[arg(long)]
+    valid_for: Option<u64>,
+
+    /// Network: mainnet or testnet
+    #[arg(short, long, default_value = "mainnet")]
+    network: String,
+
+    /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+    #[arg(long)]
+    node: Option<String>,
+
+    /// Sign and broadcast even if the node's chain ID does not match --network
+    #[arg(long)]
+    force: bool,
+
+    /// The receiver account does not exist yet, so the account creation fee
+    /// applies (looked up on the node with --auto-nonce)
+    #[arg(long)]
+    new_account: bool,
+
+    /// Show the signed payment as a QR code, to carry it to another device
+    #[arg(long)]
+    qr: bool,
+}
+
+impl PaymentArgs {
+    /// Whether signing needs the node, for the nonce or the fee
+    fn queries_node(&self) -> bool {
+        self.nonce.is_none() || matches!(self.fee.as_str(), "slow" | "standard" | "fast")
+    }
+}
+
+/// Search for a vanity address on every thread of `pool`, showing the
+/// attempt rate and the expected time left on stderr
+fn grind_vanity(
+    pool: &rayon::ThreadPool,
+    threads: usize,
+    pattern: &str,
+    position: VanityPosition,
+    network: &NetworkId,
+    expected: f64,
+) -> Result<Wallet, vanity::VanityError> {
+    let cancel = CancellationToken::new();
+    let done = CancellationToken::new();
+    let attempts = AtomicU64::new(0);
+    let started = std::time::Instant::now();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.is_cancelled() {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                let attempts = attempts.load(Ordering::Relaxed);
+                let rate = attempts as f64 / started.elapsed().as_secs_f64().max(f64::EPSILON);
+                let eta = ((expected - attempts as f64) / rate.max(1.0)).max(0.0);
+                eprint!(
+                    "\r  {} attempts, {:.0}/s, about {} left   ",
+                    attempts,
+                    rate,
+                    format_duration(eta as u64)
+                );
+            }
+            eprintln!();
+        });
+
+        let result = pool.install(|| {
+            (0..threads)
+                .into_par_iter()
+                .map(|_| {
+                    let result = vanity::generate_vanity(
+                        pattern,
+                        position,
+                        network.clone(),
+                        &cancel,
+                        |_| {
+                            attempts.fetch_add(vanity::PROGRESS_INTERVAL, Ordering::Relaxed);
+                        },
+                    );
+                    // The first match stops the other threads
+                    if result.is_ok() {
+                        cancel.cancel();
+                    }
+                    result
+                })
+                .reduce_with(|a, b| if a.is_ok() { a } else { b })
+                .expect("at least one thread")
+        });
+        done.cancel();
+        result
+    })
+}
+
+/// Render `data` as a QR code on stderr, so that stdout stays machine-readable
+fn print_qr(data: &str) {
+    match qr::generate_terminal_qr(data) {
+        Ok(code) => eprintln!("{}", code),
+        Err(e) => eprintln!("Warning: cannot show a QR code: {}", e),
+    }
+}
+
+/// Show `data` as UR QR codes on stderr, cycling through the parts until
+/// interrupted when it does not fit in one
+fn print_qr_ur(data: &str) {
+    // Upper case fits the alphanumeric mode of QR codes, for smaller codes
+    let parts: Vec<String> = ur::encode(data.as_bytes(), ur::DEFAULT_FRAGMENT_LEN)
+        .into_iter()
+        .map(|part| part.to_ascii_uppercase())
+        .collect();
+    if let [part] = parts.as_slice() {
+        print_qr(part);
+        return;
+    }
+    for (index, part) in parts.iter().enumerate().cycle() {
+        // Clear the terminal, so that each part replaces the previous one
+        eprint!("\x1b[2J\x1b[H");
+        print_qr(part);
+        eprintln!(
+            "Part {} of {}: press Ctrl-C once the other side has read every part",
+            index + 1,
+            parts.len()
+        );
+        std::thread::sleep(std::time::Duration::from_millis(UR_FRAME_MS));
+    }
+}
+
+/// Decode the scanned `ur:` parts of an envelope, one per line
+fn decode_ur_lines(text: &str) -> Result<String, String> {
+    let mut decoder = ur::UrDecoder::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match decoder.receive(line) {
+            Ok(Some(payload)) => {
+                return String::from_utf8(payload)
+                    .map_err(|_| "The UR payload is not a transaction envelope".to_string());
+            }
+            Ok(None) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let (read, count) = decoder.progress();
+    Err(format!("Only {} of {} UR parts were given", read, count))
+}
+
+/// Parse a duration such as `90s`, `10m` or `2h` into seconds; a bare number
+/// is in seconds
+fn parse_timeout(text: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid duration '{}': use e.g. 90s, 10m or 2h", text);
+    let (number, unit) = match text.char_indices().last() {
+        Some((i, 's')) => (&text[..i], 1),
+        Some((i, 'm')) => (&text[..i], 60),
+        Some((i, 'h')) => (&text[..i], 3600),
+        _ => (text, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|&secs| secs > 0)
+        .ok_or_else(invalid)
+}
+
+/// A duration in seconds, as `1h 02m 03s`
+fn format_duration(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+/// A wallet written by `generate --output`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeneratedWallet {
+    address: String,
+    /// Decrypts like a keystore entry, with the address as associated data
+    secret: EncryptedSecret,
+}
+
+/// Write generated wallets to a new file, as CSV or JSON lines
+fn write_generated_wallets(path: &std::path::Path, rows: &[GeneratedWallet]) -> Result<(), String> {
+    // Never overwrite a file that may hold the only copy of other keys
+    let file = std::fs::File::create_new(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = std::io::BufWriter::new(file);
+
+    if path.extension().is_some_and(|ext| ext == "jsonl") {
+        for row in rows {
+            let line = serde_json::to_string(row).map_err(|e| e.to_string())?;
+            writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+        }
+        return out.flush().map_err(|e| e.to_string());
+    }
+
+    let mut writer = csv::Writer::from_writer(out);
+    writer
+        .write_record([
+            "address",
+            "version",
+            "memoryKib",
+            "iterations",
+            "parallelism",
+            "salt",
+            "nonce",
+            "ciphertext",
+        ])
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let secret = &row.secret;
+        writer
+            .write_record([
+                row.address.clone(),
+                secret.version.to_string(),
+                secret.kdf.memory_kib.to_string(),
+                secret.kdf.iterations.to_string(),
+                secret.kdf.parallelism.to_string(),
+                secret.salt.clone(),
+                secret.nonce.clone(),
+                secret.ciphertext.clone(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// A payment request read by `batch`; amounts are in MINA
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PaymentRequest {
+    to: String,
+    amount: String,
+    fee: Option<String>,
+    memo: Option<String>,
+    valid_until: Option<u32>,
+}
+
+/// A row of a `send-batch` file; amounts are in MINA
+#[derive(Deserialize)]
+struct CsvPayment {
+    #[serde(alias = "recipient")]
+    to: String,
+    amount: String,
+    #[serde(default)]
+    memo: String,
+    #[serde(default)]
+    fee: Option<String>,
+}
+
+/// Where a command gets its secret key
+#[derive(Args)]
+struct SecretArgs {
+    /// Label or address of a keystore key. A raw secret key in hex or base58
+    /// is accepted but ends up in shell history. If omitted, the key named by
+    /// $MINA_WALLET_KEY or the default keystore key is used, or the secret key
+    /// is prompted for
+    secret_key: Option<String>,
+
+    /// Read the secret key from the first line of stdin
+    #[arg(long, conflicts_with = "secret_key")]
+    stdin: bool,
+}
+
+/// Settings of the configuration file, all optional:
+///
+/// ```toml
+/// network = "testnet"
+/// fee = "standard"
+/// default-account = "main"
+/// output = "json"
+///
+/// [nodes]
+/// testnet = ["http://localhost:3085/graphql", "https://api.minascan.io/node/devnet/v1/graphql"]
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Default --network of every command
+    network: Option<String>,
+    /// Default --fee of sign-payment and send: MINA, or slow, standard or fast
+    fee: Option<String>,
+    /// Keystore key used when a command is given no secret key
+    default_account: Option<String>,
+    /// text, or json to behave as if --json was passed
+    output: Option<String>,
+    /// GraphQL endpoints used without --node, in order of preference
+    #[serde(default)]
+    nodes: NodesConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodesConfig {
+    #[serde(default)]
+    mainnet: Vec<String>,
+    #[serde(default)]
+    testnet: Vec<String>,
+}
+
+impl Config {
+    /// Let a profile override the network and nodes
+    fn apply_profile(&mut self, profile: &Profile) {
+        self.network = Some(profile.network.clone());
+        if profile.nodes.is_empty() {
+            return;
+        }
+        match parse_network(&profile.network) {
+            Ok(NetworkId::MAINNET) => self.nodes.mainnet = profile.nodes.clone(),
+            Ok(NetworkId::TESTNET) => self.nodes.testnet = profile.nodes.clone(),
+            // Checked by `profile add`
+            Err(_) => {}
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(network) = &self.network {
+            parse_network(network)?;
+        }
+        if let Some(fee) = &self.fee
+            && !matches!(fee.as_str(), "slow" | "standard" | "fast")
+        {
+            fee.parse::<Amount>()
+                .map_err(|e| format!("Invalid fee '{}': {}", fee, e))?;
+        }
+        match self.output.as_deref() {
+            None | Some("text" | "json") => Ok(()),
+            Some(other) => Err(format!("Invalid output '{}', expected text or json", other)),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TxCommands {
+    /// Create an unsigned payment, to be signed offline with `tx sign`
+    Create {
+        /// Sender address
+        #[arg(long)]
+        from: String,
+
+        /// Receiver address
+        #[arg(long)]
+        to: String,
+
+        /// Amount to send, in MINA (e.g. 1.5)
+        #[arg(long)]
+        amount: String,
+
+        /// Fee, in MINA
+        #[arg(long, default_value = "0.1")]
+        fee: String,
+
+        /// Nonce of the transaction
+        #[arg(
+            long,
+            conflicts_with = "auto_nonce",
+            required_unless_present = "auto_nonce"
+        )]
+        nonce: Option<u32>,
+
+        /// Fetch the nonce from the node
+        #[arg(long)]
+        auto_nonce: bool,
+
+        /// Memo, up to 32 bytes
+        #[arg(long, default_value = "")]
+        memo: String,
+
+        /// Last global slot at which the payment can be included
+        #[arg(long, conflicts_with = "valid_for")]
+        valid_until: Option<u32>,
+
+        /// Minutes during which the payment can be included, converted to
+        /// --valid-until. Leave time to carry the payment to the offline machine
+        /// and back
+        #[arg(long)]
+        valid_for: Option<u64>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Fetch the nonce even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+
+        /// File to write the unsigned payment to (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+
+        /// Show the unsigned payment as an animated UR QR code, to carry it
+        /// to the offline machine by camera
+        #[arg(long)]
+        qr_ur: bool,
+    },
+
+    /// Sign a payment created by `tx create`, without touching the network
+    Sign {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// File with the unsigned payment, as JSON or as the scanned `ur:`
+        /// parts of its QR code, one per line
+        #[arg(short, long)]
+        input: std::path::PathBuf,
+
+        /// File to write the signed payment to (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+
+        /// Show the signed payment as a QR code, to carry it to the online machine
+        #[arg(long, conflicts_with = "qr_ur")]
+        qr: bool,
+
+        /// Show the signed payment as an animated UR QR code instead, for
+        /// payments too large to scan as one QR code
+        #[arg(long)]
+        qr_ur: bool,
+    },
+
+    /// Summarize a payment and warn about likely mistakes, before it is
+    /// signed or broadcast
+    Preview {
+        /// File with a `tx create` or `tx sign` envelope, or a payment signed
+        /// by sign-payment (stdin if omitted)
+        #[arg(short, long)]
+        input: Option<std::path::PathBuf>,
+
+        /// Network of a payment that is not in an envelope
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Look up the receiver account on the node, to warn about the
+        /// account creation fee
+        #[arg(long)]
+        online: bool,
+
+        /// GraphQL endpoints of the node, with --online, comma-separated for failover
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Broadcast a payment signed by `tx sign`
+    Broadcast {
+        /// File with the signed payment (stdin if omitted)
+        #[arg(short, long)]
+        input: Option<std::path::PathBuf>,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Broadcast even if the node's chain ID does not match the network of the payment
+        #[arg(long)]
+        force: bool,
+
+        /// Poll until the payment is final
+        #[arg(long)]
+        wait: bool,
+
+        /// Confirmations after which the payment is final, with --wait
+        #[arg(long, default_value_t = network::tracker::DEFAULT_CONFIRMATIONS)]
+        confirmations: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueueCommands {
+    /// List queued transactions
+    List {
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Broadcast queued transactions whose nonce is next for their sender
+    Flush {
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// GraphQL endpoints of the node, comma-separated for failover (defaults to a public node)
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Broadcast even if the node's chain ID does not match --network
+        #[arg(long)]
+        force: bool,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Remove a queued transaction
+    Remove {
+        /// Sender address
+        from: String,
+
+        /// Nonce of the transaction
+        nonce: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// Encrypt a secret key with a passphrase and add it to the keystore
+    Add {
+        /// Secret key in hex or base58 format (prompted for if omitted)
+        secret_key: Option<String>,
+
+        /// Generate a new key instead of importing one
+        #[arg(long, conflicts_with = "secret_key")]
+        generate: bool,
+
+        /// Label to refer to the key by
+        #[arg(short, long)]
+        label: Option<String>,
+
+        /// Save the passphrase in the OS keychain, so it is not asked again
+        #[arg(long)]
+        keyring: bool,
+    },
+
+    /// List the keys in the keystore
+    List {
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Delete a key from the keystore
+    Remove {
+        /// Label or address of the key
+        name: String,
+
+        /// Confirm the deletion; the key cannot be recovered without a backup
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Set or clear the label of a key
+    Label {
+        /// Label or address of the key
+        name: String,
+
+        /// New label; omit to clear it
+        label: Option<String>,
+    },
+
+    /// Show the default key, or make a key the default
+    Default {
+        /// Label or address of the key
+        name: Option<String>,
+    },
+
+    /// Save the passphrase of a key in the OS keychain (macOS Keychain,
+    /// Windows Credential Manager or Secret Service)
+    Keyring {
+        /// Label or address of the key
+        name: String,
+
+        /// Delete the saved passphrase instead
+        #[arg(long)]
+        forget: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Create or replace a profile
+    Add {
+        /// Name of the profile, e.g. berkeley
+        name: String,
+
+        /// Network the keys of the profile sign for: mainnet or testnet
+        #[arg(
+            short,
+            long = "network",
+            id = "profile_network",
+            value_name = "NETWORK"
+        )]
+        network: String,
+
+        /// GraphQL endpoints of the chain, comma-separated for failover
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Keystore directory of the profile (defaults to the shared keystore)
+        #[arg(long)]
+        keystore: Option<std::path::PathBuf>,
+    },
+
+    /// Use a profile when --profile is not given
+    Use {
+        /// Name of the profile
+        #[arg(required_unless_present = "none")]
+        name: Option<String>,
+
+        /// Stop using a profile by default
+        #[arg(long, conflicts_with = "name")]
+        none: bool,
+    },
+
+    /// List the profiles
+    List {
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContactsCommands {
+    /// Save an address under a label
+    Add {
+        /// Label, used as @label in place of the address
+        label: String,
+
+        /// Address of the contact
+        address: String,
+    },
+
+    /// List the contacts
+    List {
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Delete a contact
+    Remove {
+        /// Label of the contact
+        label: String,
+    },
+
+    /// Change the label or the address of a contact
+    Edit {
+        /// Label of the contact
+        label: String,
+
+        /// New label
+        #[arg(long)]
+        rename: Option<String>,
+
+        /// New address
+        #[arg(long)]
+        address: Option<String>,
+    },
+
+    /// Mark a contact as a favorite, listed first
+    Favorite {
+        /// Label of the contact
+        label: String,
+
+        /// Unmark it instead
+        #[arg(long)]
+        off: bool,
+    },
+
+    /// Find contacts by label or address prefix, favorites first
+    Search {
+        /// Part of the label, or start of the address
+        query: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConvertCommands {
+    /// Secret key, hex to Base58 or Base58 to hex
+    Secret {
+        /// Secret key; ends up in shell history, so prefer the prompt or --stdin
+        secret_key: Option<String>,
+
+        /// Read the secret key from the first line of stdin
+        #[arg(long, conflicts_with = "secret_key")]
+        stdin: bool,
+    },
+
+    /// Public key, address to raw bytes (x || y, little-endian hex) or back
+    PublicKey {
+        /// Address, or 64 bytes in hex
+        value: String,
+    },
+
+    /// Field element, decimal to 0x hex or 0x hex to decimal
+    Field {
+        /// Decimal, or big-endian hex starting with 0x
+        value: String,
+    },
+
+    /// Memo, text to Base58 or Base58 to text
+    Memo {
+        /// Text of at most 32 bytes, or a Base58 memo
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum LedgerCommands {
+    /// Show the address of an account of the device
+    Address {
+        /// Account index N, for the path m/44'/12586'/N'/0/0
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Sign a payment on the device, after checking it on its screen
+    SignPayment {
+        /// Account index N, for the path m/44'/12586'/N'/0/0
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+
+        #[command(flatten)]
+        payment: PaymentArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// List the signed transactions, oldest first
+    Show {
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Check that no entry was modified, removed or reordered
+    Verify,
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Split a secret key into N shares, any K of which recover it
+    Split {
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Number of shares required to recover the key (K)
+        #[arg(short, long, default_value_t = 2)]
+        threshold: u8,
+
+        /// Total number of shares to generate (N)
+        #[arg(short, long, default_value_t = 3)]
+        shares: u8,
+    },
+
+    /// Recover a secret key from shares
+    Recover {
+        /// Hex-encoded shares, at least as many as the threshold
+        #[arg(required = true)]
+        shares: Vec<String>,
+
+        /// Network: mainnet or testnet
+        #[arg(short, long, default_value = "mainnet")]
+        network: String,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Export the whole keystore as a single passphrase-encrypted file
+    Export {
+        /// File to write the backup to; it must not exist
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+    },
+
+    /// Restore the keys of a backup, keeping the keys already in the keystore
+    Import {
+        /// Backup file written by `backup export`
+        file: std::path::PathBuf,
+
+        /// Output format: text or json
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Output format of a command: json with --json, else its --format
+fn output_format(format: &str) -> &str {
+    if json_output() { "json" } else { format }
+}
+
+/// Report an error and exit: `{"error": {"code", "message"}}` on stdout with
+/// --json, else a message on stderr
+fn fail(message: impl std::fmt::Display) -> ! {
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "error": { "code": EXIT_ERROR, "message": message.to_string() }
+            })
+        );
+    } else {
+        eprintln!("Error: {}", message);
+    }
+    std::process::exit(EXIT_ERROR);
+}
+
+fn parse_network(network: &str) -> Result<NetworkId, String> {
+    match network.to_lowercase().as_str() {
+        "mainnet" => Ok(NetworkId::MAINNET),
+        "testnet" => Ok(NetworkId::TESTNET),
+        _ => Err(format!(
+            "Invalid network '{}'. Use 'mainnet' or 'testnet'.",
+            network
+        )),
+    }
+}
+
+fn import_wallet(
+    secret_key: &str,
+    network: NetworkId,
+    keys: &FileStorage,
+) -> Result<Wallet, String> {
+    // Try hex format first
+    if let Ok(wallet) = Wallet::from_secret_key_hex(secret_key, network.clone()) {
+        return Ok(wallet);
+    }
+
+    // Try base58 format
+    if let Ok(wallet) = Wallet::from_secret_key_base58(secret_key, network.clone()) {
+        return Ok(wallet);
+    }
+
+    // Then a key of the keystore
+    if load_keystore(keys).get(secret_key).is_some() {
+        return unlock_key(Some(secret_key), network, keys);
+    }
+
+    Err("Invalid secret key format. Expected hex (64 chars), base58 (52 chars), or the label or address of a keystore key.".to_string())
+}
+
+/// Generate a wallet from entropy typed in without echo, mixed with the OS
+/// randomness
+fn generate_from_entropy(source: &str, network: NetworkId) -> Result<Wallet, String> {
+    let source: EntropySource = source.parse().map_err(|e: EntropyError| e.to_string())?;
+    let prompt = match source {
+        EntropySource::Dice => format!("Dice rolls (1 to 6, at least {}): ", source.min_symbols()),
+        EntropySource::Coin => format!("Coin flips (H or T, at least {}): ", source.min_symbols()),
+        EntropySource::Hex => format!("Hex characters (at least {}): ", source.min_symbols()),
+    };
+    let input = prompt_secret(&prompt)?;
+    let user_entropy = UserEntropy::parse(source, &input).map_err(|e| e.to_string())?;
+    eprintln!(
+        "Mixing {:.0} bits of {} entropy with {} bytes from the OS",
+        user_entropy.bits(),
+        source,
+        entropy::OS_RANDOM_LEN
+    );
+    entropy::generate(&user_entropy, network).map_err(|e| e.to_string())
+}
+
+/// Import the key of an export of another wallet, asking for the passphrase
+/// of encrypted ones
+fn import_export(
+    from: &str,
+    file: Option<std::path::PathBuf>,
+    network: NetworkId,
+) -> Result<Wallet, String> {
+    let format: ExportFormat = from.parse().map_err(|e: ImportError| e.to_string())?;
+    let export = Zeroizing::new(read_input(None, file)?);
+    match ExportFormat::detect(&export) {
+        Some(detected) if detected != format => {
+            return Err(format!(
+                "This looks like an export of {:?}, not {:?}",
+                detected, format
+            ));
+        }
+        _ => {}
+    }
+    let passphrase = if format.needs_passphrase() {
+        read_passphrase(&format!("{:?} passphrase: ", format), false)?
+    } else {
+        Zeroizing::new(String::new())
+    };
+    import::import(format, &export, &passphrase, network).map_err(|e| e.to_string())
+}
+
+/// Get the wallet of a command, warning loudly when a raw secret key was
+/// passed on the command line
+fn resolve_wallet(
+    secret: &SecretArgs,
+    network: NetworkId,
+    keys: &FileStorage,
+) -> Result<Wallet, String> {
+    match &secret.secret_key {
+        Some(secret_key) => {
+            let wallet = import_wallet(secret_key, network, keys)?;
+            if load_keystore(keys).get(secret_key).is_none() {
+                warn_secret_in_argv();
+            }
+            Ok(wallet)
+        }
+        None if secret.stdin => {
+            let mut line = Zeroizing::new(String::new());
+            std::io::stdin()
+                .read_line(&mut line)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            import_wallet(line.trim(), network, keys)
+        }
+        None => match std::env::var(KEY_ENV)
+            .ok()
+            .or_else(|| config().default_account.clone())
+        {
+            Some(account) => unlock_key(Some(&account), network, keys),
+            None if load_keystore(keys).default_key().is_some() => unlock_key(None, network, keys),
+            None => {
+                let secret_key = prompt_secret("Secret key: ")?;
+                import_wallet(secret_key.trim(), network, keys)
+            }
+        },
+    }
+}
+
+fn warn_secret_in_argv() {
+    eprintln!("WARNING: a secret key was passed on the command line.");
+    eprintln!(
+        "WARNING: it is saved in your shell history and visible to other users in the process list."
+    );
+    eprintln!("WARNING: use `keys add`, --stdin or the prompt instead.");
+}
+
+/// Decrypt a keystore key, or the default key if `name` is `None`
+fn unlock_key(
+    name: Option<&str>,
+    network: NetworkId,
+    keys: &FileStorage,
+) -> Result<Wallet, String> {
+    let keystore = load_keystore(keys);
+    let entry = match name {
+        Some(name) => keystore.get(name),
+        None => keystore.default_key(),
+    }
+    .ok_or_else(|| match name {
+        Some(name) => format!("No key '{}' in the keystore", name),
+        None => {
+            "No default key: pass a secret key, or see `keys add` and `keys default`".to_string()
+        }
+    })?;
+    if let Some(key) = agent::get(&agent::socket_path(keys.dir()), &entry.address) {
+        // A key cached before the passphrase was changed no longer decrypts the entry
+        if let Ok(wallet) = keystore.unlock_with_key(&entry.address, &key, network.clone()) {
+            return Ok(wallet);
+        }
+    }
+    let passphrase = match keyring_get(&entry.address)? {
+        Some(passphrase) => passphrase,
+        None => read_passphrase(&format!("Passphrase for {}: ", key_name(entry)), false)?,
+    };
+    keystore
+        .unlock(&entry.address, &passphrase, network)
+        .map_err(|e| e.to_string())
+}
+
+/// OS keychain entry holding the passphrase of a keystore key
+#[cfg(feature = "keyring")]
+fn keyring_entry(address: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, address).map_err(|e| format!("OS keychain: {}", e))
+}
+
+/// Passphrase of a key saved in the OS keychain, if any
+#[cfg(feature = "keyring")]
+fn keyring_get(address: &str) -> Result<Option<Zeroizing<String>>, String> {
+    match keyring_entry(address)?.get_password() {
+        Ok(passphrase) => Ok(Some(Zeroizing::new(passphrase))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("OS keychain: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_get(_address: &str) -> Result<Option<Zeroizing<String>>, String> {
+    Ok(None)
+}
+
+/// Save the passphrase of a key in the OS keychain
+#[cfg(feature = "keyring")]
+fn keyring_set(address: &str, passphrase: &str) -> Result<(), String> {
+    keyring_entry(address)?
+        .set_password(passphrase)
+        .map_err(|e| format!("OS keychain: {}", e))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_set(_address: &str, _passphrase: &str) -> Result<(), String> {
+    Err(KEYRING_UNSUPPORTED.to_string())
+}
+
+/// Delete the passphrase of a key from the OS keychain, if it is there
+#[cfg(feature = "keyring")]
+fn keyring_delete(address: &str) -> Result<(), String> {
+    match keyring_entry(address)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("OS keychain: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_delete(_address: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Read a passphrase from $MINA_WALLET_PASSPHRASE, the file named by
+/// $MINA_WALLET_PASSPHRASE_FILE, or prompt for it without echo
+fn read_passphrase(prompt: &str, confirm: bool) -> Result<Zeroizing<String>, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    if let Some(path) = std::env::var_os(PASSPHRASE_FILE_ENV) {
+        let contents = Zeroizing::new(std::fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "Cannot read ${} ({}): {}",
+                PASSPHRASE_FILE_ENV,
+                std::path::Path::new(&path).display(),
+                e
+            )
+        })?);
+        // The first line, without its line ending
+        let line = contents.lines().next().unwrap_or_default();
+        return Ok(Zeroizing::new(line.to_string()));
+    }
+    let passphrase = prompt_secret(prompt)?;
+    if confirm {
+        let again = prompt_secret("Repeat passphrase: ")?;
+        if !secret::ct_eq(passphrase.as_bytes(), again.as_bytes()) {
+            return Err("Passphrases do not match".to_string());
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Prompt for a secret without echo, unless --non-interactive disables prompts
+fn prompt_secret(prompt: &str) -> Result<Zeroizing<String>, String> {
+    let what = prompt.trim_end_matches([':', ' ']);
+    if NON_INTERACTIVE.load(Ordering::Relaxed) {
+        return Err(format!(
+            "{} needed, but prompts are disabled by --non-interactive",
+            what
+        ));
+    }
+    rpassword::prompt_password(prompt)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Cannot read {}: {}", what.to_lowercase(), e))
+}
+
+/// Label of a key, or its address if it has none
+fn key_name(entry: &KeyEntry) -> &str {
+    entry.label.as_deref().unwrap_or(&entry.address)
+}
+
+/// Report a key added to the keystore, as `{"address", "label", "keystore",
+/// "keyring"}` in the json format
+fn print_key_added(entry: &KeyEntry, keys: &FileStorage, keyring: bool, format: &str) {
+    if format == "json" {
+        println!(
+            "{}",
+            serde_json::json!({
+                "address": entry.address,
+                "label": entry.label,
+                "keystore": keys.dir(),
+                "keyring": keyring,
+            })
+        );
+        return;
+    }
+    println!("Added {} to {}", key_name(entry), keys.dir().display());
+    println!("Address: {}", entry.address);
+    if keyring {
+        println!("Passphrase saved in the OS keychain");
+    }
+}
+
+/// Storage of the keystore, in the `keys` directory of the data directory
+fn keystore_storage(storage: &FileStorage) -> FileStorage {
+    FileStorage::new(storage.dir().join("keys"))
+}
+
+fn load_keystore(keys: &FileStorage) -> Keystore {
+    match Keystore::load(keys) {
+        Ok(keystore) => keystore,
+        Err(e) => fail(e),
+    }
+}
+
+fn save_keystore(keys: &mut FileStorage, keystore: &Keystore) {
+    if let Err(e) = keystore.save(keys) {
+        fail(e);
+    }
+}
+
+fn graphql_client(node: Option<&str>, network: &NetworkId) -> GraphQlClient {
+    GraphQlClient::with_endpoints(match node {
+        Some(node) => parse_endpoints(node),
+        None => default_endpoints(network),
+    })
+}
+
+/// GraphQL endpoints used without --node: those of the configuration file,
+/// else a public node
+fn default_endpoints(network: &NetworkId) -> Vec<String> {
+    let configured = match network {
+        NetworkId::MAINNET => &config().nodes.mainnet,
+        NetworkId::TESTNET => &config().nodes.testnet,
+    };
+    if configured.is_empty() {
+        vec![network::default_endpoint(network).to_string()]
+    } else {
+        configured.clone()
+    }
+}
+
+/// Refuse to go on if the node is not on the chain of `network`, unless `force` is set
+fn check_chain_id(client: &GraphQlClient, network: &NetworkId, force: bool) -> network::Result<()> {
+    match block_on(client.verify_chain_id(network)) {
+        Err(e @ network::NetworkError::ChainMismatch { .. }) if force => {
+            eprintln!("Warning: {} (continuing because of --force)", e);
+            Ok(())
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+/// Describe a failed chain ID check
+fn chain_id_error(e: network::NetworkError) -> String {
+    match e {
+        network::NetworkError::ChainMismatch { .. } => {
+            format!("{}. Pass --force to continue anyway", e)
+        }
+        e => format!("Failed to check the chain ID of the node: {}", e),
+    }
+}
+
+/// Reasons to doubt that `delegate` produces blocks, and its entry in the
+/// pool directory if it is listed
+fn delegate_warnings(
+    client: &GraphQlClient,
+    delegate: &str,
+    pool_directory: Option<&str>,
+) -> (Option<staking::PoolInfo>, Vec<String>) {
+    let mut warnings = Vec::new();
+    match block_on(client.staking_info(delegate)) {
+        Ok(info) => {
+            if let Some(own) = info.delegate.as_deref().filter(|own| *own != delegate) {
+                warnings.push(format!(
+                    "{} delegates its own stake to {}, so it is probably not a block producer",
+                    delegate, own
+                ));
+            } else if info.delegators.is_empty() {
+                warnings.push(format!("No account delegates to {} yet", delegate));
+            }
+        }
+        Err(network::NetworkError::AccountNotFound(_)) => {
+            warnings.push(format!("{} has no account on chain", delegate));
+        }
+        Err(e) => warnings.push(format!("Could not look up {}: {}", delegate, e)),
+    }
+
+    let pool = pool_directory.and_then(|url| match block_on(staking::fetch_pool_directory(url)) {
+        Ok(pools) => {
+            let pool = pools.into_iter().find(|p| p.address == delegate);
+            if pool.is_none() {
+                warnings.push(format!("{} is not in the pool directory", delegate));
+            }
+            pool
+        }
+        Err(e) => {
+            warnings.push(format!("Could not fetch the pool directory: {}", e));
+            None
+        }
+    });
+    (pool, warnings)
+}
+
+/// Split a comma-separated list of endpoints, in order of preference
+fn parse_endpoints(endpoints: &str) -> Vec<String> {
+    endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Run a network future to completion on a single-threaded runtime
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the async runtime")
+        .block_on(future)
+}
+
+/// Build and sign a payment, fetching the nonce from the node if requested
+fn sign_payment(
+    secret: &SecretArgs,
+    args: &PaymentArgs,
+    storage: &FileStorage,
+    keys: &FileStorage,
+) -> Result<SignedTransaction, String> {
+    let network_id = parse_network(&args.network)?;
+    let wallet = resolve_wallet(secret, network_id.clone(), keys)?;
+    let payment = build_payment(args, &wallet.address(), storage)?;
+    let signed = wallet
+        .sign_transaction(&payment)
+        .map_err(|e| e.to_string())?;
+    audit_signature(&audit_log_path(storage), &signed)?;
+    Ok(signed)
+}
+
+/// Build a payment from `from`, fetching the nonce and fee from the node if
+/// requested, and show what it costs
+fn build_payment(
+    args: &PaymentArgs,
+    from: &str,
+    storage: &FileStorage,
+) -> Result<Transaction, String> {
+    let network_id = parse_network(&args.network)?;
+    let amount: Amount = args.amount.parse().map_err(|e| format!("{}", e))?;
+    let client = graphql_client(args.node.as_deref(), &network_id);
+    if args.queries_node() {
+        check_chain_id(&client, &network_id, args.force).map_err(chain_id_error)?;
+    }
+
+    let fee: Amount = match args.fee.as_str() {
+        speed @ ("slow" | "standard" | "fast") => {
+            let suggestion = block_on(client.suggest_fee())
+                .map_err(|e| format!("Failed to suggest a fee: {}", e))?;
+            let fee = match speed {
+                "slow" => suggestion.slow,
+                "fast" => suggestion.fast,
+                _ => suggestion.standard,
+            };
+            eprintln!(
+                "Using {} fee of {} MINA from {} pending transactions",
+                speed, fee, suggestion.pool_size
+            );
+            fee
+        }
+        fee => fee.parse().map_err(|e| format!("{}", e))?,
+    };
+
+    // The receiver account is looked up on the node when it is queried anyway
+    let (nonce, creates_account) = match args.nonce {
+        Some(n) => (n, args.new_account.then_some(true)),
+        None => {
+            let n = next_nonce(storage, &client, from)?;
+            eprintln!("Using nonce {} from {}", n, client.endpoint());
+            let creates_account = if args.new_account {
+                Some(true)
+            } else {
+                match block_on(client.account_exists(&args.to)) {
+                    Ok(exists) => Some(!exists),
+                    Err(e) => {
+                        eprintln!("Warning: could not look up the receiver account: {}", e);
+                        None
+                    }
+                }
+            };
+            (n, creates_account)
+        }
+    };
+
+    let mut payment =
+        Transaction::payment(from, &args.to, amount, fee, nonce).with_memo(&args.memo);
+    let valid_until = match args.valid_for {
+        Some(minutes) => Some(
+            ConsensusConstants::for_network(&network_id)
+                .valid_until(unix_now(), minutes.saturating_mul(60))
+                .ok_or("--valid-for is out of range")?,
+        ),
+        None => args.valid_until,
+    };
+    if let Some(valid_until) = valid_until {
+        payment = payment.with_valid_until(valid_until);
+    }
+    let preview = payment.preview(creates_account);
+    print_payment_preview(&preview);
+    if preview.credited.is_none() {
+        return Err(format!(
+            "The receiver account does not exist yet, and the amount does not cover the {} MINA account creation fee",
+            preview.account_creation_fee
+        ));
+    }
+    Ok(payment)
+}
+
+fn sign_request(
+    wallet: &Wallet,
+    line: &str,
+    default_fee: Amount,
+    nonce: u32,
+) -> Result<SignedTransaction, String> {
+    let request: PaymentRequest =
+        serde_json::from_str(line).map_err(|e| format!("Invalid request: {}", e))?;
+    let amount: Amount = request.amount.parse().map_err(|e| format!("{}", e))?;
+    let fee = match &request.fee {
+        Some(fee) => fee.parse().map_err(|e| format!("{}", e))?,
+        None => default_fee,
+    };
+    let mut payment = Transaction::payment(&wallet.address(), &request.to, amount, fee, nonce)
+        .with_memo(request.memo.as_deref().unwrap_or(""));
+    if let Some(valid_until) = request.valid_until {
+        payment = payment.with_valid_until(valid_until);
+    }
+    wallet.sign_transaction(&payment).map_err(|e| e.to_string())
+}
+
+/// Read and check every row of a `send-batch` file, so that nothing is sent
+/// from a file with a mistake in it
+fn read_batch(
+    path: &std::path::Path,
+    from: &str,
+    default_fee: Amount,
+    storage: &FileStorage,
+) -> Result<Vec<BatchRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut rows = Vec::new();
+    for (index, record) in reader.deserialize::<CsvPayment>().enumerate() {
+        let number = index + 1;
+        let invalid = |e: String| format!("Row {}: {}", number, e);
+        let record = record.map_err(|e| invalid(e.to_string()))?;
+        let row = BatchRow {
+            to: resolve_recipient(storage, &record.to),
+            amount: record
+                .amount
+                .parse()
+                .map_err(|e| invalid(format!("{}", e)))?,
+            fee: match record.fee.as_deref() {
+                Some(fee) => fee.parse().map_err(|e| invalid(format!("{}", e)))?,
+                None => default_fee,
+            },
+            memo: record.memo,
+        };
+        row.payment(from, 0)
+            .validate()
+            .map_err(|e| invalid(e.to_string()))?;
+        rows.push(row);
+    }
+    if rows.is_empty() {
+        return Err(format!("{} has no payments", path.display()));
+    }
+    Ok(rows)
+}
+
+fn load_batch_progress(path: &std::path::Path) -> Result<BatchProgress, String> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => {
+            BatchProgress::from_json(&json).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BatchProgress::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Report what happened to a row of `send-batch`
+fn print_batch_row(number: usize, row: &BatchRow, status: &str, nonce: u32, hash: &str) {
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "row": number,
+                "status": status,
+                "to": row.to,
+                "amount": row.amount,
+                "nonce": nonce,
+                "hash": hash,
+            })
+        );
+    } else {
+        println!(
+            "Row {}: {} MINA to {}, {} (nonce {}, {})",
+            number, row.amount, row.to, status, nonce, hash
+        );
+    }
+}
+
+/// Poll the node until a broadcast payment is final, exiting with
+/// `EXIT_NOT_FOUND` if the node does not know it
+fn wait_for_payment(client: GraphQlClient, hash: &str, confirmations: u32) {
+    let tracker = TxTracker::new(client).with_confirmations(confirmations);
+    match block_on(tracker.track(hash, tokio::time::sleep, |status| {
+        print_tx_status(status, output_format("text"))
+    })) {
+        Ok(TxStatus::Unknown) => std::process::exit(EXIT_NOT_FOUND),
+        Ok(_) => {}
+        Err(e) => fail(format!("Failed to track payment: {}", e)),
+    }
+}
+
+/// Write an envelope to `output`, or to stdout
+fn write_envelope(envelope: &TransactionEnvelope, output: Option<&std::path::Path>) {
+    let json = envelope.to_json();
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, json + "\n") {
+                fail(format!("Failed to write {}: {}", path.display(), e));
+            }
+            if json_output() {
+                println!("{}", serde_json::json!({ "output": path }));
+            } else {
+                eprintln!("Wrote {}", path.display());
+            }
+        }
+        None => println!("{}", json),
+    }
+}
+
+/// A payment read by `tx preview`
+struct DecodedTransaction {
+    network: String,
+    transaction: Transaction,
+    signed: Option<SignedTransaction>,
+}
+
+/// Decode a `tx` envelope, or a bare signed or unsigned payment for `network`
+fn decode_transaction(json: &str, network: &str) -> Result<DecodedTransaction, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    if value.get("version").is_some() {
+        let envelope = TransactionEnvelope::from_json(json).map_err(|e| e.to_string())?;
+        let (transaction, signed) = match envelope.payload {
+            EnvelopePayload::Unsigned { transaction } => (transaction, None),
+            EnvelopePayload::Signed { signed_transaction } => {
+                (signed_transaction.data.clone(), Some(signed_transaction))
+            }
+        };
+        return Ok(DecodedTransaction {
+            network: envelope.network,
+            transaction,
+            signed,
+        });
+    }
+    let (transaction, signed) = if value.get("signature").is_some() {
+        let signed: SignedTransaction =
+            serde_json::from_value(value).map_err(|e| format!("Invalid signed payment: {}", e))?;
+        (signed.data.clone(), Some(signed))
+    } else {
+        let transaction =
+            serde_json::from_value(value).map_err(|e| format!("Invalid payment: {}", e))?;
+        (transaction, None)
+    };
+    Ok(DecodedTransaction {
+        network: network.to_string(),
+        transaction,
+        signed,
+    })
+}
+
+fn read_envelope(input: Option<std::path::PathBuf>) -> TransactionEnvelope {
+    let mut json = match read_input(None, input) {
+        Ok(json) => json,
+        Err(e) => fail(e),
+    };
+    if json
+        .trim_start()
+        .get(..3)
+        .is_some_and(|p| p.eq_ignore_ascii_case("ur:"))
+    {
+        json = match decode_ur_lines(&json) {
+            Ok(json) => json,
+            Err(e) => fail(e),
+        };
+    }
+    match TransactionEnvelope::from_json(&json) {
+        Ok(envelope) => envelope,
+        Err(e) => fail(e),
+    }
+}
+
+/// Show what a payment costs and credits, on stderr so that stdout stays JSON
+fn print_payment_preview(preview: &PaymentPreview) {
+    eprintln!("Amount:     {} MINA", preview.amount);
+    eprintln!("Fee:        {} MINA", preview.fee);
+    eprintln!("Total cost: {} MINA", preview.total_cost);
+    match (preview.creates_account, preview.credited) {
+        (Some(true), Some(credited)) => eprintln!(
+            "Receiver account does not exist yet: the {} MINA account creation fee applies, {} MINA will be credited",
+            preview.account_creation_fee, credited
+        ),
+        (None, _) => eprintln!(
+            "Receiver account not checked: if it does not exist yet, the {} MINA account creation fee is deducted from the amount (see --new-account)",
+            transaction::ACCOUNT_CREATION_FEE
+        ),
+        _ => {}
+    }
+}
+
+fn print_tx_status(status: &TxStatus, format: &str) {
+    if format == "json" {
+        println!("{}", serde_json::to_string(status).unwrap());
+        return;
+    }
+    match status {
+        TxStatus::Pending => println!("Pending: waiting in the transaction pool"),
+        TxStatus::Included {
+            block_height,
+            confirmations,
+            ..
+        } => println!(
+            "Included in block {} ({} confirmations)",
+            block_height, confirmations
+        ),
+        TxStatus::Finalized {
+            block_height,
+            confirmations,
+            ..
+        } => println!(
+            "Finalized in block {} ({} confirmations)",
+            block_height, confirmations
+        ),
+        TxStatus::Unknown => println!("Unknown: not found in the pool or recent blocks"),
+    }
+}
+
+fn print_history_text(page: &HistoryPage) {
+    for tx in &page.transactions {
+        let (direction, counterparty) = match tx.direction {
+            Direction::Incoming => ("IN", &tx.from),
+            Direction::Outgoing => ("OUT", &tx.to),
+            Direction::SelfTransfer => ("SELF", &tx.to),
+        };
+        let failed = if tx.failure_reason.is_some() {
+            " (failed)"
+        } else {
+            ""
+        };
+        println!(
+            "{}  {:<4} {:<10} {:>16} MINA  {}  {}{}",
+            time::to_rfc3339(tx.timestamp),
+            direction,
+            tx.kind,
+            tx.amount,
+            counterparty,
+            tx.hash,
+            failed
+        );
+    }
+}
+
+fn print_watch_event(event: &WatchEvent, format: &str) {
+    if format == "json" {
+        println!("{}", serde_json::to_string(event).unwrap());
+        return;
+    }
+    let tx = &event.command;
+    let (direction, counterparty) = match event.direction {
+        Direction::Incoming => ("IN", &tx.from),
+        Direction::Outgoing => ("OUT", &tx.to),
+        Direction::SelfTransfer => ("SELF", &tx.to),
+    };
+    println!(
+        "{}  {:<4} {:<16} {:>16} MINA  {}  {}",
+        event.block_height, direction, tx.kind, tx.amount, counterparty, tx.hash
+    );
+}
+
+/// The HMAC secret of `watch --webhook`, from a file or the environment
+fn webhook_secret(file: Option<&std::path::Path>) -> Result<Zeroizing<String>, String> {
+    let secret = match file {
+        Some(path) => Zeroizing::new(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        ),
+        None => Zeroizing::new(std::env::var(WEBHOOK_SECRET_ENV).map_err(|_| {
+            format!(
+                "--webhook needs a secret: set ${} or pass --webhook-secret-file",
+                WEBHOOK_SECRET_ENV
+            )
+        })?),
+    };
+    let secret = Zeroizing::new(secret.trim().to_string());
+    if secret.is_empty() {
+        return Err("The webhook secret is empty".to_string());
+    }
+    Ok(secret)
+}
+
+/// Deliver a payload to every webhook, retrying failed deliveries
+///
+/// Failures are reported on stderr only: a receiver being down must not stop
+/// the watch.
+async fn notify_webhooks(webhooks: &[Webhook], payload: &WebhookPayload) {
+    let policy = RetryPolicy::default();
+    for webhook in webhooks {
+        for attempt in 0..WEBHOOK_ATTEMPTS {
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            match webhook.send(payload).await {
+                Ok(()) => break,
+                Err(e) if attempt + 1 == WEBHOOK_ATTEMPTS => eprintln!(
+                    "Warning: {} of {} not delivered to {}: {}",
+                    payload.event.as_str(),
+                    payload.transaction.hash,
+                    webhook.url(),
+                    e
+                ),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+fn print_history_csv(page: &HistoryPage) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    for tx in &page.transactions {
+        writer.serialize(tx).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Every transaction of an address older than `before`, fetching pages of
+/// `limit` transactions
+async fn full_history(
+    client: &ArchiveClient,
+    address: &str,
+    limit: u32,
+    mut before: Option<u64>,
+) -> Result<Vec<network::HistoryEntry>, network::NetworkError> {
+    let mut entries = Vec::new();
+    loop {
+        let page = client.history(address, limit, before).await?;
+        entries.extend(page.transactions);
+        match page.next_cursor {
+            Some(cursor) => before = Some(cursor),
+            None => return Ok(entries),
+        }
+    }
+}
+
+fn print_accounting_csv(rows: &[AccountingRow], currency: &str) -> Result<(), String> {
+    let currency = currency.to_lowercase();
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer
+        .write_record([
+            "date",
+            "hash",
+            "kind",
+            "direction",
+            "counterparty",
+            "amount",
+            "fee",
+            "net",
+            "memo",
+            "failed",
+            &format!("price_{}", currency),
+            &format!("value_{}", currency),
+        ])
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        writer
+            .write_record([
+                row.date.as_str(),
+                &row.hash,
+                &row.kind,
+                row.direction.as_str(),
+                &row.counterparty,
+                &row.amount.to_string(),
+                &row.fee.to_string(),
+                &row.net,
+                &row.memo,
+                if row.failed { "true" } else { "false" },
+                &row.price.map(|p| p.to_string()).unwrap_or_default(),
+                row.value.as_deref().unwrap_or_default(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// The configuration loaded at startup, or the defaults
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The --config file, else the default configuration file if there is one
+///
+/// The configuration sets argument defaults, so it is located before clap
+/// parses the arguments.
+fn config_path() -> Option<(std::path::PathBuf, bool)> {
+    if let Some(path) = prescan_arg("--config") {
+        return Some((path.into(), true));
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })
+        .map(|dir| (dir.join("mina-wallet").join("config.toml"), false))
+}
+
+/// Value of a global option, read from the command line before clap parses it
+fn prescan_arg(name: &str) -> Option<std::ffi::OsString> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == name {
+            return args.next();
+        }
+        if let Some(value) = arg
+            .to_str()
+            .and_then(|a| a.strip_prefix(name))
+            .and_then(|a| a.strip_prefix('='))
+        {
+            return Some(value.into());
+        }
+    }
+    None
+}
+
+/// Read the configuration file; a missing default file is an empty configuration
+fn load_config() -> Result<Config, String> {
+    let Some((path, explicit)) = config_path() else {
+        return Ok(Config::default());
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+            return Ok(Config::default());
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let config: Config =
+        toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    config
+        .validate()
+        .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    Ok(config)
+}
+
+/// Use the configuration file for the defaults of --network, and of --fee
+/// where it accepts a fee speed
+fn apply_config_defaults(mut command: clap::Command, config: &Config) -> clap::Command {
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in subcommands {
+        command = command.mut_subcommand(name, |sub| apply_config_defaults(sub, config));
+    }
+
+    let has_arg =
+        |command: &clap::Command, id: &str| command.get_arguments().any(|a| a.get_id() == id);
+    if let Some(network) = &config.network
+        && has_arg(&command, "network")
+    {
+        command = command.mut_arg("network", |a| a.default_value(network.clone()));
+    }
+    if let Some(fee) = &config.fee
+        && matches!(command.get_name(), "send" | "sign-payment")
+    {
+        command = command.mut_arg("fee", |a| a.default_value(fee.clone()));
+    }
+    command
+}
+
+/// Directory for wallet state: --data-dir, $MINA_WALLET_DIR, then ~/.mina-wallet
+fn data_dir(dir: Option<std::path::PathBuf>) -> std::path::PathBuf {
+    dir.or_else(|| std::env::var_os("MINA_WALLET_DIR").map(Into::into))
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".mina-wallet"))
+        })
+        .unwrap_or_else(|| ".mina-wallet".into())
+}
+
+/// Current Unix timestamp, in seconds
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn load_queue(storage: &FileStorage) -> BroadcastQueue {
+    match BroadcastQueue::load(storage) {
+        Ok(queue) => queue,
+        Err(e) => fail(e),
+    }
+}
+
+fn save_queue(storage: &mut FileStorage, queue: &BroadcastQueue) {
+    if let Err(e) = queue.save(storage) {
+        fail(e);
+    }
+}
+
+fn load_address_book(storage: &FileStorage) -> AddressBook {
+    match AddressBook::load(storage) {
+        Ok(book) => book,
+        Err(e) => fail(e),
+    }
+}
+
+fn save_address_book(storage: &mut FileStorage, book: &AddressBook) {
+    if let Err(e) = book.save(storage) {
+        fail(e);
+    }
+}
+
+/// One line of a contact list, favorites marked with `*`
+fn print_contact(contact: &Contact) {
+    let marker = if contact.favorite { "*" } else { " " };
+    println!("{} @{:<20} {}", marker, contact.label, contact.address);
+}
+
+/// Report a contact changed by `contacts`, as `{"label", "address",
+/// "favorite"}` with --json, else as `message`
+fn print_saved_contact(contact: &Contact, message: &str) {
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "label": contact.label,
+                "address": contact.address,
+                "favorite": contact.favorite,
+            })
+        );
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Address of a receiver given as an address or as @label of a contact
+fn resolve_recipient(storage: &FileStorage, to: &str) -> String {
+    if !to.starts_with(CONTACT_PREFIX) {
+        return to.to_string();
+    }
+    match load_address_book(storage).resolve(to) {
+        Ok(address) => {
+            eprintln!("{} is {}", to, address);
+            address
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// Audit log of the transactions signed with this data directory
+fn audit_log_path(storage: &FileStorage) -> std::path::PathBuf {
+    storage.dir().join(AUDIT_LOG_FILE)
+}
+
+/// Read the audit log, empty if nothing was signed yet
+fn load_audit_log(path: &std::path::Path) -> Result<AuditLog, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => AuditLog::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AuditLog::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Append a signed transaction to the audit log
+///
+/// Called before the transaction leaves the wallet, so that nothing is signed
+/// without a record.
+fn audit_signature(path: &std::path::Path, signed: &SignedTransaction) -> Result<(), String> {
+    let mut log = load_audit_log(path)?;
+    let line = log.append(signed, unix_now()).to_line();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| format!("Failed to write the audit log {}: {}", path.display(), e))
+}
+
+fn load_nonces(storage: &FileStorage) -> NonceManager {
+    match NonceManager::load(storage) {
+        Ok(nonces) => nonces,
+        Err(e) => fail(e),
+    }
+}
+
+fn save_nonces(storage: &mut FileStorage, nonces: &NonceManager) {
+    if let Err(e) = nonces.save(storage) {
+        fail(e);
+    }
+}
+
+/// Next nonce of an address: the node's inferred nonce, or the nonce after
+/// the transactions this wallet already broadcast or queued, if higher
+fn next_nonce(storage: &FileStorage, client: &GraphQlClient, address: &str) -> Result<u32, String> {
+    block_on(load_nonces(storage).fetch_next_nonce(client, address))
+        .map_err(|e| format!("Failed to fetch nonce: {}", e))
+}
+
+/// Remember that the nonce of a broadcast or queued transaction is used
+///
+/// The transaction is already out, so a failure is only a warning.
+fn record_nonce(storage: &mut FileStorage, from: &str, nonce: u32) {
+    let recorded = NonceManager::load(storage).and_then(|mut nonces| {
+        nonces.observe(from, nonce.saturating_add(1));
+        nonces.save(storage)
+    });
+    if let Err(e) = recorded {
+        eprintln!("Warning: could not record the used nonce: {}", e);
+    }
+}
+
+fn queue_transaction(storage: &mut FileStorage, signed: SignedTransaction) {
+    let mut queue = load_queue(storage);
+    let (from, nonce) = (signed.data.from.clone(), signed.data.nonce);
+    if let Err(e) = queue.push(signed, unix_now()) {
+        fail(e);
+    }
+    save_queue(storage, &queue);
+    record_nonce(storage, &from, nonce);
+    if json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "from": from, "nonce": nonce, "queued": true })
+        );
+        return;
+    }
+    println!(
+        "Queued transaction from {} with nonce {} in {}",
+        from,
+        nonce,
+        storage.dir().display()
+    );
+}
+
+fn read_input(data: Option<String>, file: Option<std::path::PathBuf>) -> Result<String, String> {
+    match (data, file) {
+        (Some(data), _) => Ok(data),
+        (None, Some(path)) => std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        (None, None) => {
+            let mut input = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            Ok(input)
+        }
+    }
+}
+
+/// Parse a JSON array of field elements, given as decimal strings or integers
+fn parse_fields(input: &str) -> Result<Vec<Fp>, String> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(input)
+        .map_err(|e| format!("Expected a JSON array of fields: {}", e))?;
+    if values.is_empty() {
+        return Err("No fields to sign".to_string());
+    }
+    values
+        .iter()
+        .map(|value| match value {
+            serde_json::Value::String(_) | serde_json::Value::Number(_) => {
+                oracle::encode_value(value).map_err(|e| e.to_string())
+            }
+            other => Err(format!("{} is not a field element", other)),
+        })
+        .collect()
+}
+
+/// Print the result of `convert`, as `{"value": ...}` with --json
+fn print_converted(converted: &str) {
+    if json_output() {
+        println!("{}", serde_json::json!({ "value": converted }));
+    } else {
+        println!("{}", converted);
+    }
+}
+
+fn print_wallet_text(wallet: &Wallet) {
+    println!("Wallet Generated Successfully!");
+    println!("==============================");
+    println!("Address:          {}", wallet.address());
+    println!("Secret Key (Hex): {}", wallet.secret_key_hex().as_str());
+    println!("Secret Key (B58): {}", wallet.secret_key_base58().as_str());
+    println!("Network:          {:?}", wallet.network());
+    println!();
+    println!(
+        "WARNING: Store your secret key securely! Anyone with access to it can control your funds."
+    );
+}
+
+fn print_wallet_json(wallet: &Wallet) {
+    let json = serde_json::json!({
+        "address": wallet.address(),
+        "secret_key_hex": wallet.secret_key_hex().as_str(),
+        "secret_key_base58": wallet.secret_key_base58().as_str(),
+        "network": format!("{:?}", wallet.network()).to_lowercase(),
+    });
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
+
+fn print_paper_wallet_text(paper: &PaperWallet) {
+    println!("Mina Paper Wallet ({})", paper.network);
+    println!("==============================");
+    println!("Address:");
+    println!("  {}", paper.address);
+    println!();
+    println!("Secret Key (B58), in order:");
+    for (i, row) in paper.secret_chunks.chunks(4).enumerate() {
+        let numbered: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(j, chunk)| format!("{:>2}. {}", i * 4 + j + 1, chunk))
+            .collect();
+        println!("  {}", numbered.join("   "));
+    }
+    println!();
+    println!("Checksum words:   {}", paper.checksum_words.join(" "));
+    println!("Receive QR data:  {}", paper.address_qr);
+    println!("Restore QR data:  {}", paper.secret_qr);
+    println!();
+    println!("WARNING: Anyone who sees this paper can control your funds.");
+}
+
+fn main() {
+    let mut config = match load_config() {
+        Ok(config) => config,
+        Err(e) => fail(e),
+    };
+    // Like the configuration, the profile sets argument defaults
+    let profiles = match Profiles::load(&FileStorage::new(data_dir(
+        prescan_arg("--data-dir").map(Into::into),
+    ))) {
+        Ok(profiles) => profiles,
+        Err(e) => fail(e),
+    };
+    let profile_name = prescan_arg("--profile").map(|name| name.to_string_lossy().into_owned());
+    let profile = match profiles.select(profile_name.as_deref()) {
+        Ok(profile) => profile.map(|(_, profile)| profile.clone()),
+        Err(e) => fail(e),
+    };
+    if let Some(profile) = &profile {
+        config.apply_profile(profile);
+    }
+    let matches = apply_config_defaults(Cli::command(), &config).get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+    let non_interactive = cli.non_interactive
+        || std::env::var(NON_INTERACTIVE_ENV).is_ok_and(|v| v == "1" || v == "true");
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+    JSON_OUTPUT.store(
+        cli.json || non_interactive || config.output.as_deref() == Some("json"),
+        Ordering::Relaxed,
+    );
+    let _ = CONFIG.set(config);
+    let mut storage = FileStorage::new(data_dir(cli.data_dir));
+    let mut keys = match profile.and_then(|profile| profile.keystore) {
+        Some(dir) => FileStorage::new(dir),
+        None => keystore_storage(&storage),
+    };
+
+    match cli.command {
+        Commands::Generate {
+            network,
+            format,
+            count,
+            output,
+            qr,
+            entropy_from,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            if let Some(output) = output {
+                let passphrase = match read_passphrase("Passphrase for the secret keys: ", true) {
+                    Ok(p) => p,
+                    Err(e) => fail(e),
+                };
+                if passphrase.is_empty() {
+                    fail("the passphrase must not be empty");
+                }
+                let rows: Vec<GeneratedWallet> = match (0..count.unwrap_or(1))
+                    .into_par_iter()
+                    .map(|_| {
+                        let wallet = Wallet::new(network_id.clone())
+                            .map_err(|e| format!("Failed to generate wallet: {}", e))?;
+                        let secret =
+                            EncryptedSecret::from_wallet(&wallet, &passphrase, KdfCost::default())
+                                .map_err(|e| e.to_string())?;
+                        Ok(GeneratedWallet {
+                            address: wallet.address(),
+                            secret,
+                        })
+                    })
+                    .collect::<Result<_, String>>()
+                {
+                    Ok(rows) => rows,
+                    Err(e) => fail(e),
+                };
+                if let Err(e) = write_generated_wallets(&output, &rows) {
+                    fail(e);
+                }
+                match output_format(&format) {
+                    "json" => println!(
+                        "{}",
+                        serde_json::json!({ "count": rows.len(), "output": output })
+                    ),
+                    _ => println!("Generated {} wallets in {}", rows.len(), output.display()),
+                }
+                return;
+            }
+
+            let wallet = match entropy_from {
+                Some(source) => generate_from_entropy(&source, network_id),
+                None => {
+                    Wallet::new(network_id).map_err(|e| format!("Failed to generate wallet: {}", e))
+                }
+            };
+            let wallet = match wallet {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+
+            match output_format(&format) {
+                "json" => print_wallet_json(&wallet),
+                _ => print_wallet_text(&wallet),
+            }
+            if qr {
+                print_qr(&wallet.address());
+            }
+        }
+
+        Commands::Import {
+            secret,
+            from,
+            file,
+            network,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let wallet = match from {
+                Some(from) => import_export(&from, file, network_id),
+                None => resolve_wallet(&secret, network_id, &keys),
+            };
+            match wallet {
+                Ok(wallet) => match output_format(&format) {
+                    "json" => print_wallet_json(&wallet),
+                    _ => print_wallet_text(&wallet),
+                },
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Validate { address } => match mina_web_wallet_core::address_to_pubkey(&address) {
+            Ok(_) if json_output() => {
+                println!(
+                    "{}",
+                    serde_json::json!({ "address": address, "valid": true })
+                )
+            }
+            Ok(_) => println!("Address is valid: {}", address),
+            Err(e) => fail(format!("Invalid address: {:?}", e)),
+        },
+
+        Commands::Address { secret, qr } => {
+            // Default to mainnet for address derivation
+            let address = match resolve_wallet(&secret, NetworkId::MAINNET, &keys) {
+                Ok(wallet) => wallet.address(),
+                Err(e) => fail(e),
+            };
+            if json_output() {
+                println!("{}", serde_json::json!({ "address": address }));
+            } else {
+                println!("{}", address);
+            }
+            if qr {
+                print_qr(&address);
+            }
+        }
+
+        Commands::Vanity {
+            prefix,
+            suffix,
+            threads,
+            label,
+            print,
+            network,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let (pattern, position) = match (prefix, suffix) {
+                (Some(p), _) => (p, VanityPosition::Prefix),
+                (None, Some(s)) => (s, VanityPosition::Suffix),
+                (None, None) => unreachable!("clap requires --prefix or --suffix"),
+            };
+
+            let expected = match vanity::normalize_pattern(&pattern, position) {
+                Ok(p) => vanity::expected_attempts(&p),
+                Err(e) => fail(e),
+            };
+            // Ask before grinding, so that the search does not end on a prompt
+            let passphrase = if print {
+                None
+            } else {
+                match read_passphrase("Passphrase for the new key: ", true) {
+                    Ok(p) if p.is_empty() => fail("the passphrase must not be empty"),
+                    Ok(p) => Some(p),
+                    Err(e) => fail(e),
+                }
+            };
+
+            let threads = threads
+                .unwrap_or_else(|| {
+                    std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+                })
+                .max(1);
+            let pool = match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => pool,
+                Err(e) => fail(e),
+            };
+            eprintln!(
+                "Searching on {} threads, about {:.0} attempts expected...",
+                threads, expected
+            );
+
+            let result = grind_vanity(&pool, threads, &pattern, position, &network_id, expected);
+            let wallet = match result {
+                Ok(wallet) => wallet,
+                Err(e) => fail(e),
+            };
+
+            let Some(passphrase) = passphrase else {
+                match output_format(&format) {
+                    "json" => print_wallet_json(&wallet),
+                    _ => print_wallet_text(&wallet),
+                }
+                return;
+            };
+            let mut keystore = load_keystore(&keys);
+            let entry = match keystore.add(&wallet, &passphrase, label.as_deref(), unix_now()) {
+                Ok(entry) => entry.clone(),
+                Err(e) => fail(e),
+            };
+            save_keystore(&mut keys, &keystore);
+            print_key_added(&entry, &keys, false, output_format(&format));
+        }
+
+        Commands::Encrypt { to, message } => {
+            match encryption::encrypt_to_address(&to, message.as_bytes()) {
+                Ok(ciphertext) if json_output() => {
+                    println!("{}", serde_json::json!({ "ciphertext": ciphertext }))
+                }
+                Ok(ciphertext) => println!("{}", ciphertext),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Decrypt { secret, ciphertext } => {
+            let wallet = match resolve_wallet(&secret, NetworkId::MAINNET, &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+
+            let ciphertext = match read_input(ciphertext, None) {
+                Ok(c) => c,
+                Err(e) => fail(e),
+            };
+
+            match wallet.decrypt(ciphertext.trim()) {
+                Ok(plaintext) if json_output() => println!(
+                    "{}",
+                    serde_json::json!({ "plaintext": String::from_utf8_lossy(&plaintext) })
+                ),
+                Ok(plaintext) => println!("{}", String::from_utf8_lossy(&plaintext)),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::SignMessage {
+            secret,
+            message,
+            file,
+            qr,
+            network,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let wallet = match resolve_wallet(&secret, network_id, &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+
+            let message = match read_input(message, file) {
+                Ok(m) => m,
+                Err(e) => fail(e),
+            };
+
+            let signed = wallet.sign_message(&message);
+            let signature = match signed.signature.to_base58() {
+                Ok(s) => s,
+                Err(e) => fail(e),
+            };
+            match output_format(&format) {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "publicKey": signed.public_key,
+                        "data": signed.data,
+                        "signature": signed.signature,
+                        "signatureBase58": signature,
+                    }))
+                    .unwrap()
+                ),
+                _ => {
+                    println!("Address:   {}", signed.public_key);
+                    println!("Signature: {}", signature);
+                }
+            }
+            if qr {
+                print_qr(&serde_json::to_string(&signed).unwrap());
+            }
+        }
+
+        Commands::VerifyMessage {
+            message,
+            file,
+            address,
+            signature,
+            network,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let message = match read_input(message, file) {
+                Ok(m) => m,
+                Err(e) => fail(e),
+            };
+
+            let signature = match SignatureJson::from_base58(&signature) {
+                Ok(s) => s,
+                Err(e) => fail(e),
+            };
+            let signed = SignedMessage {
+                public_key: address,
+                data: message,
+                signature,
+            };
+            match verify_message(&signed, network_id) {
+                Ok(valid) if json_output() => {
+                    println!("{}", serde_json::json!({ "valid": valid }));
+                    if !valid {
+                        std::process::exit(EXIT_ERROR);
+                    }
+                }
+                Ok(true) => println!("Valid: the message was signed by {}", signed.public_key),
+                Ok(false) => {
+                    println!("Invalid: the signature does not match the message and address");
+                    std::process::exit(EXIT_ERROR);
+                }
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::SignOracle {
+            secret,
+            data,
+            file,
+            network,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let wallet = match resolve_wallet(&secret, network_id, &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+
+            let data: serde_json::Value = match read_input(data, file).and_then(|input| {
+                serde_json::from_str(&input).map_err(|e| format!("Invalid JSON: {}", e))
+            }) {
+                Ok(d) => d,
+                Err(e) => fail(e),
+            };
+
+            match wallet.sign_oracle_data(&data) {
+                Ok(response) => println!("{}", serde_json::to_string_pretty(&response).unwrap()),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::SignFields {
+            secret,
+            fields,
+            file,
+            network,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let wallet = match resolve_wallet(&secret, network_id, &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+
+            let fields = match read_input(fields, file).and_then(|input| parse_fields(&input)) {
+                Ok(f) => f,
+                Err(e) => fail(e),
+            };
+
+            let signature = SignatureJson::from(&wallet.sign_fields(&fields));
+            match signature.to_base58() {
+                Ok(signature) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "data": fields.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+                        "signature": signature,
+                        "publicKey": wallet.address(),
+                    }))
+                    .unwrap()
+                ),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Inspect { json, file, format } => {
+            let inspection = match read_input(json, file)
+                .and_then(|json| inspect::inspect(json.trim()).map_err(|e| e.to_string()))
+            {
+                Ok(inspection) => inspection,
+                Err(e) => fail(e),
+            };
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&inspection).unwrap()),
+                _ => {
+                    let tx = &inspection.signed_transaction.data;
+                    let (kind, receiver) = match tx.kind() {
+                        TransactionKind::Payment => ("payment", "To:"),
+                        TransactionKind::Delegation => ("delegation", "Delegate:"),
+                    };
+                    let shape = match inspection.format {
+                        TransactionFormat::Signer => "mina-signer / Auro",
+                        TransactionFormat::Daemon => "daemon GraphQL",
+                        TransactionFormat::Envelope => "envelope",
+                    };
+                    println!("Format:      {}", shape);
+                    match &inspection.network {
+                        Some(network) => println!("Network:     {} (signature valid)", network),
+                        None => println!("Network:     none, SIGNATURE INVALID on both networks"),
+                    }
+                    println!("Type:        {}", kind);
+                    println!("From:        {}", tx.from);
+                    println!("{:<12} {}", receiver, tx.to);
+                    if let Some(amount) = tx.amount {
+                        println!("Amount:      {} MINA", amount);
+                    }
+                    println!("Fee:         {} MINA", tx.fee);
+                    println!("Nonce:       {}", tx.nonce);
+                    if !tx.memo.is_empty() {
+                        println!("Memo:        {}", tx.memo);
+                    }
+                    if tx.valid_until != u32::MAX {
+                        println!("Valid until: slot {}", tx.valid_until);
+                    }
+                }
+            }
+            if inspection.network.is_none() {
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        Commands::Convert { command } => match command {
+            ConvertCommands::Secret { secret_key, stdin } => {
+                let input = match (secret_key, stdin) {
+                    (Some(secret_key), _) => {
+                        warn_secret_in_argv();
+                        Ok(Zeroizing::new(secret_key))
+                    }
+                    (None, true) => {
+                        let mut line = Zeroizing::new(String::new());
+                        std::io::stdin()
+                            .read_line(&mut line)
+                            .map(|_| line)
+                            .map_err(|e| format!("Failed to read stdin: {}", e))
+                    }
+                    (None, false) => prompt_secret("Secret key: "),
+                };
+                match input.and_then(|input| {
+                    convert::convert_secret_key(input.trim()).map_err(|e| e.to_string())
+                }) {
+                    Ok(converted) => print_converted(&converted),
+                    Err(e) => fail(e),
+                }
+            }
+            ConvertCommands::PublicKey { value } => match convert::convert_public_key(&value) {
+                Ok(converted) => print_converted(&converted),
+                Err(e) => fail(e),
+            },
+            ConvertCommands::Field { value } => match convert::convert_field(&value) {
+                Ok(converted) => print_converted(&converted),
+                Err(e) => fail(e),
+            },
+            ConvertCommands::Memo { value } => match convert::convert_memo(&value) {
+                Ok(converted) => print_converted(&converted),
+                Err(e) => fail(e),
+            },
+        },
+
+        Commands::Qr {
+            data,
+            format,
+            output,
+        } => {
+            let format = match format.parse::<QrFormat>() {
+                Ok(f) => f,
+                Err(e) => fail(e),
+            };
+            let image = match qr::generate_qr(&data, format) {
+                Ok(image) => image,
+                Err(e) => fail(e),
+            };
+            match output {
+                Some(path) => {
+                    if let Err(e) = std::fs::write(&path, image) {
+                        fail(format!("Failed to write {}: {}", path.display(), e));
+                    }
+                    if json_output() {
+                        println!("{}", serde_json::json!({ "output": path }));
+                    } else {
+                        eprintln!("Wrote {}", path.display());
+                    }
+                }
+                None if format == QrFormat::Svg && json_output() => println!(
+                    "{}",
+                    serde_json::json!({ "svg": String::from_utf8_lossy(&image) })
+                ),
+                None if format == QrFormat::Svg => {
+                    println!("{}", String::from_utf8_lossy(&image));
+                }
+                None => fail("--output is required for png"),
+            }
+        }
+
+        Commands::TestVectors {
+            count,
+            scheme,
+            seed,
+            network,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let scheme: SignatureScheme = match scheme.parse() {
+                Ok(s) => s,
+                Err(e) => fail(e),
+            };
+            match test_vectors::generate(&seed, count, scheme, network_id) {
+                Ok(vectors) => println!("{}", serde_json::to_string_pretty(&vectors).unwrap()),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Export {
+            secret,
+            paper: _,
+            network,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            match resolve_wallet(&secret, network_id, &keys) {
+                Ok(wallet) => {
+                    let paper = PaperWallet::new(&wallet);
+                    match output_format(&format) {
+                        "json" => println!("{}", serde_json::to_string_pretty(&paper).unwrap()),
+                        _ => print_paper_wallet_text(&paper),
+                    }
+                }
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Balance {
+            address,
+            network,
+            node,
+            backend,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+                fail(format!("Invalid address: {:?}", e));
+            }
+
+            let kind = match backend.parse::<BackendKind>() {
+                Ok(kind) => kind,
+                Err(e) => fail(e),
+            };
+            let endpoints = match (kind, node) {
+                (_, Some(node)) => parse_endpoints(&node),
+                (BackendKind::GraphQl, None) => default_endpoints(&network_id),
+                (BackendKind::Rosetta, None) => fail("--node is required with the rosetta backend"),
+            };
+
+            let client = Backend::with_endpoints(kind, endpoints, &network_id);
+            match block_on(client.get_balance(&address)) {
+                Ok(balance) => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(&balance).unwrap()),
+                    _ => {
+                        println!("Address: {}", address);
+                        println!("Total:   {} MINA", balance.total);
+                        if let Some(liquid) = balance.liquid {
+                            println!("Liquid:  {} MINA", liquid);
+                        }
+                        if let Some(locked) = balance.locked {
+                            println!("Locked:  {} MINA", locked);
+                        }
+                    }
+                },
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Nonce {
+            address,
+            reset,
+            reserve,
+            network,
+            node,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let address = resolve_recipient(&storage, &address);
+            if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+                fail(format!("Invalid address: {:?}", e));
+            }
+
+            let mut nonces = load_nonces(&storage);
+            if reset {
+                nonces.reset(&address);
+                save_nonces(&mut storage, &nonces);
+                eprintln!("Forgot the nonces recorded for {}", address);
+            }
+            if let Some(next) = reserve {
+                nonces.set(&address, next);
+                save_nonces(&mut storage, &nonces);
+                eprintln!("Next nonce of {} set to {}", address, next);
+            }
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            let account = match block_on(client.account(&address)) {
+                Ok(Some(account)) => account,
+                Ok(None) => fail(network::NetworkError::AccountNotFound(address)),
+                Err(e) => fail(e),
+            };
+            let inferred = account.inferred_nonce.unwrap_or(account.nonce);
+            let local = nonces.peek(&address);
+            // What fetching the nonce for a payment would return
+            let next = local.map_or(inferred, |local| local.max(inferred));
+            match output_format(&format) {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "address": address,
+                        "chainNonce": account.nonce,
+                        "inferredNonce": inferred,
+                        "localNonce": local,
+                        "nextNonce": next,
+                    }))
+                    .unwrap()
+                ),
+                _ => {
+                    println!("Address:        {}", address);
+                    println!("Chain nonce:    {}", account.nonce);
+                    println!("Inferred nonce: {} (with the transaction pool)", inferred);
+                    match local {
+                        Some(local) => println!(
+                            "Local nonce:    {} (after transactions sent by this wallet)",
+                            local
+                        ),
+                        None => println!("Local nonce:    none"),
+                    }
+                    println!("Next nonce:     {}", next);
+                }
+            }
+        }
+
+        Commands::SignPayment {
+            secret,
+            mut payment,
+        } => {
+            payment.to = resolve_recipient(&storage, &payment.to);
+            match sign_payment(&secret, &payment, &storage, &keys) {
+                Ok(signed) => {
+                    println!("{}", serde_json::to_string_pretty(&signed).unwrap());
+                    if payment.qr {
+                        print_qr(&serde_json::to_string(&signed).unwrap());
+                    }
+                }
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Send {
+            secret,
+            mut payment,
+            broadcast,
+            wait,
+            confirmations,
+            queue,
+        } => {
+            payment.to = resolve_recipient(&storage, &payment.to);
+            let signed = match sign_payment(&secret, &payment, &storage, &keys) {
+                Ok(s) => s,
+                Err(e) => fail(e),
+            };
+
+            if !broadcast && !wait {
+                if queue {
+                    queue_transaction(&mut storage, signed);
+                    return;
+                }
+                println!("{}", serde_json::to_string_pretty(&signed).unwrap());
+                if payment.qr {
+                    print_qr(&serde_json::to_string(&signed).unwrap());
+                }
+                eprintln!("Not broadcast. Pass --broadcast to submit the payment.");
+                return;
+            }
+
+            // sign_payment already validated the network name
+            let network_id = parse_network(&payment.network).unwrap();
+            let client = graphql_client(payment.node.as_deref(), &network_id);
+            // When it queried the node, sign_payment already checked the chain
+            let checked = if payment.queries_node() {
+                Ok(())
+            } else {
+                check_chain_id(&client, &network_id, payment.force)
+            };
+            let result = checked.and_then(|()| block_on(client.broadcast(&signed)));
+            match result {
+                Ok(hash) => {
+                    record_nonce(&mut storage, &signed.data.from, signed.data.nonce);
+                    if json_output() {
+                        println!("{}", serde_json::json!({ "hash": hash }));
+                    } else {
+                        println!("Transaction hash: {}", hash);
+                    }
+                    if wait {
+                        wait_for_payment(client, &hash, confirmations);
+                    }
+                }
+                Err(e) if queue && e.is_transient() => {
+                    eprintln!("Node unreachable ({}), queueing the payment", e);
+                    queue_transaction(&mut storage, signed);
+                }
+                Err(e @ network::NetworkError::ChainMismatch { .. }) => fail(chain_id_error(e)),
+                Err(e) => fail(format!("Failed to broadcast payment: {}", e)),
+            }
+        }
+
+        Commands::Batch {
+            secret,
+            nonce,
+            fee,
+            broadcast,
+            network,
+            node,
+            force,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let wallet = match resolve_wallet(&secret, network_id.clone(), &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+            let default_fee: Amount = match fee.parse() {
+                Ok(f) => f,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            if (nonce.is_none() || broadcast)
+                && let Err(e) = check_chain_id(&client, &network_id, force)
+            {
+                fail(chain_id_error(e));
+            }
+            let mut next_nonce = match nonce {
+                Some(n) => n,
+                None => match next_nonce(&storage, &client, &wallet.address()) {
+                    Ok(n) => n,
+                    Err(e) => fail(e),
+                },
+            };
+
+            let mut failed = false;
+            for (index, line) in std::io::stdin().lines().enumerate() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(e) => fail(format!("Failed to read stdin: {}", e)),
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let number = index + 1;
+                // A rejected request does not use up its nonce
+                let signed = match sign_request(&wallet, &line, default_fee, next_nonce) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        failed = true;
+                        println!(
+                            "{}",
+                            serde_json::json!({
+                                "line": number,
+                                "error": { "code": EXIT_ERROR, "message": e },
+                            })
+                        );
+                        continue;
+                    }
+                };
+                next_nonce += 1;
+                if let Err(e) = audit_signature(&audit_log_path(&storage), &signed) {
+                    fail(e);
+                }
+
+                if !broadcast {
+                    println!(
+                        "{}",
+                        serde_json::json!({ "line": number, "nonce": signed.data.nonce, "signed": signed })
+                    );
+                    continue;
+                }
+                match block_on(client.broadcast(&signed)) {
+                    Ok(hash) => {
+                        record_nonce(&mut storage, &signed.data.from, signed.data.nonce);
+                        println!(
+                            "{}",
+                            serde_json::json!({ "line": number, "nonce": signed.data.nonce, "hash": hash })
+                        );
+                    }
+                    // Later payments would wait on this nonce: stop here
+                    Err(e) => {
+                        println!(
+                            "{}",
+                            serde_json::json!({
+                                "line": number,
+                                "nonce": signed.data.nonce,
+                                "error": { "code": EXIT_ERROR, "message": e.to_string() },
+                            })
+                        );
+                        std::process::exit(EXIT_ERROR);
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        Commands::SendBatch {
+            file,
+            secret,
+            fee,
+            progress,
+            network,
+            node,
+            force,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let wallet = match resolve_wallet(&secret, network_id.clone(), &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+            let default_fee: Amount = match fee.parse() {
+                Ok(f) => f,
+                Err(e) => fail(e),
+            };
+            let rows = match read_batch(&file, &wallet.address(), default_fee, &storage) {
+                Ok(rows) => rows,
+                Err(e) => fail(e),
+            };
+            let progress_path = progress.unwrap_or_else(|| {
+                let mut name = file.clone().into_os_string();
+                name.push(".progress.json");
+                name.into()
+            });
+            let mut progress = match load_batch_progress(&progress_path) {
+                Ok(p) => p,
+                Err(e) => fail(e),
+            };
+
+            let mut total = Amount::ZERO;
+            let mut pending = 0;
+            for (index, row) in rows.iter().enumerate() {
+                match progress.status(index + 1, row) {
+                    RowStatus::Pending => {
+                        pending += 1;
+                        total = match total
+                            .checked_add(row.amount)
+                            .and_then(|t| t.checked_add(row.fee))
+                        {
+                            Some(t) => t,
+                            None => fail("The total of the batch is out of range"),
+                        };
+                    }
+                    RowStatus::Sent(_) => {}
+                    RowStatus::Changed(sent) => fail(format!(
+                        "Row {} was edited after it was sent in {}: restore it, or start a new batch",
+                        index + 1,
+                        sent.hash
+                    )),
+                }
+            }
+            if pending == 0 {
+                eprintln!("All {} payments were already sent", rows.len());
+            } else {
+                eprintln!(
+                    "Sending {} of {} payments: {} MINA with fees, from {}",
+                    pending,
+                    rows.len(),
+                    total,
+                    wallet.address()
+                );
+            }
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            if pending > 0
+                && let Err(e) = check_chain_id(&client, &network_id, force)
+            {
+                fail(chain_id_error(e));
+            }
+            let mut nonce = None;
+            for (index, row) in rows.iter().enumerate() {
+                let number = index + 1;
+                if let RowStatus::Sent(sent) = progress.status(number, row) {
+                    print_batch_row(number, row, "already sent", sent.nonce, &sent.hash);
+                    continue;
+                }
+                let next = match nonce {
+                    Some(n) => n,
+                    None => match next_nonce(&storage, &client, &wallet.address()) {
+                        Ok(n) => n,
+                        Err(e) => fail(e),
+                    },
+                };
+                let signed = match wallet.sign_transaction(&row.payment(&wallet.address(), next)) {
+                    Ok(s) => s,
+                    Err(e) => fail(format!("Row {}: {}", number, e)),
+                };
+                if let Err(e) = audit_signature(&audit_log_path(&storage), &signed) {
+                    fail(e);
+                }
+                let hash = match block_on(client.broadcast(&signed)) {
+                    Ok(hash) => hash,
+                    Err(e) => fail(format!(
+                        "Row {} was not sent: {}. Run the same command again to resume from it",
+                        number, e
+                    )),
+                };
+                record_nonce(&mut storage, &signed.data.from, next);
+                progress.record(number, row, next, &hash, unix_now());
+                // Without this record, a second run would pay the row again
+                if let Err(e) = std::fs::write(&progress_path, progress.to_json() + "\n") {
+                    fail(format!(
+                        "Row {} was sent ({}), but {} could not be updated: {}. Remove the row before running again",
+                        number,
+                        hash,
+                        progress_path.display(),
+                        e
+                    ));
+                }
+                print_batch_row(number, row, "sent", next, &hash);
+                nonce = Some(next + 1);
+            }
+        }
+
+        Commands::Status {
+            hash,
+            confirmations,
+            wait,
+            network,
+            node,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let tracker = TxTracker::new(graphql_client(node.as_deref(), &network_id))
+                .with_confirmations(confirmations);
+            let result = if wait {
+                block_on(tracker.track(&hash, tokio::time::sleep, |status| {
+                    print_tx_status(status, output_format(&format))
+                }))
+            } else {
+                block_on(tracker.status(&hash))
+                    .inspect(|status| print_tx_status(status, output_format(&format)))
+            };
+
+            match result {
+                Ok(TxStatus::Unknown) => std::process::exit(EXIT_NOT_FOUND),
+                Ok(_) => {}
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Pending {
+            address,
+            network,
+            node,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            let report = match block_on(client.pending_transactions(&address)) {
+                Ok(r) => r,
+                Err(e) => fail(format!("Failed to fetch pending transactions: {}", e)),
+            };
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+                _ => {
+                    println!("Ledger nonce: {}", report.ledger_nonce);
+                    if report.transactions.is_empty() {
+                        println!("No pending transactions");
+                    }
+                    for tx in &report.transactions {
+                        println!(
+                            "nonce {:>6}  {:<16}  fee {:>12} MINA  {}",
+                            tx.nonce, tx.kind, tx.fee, tx.hash
+                        );
+                    }
+                    for issue in &report.issues {
+                        println!("WARNING: {}", issue.suggestion());
+                    }
+                }
+            }
+        }
+
+        Commands::History {
+            address,
+            limit,
+            before,
+            archive,
+            network,
+            format,
+            export,
+            price_source,
+            currency,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let endpoint = match archive
+                .as_deref()
+                .or_else(|| archive::default_archive_endpoint(&network_id))
+            {
+                Some(e) => e.to_string(),
+                None => fail(format!(
+                    "no default archive indexer for {}; pass --archive",
+                    network
+                )),
+            };
+
+            let client = ArchiveClient::with_endpoints(parse_endpoints(&endpoint));
+            if let Some(export) = export {
+                if !matches!(export.as_str(), "csv" | "ofx") {
+                    fail(format!(
+                        "Unknown export format '{}': expected csv or ofx",
+                        export
+                    ));
+                }
+                let entries = match block_on(full_history(&client, &address, limit, before)) {
+                    Ok(entries) => entries,
+                    Err(e) => fail(e),
+                };
+                let prices = match price_source {
+                    Some(source) => {
+                        let api = match source.as_str() {
+                            "coingecko" => accounting::DEFAULT_PRICE_API,
+                            url => url,
+                        };
+                        let from = entries.iter().map(|e| e.timestamp).min().unwrap_or(0);
+                        let to = entries.iter().map(|e| e.timestamp).max().unwrap_or(0);
+                        match block_on(accounting::fetch_price_history(api, &currency, from, to)) {
+                            Ok(prices) => Some(prices),
+                            Err(e) => fail(format!("Failed to fetch prices: {}", e)),
+                        }
+                    }
+                    None => None,
+                };
+                let rows = accounting::rows(&entries, prices.as_ref());
+                if rows
+                    .iter()
+                    .any(|row| prices.is_some() && row.price.is_none())
+                {
+                    eprintln!("Warning: some transactions have no price within two days");
+                }
+                if export == "ofx" {
+                    print!("{}", accounting::to_ofx(&address, &rows));
+                } else if let Err(e) = print_accounting_csv(&rows, &currency) {
+                    fail(format!("Failed to write CSV: {}", e));
+                }
+                eprintln!("Exported {} transactions", rows.len());
+                return;
+            }
+            let page = match block_on(client.history(&address, limit, before)) {
+                Ok(p) => p,
+                Err(e) => fail(e),
+            };
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&page).unwrap()),
+                "csv" => {
+                    if let Err(e) = print_history_csv(&page) {
+                        fail(format!("Failed to write CSV: {}", e));
+                    }
+                }
+                _ => print_history_text(&page),
+            }
+            if let Some(cursor) = page.next_cursor
+                && format != "json"
+            {
+                eprintln!("More transactions: --before {}", cursor);
+            }
+        }
+
+        Commands::Watch {
+            address,
+            interval,
+            webhook,
+            webhook_secret_file,
+            confirmations,
+            network,
+            node,
+            format,
+        } => {
+            if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+                fail(format!("Invalid address: {:?}", e));
+            }
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let webhooks = if webhook.is_empty() {
+                Vec::new()
+            } else {
+                let secret = match webhook_secret(webhook_secret_file.as_deref()) {
+                    Ok(s) => s,
+                    Err(e) => fail(e),
+                };
+                webhook
+                    .iter()
+                    .map(|url| {
+                        if !url.starts_with("https://") && !url.starts_with("http://") {
+                            fail(format!(
+                                "Invalid webhook URL '{}': expected http(s)://",
+                                url
+                            ));
+                        }
+                        Webhook::new(url, secret.as_bytes())
+                    })
+                    .collect()
+            };
+            let client = graphql_client(node.as_deref(), &network_id);
+            eprintln!("Watching {} on {}", address, client.endpoint());
+            let tracker = TxTracker::new(client.clone()).with_confirmations(confirmations);
+            let mut watcher = AccountWatcher::new(client, &address)
+                .with_poll_interval(std::time::Duration::from_secs(interval.max(1)));
+            let format = output_format(&format);
+            // Transactions waiting for transaction.confirmed, by hash, with
+            // the number of polls they were unknown to the node
+            let mut unconfirmed: std::collections::BTreeMap<String, (WatchEvent, u32)> =
+                std::collections::BTreeMap::new();
+            block_on(async {
+                loop {
+                    match watcher.poll().await {
+                        Ok(events) => {
+                            for event in &events {
+                                print_watch_event(event, format);
+                                if webhooks.is_empty() {
+                                    continue;
+                                }
+                                if let Some(payload) =
+                                    WebhookPayload::payment_received(&address, event)
+                                {
+                                    notify_webhooks(&webhooks, &payload).await;
+                                }
+                                unconfirmed.insert(event.command.hash.clone(), (event.clone(), 0));
+                            }
+                        }
+                        // Keep watching through node outages
+                        Err(e) if e.is_transient() => eprintln!("Warning: {}", e),
+                        Err(e) => fail(format!("Failed to watch {}: {}", address, e)),
+                    }
+                    let hashes: Vec<String> = unconfirmed.keys().cloned().collect();
+                    for hash in hashes {
+                        let status = match tracker.status(&hash).await {
+                            Ok(status) => status,
+                            Err(e) => {
+                                eprintln!("Warning: {}", e);
+                                continue;
+                            }
+                        };
+                        let Some((event, unknown_polls)) = unconfirmed.get_mut(&hash) else {
+                            continue;
+                        };
+                        if let Some(payload) =
+                            WebhookPayload::transaction_confirmed(&address, event, &status)
+                        {
+                            notify_webhooks(&webhooks, &payload).await;
+                            unconfirmed.remove(&hash);
+                        } else if status == TxStatus::Unknown {
+                            *unknown_polls += 1;
+                            if *unknown_polls >= WEBHOOK_MAX_UNKNOWN_POLLS {
+                                eprintln!(
+                                    "Warning: {} left the chain, no transaction.confirmed will be sent",
+                                    hash
+                                );
+                                unconfirmed.remove(&hash);
+                            }
+                        } else {
+                            *unknown_polls = 0;
+                        }
+                    }
+                    tokio::time::sleep(watcher.poll_interval()).await;
+                }
+            })
+        }
+
+        Commands::Delegate {
+            secret,
+            to,
+            fee,
+            nonce,
+            pool_directory,
+            yes,
+            network,
+            node,
+            force,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let wallet = match resolve_wallet(&secret, network_id.clone(), &keys) {
+                Ok(w) => w,
+                Err(e) => fail(e),
+            };
+            let to = resolve_recipient(&storage, &to);
+            if let Err(e) = mina_web_wallet_core::address_to_pubkey(&to) {
+                fail(format!("Invalid delegate address: {:?}", e));
+            }
+            let fee: Amount = match fee.parse() {
+                Ok(f) => f,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            if let Err(e) = check_chain_id(&client, &network_id, force) {
+                fail(chain_id_error(e));
+            }
+            let (pool, warnings) = delegate_warnings(&client, &to, pool_directory.as_deref());
+            if let Some(pool) = &pool {
+                eprintln!(
+                    "Pool:     {}{}",
+                    pool.name,
+                    pool.website
+                        .as_deref()
+                        .map(|w| format!(" ({})", w))
+                        .unwrap_or_default()
+                );
+            }
+            for warning in &warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            if !warnings.is_empty() && !yes {
+                fail("the delegate may not produce blocks; pass --yes to delegate anyway");
+            }
+
+            let nonce = match nonce {
+                Some(n) => n,
+                None => match next_nonce(&storage, &client, &wallet.address()) {
+                    Ok(n) => n,
+                    Err(e) => fail(e),
+                },
+            };
+            let delegation = Transaction::delegation(&wallet.address(), &to, fee, nonce);
+            eprintln!("From:     {}", wallet.address());
+            eprintln!("Delegate: {}", to);
+            eprintln!("Fee:      {} MINA", fee);
+            eprintln!("Nonce:    {}", nonce);
+            let signed = match wallet.sign_transaction(&delegation) {
+                Ok(s) => s,
+                Err(e) => fail(e),
+            };
+            if let Err(e) = audit_signature(&audit_log_path(&storage), &signed) {
+                fail(e);
+            }
+            let hash = match block_on(client.broadcast(&signed)) {
+                Ok(hash) => hash,
+                Err(e) => fail(format!("Failed to broadcast delegation: {}", e)),
+            };
+            record_nonce(&mut storage, &signed.data.from, signed.data.nonce);
+
+            // Counted from the current slot; inclusion may slip into the next epoch
+            let consensus = ConsensusConstants::for_network(&network_id);
+            let effective = consensus
+                .slot_at(unix_now())
+                .map(|slot| consensus.delegation_effective_epoch(slot));
+            if json_output() {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "hash": hash,
+                        "delegate": to,
+                        "pool": pool,
+                        "warnings": warnings,
+                        "effectiveEpoch": effective,
+                        "effectiveAt": effective
+                            .map(|e| consensus.slot_start(consensus.epoch_start_slot(e))),
+                    })
+                );
+                return;
+            }
+            println!("Transaction hash: {}", hash);
+            if let Some(epoch) = effective {
+                println!(
+                    "The delegation takes effect in epoch {}, from {}, once it is in a block of the current epoch",
+                    epoch,
+                    time::to_rfc3339(consensus.slot_start(consensus.epoch_start_slot(epoch)))
+                );
+            }
+        }
+
+        Commands::StakingInfo {
+            address,
+            network,
+            node,
+            archive,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            let info = match block_on(client.staking_info(&address)) {
+                Ok(i) => i,
+                Err(e) => fail(e),
+            };
+
+            // The staking ledger is only available from an indexer
+            let entry = match archive
+                .as_deref()
+                .or_else(|| archive::default_archive_endpoint(&network_id))
+            {
+                Some(endpoint) => {
+                    let indexer = ArchiveClient::with_endpoints(parse_endpoints(endpoint));
+                    match block_on(indexer.staking_ledger_entry(&address, info.epoch)) {
+                        Ok(e) => e,
+                        Err(e) => {
+                            eprintln!("Warning: could not fetch the staking ledger: {}", e);
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            if output_format(&format) == "json" {
+                let active = entry
+                    .as_ref()
+                    .map(|e| staking::delegation_is_active(&info, e));
+                let json = serde_json::json!({
+                    "info": info,
+                    "stakingLedger": entry,
+                    "delegationActive": active,
+                });
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                return;
+            }
+
+            println!("Address:        {}", info.address);
+            println!(
+                "Delegate:       {}",
+                info.delegate.as_deref().unwrap_or("(none)")
+            );
+            println!("Epoch:          {}", info.epoch);
+            println!("Staking ledger: {}", info.staking_ledger_hash);
+            println!(
+                "Delegators:     {} ({} MINA)",
+                info.delegators.len(),
+                info.delegated_balance().unwrap_or_default()
+            );
+            match entry {
+                Some(entry) => {
+                    println!(
+                        "Staking delegate for epoch {}: {}",
+                        entry.epoch, entry.delegate
+                    );
+                    if staking::delegation_is_active(&info, &entry) {
+                        println!("Delegation is active in the current epoch.");
+                    } else {
+                        println!(
+                            "Delegation is not active yet; it takes effect once it is in a staking ledger."
+                        );
+                    }
+                }
+                None => println!("Staking ledger entry: not available"),
+            }
+        }
+
+        Commands::RewardsEstimate {
+            stake,
+            fee,
+            locked,
+            supercharged,
+            total_stake,
+            network,
+            node,
+            format,
+        } => {
+            let parsed = stake
+                .parse::<Amount>()
+                .map_err(|e| format!("Invalid stake: {}", e))
+                .and_then(|stake| {
+                    let fee = rewards::parse_percent(&fee).map_err(|e| e.to_string())?;
+                    Ok((stake, fee))
+                });
+            let (stake, fee_bps) = match parsed {
+                Ok(p) => p,
+                Err(e) => fail(e),
+            };
+
+            let total_stake = match total_stake {
+                Some(total) => match total.parse::<Amount>() {
+                    Ok(total) => total,
+                    Err(e) => fail(format!("Invalid total stake: {}", e)),
+                },
+                None => {
+                    let network_id = match parse_network(&network) {
+                        Ok(n) => n,
+                        Err(e) => fail(e),
+                    };
+                    let client = graphql_client(node.as_deref(), &network_id);
+                    match block_on(client.total_stake()) {
+                        Ok(total) => total,
+                        Err(e) => fail(format!("Failed to fetch the total stake: {}", e)),
+                    }
+                }
+            };
+
+            let params = RewardParameters::new(total_stake).with_supercharged(supercharged);
+            let estimate = match rewards::estimate(stake, fee_bps, locked, &params) {
+                Ok(e) => e,
+                Err(e) => fail(e),
+            };
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&estimate).unwrap()),
+                _ => {
+                    println!("Stake:            {} MINA", estimate.stake);
+                    println!("Total stake:      {} MINA", total_stake);
+                    println!("Delegate fee:     {}%", f64::from(fee_bps) / 100.0);
+                    println!("Coinbase:         {} MINA", estimate.coinbase);
+                    println!(
+                        "Blocks per epoch: {:.4}",
+                        estimate.expected_blocks_per_epoch
+                    );
+                    println!("Per epoch:        {} MINA", estimate.per_epoch);
+                    println!("Per year:         {} MINA", estimate.per_year);
+                    println!(
+                        "Yearly yield:     {:.2}%",
+                        estimate.yearly_yield_bps as f64 / 100.0
+                    );
+                    println!("Estimates assume a delegate with full uptime; actual rewards vary.");
+                }
+            }
+        }
+
+        Commands::ZkappState {
+            address,
+            network,
+            node,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            let state = match block_on(client.zkapp_state(&address)) {
+                Ok(s) => s,
+                Err(e) => fail(format!("Failed to fetch zkApp state: {}", e)),
+            };
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&state).unwrap()),
+                _ => {
+                    println!("Address: {}", state.address);
+                    if !state.is_zkapp() {
+                        println!("No zkApp is deployed to this account");
+                        return;
+                    }
+                    println!("zkApp URI: {}", state.zkapp_uri.as_deref().unwrap_or("-"));
+                    println!(
+                        "Verification key hash: {}",
+                        state.verification_key_hash.as_deref().unwrap_or("-")
+                    );
+                    println!("Proved state: {}", state.proved_state);
+                    println!("App state:");
+                    for (i, field) in state.app_state.iter().enumerate() {
+                        println!("  [{}] {} ({})", i, field.decimal, field.hex);
+                    }
+                    if let Some(permissions) = &state.permissions {
+                        println!("Permissions:");
+                        let value = serde_json::to_value(permissions).unwrap();
+                        for (name, auth) in value.as_object().into_iter().flatten() {
+                            match auth {
+                                serde_json::Value::String(auth) => println!("  {}: {}", name, auth),
+                                other => println!("  {}: {}", name, other),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::VerificationKey {
+            data,
+            file,
+            address,
+            network,
+            node,
+            format,
+        } => {
+            let vk = match read_input(data, file)
+                .and_then(|input| VerificationKey::from_base64(&input).map_err(|e| e.to_string()))
+            {
+                Ok(vk) => vk,
+                Err(e) => fail(e),
+            };
+            let hash = vk.hash().to_string();
+
+            let deployed_hash = match address {
+                Some(address) => {
+                    let network_id = match parse_network(&network) {
+                        Ok(n) => n,
+                        Err(e) => fail(e),
+                    };
+                    let client = graphql_client(node.as_deref(), &network_id);
+                    match block_on(client.zkapp_state(&address)) {
+                        Ok(state) => match state.verification_key_hash {
+                            Some(hash) => Some(hash),
+                            None => fail(format!("no zkApp is deployed to {}", address)),
+                        },
+                        Err(e) => fail(format!("Failed to fetch zkApp state: {}", e)),
+                    }
+                }
+                None => None,
+            };
+            let matches = deployed_hash.as_ref().map(|deployed| *deployed == hash);
+
+            match output_format(&format) {
+                "json" => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "hash": hash,
+                        "maxProofsVerified": vk.max_proofs_verified,
+                        "actualWrapDomainSize": vk.actual_wrap_domain_size,
+                        "deployedHash": deployed_hash,
+                        "matches": matches,
+                    }))
+                    .unwrap()
+                ),
+                _ => {
+                    println!("Hash:                {}", hash);
+                    println!("Max proofs verified: {}", vk.max_proofs_verified);
+                    if let Some(deployed) = &deployed_hash {
+                        println!("Deployed hash:       {}", deployed);
+                        println!(
+                            "{}",
+                            if matches == Some(true) {
+                                "The deployed verification key matches"
+                            } else {
+                                "MISMATCH: the deployed verification key is different"
+                            }
+                        );
+                    }
+                }
+            }
+            if matches == Some(false) {
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        Commands::Epoch {
+            slot,
+            at,
+            network,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let constants = ConsensusConstants::for_network(&network_id);
+
+            let now = unix_now();
+            let slot = match (slot, at) {
+                (Some(slot), _) => Some(slot),
+                (None, Some(at)) => match time::from_rfc3339(&at) {
+                    Some(timestamp) => constants.slot_at(timestamp),
+                    None => fail(format!(
+                        "invalid time '{}', expected YYYY-MM-DDTHH:MM:SSZ",
+                        at
+                    )),
+                },
+                (None, None) => constants.slot_at(now),
+            };
+            let Some(slot) = slot else {
+                fail(format!("the time is before the genesis of {}", network));
+            };
+            let info = constants.slot_info(slot);
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&info).unwrap()),
+                _ => {
+                    println!("Slot:          {}", info.slot);
+                    println!(
+                        "Epoch:         {} (slot {} of {})",
+                        info.epoch, info.slot_in_epoch, constants.slots_per_epoch
+                    );
+                    println!("Slot start:    {}", time::to_rfc3339(info.slot_start));
+                    println!("Epoch start:   {}", time::to_rfc3339(info.epoch_start));
+                    println!("Next epoch:    {}", time::to_rfc3339(info.next_epoch_start));
+                    if info.next_epoch_start > now {
+                        let minutes = (info.next_epoch_start - now) / 60;
+                        println!(
+                            "Next epoch in: {}d {}h {}m",
+                            minutes / 1440,
+                            (minutes % 1440) / 60,
+                            minutes % 60
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::NodeStatus {
+            network,
+            node,
+            format,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            let status = match block_on(client.node_status()) {
+                Ok(s) => s,
+                Err(e) => fail(format!("node {} is unreachable: {}", client.endpoint(), e)),
+            };
+
+            match output_format(&format) {
+                "json" => println!("{}", serde_json::to_string_pretty(&status).unwrap()),
+                _ => {
+                    println!("Endpoint:     {}", status.endpoint);
+                    println!("Sync status:  {}", status.sync_status);
+                    println!("Chain ID:     {}", status.chain_id);
+                    println!(
+                        "Block height: {} (network: {})",
+                        status.block_height, status.highest_block_received
+                    );
+                    println!("Latency:      {} ms", status.latency_ms);
+                    for warning in status.warnings() {
+                        println!("WARNING: {}", warning);
+                    }
+                }
+            }
+            if !status.is_healthy() {
+                std::process::exit(EXIT_ERROR);
+            }
+        }
+
+        Commands::Fee {
+            suggest,
+            network,
+            node,
+            format,
+        } => {
+            if !suggest {
+                match output_format(&format) {
+                    "json" => println!(
+                        "{}",
+                        serde_json::json!({
+                            "minimum": transaction::MINIMUM_FEE,
+                            "default": transaction::DEFAULT_FEE,
+                        })
+                    ),
+                    _ => {
+                        println!("Minimum fee: {} MINA", transaction::MINIMUM_FEE);
+                        println!("Default fee: {} MINA", transaction::DEFAULT_FEE);
+                        println!("Use --suggest to base the fee on the transaction pool");
+                    }
+                }
+                return;
+            }
+
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+
+            let client = graphql_client(node.as_deref(), &network_id);
+            match block_on(client.suggest_fee()) {
+                Ok(suggestion) => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(&suggestion).unwrap()),
+                    _ => {
+                        println!("Based on {} pending transactions:", suggestion.pool_size);
+                        println!("  Slow:     {} MINA", suggestion.slow);
+                        println!("  Standard: {} MINA", suggestion.standard);
+                        println!("  Fast:     {} MINA", suggestion.fast);
+                    }
+                },
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Tx { command } => match command {
+            TxCommands::Create {
+                from,
+                to,
+                amount,
+                fee,
+                nonce,
+                auto_nonce: _,
+                memo,
+                valid_until,
+                valid_for,
+                network,
+                node,
+                force,
+                output,
+                qr_ur,
+            } => {
+                let network_id = match parse_network(&network) {
+                    Ok(n) => n,
+                    Err(e) => fail(e),
+                };
+                let amount: Amount = match amount.parse() {
+                    Ok(a) => a,
+                    Err(e) => fail(e),
+                };
+                let fee: Amount = match fee.parse() {
+                    Ok(f) => f,
+                    Err(e) => fail(e),
+                };
+                let nonce = match nonce {
+                    Some(n) => n,
+                    None => {
+                        let client = graphql_client(node.as_deref(), &network_id);
+                        if let Err(e) = check_chain_id(&client, &network_id, force) {
+                            fail(chain_id_error(e));
+                        }
+                        match next_nonce(&storage, &client, &from) {
+                            Ok(n) => {
+                                eprintln!("Using nonce {} from {}", n, client.endpoint());
+                                n
+                            }
+                            Err(e) => fail(e),
+                        }
+                    }
+                };
+
+                let to = resolve_recipient(&storage, &to);
+                let mut payment =
+                    Transaction::payment(&from, &to, amount, fee, nonce).with_memo(&memo);
+                let valid_until = match valid_for {
+                    Some(minutes) => match ConsensusConstants::for_network(&network_id)
+                        .valid_until(unix_now(), minutes.saturating_mul(60))
+                    {
+                        Some(slot) => Some(slot),
+                        None => fail("--valid-for is out of range"),
+                    },
+                    None => valid_until,
+                };
+                if let Some(valid_until) = valid_until {
+                    payment = payment.with_valid_until(valid_until);
+                }
+                // Catch invalid addresses and memos now rather than on the offline machine
+                if let Err(e) = payment.validate() {
+                    fail(e);
+                }
+                print_payment_preview(&payment.preview(None));
+                let envelope = TransactionEnvelope::unsigned(payment, &network_id, unix_now());
+                write_envelope(&envelope, output.as_deref());
+                if qr_ur {
+                    print_qr_ur(&envelope.to_json());
+                }
+            }
+
+            TxCommands::Sign {
+                secret,
+                input,
+                output,
+                qr,
+                qr_ur,
+            } => {
+                let envelope = read_envelope(Some(input));
+                // from_json already checked the network name
+                let network_id = envelope.network_id().unwrap();
+                let current_slot = ConsensusConstants::for_network(&network_id).slot_at(unix_now());
+                let wallet = match resolve_wallet(&secret, network_id, &keys) {
+                    Ok(w) => w,
+                    Err(e) => fail(e),
+                };
+                // The offline machine cannot look anything up: show what is signed
+                let tx = envelope.transaction();
+                eprintln!("Network:    {}", envelope.network);
+                eprintln!("From:       {}", tx.from);
+                eprintln!("To:         {}", tx.to);
+                eprintln!("Nonce:      {}", tx.nonce);
+                if !tx.memo.is_empty() {
+                    eprintln!("Memo:       {}", tx.memo);
+                }
+                print_payment_preview(&tx.preview(None));
+                for warning in tx.warnings(None, current_slot) {
+                    eprintln!("Warning: {}", warning.message());
+                }
+                let signed = match envelope.sign(&wallet, unix_now()) {
+                    Ok(signed) => signed,
+                    Err(e) => fail(e),
+                };
+                let audited = signed
+                    .verified_transaction()
+                    .map_err(|e| e.to_string())
+                    .and_then(|tx| audit_signature(&audit_log_path(&storage), tx));
+                if let Err(e) = audited {
+                    fail(e);
+                }
+                write_envelope(&signed, output.as_deref());
+                if qr {
+                    print_qr(&serde_json::to_string(&signed).unwrap());
+                }
+                if qr_ur {
+                    print_qr_ur(&signed.to_json());
+                }
+            }
+
+            TxCommands::Preview {
+                input,
+                network,
+                online,
+                node,
+                format,
+            } => {
+                let json = match read_input(None, input) {
+                    Ok(json) => json,
+                    Err(e) => fail(e),
+                };
+                let decoded = match decode_transaction(&json, &network) {
+                    Ok(decoded) => decoded,
+                    Err(e) => fail(e),
+                };
+                let network_id = match parse_network(&decoded.network) {
+                    Ok(n) => n,
+                    Err(e) => fail(e),
+                };
+                let tx = &decoded.transaction;
+                let signature_valid = decoded.signed.as_ref().map(|signed| {
+                    transaction::verify_transaction(signed, network_id.clone()).unwrap_or(false)
+                });
+
+                let creates_account = if online && tx.amount.is_some() {
+                    let client = graphql_client(node.as_deref(), &network_id);
+                    match block_on(client.account_exists(&tx.to)) {
+                        Ok(exists) => Some(!exists),
+                        Err(e) => {
+                            eprintln!("Warning: could not look up the receiver account: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let constants = ConsensusConstants::for_network(&network_id);
+                let preview = tx.preview(creates_account);
+                let warnings = tx.warnings(creates_account, constants.slot_at(unix_now()));
+                // The transaction can be included until the end of its last slot
+                let expires = (tx.valid_until != u32::MAX).then(|| {
+                    time::to_rfc3339(
+                        constants.slot_start(tx.valid_until) + constants.slot_duration_secs,
+                    )
+                });
+
+                match output_format(&format) {
+                    "json" => {
+                        let warnings: Vec<serde_json::Value> = warnings
+                            .iter()
+                            .map(|w| {
+                                let mut value = serde_json::to_value(w).unwrap();
+                                value["message"] = w.message().into();
+                                value
+                            })
+                            .collect();
+                        let summary = serde_json::json!({
+                            "network": decoded.network,
+                            "kind": tx.kind(),
+                            "signed": decoded.signed.is_some(),
+                            "signatureValid": signature_valid,
+                            "transaction": tx,
+                            "preview": preview,
+                            "expiresAt": expires,
+                            "warnings": warnings,
+                        });
+                        println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+                    }
+                    _ => {
+                        let (kind, receiver) = match tx.kind() {
+                            transaction::TransactionKind::Payment => ("payment", "To:"),
+                            transaction::TransactionKind::Delegation => ("delegation", "Delegate:"),
+                        };
+                        let signature = match signature_valid {
+                            None => "unsigned",
+                            Some(true) => "signed, signature valid",
+                            Some(false) => "signed, SIGNATURE INVALID",
+                        };
+                        println!("Network:    {}", decoded.network);
+                        println!("Type:       {} ({})", kind, signature);
+                        println!("From:       {}", tx.from);
+                        println!("{:<11} {}", receiver, tx.to);
+                        if tx.amount.is_some() {
+                            println!("Amount:     {} MINA", preview.amount);
+                        }
+                        println!("Fee:        {} MINA", preview.fee);
+                        println!("Total cost: {} MINA", preview.total_cost);
+                        println!("Nonce:      {}", tx.nonce);
+                        if !tx.memo.is_empty() {
+                            println!("Memo:       {}", tx.memo);
+                        }
+                        match &expires {
+                            Some(at) => {
+                                println!("Expires:    after slot {} ({})", tx.valid_until, at)
+                            }
+                            None => println!("Expires:    never"),
+                        }
+                        for warning in &warnings {
+                            println!("Warning: {}", warning.message());
+                        }
+                    }
+                }
+            }
+
+            TxCommands::Broadcast {
+                input,
+                node,
+                force,
+                wait,
+                confirmations,
+            } => {
+                let envelope = read_envelope(input);
+                // from_json already checked the network name
+                let network_id = envelope.network_id().unwrap();
+                let signed = match envelope.verified_transaction() {
+                    Ok(signed) => signed,
+                    Err(e) => fail(e),
+                };
+                let client = graphql_client(node.as_deref(), &network_id);
+                if let Err(e) = check_chain_id(&client, &network_id, force) {
+                    fail(chain_id_error(e));
+                }
+                match block_on(client.broadcast(signed)) {
+                    Ok(hash) => {
+                        record_nonce(&mut storage, &signed.data.from, signed.data.nonce);
+                        if json_output() {
+                            println!("{}", serde_json::json!({ "hash": hash }));
+                        } else {
+                            println!("Transaction hash: {}", hash);
+                        }
+                        if wait {
+                            wait_for_payment(client, &hash, confirmations);
+                        }
+                    }
+                    Err(e) => fail(format!("Failed to broadcast payment: {}", e)),
+                }
+            }
+        },
+
+        Commands::Serve {
+            http,
+            access,
+            max_body_size,
+            tls_cert,
+            tls_key,
+            key,
+            network,
+            node,
+            force,
+            insecure,
+        } => {
+            let network_id = match parse_network(&network) {
+                Ok(n) => n,
+                Err(e) => fail(e),
+            };
+            let access = match access {
+                Some(path) => match access::AccessControl::load(&path) {
+                    Ok(a) => a,
+                    Err(e) => fail(e),
+                },
+                None => access::AccessControl::open(),
+            };
+            let tls = match (&tls_cert, &tls_key) {
+                (Some(cert), Some(key)) => match tls::server_config(cert, key) {
+                    Ok(config) => Some(config),
+                    Err(e) => fail(e),
+                },
+                _ => None,
+            };
+            // Checked before unlocking, so that no passphrase is asked in vain
+            if let Err(e) = access.check_exposure(&http, key.is_some(), tls.is_some(), insecure) {
+                fail(e);
+            }
+            let wallet = key.map(
+                |key| match unlock_key(Some(&key), network_id.clone(), &keys) {
+                    Ok(w) => w,
+                    Err(e) => fail(e),
+                },
+            );
+            if let Some(wallet) = &wallet {
+                eprintln!("Signing payments from {}", wallet.address());
+            }
+            let server = serve::Server {
+                client: graphql_client(node.as_deref(), &network_id),
+                network: network_id,
+                wallet,
+                force,
+                audit_log: std::sync::Mutex::new(audit_log_path(&storage)),
+                nonces: std::sync::Mutex::new(storage.clone()),
+                metrics: metrics::Metrics::default(),
+                access,
+                max_body_size,
+            };
+            let result = block_on(async {
+                let listener = tokio::net::TcpListener::bind(http).await?;
+                let local_addr = listener.local_addr()?;
+                match tls {
+                    Some(config) => {
+                        eprintln!("Listening on https://{}", local_addr);
+                        serve::run(tls::TlsListener::new(listener, config)?, server).await
+                    }
+                    None => {
+                        eprintln!("Listening on http://{}", local_addr);
+                        serve::run(listener, server).await
+                    }
+                }
+            });
+            if let Err(e) = result {
+                fail(format!("Failed to serve on {}: {}", http, e));
+            }
+        }
+
+        Commands::Queue { command } => {
+            let mut queue = load_queue(&storage);
+            match command {
+                QueueCommands::List { format } => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(&queue).unwrap()),
+                    _ if queue.is_empty() => println!("No queued transactions"),
+                    _ => {
+                        for entry in queue.transactions() {
+                            let data = &entry.transaction.data;
+                            println!(
+                                "{} nonce {}: {} MINA to {} (queued {})",
+                                data.from,
+                                data.nonce,
+                                data.amount.unwrap_or(Amount::ZERO),
+                                data.to,
+                                time::to_rfc3339(entry.queued_at)
+                            );
+                            if let Some(error) = &entry.last_error {
+                                println!("  last error: {}", error);
+                            }
+                        }
+                    }
+                },
+                QueueCommands::Flush {
+                    network,
+                    node,
+                    force,
+                    format,
+                } => {
+                    let network_id = match parse_network(&network) {
+                        Ok(n) => n,
+                        Err(e) => fail(e),
+                    };
+
+                    let client = graphql_client(node.as_deref(), &network_id);
+                    if let Err(e) = check_chain_id(&client, &network_id, force) {
+                        fail(chain_id_error(e));
+                    }
+                    let report = block_on(queue.flush(&client));
+                    save_queue(&mut storage, &queue);
+                    match output_format(&format) {
+                        "json" => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+                        _ => {
+                            for result in &report.results {
+                                let outcome = match &result.outcome {
+                                    FlushOutcome::Sent { hash } => format!("sent ({})", hash),
+                                    FlushOutcome::Conflict { next_nonce } => format!(
+                                        "dropped, nonce already used (next nonce is {})",
+                                        next_nonce
+                                    ),
+                                    FlushOutcome::Waiting => "waiting".to_string(),
+                                    FlushOutcome::Failed { error } => format!("failed: {}", error),
+                                };
+                                println!("{} nonce {}: {}", result.from, result.nonce, outcome);
+                            }
+                            println!("{} transaction(s) left in the queue", queue.len());
+                        }
+                    }
+                    if report.offline {
+                        fail("node unreachable, remaining transactions stay queued");
+                    }
+                }
+                QueueCommands::Remove { from, nonce } => {
+                    if queue.remove(&from, nonce).is_none() {
+                        fail(format!(
+                            "no queued transaction from {} with nonce {}",
+                            from, nonce
+                        ));
+                    }
+                    save_queue(&mut storage, &queue);
+                    if json_output() {
+                        println!(
+                            "{}",
+                            serde_json::json!({ "from": from, "nonce": nonce, "removed": true })
+                        );
+                    } else {
+                        println!("Removed transaction from {} with nonce {}", from, nonce);
+                    }
+                }
+            }
+        }
+
+        Commands::Keys { command } => {
+            let mut keystore = load_keystore(&keys);
+            match command {
+                KeysCommands::Add {
+                    secret_key,
+                    generate,
+                    label,
+                    keyring,
+                } => {
+                    #[cfg(not(feature = "keyring"))]
+                    if keyring {
+                        fail(KEYRING_UNSUPPORTED);
+                    }
+
+                    let wallet = if generate {
+                        Wallet::new(NetworkId::MAINNET).map_err(|e| e.to_string())
+                    } else {
+                        let secret_key = match secret_key {
+                            Some(secret_key) => {
+                                warn_secret_in_argv();
+                                Zeroizing::new(secret_key)
+                            }
+                            None => match prompt_secret("Secret key: ") {
+                                Ok(secret_key) => secret_key,
+                                Err(e) => fail(e),
+                            },
+                        };
+                        Wallet::from_secret_key_strict(secret_key.trim(), NetworkId::MAINNET)
+                            .map_err(|e| e.to_string())
+                    };
+                    let wallet = match wallet {
+                        Ok(w) => w,
+                        Err(e) => fail(e),
+                    };
+                    let passphrase = match read_passphrase("New passphrase: ", true) {
+                        Ok(p) => p,
+                        Err(e) => fail(e),
+                    };
+                    if passphrase.is_empty() {
+                        fail("the passphrase must not be empty");
+                    }
+
+                    let entry =
+                        match keystore.add(&wallet, &passphrase, label.as_deref(), unix_now()) {
+                            Ok(entry) => entry.clone(),
+                            Err(e) => fail(e),
+                        };
+                    save_keystore(&mut keys, &keystore);
+                    if keyring && let Err(e) = keyring_set(&entry.address, &passphrase) {
+                        fail(format!(
+                            "added {} to {}, but its passphrase was not saved: {}",
+                            key_name(&entry),
+                            keys.dir().display(),
+                            e
+                        ));
+                    }
+                    print_key_added(&entry, &keys, keyring, output_format("text"));
+                }
+                KeysCommands::List { format } => {
+                    let default = keystore.default_key().map(|entry| entry.address.clone());
+                    let is_default = |entry: &KeyEntry| default.as_deref() == Some(&entry.address);
+                    match output_format(&format) {
+                        "json" => {
+                            let entries: Vec<_> = keystore
+                                .keys()
+                                .iter()
+                                .map(|entry| {
+                                    serde_json::json!({
+                                        "address": entry.address,
+                                        "label": entry.label,
+                                        "createdAt": entry.created_at,
+                                        "default": is_default(entry),
+                                    })
+                                })
+                                .collect();
+                            println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+                        }
+                        _ if keystore.is_empty() => println!("No keys. Add one with `keys add`."),
+                        _ => {
+                            for entry in keystore.keys() {
+                                println!(
+                                    "{} {:<20} {} (added {})",
+                                    if is_default(entry) { "*" } else { " " },
+                                    entry.label.as_deref().unwrap_or("-"),
+                                    entry.address,
+                                    time::to_rfc3339(entry.created_at)
+                                );
+                            }
+                        }
+                    }
+                }
+                KeysCommands::Remove { name, yes } => {
+                    if !yes {
+                        fail(
+                            "this deletes the encrypted key for good; back it up first, then pass --yes",
+                        );
+                    }
+                    match keystore.remove(&name) {
+                        Ok(entry) => {
+                            save_keystore(&mut keys, &keystore);
+                            if let Err(e) = keyring_delete(&entry.address) {
+                                eprintln!("Warning: {}", e);
+                            }
+                            if json_output() {
+                                println!(
+                                    "{}",
+                                    serde_json::json!({ "address": entry.address, "removed": true })
+                                );
+                            } else {
+                                println!("Removed {}", entry.address);
+                            }
+                        }
+                        Err(e) => fail(e),
+                    }
+                }
+                KeysCommands::Label { name, label } => {
+                    if let Err(e) = keystore.set_label(&name, label.as_deref()) {
+                        fail(e);
+                    }
+                    save_keystore(&mut keys, &keystore);
+                    match label {
+                        _ if json_output() => {
+                            println!("{}", serde_json::json!({ "name": name, "label": label }))
+                        }
+                        Some(label) => println!("Labeled {} as {}", name, label),
+                        None => println!("Cleared the label of {}", name),
+                    }
+                }
+                KeysCommands::Default { name: None } => match keystore.default_key() {
+                    Some(entry) if json_output() => println!(
+                        "{}",
+                        serde_json::json!({ "address": entry.address, "label": entry.label })
+                    ),
+                    Some(entry) => println!("{} ({})", key_name(entry), entry.address),
+                    None => fail("No default key"),
+                },
+                KeysCommands::Default { name: Some(name) } => {
+                    if let Err(e) = keystore.set_default(&name) {
+                        fail(e);
+                    }
+                    save_keystore(&mut keys, &keystore);
+                    match keystore.default_key() {
+                        Some(entry) if json_output() => println!(
+                            "{}",
+                            serde_json::json!({ "address": entry.address, "label": entry.label })
+                        ),
+                        _ => println!("Default key: {}", name),
+                    }
+                }
+                KeysCommands::Keyring { name, forget } => {
+                    let Some(entry) = keystore.get(&name) else {
+                        fail(format!("no key '{}' in the keystore", name));
+                    };
+                    if forget {
+                        if let Err(e) = keyring_delete(&entry.address) {
+                            fail(e);
+                        }
+                        if json_output() {
+                            println!(
+                                "{}",
+                                serde_json::json!({ "address": entry.address, "keyring": false })
+                            );
+                        } else {
+                            println!(
+                                "Passphrase of {} deleted from the OS keychain",
+                                key_name(entry)
+                            );
+                        }
+                        return;
+                    }
+
+                    let passphrase = match read_passphrase(
+                        &format!("Passphrase for {}: ", key_name(entry)),
+                        false,
+                    ) {
+                        Ok(p) => p,
+                        Err(e) => fail(e),
+                    };
+                    // Only save a passphrase that decrypts the key
+                    let saved = keystore
+                        .unlock(&entry.address, &passphrase, NetworkId::MAINNET)
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| keyring_set(&entry.address, &passphrase));
+                    match saved {
+                        Ok(()) if json_output() => println!(
+                            "{}",
+                            serde_json::json!({ "address": entry.address, "keyring": true })
+                        ),
+                        Ok(()) => {
+                            println!("Passphrase of {} saved in the OS keychain", key_name(entry))
+                        }
+                        Err(e) => fail(e),
+                    }
+                }
+            }
+        }
+
+        Commands::Unlock { key, timeout } => {
+            let timeout = match parse_timeout(&timeout) {
+                Ok(t) => t,
+                Err(e) => fail(e),
+            };
+            let keystore = load_keystore(&keys);
+            let entry = match key.as_deref() {
+                Some(name) => keystore.get(name),
+                None => keystore.default_key(),
+            };
+            let entry = match (entry, key) {
+                (Some(entry), _) => entry,
+                (None, Some(name)) => fail(format!("No key '{}' in the keystore", name)),
+                (None, None) => fail("No default key: name the key to unlock"),
+            };
+            let passphrase = match keyring_get(&entry.address) {
+                Ok(Some(p)) => p,
+                Ok(None) => {
+                    match read_passphrase(&format!("Passphrase for {}: ", key_name(entry)), false) {
+                        Ok(p) => p,
+                        Err(e) => fail(e),
+                    }
+                }
+                Err(e) => fail(e),
+            };
+            let derived = match entry.secret.derive_key(&passphrase) {
+                Ok(k) => k,
+                Err(e) => fail(e),
+            };
+            // Only hand over a key that decrypts the entry
+            if let Err(e) = keystore.unlock_with_key(&entry.address, &derived, NetworkId::MAINNET) {
+                fail(e);
+            }
+            let socket = agent::socket_path(keys.dir());
+            match agent::add(&socket, &entry.address, &derived, timeout) {
+                Ok(()) if json_output() => println!(
+                    "{}",
+                    serde_json::json!({ "address": entry.address, "expiresIn": timeout })
+                ),
+                Ok(()) => println!(
+                    "{} is unlocked for {}; `lock` locks it now",
+                    key_name(entry),
+                    format_duration(timeout)
+                ),
+                Err(e) => fail(e),
+            }
+        }
+
+        Commands::Lock => {
+            let socket = agent::socket_path(keys.dir());
+            let unlocked = agent::list(&socket);
+            let locked = if agent::lock(&socket) {
+                unlocked.len()
+            } else {
+                0
+            };
+            if json_output() {
+                println!("{}", serde_json::json!({ "locked": locked }));
+            } else if locked > 0 {
+                println!("Locked {} key(s)", locked);
+            } else {
+                println!("No key is unlocked");
+            }
+        }
+
+        Commands::Agent { socket } => {
+            if let Err(e) = agent::run(&socket) {
+                fail(e);
+            }
+        }
+
+        Commands::Contacts { command } => {
+            let mut book = load_address_book(&storage);
+            match command {
+                ContactsCommands::Add { label, address } => {
+                    let label = label.strip_prefix(CONTACT_PREFIX).unwrap_or(&label);
+                    let contact = match book.add(label, &address) {
+                        Ok(contact) => contact.clone(),
+                        Err(e) => fail(e),
+                    };
+                    save_address_book(&mut storage, &book);
+                    print_saved_contact(
+                        &contact,
+                        &format!("Saved {} as @{}", contact.address, contact.label),
+                    );
+                }
+                ContactsCommands::List { format } => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(&book).unwrap()),
+                    _ if book.is_empty() => {
+                        println!("No contacts. Add one with `contacts add <label> <address>`.")
+                    }
+                    _ => {
+                        for contact in book.contacts() {
+                            print_contact(contact);
+                        }
+                    }
+                },
+                ContactsCommands::Remove { label } => {
+                    let label = label.strip_prefix(CONTACT_PREFIX).unwrap_or(&label);
+                    match book.remove(label) {
+                        Ok(contact) => {
+                            save_address_book(&mut storage, &book);
+                            print_saved_contact(
+                                &contact,
+                                &format!("Removed @{} ({})", contact.label, contact.address),
+                            );
+                        }
+                        Err(e) => fail(e),
+                    }
+                }
+                ContactsCommands::Edit {
+                    label,
+                    rename,
+                    address,
+                } => {
+                    let label = label.strip_prefix(CONTACT_PREFIX).unwrap_or(&label);
+                    let Some(contact) = book.get(label).cloned() else {
+                        fail(AddressBookError::ContactNotFound(label.to_string()));
+                    };
+                    let new_label = rename.as_deref().unwrap_or(&contact.label);
+                    let new_label = new_label.strip_prefix(CONTACT_PREFIX).unwrap_or(new_label);
+                    let address = address.as_deref().unwrap_or(&contact.address);
+                    let contact = match book.update(label, new_label, address) {
+                        Ok(contact) => contact.clone(),
+                        Err(e) => fail(e),
+                    };
+                    save_address_book(&mut storage, &book);
+                    print_saved_contact(
+                        &contact,
+                        &format!("Saved {} as @{}", contact.address, contact.label),
+                    );
+                }
+                ContactsCommands::Favorite { label, off } => {
+                    let label = label.strip_prefix(CONTACT_PREFIX).unwrap_or(&label);
+                    let contact = match book.set_favorite(label, !off) {
+                        Ok(contact) => contact.clone(),
+                        Err(e) => fail(e),
+                    };
+                    save_address_book(&mut storage, &book);
+                    let message = if off {
+                        format!("@{} is no longer a favorite", label)
+                    } else {
+                        format!("@{} is a favorite", label)
+                    };
+                    print_saved_contact(&contact, &message);
+                }
+                ContactsCommands::Search { query, format } => {
+                    let found = book.search(&query);
+                    match output_format(&format) {
+                        "json" => println!("{}", serde_json::to_string_pretty(&found).unwrap()),
+                        _ if found.is_empty() => println!("No contact matches '{}'", query),
+                        _ => {
+                            for contact in found {
+                                print_contact(contact);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::Profile { command } => {
+            let mut profiles = match Profiles::load(&storage) {
+                Ok(profiles) => profiles,
+                Err(e) => fail(e),
+            };
+            match command {
+                ProfileCommands::Add {
+                    name,
+                    network,
+                    node,
+                    keystore,
+                } => {
+                    if let Err(e) =
+                        profile::check_name(&name).and_then(|()| parse_network(&network))
+                    {
+                        fail(e);
+                    }
+                    let profile = Profile {
+                        network: network.to_lowercase(),
+                        nodes: node.as_deref().map(parse_endpoints).unwrap_or_default(),
+                        keystore,
+                    };
+                    let replaced = profiles.profiles.insert(name.clone(), profile).is_some();
+                    if let Err(e) = profiles.save(&mut storage) {
+                        fail(e);
+                    }
+                    if json_output() {
+                        println!(
+                            "{}",
+                            serde_json::json!({ "name": name, "replaced": replaced })
+                        );
+                        return;
+                    }
+                    println!(
+                        "{} profile {}. Use it with --profile {} or `profile use {}`",
+                        if replaced { "Replaced" } else { "Added" },
+                        name,
+                        name,
+                        name
+                    );
+                }
+                ProfileCommands::Use { name, none: _ } => {
+                    if let Some(name) = &name
+                        && !profiles.profiles.contains_key(name)
+                    {
+                        fail(format!("No profile named '{}'", name));
+                    }
+                    profiles.active = name;
+                    if let Err(e) = profiles.save(&mut storage) {
+                        fail(e);
+                    }
+                    match &profiles.active {
+                        active if json_output() => {
+                            println!("{}", serde_json::json!({ "active": active }))
+                        }
+                        Some(name) => println!("Using profile {}", name),
+                        None => println!("Not using a profile by default"),
+                    }
+                }
+                ProfileCommands::List { format } => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(&profiles).unwrap()),
+                    _ if profiles.profiles.is_empty() => {
+                        println!("No profiles. Add one with `profile add`.")
+                    }
+                    _ => {
+                        for (name, profile) in &profiles.profiles {
+                            let active = profiles.active.as_deref() == Some(name.as_str());
+                            println!(
+                                "{} {:<16} {:<8} nodes: {}, keystore: {}",
+                                if active { "*" } else { " " },
+                                name,
+                                profile.network,
+                                if profile.nodes.is_empty() {
+                                    "default".to_string()
+                                } else {
+                                    profile.nodes.join(",")
+                                },
+                                profile
+                                    .keystore
+                                    .as_ref()
+                                    .map_or("shared".to_string(), |dir| dir.display().to_string())
+                            );
+                        }
+                    }
+                },
+            }
+        }
+
+        Commands::Ledger { command } => {
+            let device = match Ledger::open() {
+                Ok(device) => device,
+                Err(e) => fail(e),
+            };
+            match command {
+                LedgerCommands::Address { index, format } => match device.address(index) {
+                    Ok(address) => match output_format(&format) {
+                        "json" => println!(
+                            "{}",
+                            serde_json::json!({ "index": index, "address": address })
+                        ),
+                        _ => println!("{}", address),
+                    },
+                    Err(e) => fail(e),
+                },
+                LedgerCommands::SignPayment { index, mut payment } => {
+                    payment.to = resolve_recipient(&storage, &payment.to);
+                    let network_id = match parse_network(&payment.network) {
+                        Ok(n) => n,
+                        Err(e) => fail(e),
+                    };
+                    let from = match device.address(index) {
+                        Ok(address) => address,
+                        Err(e) => fail(e),
+                    };
+                    eprintln!("From:       {}", from);
+                    let transaction = match build_payment(&payment, &from, &storage) {
+                        Ok(transaction) => transaction,
+                        Err(e) => fail(e),
+                    };
+                    eprintln!("Check and confirm the payment on the device");
+                    let signed = match device.sign(&transaction, index, network_id) {
+                        Ok(signed) => signed,
+                        Err(e) => fail(e),
+                    };
+                    if let Err(e) = audit_signature(&audit_log_path(&storage), &signed) {
+                        fail(e);
+                    }
+                    println!("{}", serde_json::to_string_pretty(&signed).unwrap());
+                    if payment.qr {
+                        print_qr(&serde_json::to_string(&signed).unwrap());
+                    }
+                }
+            }
+        }
+
+        Commands::Audit { command } => {
+            let path = audit_log_path(&storage);
+            let log = match load_audit_log(&path) {
+                Ok(log) => log,
+                Err(e) => fail(e),
+            };
+            match command {
+                AuditCommands::Show { format } => match output_format(&format) {
+                    "json" => println!("{}", serde_json::to_string_pretty(log.entries()).unwrap()),
+                    _ if log.entries().is_empty() => {
+                        println!("No signed transactions in {}", path.display())
+                    }
+                    _ => {
+                        for entry in log.entries() {
+                            let kind = match entry.kind {
+                                TransactionKind::Payment => "payment",
+                                TransactionKind::Delegation => "delegation",
+                            };
+                            let amount = entry
+                                .amount
+                                .map_or(String::new(), |amount| format!("{} MINA ", amount));
+                            println!(
+                                "{:>5}  {}  {:<10} {} -> {}  {}fee {} MINA  nonce {}  key {}",
+                                entry.sequence,
+                                time::to_rfc3339(entry.timestamp),
+                                kind,
+                                entry.from,
+                                entry.to,
+                                amount,
+                                entry.fee,
+                                entry.nonce,
+                                entry.key_fingerprint
+                            );
+                        }
+                    }
+                },
+                AuditCommands::Verify => {
+                    if let Err(e) = log.verify() {
+                        fail(format!("{}: {}", path.display(), e));
+                    }
+                    let last_hash = log.entries().last().map(|entry| entry.hash.as_str());
+                    if json_output() {
+                        println!(
+                            "{}",
+                            serde_json::json!({
+                                "entries": log.entries().len(),
+                                "lastHash": last_hash,
+                            })
+                        );
+                    } else {
+                        println!("{} entries, none modified", log.entries().len());
+                        if let Some(hash) = last_hash {
+                            // Truncating the log is only detected against a saved hash
+                            println!("Last hash: {}", hash);
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::Backup { command } => match command {
+            BackupCommands::Split {
+                secret,
+                threshold,
+                shares,
+            } => {
+                let wallet = match resolve_wallet(&secret, NetworkId::MAINNET, &keys) {
+                    Ok(w) => w,
+                    Err(e) => fail(e),
+                };
+
+                match sss::split_wallet(&wallet, threshold, shares) {
+                    Ok(encoded) if json_output() => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({
+                            "address": wallet.address(),
+                            "threshold": threshold,
+                            "shares": encoded,
+                        }))
+                        .unwrap()
+                    ),
+                    Ok(encoded) => {
+                        println!("Backup shares for {}", wallet.address());
+                        println!(
+                            "Any {} of these {} shares recover the key:",
+                            threshold, shares
+                        );
+                        println!();
+                        for (i, share) in encoded.iter().enumerate() {
+                            println!("Share {}: {}", i + 1, share);
+                        }
+                        println!();
+                        println!("WARNING: Store each share in a different place.");
+                    }
+                    Err(e) => fail(format!("Failed to split secret key: {}", e)),
+                }
+            }
+
+            BackupCommands::Recover {
+                shares,
+                network,
+                format,
+            } => {
+                let network_id = match parse_network(&network) {
+                    Ok(n) => n,
+                    Err(e) => fail(e),
+                };
+
+                match sss::recover_wallet(&shares, network_id) {
+                    Ok(wallet) => match output_format(&format) {
+                        "json" => print_wallet_json(&wallet),
+                        _ => print_wallet_text(&wallet),
+                    },
+                    Err(e) => fail(format!("Failed to recover secret key: {}", e)),
+                }
+            }
+
+            BackupCommands::Export { output } => {
+                let contents = BackupContents {
+                    keystore: load_keystore(&keys),
+                    address_book: load_address_book(&storage),
+                };
+                if contents.keystore.is_empty() && contents.address_book.is_empty() {
+                    fail("the keystore and the address book are empty, nothing to back up");
+                }
+                let passphrase = match read_passphrase("Backup passphrase: ", true) {
+                    Ok(p) => p,
+                    Err(e) => fail(e),
+                };
+                if passphrase.is_empty() {
+                    fail("the passphrase must not be empty");
+                }
+                let archive = match BackupArchive::seal(
+                    &contents,
+                    &passphrase,
+                    KdfCost::default(),
+                    unix_now(),
+                ) {
+                    Ok(archive) => archive,
+                    Err(e) => fail(e),
+                };
+
+                // Never overwrite a previous backup
+                let written = std::fs::File::create_new(&output)
+                    .and_then(|mut file| writeln!(file, "{}", archive.to_json()));
+                if let Err(e) = written {
+                    fail(format!("Failed to write {}: {}", output.display(), e));
+                }
+                if json_output() {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "output": output,
+                            "keys": contents.keystore.len(),
+                            "contacts": contents.address_book.len(),
+                        })
+                    );
+                    return;
+                }
+                println!(
+                    "Backed up {} key(s) and {} contact(s) to {}",
+                    contents.keystore.len(),
+                    contents.address_book.len(),
+                    output.display()
+                );
+                println!("Keys stay encrypted with their own passphrases inside the backup.");
+            }
+
+            BackupCommands::Import { file, format } => {
+                let json = match std::fs::read_to_string(&file) {
+                    Ok(json) => json,
+                    Err(e) => fail(format!("Failed to read {}: {}", file.display(), e)),
+                };
+                let archive = match BackupArchive::from_json(&json) {
+                    Ok(archive) => archive,
+                    Err(e) => fail(e),
+                };
+                let passphrase = match read_passphrase("Backup passphrase: ", false) {
+                    Ok(p) => p,
+                    Err(e) => fail(e),
+                };
+                let contents = match archive.open(&passphrase) {
+                    Ok(contents) => contents,
+                    Err(e) => fail(e),
+                };
+
+                let mut keystore = load_keystore(&keys);
+                let report = keystore.merge(contents.keystore);
+                save_keystore(&mut keys, &keystore);
+                let mut book = load_address_book(&storage);
+                let contacts_before = book.len();
+                let conflicts = book.merge(contents.address_book);
+                save_address_book(&mut storage, &book);
+                let contacts_added = book.len() - contacts_before;
+
+                if output_format(&format) == "json" {
+                    let json = serde_json::json!({
+                        "keys": report,
+                        "contacts": { "added": contacts_added, "conflicts": conflicts },
+                    });
+                    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                    return;
+                }
+                println!(
+                    "Imported {} key(s) into {}",
+                    report.added.len(),
+                    keys.dir().display()
+                );
+                for address in &report.added {
+                    println!("  {}", address);
+                }
+                if !report.skipped.is_empty() {
+                    println!("Already in the keystore: {}", report.skipped.len());
+                }
+                for label in &report.dropped_labels {
+                    println!(
+                        "Label '{}' is already used, the imported key has none",
+                        label
+                    );
+                }
+                println!("Imported {} contact(s)", contacts_added);
+                for label in &conflicts {
+                    println!(
+                        "Contact @{} already exists with another address, kept it",
+                        label
+                    );
+                }
+            }
+        },
     }
 }
@@ -0,0 +1,185 @@
+//! Prometheus metrics of `mina-wallet serve`
+//!
+//! `GET /metrics` returns the counters below in the Prometheus text format,
+//! so that operators can monitor a production signer:
+//! - `mina_wallet_http_requests_total{endpoint, status}`
+//! - `mina_wallet_http_request_duration_seconds{endpoint}`, a histogram
+//! - `mina_wallet_signatures_total`
+//! - `mina_wallet_broadcasts_total{result}`, `success` or `failure`
+//! - `mina_wallet_broadcast_duration_seconds`, a histogram of node round trips
+//!
+//! Metrics are kept in memory and reset when the server restarts.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Counters and latencies of the server
+#[derive(Default)]
+pub struct Metrics {
+    /// Requests by endpoint and HTTP status
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    request_durations: Mutex<BTreeMap<String, Histogram>>,
+    signatures: AtomicU64,
+    broadcasts: AtomicU64,
+    broadcast_failures: AtomicU64,
+    broadcast_durations: Mutex<Histogram>,
+}
+
+#[derive(Default, Clone)]
+struct Histogram {
+    /// Observations at most each bound of `BUCKETS`, not cumulated
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulated = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets) {
+            cumulated += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulated
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braced, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced, self.count);
+    }
+}
+
+impl Metrics {
+    /// Record a request to `endpoint`, the route it matched
+    pub fn record_request(&self, endpoint: &str, status: u16, duration: Duration) {
+        *lock(&self.requests)
+            .entry((endpoint.to_string(), status))
+            .or_default() += 1;
+        lock(&self.request_durations)
+            .entry(endpoint.to_string())
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Record a signature produced with the key of the server
+    pub fn record_signature(&self) {
+        self.signatures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a broadcast to the node and how long the node took
+    pub fn record_broadcast(&self, success: bool, duration: Duration) {
+        if success {
+            self.broadcasts.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.broadcast_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        lock(&self.broadcast_durations).observe(duration);
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str(
+            "# HELP mina_wallet_http_requests_total HTTP requests by endpoint and status.\n",
+        );
+        out.push_str("# TYPE mina_wallet_http_requests_total counter\n");
+        for ((endpoint, status), count) in lock(&self.requests).iter() {
+            let _ = writeln!(
+                out,
+                "mina_wallet_http_requests_total{{endpoint=\"{}\",status=\"{}\"}} {}",
+                escape_label(endpoint),
+                status,
+                count
+            );
+        }
+
+        out.push_str(
+            "# HELP mina_wallet_http_request_duration_seconds Time to answer HTTP requests.\n",
+        );
+        out.push_str("# TYPE mina_wallet_http_request_duration_seconds histogram\n");
+        for (endpoint, histogram) in lock(&self.request_durations).iter() {
+            histogram.render(
+                &mut out,
+                "mina_wallet_http_request_duration_seconds",
+                &format!("endpoint=\"{}\"", escape_label(endpoint)),
+            );
+        }
+
+        out.push_str(
+            "# HELP mina_wallet_signatures_total Transactions signed with the key of the server.\n",
+        );
+        out.push_str("# TYPE mina_wallet_signatures_total counter\n");
+        let _ = writeln!(
+            out,
+            "mina_wallet_signatures_total {}",
+            self.signatures.load(Ordering::Relaxed)
+        );
+
+        out.push_str(
+            "# HELP mina_wallet_broadcasts_total Transactions submitted to the node, by result.\n",
+        );
+        out.push_str("# TYPE mina_wallet_broadcasts_total counter\n");
+        let _ = writeln!(
+            out,
+            "mina_wallet_broadcasts_total{{result=\"success\"}} {}",
+            self.broadcasts.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "mina_wallet_broadcasts_total{{result=\"failure\"}} {}",
+            self.broadcast_failures.load(Ordering::Relaxed)
+        );
+
+        out.push_str(
+            "# HELP mina_wallet_broadcast_duration_seconds Time the node took to accept or refuse a transaction.\n",
+        );
+        out.push_str("# TYPE mina_wallet_broadcast_duration_seconds histogram\n");
+        lock(&self.broadcast_durations).render(
+            &mut out,
+            "mina_wallet_broadcast_duration_seconds",
+            "",
+        );
+        out
+    }
+}
+
+/// Lock a mutex, keeping the metrics of a handler that panicked
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
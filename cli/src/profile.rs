@@ -0,0 +1,86 @@
+//! Named profiles of `mina-wallet`
+//!
+//! A profile combines a network, the GraphQL endpoints of its nodes and a
+//! keystore directory, so that the keys and endpoints of different chains are
+//! not mixed up. Profiles are kept in `profiles.json` in the data directory.
+//! The profile in use is the one given with --profile, else the one chosen
+//! with `profile use`; it overrides the network and nodes of the configuration
+//! file.
+
+use mina_web_wallet_core::storage::{self, FileStorage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Storage key of the profiles
+const PROFILES_STORAGE_KEY: &str = "profiles";
+
+/// Longest profile name, in characters
+const MAX_NAME_LEN: usize = 32;
+
+/// All the profiles, and the one in use
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profiles {
+    /// Profile used without --profile
+    pub active: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    /// mainnet or testnet, the network signatures are made for
+    pub network: String,
+    /// GraphQL endpoints in order of preference, else those of the
+    /// configuration file or a public node
+    #[serde(default)]
+    pub nodes: Vec<String>,
+    /// Keystore directory, else the `keys` directory of the data directory
+    pub keystore: Option<PathBuf>,
+}
+
+impl Profiles {
+    /// Read the profiles of a data directory; none were saved yet if missing
+    pub fn load(storage: &FileStorage) -> Result<Self, String> {
+        storage::load_json(storage, PROFILES_STORAGE_KEY)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, storage: &mut FileStorage) -> Result<(), String> {
+        storage::save_json(storage, PROFILES_STORAGE_KEY, self).map_err(|e| e.to_string())
+    }
+
+    /// The profile called `name`, else the active one, if any
+    pub fn select(&self, name: Option<&str>) -> Result<Option<(&str, &Profile)>, String> {
+        let Some(name) = name.or(self.active.as_deref()) else {
+            return Ok(None);
+        };
+        match self.profiles.get_key_value(name) {
+            Some((name, profile)) => Ok(Some((name, profile))),
+            None => Err(format!(
+                "No profile named '{}'. Create it with `profile add`",
+                name
+            )),
+        }
+    }
+}
+
+/// Check that a profile name is 1 to 32 letters, digits, '-' or '_'
+pub fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid profile name '{}': use 1 to {} letters, digits, '-' or '_'",
+            name, MAX_NAME_LEN
+        ))
+    }
+}
@@ -0,0 +1,438 @@
+//! HTTP API of `mina-wallet serve`
+//!
+//! JSON endpoints for programs written in any language:
+//! - `POST /v1/address` - derive the address of a secret key
+//! - `GET /v1/validate/{address}` - check an address
+//! - `POST /v1/sign-payment` - sign a payment with the key the server was started with
+//! - `POST /v1/broadcast` - verify and submit a signed payment to the node
+//! - `GET /v1/openapi.json` - OpenAPI description, generated from the types below
+//! - `GET /metrics` - Prometheus metrics, see `crate::metrics`
+//!
+//! Errors are returned as `{"error": {"code": <HTTP status>, "message": "..."}}`,
+//! like the errors of the CLI in `--json` mode.
+//!
+//! Endpoints can be restricted to API tokens, see `crate::access`, and request
+//! bodies are limited to `Server::max_body_size` bytes.
+
+use axum::extract::{DefaultBodyLimit, MatchedPath, Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::serve::Listener;
+use axum::{Json, Router};
+use mina_signer::NetworkId;
+use mina_web_wallet_core::network::{GraphQlClient, NetworkError};
+use mina_web_wallet_core::nonce::NonceManager;
+use mina_web_wallet_core::storage::FileStorage;
+use mina_web_wallet_core::transaction::{SignedTransaction, Transaction, verify_transaction};
+use mina_web_wallet_core::wallet::network_name;
+use mina_web_wallet_core::{Amount, Wallet};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use utoipa::{OpenApi, ToSchema};
+use zeroize::Zeroizing;
+
+use crate::access::{AccessControl, Denied};
+use crate::metrics::Metrics;
+
+/// Largest request body accepted by default, in bytes; payments are far smaller
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Mina wallet API",
+        description = "Address derivation, validation, payment signing and broadcasting. \
+            Endpoints may require an API token, sent as `Authorization: Bearer <token>`."
+    ),
+    paths(derive_address, validate_address, sign_payment, broadcast)
+)]
+struct ApiDoc;
+
+/// State shared by the request handlers
+pub struct Server {
+    pub network: NetworkId,
+    pub client: GraphQlClient,
+    /// Key used by `/v1/sign-payment`, if the server was started with one
+    pub wallet: Option<Wallet>,
+    /// Sign and broadcast even if the node's chain ID does not match the network
+    pub force: bool,
+    /// Audit log of the signed payments, locked while one is recorded
+    pub audit_log: Mutex<PathBuf>,
+    /// State directory whose nonce manager hands out the nonces of payments
+    /// signed without one, locked while one is reserved
+    pub nonces: Mutex<FileStorage>,
+    pub metrics: Metrics,
+    /// Tokens allowed to call each endpoint
+    pub access: AccessControl,
+    /// Larger request bodies are refused with 413
+    pub max_body_size: usize,
+}
+
+/// Serve the API on `listener`, plain TCP or `crate::tls::TlsListener`,
+/// until the process is stopped
+pub async fn run<L>(listener: L, server: Server) -> std::io::Result<()>
+where
+    L: Listener<Addr = std::net::SocketAddr>,
+{
+    axum::serve(listener, router(Arc::new(server))).await
+}
+
+fn router(server: Arc<Server>) -> Router {
+    Router::new()
+        .route("/v1/openapi.json", get(openapi))
+        .route("/v1/address", post(derive_address))
+        .route("/v1/validate/{address}", get(validate_address))
+        .route("/v1/sign-payment", post(sign_payment))
+        .route("/v1/broadcast", post(broadcast))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(server.clone(), authorize))
+        .route_layer(middleware::from_fn_with_state(server.clone(), track))
+        .layer(DefaultBodyLimit::max(server.max_body_size))
+        .with_state(server)
+}
+
+/// Name of the endpoint at `path` in access files
+fn method_name(path: &str) -> &'static str {
+    match path {
+        "/v1/address" => "address",
+        "/v1/validate/{address}" => "validate",
+        "/v1/sign-payment" => "sign-payment",
+        "/v1/broadcast" => "broadcast",
+        "/v1/openapi.json" => "openapi",
+        "/metrics" => "metrics",
+        _ => "",
+    }
+}
+
+/// Refuse requests without a token allowed to call the endpoint
+async fn authorize(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    let method = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| method_name(path.as_str()))
+        .unwrap_or_default();
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    match server.access.authorize(method, bearer) {
+        Ok(()) => next.run(request).await,
+        Err(Denied::Unauthenticated) => (
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "A valid API token is required: send Authorization: Bearer <token>",
+            ),
+        )
+            .into_response(),
+        Err(Denied::Forbidden { token }) => ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("Token '{}' may not call {}", token, method),
+        )
+        .into_response(),
+    }
+}
+
+/// Count requests and their latency, by the route they matched
+async fn track(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    server
+        .metrics
+        .record_request(&endpoint, response.status().as_u16(), started.elapsed());
+    response
+}
+
+/// Body of error responses
+#[derive(Serialize, ToSchema)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    /// HTTP status code
+    code: u16,
+    message: String,
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn bad_request(message: impl std::fmt::Display) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// The node failed or refused the request
+    fn node(e: NetworkError) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorResponse {
+            error: ErrorBody {
+                code: self.status.as_u16(),
+                message: self.message,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AddressRequest {
+    /// Secret key in hex (64 characters) or base58 (52 characters)
+    secret_key: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AddressResponse {
+    address: String,
+    /// mainnet or testnet
+    network: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ValidateResponse {
+    address: String,
+    valid: bool,
+    /// Why the address is invalid
+    error: Option<String>,
+}
+
+/// A payment from the key of the server
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PaymentRequest {
+    /// Receiver address
+    to: String,
+    /// Amount in MINA, e.g. "1.5"
+    amount: String,
+    /// Fee in MINA, e.g. "0.1"
+    fee: String,
+    /// Nonce of the payment; if omitted, the next one after the node's
+    /// inferred nonce and those already handed out
+    nonce: Option<u32>,
+    /// Memo, up to 32 bytes
+    #[serde(default)]
+    memo: String,
+    /// Last global slot at which the payment can be included
+    valid_until: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct SignedPayment {
+    /// Signed payment, as printed by `mina-wallet sign-payment`
+    #[schema(value_type = Object)]
+    signed_transaction: SignedTransaction,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BroadcastResponse {
+    /// Hash of the broadcast transaction
+    hash: String,
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn metrics(State(server): State<Arc<Server>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        server.metrics.render(),
+    )
+}
+
+/// Derive the address of a secret key
+///
+/// The secret key is only used for the derivation and is not kept.
+#[utoipa::path(
+    post,
+    path = "/v1/address",
+    request_body = AddressRequest,
+    responses(
+        (status = 200, body = AddressResponse),
+        (status = 400, description = "Invalid secret key", body = ErrorResponse)
+    )
+)]
+async fn derive_address(
+    State(server): State<Arc<Server>>,
+    Json(request): Json<AddressRequest>,
+) -> Result<Json<AddressResponse>, ApiError> {
+    let secret_key = Zeroizing::new(request.secret_key);
+    let wallet = Wallet::from_secret_key_hex(&secret_key, server.network.clone())
+        .or_else(|_| Wallet::from_secret_key_base58(&secret_key, server.network.clone()))
+        .map_err(|_| {
+            ApiError::bad_request(
+                "Invalid secret key format. Expected hex (64 chars) or base58 (52 chars).",
+            )
+        })?;
+    Ok(Json(AddressResponse {
+        address: wallet.address(),
+        network: network_name(&server.network).to_string(),
+    }))
+}
+
+/// Check that an address is a valid Mina public key
+#[utoipa::path(
+    get,
+    path = "/v1/validate/{address}",
+    params(("address" = String, Path, description = "Address to check")),
+    responses((status = 200, body = ValidateResponse))
+)]
+async fn validate_address(Path(address): Path<String>) -> Json<ValidateResponse> {
+    let error = mina_web_wallet_core::address_to_pubkey(&address)
+        .err()
+        .map(|e| format!("{:?}", e));
+    Json(ValidateResponse {
+        valid: error.is_none(),
+        address,
+        error,
+    })
+}
+
+/// Sign a payment with the key the server was started with (`--key`)
+#[utoipa::path(
+    post,
+    path = "/v1/sign-payment",
+    request_body = PaymentRequest,
+    responses(
+        (status = 200, body = SignedPayment),
+        (status = 400, description = "Invalid payment", body = ErrorResponse),
+        (status = 403, description = "The server has no signing key", body = ErrorResponse),
+        (status = 500, description = "The nonce or the payment could not be recorded", body = ErrorResponse),
+        (status = 502, description = "The nonce could not be fetched, or the node is on another chain", body = ErrorResponse)
+    )
+)]
+async fn sign_payment(
+    State(server): State<Arc<Server>>,
+    Json(request): Json<PaymentRequest>,
+) -> Result<Json<SignedPayment>, ApiError> {
+    let Some(wallet) = &server.wallet else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "No signing key: start the server with --key",
+        ));
+    };
+    let amount: Amount = request.amount.parse().map_err(ApiError::bad_request)?;
+    let fee: Amount = request.fee.parse().map_err(ApiError::bad_request)?;
+    let nonce = match request.nonce {
+        Some(nonce) => nonce,
+        None => {
+            // A nonce from a node of another chain would sign a payment for it
+            check_chain_id(&server).await?;
+            let inferred = server
+                .client
+                .get_inferred_nonce(&wallet.address())
+                .await
+                .map_err(ApiError::node)?;
+            reserve_nonce(&server, &wallet.address(), inferred)?
+        }
+    };
+
+    let mut payment = Transaction::payment(&wallet.address(), &request.to, amount, fee, nonce)
+        .with_memo(&request.memo);
+    if let Some(valid_until) = request.valid_until {
+        payment = payment.with_valid_until(valid_until);
+    }
+    let signed_transaction = wallet
+        .sign_transaction(&payment)
+        .map_err(ApiError::bad_request)?;
+    server.metrics.record_signature();
+    let audit_log = server
+        .audit_log
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    crate::audit_signature(&audit_log, &signed_transaction)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(SignedPayment { signed_transaction }))
+}
+
+/// Verify a signed payment and submit it to the node
+#[utoipa::path(
+    post,
+    path = "/v1/broadcast",
+    request_body = SignedPayment,
+    responses(
+        (status = 200, body = BroadcastResponse),
+        (status = 400, description = "Invalid signature", body = ErrorResponse),
+        (status = 502, description = "The node refused the payment or is on another chain", body = ErrorResponse)
+    )
+)]
+async fn broadcast(
+    State(server): State<Arc<Server>>,
+    Json(request): Json<SignedPayment>,
+) -> Result<Json<BroadcastResponse>, ApiError> {
+    let signed = request.signed_transaction;
+    match verify_transaction(&signed, server.network.clone()) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(ApiError::bad_request(format!(
+                "The signature does not match the transaction on {}",
+                network_name(&server.network)
+            )));
+        }
+        Err(e) => return Err(ApiError::bad_request(e)),
+    }
+    check_chain_id(&server).await?;
+    let started = Instant::now();
+    let result = server.client.broadcast(&signed).await;
+    server
+        .metrics
+        .record_broadcast(result.is_ok(), started.elapsed());
+    let hash = result.map_err(ApiError::node)?;
+    Ok(Json(BroadcastResponse { hash }))
+}
+
+/// Reserve the next nonce of `address` in the nonce manager of the state
+/// directory, after the node's inferred nonce, as `send` does: concurrent
+/// requests, and runs of the CLI, then sign with distinct nonces
+fn reserve_nonce(server: &Server, address: &str, inferred: u32) -> Result<u32, ApiError> {
+    let internal = |e: String| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e);
+    let mut storage = server.nonces.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut nonces = NonceManager::load(&*storage)
+        .map_err(|e| internal(format!("Failed to load the nonces: {}", e)))?;
+    nonces.observe(address, inferred);
+    let nonce = nonces
+        .reserve(address)
+        .ok_or_else(|| internal("The nonce of the sender overflowed".to_string()))?;
+    nonces
+        .save(&mut *storage)
+        .map_err(|e| internal(format!("Failed to save the nonces: {}", e)))?;
+    Ok(nonce)
+}
+
+/// Check that the node is on the network of the server, unless forced
+async fn check_chain_id(server: &Server) -> Result<(), ApiError> {
+    match server.client.verify_chain_id(&server.network).await {
+        Ok(_) => Ok(()),
+        Err(NetworkError::ChainMismatch { .. }) if server.force => Ok(()),
+        Err(e) => Err(ApiError::node(e)),
+    }
+}
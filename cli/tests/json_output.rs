@@ -0,0 +1,268 @@
+//! Every subcommand prints one JSON document on stdout under --json
+//!
+//! Only the subcommands that work without a node are run.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::Value;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+/// A data directory of its own, removed when dropped
+struct DataDir(PathBuf);
+
+impl DataDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("mina-wallet-json-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+
+    /// Run `mina-wallet --json` in this directory, check that it succeeded
+    /// and parse its stdout
+    fn run(&self, args: &[&str]) -> Value {
+        let output = self.command(args).output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}{}",
+            args,
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        parse(args, &stdout)
+    }
+
+    /// Run `mina-wallet --json` in this directory, check that it failed with
+    /// an error object
+    fn run_err(&self, args: &[&str]) -> Value {
+        let output = self.command(args).output().unwrap();
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        let value = parse(args, &String::from_utf8(output.stdout).unwrap());
+        assert!(
+            value["error"]["message"].is_string(),
+            "{:?}: {}",
+            args,
+            value
+        );
+        value
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_mina-wallet"));
+        command
+            .arg("--json")
+            .arg("--data-dir")
+            .arg(&self.0)
+            .args(args)
+            // Keep the configuration file and keychain of the user out of it
+            .env("HOME", &self.0)
+            .env("XDG_CONFIG_HOME", &self.0)
+            .env("MINA_WALLET_PASSPHRASE", PASSPHRASE)
+            .env("MINA_WALLET_NON_INTERACTIVE", "1")
+            .env_remove("MINA_WALLET_DIR")
+            .env_remove("MINA_WALLET_KEY")
+            .env_remove("MINA_WALLET_PASSPHRASE_FILE");
+        command
+    }
+}
+
+impl Drop for DataDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Parse stdout as a single JSON document
+fn parse(args: &[&str], stdout: &str) -> Value {
+    serde_json::from_str(stdout)
+        .unwrap_or_else(|e| panic!("{:?} printed no JSON ({}): {}", args, e, stdout))
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn test_offline_commands() {
+    let dir = DataDir::new("offline");
+    let wallet = dir.run(&["generate"]);
+    let address = wallet["address"].as_str().unwrap();
+    let secret = wallet["secret_key_hex"].as_str().unwrap();
+
+    assert_eq!(dir.run(&["validate", address])["valid"], true);
+    assert_eq!(dir.run(&["address", secret])["address"], address);
+    assert_eq!(dir.run(&["import", secret])["address"], address);
+    assert!(dir.run(&["convert", "field", "42"])["value"].is_string());
+
+    let encrypted = dir.run(&["encrypt", "--to", address, "hello"]);
+    let ciphertext = encrypted["ciphertext"].as_str().unwrap();
+    assert_eq!(
+        dir.run(&["decrypt", secret, ciphertext])["plaintext"],
+        "hello"
+    );
+
+    let signed = dir.run(&["sign-message", secret, "hello"]);
+    let signature = signed["signatureBase58"].as_str().unwrap();
+    let verified = dir.run(&[
+        "verify-message",
+        "hello",
+        "--address",
+        address,
+        "--signature",
+        signature,
+    ]);
+    assert_eq!(verified["valid"], true);
+
+    assert!(dir.run(&["sign-fields", secret, r#"["1","2"]"#])["signature"].is_string());
+    assert!(dir.run(&["qr", address])["svg"].is_string());
+    let png = dir.path("address.png");
+    let written = dir.run(&["qr", address, "--format", "png", "-o", path_str(&png)]);
+    assert_eq!(written["output"], path_str(&png));
+    assert!(dir.run(&["test-vectors", "--count", "1"])["vectors"].is_array());
+    assert!(dir.run(&["export", secret, "--paper"]).is_object());
+    assert!(dir.run(&["epoch", "--slot", "0"]).is_object());
+    assert!(dir.run(&["fee"])["minimum"].is_string());
+    assert!(dir.run(&["lock"])["locked"].is_number());
+
+    let split = dir.run(&["backup", "split", secret]);
+    assert_eq!(split["address"], address);
+    let shares: Vec<&str> = split["shares"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|share| share.as_str().unwrap())
+        .collect();
+    let recovered = dir.run(&["backup", "recover", shares[0], shares[2]]);
+    assert_eq!(recovered["address"], address);
+
+    dir.run_err(&["validate", "B62qnot-an-address"]);
+}
+
+#[test]
+fn test_keys_and_queue() {
+    let dir = DataDir::new("keys");
+    let added = dir.run(&["keys", "add", "--generate", "--label", "main"]);
+    let address = added["address"].as_str().unwrap();
+    assert_eq!(added["label"], "main");
+    assert_eq!(added["keyring"], false);
+
+    assert_eq!(dir.run(&["keys", "list"])[0]["address"], address);
+    assert_eq!(dir.run(&["keys", "default", "main"])["address"], address);
+    assert_eq!(dir.run(&["keys", "default"])["label"], "main");
+    assert_eq!(dir.run(&["keys", "label", "main", "cold"])["label"], "cold");
+
+    let unsigned = dir.path("unsigned.json");
+    let signed = dir.path("signed.json");
+    let created = dir.run(&[
+        "tx",
+        "create",
+        "--from",
+        address,
+        "--to",
+        address,
+        "--amount",
+        "1",
+        "--nonce",
+        "0",
+        "-o",
+        path_str(&unsigned),
+    ]);
+    assert_eq!(created["output"], path_str(&unsigned));
+    let sign = ["tx", "sign", "cold", "-i", path_str(&unsigned)];
+    assert!(dir.run(&sign).is_object());
+    dir.run(&[&sign[..], &["-o", path_str(&signed)]].concat());
+    assert_eq!(
+        dir.run(&["tx", "preview", "-i", path_str(&signed)])["signed"],
+        true
+    );
+
+    let queued = dir.run(&[
+        "send", "cold", "--to", address, "--amount", "1", "--nonce", "1", "--queue",
+    ]);
+    assert_eq!(queued["queued"], true);
+    assert_eq!(
+        dir.run(&["queue", "list"]).as_array().map(Vec::len),
+        Some(1)
+    );
+    let removed = dir.run(&["queue", "remove", address, "1"]);
+    assert_eq!(removed["nonce"], 1);
+
+    assert!(dir.run(&["audit", "show"]).is_array());
+    assert!(dir.run(&["audit", "verify"])["entries"].is_number());
+
+    let backup = dir.path("backup.json");
+    let exported = dir.run(&["backup", "export", "-o", path_str(&backup)]);
+    assert_eq!(exported["keys"], 1);
+    assert!(
+        dir.run(&["backup", "import", path_str(&backup)])
+            .is_object()
+    );
+
+    assert_eq!(
+        dir.run(&["keys", "remove", "cold", "--yes"])["removed"],
+        true
+    );
+}
+
+#[test]
+fn test_contacts() {
+    let dir = DataDir::new("contacts");
+    let address = dir.run(&["generate"])["address"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let other = dir.run(&["generate"])["address"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let added = dir.run(&["contacts", "add", "alice", &address]);
+    assert_eq!(added["label"], "alice");
+    assert_eq!(added["address"], address.as_str());
+    let edited = dir.run(&["contacts", "edit", "@alice", "--address", &other]);
+    assert_eq!(edited["address"], other.as_str());
+    assert_eq!(
+        dir.run(&["contacts", "favorite", "alice"])["favorite"],
+        true
+    );
+    let unmarked = dir.run(&["contacts", "favorite", "alice", "--off"]);
+    assert_eq!(unmarked["favorite"], false);
+    assert!(dir.run(&["contacts", "list"]).is_array());
+    assert!(dir.run(&["contacts", "search", "ali"]).is_array());
+    assert_eq!(dir.run(&["contacts", "remove", "alice"])["label"], "alice");
+}
+
+#[test]
+fn test_vanity() {
+    let dir = DataDir::new("vanity");
+    let printed = dir.run(&["vanity", "--suffix", "a", "--print"]);
+    assert!(printed["address"].as_str().unwrap().ends_with('a'));
+
+    // Saved keys are reported as `keys add` reports them
+    let saved = dir.run(&["vanity", "--suffix", "a", "--label", "grind"]);
+    assert!(saved["address"].as_str().unwrap().ends_with('a'));
+    assert_eq!(saved["label"], "grind");
+    assert_eq!(saved["keyring"], false);
+    assert!(saved["keystore"].is_string());
+}
+
+#[test]
+fn test_profiles() {
+    let dir = DataDir::new("profiles");
+    let added = dir.run(&["profile", "add", "local", "--network", "testnet"]);
+    assert_eq!(added["replaced"], false);
+    assert_eq!(dir.run(&["profile", "use", "local"])["active"], "local");
+    assert!(dir.run(&["profile", "list"]).is_object());
+    assert_eq!(
+        dir.run(&["profile", "use", "--none"])["active"],
+        Value::Null
+    );
+}
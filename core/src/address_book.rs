@@ -0,0 +1,361 @@
+//! Address book
+//!
+//! Contacts are labeled addresses, persisted through a `Storage` backend.
+//! Addresses are validated when a contact is saved, so that a typo is caught
+//! before any payment is made to it. A recipient written `@label` is resolved
+//! to the address of the contact with that label; any other recipient is
+//! taken as an address.
+//!
+//! Contacts can be marked as favorites, and remember when a payment was last
+//! sent to them, so that contact pickers list the likely recipients first
+//! (`search`).
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::storage::{self, Storage, StorageError};
+
+/// Storage key of the address book
+pub const ADDRESS_BOOK_STORAGE_KEY: &str = "address_book";
+
+/// Longest label, in characters
+pub const MAX_CONTACT_LABEL_LEN: usize = 32;
+
+/// Prefix of a recipient referring to a contact, as in `@alice`
+pub const CONTACT_PREFIX: char = '@';
+
+/// Errors that can occur while managing the address book
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressBookError {
+    #[error("No contact with label '{0}'")]
+    ContactNotFound(String),
+    #[error("Label '{0}' is already used")]
+    DuplicateLabel(String),
+    #[error("Invalid label '{0}': use 1 to {MAX_CONTACT_LABEL_LEN} letters, digits, '-' or '_'")]
+    InvalidLabel(String),
+    #[error("Invalid address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+pub type Result<T> = std::result::Result<T, AddressBookError>;
+
+/// A labeled address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub label: String,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// When a payment was last sent to the contact, as a Unix timestamp in
+    /// seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
+}
+
+/// Contacts, sorted by label
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AddressBook {
+    contacts: Vec<Contact>,
+}
+
+impl AddressBook {
+    /// Create an empty address book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the address book from storage, or an empty one if none was saved
+    pub fn load(storage: &dyn Storage) -> Result<Self> {
+        Ok(storage::load_json(storage, ADDRESS_BOOK_STORAGE_KEY)?.unwrap_or_default())
+    }
+
+    /// Save the address book to storage
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<()> {
+        Ok(storage::save_json(storage, ADDRESS_BOOK_STORAGE_KEY, self)?)
+    }
+
+    /// Add a contact, after checking its label and address
+    pub fn add(&mut self, label: &str, address: &str) -> Result<&Contact> {
+        check_label(label)?;
+        if self.position(label).is_some() {
+            return Err(AddressBookError::DuplicateLabel(label.to_string()));
+        }
+        check_address(address)?;
+
+        self.contacts.push(Contact {
+            label: label.to_string(),
+            address: address.to_string(),
+            favorite: false,
+            last_used: None,
+        });
+        self.contacts.sort_by(|a, b| a.label.cmp(&b.label));
+        Ok(&self.contacts[self.position(label).expect("contact was just added")])
+    }
+
+    /// Change the label and address of a contact, keeping its favorite mark
+    /// and last use
+    pub fn update(&mut self, label: &str, new_label: &str, address: &str) -> Result<&Contact> {
+        let index = self
+            .position(label)
+            .ok_or_else(|| AddressBookError::ContactNotFound(label.to_string()))?;
+        check_label(new_label)?;
+        if new_label != label && self.position(new_label).is_some() {
+            return Err(AddressBookError::DuplicateLabel(new_label.to_string()));
+        }
+        check_address(address)?;
+
+        let contact = &mut self.contacts[index];
+        contact.label = new_label.to_string();
+        contact.address = address.to_string();
+        self.contacts.sort_by(|a, b| a.label.cmp(&b.label));
+        Ok(&self.contacts[self.position(new_label).expect("contact was just updated")])
+    }
+
+    /// Mark or unmark a contact as a favorite
+    pub fn set_favorite(&mut self, label: &str, favorite: bool) -> Result<&Contact> {
+        let index = self
+            .position(label)
+            .ok_or_else(|| AddressBookError::ContactNotFound(label.to_string()))?;
+        self.contacts[index].favorite = favorite;
+        Ok(&self.contacts[index])
+    }
+
+    /// Record a payment to `address` at `now`, on every contact with that
+    /// address
+    ///
+    /// Returns whether any contact has the address.
+    pub fn mark_used(&mut self, address: &str, now: u64) -> bool {
+        let mut found = false;
+        for contact in self.contacts.iter_mut().filter(|c| c.address == address) {
+            contact.last_used = Some(now);
+            found = true;
+        }
+        found
+    }
+
+    /// Contacts whose label contains `query`, ignoring case, or whose address
+    /// starts with it; all of them for an empty query
+    ///
+    /// Favorites come first, then the most recently used, then by label.
+    pub fn search(&self, query: &str) -> Vec<&Contact> {
+        let query = query.trim().trim_start_matches(CONTACT_PREFIX);
+        let lowercase = query.to_lowercase();
+        let mut found: Vec<&Contact> = self
+            .contacts
+            .iter()
+            .filter(|c| c.label.to_lowercase().contains(&lowercase) || c.address.starts_with(query))
+            .collect();
+        // The sort is stable, so contacts stay sorted by label otherwise
+        found.sort_by(|a, b| {
+            b.favorite
+                .cmp(&a.favorite)
+                .then_with(|| b.last_used.cmp(&a.last_used))
+        });
+        found
+    }
+
+    /// The contacts used most recently, at most `limit`
+    pub fn recent(&self, limit: usize) -> Vec<&Contact> {
+        let mut used: Vec<&Contact> = self
+            .contacts
+            .iter()
+            .filter(|c| c.last_used.is_some())
+            .collect();
+        used.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        used.truncate(limit);
+        used
+    }
+
+    /// Remove a contact by label
+    pub fn remove(&mut self, label: &str) -> Result<Contact> {
+        let index = self
+            .position(label)
+            .ok_or_else(|| AddressBookError::ContactNotFound(label.to_string()))?;
+        Ok(self.contacts.remove(index))
+    }
+
+    /// Find a contact by label
+    pub fn get(&self, label: &str) -> Option<&Contact> {
+        self.position(label).map(|i| &self.contacts[i])
+    }
+
+    /// Contacts, sorted by label
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Number of contacts
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    /// Whether the address book is empty
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    /// Address of a recipient: the contact's address for `@label`, else the
+    /// recipient itself
+    pub fn resolve(&self, recipient: &str) -> Result<String> {
+        match recipient.strip_prefix(CONTACT_PREFIX) {
+            Some(label) => self
+                .get(label)
+                .map(|c| c.address.clone())
+                .ok_or_else(|| AddressBookError::ContactNotFound(label.to_string())),
+            None => Ok(recipient.to_string()),
+        }
+    }
+
+    /// Add the contacts of `other` whose label is free
+    ///
+    /// Returns the labels of the contacts left out because this address book
+    /// uses them for another address.
+    pub fn merge(&mut self, other: AddressBook) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for contact in other.contacts {
+            match self.get(&contact.label) {
+                Some(existing) if existing.address == contact.address => {}
+                Some(_) => conflicts.push(contact.label),
+                None => self.contacts.push(contact),
+            }
+        }
+        self.contacts.sort_by(|a, b| a.label.cmp(&b.label));
+        conflicts
+    }
+
+    fn position(&self, label: &str) -> Option<usize> {
+        self.contacts.iter().position(|c| c.label == label)
+    }
+}
+
+fn check_address(address: &str) -> Result<()> {
+    crate::address_to_pubkey(address).map_err(|e| AddressBookError::InvalidAddress {
+        address: address.to_string(),
+        reason: format!("{:?}", e),
+    })?;
+    Ok(())
+}
+
+fn check_label(label: &str) -> Result<()> {
+    let valid = !label.is_empty()
+        && label.chars().count() <= MAX_CONTACT_LABEL_LEN
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AddressBookError::InvalidLabel(label.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::wallet::Wallet;
+    use mina_signer::NetworkId;
+
+    const ALICE: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn bob() -> String {
+        Wallet::new(NetworkId::TESTNET).unwrap().address()
+    }
+
+    #[test]
+    fn test_address_book_lifecycle() {
+        let bob = bob();
+        let mut book = AddressBook::new();
+        book.add("bob", &bob).unwrap();
+        book.add("alice", ALICE).unwrap();
+        let labels: Vec<&str> = book.contacts().iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["alice", "bob"]);
+
+        assert_eq!(
+            book.add("alice", &bob),
+            Err(AddressBookError::DuplicateLabel("alice".to_string()))
+        );
+        assert_eq!(
+            book.add("@carol", &bob),
+            Err(AddressBookError::InvalidLabel("@carol".to_string()))
+        );
+        assert!(matches!(
+            book.add("carol", "B62qnotanaddress"),
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+
+        let mut storage = MemoryStorage::new();
+        book.save(&mut storage).unwrap();
+        let mut loaded = AddressBook::load(&storage).unwrap();
+        assert_eq!(loaded, book);
+        assert_eq!(loaded.remove("bob").unwrap().address, bob);
+        assert_eq!(
+            loaded.remove("bob"),
+            Err(AddressBookError::ContactNotFound("bob".to_string()))
+        );
+        assert_eq!(loaded.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_and_merge() {
+        let bob = bob();
+        let mut book = AddressBook::new();
+        book.add("alice", ALICE).unwrap();
+        assert_eq!(book.resolve("@alice").unwrap(), ALICE);
+        assert_eq!(book.resolve(&bob).unwrap(), bob);
+        assert_eq!(
+            book.resolve("@bob"),
+            Err(AddressBookError::ContactNotFound("bob".to_string()))
+        );
+
+        let mut other = AddressBook::new();
+        other.add("alice", &bob).unwrap();
+        other.add("bob", &bob).unwrap();
+        assert_eq!(book.merge(other), vec!["alice".to_string()]);
+        assert_eq!(book.resolve("@alice").unwrap(), ALICE);
+        assert_eq!(book.resolve("@bob").unwrap(), bob);
+    }
+
+    #[test]
+    fn test_favorites_and_search() {
+        let bob = bob();
+        let mut book = AddressBook::new();
+        book.add("alice", ALICE).unwrap();
+        book.add("bob", &bob).unwrap();
+        book.add("carol", &bob).unwrap();
+
+        let labels = |contacts: Vec<&Contact>| -> Vec<String> {
+            contacts.iter().map(|c| c.label.clone()).collect()
+        };
+        assert_eq!(labels(book.search("")), ["alice", "bob", "carol"]);
+        assert!(book.mark_used(&bob, 100));
+        assert!(book.mark_used(ALICE, 50));
+        assert!(!book.mark_used(&self::bob(), 100));
+        book.set_favorite("carol", true).unwrap();
+        assert_eq!(labels(book.search("")), ["carol", "bob", "alice"]);
+        assert_eq!(labels(book.search("@BO")), ["bob"]);
+        assert_eq!(labels(book.search(&ALICE[..10])), ["alice"]);
+        assert_eq!(labels(book.recent(1)), ["bob"]);
+
+        let updated = book.update("bob", "robert", &bob).unwrap();
+        assert_eq!(updated.last_used, Some(100));
+        assert_eq!(
+            book.update("robert", "alice", &bob),
+            Err(AddressBookError::DuplicateLabel("alice".to_string()))
+        );
+        assert_eq!(
+            book.set_favorite("bob", true),
+            Err(AddressBookError::ContactNotFound("bob".to_string()))
+        );
+
+        // Contacts saved before favorites existed still load
+        let old: AddressBook =
+            serde_json::from_str(&format!(r#"[{{"label":"alice","address":"{}"}}]"#, ALICE))
+                .unwrap();
+        assert!(!old.contacts()[0].favorite);
+    }
+}
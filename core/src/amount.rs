@@ -0,0 +1,219 @@
+//! MINA amounts
+//!
+//! Amounts are stored as an integer number of nanomina (1 MINA = 10^9
+//! nanomina), the unit used on chain. Parsing and formatting of decimal MINA
+//! strings is done on the digits directly, never through floating point.
+//!
+//! In JSON, amounts are nanomina decimal strings, as in the daemon GraphQL API.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+/// Number of decimal places of one MINA
+pub const DECIMALS: usize = 9;
+
+/// Nanomina in one MINA
+pub const NANOMINA_PER_MINA: u64 = 1_000_000_000;
+
+/// Errors that can occur while parsing an amount
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    #[error("Amount is empty")]
+    Empty,
+    #[error("Invalid amount '{0}'")]
+    Invalid(String),
+    #[error("Too many decimal places: MINA has at most {DECIMALS}")]
+    TooManyDecimals,
+    #[error("Amount overflows")]
+    Overflow,
+}
+
+#[cfg(feature = "tsify")]
+#[wasm_bindgen::prelude::wasm_bindgen(typescript_custom_section)]
+const TS_AMOUNT: &str = r#"/** An amount of MINA, as a nanomina decimal string */
+export type Amount = string;"#;
+
+/// An amount of MINA, in nanomina
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    /// Zero MINA
+    pub const ZERO: Amount = Amount(0);
+
+    /// Create an amount from nanomina
+    pub const fn from_nanomina(nanomina: u64) -> Self {
+        Self(nanomina)
+    }
+
+    /// Create an amount from whole MINA, or `None` on overflow
+    pub const fn from_mina(mina: u64) -> Option<Self> {
+        match mina.checked_mul(NANOMINA_PER_MINA) {
+            Some(n) => Some(Self(n)),
+            None => None,
+        }
+    }
+
+    /// The amount in nanomina
+    pub const fn as_nanomina(self) -> u64 {
+        self.0
+    }
+
+    /// Add two amounts, or `None` on overflow
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Subtract two amounts, or `None` if the result would be negative
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    /// Parse a nanomina integer string
+    pub fn from_nanomina_str(s: &str) -> Result<Self, AmountError> {
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AmountError::Invalid(s.to_string()));
+        }
+        s.parse().map(Amount).map_err(|_| AmountError::Overflow)
+    }
+}
+
+/// Formats as decimal MINA without trailing zeros, e.g. `1.5` or `0.000000001`
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / NANOMINA_PER_MINA;
+        let frac = self.0 % NANOMINA_PER_MINA;
+        // `pad` honours width and alignment, e.g. `{:>16}`
+        if frac == 0 {
+            return f.pad(&whole.to_string());
+        }
+        let frac = format!("{:0width$}", frac, width = DECIMALS);
+        f.pad(&format!("{}.{}", whole, frac.trim_end_matches('0')))
+    }
+}
+
+/// Parses decimal MINA, e.g. `1.5`, `0.25` or `100`
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && frac.is_empty())
+            || !is_digits(whole)
+            || !is_digits(frac)
+            || (s.contains('.') && frac.is_empty())
+        {
+            return Err(AmountError::Invalid(s.to_string()));
+        }
+        if frac.len() > DECIMALS {
+            return Err(AmountError::TooManyDecimals);
+        }
+
+        let whole: u64 = match whole {
+            "" => 0,
+            w => w.parse().map_err(|_| AmountError::Overflow)?,
+        };
+        let frac: u64 = match frac {
+            "" => 0,
+            f => format!("{:0<width$}", f, width = DECIMALS)
+                .parse()
+                .map_err(|_| AmountError::Invalid(s.to_string()))?,
+        };
+        whole
+            .checked_mul(NANOMINA_PER_MINA)
+            .and_then(|n| n.checked_add(frac))
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+/// Accepts nanomina as a decimal string or a JSON number
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u64),
+            String(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(n) => Ok(Amount(n)),
+            Raw::String(s) => Amount::from_nanomina_str(&s).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        for (text, nanomina) in [
+            ("1.5", 1_500_000_000),
+            ("0", 0),
+            ("100", 100_000_000_000),
+            ("0.000000001", 1),
+            ("18446744073.709551615", u64::MAX),
+        ] {
+            let amount: Amount = text.parse().unwrap();
+            assert_eq!(amount.as_nanomina(), nanomina);
+            assert_eq!(amount.to_string(), text);
+        }
+        assert_eq!("0.50".parse::<Amount>().unwrap().to_string(), "0.5");
+        assert_eq!(".5".parse::<Amount>().unwrap().as_nanomina(), 500_000_000);
+        assert_eq!(
+            format!("{:>5}", Amount::from_nanomina(1_500_000_000)),
+            "  1.5"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!("".parse::<Amount>(), Err(AmountError::Empty));
+        assert_eq!(
+            "0.0000000001".parse::<Amount>(),
+            Err(AmountError::TooManyDecimals)
+        );
+        assert_eq!(
+            "18446744073.709551616".parse::<Amount>(),
+            Err(AmountError::Overflow)
+        );
+        for invalid in ["-1", "1.", ".", "1e9", "1,5", "1.2.3"] {
+            assert!(invalid.parse::<Amount>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let amount = Amount::from_nanomina(1_500_000_000);
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "\"1500000000\"");
+        assert_eq!(
+            serde_json::from_str::<Amount>("\"1500000000\"").unwrap(),
+            amount
+        );
+        assert_eq!(
+            serde_json::from_str::<Amount>("1500000000").unwrap(),
+            amount
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Audit log of signed transactions
+//!
+//! Every signed transaction can be recorded as an `AuditEntry`: when it was
+//! signed, what it does, and a fingerprint of the signing key. Nothing secret
+//! is recorded. Entries are hash-chained: each one carries the hash of the
+//! previous entry, and its own hash covers all its fields, so that editing,
+//! removing or reordering entries is detected by `AuditLog::verify`.
+//!
+//! A log is stored as JSON lines, one entry per line, and is only ever
+//! appended to.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::amount::Amount;
+use crate::transaction::{SignedTransaction, TransactionKind};
+
+/// `previous_hash` of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Errors that can occur while reading or verifying a log
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AuditError {
+    #[error("Invalid entry on line {line}: {reason}")]
+    InvalidEntry { line: usize, reason: String },
+    #[error("Entry {sequence} is out of sequence or does not follow the previous entry")]
+    BrokenChain { sequence: u64 },
+    #[error("Entry {sequence} was modified after it was written")]
+    TamperedEntry { sequence: u64 },
+}
+
+pub type Result<T> = std::result::Result<T, AuditError>;
+
+/// A signed transaction, as recorded in the log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Position in the log, from 0
+    pub sequence: u64,
+    /// When the transaction was signed, as a Unix timestamp in seconds
+    pub timestamp: u64,
+    pub kind: TransactionKind,
+    pub from: String,
+    /// Receiver of the payment, or new delegate
+    pub to: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    pub fee: Amount,
+    pub nonce: u32,
+    /// SHA-256 of the signed transaction JSON, in hex
+    pub transaction_digest: String,
+    /// Fingerprint of the public key that signed
+    pub key_fingerprint: String,
+    pub previous_hash: String,
+    /// SHA-256 of the entry with an empty `hash`, in hex
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Record `signed`, after the entry `previous` if the log is not empty
+    pub fn new(signed: &SignedTransaction, timestamp: u64, previous: Option<&AuditEntry>) -> Self {
+        let transaction = &signed.data;
+        let json = serde_json::to_vec(signed).expect("signed transactions serialize to JSON");
+        let mut entry = Self {
+            sequence: previous.map_or(0, |p| p.sequence + 1),
+            timestamp,
+            kind: transaction.kind(),
+            from: transaction.from.clone(),
+            to: transaction.to.clone(),
+            amount: transaction.amount,
+            fee: transaction.fee,
+            nonce: transaction.nonce,
+            transaction_digest: hex::encode(Sha256::digest(json)),
+            key_fingerprint: key_fingerprint(&signed.public_key),
+            previous_hash: previous.map_or(GENESIS_HASH.to_string(), |p| p.hash.clone()),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        entry
+    }
+
+    /// Encode the entry as one line of JSON, without the newline
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("audit entries serialize to JSON")
+    }
+
+    fn compute_hash(&self) -> String {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&unhashed).expect("audit entries serialize to JSON");
+        hex::encode(Sha256::digest(json))
+    }
+}
+
+/// Fingerprint of a public key: the first 8 bytes of the SHA-256 of its
+/// address, in hex
+pub fn key_fingerprint(address: &str) -> String {
+    hex::encode(&Sha256::digest(address.as_bytes())[..8])
+}
+
+/// Entries of an audit log, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a log from JSON lines, without verifying it
+    pub fn parse(text: &str) -> Result<Self> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| AuditError::InvalidEntry {
+                    line: index + 1,
+                    reason: e.to_string(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Record a signed transaction, returning the entry to append to storage
+    pub fn append(&mut self, signed: &SignedTransaction, timestamp: u64) -> &AuditEntry {
+        let entry = AuditEntry::new(signed, timestamp, self.entries.last());
+        self.entries.push(entry);
+        &self.entries[self.entries.len() - 1]
+    }
+
+    /// Entries, oldest first
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Check that every entry is intact and follows the one before it
+    ///
+    /// Removing entries from the end of the log cannot be detected from the
+    /// log alone; compare the last hash with one kept elsewhere for that.
+    pub fn verify(&self) -> Result<()> {
+        let mut previous: Option<&AuditEntry> = None;
+        for entry in &self.entries {
+            let expected_sequence = previous.map_or(0, |p| p.sequence + 1);
+            let expected_previous = previous.map_or(GENESIS_HASH, |p| p.hash.as_str());
+            if entry.sequence != expected_sequence || entry.previous_hash != expected_previous {
+                return Err(AuditError::BrokenChain {
+                    sequence: entry.sequence,
+                });
+            }
+            if !crate::secret::ct_eq(entry.compute_hash().as_bytes(), entry.hash.as_bytes()) {
+                return Err(AuditError::TamperedEntry {
+                    sequence: entry.sequence,
+                });
+            }
+            previous = Some(entry);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+    use crate::wallet::Wallet;
+    use mina_signer::NetworkId;
+
+    fn log() -> (Wallet, AuditLog) {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let mut log = AuditLog::new();
+        for nonce in 0..3 {
+            let payment = Transaction::payment(
+                &wallet.address(),
+                &wallet.address(),
+                "1".parse().unwrap(),
+                "0.1".parse().unwrap(),
+                nonce,
+            );
+            log.append(
+                &wallet.sign_transaction(&payment).unwrap(),
+                100 + u64::from(nonce),
+            );
+        }
+        (wallet, log)
+    }
+
+    #[test]
+    fn test_append_and_verify() {
+        let (wallet, log) = log();
+        assert_eq!(log.entries()[0].previous_hash, GENESIS_HASH);
+        assert_eq!(log.entries()[2].sequence, 2);
+        assert_eq!(log.entries()[2].previous_hash, log.entries()[1].hash);
+        assert_eq!(
+            log.entries()[0].key_fingerprint,
+            key_fingerprint(&wallet.address())
+        );
+
+        let text: String = log.entries().iter().map(|e| e.to_line() + "\n").collect();
+        assert!(!text.contains(wallet.secret_key_hex().as_str()));
+        let parsed = AuditLog::parse(&text).unwrap();
+        assert_eq!(parsed, log);
+        assert_eq!(parsed.verify(), Ok(()));
+        assert!(matches!(
+            AuditLog::parse("{}"),
+            Err(AuditError::InvalidEntry { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_detects_tampering() {
+        let (_, log) = log();
+
+        let mut edited = log.clone();
+        edited.entries[1].amount = Some("1000".parse().unwrap());
+        assert_eq!(
+            edited.verify(),
+            Err(AuditError::TamperedEntry { sequence: 1 })
+        );
+
+        let mut removed = log.clone();
+        removed.entries.remove(1);
+        assert_eq!(
+            removed.verify(),
+            Err(AuditError::BrokenChain { sequence: 2 })
+        );
+
+        let mut reordered = log;
+        reordered.entries.swap(0, 1);
+        assert_eq!(
+            reordered.verify(),
+            Err(AuditError::BrokenChain { sequence: 1 })
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Encrypted backups of the wallet state
+//!
+//! A backup archive carries everything needed to restore the wallet on
+//! another machine, the keystore and the address book, as a single JSON
+//! document. The contents are encrypted with a passphrase like keystore
+//! entries (Argon2id and ChaCha20-Poly1305), with the archive header as
+//! associated data. Keys stay encrypted with their own passphrases inside the
+//! archive; the archive passphrase also hides their addresses and labels.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::address_book::AddressBook;
+use crate::keystore::{EncryptedSecret, KdfCost, Keystore, KeystoreError};
+
+/// Value of the `format` field of an archive
+pub const BACKUP_FORMAT: &str = "mina-wallet-backup";
+
+/// Version of the archive format
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Errors that can occur while creating or opening a backup
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BackupError {
+    #[error("Not a wallet backup: {0}")]
+    InvalidArchive(String),
+    #[error("Unsupported backup version {0}, expected {BACKUP_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+}
+
+pub type Result<T> = std::result::Result<T, BackupError>;
+
+/// What a backup restores
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupContents {
+    pub keystore: Keystore,
+    /// Missing from backups made before the address book existed
+    #[serde(default)]
+    pub address_book: AddressBook,
+}
+
+/// A passphrase-encrypted backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupArchive {
+    pub format: String,
+    pub version: u32,
+    /// When the backup was made, as a Unix timestamp in seconds
+    pub created_at: u64,
+    /// `BackupContents` as encrypted JSON
+    pub contents: EncryptedSecret,
+}
+
+impl BackupArchive {
+    /// Encrypt `contents` with `passphrase`
+    pub fn seal(
+        contents: &BackupContents,
+        passphrase: &str,
+        kdf: KdfCost,
+        created_at: u64,
+    ) -> Result<Self> {
+        let json = Zeroizing::new(
+            serde_json::to_vec(contents).expect("backup contents serialize to JSON"),
+        );
+        let contents = EncryptedSecret::encrypt(
+            &json,
+            passphrase,
+            header(BACKUP_VERSION, created_at).as_bytes(),
+            kdf,
+        )?;
+        Ok(Self {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_VERSION,
+            created_at,
+            contents,
+        })
+    }
+
+    /// Decrypt the contents with `passphrase`
+    pub fn open(&self, passphrase: &str) -> Result<BackupContents> {
+        let json = self
+            .contents
+            .decrypt(passphrase, header(self.version, self.created_at).as_bytes())?;
+        serde_json::from_slice(&json).map_err(|e| BackupError::InvalidArchive(e.to_string()))
+    }
+
+    /// Decode an archive, refusing other formats and unknown versions
+    pub fn from_json(json: &str) -> Result<Self> {
+        let archive: Self =
+            serde_json::from_str(json).map_err(|e| BackupError::InvalidArchive(e.to_string()))?;
+        if archive.format != BACKUP_FORMAT {
+            return Err(BackupError::InvalidArchive(format!(
+                "unknown format '{}'",
+                archive.format
+            )));
+        }
+        if archive.version != BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion(archive.version));
+        }
+        Ok(archive)
+    }
+
+    /// Encode the archive as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("archives serialize to JSON")
+    }
+}
+
+/// Associated data binding the contents to the archive header
+fn header(version: u32, created_at: u64) -> String {
+    format!("{}/{}/{}", BACKUP_FORMAT, version, created_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+    use mina_signer::NetworkId;
+
+    /// Cheap parameters, so that tests run fast
+    const TEST_COST: KdfCost = KdfCost {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn contents() -> (Wallet, BackupContents) {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let mut keystore = Keystore::new().with_kdf_cost(TEST_COST);
+        keystore.add(&wallet, "key pass", Some("main"), 1).unwrap();
+        let mut address_book = AddressBook::new();
+        address_book.add("self", &wallet.address()).unwrap();
+        (
+            wallet,
+            BackupContents {
+                keystore,
+                address_book,
+            },
+        )
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let (wallet, contents) = contents();
+        let archive = BackupArchive::seal(&contents, "backup pass", TEST_COST, 100).unwrap();
+        assert!(!archive.to_json().contains(&wallet.address()));
+
+        let restored = BackupArchive::from_json(&archive.to_json())
+            .unwrap()
+            .open("backup pass")
+            .unwrap();
+        assert_eq!(restored.keystore.keys(), contents.keystore.keys());
+        assert_eq!(restored.address_book, contents.address_book);
+        assert!(
+            restored
+                .keystore
+                .unlock("main", "key pass", NetworkId::TESTNET)
+                .unwrap()
+                .secret_eq(&wallet)
+        );
+
+        assert_eq!(
+            archive.open("key pass"),
+            Err(BackupError::Keystore(KeystoreError::DecryptionFailed))
+        );
+        // The header is authenticated along with the contents
+        let mut tampered = archive.clone();
+        tampered.created_at = 101;
+        assert!(tampered.open("backup pass").is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_documents() {
+        let (_, contents) = contents();
+        let archive = BackupArchive::seal(&contents, "pass", TEST_COST, 100).unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&archive.to_json()).unwrap();
+
+        json["version"] = 2.into();
+        assert_eq!(
+            BackupArchive::from_json(&json.to_string()),
+            Err(BackupError::UnsupportedVersion(2))
+        );
+        json["format"] = "keystore".into();
+        assert!(matches!(
+            BackupArchive::from_json(&json.to_string()),
+            Err(BackupError::InvalidArchive(_))
+        ));
+        assert!(BackupArchive::from_json("{}").is_err());
+    }
+}
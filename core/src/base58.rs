@@ -0,0 +1,123 @@
+//! Base58Check encoding
+//!
+//! Mina encodes addresses, secret keys, signatures and memos as
+//! `base58(version || payload || checksum)`, where the checksum is the first
+//! four bytes of `SHA-256(SHA-256(version || payload))`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Version byte of addresses, Base58-encoded compressed public keys
+pub const ADDRESS_VERSION: u8 = 0xcb;
+
+/// Version byte of Base58-encoded secret keys
+pub const SECRET_KEY_VERSION: u8 = 0x5a;
+
+/// Version byte of Base58-encoded signatures
+pub const SIGNATURE_VERSION: u8 = 0x9a;
+
+/// Version byte of Base58-encoded transaction memos
+pub const MEMO_VERSION: u8 = 0x14;
+
+/// Number of checksum bytes appended before encoding
+const CHECKSUM_LEN: usize = 4;
+
+/// Errors that can occur while decoding Base58Check data
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Base58Error {
+    #[error("Invalid Base58: {0}")]
+    InvalidBase58(String),
+    #[error("Input is too short")]
+    TooShort,
+    #[error("Checksum mismatch")]
+    InvalidChecksum,
+    #[error("Unexpected version byte: expected {expected:#04x}, got {got:#04x}")]
+    InvalidVersion { expected: u8, got: u8 },
+}
+
+/// Encode a payload with its version byte and checksum
+pub fn encode_check(version: u8, payload: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(1 + payload.len() + CHECKSUM_LEN);
+    bytes.push(version);
+    bytes.extend_from_slice(payload);
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    bs58::encode(bytes).into_string()
+}
+
+/// Decode Base58Check data, returning the version byte and payload
+pub fn decode_check_any(encoded: &str) -> Result<(u8, Vec<u8>), Base58Error> {
+    let bytes = bs58::decode(encoded)
+        .into_vec()
+        .map_err(|e| Base58Error::InvalidBase58(e.to_string()))?;
+    if bytes.len() < 1 + CHECKSUM_LEN {
+        return Err(Base58Error::TooShort);
+    }
+
+    let (body, check) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if checksum(body) != check {
+        return Err(Base58Error::InvalidChecksum);
+    }
+    Ok((body[0], body[1..].to_vec()))
+}
+
+/// Decode Base58Check data, checking the version byte
+pub fn decode_check(encoded: &str, version: u8) -> Result<Vec<u8>, Base58Error> {
+    let (got, payload) = decode_check_any(encoded)?;
+    if got != version {
+        return Err(Base58Error::InvalidVersion {
+            expected: version,
+            got,
+        });
+    }
+    Ok(payload)
+}
+
+/// What a version byte encodes: "address", "secretKey", "signature" or
+/// "memo"; `None` for versions this wallet does not use
+pub fn version_name(version: u8) -> Option<&'static str> {
+    match version {
+        ADDRESS_VERSION => Some("address"),
+        SECRET_KEY_VERSION => Some("secretKey"),
+        SIGNATURE_VERSION => Some("signature"),
+        MEMO_VERSION => Some("memo"),
+        _ => None,
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::digest(Sha256::digest(bytes));
+    let mut out = [0u8; CHECKSUM_LEN];
+    out.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let encoded = encode_check(0x42, b"payload");
+        assert_eq!(decode_check(&encoded, 0x42).unwrap(), b"payload");
+        assert_eq!(
+            decode_check(&encoded, 0x43),
+            Err(Base58Error::InvalidVersion {
+                expected: 0x43,
+                got: 0x42
+            })
+        );
+    }
+
+    #[test]
+    fn test_mina_address_checksum() {
+        let address = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+        let (version, payload) = decode_check_any(address).unwrap();
+        assert_eq!(version, ADDRESS_VERSION);
+        assert_eq!(version_name(version), Some("address"));
+        assert_eq!(encode_check(version, &payload), address);
+        assert_eq!(version_name(0x42), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! Resumable batch payments
+//!
+//! A batch pays many receivers from one account, as for a payroll or an
+//! airdrop. `BatchProgress` records the rows that were broadcast, with their
+//! nonce and transaction hash, so that a batch stopped by a node failure can
+//! be run again without paying anyone twice. Rows are recorded with a digest
+//! of their content: a row edited after it was sent is reported rather than
+//! skipped or paid again.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::amount::Amount;
+use crate::transaction::Transaction;
+
+/// Errors that can occur while reading the progress of a batch
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    #[error("Invalid batch progress: {0}")]
+    InvalidProgress(String),
+}
+
+pub type Result<T> = std::result::Result<T, BatchError>;
+
+/// A payment of a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchRow {
+    pub to: String,
+    pub amount: Amount,
+    pub fee: Amount,
+    pub memo: String,
+}
+
+impl BatchRow {
+    /// SHA-256 of the receiver, amount, fee and memo, in hex
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            self.to.as_str(),
+            &self.amount.as_nanomina().to_string(),
+            &self.fee.as_nanomina().to_string(),
+            &self.memo,
+        ] {
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// The payment of this row from `from`
+    pub fn payment(&self, from: &str, nonce: u32) -> Transaction {
+        Transaction::payment(from, &self.to, self.amount, self.fee, nonce).with_memo(&self.memo)
+    }
+}
+
+/// A row that was broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SentRow {
+    /// `BatchRow::digest` of the row when it was sent
+    pub digest: String,
+    pub nonce: u32,
+    pub hash: String,
+    /// When the row was broadcast, as a Unix timestamp in seconds
+    pub sent_at: u64,
+}
+
+/// Where a row of a batch stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowStatus<'a> {
+    /// Not sent yet
+    Pending,
+    /// Already broadcast
+    Sent(&'a SentRow),
+    /// Sent, but edited since: neither skipped nor sent again
+    Changed(&'a SentRow),
+}
+
+/// Rows of a batch that were broadcast, by row number
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchProgress {
+    sent: BTreeMap<usize, SentRow>,
+}
+
+impl BatchProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| BatchError::InvalidProgress(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("batch progress serializes to JSON")
+    }
+
+    /// Status of row `number` of the batch
+    pub fn status(&self, number: usize, row: &BatchRow) -> RowStatus<'_> {
+        match self.sent.get(&number) {
+            None => RowStatus::Pending,
+            Some(sent) if sent.digest == row.digest() => RowStatus::Sent(sent),
+            Some(sent) => RowStatus::Changed(sent),
+        }
+    }
+
+    /// Record that row `number` was broadcast
+    pub fn record(&mut self, number: usize, row: &BatchRow, nonce: u32, hash: &str, now: u64) {
+        self.sent.insert(
+            number,
+            SentRow {
+                digest: row.digest(),
+                nonce,
+                hash: hash.to_string(),
+                sent_at: now,
+            },
+        );
+    }
+
+    /// Number of rows broadcast
+    pub fn sent_count(&self) -> usize {
+        self.sent.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECEIVER: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn row(amount: &str) -> BatchRow {
+        BatchRow {
+            to: RECEIVER.to_string(),
+            amount: amount.parse().unwrap(),
+            fee: "0.01".parse().unwrap(),
+            memo: "salary".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_resume() {
+        let mut progress = BatchProgress::new();
+        assert_eq!(progress.status(1, &row("1.5")), RowStatus::Pending);
+
+        progress.record(1, &row("1.5"), 7, "5Jthehash", 100);
+        let restored = BatchProgress::from_json(&progress.to_json()).unwrap();
+        assert_eq!(restored, progress);
+        assert_eq!(restored.sent_count(), 1);
+        match restored.status(1, &row("1.5")) {
+            RowStatus::Sent(sent) => assert_eq!((sent.nonce, sent.hash.as_str()), (7, "5Jthehash")),
+            status => panic!("unexpected status {:?}", status),
+        }
+        assert_eq!(restored.status(2, &row("1.5")), RowStatus::Pending);
+    }
+
+    #[test]
+    fn test_changed_rows() {
+        let mut progress = BatchProgress::new();
+        progress.record(1, &row("1.5"), 7, "5Jthehash", 100);
+        assert!(matches!(
+            progress.status(1, &row("2")),
+            RowStatus::Changed(_)
+        ));
+        let mut memo = row("1.5");
+        memo.memo = "bonus".to_string();
+        assert!(matches!(progress.status(1, &memo), RowStatus::Changed(_)));
+
+        assert_eq!(row("1.5").payment("B62qfrom", 3).nonce, 3);
+        assert!(BatchProgress::from_json("[]").is_err());
+    }
+}
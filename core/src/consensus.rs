@@ -0,0 +1,163 @@
+//! Slot and epoch arithmetic
+//!
+//! Mina time is divided into slots of 3 minutes, grouped into epochs of
+//! 7140 slots. Transactions expire at a global slot (`valid_until`), and
+//! delegations take effect at epoch boundaries, so the wallet converts
+//! between wall-clock time and slots.
+//!
+//! Global slots count from the original genesis of each network and keep
+//! counting across hard forks. Like `time`, nothing here reads the clock:
+//! callers pass Unix timestamps in seconds.
+
+use mina_signer::NetworkId;
+use serde::{Deserialize, Serialize};
+
+/// Genesis of mainnet, 2021-03-17T00:00:00Z
+pub const MAINNET_GENESIS_TIMESTAMP: u64 = 1_615_939_200;
+
+/// Genesis of devnet after the Berkeley upgrade, 2024-04-09T21:00:00Z
+pub const DEVNET_GENESIS_TIMESTAMP: u64 = 1_712_696_400;
+
+/// Duration of a slot, in seconds
+pub const SLOT_DURATION_SECS: u64 = 180;
+
+/// Number of slots in an epoch
+pub const SLOTS_PER_EPOCH: u32 = 7140;
+
+/// Timing parameters of a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusConstants {
+    /// Start of global slot 0, as a Unix timestamp in seconds
+    pub genesis_timestamp: u64,
+    pub slot_duration_secs: u64,
+    pub slots_per_epoch: u32,
+}
+
+/// Position of a slot in its epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotInfo {
+    /// Global slot since genesis
+    pub slot: u32,
+    pub epoch: u32,
+    /// Slot within the epoch, from 0
+    pub slot_in_epoch: u32,
+    /// Start of the slot, as a Unix timestamp in seconds
+    pub slot_start: u64,
+    /// Start of the epoch, as a Unix timestamp in seconds
+    pub epoch_start: u64,
+    /// Start of the next epoch, as a Unix timestamp in seconds
+    pub next_epoch_start: u64,
+}
+
+impl ConsensusConstants {
+    /// Constants of a network
+    pub fn for_network(network: &NetworkId) -> Self {
+        let genesis_timestamp = match network {
+            NetworkId::MAINNET => MAINNET_GENESIS_TIMESTAMP,
+            NetworkId::TESTNET => DEVNET_GENESIS_TIMESTAMP,
+        };
+        Self {
+            genesis_timestamp,
+            slot_duration_secs: SLOT_DURATION_SECS,
+            slots_per_epoch: SLOTS_PER_EPOCH,
+        }
+    }
+
+    /// Global slot at a timestamp, or `None` before genesis
+    pub fn slot_at(&self, timestamp: u64) -> Option<u32> {
+        let elapsed = timestamp.checked_sub(self.genesis_timestamp)?;
+        u32::try_from(elapsed / self.slot_duration_secs).ok()
+    }
+
+    /// Start of a global slot, as a Unix timestamp
+    pub fn slot_start(&self, slot: u32) -> u64 {
+        self.genesis_timestamp + u64::from(slot) * self.slot_duration_secs
+    }
+
+    /// Epoch of a global slot
+    pub fn epoch_of(&self, slot: u32) -> u32 {
+        slot / self.slots_per_epoch
+    }
+
+    /// First global slot of an epoch
+    pub fn epoch_start_slot(&self, epoch: u32) -> u32 {
+        epoch.saturating_mul(self.slots_per_epoch)
+    }
+
+    /// Position of a global slot in its epoch
+    pub fn slot_info(&self, slot: u32) -> SlotInfo {
+        let epoch = self.epoch_of(slot);
+        let epoch_start_slot = self.epoch_start_slot(epoch);
+        SlotInfo {
+            slot,
+            epoch,
+            slot_in_epoch: slot - epoch_start_slot,
+            slot_start: self.slot_start(slot),
+            epoch_start: self.slot_start(epoch_start_slot),
+            next_epoch_start: self.slot_start(self.epoch_start_slot(epoch + 1)),
+        }
+    }
+
+    /// First epoch in which a delegation included at `slot` counts for
+    /// block production
+    ///
+    /// The staking ledger of an epoch is the ledger as it was at the end of
+    /// the epoch two before it.
+    pub fn delegation_effective_epoch(&self, slot: u32) -> u32 {
+        self.epoch_of(slot) + 2
+    }
+
+    /// Slot at which a transaction signed at `now` should expire to stay
+    /// valid for at least `duration_secs`, for `Transaction::with_valid_until`
+    ///
+    /// Returns `None` before genesis, or if the slot would not fit.
+    pub fn valid_until(&self, now: u64, duration_secs: u64) -> Option<u32> {
+        // The transaction can be included until the end of that slot
+        self.slot_at(now.checked_add(duration_secs)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_conversions() {
+        let mainnet = ConsensusConstants::for_network(&NetworkId::MAINNET);
+        assert_eq!(mainnet.slot_at(MAINNET_GENESIS_TIMESTAMP - 1), None);
+        assert_eq!(mainnet.slot_at(MAINNET_GENESIS_TIMESTAMP + 179), Some(0));
+
+        // The Berkeley hard fork genesis, 2024-06-05T00:00:00Z, was slot 564480
+        assert_eq!(mainnet.slot_at(1_717_545_600), Some(564_480));
+        assert_eq!(mainnet.slot_start(564_480), 1_717_545_600);
+        assert_eq!(mainnet.epoch_of(564_480), 79);
+    }
+
+    #[test]
+    fn test_slot_info_and_valid_until() {
+        let mainnet = ConsensusConstants::for_network(&NetworkId::MAINNET);
+        let info = mainnet.slot_info(SLOTS_PER_EPOCH + 10);
+        assert_eq!(info.epoch, 1);
+        assert_eq!(info.slot_in_epoch, 10);
+        assert_eq!(info.epoch_start, MAINNET_GENESIS_TIMESTAMP + 7140 * 180);
+        assert_eq!(
+            info.next_epoch_start - info.epoch_start,
+            u64::from(SLOTS_PER_EPOCH) * SLOT_DURATION_SECS
+        );
+
+        let now = mainnet.slot_start(1000);
+        assert_eq!(mainnet.valid_until(now, 3600), Some(1020));
+        assert_eq!(mainnet.valid_until(now, 3779), Some(1020));
+        assert_eq!(mainnet.valid_until(now, 3780), Some(1021));
+        assert_eq!(mainnet.valid_until(0, 60), None);
+
+        // Included in epoch 1, in the staking ledger of epoch 3
+        assert_eq!(mainnet.delegation_effective_epoch(SLOTS_PER_EPOCH + 10), 3);
+        assert_eq!(
+            mainnet.delegation_effective_epoch(2 * SLOTS_PER_EPOCH - 1),
+            3
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Conversions between the encodings of keys, fields and memos
+//!
+//! Each conversion detects the encoding of its input and returns the other one:
+//! - Secret keys: hex (64 characters) and Base58 (52 characters)
+//! - Public keys: raw bytes and addresses. Raw bytes are the uncompressed
+//!   point, `x || y` as 32-byte little-endian field elements, in hex
+//! - Field elements: decimal and `0x`-prefixed big-endian hex
+//! - Memos: text and the Base58 form used by the daemon and explorers
+
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, PrimeField};
+use mina_curves::pasta::Pallas;
+use mina_signer::PubKey;
+use o1_utils::field_helpers::FieldHelpers;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::fields::Fp;
+use crate::oracle::parse_field;
+use crate::secret::{self, SecretFormat, SecretParseError};
+use crate::transaction::{decode_memo_base58, encode_memo_base58};
+
+/// Length of a field element, in bytes
+const FIELD_BYTES: usize = 32;
+
+/// Errors that can occur during a conversion
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConvertError {
+    #[error(transparent)]
+    SecretKey(#[from] SecretParseError),
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error("Invalid field element: {0}")]
+    InvalidField(String),
+    #[error("Invalid memo: {0}")]
+    InvalidMemo(String),
+}
+
+pub type Result<T> = std::result::Result<T, ConvertError>;
+
+/// Convert a secret key from hex to Base58, or from Base58 to hex
+pub fn convert_secret_key(input: &str) -> Result<Zeroizing<String>> {
+    let (key, format) = secret::parse_strict(input)?;
+    Ok(Zeroizing::new(match format {
+        SecretFormat::Hex => key.to_base58(),
+        SecretFormat::Base58 => key.to_hex(),
+    }))
+}
+
+/// Convert an address to raw public key bytes, or raw bytes to an address
+pub fn convert_public_key(input: &str) -> Result<String> {
+    if input.starts_with("B62") {
+        let public_key = PubKey::from_address(input)
+            .map_err(|e| ConvertError::InvalidPublicKey(format!("{:?}", e)))?;
+        let point = public_key.point();
+        return Ok(hex::encode(
+            [point.x.to_bytes(), point.y.to_bytes()].concat(),
+        ));
+    }
+
+    let bytes = hex::decode(input.strip_prefix("0x").unwrap_or(input))
+        .map_err(|e| ConvertError::InvalidPublicKey(e.to_string()))?;
+    if bytes.len() != 2 * FIELD_BYTES {
+        return Err(ConvertError::InvalidPublicKey(format!(
+            "expected an address or {} bytes, got {} bytes",
+            2 * FIELD_BYTES,
+            bytes.len()
+        )));
+    }
+    let coordinate = |bytes: &[u8]| {
+        Fp::from_bytes(bytes).map_err(|e| ConvertError::InvalidPublicKey(format!("{:?}", e)))
+    };
+    let point = Pallas::new_unchecked(
+        coordinate(&bytes[..FIELD_BYTES])?,
+        coordinate(&bytes[FIELD_BYTES..])?,
+    );
+    if point.is_zero() || !point.is_on_curve() {
+        return Err(ConvertError::InvalidPublicKey(
+            "the point is not on the curve".to_string(),
+        ));
+    }
+    Ok(PubKey::from_point_unsafe(point).into_address())
+}
+
+/// Convert a field element from decimal to `0x` hex, or from `0x` hex to decimal
+pub fn convert_field(input: &str) -> Result<String> {
+    if input.starts_with("0x") {
+        return Ok(parse_field_hex(input)?.to_string());
+    }
+    let field = parse_field(input).ok_or_else(|| {
+        ConvertError::InvalidField(format!("'{}' is neither decimal nor 0x hex", input))
+    })?;
+    Ok(field_to_hex(&field))
+}
+
+/// Parse a field element from big-endian hex, `0x` prefix optional
+pub fn parse_field_hex(input: &str) -> Result<Fp> {
+    let digits = input.strip_prefix("0x").unwrap_or(input);
+    let even = if digits.len() % 2 == 1 {
+        format!("0{}", digits)
+    } else {
+        digits.to_string()
+    };
+    let mut bytes = hex::decode(even).map_err(|e| ConvertError::InvalidField(e.to_string()))?;
+    if bytes.len() > FIELD_BYTES {
+        return Err(ConvertError::InvalidField(format!(
+            "{} bytes is longer than a field element",
+            bytes.len()
+        )));
+    }
+    bytes.splice(0..0, std::iter::repeat_n(0, FIELD_BYTES - bytes.len()));
+    let field = Fp::from_be_bytes_mod_order(&bytes);
+    if field.into_bigint().to_bytes_be() != bytes {
+        return Err(ConvertError::InvalidField(format!(
+            "{} is not smaller than the field order",
+            input
+        )));
+    }
+    Ok(field)
+}
+
+/// A field element as `0x` and 64 big-endian hex digits
+pub fn field_to_hex(field: &Fp) -> String {
+    format!("0x{}", hex::encode(field.into_bigint().to_bytes_be()))
+}
+
+/// Convert a Base58 memo to its text, or a text to a Base58 memo
+///
+/// Any input that decodes as a Base58 memo, checksum included, is taken as one.
+pub fn convert_memo(input: &str) -> Result<String> {
+    if let Some(text) = decode_memo_base58(input) {
+        return Ok(text);
+    }
+    encode_memo_base58(input).map_err(|e| ConvertError::InvalidMemo(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+    use mina_signer::NetworkId;
+
+    #[test]
+    fn test_keys_roundtrip() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let hex = wallet.secret_key_hex();
+        let base58 = wallet.secret_key_base58();
+        assert_eq!(*convert_secret_key(&hex).unwrap(), *base58);
+        assert_eq!(*convert_secret_key(&base58).unwrap(), *hex);
+        assert!(matches!(
+            convert_secret_key("abc"),
+            Err(ConvertError::SecretKey(_))
+        ));
+
+        let raw = convert_public_key(&wallet.address()).unwrap();
+        assert_eq!(raw.len(), 4 * FIELD_BYTES);
+        assert_eq!(convert_public_key(&raw).unwrap(), wallet.address());
+        // Flipping a bit of y moves the point off the curve
+        let mut bytes = hex::decode(&raw).unwrap();
+        bytes[FIELD_BYTES] ^= 1;
+        assert!(matches!(
+            convert_public_key(&hex::encode(bytes)),
+            Err(ConvertError::InvalidPublicKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_fields_and_memos() {
+        assert_eq!(
+            convert_field("255").unwrap(),
+            format!("0x{}ff", "00".repeat(31))
+        );
+        assert_eq!(convert_field("0xff").unwrap(), "255");
+        assert_eq!(convert_field("0xfff").unwrap(), "4095");
+        let max = (-Fp::from(1u64)).to_string();
+        assert_eq!(convert_field(&convert_field(&max).unwrap()).unwrap(), max);
+        assert!(convert_field(&format!("0x{}", "ff".repeat(32))).is_err());
+        assert!(convert_field("-1").is_err());
+        assert_eq!(parse_field_hex("ff").unwrap(), Fp::from(255u64));
+        assert!(parse_field_hex("0xzz").is_err());
+
+        let encoded = convert_memo("hello").unwrap();
+        assert_eq!(encoded, encode_memo_base58("hello").unwrap());
+        assert_eq!(convert_memo(&encoded).unwrap(), "hello");
+        assert!(matches!(
+            convert_memo(&"x".repeat(33)),
+            Err(ConvertError::InvalidMemo(_))
+        ));
+    }
+}
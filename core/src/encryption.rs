@@ -0,0 +1,180 @@
+//! Encryption to Mina addresses
+//!
+//! This module implements an ECIES-style scheme so that notes can be
+//! encrypted to a Mina address and decrypted only by its owner:
+//! - An ephemeral Pallas keypair performs ECDH with the recipient public key
+//! - HKDF-SHA256 derives a one-time key from the shared point
+//! - ChaCha20-Poly1305 encrypts and authenticates the message
+//!
+//! Ciphertexts are hex strings: `version || ephemeral point (x, y) || AEAD output`.
+//! Since every message uses a fresh ephemeral key, the AEAD nonce is fixed to zero.
+
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::UniformRand;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use mina_curves::pasta::{Fp, Fq, Pallas};
+use mina_signer::PubKey;
+use o1_utils::field_helpers::FieldHelpers;
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::wallet::Wallet;
+
+/// Version byte of the ciphertext format
+const VERSION: u8 = 1;
+
+/// HKDF context string binding derived keys to this scheme
+const HKDF_INFO: &[u8] = b"mina-web-wallet/ecies/v1";
+
+/// Length of a serialized field element
+const FIELD_LEN: usize = 32;
+
+/// Length of the header: version byte and uncompressed ephemeral point
+const HEADER_LEN: usize = 1 + 2 * FIELD_LEN;
+
+/// Length of the Poly1305 authentication tag
+const TAG_LEN: usize = 16;
+
+/// Errors that can occur during encryption or decryption
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid ciphertext: {0}")]
+    InvalidCiphertext(String),
+    #[error("Decryption failed: wrong key or tampered ciphertext")]
+    DecryptionFailed,
+}
+
+pub type Result<T> = std::result::Result<T, EncryptionError>;
+
+/// Encrypt a message so that only the owner of `address` can read it
+pub fn encrypt_to_address(address: &str, plaintext: &[u8]) -> Result<String> {
+    let recipient = PubKey::from_address(address)
+        .map_err(|e| EncryptionError::InvalidAddress(format!("{:?}", e)))?;
+
+    let ephemeral = Zeroizing::new(Fq::rand(&mut rand::rngs::OsRng));
+    let ephemeral_point = (Pallas::generator() * *ephemeral).into_affine();
+    let shared = (*recipient.point() * *ephemeral).into_affine();
+
+    let mut output = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    output.push(VERSION);
+    output.extend_from_slice(&ephemeral_point.x.to_bytes());
+    output.extend_from_slice(&ephemeral_point.y.to_bytes());
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(&output, &shared));
+    let sealed = cipher
+        .encrypt(
+            &Nonce::default(),
+            Payload {
+                msg: plaintext,
+                aad: &output,
+            },
+        )
+        .map_err(|_| EncryptionError::InvalidCiphertext("encryption failed".to_string()))?;
+    output.extend_from_slice(&sealed);
+
+    Ok(hex::encode(output))
+}
+
+impl Wallet {
+    /// Decrypt a message produced by `encrypt_to_address` for this wallet
+    pub fn decrypt(&self, ciphertext: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let bytes = hex::decode(ciphertext.trim())
+            .map_err(|e| EncryptionError::InvalidCiphertext(format!("not valid hex: {}", e)))?;
+        if bytes.len() < HEADER_LEN + TAG_LEN {
+            return Err(EncryptionError::InvalidCiphertext(
+                "ciphertext is too short".to_string(),
+            ));
+        }
+        if bytes[0] != VERSION {
+            return Err(EncryptionError::InvalidCiphertext(format!(
+                "unsupported version {}",
+                bytes[0]
+            )));
+        }
+
+        let (header, sealed) = bytes.split_at(HEADER_LEN);
+        let ephemeral_point = parse_point(&header[1..])?;
+        let shared = (ephemeral_point * *self.keypair().secret.scalar()).into_affine();
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(header, &shared));
+        cipher
+            .decrypt(
+                &Nonce::default(),
+                Payload {
+                    msg: sealed,
+                    aad: header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptionError::DecryptionFailed)
+    }
+}
+
+/// Derive the symmetric key from the ciphertext header and the ECDH shared point
+fn derive_key(header: &[u8], shared: &Pallas) -> Key {
+    let shared_x = Zeroizing::new(shared.x.to_bytes());
+    let hkdf = Hkdf::<Sha256>::new(Some(header), &shared_x);
+    let mut key = Key::default();
+    hkdf.expand(HKDF_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// Parse an uncompressed point, rejecting points that are not on the curve
+fn parse_point(bytes: &[u8]) -> Result<Pallas> {
+    let x = Fp::from_bytes(&bytes[..FIELD_LEN])
+        .map_err(|e| EncryptionError::InvalidCiphertext(format!("invalid point: {:?}", e)))?;
+    let y = Fp::from_bytes(&bytes[FIELD_LEN..2 * FIELD_LEN])
+        .map_err(|e| EncryptionError::InvalidCiphertext(format!("invalid point: {:?}", e)))?;
+
+    let point = Pallas::new_unchecked(x, y);
+    if point.is_zero() || !point.is_on_curve() {
+        return Err(EncryptionError::InvalidCiphertext(
+            "ephemeral key is not on the curve".to_string(),
+        ));
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mina_signer::NetworkId;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let ciphertext = encrypt_to_address(&wallet.address(), b"hello mina").unwrap();
+
+        let plaintext = wallet.decrypt(&ciphertext).unwrap();
+        assert_eq!(plaintext.as_slice(), b"hello mina");
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_wallet() {
+        let alice = Wallet::new(NetworkId::MAINNET).unwrap();
+        let bob = Wallet::new(NetworkId::MAINNET).unwrap();
+        let ciphertext = encrypt_to_address(&alice.address(), b"for alice only").unwrap();
+
+        assert!(matches!(
+            bob.decrypt(&ciphertext),
+            Err(EncryptionError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let ciphertext = encrypt_to_address(&wallet.address(), b"integrity").unwrap();
+
+        let mut bytes = hex::decode(&ciphertext).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(wallet.decrypt(&hex::encode(bytes)).is_err());
+    }
+}
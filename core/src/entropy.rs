@@ -0,0 +1,299 @@
+//! Key generation from user-provided entropy
+//!
+//! For cold storage, users who do not want to trust a single random number
+//! generator can roll dice, flip coins or type hex themselves. Their entropy
+//! is mixed with 32 bytes from the OS, so that the key stays secret if either
+//! source is good:
+//! - The input is normalized to its symbols (`1`-`6`, `0`/`1` for heads and
+//!   tails, lowercase hex), separators and whitespace removed
+//! - HKDF-SHA256 extracts with the OS bytes as salt and the symbols as input
+//!   key material, then expands 64 bytes with the info
+//!   `mina-web-wallet entropy v1 <source>`
+//! - The 64 bytes, read little-endian, are reduced modulo the scalar field
+//!   into the secret key
+//!
+//! At least `MIN_ENTROPY_BITS` are required from the user: 50 dice rolls,
+//! 128 coin flips or 32 hex characters.
+//!
+//! Extra bytes of unknown quality, such as mouse movements collected by a web
+//! page, can also be mixed in (`generate_with_extra`), with no minimum: they
+//! only add to the OS randomness, in case it is compromised. They go through
+//! the same HKDF, with the info `mina-web-wallet entropy v1 extra`.
+
+use ark_ff::PrimeField;
+use hkdf::Hkdf;
+use mina_signer::{NetworkId, SecKey};
+use rand::RngCore;
+use sha2::Sha256;
+use std::str::FromStr;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::fields::Fq;
+use crate::wallet::{Wallet, WalletError};
+
+/// Entropy required from the user, in bits
+pub const MIN_ENTROPY_BITS: f64 = 128.0;
+
+/// Length of the randomness drawn from the OS
+pub const OS_RANDOM_LEN: usize = 32;
+
+const HKDF_INFO: &str = "mina-web-wallet entropy v1";
+
+/// Errors that can occur while reading user entropy
+#[derive(Error, Debug)]
+pub enum EntropyError {
+    #[error("Unknown entropy source '{0}': expected dice, coin or hex")]
+    UnknownSource(String),
+    #[error("Invalid {kind} entropy: unexpected '{character}'")]
+    InvalidSymbol {
+        kind: EntropySource,
+        character: char,
+    },
+    #[error("Not enough entropy: {got} {kind} symbols, at least {needed} are needed")]
+    NotEnough {
+        kind: EntropySource,
+        got: usize,
+        needed: usize,
+    },
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+pub type Result<T> = std::result::Result<T, EntropyError>;
+
+/// Where the user's entropy comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    /// Six-sided dice rolls, `1` to `6`
+    Dice,
+    /// Coin flips, `h`/`t` or `1`/`0`
+    Coin,
+    /// Hex characters
+    Hex,
+}
+
+impl EntropySource {
+    /// Bits of entropy of one symbol
+    pub fn bits_per_symbol(self) -> f64 {
+        match self {
+            Self::Dice => 6f64.log2(),
+            Self::Coin => 1.0,
+            Self::Hex => 4.0,
+        }
+    }
+
+    /// Number of symbols giving `MIN_ENTROPY_BITS`
+    pub fn min_symbols(self) -> usize {
+        (MIN_ENTROPY_BITS / self.bits_per_symbol()).ceil() as usize
+    }
+
+    /// The symbol of `c`, if it is one of this source
+    fn symbol(self, c: char) -> Option<char> {
+        let c = c.to_ascii_lowercase();
+        match (self, c) {
+            (Self::Dice, '1'..='6') => Some(c),
+            (Self::Coin, 'h' | '1') => Some('1'),
+            (Self::Coin, 't' | '0') => Some('0'),
+            (Self::Hex, _) if c.is_ascii_hexdigit() => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for EntropySource {
+    type Err = EntropyError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dice" => Ok(Self::Dice),
+            "coin" | "coins" => Ok(Self::Coin),
+            "hex" => Ok(Self::Hex),
+            _ => Err(EntropyError::UnknownSource(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for EntropySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Dice => "dice",
+            Self::Coin => "coin",
+            Self::Hex => "hex",
+        })
+    }
+}
+
+/// Entropy typed by the user, normalized to its symbols
+pub struct UserEntropy {
+    source: EntropySource,
+    symbols: Zeroizing<String>,
+}
+
+impl UserEntropy {
+    /// Parse user input, ignoring whitespace, commas and dashes
+    pub fn parse(source: EntropySource, input: &str) -> Result<Self> {
+        let mut symbols = Zeroizing::new(String::with_capacity(input.len()));
+        for c in input.chars() {
+            if c.is_whitespace() || matches!(c, ',' | '-') {
+                continue;
+            }
+            match source.symbol(c) {
+                Some(symbol) => symbols.push(symbol),
+                None => {
+                    return Err(EntropyError::InvalidSymbol {
+                        kind: source,
+                        character: c,
+                    });
+                }
+            }
+        }
+        let needed = source.min_symbols();
+        if symbols.len() < needed {
+            return Err(EntropyError::NotEnough {
+                kind: source,
+                got: symbols.len(),
+                needed,
+            });
+        }
+        Ok(Self { source, symbols })
+    }
+
+    /// Entropy of the input, in bits
+    pub fn bits(&self) -> f64 {
+        self.symbols.len() as f64 * self.source.bits_per_symbol()
+    }
+
+    /// The wallet derived from this entropy and `os_random`
+    pub fn wallet(&self, os_random: &[u8; OS_RANDOM_LEN], network: NetworkId) -> Result<Wallet> {
+        let info = format!("{} {}", HKDF_INFO, self.source);
+        derive(self.symbols.as_bytes(), os_random, &info, network)
+    }
+}
+
+/// Generate a wallet from user entropy mixed with OS randomness
+pub fn generate(entropy: &UserEntropy, network: NetworkId) -> Result<Wallet> {
+    entropy.wallet(&os_random(), network)
+}
+
+/// The wallet derived from extra bytes of any quality and `os_random`
+pub fn wallet_with_extra(
+    extra: &[u8],
+    os_random: &[u8; OS_RANDOM_LEN],
+    network: NetworkId,
+) -> Result<Wallet> {
+    let info = format!("{} extra", HKDF_INFO);
+    derive(extra, os_random, &info, network)
+}
+
+/// Generate a wallet from OS randomness mixed with extra bytes of any quality
+pub fn generate_with_extra(extra: &[u8], network: NetworkId) -> Result<Wallet> {
+    wallet_with_extra(extra, &os_random(), network)
+}
+
+fn os_random() -> Zeroizing<[u8; OS_RANDOM_LEN]> {
+    let mut os_random = Zeroizing::new([0u8; OS_RANDOM_LEN]);
+    rand::rngs::OsRng.fill_bytes(os_random.as_mut());
+    os_random
+}
+
+fn derive(
+    input: &[u8],
+    os_random: &[u8; OS_RANDOM_LEN],
+    info: &str,
+    network: NetworkId,
+) -> Result<Wallet> {
+    let hkdf = Hkdf::<Sha256>::new(Some(os_random), input);
+    let mut okm = Zeroizing::new([0u8; 64]);
+    hkdf.expand(info.as_bytes(), okm.as_mut())
+        .expect("64 bytes is a valid HKDF-SHA256 length");
+    let secret = Zeroizing::new(SecKey::new(Fq::from_le_bytes_mod_order(okm.as_ref())).to_hex());
+    Ok(Wallet::from_secret_key_hex(&secret, network)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLLS: &str = "1234 5612 3456 1234 5612 3456 1234 5612 3456 1234 5612 3456 12";
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(EntropySource::Dice.min_symbols(), 50);
+        assert_eq!(EntropySource::Coin.min_symbols(), 128);
+        assert_eq!(EntropySource::Hex.min_symbols(), 32);
+
+        let entropy = UserEntropy::parse(EntropySource::Dice, ROLLS).unwrap();
+        assert!(entropy.bits() >= MIN_ENTROPY_BITS);
+        assert!(matches!(
+            UserEntropy::parse(EntropySource::Dice, "1234567"),
+            Err(EntropyError::InvalidSymbol { character: '7', .. })
+        ));
+        assert!(matches!(
+            UserEntropy::parse(EntropySource::Dice, "123456"),
+            Err(EntropyError::NotEnough {
+                got: 6,
+                needed: 50,
+                ..
+            })
+        ));
+
+        let flips = "HT".repeat(64);
+        let coin = UserEntropy::parse(EntropySource::Coin, &flips).unwrap();
+        let binary = UserEntropy::parse(EntropySource::Coin, &"10".repeat(64)).unwrap();
+        assert_eq!(coin.symbols, binary.symbols);
+        assert_eq!(
+            "coins".parse::<EntropySource>().unwrap(),
+            EntropySource::Coin
+        );
+    }
+
+    #[test]
+    fn test_derivation() {
+        let entropy = UserEntropy::parse(EntropySource::Dice, ROLLS).unwrap();
+        let first = entropy
+            .wallet(&[1; OS_RANDOM_LEN], NetworkId::MAINNET)
+            .unwrap();
+        let again = entropy
+            .wallet(&[1; OS_RANDOM_LEN], NetworkId::MAINNET)
+            .unwrap();
+        assert_eq!(first.address(), again.address());
+
+        // Either source changes the key
+        let other_os = entropy
+            .wallet(&[2; OS_RANDOM_LEN], NetworkId::MAINNET)
+            .unwrap();
+        assert_ne!(first.address(), other_os.address());
+        let rolls = ROLLS.replacen('1', "2", 1);
+        let other_rolls = UserEntropy::parse(EntropySource::Dice, &rolls).unwrap();
+        let other_user = other_rolls
+            .wallet(&[1; OS_RANDOM_LEN], NetworkId::MAINNET)
+            .unwrap();
+        assert_ne!(first.address(), other_user.address());
+
+        let random = generate(&entropy, NetworkId::MAINNET).unwrap();
+        assert_ne!(random.address(), first.address());
+    }
+
+    #[test]
+    fn test_extra_entropy() {
+        let os = [1; OS_RANDOM_LEN];
+        let first = wallet_with_extra(b"mouse", &os, NetworkId::MAINNET).unwrap();
+        let again = wallet_with_extra(b"mouse", &os, NetworkId::MAINNET).unwrap();
+        assert_eq!(first.address(), again.address());
+        let other = wallet_with_extra(b"mice", &os, NetworkId::MAINNET).unwrap();
+        assert_ne!(first.address(), other.address());
+
+        // Not the key of the same bytes typed as hex entropy
+        let hex = "00".repeat(16);
+        let typed = UserEntropy::parse(EntropySource::Hex, &hex).unwrap();
+        let extra = wallet_with_extra(hex.as_bytes(), &os, NetworkId::MAINNET).unwrap();
+        assert_ne!(
+            typed.wallet(&os, NetworkId::MAINNET).unwrap().address(),
+            extra.address()
+        );
+
+        let random = generate_with_extra(b"mouse", NetworkId::MAINNET).unwrap();
+        assert_ne!(random.address(), first.address());
+    }
+}
@@ -0,0 +1,212 @@
+//! Transaction envelopes for air-gapped signing
+//!
+//! Cold-storage users build a transaction on an online machine, carry it to
+//! an offline machine that holds the secret key, sign it there, and carry the
+//! signed transaction back to be broadcast. An envelope is the JSON document
+//! exchanged at each step:
+//! - `version`: format version, checked on decoding
+//! - `network`: `mainnet` or `testnet`, since a signature is only valid on
+//!   the network it was made for
+//! - `createdAt`: when the envelope was created, as a Unix timestamp
+//! - `type`: `unsigned` with a `transaction`, or `signed` with a
+//!   `signedTransaction`
+
+use mina_signer::NetworkId;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::transaction::{SignedTransaction, Transaction, TransactionError, verify_transaction};
+use crate::wallet::{Wallet, network_name};
+
+/// Version of the envelope format
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Errors that can occur while handling an envelope
+#[derive(Error, Debug)]
+pub enum EnvelopeError {
+    #[error("Invalid envelope: {0}")]
+    InvalidJson(String),
+    #[error("Unsupported envelope version {0}, expected {ENVELOPE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Unknown network '{0}'")]
+    UnknownNetwork(String),
+    #[error("The envelope is for {envelope}, not {expected}")]
+    NetworkMismatch { envelope: String, expected: String },
+    #[error("The envelope is already signed")]
+    AlreadySigned,
+    #[error("The envelope is not signed")]
+    NotSigned,
+    #[error("The signature does not match the transaction")]
+    InvalidSignature,
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+}
+
+pub type Result<T> = std::result::Result<T, EnvelopeError>;
+
+/// Content of an envelope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum EnvelopePayload {
+    /// A transaction to be signed offline
+    Unsigned { transaction: Transaction },
+    /// A signed transaction, ready to be broadcast
+    #[serde(rename_all = "camelCase")]
+    Signed {
+        signed_transaction: SignedTransaction,
+    },
+}
+
+/// A transaction on its way between an online and an offline machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionEnvelope {
+    pub version: u32,
+    pub network: String,
+    pub created_at: u64,
+    #[serde(flatten)]
+    pub payload: EnvelopePayload,
+}
+
+impl TransactionEnvelope {
+    /// Wrap a transaction to be signed for `network`
+    pub fn unsigned(transaction: Transaction, network: &NetworkId, created_at: u64) -> Self {
+        Self {
+            version: ENVELOPE_VERSION,
+            network: network_name(network).to_string(),
+            created_at,
+            payload: EnvelopePayload::Unsigned { transaction },
+        }
+    }
+
+    /// Decode an envelope, refusing unknown versions and networks
+    pub fn from_json(json: &str) -> Result<Self> {
+        let envelope: Self =
+            serde_json::from_str(json).map_err(|e| EnvelopeError::InvalidJson(e.to_string()))?;
+        if envelope.version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(envelope.version));
+        }
+        envelope.network_id()?;
+        Ok(envelope)
+    }
+
+    /// Encode the envelope as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("envelopes serialize to JSON")
+    }
+
+    /// Network the transaction is signed for
+    pub fn network_id(&self) -> Result<NetworkId> {
+        match self.network.as_str() {
+            "mainnet" => Ok(NetworkId::MAINNET),
+            "testnet" => Ok(NetworkId::TESTNET),
+            other => Err(EnvelopeError::UnknownNetwork(other.to_string())),
+        }
+    }
+
+    /// The transaction, signed or not
+    pub fn transaction(&self) -> &Transaction {
+        match &self.payload {
+            EnvelopePayload::Unsigned { transaction } => transaction,
+            EnvelopePayload::Signed { signed_transaction } => &signed_transaction.data,
+        }
+    }
+
+    /// Sign the transaction, producing the envelope to carry back online
+    ///
+    /// The wallet must be for the network of the envelope.
+    pub fn sign(&self, wallet: &Wallet, created_at: u64) -> Result<Self> {
+        let EnvelopePayload::Unsigned { transaction } = &self.payload else {
+            return Err(EnvelopeError::AlreadySigned);
+        };
+        let wallet_network = network_name(wallet.network());
+        if wallet_network != self.network {
+            return Err(EnvelopeError::NetworkMismatch {
+                envelope: self.network.clone(),
+                expected: wallet_network.to_string(),
+            });
+        }
+        Ok(Self {
+            version: ENVELOPE_VERSION,
+            network: self.network.clone(),
+            created_at,
+            payload: EnvelopePayload::Signed {
+                signed_transaction: wallet.sign_transaction(transaction)?,
+            },
+        })
+    }
+
+    /// The signed transaction, after checking its signature
+    pub fn verified_transaction(&self) -> Result<&SignedTransaction> {
+        let EnvelopePayload::Signed { signed_transaction } = &self.payload else {
+            return Err(EnvelopeError::NotSigned);
+        };
+        if !verify_transaction(signed_transaction, self.network_id()?)? {
+            return Err(EnvelopeError::InvalidSignature);
+        }
+        Ok(signed_transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unsigned_envelope(wallet: &Wallet) -> TransactionEnvelope {
+        let payment = Transaction::payment(
+            &wallet.address(),
+            &wallet.address(),
+            "1".parse().unwrap(),
+            "0.1".parse().unwrap(),
+            3,
+        );
+        TransactionEnvelope::unsigned(payment, &NetworkId::TESTNET, 1_700_000_000)
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let unsigned = unsigned_envelope(&wallet);
+        let decoded = TransactionEnvelope::from_json(&unsigned.to_json()).unwrap();
+        assert_eq!(decoded, unsigned);
+        assert!(matches!(
+            unsigned.verified_transaction(),
+            Err(EnvelopeError::NotSigned)
+        ));
+
+        let signed = decoded.sign(&wallet, 1_700_000_060).unwrap();
+        let signed = TransactionEnvelope::from_json(&signed.to_json()).unwrap();
+        assert_eq!(signed.verified_transaction().unwrap().data.nonce, 3);
+        assert!(matches!(
+            signed.sign(&wallet, 0),
+            Err(EnvelopeError::AlreadySigned)
+        ));
+
+        let mainnet = Wallet::new(NetworkId::MAINNET).unwrap();
+        assert!(matches!(
+            unsigned.sign(&mainnet, 0),
+            Err(EnvelopeError::NetworkMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let mut json: serde_json::Value =
+            serde_json::from_str(&unsigned_envelope(&wallet).to_json()).unwrap();
+        assert_eq!(json["type"], "unsigned");
+
+        json["version"] = 2.into();
+        assert!(matches!(
+            TransactionEnvelope::from_json(&json.to_string()),
+            Err(EnvelopeError::UnsupportedVersion(2))
+        ));
+
+        json["version"] = 1.into();
+        json["network"] = "berkeley".into();
+        assert!(matches!(
+            TransactionEnvelope::from_json(&json.to_string()),
+            Err(EnvelopeError::UnknownNetwork(_))
+        ));
+    }
+}
@@ -0,0 +1,313 @@
+//! Import of keys exported by other Mina wallets
+//!
+//! Users moving to this wallet can bring the key files of the wallets they
+//! leave as they are:
+//! - Auro exports a private key as JSON, `{ "privateKey": "EK...",
+//!   "publicKey": "B62q..." }`, or as the bare Base58 key
+//! - Clorio keystores use the secret box format of the Mina daemon key files:
+//!   the key is encrypted with XSalsa20-Poly1305 under an Argon2i hash of the
+//!   passphrase, and every binary field is Base58Check-encoded
+//!
+//! When an export names its address, the imported key must derive it.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::{Key, Nonce, XSalsa20Poly1305};
+use mina_signer::NetworkId;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::base58::{self, SECRET_KEY_VERSION};
+use crate::wallet::{Wallet, WalletError};
+
+/// Version byte of the Base58Check fields of a secret box
+const SECRET_BOX_VERSION: u8 = 0x02;
+
+/// Version of the binary form of a secret key, before its 32 bytes
+const SECRET_KEY_BIN_VERSION: u8 = 0x01;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+
+/// Errors that can occur while importing a key from another wallet
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Unknown wallet '{0}': expected auro or clorio")]
+    UnknownWallet(String),
+    #[error("Not {0} export: {1}")]
+    InvalidExport(&'static str, String),
+    #[error("Unsupported {0} '{1}'")]
+    Unsupported(&'static str, String),
+    #[error("Wrong passphrase or corrupted keystore")]
+    DecryptionFailed,
+    #[error("The key is for {got}, but the export names {expected}")]
+    AddressMismatch { expected: String, got: String },
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+pub type Result<T> = std::result::Result<T, ImportError>;
+
+/// A wallet whose exports can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Private key exported by Auro
+    Auro,
+    /// Passphrase-encrypted Clorio keystore
+    Clorio,
+}
+
+impl ExportFormat {
+    /// Guess the wallet an export comes from, without decrypting it
+    pub fn detect(export: &str) -> Option<Self> {
+        let export = export.trim();
+        if serde_json::from_str::<SecretBox>(export).is_ok() {
+            Some(Self::Clorio)
+        } else if serde_json::from_str::<AuroExport>(export).is_ok()
+            || base58::decode_check(export, SECRET_KEY_VERSION).is_ok()
+        {
+            Some(Self::Auro)
+        } else {
+            None
+        }
+    }
+
+    /// Whether exports in this format are encrypted with a passphrase
+    pub fn needs_passphrase(self) -> bool {
+        self == Self::Clorio
+    }
+
+    fn article_name(self) -> &'static str {
+        match self {
+            Self::Auro => "an Auro",
+            Self::Clorio => "a Clorio",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ImportError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auro" => Ok(Self::Auro),
+            "clorio" => Ok(Self::Clorio),
+            _ => Err(ImportError::UnknownWallet(s.to_string())),
+        }
+    }
+}
+
+/// Private key exported by Auro
+#[derive(Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AuroExport {
+    private_key: String,
+    #[serde(default, alias = "address")]
+    public_key: Option<String>,
+}
+
+/// Secret box of a Clorio keystore or a daemon key file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SecretBox {
+    box_primitive: String,
+    pw_primitive: String,
+    nonce: String,
+    pwsalt: String,
+    /// Argon2 memory in bytes and iterations
+    pwdiff: (u32, u32),
+    ciphertext: String,
+}
+
+/// Import the key of an export; `passphrase` is only used by Clorio keystores
+pub fn import(
+    format: ExportFormat,
+    export: &str,
+    passphrase: &str,
+    network: NetworkId,
+) -> Result<Wallet> {
+    match format {
+        ExportFormat::Auro => import_auro(export, network),
+        ExportFormat::Clorio => import_clorio(export, passphrase, network),
+    }
+}
+
+/// Import a private key exported by Auro
+pub fn import_auro(export: &str, network: NetworkId) -> Result<Wallet> {
+    let export = export.trim();
+    if !export.starts_with('{') {
+        return Ok(Wallet::from_secret_key_base58(export, network)?);
+    }
+    let export: AuroExport =
+        serde_json::from_str(export).map_err(|e| invalid(ExportFormat::Auro, e.to_string()))?;
+    let wallet = Wallet::from_secret_key_base58(export.private_key.trim(), network)?;
+    check_address(&wallet, export.public_key.as_deref())?;
+    Ok(wallet)
+}
+
+/// Decrypt a Clorio keystore
+pub fn import_clorio(export: &str, passphrase: &str, network: NetworkId) -> Result<Wallet> {
+    let secret_box: SecretBox = serde_json::from_str(export.trim())
+        .map_err(|e| invalid(ExportFormat::Clorio, e.to_string()))?;
+    let secret = secret_box.open(passphrase)?;
+    let secret = match secret.len() {
+        KEY_LEN => {
+            let mut versioned = Zeroizing::new(vec![SECRET_KEY_BIN_VERSION]);
+            versioned.extend_from_slice(&secret);
+            versioned
+        }
+        _ => secret,
+    };
+    let secret_b58 = Zeroizing::new(base58::encode_check(SECRET_KEY_VERSION, &secret));
+    Ok(Wallet::from_secret_key_base58(&secret_b58, network)?)
+}
+
+impl SecretBox {
+    /// Decrypt the secret key, in its binary form
+    fn open(&self, passphrase: &str) -> Result<Zeroizing<Vec<u8>>> {
+        if self.box_primitive != "xsalsa20poly1305" {
+            return Err(ImportError::Unsupported(
+                "box primitive",
+                self.box_primitive.clone(),
+            ));
+        }
+        if self.pw_primitive != "argon2i" {
+            return Err(ImportError::Unsupported(
+                "password primitive",
+                self.pw_primitive.clone(),
+            ));
+        }
+        let field = |name: &str, value: &str| {
+            base58::decode_check(value, SECRET_BOX_VERSION)
+                .map_err(|e| invalid(ExportFormat::Clorio, format!("{}: {}", name, e)))
+        };
+        let nonce = field("nonce", &self.nonce)?;
+        let salt = field("pwsalt", &self.pwsalt)?;
+        let ciphertext = field("ciphertext", &self.ciphertext)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(invalid(ExportFormat::Clorio, "nonce length".to_string()));
+        }
+
+        // libsodium gives the memory in bytes, Argon2 in KiB
+        let (memory, iterations) = self.pwdiff;
+        let params = Params::new(memory / 1024, iterations, 1, Some(KEY_LEN))
+            .map_err(|e| ImportError::Unsupported("password difficulty", e.to_string()))?;
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| invalid(ExportFormat::Clorio, e.to_string()))?;
+
+        let cipher = XSalsa20Poly1305::new(Key::from_slice(key.as_ref()));
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| ImportError::DecryptionFailed)
+    }
+}
+
+fn check_address(wallet: &Wallet, expected: Option<&str>) -> Result<()> {
+    match expected {
+        Some(expected) if expected.trim() != wallet.address() => {
+            Err(ImportError::AddressMismatch {
+                expected: expected.trim().to_string(),
+                got: wallet.address(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn invalid(format: ExportFormat, reason: String) -> ImportError {
+    ImportError::InvalidExport(format.article_name(), reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    /// A Clorio keystore of `wallet`, with a cheap Argon2 cost
+    fn clorio_keystore(wallet: &Wallet, passphrase: &str) -> String {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let params = Params::new(64, 1, 1, Some(KEY_LEN)).unwrap();
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .unwrap();
+        let secret = base58::decode_check(&wallet.secret_key_base58(), SECRET_KEY_VERSION).unwrap();
+        let ciphertext = XSalsa20Poly1305::new(Key::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), secret.as_slice())
+            .unwrap();
+        serde_json::to_string(&SecretBox {
+            box_primitive: "xsalsa20poly1305".to_string(),
+            pw_primitive: "argon2i".to_string(),
+            nonce: base58::encode_check(SECRET_BOX_VERSION, &nonce),
+            pwsalt: base58::encode_check(SECRET_BOX_VERSION, &salt),
+            pwdiff: (64 * 1024, 1),
+            ciphertext: base58::encode_check(SECRET_BOX_VERSION, &ciphertext),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_import_auro() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let export = serde_json::json!({
+            "privateKey": wallet.secret_key_base58().as_str(),
+            "publicKey": wallet.address(),
+        })
+        .to_string();
+        assert_eq!(ExportFormat::detect(&export), Some(ExportFormat::Auro));
+        let imported = import_auro(&export, NetworkId::MAINNET).unwrap();
+        assert_eq!(imported.address(), wallet.address());
+
+        let bare = import_auro(&wallet.secret_key_base58(), NetworkId::MAINNET).unwrap();
+        assert_eq!(bare.address(), wallet.address());
+
+        let other = Wallet::new(NetworkId::MAINNET).unwrap();
+        let mismatch = serde_json::json!({
+            "privateKey": wallet.secret_key_base58().as_str(),
+            "publicKey": other.address(),
+        })
+        .to_string();
+        assert!(matches!(
+            import_auro(&mismatch, NetworkId::MAINNET),
+            Err(ImportError::AddressMismatch { .. })
+        ));
+        assert_eq!(ExportFormat::detect("not an export"), None);
+        assert_eq!(
+            "Clorio".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Clorio
+        );
+    }
+
+    #[test]
+    fn test_import_clorio() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let keystore = clorio_keystore(&wallet, "hunter2");
+        assert_eq!(ExportFormat::detect(&keystore), Some(ExportFormat::Clorio));
+
+        let imported = import(
+            ExportFormat::Clorio,
+            &keystore,
+            "hunter2",
+            NetworkId::TESTNET,
+        )
+        .unwrap();
+        assert_eq!(imported.address(), wallet.address());
+        assert!(matches!(
+            import_clorio(&keystore, "wrong", NetworkId::TESTNET),
+            Err(ImportError::DecryptionFailed)
+        ));
+
+        let argon2id = keystore.replace("\"argon2i\"", "\"argon2id\"");
+        assert!(matches!(
+            import_clorio(&argon2id, "hunter2", NetworkId::TESTNET),
+            Err(ImportError::Unsupported(..))
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! Decoding of signed transactions produced by other tools
+//!
+//! A signed payment or delegation comes in one of several JSON shapes:
+//! - `{ publicKey, data, signature }`, from this wallet, Auro and `mina-signer`,
+//!   with the signature as `{ field, scalar }` or in Base58
+//! - `{ input, signature }`, the variables of the daemon's `sendPayment` and
+//!   `sendDelegation` mutations, possibly inside a whole GraphQL request,
+//!   with the signature as `{ field, scalar }` or `{ rawSignature }`
+//! - a signed envelope of `tx sign`
+//!
+//! `inspect` normalizes any of them to a `SignedTransaction`, and finds the
+//! network the signature was made for by verifying it on each network.
+//! Nonces and slots are accepted as numbers or strings, amounts in nanomina.
+//!
+//! `preview` also takes unsigned transactions, in the same shapes without
+//! the signature, and summarizes either kind for a confirmation dialog.
+
+use mina_signer::NetworkId;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::amount::Amount;
+use crate::consensus::ConsensusConstants;
+use crate::envelope::{EnvelopePayload, TransactionEnvelope};
+use crate::signing::{SignatureError, SignatureJson};
+use crate::transaction::{
+    SignedTransaction, Transaction, TransactionError, TransactionKind, decode_memo_base58,
+    verify_transaction,
+};
+use crate::wallet::network_name;
+
+/// Errors that can occur while decoding a signed transaction
+#[derive(Error, Debug)]
+pub enum InspectError {
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
+    #[error("Not a signed transaction: {0}")]
+    UnknownShape(String),
+    #[error("The envelope is not signed")]
+    NotSigned,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+}
+
+pub type Result<T> = std::result::Result<T, InspectError>;
+
+/// Shape a signed transaction was given in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionFormat {
+    /// `{ publicKey, data, signature }`, as produced by `mina-signer` and Auro
+    Signer,
+    /// Variables of the daemon's GraphQL mutations
+    Daemon,
+    /// Envelope of `tx sign`
+    Envelope,
+}
+
+/// A decoded signed transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inspection {
+    pub format: TransactionFormat,
+    /// The transaction in the shape used by this wallet
+    pub signed_transaction: SignedTransaction,
+    /// `mainnet` or `testnet`, the network the signature is valid on; `None`
+    /// if it is valid on neither
+    pub network: Option<String>,
+}
+
+/// A signed or unsigned transaction, summarized for a confirmation dialog
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPreview {
+    pub kind: TransactionKind,
+    pub from: String,
+    pub to: String,
+    /// Amount, in MINA; absent for delegations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    /// Fee, in MINA
+    pub fee: String,
+    /// Amount plus fee, in MINA
+    pub total_cost: String,
+    pub nonce: u32,
+    /// Memo text, decoded if it was given in Base58
+    pub memo: String,
+    /// Last global slot at which the transaction can be included; `None` if
+    /// it never expires
+    pub valid_until: Option<u32>,
+    pub signed: bool,
+    /// `mainnet` or `testnet`: the network the signature is valid on, or
+    /// that of an unsigned envelope
+    pub network: Option<String>,
+    /// Explanations of what is worth a second look before signing or
+    /// broadcasting
+    pub warnings: Vec<String>,
+}
+
+/// Decode a signed transaction in any supported shape and verify it
+pub fn inspect(json: &str) -> Result<Inspection> {
+    let (format, signed_transaction) = parse_signed_transaction(json)?;
+    let network = signed_network(&signed_transaction)?.map(|n| network_name(&n).to_string());
+    Ok(Inspection {
+        format,
+        signed_transaction,
+        network,
+    })
+}
+
+/// Decode a signed transaction in any supported shape, without verifying it
+pub fn parse_signed_transaction(json: &str) -> Result<(TransactionFormat, SignedTransaction)> {
+    let mut value: Value =
+        serde_json::from_str(json).map_err(|e| InspectError::InvalidJson(e.to_string()))?;
+    if value.get("version").is_some() {
+        let envelope = TransactionEnvelope::from_json(json)
+            .map_err(|e| InspectError::UnknownShape(e.to_string()))?;
+        return match envelope.payload {
+            EnvelopePayload::Signed { signed_transaction } => {
+                Ok((TransactionFormat::Envelope, signed_transaction))
+            }
+            EnvelopePayload::Unsigned { .. } => Err(InspectError::NotSigned),
+        };
+    }
+    // A whole GraphQL request, as sent to the daemon
+    if let Some(variables) = value.get_mut("variables") {
+        value = variables.take();
+    }
+
+    let (format, transaction, signature, public_key) = if value.get("input").is_some() {
+        let daemon: DaemonShape =
+            serde_json::from_value(value).map_err(|e| InspectError::UnknownShape(e.to_string()))?;
+        (
+            TransactionFormat::Daemon,
+            daemon.input,
+            daemon.signature,
+            None,
+        )
+    } else if value.get("data").is_some() {
+        let signer: SignerShape =
+            serde_json::from_value(value).map_err(|e| InspectError::UnknownShape(e.to_string()))?;
+        (
+            TransactionFormat::Signer,
+            signer.data,
+            signer.signature,
+            signer.public_key,
+        )
+    } else {
+        return Err(InspectError::UnknownShape(
+            "expected `data` and `signature`, or `input` and `signature`".to_string(),
+        ));
+    };
+
+    let data = Transaction::from(transaction);
+    let signature = match signature {
+        LooseSignature::Json(signature) => signature,
+        LooseSignature::Raw { raw_signature } => SignatureJson::from_hex(&raw_signature)?,
+        LooseSignature::Base58(signature) => SignatureJson::from_base58(&signature)?,
+    };
+    Ok((
+        format,
+        SignedTransaction {
+            public_key: public_key.unwrap_or_else(|| data.from.clone()),
+            data,
+            signature,
+        },
+    ))
+}
+
+/// Network the signature of `signed` is valid on, if any
+pub fn signed_network(signed: &SignedTransaction) -> Result<Option<NetworkId>> {
+    for network in [NetworkId::MAINNET, NetworkId::TESTNET] {
+        if verify_transaction(signed, network.clone())? {
+            return Ok(Some(network));
+        }
+    }
+    Ok(None)
+}
+
+/// Summarize a signed or unsigned transaction in any supported shape
+///
+/// Signatures are verified; `now`, a Unix timestamp in seconds, is used to
+/// flag expired transactions when their network is known.
+pub fn preview(json: &str, now: Option<u64>) -> Result<TransactionPreview> {
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| InspectError::InvalidJson(e.to_string()))?;
+    let signed = value.get("signature").is_some()
+        || value.pointer("/variables/signature").is_some()
+        || value.get("signedTransaction").is_some();
+
+    let mut warnings = Vec::new();
+    let (transaction, network) = if signed {
+        let (_, signed_transaction) = parse_signed_transaction(json)?;
+        let network = signed_network(&signed_transaction)?;
+        if network.is_none() {
+            warnings.push("The signature is valid on neither mainnet nor testnet".to_string());
+        }
+        (signed_transaction.data, network)
+    } else {
+        parse_unsigned_transaction(json, value)?
+    };
+
+    let current_slot = network
+        .as_ref()
+        .zip(now)
+        .and_then(|(network, now)| ConsensusConstants::for_network(network).slot_at(now));
+    warnings.extend(
+        transaction
+            .warnings(None, current_slot)
+            .iter()
+            .map(|w| w.message()),
+    );
+    let memo = decode_memo_base58(&transaction.memo).unwrap_or_else(|| transaction.memo.clone());
+    Ok(TransactionPreview {
+        kind: transaction.kind(),
+        amount: transaction.amount.map(|a| a.to_string()),
+        fee: transaction.fee.to_string(),
+        total_cost: transaction.preview(None).total_cost.to_string(),
+        nonce: transaction.nonce,
+        memo,
+        valid_until: (transaction.valid_until != u32::MAX).then_some(transaction.valid_until),
+        signed,
+        network: network.map(|n| network_name(&n).to_string()),
+        warnings,
+        from: transaction.from,
+        to: transaction.to,
+    })
+}
+
+/// Decode an unsigned transaction: bare, as the `data` or `input` of a
+/// signed shape, or in an unsigned envelope, with the envelope's network
+fn parse_unsigned_transaction(
+    json: &str,
+    mut value: Value,
+) -> Result<(Transaction, Option<NetworkId>)> {
+    if value.get("version").is_some() {
+        let envelope = TransactionEnvelope::from_json(json)
+            .map_err(|e| InspectError::UnknownShape(e.to_string()))?;
+        let network = envelope.network_id().ok();
+        return match envelope.payload {
+            EnvelopePayload::Unsigned { transaction } => Ok((transaction, network)),
+            EnvelopePayload::Signed { signed_transaction } => {
+                Ok((signed_transaction.data, network))
+            }
+        };
+    }
+    if let Some(variables) = value.get_mut("variables") {
+        value = variables.take();
+    }
+    for key in ["data", "input"] {
+        if let Some(inner) = value.get_mut(key) {
+            value = inner.take();
+            break;
+        }
+    }
+    let transaction: LooseTransaction =
+        serde_json::from_value(value).map_err(|e| InspectError::UnknownShape(e.to_string()))?;
+    Ok((transaction.into(), None))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignerShape {
+    #[serde(default)]
+    public_key: Option<String>,
+    data: LooseTransaction,
+    signature: LooseSignature,
+}
+
+#[derive(Deserialize)]
+struct DaemonShape {
+    input: LooseTransaction,
+    signature: LooseSignature,
+}
+
+/// Transaction fields as written by other tools
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LooseTransaction {
+    to: String,
+    from: String,
+    fee: Amount,
+    #[serde(default)]
+    amount: Option<Amount>,
+    #[serde(deserialize_with = "integer")]
+    nonce: u32,
+    #[serde(default)]
+    memo: Option<String>,
+    #[serde(default, deserialize_with = "optional_integer")]
+    valid_until: Option<u32>,
+}
+
+impl From<LooseTransaction> for Transaction {
+    fn from(transaction: LooseTransaction) -> Self {
+        Self {
+            to: transaction.to,
+            from: transaction.from,
+            fee: transaction.fee,
+            amount: transaction.amount,
+            nonce: transaction.nonce,
+            memo: transaction.memo.unwrap_or_default(),
+            valid_until: transaction.valid_until.unwrap_or(u32::MAX),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LooseSignature {
+    Json(SignatureJson),
+    Raw {
+        #[serde(rename = "rawSignature")]
+        raw_signature: String,
+    },
+    Base58(String),
+}
+
+/// A `u32` written as a number or a decimal string
+fn integer<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u32),
+        String(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(n) => Ok(n),
+        Raw::String(s) => s.parse().map_err(de::Error::custom),
+    }
+}
+
+fn optional_integer<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "integer")] u32);
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(n)| n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+    use serde_json::json;
+
+    const RECEIVER: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn signed() -> SignedTransaction {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address(),
+            RECEIVER,
+            "1.5".parse().unwrap(),
+            "0.01".parse().unwrap(),
+            7,
+        )
+        .with_memo("rent");
+        wallet.sign_transaction(&payment).unwrap()
+    }
+
+    #[test]
+    fn test_inspect_shapes() {
+        let signed = signed();
+        let tx = &signed.data;
+
+        let ours = inspect(&serde_json::to_string(&signed).unwrap()).unwrap();
+        assert_eq!(ours.format, TransactionFormat::Signer);
+        assert_eq!(ours.signed_transaction, signed);
+        assert_eq!(ours.network.as_deref(), Some("testnet"));
+
+        // mina-signer: Base58 signature, numbers for the nonce
+        let base58 = json!({
+            "publicKey": tx.from,
+            "data": {
+                "to": tx.to, "from": tx.from, "fee": "10000000", "amount": 1_500_000_000u64,
+                "nonce": 7, "memo": "rent",
+            },
+            "signature": signed.signature.to_base58().unwrap(),
+        });
+        assert_eq!(
+            inspect(&base58.to_string()).unwrap().signed_transaction,
+            signed
+        );
+
+        // Daemon: strings for integers, raw hex signature, in a GraphQL request
+        let daemon = json!({
+            "query": "mutation SendPayment(...) { ... }",
+            "variables": {
+                "input": {
+                    "to": tx.to, "from": tx.from, "fee": "10000000", "amount": "1500000000",
+                    "nonce": "7", "memo": "rent", "validUntil": u32::MAX.to_string(),
+                },
+                "signature": { "rawSignature": signed.signature.to_hex().unwrap() },
+            },
+        });
+        let inspection = inspect(&daemon.to_string()).unwrap();
+        assert_eq!(inspection.format, TransactionFormat::Daemon);
+        assert_eq!(inspection.signed_transaction, signed);
+        assert_eq!(inspection.network.as_deref(), Some("testnet"));
+    }
+
+    #[test]
+    fn test_invalid_or_tampered() {
+        let mut signed = signed();
+        signed.data.nonce = 8;
+        let inspection = inspect(&serde_json::to_string(&signed).unwrap()).unwrap();
+        assert_eq!(inspection.network, None);
+
+        assert!(matches!(
+            inspect(r#"{"to": "B62q"}"#),
+            Err(InspectError::UnknownShape(_))
+        ));
+        assert!(matches!(
+            inspect("not json"),
+            Err(InspectError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn test_preview() {
+        let signed = signed();
+        let summary = preview(&serde_json::to_string(&signed).unwrap(), None).unwrap();
+        assert!(summary.signed);
+        assert_eq!(summary.kind, TransactionKind::Payment);
+        assert_eq!(summary.amount.as_deref(), Some("1.5"));
+        assert_eq!(summary.total_cost, "1.51");
+        assert_eq!(summary.network.as_deref(), Some("testnet"));
+        assert_eq!(summary.valid_until, None);
+        assert!(summary.warnings.is_empty());
+
+        // Unsigned, with a Base58 memo and an expiry in the past
+        let unsigned = json!({
+            "to": RECEIVER, "from": signed.data.from, "fee": "10000000",
+            "nonce": "7", "validUntil": 10,
+            "memo": crate::transaction::encode_memo_base58("rent").unwrap(),
+        });
+        let summary = preview(&unsigned.to_string(), Some(1_800_000_000)).unwrap();
+        assert!(!summary.signed);
+        assert_eq!(summary.kind, TransactionKind::Delegation);
+        assert_eq!(summary.memo, "rent");
+        assert_eq!(summary.valid_until, Some(10));
+        // The network of a bare transaction is unknown, so is its expiry
+        assert!(summary.warnings.is_empty());
+    }
+}
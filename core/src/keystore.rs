@@ -0,0 +1,634 @@
+//! Passphrase-encrypted keystore
+//!
+//! Secret keys are kept encrypted at rest, so that front-ends can refer to
+//! them by label or address instead of handling raw secret keys:
+//! - Argon2id derives a 32-byte key from the passphrase and a random salt
+//! - ChaCha20-Poly1305 encrypts the secret key, with the address as
+//!   associated data so that an entry cannot be moved to another address
+//!
+//! The keystore is a single JSON value persisted through a `Storage`
+//! backend. Argon2 parameters are stored with each entry, so the cost can be
+//! raised later without breaking existing keys.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use mina_signer::NetworkId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::storage::{self, Storage, StorageError};
+use crate::wallet::Wallet;
+
+/// Storage key of the keystore
+pub const KEYSTORE_STORAGE_KEY: &str = "keystore";
+
+/// Longest label, in characters
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Version of the entry format
+const VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Length of the key derived from a passphrase
+pub const KEY_LEN: usize = 32;
+
+/// Errors that can occur while managing the keystore
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeystoreError {
+    #[error("No key with label or address '{0}'")]
+    KeyNotFound(String),
+    #[error("Key {0} is already in the keystore")]
+    DuplicateKey(String),
+    #[error("Label '{0}' is already used")]
+    DuplicateLabel(String),
+    #[error(
+        "Invalid label '{0}': use 1 to {MAX_LABEL_LEN} letters, digits, '-' or '_', not starting with B62"
+    )]
+    InvalidLabel(String),
+    #[error("Wrong passphrase or corrupted entry")]
+    DecryptionFailed,
+    #[error("Invalid keystore entry: {0}")]
+    InvalidEntry(String),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+pub type Result<T> = std::result::Result<T, KeystoreError>;
+
+/// Cost of the Argon2id key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfCost {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfCost {
+    /// The OWASP recommendation for Argon2id: 19 MiB, 2 iterations
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// A secret key encrypted with a passphrase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedSecret {
+    pub version: u8,
+    #[serde(flatten)]
+    pub kdf: KdfCost,
+    /// Argon2 salt, hex
+    pub salt: String,
+    /// ChaCha20-Poly1305 nonce, hex
+    pub nonce: String,
+    /// Encrypted secret key and authentication tag, hex
+    pub ciphertext: String,
+}
+
+impl EncryptedSecret {
+    /// Encrypt `secret` with `passphrase`, bound to `associated_data`
+    pub fn encrypt(
+        secret: &[u8],
+        passphrase: &str,
+        associated_data: &[u8],
+        kdf: KdfCost,
+    ) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let key = derive_key(passphrase, &salt, &kdf)?;
+        let (nonce, ciphertext) = seal(&key, secret, associated_data)?;
+
+        Ok(Self {
+            version: VERSION,
+            kdf,
+            salt: hex::encode(salt),
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Encrypt the secret key of `wallet`, bound to its address
+    pub fn from_wallet(wallet: &Wallet, passphrase: &str, kdf: KdfCost) -> Result<Self> {
+        Self::encrypt(
+            wallet.secret_key_hex().as_bytes(),
+            passphrase,
+            wallet.address().as_bytes(),
+            kdf,
+        )
+    }
+
+    /// Decrypt the secret with `passphrase`
+    pub fn decrypt(&self, passphrase: &str, associated_data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let key = self.derive_key(passphrase)?;
+        self.decrypt_with_key(&key, associated_data)
+    }
+
+    /// Derive the encryption key from `passphrase`, the slow part of
+    /// decryption, so that it can be cached for a session
+    pub fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        check_version(self.version)?;
+        derive_key(passphrase, &decode_hex("salt", &self.salt)?, &self.kdf)
+    }
+
+    /// Decrypt the secret with a key from `derive_key`
+    pub fn decrypt_with_key(
+        &self,
+        key: &[u8; KEY_LEN],
+        associated_data: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        check_version(self.version)?;
+        open(key, &self.nonce, &self.ciphertext, associated_data)
+    }
+}
+
+/// A key from `EncryptedSecret::derive_key`, encrypted with a secret held by
+/// an authenticator, e.g. the output of the WebAuthn PRF extension
+///
+/// The wrapped key unlocks an entry without its passphrase, but only with
+/// the authenticator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WrappedKey {
+    pub version: u8,
+    /// ChaCha20-Poly1305 nonce, hex
+    pub nonce: String,
+    /// Encrypted key and authentication tag, hex
+    pub ciphertext: String,
+}
+
+impl WrappedKey {
+    /// Encrypt `key` with `wrapping_key`, bound to `associated_data`
+    pub fn wrap(
+        key: &[u8; KEY_LEN],
+        wrapping_key: &[u8; KEY_LEN],
+        associated_data: &[u8],
+    ) -> Result<Self> {
+        let (nonce, ciphertext) = seal(wrapping_key, key, associated_data)?;
+        Ok(Self {
+            version: VERSION,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypt the key with `wrapping_key`
+    pub fn unwrap(
+        &self,
+        wrapping_key: &[u8; KEY_LEN],
+        associated_data: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        check_version(self.version)?;
+        let key = open(wrapping_key, &self.nonce, &self.ciphertext, associated_data)?;
+        let key: [u8; KEY_LEN] = key
+            .as_slice()
+            .try_into()
+            .map_err(|_| KeystoreError::InvalidEntry("invalid key length".to_string()))?;
+        Ok(Zeroizing::new(key))
+    }
+}
+
+/// A key in the keystore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyEntry {
+    pub address: String,
+    pub label: Option<String>,
+    /// When the key was added, as a Unix timestamp in seconds
+    pub created_at: u64,
+    pub secret: EncryptedSecret,
+}
+
+/// Outcome of `Keystore::merge`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    /// Addresses of the keys added
+    pub added: Vec<String>,
+    /// Addresses of the keys already in the keystore, left untouched
+    pub skipped: Vec<String>,
+    /// Labels dropped because another key already used them
+    pub dropped_labels: Vec<String>,
+}
+
+/// Encrypted secret keys, with optional labels and a default key
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Keystore {
+    /// Address of the default key
+    default: Option<String>,
+    keys: Vec<KeyEntry>,
+    #[serde(skip)]
+    kdf: KdfCost,
+}
+
+impl Keystore {
+    /// Create an empty keystore
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the keystore from storage, or an empty one if none was saved
+    pub fn load(storage: &dyn Storage) -> Result<Self> {
+        Ok(storage::load_json(storage, KEYSTORE_STORAGE_KEY)?.unwrap_or_default())
+    }
+
+    /// Save the keystore to storage
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<()> {
+        Ok(storage::save_json(storage, KEYSTORE_STORAGE_KEY, self)?)
+    }
+
+    /// Set the key derivation cost of keys added from now on
+    pub fn with_kdf_cost(mut self, kdf: KdfCost) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Encrypt the secret key of `wallet` with `passphrase` and add it
+    ///
+    /// The first key added becomes the default key.
+    pub fn add(
+        &mut self,
+        wallet: &Wallet,
+        passphrase: &str,
+        label: Option<&str>,
+        created_at: u64,
+    ) -> Result<&KeyEntry> {
+        let address = wallet.address();
+        if self.position(&address).is_some() {
+            return Err(KeystoreError::DuplicateKey(address));
+        }
+        if let Some(label) = label {
+            self.check_label(label)?;
+        }
+
+        let secret = EncryptedSecret::from_wallet(wallet, passphrase, self.kdf)?;
+        if self.default.is_none() {
+            self.default = Some(address.clone());
+        }
+        self.keys.push(KeyEntry {
+            address,
+            label: label.map(str::to_string),
+            created_at,
+            secret,
+        });
+        Ok(&self.keys[self.keys.len() - 1])
+    }
+
+    /// Remove a key, by label or address
+    ///
+    /// If it was the default key, the keystore is left without a default.
+    pub fn remove(&mut self, name: &str) -> Result<KeyEntry> {
+        let index = self.position(name).ok_or_else(|| not_found(name))?;
+        let entry = self.keys.remove(index);
+        if self.default.as_deref() == Some(entry.address.as_str()) {
+            self.default = None;
+        }
+        Ok(entry)
+    }
+
+    /// Set or clear the label of a key
+    pub fn set_label(&mut self, name: &str, label: Option<&str>) -> Result<()> {
+        let index = self.position(name).ok_or_else(|| not_found(name))?;
+        if let Some(label) = label
+            && self.keys[index].label.as_deref() != Some(label)
+        {
+            self.check_label(label)?;
+        }
+        self.keys[index].label = label.map(str::to_string);
+        Ok(())
+    }
+
+    /// Make a key the default one
+    pub fn set_default(&mut self, name: &str) -> Result<()> {
+        let index = self.position(name).ok_or_else(|| not_found(name))?;
+        self.default = Some(self.keys[index].address.clone());
+        Ok(())
+    }
+
+    /// The default key, if any
+    pub fn default_key(&self) -> Option<&KeyEntry> {
+        self.default
+            .as_deref()
+            .and_then(|address| self.get(address))
+    }
+
+    /// Find a key by label or address
+    pub fn get(&self, name: &str) -> Option<&KeyEntry> {
+        self.position(name).map(|index| &self.keys[index])
+    }
+
+    /// Keys, in the order they were added
+    pub fn keys(&self) -> &[KeyEntry] {
+        &self.keys
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the keystore has no keys
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Add the keys of `other` that are not in this keystore yet, still
+    /// encrypted with their own passphrases
+    ///
+    /// The default key of `other` becomes the default only if this keystore
+    /// has none.
+    pub fn merge(&mut self, other: Keystore) -> MergeReport {
+        let mut report = MergeReport::default();
+        for mut entry in other.keys {
+            if self.position(&entry.address).is_some() {
+                report.skipped.push(entry.address);
+                continue;
+            }
+            if let Some(label) = &entry.label
+                && self.check_label(label).is_err()
+            {
+                report.dropped_labels.extend(entry.label.take());
+            }
+            report.added.push(entry.address.clone());
+            self.keys.push(entry);
+        }
+        if self.default.is_none() {
+            self.default = other.default.filter(|address| self.get(address).is_some());
+        }
+        report
+    }
+
+    /// Decrypt a key, by label or address, into a wallet
+    pub fn unlock(&self, name: &str, passphrase: &str, network: NetworkId) -> Result<Wallet> {
+        let entry = self.get(name).ok_or_else(|| not_found(name))?;
+        let key = entry.secret.derive_key(passphrase)?;
+        self.unlock_with_key(name, &key, network)
+    }
+
+    /// Decrypt a key, by label or address, with a key from
+    /// `EncryptedSecret::derive_key`
+    pub fn unlock_with_key(
+        &self,
+        name: &str,
+        key: &[u8; KEY_LEN],
+        network: NetworkId,
+    ) -> Result<Wallet> {
+        let entry = self.get(name).ok_or_else(|| not_found(name))?;
+        let secret = entry
+            .secret
+            .decrypt_with_key(key, entry.address.as_bytes())?;
+        let secret = std::str::from_utf8(&secret)
+            .map_err(|_| KeystoreError::InvalidEntry("secret key is not hex".to_string()))?;
+        let wallet = Wallet::from_secret_key_hex(secret, network)
+            .map_err(|e| KeystoreError::InvalidEntry(e.to_string()))?;
+        if wallet.address() != entry.address {
+            return Err(KeystoreError::InvalidEntry(
+                "secret key does not match the address".to_string(),
+            ));
+        }
+        Ok(wallet)
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.keys
+            .iter()
+            .position(|entry| entry.address == name || entry.label.as_deref() == Some(name))
+    }
+
+    fn check_label(&self, label: &str) -> Result<()> {
+        // Labels and addresses share a namespace: a label must not look like an address
+        let valid = !label.is_empty()
+            && label.chars().count() <= MAX_LABEL_LEN
+            && !label.starts_with("B62")
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(KeystoreError::InvalidLabel(label.to_string()));
+        }
+        if self.position(label).is_some() {
+            return Err(KeystoreError::DuplicateLabel(label.to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn not_found(name: &str) -> KeystoreError {
+    KeystoreError::KeyNotFound(name.to_string())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: &KdfCost) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+    let params = Params::new(
+        kdf.memory_kib,
+        kdf.iterations,
+        kdf.parallelism,
+        Some(KEY_LEN),
+    )
+    .map_err(|e| KeystoreError::InvalidEntry(format!("invalid Argon2 parameters: {}", e)))?;
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| KeystoreError::InvalidEntry(format!("key derivation failed: {}", e)))?;
+    Ok(key)
+}
+
+/// Encrypt `msg` under a fresh nonce; returns the nonce and the ciphertext, hex
+fn seal(key: &[u8; KEY_LEN], msg: &[u8], aad: &[u8]) -> Result<(String, String)> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg, aad })
+        .map_err(|_| KeystoreError::InvalidEntry("encryption failed".to_string()))?;
+    Ok((hex::encode(nonce), hex::encode(ciphertext)))
+}
+
+fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &str,
+    ciphertext: &str,
+    aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let nonce = decode_hex("nonce", nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(KeystoreError::InvalidEntry(
+            "invalid nonce length".to_string(),
+        ));
+    }
+    let ciphertext = decode_hex("ciphertext", ciphertext)?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| KeystoreError::DecryptionFailed)
+}
+
+fn check_version(version: u8) -> Result<()> {
+    if version != VERSION {
+        return Err(KeystoreError::InvalidEntry(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|_| KeystoreError::InvalidEntry(format!("invalid {} hex", field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    /// Cheap parameters, so that tests run fast
+    const TEST_COST: KdfCost = KdfCost {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let encrypted =
+            EncryptedSecret::encrypt(b"secret", "correct horse", b"B62qaddress", TEST_COST)
+                .unwrap();
+        assert_eq!(
+            encrypted
+                .decrypt("correct horse", b"B62qaddress")
+                .unwrap()
+                .as_slice(),
+            b"secret"
+        );
+        assert_eq!(
+            encrypted.decrypt("wrong", b"B62qaddress"),
+            Err(KeystoreError::DecryptionFailed)
+        );
+        assert_eq!(
+            encrypted.decrypt("correct horse", b"B62qother"),
+            Err(KeystoreError::DecryptionFailed)
+        );
+
+        let key = encrypted.derive_key("correct horse").unwrap();
+        assert_eq!(
+            encrypted
+                .decrypt_with_key(&key, b"B62qaddress")
+                .unwrap()
+                .as_slice(),
+            b"secret"
+        );
+        assert_eq!(
+            encrypted.decrypt_with_key(&[0; KEY_LEN], b"B62qaddress"),
+            Err(KeystoreError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn test_wrap_key() {
+        let key = [7u8; KEY_LEN];
+        let wrapping_key = [9u8; KEY_LEN];
+        let wrapped = WrappedKey::wrap(&key, &wrapping_key, b"B62qaddress").unwrap();
+        assert_eq!(*wrapped.unwrap(&wrapping_key, b"B62qaddress").unwrap(), key);
+        assert_eq!(
+            wrapped.unwrap(&[0; KEY_LEN], b"B62qaddress"),
+            Err(KeystoreError::DecryptionFailed)
+        );
+        assert_eq!(
+            wrapped.unwrap(&wrapping_key, b"B62qother"),
+            Err(KeystoreError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn test_keystore_lifecycle() {
+        let mut keystore = Keystore::new().with_kdf_cost(TEST_COST);
+        let first = Wallet::new(NetworkId::TESTNET).unwrap();
+        let second = Wallet::new(NetworkId::TESTNET).unwrap();
+
+        keystore.add(&first, "pass", Some("main"), 1).unwrap();
+        keystore.add(&second, "pass", None, 2).unwrap();
+        assert_eq!(keystore.default_key().unwrap().address, first.address());
+        assert_eq!(
+            keystore.add(&first, "pass", None, 3).unwrap_err(),
+            KeystoreError::DuplicateKey(first.address())
+        );
+        assert_eq!(
+            keystore.set_label(&second.address(), Some("main")),
+            Err(KeystoreError::DuplicateLabel("main".to_string()))
+        );
+        assert!(keystore.set_label(&second.address(), Some("B62q")).is_err());
+
+        keystore
+            .set_label(&second.address(), Some("savings"))
+            .unwrap();
+        keystore.set_default("savings").unwrap();
+        let unlocked = keystore
+            .unlock("savings", "pass", NetworkId::TESTNET)
+            .unwrap();
+        assert!(unlocked.secret_eq(&second));
+        assert!(
+            keystore
+                .unlock("main", "wrong", NetworkId::TESTNET)
+                .is_err()
+        );
+
+        let mut storage = MemoryStorage::new();
+        keystore.save(&mut storage).unwrap();
+        let mut loaded = Keystore::load(&storage).unwrap();
+        assert_eq!(loaded.keys(), keystore.keys());
+
+        loaded.remove("savings").unwrap();
+        assert!(loaded.default_key().is_none());
+        assert_eq!(
+            loaded.remove("savings").unwrap_err(),
+            KeystoreError::KeyNotFound("savings".to_string())
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let shared = Wallet::new(NetworkId::TESTNET).unwrap();
+        let other = Wallet::new(NetworkId::TESTNET).unwrap();
+        let mut keystore = Keystore::new().with_kdf_cost(TEST_COST);
+        keystore.add(&shared, "pass", Some("main"), 1).unwrap();
+
+        let mut backup = Keystore::new().with_kdf_cost(TEST_COST);
+        backup.add(&other, "other pass", Some("main"), 2).unwrap();
+        backup.add(&shared, "pass", None, 3).unwrap();
+
+        // The default key of the backup only applies to a keystore without one
+        let mut empty = Keystore::new();
+        empty.merge(backup.clone());
+        assert_eq!(empty.default_key().unwrap().address, other.address());
+
+        let report = keystore.merge(backup);
+        assert_eq!(report.added, vec![other.address()]);
+        assert_eq!(report.skipped, vec![shared.address()]);
+        assert_eq!(report.dropped_labels, vec!["main".to_string()]);
+        assert_eq!(keystore.get("main").unwrap().address, shared.address());
+        assert_eq!(keystore.default_key().unwrap().address, shared.address());
+        assert!(
+            keystore
+                .unlock(&other.address(), "other pass", NetworkId::TESTNET)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Messages of the Mina Ledger app
+//!
+//! A Ledger device holds the secret keys and signs on its own screen, after
+//! the user checks the transaction there. This module builds the messages
+//! exchanged with the Mina app and decodes its answers, without talking to
+//! the device itself:
+//! - APDU commands to read the address of account N (BIP44 path
+//!   `m/44'/12586'/N'/0/0`) and to sign a payment or delegation
+//! - Framing of APDUs into the 64-byte HID reports of the USB transport
+//! - Decoding of status words and signatures; a signature is only returned
+//!   once it is checked against the transaction
+
+use mina_signer::NetworkId;
+use thiserror::Error;
+
+use crate::amount::Amount;
+use crate::signing::SignatureJson;
+use crate::transaction::{
+    SignedTransaction, Transaction, TransactionError, TransactionKind, verify_transaction,
+};
+
+/// USB vendor ID of Ledger devices
+pub const LEDGER_VENDOR_ID: u16 = 0x2c97;
+
+/// Length of a HID report
+pub const HID_PACKET_LEN: usize = 64;
+
+/// Class byte of the Mina app commands
+const CLA: u8 = 0xe0;
+
+/// Instruction to read an address
+const INS_GET_ADDRESS: u8 = 0x02;
+
+/// Instruction to sign a transaction
+const INS_SIGN_TRANSACTION: u8 = 0x03;
+
+/// HID channel of the transport
+const CHANNEL: u16 = 0x0101;
+
+/// Tag of APDU reports
+const TAG_APDU: u8 = 0x05;
+
+/// Length of an address, in ASCII characters
+const ADDRESS_LEN: usize = 55;
+
+/// Length of the memo field, zero padded
+const MEMO_LEN: usize = 32;
+
+/// Status words of the Mina app
+const SW_OK: u16 = 0x9000;
+const SW_REJECTED: u16 = 0x6986;
+const SW_LOCKED: u16 = 0x5515;
+const SW_APP_NOT_OPEN: [u16; 4] = [0x6d00, 0x6e00, 0x6e01, 0x6511];
+
+/// Errors that can occur while talking to the Mina app
+#[derive(Error, Debug)]
+pub enum LedgerError {
+    #[error("The transaction was rejected on the device")]
+    Rejected,
+    #[error("The device is locked: unlock it with its PIN")]
+    Locked,
+    #[error("The Mina app is not open on the device")]
+    AppNotOpen,
+    #[error("The device answered with status {0:#06x}")]
+    Status(u16),
+    #[error("Invalid answer from the device: {0}")]
+    InvalidResponse(String),
+    #[error("The device cannot sign this transaction: {0}")]
+    Unsupported(String),
+    #[error("The signature from the device does not match the transaction")]
+    InvalidSignature,
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+}
+
+pub type Result<T> = std::result::Result<T, LedgerError>;
+
+/// APDU reading the address of an account
+pub fn get_address_apdu(account: u32) -> Vec<u8> {
+    apdu(INS_GET_ADDRESS, &account.to_be_bytes())
+}
+
+/// APDU signing `transaction` with the key of `account`
+///
+/// The transaction is validated first, as for software signing.
+pub fn sign_transaction_apdu(
+    transaction: &Transaction,
+    account: u32,
+    network: &NetworkId,
+) -> Result<Vec<u8>> {
+    transaction.validate()?;
+    if !transaction.memo.is_ascii() {
+        return Err(LedgerError::Unsupported(
+            "the memo must be ASCII text".to_string(),
+        ));
+    }
+    let (amount, tag) = match transaction.kind() {
+        TransactionKind::Payment => (transaction.amount.unwrap_or(Amount::ZERO), 0x00),
+        TransactionKind::Delegation => (Amount::ZERO, 0x04),
+    };
+    let network = match network {
+        NetworkId::TESTNET => 0x00,
+        NetworkId::MAINNET => 0x01,
+    };
+
+    let mut data = Vec::with_capacity(4 + 2 * ADDRESS_LEN + 8 + 8 + 4 + 4 + MEMO_LEN + 2);
+    data.extend_from_slice(&account.to_be_bytes());
+    data.extend_from_slice(address_bytes(&transaction.from)?);
+    data.extend_from_slice(address_bytes(&transaction.to)?);
+    data.extend_from_slice(&amount.as_nanomina().to_be_bytes());
+    data.extend_from_slice(&transaction.fee.as_nanomina().to_be_bytes());
+    data.extend_from_slice(&transaction.nonce.to_be_bytes());
+    data.extend_from_slice(&transaction.valid_until.to_be_bytes());
+    let mut memo = [0u8; MEMO_LEN];
+    memo[..transaction.memo.len()].copy_from_slice(transaction.memo.as_bytes());
+    data.extend_from_slice(&memo);
+    data.push(tag);
+    data.push(network);
+    Ok(apdu(INS_SIGN_TRANSACTION, &data))
+}
+
+/// Decode the answer to `get_address_apdu`
+pub fn parse_address(response: &[u8]) -> Result<String> {
+    let data = check_status(response)?;
+    let address = std::str::from_utf8(data)
+        .map_err(|_| LedgerError::InvalidResponse("the address is not text".to_string()))?;
+    crate::address_to_pubkey(address)
+        .map_err(|e| LedgerError::InvalidResponse(format!("invalid address: {:?}", e)))?;
+    Ok(address.to_string())
+}
+
+/// Decode the answer to `sign_transaction_apdu`, checking the signature
+///
+/// The app returns the signature as `rx || s`, both 32 bytes big-endian.
+pub fn parse_signature(
+    response: &[u8],
+    transaction: &Transaction,
+    network: NetworkId,
+) -> Result<SignedTransaction> {
+    let data = check_status(response)?;
+    if data.len() != 64 {
+        return Err(LedgerError::InvalidResponse(format!(
+            "expected a 64-byte signature, got {} bytes",
+            data.len()
+        )));
+    }
+    let mut little_endian = data.to_vec();
+    little_endian[..32].reverse();
+    little_endian[32..].reverse();
+    let signature = SignatureJson::from_hex(&hex::encode(little_endian))
+        .map_err(|e| LedgerError::InvalidResponse(e.to_string()))?;
+
+    let signed = SignedTransaction {
+        public_key: transaction.from.clone(),
+        data: transaction.clone(),
+        signature,
+    };
+    if !verify_transaction(&signed, network)? {
+        return Err(LedgerError::InvalidSignature);
+    }
+    Ok(signed)
+}
+
+/// Split an APDU into HID reports
+pub fn frame(apdu: &[u8]) -> Vec<[u8; HID_PACKET_LEN]> {
+    // The first report carries the length of the APDU before its data
+    let mut payload = Vec::with_capacity(2 + apdu.len());
+    payload.extend_from_slice(&(apdu.len() as u16).to_be_bytes());
+    payload.extend_from_slice(apdu);
+
+    payload
+        .chunks(HID_PACKET_LEN - 5)
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut packet = [0u8; HID_PACKET_LEN];
+            packet[..2].copy_from_slice(&CHANNEL.to_be_bytes());
+            packet[2] = TAG_APDU;
+            packet[3..5].copy_from_slice(&(sequence as u16).to_be_bytes());
+            packet[5..5 + chunk.len()].copy_from_slice(chunk);
+            packet
+        })
+        .collect()
+}
+
+/// Reassembles an answer from the HID reports read from the device
+#[derive(Debug, Default)]
+pub struct Deframer {
+    length: Option<usize>,
+    sequence: u16,
+    data: Vec<u8>,
+}
+
+impl Deframer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next report; returns the answer once it is complete
+    pub fn push(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>> {
+        if packet.len() < 5
+            || packet[..2] != CHANNEL.to_be_bytes()
+            || packet[2] != TAG_APDU
+            || packet[3..5] != self.sequence.to_be_bytes()
+        {
+            return Err(LedgerError::InvalidResponse(
+                "unexpected HID report".to_string(),
+            ));
+        }
+        let mut chunk = &packet[5..];
+        let length = match self.length {
+            Some(length) => length,
+            None => {
+                if chunk.len() < 2 {
+                    return Err(LedgerError::InvalidResponse(
+                        "HID report too short".to_string(),
+                    ));
+                }
+                let length = usize::from(u16::from_be_bytes([chunk[0], chunk[1]]));
+                chunk = &chunk[2..];
+                *self.length.insert(length)
+            }
+        };
+        let wanted = (length - self.data.len()).min(chunk.len());
+        self.data.extend_from_slice(&chunk[..wanted]);
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok((self.data.len() == length).then(|| std::mem::take(&mut self.data)))
+    }
+}
+
+fn apdu(instruction: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![CLA, instruction, 0x00, 0x00, data.len() as u8];
+    apdu.extend_from_slice(data);
+    apdu
+}
+
+fn address_bytes(address: &str) -> Result<&[u8]> {
+    if address.len() != ADDRESS_LEN {
+        return Err(LedgerError::Unsupported(format!(
+            "address {} is not {} characters long",
+            address, ADDRESS_LEN
+        )));
+    }
+    Ok(address.as_bytes())
+}
+
+/// Check the status word ending an answer, returning the data before it
+fn check_status(response: &[u8]) -> Result<&[u8]> {
+    let Some((data, status)) = response.split_last_chunk::<2>() else {
+        return Err(LedgerError::InvalidResponse("empty answer".to_string()));
+    };
+    match u16::from_be_bytes(*status) {
+        SW_OK => Ok(data),
+        SW_REJECTED => Err(LedgerError::Rejected),
+        SW_LOCKED => Err(LedgerError::Locked),
+        status if SW_APP_NOT_OPEN.contains(&status) => Err(LedgerError::AppNotOpen),
+        status => Err(LedgerError::Status(status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    const RECEIVER: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    #[test]
+    fn test_sign_apdu_and_signature() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address(),
+            RECEIVER,
+            "1.5".parse().unwrap(),
+            "0.01".parse().unwrap(),
+            7,
+        )
+        .with_memo("rent");
+        let apdu = sign_transaction_apdu(&payment, 3, &NetworkId::TESTNET).unwrap();
+        assert_eq!(apdu[..5], [CLA, INS_SIGN_TRANSACTION, 0, 0, 172]);
+        assert_eq!(apdu[5..9], 3u32.to_be_bytes());
+        assert_eq!(&apdu[9..64], wallet.address().as_bytes());
+        assert_eq!(apdu[119..127], 1_500_000_000u64.to_be_bytes());
+        assert_eq!(&apdu[143..147], b"rent");
+        assert_eq!(apdu[175..], [0x00, 0x00]);
+        assert!(matches!(
+            sign_transaction_apdu(&payment.clone().with_memo("café"), 0, &NetworkId::TESTNET),
+            Err(LedgerError::Unsupported(_))
+        ));
+
+        // What the device would answer: the signature in big-endian
+        let signed = wallet.sign_transaction(&payment).unwrap();
+        let mut response = hex::decode(signed.signature.to_hex().unwrap()).unwrap();
+        response[..32].reverse();
+        response[32..].reverse();
+        response.extend_from_slice(&SW_OK.to_be_bytes());
+        let parsed = parse_signature(&response, &payment, NetworkId::TESTNET).unwrap();
+        assert_eq!(parsed, signed);
+        assert!(matches!(
+            parse_signature(&response, &payment, NetworkId::MAINNET),
+            Err(LedgerError::InvalidSignature)
+        ));
+        assert!(matches!(
+            parse_signature(&[0x69, 0x86], &payment, NetworkId::TESTNET),
+            Err(LedgerError::Rejected)
+        ));
+    }
+
+    #[test]
+    fn test_framing() {
+        let apdu: Vec<u8> = (0..=200).collect();
+        let packets = frame(&apdu);
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[0][..7], [0x01, 0x01, 0x05, 0x00, 0x00, 0x00, 201]);
+        assert_eq!(packets[1][3..5], [0x00, 0x01]);
+
+        let mut deframer = Deframer::new();
+        let mut answer = None;
+        for packet in &packets {
+            assert!(answer.is_none());
+            answer = deframer.push(packet).unwrap();
+        }
+        assert_eq!(answer.unwrap(), apdu);
+
+        // Reports must come in sequence
+        let mut deframer = Deframer::new();
+        assert!(deframer.push(&packets[1]).is_err());
+
+        assert_eq!(
+            get_address_apdu(1),
+            [CLA, INS_GET_ADDRESS, 0, 0, 4, 0, 0, 0, 1]
+        );
+        let mut response = RECEIVER.as_bytes().to_vec();
+        response.extend_from_slice(&SW_OK.to_be_bytes());
+        assert_eq!(parse_address(&response).unwrap(), RECEIVER);
+        assert!(matches!(
+            parse_address(&[0x55, 0x15]),
+            Err(LedgerError::Locked)
+        ));
+    }
+}
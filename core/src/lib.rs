@@ -3,11 +3,127 @@
 //! This library provides the core cryptographic functionality for the Mina web wallet,
 //! built on top of o1-labs/proof-systems crates:
 //! - Key generation and management
+//! - Key generation from dice, coin or hex entropy mixed with the OS RNG
+//! - MINA amounts without floating point
 //! - Schnorr signatures
 //! - Address encoding/decoding
-//! - Transaction signing
+//! - Transaction signing and nonce management
+//! - Resumable batch payments
+//! - Transaction envelopes for air-gapped signing
+//! - UR encoding of envelopes for animated QR codes
+//! - Decoding of signed transactions from other wallets and the daemon
+//! - Slot and epoch arithmetic
+//! - Staking reward estimates
+//! - Shamir secret sharing backups
+//! - Paper wallet export
+//! - QR codes as SVG, PNG or terminal text
+//! - Vanity address generation
+//! - Strict secret key parsing
+//! - Deterministic keys from raw seed bytes
+//! - Conversions between key, field and memo encodings
+//! - Encryption of messages to Mina addresses
+//! - Message signing and sign-in with Mina
+//! - o1js-compatible oracle data signing
+//! - Poseidon hashing compatible with o1js
+//! - Async GraphQL client for Mina nodes (`network` feature)
+//! - Persistent storage and an offline broadcast queue
+//! - Passphrase-encrypted keystore
+//! - Several accounts of the keystore unlocked at once
+//! - Import of keys exported by Auro and Clorio
+//! - Messages of the Mina Ledger app, for hardware wallet signing
+//! - Address book of labeled addresses
+//! - Encrypted backups of the keystore and address book
+//! - Hash-chained audit log of signed transactions
+//! - zkApp verification key decoding and hashing
+//! - Deterministic test vectors for other Mina signers
+//! - WalletConnect v2 sessions with dApps
+//!
+//! Without the default `std` feature, the crate itself is `no_std` with
+//! `alloc`: it keeps the wallet, signing, Poseidon, amount, Base58 and strict
+//! secret key modules, but not random key generation, which needs the OS
+//! random number generator. Bring the key from elsewhere and import it with
+//! `Wallet::from_secret_key_strict`. mina-signer and o1-utils still link std,
+//! so the crate does not build for targets without it, such as bare-metal
+//! microcontrollers.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod address_book;
+pub mod amount;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod backup;
+pub mod base58;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod consensus;
+#[cfg(feature = "std")]
+pub mod convert;
+#[cfg(feature = "std")]
+pub mod encryption;
+#[cfg(feature = "std")]
+pub mod entropy;
+#[cfg(feature = "std")]
+pub mod envelope;
+#[cfg(feature = "std")]
+pub mod import;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod ledger;
+#[cfg(feature = "std")]
+pub mod login;
+#[cfg(feature = "std")]
+pub mod manager;
+#[cfg(feature = "std")]
+pub mod message;
+#[cfg(feature = "network")]
+pub mod network;
+#[cfg(feature = "std")]
+pub mod nonce;
+#[cfg(feature = "std")]
+pub mod oracle;
+#[cfg(feature = "std")]
+pub mod paper;
+pub mod poseidon;
+#[cfg(feature = "std")]
+pub mod qr;
+#[cfg(feature = "std")]
+pub mod queue;
+#[cfg(feature = "std")]
+pub mod rewards;
+pub mod secret;
+#[cfg(feature = "std")]
+pub mod seed;
+pub mod signing;
+#[cfg(feature = "std")]
+pub mod sss;
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
+pub mod test_vectors;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "std")]
+pub mod transaction;
+#[cfg(feature = "std")]
+pub mod ur;
+#[cfg(feature = "std")]
+pub mod vanity;
+#[cfg(feature = "std")]
+pub mod verification_key;
 pub mod wallet;
+#[cfg(feature = "std")]
+pub mod walletconnect;
+
+use alloc::string::String;
 
 // Re-export types from mina-signer for convenience
 pub use mina_signer::keypair::KeypairError;
@@ -15,7 +131,8 @@ pub use mina_signer::pubkey::PubKeyError;
 pub use mina_signer::{CompressedPubKey, Keypair, NetworkId, PubKey, SecKey, Signature};
 
 // Re-export our wallet functionality
-pub use wallet::{Wallet, WalletError, WalletInfo};
+pub use amount::Amount;
+pub use wallet::{Wallet, WalletError, WalletInfo, WatchOnlyWallet};
 
 /// Field types from mina-curves
 pub mod fields {
@@ -23,6 +140,7 @@ pub mod fields {
 }
 
 /// Create a new random keypair
+#[cfg(feature = "std")]
 pub fn generate_keypair() -> Result<Keypair, KeypairError> {
     Keypair::rand(&mut rand::rngs::OsRng)
 }
@@ -0,0 +1,370 @@
+//! Sign-in with Mina
+//!
+//! A SIWE-style authentication flow where a website proves that a visitor
+//! controls a Mina address:
+//! 1. The server creates a `LoginChallenge` with a fresh nonce
+//! 2. The wallet signs the challenge text with `Wallet::sign_login_challenge`
+//! 3. The server checks domain, nonce, validity window and signature with `verify_login`
+//!
+//! The challenge text looks like:
+//!
+//! ```text
+//! example.com wants you to sign in with your Mina account:
+//! B62q...
+//!
+//! Sign in to Example
+//!
+//! URI: https://example.com/login
+//! Network: mainnet
+//! Nonce: 8f3b0c1d2e4a5b6c
+//! Issued At: 2024-01-01T00:00:00Z
+//! Expiration Time: 2024-01-01T00:10:00Z
+//! ```
+
+use mina_signer::NetworkId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::message::{self, SignedMessage};
+use crate::signing::SignatureError;
+use crate::time;
+use crate::wallet::{Wallet, WalletInfo};
+
+/// Suffix of the first line of every challenge
+const HEADER_SUFFIX: &str = " wants you to sign in with your Mina account:";
+
+/// Tolerated clock skew between client and server, in seconds
+pub const CLOCK_SKEW: u64 = 60;
+
+/// Minimum nonce length, in characters
+const MIN_NONCE_LEN: usize = 8;
+
+/// Errors that can occur while creating, signing or verifying a login
+#[derive(Error, Debug)]
+pub enum LoginError {
+    #[error("Invalid login message: {0}")]
+    InvalidMessage(String),
+    #[error("Domain mismatch: expected {expected}, got {got}")]
+    DomainMismatch { expected: String, got: String },
+    #[error("Nonce mismatch")]
+    NonceMismatch,
+    #[error("Address mismatch: message is for {message}, signed by {signer}")]
+    AddressMismatch { message: String, signer: String },
+    #[error("Network mismatch: challenge is for {0}")]
+    NetworkMismatch(String),
+    #[error("Login challenge has expired")]
+    Expired,
+    #[error("Login challenge is not valid yet")]
+    NotYetValid,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+}
+
+pub type Result<T> = std::result::Result<T, LoginError>;
+
+/// A login challenge issued by a website
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginChallenge {
+    /// Domain requesting the sign-in (e.g. `example.com`)
+    pub domain: String,
+    /// Mina address signing in
+    pub address: String,
+    /// Optional human-readable statement shown to the user
+    pub statement: Option<String>,
+    /// Optional URI of the resource being accessed
+    pub uri: Option<String>,
+    /// Network name: `mainnet` or `testnet`
+    pub network: String,
+    /// Random nonce chosen by the server, to prevent replays
+    pub nonce: String,
+    /// Issue time, as a Unix timestamp in seconds
+    pub issued_at: u64,
+    /// Optional expiration time, as a Unix timestamp in seconds
+    pub expiration_time: Option<u64>,
+}
+
+impl LoginChallenge {
+    /// Create a challenge with a fresh random nonce
+    pub fn new(domain: &str, address: &str, network: &str, issued_at: u64) -> Self {
+        Self {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            statement: None,
+            uri: None,
+            network: network.to_lowercase(),
+            nonce: generate_nonce(),
+            issued_at,
+            expiration_time: None,
+        }
+    }
+
+    /// Set the statement shown to the user
+    pub fn with_statement(mut self, statement: &str) -> Self {
+        self.statement = Some(statement.to_string());
+        self
+    }
+
+    /// Set the URI of the resource being accessed
+    pub fn with_uri(mut self, uri: &str) -> Self {
+        self.uri = Some(uri.to_string());
+        self
+    }
+
+    /// Expire the challenge `ttl` seconds after it was issued
+    pub fn with_ttl(mut self, ttl: u64) -> Self {
+        self.expiration_time = Some(self.issued_at + ttl);
+        self
+    }
+
+    /// Render the challenge as the text that gets signed
+    pub fn to_message(&self) -> String {
+        let mut lines = vec![
+            format!("{}{}", self.domain, HEADER_SUFFIX),
+            self.address.clone(),
+            String::new(),
+        ];
+        if let Some(statement) = &self.statement {
+            lines.push(statement.clone());
+            lines.push(String::new());
+        }
+        if let Some(uri) = &self.uri {
+            lines.push(format!("URI: {}", uri));
+        }
+        lines.push(format!("Network: {}", self.network));
+        lines.push(format!("Nonce: {}", self.nonce));
+        lines.push(format!("Issued At: {}", time::to_rfc3339(self.issued_at)));
+        if let Some(expiration) = self.expiration_time {
+            lines.push(format!("Expiration Time: {}", time::to_rfc3339(expiration)));
+        }
+        lines.join("\n")
+    }
+
+    /// Parse a challenge from its text form
+    pub fn parse(message: &str) -> Result<Self> {
+        let invalid = |reason: &str| LoginError::InvalidMessage(reason.to_string());
+        let mut lines = message.split('\n');
+
+        let domain = lines
+            .next()
+            .and_then(|l| l.strip_suffix(HEADER_SUFFIX))
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| invalid("missing header line"))?;
+        let address = lines.next().ok_or_else(|| invalid("missing address"))?;
+        if lines.next() != Some("") {
+            return Err(invalid("expected a blank line after the address"));
+        }
+
+        let mut rest: Vec<&str> = lines.collect();
+        let mut statement = None;
+        if let Some(first) = rest.first()
+            && !is_field_line(first)
+        {
+            if rest.get(1) != Some(&"") {
+                return Err(invalid("expected a blank line after the statement"));
+            }
+            statement = Some(first.to_string());
+            rest.drain(..2);
+        }
+
+        let mut challenge = Self {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            statement,
+            uri: None,
+            network: String::new(),
+            nonce: String::new(),
+            issued_at: 0,
+            expiration_time: None,
+        };
+        let mut issued_at = None;
+        for line in rest {
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| LoginError::InvalidMessage(format!("malformed line '{}'", line)))?;
+            match key {
+                "URI" => challenge.uri = Some(value.to_string()),
+                "Network" => challenge.network = value.to_string(),
+                "Nonce" => challenge.nonce = value.to_string(),
+                "Issued At" => issued_at = time::from_rfc3339(value),
+                "Expiration Time" => {
+                    challenge.expiration_time = Some(
+                        time::from_rfc3339(value)
+                            .ok_or_else(|| invalid("invalid expiration time"))?,
+                    )
+                }
+                _ => {
+                    return Err(LoginError::InvalidMessage(format!(
+                        "unknown field '{}'",
+                        key
+                    )));
+                }
+            }
+        }
+
+        challenge.issued_at = issued_at.ok_or_else(|| invalid("missing or invalid issue time"))?;
+        if parse_network(&challenge.network).is_none() {
+            return Err(invalid("missing or unknown network"));
+        }
+        if challenge.nonce.len() < MIN_NONCE_LEN
+            || !challenge.nonce.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(invalid("nonce must be at least 8 alphanumeric characters"));
+        }
+        Ok(challenge)
+    }
+}
+
+impl Wallet {
+    /// Sign a login challenge addressed to this wallet
+    pub fn sign_login_challenge(&self, challenge: &LoginChallenge) -> Result<SignedMessage> {
+        if challenge.address != self.address() {
+            return Err(LoginError::AddressMismatch {
+                message: challenge.address.clone(),
+                signer: self.address(),
+            });
+        }
+        if challenge.network != WalletInfo::from(self).network {
+            return Err(LoginError::NetworkMismatch(challenge.network.clone()));
+        }
+        Ok(self.sign_message(&challenge.to_message()))
+    }
+}
+
+/// Verify a signed login on the server side
+///
+/// Checks that the message is a well-formed challenge for `domain` carrying
+/// `nonce`, that it is valid at time `now` (Unix seconds, with `CLOCK_SKEW`
+/// tolerance), and that it was signed by the address it names.
+pub fn verify_login(
+    signed: &SignedMessage,
+    domain: &str,
+    nonce: &str,
+    now: u64,
+) -> Result<LoginChallenge> {
+    let challenge = LoginChallenge::parse(&signed.data)?;
+
+    if challenge.domain != domain {
+        return Err(LoginError::DomainMismatch {
+            expected: domain.to_string(),
+            got: challenge.domain,
+        });
+    }
+    if challenge.nonce != nonce {
+        return Err(LoginError::NonceMismatch);
+    }
+    if challenge.address != signed.public_key {
+        return Err(LoginError::AddressMismatch {
+            message: challenge.address,
+            signer: signed.public_key.clone(),
+        });
+    }
+    if challenge.issued_at > now + CLOCK_SKEW {
+        return Err(LoginError::NotYetValid);
+    }
+    if challenge
+        .expiration_time
+        .is_some_and(|expiration| expiration + CLOCK_SKEW <= now)
+    {
+        return Err(LoginError::Expired);
+    }
+
+    let network = parse_network(&challenge.network)
+        .ok_or_else(|| LoginError::NetworkMismatch(challenge.network.clone()))?;
+    if !message::verify_message(signed, network)? {
+        return Err(LoginError::InvalidSignature);
+    }
+    Ok(challenge)
+}
+
+/// Generate a random 128-bit nonce, hex encoded
+pub fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn is_field_line(line: &str) -> bool {
+    [
+        "URI: ",
+        "Network: ",
+        "Nonce: ",
+        "Issued At: ",
+        "Expiration Time: ",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+}
+
+fn parse_network(network: &str) -> Option<NetworkId> {
+    match network {
+        "mainnet" => Some(NetworkId::MAINNET),
+        "testnet" => Some(NetworkId::TESTNET),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn challenge_for(wallet: &Wallet) -> LoginChallenge {
+        LoginChallenge::new("example.com", &wallet.address(), "testnet", NOW)
+            .with_statement("Sign in to Example")
+            .with_uri("https://example.com/login")
+            .with_ttl(600)
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let challenge = challenge_for(&wallet);
+        assert_eq!(
+            LoginChallenge::parse(&challenge.to_message()).unwrap(),
+            challenge
+        );
+
+        let minimal = LoginChallenge::new("example.com", &wallet.address(), "testnet", NOW);
+        assert_eq!(
+            LoginChallenge::parse(&minimal.to_message()).unwrap(),
+            minimal
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_login() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let challenge = challenge_for(&wallet);
+        let signed = wallet.sign_login_challenge(&challenge).unwrap();
+
+        let verified = verify_login(&signed, "example.com", &challenge.nonce, NOW + 10).unwrap();
+        assert_eq!(verified.address, wallet.address());
+
+        assert!(matches!(
+            verify_login(&signed, "evil.com", &challenge.nonce, NOW),
+            Err(LoginError::DomainMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_login(&signed, "example.com", "0000000000000000", NOW),
+            Err(LoginError::NonceMismatch)
+        ));
+        assert!(matches!(
+            verify_login(&signed, "example.com", &challenge.nonce, NOW + 3600),
+            Err(LoginError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_sign_challenge_for_other_address() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let other = Wallet::new(NetworkId::TESTNET).unwrap();
+        assert!(matches!(
+            other.sign_login_challenge(&challenge_for(&wallet)),
+            Err(LoginError::AddressMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+//! Several accounts open at once
+//!
+//! A `WalletManager` pairs a `Keystore` with the wallets unlocked from it, so
+//! that a front-end handles many accounts instead of one wallet: accounts are
+//! added and removed in the keystore, and each one is locked or unlocked on
+//! its own. Operations without an account name act on the default account.
+//!
+//! Unlocked wallets are dropped, wiping their keys, when their account is
+//! locked or removed, and when the manager is dropped.
+//!
+//! A change that must be persisted first is made on a `staged` copy, then
+//! `merge`d once the keystore of the copy is saved, so that a failed save
+//! leaves the manager as it was.
+
+use mina_signer::NetworkId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::keystore::{KeyEntry, Keystore, KeystoreError};
+use crate::wallet::Wallet;
+
+/// Errors of the account manager
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ManagerError {
+    #[error("No default account: name one, or set a default")]
+    NoDefault,
+    #[error("Account {0} is locked")]
+    Locked(String),
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+}
+
+pub type Result<T> = std::result::Result<T, ManagerError>;
+
+/// An account of the manager, without its secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct AccountStatus {
+    pub address: String,
+    pub label: Option<String>,
+    /// When the key was added, as a Unix timestamp in seconds
+    pub created_at: u64,
+    pub is_default: bool,
+    pub unlocked: bool,
+}
+
+/// A keystore and the wallets unlocked from it, by address
+#[derive(Debug, Default)]
+pub struct WalletManager {
+    keystore: Keystore,
+    unlocked: BTreeMap<String, Wallet>,
+}
+
+impl WalletManager {
+    /// Manage the accounts of `keystore`, all locked
+    pub fn new(keystore: Keystore) -> Self {
+        Self {
+            keystore,
+            unlocked: BTreeMap::new(),
+        }
+    }
+
+    /// The keystore, to persist after a change
+    pub fn keystore(&self) -> &Keystore {
+        &self.keystore
+    }
+
+    /// A manager over a copy of the keystore, all locked, to change then
+    /// `merge` once its keystore is saved
+    pub fn staged(&self) -> Self {
+        Self::new(self.keystore.clone())
+    }
+
+    /// Take the keystore of `staged` and the wallets unlocked in it; the
+    /// accounts no longer in the keystore are locked
+    pub fn merge(&mut self, staged: Self) {
+        self.keystore = staged.keystore;
+        self.unlocked.extend(staged.unlocked);
+        let keystore = &self.keystore;
+        self.unlocked
+            .retain(|address, _| keystore.get(address).is_some());
+    }
+
+    /// The accounts, in the order they were added
+    pub fn accounts(&self) -> Vec<AccountStatus> {
+        self.keystore
+            .keys()
+            .iter()
+            .map(|entry| self.status(entry))
+            .collect()
+    }
+
+    /// Encrypt the key of `wallet` into the keystore and keep it unlocked
+    pub fn add(
+        &mut self,
+        wallet: Wallet,
+        passphrase: &str,
+        label: Option<&str>,
+        created_at: u64,
+    ) -> Result<AccountStatus> {
+        let entry = self.keystore.add(&wallet, passphrase, label, created_at)?;
+        let status = AccountStatus {
+            unlocked: true,
+            ..self.status(entry)
+        };
+        self.unlocked.insert(wallet.address(), wallet);
+        Ok(status)
+    }
+
+    /// Remove an account, by label or address, locking it first
+    pub fn remove(&mut self, name: &str) -> Result<KeyEntry> {
+        let entry = self.keystore.remove(name)?;
+        self.unlocked.remove(&entry.address);
+        Ok(entry)
+    }
+
+    /// Make an account the default one
+    pub fn set_default(&mut self, name: &str) -> Result<()> {
+        Ok(self.keystore.set_default(name)?)
+    }
+
+    /// Decrypt the key of an account and keep it until `lock`
+    pub fn unlock(
+        &mut self,
+        name: Option<&str>,
+        passphrase: &str,
+        network: NetworkId,
+    ) -> Result<AccountStatus> {
+        let address = self.entry(name)?.address.clone();
+        let wallet = self.keystore.unlock(&address, passphrase, network)?;
+        self.unlocked.insert(address.clone(), wallet);
+        Ok(self.status(self.entry(Some(&address))?))
+    }
+
+    /// Drop the wallet of an account; returns whether it was unlocked
+    pub fn lock(&mut self, name: Option<&str>) -> Result<bool> {
+        let address = self.entry(name)?.address.clone();
+        Ok(self.unlocked.remove(&address).is_some())
+    }
+
+    /// Drop every unlocked wallet
+    pub fn lock_all(&mut self) {
+        self.unlocked.clear();
+    }
+
+    /// Whether any account is unlocked
+    pub fn any_unlocked(&self) -> bool {
+        !self.unlocked.is_empty()
+    }
+
+    /// The wallet of an unlocked account
+    pub fn wallet(&self, name: Option<&str>) -> Result<&Wallet> {
+        let address = &self.entry(name)?.address;
+        self.unlocked
+            .get(address)
+            .ok_or_else(|| ManagerError::Locked(address.clone()))
+    }
+
+    /// An account by label or address, or the default one
+    fn entry(&self, name: Option<&str>) -> Result<&KeyEntry> {
+        match name {
+            Some(name) => self
+                .keystore
+                .get(name)
+                .ok_or_else(|| KeystoreError::KeyNotFound(name.to_string()).into()),
+            None => self.keystore.default_key().ok_or(ManagerError::NoDefault),
+        }
+    }
+
+    fn status(&self, entry: &KeyEntry) -> AccountStatus {
+        AccountStatus {
+            address: entry.address.clone(),
+            label: entry.label.clone(),
+            created_at: entry.created_at,
+            is_default: self
+                .keystore
+                .default_key()
+                .is_some_and(|default| default.address == entry.address),
+            unlocked: self.unlocked.contains_key(&entry.address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::KdfCost;
+
+    /// Cheap parameters, so that tests run fast
+    const TEST_COST: KdfCost = KdfCost {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn manager() -> (WalletManager, String, String) {
+        let mut manager = WalletManager::new(Keystore::new().with_kdf_cost(TEST_COST));
+        let first = Wallet::new(NetworkId::TESTNET).unwrap();
+        let second = Wallet::new(NetworkId::TESTNET).unwrap();
+        let (first_address, second_address) = (first.address(), second.address());
+        manager.add(first, "one", Some("main"), 1).unwrap();
+        manager.add(second, "two", Some("savings"), 2).unwrap();
+        (manager, first_address, second_address)
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let (mut manager, first, second) = manager();
+        assert!(manager.accounts().iter().all(|a| a.unlocked));
+        assert_eq!(manager.wallet(None).unwrap().address(), first);
+
+        assert!(manager.lock(Some("savings")).unwrap());
+        assert!(!manager.lock(Some("savings")).unwrap());
+        assert_eq!(
+            manager.wallet(Some("savings")).unwrap_err(),
+            ManagerError::Locked(second.clone())
+        );
+        // The other account stays unlocked
+        assert!(manager.wallet(Some("main")).is_ok());
+
+        assert_eq!(
+            manager.unlock(Some("savings"), "one", NetworkId::TESTNET),
+            Err(ManagerError::Keystore(KeystoreError::DecryptionFailed))
+        );
+        let status = manager
+            .unlock(Some(&second), "two", NetworkId::TESTNET)
+            .unwrap();
+        assert!(status.unlocked && !status.is_default);
+
+        manager.lock_all();
+        assert!(!manager.any_unlocked());
+    }
+
+    #[test]
+    fn test_default_and_remove() {
+        let (mut manager, first, second) = manager();
+        manager.set_default("savings").unwrap();
+        assert_eq!(manager.wallet(None).unwrap().address(), second);
+
+        assert_eq!(manager.remove("savings").unwrap().address, second);
+        assert_eq!(manager.wallet(None).unwrap_err(), ManagerError::NoDefault);
+        assert_eq!(manager.accounts().len(), 1);
+        assert_eq!(manager.accounts()[0].address, first);
+        assert!(matches!(
+            manager.lock(Some("savings")),
+            Err(ManagerError::Keystore(KeystoreError::KeyNotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_staged_and_merge() {
+        let (mut manager, first, second) = manager();
+        let mut staged = manager.staged();
+        staged.remove("savings").unwrap();
+        let third = Wallet::new(NetworkId::TESTNET).unwrap();
+        let third_address = third.address();
+        staged.add(third, "three", Some("spare"), 3).unwrap();
+
+        // Nothing changes until the staged copy is merged
+        assert_eq!(manager.accounts().len(), 2);
+        assert!(manager.wallet(Some(&second)).is_ok());
+
+        manager.merge(staged);
+        let accounts: Vec<_> = manager.accounts().into_iter().map(|a| a.address).collect();
+        assert_eq!(accounts, [first.clone(), third_address.clone()]);
+        // Unlocked accounts stay unlocked, removed ones are locked
+        assert!(manager.wallet(Some(&first)).is_ok());
+        assert!(manager.wallet(Some(&third_address)).is_ok());
+        assert!(!manager.unlocked.contains_key(&second));
+    }
+}
@@ -0,0 +1,85 @@
+//! String message signing
+//!
+//! Messages are hashed as their raw UTF-8 bytes with the legacy scheme, and
+//! signed messages use the same JSON shape as Auro's `signMessage`:
+//! `{ publicKey, data, signature: { field, scalar } }`.
+
+use mina_signer::{Hashable, NetworkId, PubKey, ROInput, Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::signing::{self, SignatureError, SignatureJson, SignatureScheme};
+use crate::wallet::Wallet;
+
+/// A string message to sign
+#[derive(Debug, Clone)]
+pub struct Message(pub String);
+
+impl Hashable for Message {
+    type D = NetworkId;
+
+    fn to_roinput(&self) -> ROInput {
+        ROInput::new().append_bytes(self.0.as_bytes())
+    }
+
+    fn domain_string(network_id: NetworkId) -> Option<String> {
+        signing::domain_string(network_id)
+    }
+}
+
+/// A message together with its signature and signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct SignedMessage {
+    /// Address of the signer
+    pub public_key: String,
+    /// The signed message
+    pub data: String,
+    /// The signature
+    pub signature: SignatureJson,
+}
+
+impl Wallet {
+    /// Sign a string message
+    pub fn sign_message(&self, message: &str) -> SignedMessage {
+        let signature = self.sign(&Message(message.to_string()), SignatureScheme::Legacy);
+        SignedMessage {
+            public_key: self.address(),
+            data: message.to_string(),
+            signature: SignatureJson::from(&signature),
+        }
+    }
+}
+
+/// Verify a signed message against the address it claims to come from
+pub fn verify_message(signed: &SignedMessage, network: NetworkId) -> Result<bool, SignatureError> {
+    let public_key = PubKey::from_address(&signed.public_key)
+        .map_err(|e| SignatureError::InvalidEncoding(format!("invalid public key: {:?}", e)))?;
+    let signature = Signature::try_from(&signed.signature)?;
+    Ok(signing::verify(
+        &public_key,
+        &Message(signed.data.clone()),
+        &signature,
+        network,
+        SignatureScheme::Legacy,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_message() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let signed = wallet.sign_message("I own this address");
+        assert_eq!(signed.public_key, wallet.address());
+        assert!(verify_message(&signed, NetworkId::TESTNET).unwrap());
+
+        // Tampered message and wrong network both fail
+        let mut tampered = signed.clone();
+        tampered.data.push('!');
+        assert!(!verify_message(&tampered, NetworkId::TESTNET).unwrap());
+        assert!(!verify_message(&signed, NetworkId::MAINNET).unwrap());
+    }
+}
@@ -0,0 +1,300 @@
+//! Accounting exports of transaction history
+//!
+//! History entries are turned into `AccountingRow`s for bookkeeping and tax
+//! preparation: one row per transaction, with the fee charged to the address
+//! and the signed change of its balance. Rows can be valued in a fiat
+//! currency with daily MINA prices from a CoinGecko-compatible API, and
+//! written as CSV by the caller or as an OFX bank statement by `to_ofx`.
+//!
+//! Failed transactions still pay their fee, but move no MINA.
+
+use serde::{Deserialize, Serialize};
+
+use super::archive::{Direction, HistoryEntry};
+use super::{NetworkError, Result};
+use crate::amount::Amount;
+use crate::time;
+
+/// Public CoinGecko API, used when no other price API is given
+pub const DEFAULT_PRICE_API: &str = "https://api.coingecko.com/api/v3";
+
+/// CoinGecko identifier of MINA
+pub const MINA_COIN_ID: &str = "mina-protocol";
+
+/// Furthest a price may be from a transaction to value it, in seconds
+pub const MAX_PRICE_GAP: u64 = 2 * 86_400;
+
+/// ISO 4217 code for "no currency": OFX statements are in MINA, which has none
+const OFX_CURRENCY: &str = "XXX";
+
+/// A transaction, as seen by the books of one address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountingRow {
+    /// Block time, RFC 3339 UTC
+    pub date: String,
+    pub hash: String,
+    pub kind: String,
+    pub direction: Direction,
+    /// The other side of the transaction
+    pub counterparty: String,
+    /// MINA moved, zero for delegations and failed transactions
+    pub amount: Amount,
+    /// Fee paid by the address, zero when it received the transaction
+    pub fee: Amount,
+    /// Change of the balance of the address, in MINA, e.g. `-1.51`
+    pub net: String,
+    pub memo: String,
+    pub failed: bool,
+    /// Price of one MINA in the fiat currency at the time of the transaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    /// `net` in the fiat currency, to the cent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// Daily MINA prices in a fiat currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceHistory {
+    /// Lowercase currency code, e.g. `usd`
+    pub currency: String,
+    /// Unix timestamps in seconds and prices, oldest first
+    pub points: Vec<(u64, f64)>,
+}
+
+#[derive(Deserialize)]
+struct MarketChart {
+    /// Timestamps in milliseconds and prices
+    prices: Vec<(f64, f64)>,
+}
+
+impl PriceHistory {
+    /// The price closest to `timestamp`, if one is within `MAX_PRICE_GAP`
+    pub fn price_at(&self, timestamp: u64) -> Option<f64> {
+        let after = self.points.partition_point(|&(t, _)| t <= timestamp);
+        [after.checked_sub(1), Some(after)]
+            .into_iter()
+            .flatten()
+            .filter_map(|i| self.points.get(i))
+            .min_by_key(|(t, _)| t.abs_diff(timestamp))
+            .filter(|(t, _)| t.abs_diff(timestamp) <= MAX_PRICE_GAP)
+            .map(|&(_, price)| price)
+    }
+}
+
+/// Fetch MINA prices between two timestamps from a CoinGecko-compatible API
+///
+/// CoinGecko answers with daily prices for ranges longer than 90 days, and
+/// finer ones for shorter ranges.
+pub async fn fetch_price_history(
+    api: &str,
+    currency: &str,
+    from: u64,
+    to: u64,
+) -> Result<PriceHistory> {
+    let url = format!(
+        "{}/coins/{}/market_chart/range?vs_currency={}&from={}&to={}",
+        api.trim_end_matches('/'),
+        MINA_COIN_ID,
+        currency.to_lowercase(),
+        from.saturating_sub(MAX_PRICE_GAP),
+        to.saturating_add(MAX_PRICE_GAP)
+    );
+    let text = reqwest::get(&url).await?.error_for_status()?.text().await?;
+    parse_market_chart(&text, currency)
+}
+
+fn parse_market_chart(text: &str, currency: &str) -> Result<PriceHistory> {
+    let chart: MarketChart = serde_json::from_str(text)
+        .map_err(|e| NetworkError::InvalidResponse(format!("invalid price history: {}", e)))?;
+    let mut points: Vec<(u64, f64)> = chart
+        .prices
+        .into_iter()
+        .map(|(ms, price)| ((ms / 1000.0) as u64, price))
+        .collect();
+    points.sort_by_key(|&(t, _)| t);
+    Ok(PriceHistory {
+        currency: currency.to_lowercase(),
+        points,
+    })
+}
+
+/// Accounting rows of history entries, valued with `prices` if given
+pub fn rows(entries: &[HistoryEntry], prices: Option<&PriceHistory>) -> Vec<AccountingRow> {
+    entries
+        .iter()
+        .map(|entry| {
+            let failed = entry.failure_reason.is_some();
+            let amount = if failed || entry.kind != "payment" {
+                Amount::ZERO
+            } else {
+                entry.amount
+            };
+            let (fee, counterparty) = match entry.direction {
+                Direction::Incoming => (Amount::ZERO, &entry.from),
+                Direction::Outgoing | Direction::SelfTransfer => (entry.fee, &entry.to),
+            };
+            let net = match entry.direction {
+                Direction::Incoming => i128::from(amount.as_nanomina()),
+                Direction::Outgoing => {
+                    -i128::from(amount.as_nanomina()) - i128::from(fee.as_nanomina())
+                }
+                Direction::SelfTransfer => -i128::from(fee.as_nanomina()),
+            };
+            let price = prices.and_then(|p| p.price_at(entry.timestamp));
+            AccountingRow {
+                date: time::to_rfc3339(entry.timestamp),
+                hash: entry.hash.clone(),
+                kind: entry.kind.clone(),
+                direction: entry.direction,
+                counterparty: counterparty.clone(),
+                amount,
+                fee,
+                net: format_signed(net),
+                memo: entry.memo.clone(),
+                failed,
+                price,
+                value: price.map(|price| format!("{:.2}", net as f64 / 1e9 * price)),
+            }
+        })
+        .collect()
+}
+
+/// An OFX 2 bank statement of `address`, amounts in MINA
+pub fn to_ofx(address: &str, rows: &[AccountingRow]) -> String {
+    let ofx_date = |date: &str| {
+        date.chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+    };
+    let (start, end) = match (
+        rows.iter().map(|r| &r.date).min(),
+        rows.iter().map(|r| &r.date).max(),
+    ) {
+        (Some(start), Some(end)) => (ofx_date(start), ofx_date(end)),
+        _ => (String::new(), String::new()),
+    };
+
+    let mut transactions = String::new();
+    for row in rows {
+        let kind = if row.net.starts_with('-') {
+            "DEBIT"
+        } else {
+            "CREDIT"
+        };
+        let mut memo = format!("{} {}", row.kind, row.direction.as_str());
+        if !row.memo.is_empty() {
+            memo = format!("{}: {}", memo, row.memo);
+        }
+        if row.failed {
+            memo.push_str(" (failed)");
+        }
+        transactions.push_str(&format!(
+            "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>{}</TRNAMT>\
+             <FITID>{}</FITID><NAME>{}</NAME><MEMO>{}</MEMO></STMTTRN>\n",
+            kind,
+            ofx_date(&row.date),
+            row.net,
+            escape_xml(&row.hash),
+            escape_xml(&row.counterparty.chars().take(32).collect::<String>()),
+            escape_xml(&memo)
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <?OFX OFXHEADER=\"200\" VERSION=\"211\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n\
+         <OFX><BANKMSGSRSV1><STMTTRNRS><TRNUID>0</TRNUID>\
+         <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n\
+         <STMTRS><CURDEF>{}</CURDEF>\
+         <BANKACCTFROM><BANKID>MINA</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n\
+         <BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>\n{}</BANKTRANLIST>\n\
+         </STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>\n",
+        OFX_CURRENCY,
+        escape_xml(address),
+        start,
+        end,
+        transactions
+    )
+}
+
+/// Format nanomina as signed decimal MINA
+fn format_signed(nanomina: i128) -> String {
+    let amount = Amount::from_nanomina(nanomina.unsigned_abs() as u64);
+    if nanomina < 0 {
+        format!("-{}", amount)
+    } else {
+        amount.to_string()
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn entry(direction: Direction, failure_reason: Option<&str>) -> HistoryEntry {
+        HistoryEntry {
+            hash: "5Jhash".to_string(),
+            kind: "payment".to_string(),
+            direction,
+            from: ADDRESS.to_string(),
+            to: "B62qother".to_string(),
+            amount: "1.5".parse().unwrap(),
+            fee: "0.01".parse().unwrap(),
+            nonce: 0,
+            memo: "rent <may>".to_string(),
+            block_height: 10,
+            timestamp: 1_704_067_200,
+            failure_reason: failure_reason.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_rows() {
+        let prices = parse_market_chart(
+            r#"{"prices": [[1703980800000, 1.0], [1704067300000, 2.0]], "market_caps": []}"#,
+            "USD",
+        )
+        .unwrap();
+        assert_eq!(prices.currency, "usd");
+        assert_eq!(prices.price_at(1_704_067_200), Some(2.0));
+        assert_eq!(prices.price_at(1_703_980_000), Some(1.0));
+        assert_eq!(prices.price_at(1_800_000_000), None);
+
+        let entries = [
+            entry(Direction::Outgoing, None),
+            entry(Direction::Incoming, None),
+            entry(Direction::Outgoing, Some("Amount_insufficient")),
+        ];
+        let rows = rows(&entries, Some(&prices));
+        assert_eq!(rows[0].net, "-1.51");
+        assert_eq!(rows[0].value.as_deref(), Some("-3.02"));
+        assert_eq!(rows[0].counterparty, "B62qother");
+        assert_eq!(rows[1].net, "1.5");
+        assert_eq!(rows[1].fee, Amount::ZERO);
+        assert_eq!(rows[1].counterparty, ADDRESS);
+        assert_eq!(rows[2].net, "-0.01");
+        assert!(rows[2].failed);
+        assert_eq!(rows[0].date, "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_to_ofx() {
+        let rows = rows(&[entry(Direction::Outgoing, None)], None);
+        assert_eq!(rows[0].price, None);
+        let ofx = to_ofx(ADDRESS, &rows);
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20240101000000</DTPOSTED>"));
+        assert!(ofx.contains("<TRNAMT>-1.51</TRNAMT><FITID>5Jhash</FITID>"));
+        assert!(ofx.contains("<MEMO>payment outgoing: rent &lt;may&gt;</MEMO>"));
+        assert!(ofx.contains(&format!("<ACCTID>{}</ACCTID>", ADDRESS)));
+    }
+}
@@ -0,0 +1,319 @@
+//! Transaction history from an archive indexer
+//!
+//! The daemon only knows about recent blocks, so history comes from an
+//! indexer backed by an archive node, queried through its GraphQL API
+//! (MinaExplorer schema). Results are normalized into `HistoryEntry`, with
+//! memos decoded and amounts as `Amount`.
+//!
+//! Pages are ordered from the newest block to the oldest. The cursor is a
+//! block height: a page contains transactions strictly below it.
+
+use mina_signer::NetworkId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::graphql::GraphQlClient;
+use super::{NetworkError, Result, from_str_or_number};
+use crate::amount::Amount;
+use crate::time;
+use crate::transaction;
+
+/// Public indexer used by default for mainnet history
+pub const DEFAULT_MAINNET_ARCHIVE_ENDPOINT: &str = "https://graphql.minaexplorer.com";
+
+/// Number of transactions per page when none is specified
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+const HISTORY_QUERY: &str = r#"query History($query: TransactionQueryInput!, $limit: Int) {
+  transactions(query: $query, limit: $limit, sortBy: BLOCKHEIGHT_DESC) {
+    hash
+    kind
+    from
+    to
+    amount
+    fee
+    nonce
+    memo
+    dateTime
+    blockHeight
+    failureReason
+  }
+}"#;
+
+/// Default indexer endpoint for a network, if there is one
+pub fn default_archive_endpoint(network: &NetworkId) -> Option<&'static str> {
+    match network {
+        NetworkId::MAINNET => Some(DEFAULT_MAINNET_ARCHIVE_ENDPOINT),
+        NetworkId::TESTNET => None,
+    }
+}
+
+/// Direction of a transaction, relative to the queried address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+    /// Sent from the address to itself
+    #[serde(rename = "self")]
+    SelfTransfer,
+}
+
+impl Direction {
+    /// The name of the direction, as serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+            Direction::SelfTransfer => "self",
+        }
+    }
+}
+
+/// A transaction in the history of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub hash: String,
+    /// `payment` or `delegation`
+    pub kind: String,
+    pub direction: Direction,
+    pub from: String,
+    pub to: String,
+    pub amount: Amount,
+    pub fee: Amount,
+    pub nonce: u32,
+    /// Decoded memo text
+    pub memo: String,
+    pub block_height: u64,
+    /// Block time, as a Unix timestamp in seconds
+    pub timestamp: u64,
+    /// Why the transaction failed, if it did
+    pub failure_reason: Option<String>,
+}
+
+/// A page of history, newest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub transactions: Vec<HistoryEntry>,
+    /// Cursor of the next (older) page, if there may be one
+    pub next_cursor: Option<u64>,
+}
+
+/// Client for an archive indexer
+#[derive(Debug, Clone)]
+pub struct ArchiveClient {
+    graphql: GraphQlClient,
+}
+
+#[derive(Deserialize)]
+struct HistoryData {
+    transactions: Vec<RawTransaction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTransaction {
+    hash: String,
+    kind: String,
+    from: String,
+    to: String,
+    amount: Amount,
+    fee: Amount,
+    #[serde(deserialize_with = "from_str_or_number")]
+    nonce: u32,
+    memo: String,
+    date_time: String,
+    #[serde(deserialize_with = "from_str_or_number")]
+    block_height: u64,
+    failure_reason: Option<String>,
+}
+
+impl ArchiveClient {
+    /// Create a client for the given indexer GraphQL endpoint
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            graphql: GraphQlClient::new(endpoint),
+        }
+    }
+
+    /// Create a client failing over between several indexers, in order of preference
+    pub fn with_endpoints(endpoints: Vec<String>) -> Self {
+        Self {
+            graphql: GraphQlClient::with_endpoints(endpoints),
+        }
+    }
+
+    /// The endpoint this client sends queries to
+    pub fn endpoint(&self) -> &str {
+        self.graphql.endpoint()
+    }
+
+    /// Send a raw query to the indexer and deserialize its `data` field
+    pub async fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        self.graphql.query(query, variables).await
+    }
+
+    /// Fetch a page of the canonical transactions sent or received by an address
+    ///
+    /// Pass `before = None` for the newest page, then the previous page's
+    /// `next_cursor`.
+    pub async fn history(
+        &self,
+        address: &str,
+        limit: u32,
+        before: Option<u64>,
+    ) -> Result<HistoryPage> {
+        let mut query = json!({
+            "canonical": true,
+            "OR": [{ "from": address }, { "to": address }],
+        });
+        if let Some(before) = before {
+            query["blockHeight_lt"] = json!(before);
+        }
+        let data: HistoryData = self
+            .query(HISTORY_QUERY, json!({ "query": query, "limit": limit }))
+            .await?;
+        let entries = data
+            .transactions
+            .into_iter()
+            .map(|raw| normalize(raw, address))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(paginate(entries, limit))
+    }
+}
+
+fn normalize(raw: RawTransaction, address: &str) -> Result<HistoryEntry> {
+    let direction = match (raw.from == address, raw.to == address) {
+        (true, true) => Direction::SelfTransfer,
+        (true, false) => Direction::Outgoing,
+        _ => Direction::Incoming,
+    };
+    let kind = match raw.kind.as_str() {
+        "PAYMENT" | "payment" => "payment",
+        "STAKE_DELEGATION" | "delegation" => "delegation",
+        other => other,
+    }
+    .to_string();
+    let timestamp = parse_date_time(&raw.date_time).ok_or_else(|| {
+        NetworkError::InvalidResponse(format!("invalid date '{}'", raw.date_time))
+    })?;
+    Ok(HistoryEntry {
+        hash: raw.hash,
+        kind,
+        direction,
+        from: raw.from,
+        to: raw.to,
+        amount: raw.amount,
+        fee: raw.fee,
+        nonce: raw.nonce,
+        // Indexers return memos Base58 encoded; keep anything else as is
+        memo: transaction::decode_memo_base58(&raw.memo).unwrap_or(raw.memo),
+        block_height: raw.block_height,
+        timestamp,
+        failure_reason: raw.failure_reason,
+    })
+}
+
+/// Parse `YYYY-MM-DDTHH:MM:SS[.fff]Z`, dropping fractional seconds
+fn parse_date_time(date_time: &str) -> Option<u64> {
+    match date_time.split_once('.') {
+        Some((seconds, fraction)) if fraction.ends_with('Z') => {
+            time::from_rfc3339(&format!("{}Z", seconds))
+        }
+        _ => time::from_rfc3339(date_time),
+    }
+}
+
+/// Compute the next cursor of a page fetched with `limit`
+///
+/// A block can hold several transactions of the same address, and the page
+/// limit may split them. When the page is full, the transactions of its
+/// oldest block are dropped and the next page starts again at that block.
+fn paginate(mut entries: Vec<HistoryEntry>, limit: u32) -> HistoryPage {
+    if entries.len() < limit as usize || entries.is_empty() {
+        return HistoryPage {
+            transactions: entries,
+            next_cursor: None,
+        };
+    }
+
+    let oldest = entries.iter().map(|e| e.block_height).min().unwrap_or(0);
+    if entries.iter().any(|e| e.block_height != oldest) {
+        entries.retain(|e| e.block_height != oldest);
+        HistoryPage {
+            transactions: entries,
+            next_cursor: Some(oldest + 1),
+        }
+    } else {
+        // A single block fills the whole page; move past it
+        HistoryPage {
+            transactions: entries,
+            next_cursor: Some(oldest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn raw(hash: &str, from: &str, to: &str, block_height: u64) -> RawTransaction {
+        RawTransaction {
+            hash: hash.to_string(),
+            kind: "PAYMENT".to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            amount: Amount::from_nanomina(1),
+            fee: Amount::from_nanomina(1),
+            nonce: 0,
+            memo: "E4YM2vTHhWEg66xpj52JErHUBU4pZ1yageL4TVDDpTTSsv8mK6YaH".to_string(),
+            date_time: "2024-01-01T00:00:00.000Z".to_string(),
+            block_height,
+            failure_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let entry = normalize(raw("5Ja", "B62qother", ADDRESS, 10), ADDRESS).unwrap();
+        assert_eq!(entry.direction, Direction::Incoming);
+        assert_eq!(entry.kind, "payment");
+        assert_eq!(entry.memo, "");
+        assert_eq!(entry.timestamp, 1_704_067_200);
+
+        let entry = normalize(raw("5Ja", ADDRESS, ADDRESS, 10), ADDRESS).unwrap();
+        assert_eq!(entry.direction, Direction::SelfTransfer);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let page = |heights: &[u64]| {
+            heights
+                .iter()
+                .map(|&h| normalize(raw("5J", ADDRESS, "B62qother", h), ADDRESS).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Short page: no more results
+        let result = paginate(page(&[30, 20]), 3);
+        assert_eq!(result.next_cursor, None);
+
+        // Full page: the oldest block may be split, fetch it again
+        let result = paginate(page(&[30, 20, 20]), 3);
+        assert_eq!(result.transactions.len(), 1);
+        assert_eq!(result.next_cursor, Some(21));
+
+        // Full page from a single block
+        let result = paginate(page(&[20, 20]), 2);
+        assert_eq!(result.transactions.len(), 2);
+        assert_eq!(result.next_cursor, Some(20));
+    }
+}
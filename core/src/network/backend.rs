@@ -0,0 +1,113 @@
+//! Selection of the API used to read chain state
+//!
+//! Reads go through the daemon GraphQL API by default. `Backend` lets a
+//! deployment configure a Rosetta endpoint instead, behind the same calls.
+
+use mina_signer::NetworkId;
+use std::fmt;
+use std::str::FromStr;
+
+use super::Result;
+use super::graphql::{Balance, GraphQlClient};
+use super::rosetta::RosettaClient;
+
+/// Kind of API an endpoint exposes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    #[default]
+    GraphQl,
+    Rosetta,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "graphql" => Ok(BackendKind::GraphQl),
+            "rosetta" => Ok(BackendKind::Rosetta),
+            other => Err(format!(
+                "Unknown backend '{}', expected graphql or rosetta",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendKind::GraphQl => f.write_str("graphql"),
+            BackendKind::Rosetta => f.write_str("rosetta"),
+        }
+    }
+}
+
+/// Client for whichever API is configured
+#[derive(Debug, Clone)]
+pub enum Backend {
+    GraphQl(GraphQlClient),
+    Rosetta(RosettaClient),
+}
+
+impl Backend {
+    /// Create a client of the given kind for `endpoint`
+    pub fn new(kind: BackendKind, endpoint: &str, network: &NetworkId) -> Self {
+        Self::with_endpoints(kind, vec![endpoint.to_string()], network)
+    }
+
+    /// Create a client of the given kind failing over between several endpoints
+    pub fn with_endpoints(kind: BackendKind, endpoints: Vec<String>, network: &NetworkId) -> Self {
+        match kind {
+            BackendKind::GraphQl => Backend::GraphQl(GraphQlClient::with_endpoints(endpoints)),
+            BackendKind::Rosetta => {
+                Backend::Rosetta(RosettaClient::with_endpoints(endpoints, network))
+            }
+        }
+    }
+
+    /// Kind of API this client talks to
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            Backend::GraphQl(_) => BackendKind::GraphQl,
+            Backend::Rosetta(_) => BackendKind::Rosetta,
+        }
+    }
+
+    /// The endpoint requests are sent to
+    pub fn endpoint(&self) -> &str {
+        match self {
+            Backend::GraphQl(client) => client.endpoint(),
+            Backend::Rosetta(client) => client.endpoint(),
+        }
+    }
+
+    /// Fetch the balance of an address
+    pub async fn get_balance(&self, address: &str) -> Result<Balance> {
+        match self {
+            Backend::GraphQl(client) => client.get_balance(address).await,
+            Backend::Rosetta(client) => client.get_balance(address).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_kind() {
+        assert_eq!("GraphQL".parse::<BackendKind>(), Ok(BackendKind::GraphQl));
+        assert_eq!("rosetta".parse::<BackendKind>(), Ok(BackendKind::Rosetta));
+        assert!("rest".parse::<BackendKind>().is_err());
+        assert_eq!(BackendKind::Rosetta.to_string(), "rosetta");
+
+        let backend = Backend::new(
+            BackendKind::Rosetta,
+            "http://localhost:3087/",
+            &NetworkId::MAINNET,
+        );
+        assert_eq!(backend.kind(), BackendKind::Rosetta);
+        assert_eq!(backend.endpoint(), "http://localhost:3087");
+    }
+}
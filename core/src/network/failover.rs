@@ -0,0 +1,245 @@
+//! Retries and failover across several endpoints
+//!
+//! Every client of this module sends its requests through an `EndpointPool`.
+//! A request is tried on each endpoint, best scored first, and the whole
+//! round is retried with exponential backoff if they all fail. Only
+//! transport failures are retried: an error reported by a node (e.g. a
+//! GraphQL error) is returned as is.
+//!
+//! The pool is shared between clones of a client, so health scores learned
+//! by one request benefit the next ones. Backoff delays use the Tokio timer
+//! natively, so requests must run inside a Tokio runtime, and `setTimeout`
+//! in the browser.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+use super::{NetworkError, Result};
+
+/// Score lost per consecutive failure of an endpoint
+const FAILURE_PENALTY: u32 = 20;
+
+/// Weight of the previous average when updating the latency, in percent
+const LATENCY_SMOOTHING: u64 = 80;
+
+/// How many times and how fast requests are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Rounds over all endpoints before giving up
+    pub max_attempts: u32,
+    /// Delay before the second round, doubled for each following one
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two rounds
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Try each endpoint once, without retrying
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before the given round (0-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        if attempt == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// What is known about the health of an endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub successes: u32,
+    pub failures: u32,
+    pub consecutive_failures: u32,
+    /// Smoothed response time, once a request succeeded
+    pub latency_ms: Option<u64>,
+}
+
+impl EndpointHealth {
+    fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            latency_ms: None,
+        }
+    }
+
+    /// Score from 0 to 100: the success rate, minus a penalty per consecutive failure
+    ///
+    /// Endpoints that were never used score 100.
+    pub fn score(&self) -> u32 {
+        let total = self.successes + self.failures;
+        let rate = if total == 0 {
+            100
+        } else {
+            (u64::from(self.successes) * 100 / u64::from(total)) as u32
+        };
+        rate.saturating_sub(self.consecutive_failures.saturating_mul(FAILURE_PENALTY))
+    }
+
+    fn record_success(&mut self, latency: Duration) {
+        let latency_ms = latency.as_millis() as u64;
+        self.successes = self.successes.saturating_add(1);
+        self.consecutive_failures = 0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(avg) => (avg * LATENCY_SMOOTHING + latency_ms * (100 - LATENCY_SMOOTHING)) / 100,
+            None => latency_ms,
+        });
+    }
+
+    fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+}
+
+/// A set of equivalent endpoints with their health
+#[derive(Debug, Clone)]
+pub struct EndpointPool {
+    endpoints: Arc<[String]>,
+    health: Arc<Mutex<Vec<EndpointHealth>>>,
+}
+
+impl EndpointPool {
+    /// Create a pool, in order of preference
+    pub fn new(endpoints: Vec<String>) -> Self {
+        let health = endpoints.iter().cloned().map(EndpointHealth::new).collect();
+        Self {
+            endpoints: endpoints.into(),
+            health: Arc::new(Mutex::new(health)),
+        }
+    }
+
+    /// The configured endpoints, in order of preference
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    /// Snapshot of the health of every endpoint
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.lock().clone()
+    }
+
+    /// Endpoints ordered by score, then latency, then configured order
+    pub fn ranked(&self) -> Vec<String> {
+        let mut health = self.health();
+        // Stable sort: ties keep the configured order
+        health.sort_by_key(|h| (u32::MAX - h.score(), h.latency_ms.unwrap_or(0)));
+        health.into_iter().map(|h| h.endpoint).collect()
+    }
+
+    /// Run `request` against the endpoints until one succeeds
+    ///
+    /// Returns the last transport error if every attempt failed.
+    pub async fn run<T, F, Fut>(&self, retry: &RetryPolicy, mut request: F) -> Result<T>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = None;
+        for attempt in 0..retry.max_attempts.max(1) {
+            if attempt > 0 {
+                sleep(retry.backoff(attempt)).await;
+            }
+            for endpoint in self.ranked() {
+                let start = Instant::now();
+                match request(endpoint.clone()).await {
+                    Ok(value) => {
+                        self.update(&endpoint, |h| h.record_success(start.elapsed()));
+                        return Ok(value);
+                    }
+                    Err(e) if e.is_transient() => {
+                        self.update(&endpoint, EndpointHealth::record_failure);
+                        last_error = Some(e);
+                    }
+                    // The node answered; the error would be the same elsewhere
+                    Err(e) => {
+                        self.update(&endpoint, |h| h.record_success(start.elapsed()));
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| NetworkError::Http("no endpoint configured".to_string())))
+    }
+
+    fn update(&self, endpoint: &str, f: impl FnOnce(&mut EndpointHealth)) {
+        if let Some(health) = self.lock().iter_mut().find(|h| h.endpoint == endpoint) {
+            f(health);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<EndpointHealth>> {
+        // Health is only statistics: keep using it after a panic elsewhere
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.backoff(0), Duration::ZERO);
+        assert_eq!(retry.backoff(1), Duration::from_millis(500));
+        assert_eq!(retry.backoff(3), Duration::from_secs(2));
+        assert_eq!(retry.backoff(10), Duration::from_secs(8));
+        assert_eq!(retry.backoff(64), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_ranking() {
+        let pool = EndpointPool::new(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert_eq!(pool.ranked(), ["a", "b", "c"]);
+
+        pool.update("a", EndpointHealth::record_failure);
+        pool.update("b", |h| h.record_success(Duration::from_millis(300)));
+        pool.update("c", |h| h.record_success(Duration::from_millis(100)));
+        assert_eq!(pool.ranked(), ["c", "b", "a"]);
+
+        // A single success clears the consecutive failure penalty
+        pool.update("a", |h| h.record_success(Duration::from_millis(50)));
+        let health = pool.health();
+        assert_eq!(health[0].score(), 50);
+        assert_eq!(health[0].consecutive_failures, 0);
+        assert_eq!(pool.ranked(), ["c", "b", "a"]);
+    }
+}
@@ -0,0 +1,101 @@
+//! Fee suggestions from the transaction pool
+//!
+//! Block producers pick the highest paying transactions from the pool first,
+//! so the fees of pending transactions tell how much to pay to be included
+//! quickly. Suggestions are percentiles of those fees, never below the
+//! minimum fee accepted by the daemon.
+
+use serde::{Deserialize, Serialize};
+
+use super::Result;
+use super::graphql::GraphQlClient;
+use crate::amount::Amount;
+use crate::transaction::MINIMUM_FEE;
+
+/// Percentile of pool fees suggested for a transaction that can wait
+pub const SLOW_PERCENTILE: u8 = 25;
+
+/// Percentile of pool fees suggested by default
+pub const STANDARD_PERCENTILE: u8 = 50;
+
+/// Percentile of pool fees suggested for fast inclusion
+pub const FAST_PERCENTILE: u8 = 90;
+
+/// Suggested fees, based on the transactions currently in the pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct FeeSuggestion {
+    pub slow: Amount,
+    pub standard: Amount,
+    pub fast: Amount,
+    /// Number of pending transactions the suggestion is based on
+    pub pool_size: usize,
+}
+
+impl FeeSuggestion {
+    /// Compute the suggestion from the fees of pending transactions
+    pub fn from_fees(fees: &[Amount]) -> Self {
+        let mut sorted = fees.to_vec();
+        sorted.sort_unstable();
+        Self {
+            slow: fee_percentile(&sorted, SLOW_PERCENTILE),
+            standard: fee_percentile(&sorted, STANDARD_PERCENTILE),
+            fast: fee_percentile(&sorted, FAST_PERCENTILE),
+            pool_size: sorted.len(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted fees, at least the minimum fee
+///
+/// An empty pool means any valid fee is enough.
+pub fn fee_percentile(sorted_fees: &[Amount], percentile: u8) -> Amount {
+    if sorted_fees.is_empty() {
+        return MINIMUM_FEE;
+    }
+    let percentile = usize::from(percentile.min(100));
+    let rank = (percentile * sorted_fees.len()).div_ceil(100).max(1);
+    sorted_fees[rank - 1].max(MINIMUM_FEE)
+}
+
+impl GraphQlClient {
+    /// Suggest fees from the transactions in the node's pool
+    pub async fn suggest_fee(&self) -> Result<FeeSuggestion> {
+        let pooled = self.pooled_user_commands(None).await?;
+        let fees: Vec<Amount> = pooled.iter().map(|c| c.fee).collect();
+        Ok(FeeSuggestion::from_fees(&fees))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mina(s: &str) -> Amount {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_fee_percentile() {
+        let fees: Vec<Amount> = ["0.01", "0.02", "0.03", "0.04"]
+            .iter()
+            .map(|f| mina(f))
+            .collect();
+        assert_eq!(fee_percentile(&fees, 25), mina("0.01"));
+        assert_eq!(fee_percentile(&fees, 50), mina("0.02"));
+        assert_eq!(fee_percentile(&fees, 90), mina("0.04"));
+        assert_eq!(fee_percentile(&fees, 0), mina("0.01"));
+        assert_eq!(fee_percentile(&[], 50), MINIMUM_FEE);
+    }
+
+    #[test]
+    fn test_suggestion() {
+        let fees = vec![mina("0.5"), Amount::from_nanomina(1), mina("0.1")];
+        let suggestion = FeeSuggestion::from_fees(&fees);
+        assert_eq!(suggestion.slow, MINIMUM_FEE);
+        assert_eq!(suggestion.standard, mina("0.1"));
+        assert_eq!(suggestion.fast, mina("0.5"));
+        assert_eq!(suggestion.pool_size, 3);
+    }
+}
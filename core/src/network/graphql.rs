@@ -0,0 +1,502 @@
+//! Typed client for the Mina daemon GraphQL API
+//!
+//! Only the queries the wallet needs are modelled:
+//! - `account` - balance, nonce and delegate of an address
+//! - `get_balance` - total, liquid and locked balance of an address
+//! - `get_inferred_nonce` - next nonce to use for an address
+//! - `best_chain` - height and state hash of the chain tip
+//! - `pooled_user_commands` - pending payments and delegations in the mempool
+//! - `broadcast` - submit a signed payment or delegation
+//!
+//! Any other query can be sent with `GraphQlClient::query`.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::failover::{EndpointHealth, EndpointPool, RetryPolicy};
+use super::{NetworkError, Result, from_str_or_number, opt_from_str_or_number};
+use crate::amount::Amount;
+use crate::transaction::{SignedTransaction, TransactionKind};
+
+const ACCOUNT_QUERY: &str = r#"query Account($publicKey: PublicKey!) {
+  account(publicKey: $publicKey) {
+    publicKey
+    nonce
+    inferredNonce
+    delegate
+    balance { total liquid locked }
+  }
+}"#;
+
+const BEST_CHAIN_QUERY: &str = r#"query BestChain {
+  bestChain(maxLength: 1) {
+    stateHash
+    protocolState {
+      consensusState { blockHeight epoch slotSinceGenesis }
+    }
+  }
+}"#;
+
+const POOLED_USER_COMMANDS_QUERY: &str = r#"query Pooled($publicKey: PublicKey) {
+  pooledUserCommands(publicKey: $publicKey) {
+    id
+    hash
+    kind
+    nonce
+    from
+    to
+    amount
+    fee
+    memo
+  }
+}"#;
+
+const SEND_PAYMENT_MUTATION: &str = r#"mutation SendPayment($input: SendPaymentInput!, $signature: SignatureInput) {
+  sendPayment(input: $input, signature: $signature) {
+    payment { hash }
+  }
+}"#;
+
+const SEND_DELEGATION_MUTATION: &str = r#"mutation SendDelegation($input: SendDelegationInput!, $signature: SignatureInput) {
+  sendDelegation(input: $input, signature: $signature) {
+    delegation { hash }
+  }
+}"#;
+
+/// Client for the GraphQL API of one or more equivalent nodes
+///
+/// Requests fail over between the endpoints and are retried with backoff,
+/// see `failover`.
+#[derive(Debug, Clone)]
+pub struct GraphQlClient {
+    pool: EndpointPool,
+    retry: RetryPolicy,
+    http: reqwest::Client,
+}
+
+/// Balance of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+pub struct Balance {
+    pub total: Amount,
+    /// Spendable part of the balance, if the node reports it
+    #[serde(default)]
+    pub liquid: Option<Amount>,
+    /// Part of the balance still locked by a vesting schedule
+    #[serde(default)]
+    pub locked: Option<Amount>,
+}
+
+/// On-chain state of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub public_key: String,
+    /// Nonce of the last transaction included in a block
+    #[serde(deserialize_with = "from_str_or_number")]
+    pub nonce: u32,
+    /// Nonce including transactions still in the mempool
+    #[serde(default, deserialize_with = "opt_from_str_or_number")]
+    pub inferred_nonce: Option<u32>,
+    pub delegate: Option<String>,
+    pub balance: Balance,
+}
+
+/// Summary of the chain tip
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainTip {
+    pub state_hash: String,
+    pub block_height: u64,
+    pub epoch: u64,
+    pub slot_since_genesis: u64,
+}
+
+/// A payment or delegation waiting in the transaction pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct PooledUserCommand {
+    pub id: String,
+    pub hash: String,
+    /// `PAYMENT` or `STAKE_DELEGATION`
+    pub kind: String,
+    #[serde(deserialize_with = "from_str_or_number")]
+    pub nonce: u32,
+    pub from: String,
+    pub to: String,
+    pub amount: Amount,
+    pub fee: Amount,
+    pub memo: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlErrorMessage>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlErrorMessage {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct AccountData {
+    account: Option<Account>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BestChainData {
+    best_chain: Option<Vec<Block>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Block {
+    state_hash: String,
+    protocol_state: ProtocolState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProtocolState {
+    consensus_state: ConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConsensusState {
+    #[serde(deserialize_with = "from_str_or_number")]
+    block_height: u64,
+    #[serde(deserialize_with = "from_str_or_number")]
+    epoch: u64,
+    #[serde(deserialize_with = "from_str_or_number")]
+    slot_since_genesis: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PooledUserCommandsData {
+    pooled_user_commands: Vec<PooledUserCommand>,
+}
+
+impl GraphQlClient {
+    /// Create a client for the given GraphQL endpoint URL
+    pub fn new(endpoint: &str) -> Self {
+        Self::with_endpoints(vec![endpoint.to_string()])
+    }
+
+    /// Create a client failing over between several endpoints, in order of preference
+    pub fn with_endpoints(endpoints: Vec<String>) -> Self {
+        Self {
+            pool: EndpointPool::new(endpoints),
+            retry: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The preferred endpoint of this client
+    pub fn endpoint(&self) -> &str {
+        self.pool.endpoints().first().map_or("", String::as_str)
+    }
+
+    /// All the endpoints of this client, in order of preference
+    pub fn endpoints(&self) -> &[String] {
+        self.pool.endpoints()
+    }
+
+    /// Health of the endpoints, as observed by the requests made so far
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.pool.health()
+    }
+
+    /// Send a raw query and deserialize its `data` field
+    pub async fn query<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        let (data, _) = self.query_from(query, variables).await?;
+        Ok(data)
+    }
+
+    /// Send a raw query, also returning the endpoint that answered it
+    pub(crate) async fn query_from<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<(T, String)> {
+        let body = json!({ "query": query, "variables": variables });
+        self.pool
+            .run(&self.retry, |endpoint| {
+                let body = &body;
+                async move {
+                    let data = self.send(&endpoint, body).await?;
+                    Ok((data, endpoint))
+                }
+            })
+            .await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, endpoint: &str, body: &Value) -> Result<T> {
+        let response = self.http.post(endpoint).json(body).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        match parse_response(&text) {
+            // Not a GraphQL response, e.g. an error page from a proxy
+            Err(NetworkError::InvalidResponse(_)) if !status.is_success() => Err(
+                NetworkError::Http(format!("{} returned HTTP {}", endpoint, status)),
+            ),
+            result => result,
+        }
+    }
+
+    /// Fetch an account, or `None` if it does not exist on chain yet
+    pub async fn account(&self, public_key: &str) -> Result<Option<Account>> {
+        let data: AccountData = self
+            .query(ACCOUNT_QUERY, json!({ "publicKey": public_key }))
+            .await?;
+        Ok(data.account)
+    }
+
+    /// Whether an account exists on chain
+    ///
+    /// A payment to an address without an account pays the account creation fee.
+    pub async fn account_exists(&self, address: &str) -> Result<bool> {
+        Ok(self.account(address).await?.is_some())
+    }
+
+    /// Fetch the balance of an address
+    ///
+    /// Fails with `NetworkError::AccountNotFound` if the address has never
+    /// received funds.
+    pub async fn get_balance(&self, address: &str) -> Result<Balance> {
+        self.account(address)
+            .await?
+            .map(|account| account.balance)
+            .ok_or_else(|| NetworkError::AccountNotFound(address.to_string()))
+    }
+
+    /// Fetch the next nonce to use for an address
+    ///
+    /// This is the daemon's inferred nonce, which accounts for transactions
+    /// from the address that are still in the transaction pool.
+    pub async fn get_inferred_nonce(&self, address: &str) -> Result<u32> {
+        self.account(address)
+            .await?
+            .map(|account| account.inferred_nonce.unwrap_or(account.nonce))
+            .ok_or_else(|| NetworkError::AccountNotFound(address.to_string()))
+    }
+
+    /// Fetch the tip of the best chain
+    pub async fn best_chain(&self) -> Result<ChainTip> {
+        let data: BestChainData = self.query(BEST_CHAIN_QUERY, json!({})).await?;
+        chain_tip(data)
+    }
+
+    /// List commands in the transaction pool, optionally only those sent by `public_key`
+    pub async fn pooled_user_commands(
+        &self,
+        public_key: Option<&str>,
+    ) -> Result<Vec<PooledUserCommand>> {
+        let data: PooledUserCommandsData = self
+            .query(
+                POOLED_USER_COMMANDS_QUERY,
+                json!({ "publicKey": public_key }),
+            )
+            .await?;
+        Ok(data.pooled_user_commands)
+    }
+
+    /// Submit a signed payment or delegation, returning its transaction hash
+    pub async fn broadcast(&self, signed: &SignedTransaction) -> Result<String> {
+        let variables = send_variables(signed);
+        let query = send_mutation(signed.data.kind());
+        let hash = match signed.data.kind() {
+            TransactionKind::Payment => {
+                let data: SendPaymentData = self.query(query, variables).await?;
+                data.send_payment.payment.hash
+            }
+            TransactionKind::Delegation => {
+                let data: SendDelegationData = self.query(query, variables).await?;
+                data.send_delegation.delegation.hash
+            }
+        };
+        Ok(hash)
+    }
+}
+
+/// The GraphQL request submitting a signed payment or delegation,
+/// `{ query, variables }`, for clients that post it to a node themselves
+pub fn send_request(signed: &SignedTransaction) -> Value {
+    json!({
+        "query": send_mutation(signed.data.kind()),
+        "variables": send_variables(signed),
+    })
+}
+
+fn send_mutation(kind: TransactionKind) -> &'static str {
+    match kind {
+        TransactionKind::Payment => SEND_PAYMENT_MUTATION,
+        TransactionKind::Delegation => SEND_DELEGATION_MUTATION,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendPaymentData {
+    send_payment: SentPayment,
+}
+
+#[derive(Deserialize)]
+struct SentPayment {
+    payment: SentCommand,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendDelegationData {
+    send_delegation: SentDelegation,
+}
+
+#[derive(Deserialize)]
+struct SentDelegation {
+    delegation: SentCommand,
+}
+
+#[derive(Deserialize)]
+struct SentCommand {
+    hash: String,
+}
+
+/// Variables of the `sendPayment`/`sendDelegation` mutations
+///
+/// Integers are sent as strings, as the daemon expects for `UInt32`/`UInt64`.
+fn send_variables(signed: &SignedTransaction) -> Value {
+    let tx = &signed.data;
+    let mut input = json!({
+        "from": tx.from,
+        "to": tx.to,
+        "fee": tx.fee,
+        "nonce": tx.nonce.to_string(),
+        "memo": tx.memo,
+        "validUntil": tx.valid_until.to_string(),
+    });
+    if let Some(amount) = tx.amount {
+        input["amount"] = json!(amount);
+    }
+    json!({ "input": input, "signature": signed.signature })
+}
+
+fn parse_response<T: DeserializeOwned>(text: &str) -> Result<T> {
+    let response: GraphQlResponse<T> =
+        serde_json::from_str(text).map_err(|e| NetworkError::InvalidResponse(e.to_string()))?;
+    if !response.errors.is_empty() {
+        return Err(NetworkError::GraphQl(
+            response.errors.into_iter().map(|e| e.message).collect(),
+        ));
+    }
+    response
+        .data
+        .ok_or_else(|| NetworkError::InvalidResponse("missing data".to_string()))
+}
+
+fn chain_tip(data: BestChainData) -> Result<ChainTip> {
+    let block = data
+        .best_chain
+        .and_then(|blocks| blocks.into_iter().last())
+        .ok_or_else(|| NetworkError::InvalidResponse("node returned no blocks".to_string()))?;
+    let consensus = block.protocol_state.consensus_state;
+    Ok(ChainTip {
+        state_hash: block.state_hash,
+        block_height: consensus.block_height,
+        epoch: consensus.epoch,
+        slot_since_genesis: consensus.slot_since_genesis,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_account_response() {
+        let text = r#"{"data":{"account":{
+            "publicKey":"B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg",
+            "nonce":"3","inferredNonce":"5","delegate":null,
+            "balance":{"total":"1500000000","liquid":"1500000000","locked":"0"}}}}"#;
+        let account = parse_response::<AccountData>(text)
+            .unwrap()
+            .account
+            .unwrap();
+        assert_eq!(account.nonce, 3);
+        assert_eq!(account.inferred_nonce, Some(5));
+        assert_eq!(account.balance.total, Amount::from_nanomina(1_500_000_000));
+        assert_eq!(account.balance.locked, Some(Amount::ZERO));
+
+        let missing = parse_response::<AccountData>(r#"{"data":{"account":null}}"#).unwrap();
+        assert!(missing.account.is_none());
+    }
+
+    #[test]
+    fn test_parse_best_chain_response() {
+        let text = r#"{"data":{"bestChain":[{"stateHash":"3NKx",
+            "protocolState":{"consensusState":
+            {"blockHeight":"359604","epoch":"74","slotSinceGenesis":"535240"}}}]}}"#;
+        let tip = chain_tip(parse_response(text).unwrap()).unwrap();
+        assert_eq!(tip.block_height, 359_604);
+        assert_eq!(tip.state_hash, "3NKx");
+    }
+
+    #[test]
+    fn test_send_variables() {
+        use crate::signing::SignatureJson;
+        use crate::transaction::Transaction;
+
+        let address = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+        let signature = SignatureJson {
+            field: "1".to_string(),
+            scalar: "2".to_string(),
+        };
+        let payment = Transaction::payment(
+            address,
+            address,
+            Amount::from_nanomina(5),
+            Amount::from_nanomina(10),
+            7,
+        );
+        let signed = SignedTransaction {
+            public_key: address.to_string(),
+            data: payment,
+            signature,
+        };
+        let variables = send_variables(&signed);
+        assert_eq!(variables["input"]["amount"], "5");
+        assert_eq!(variables["input"]["nonce"], "7");
+        assert_eq!(variables["input"]["validUntil"], "4294967295");
+        assert_eq!(variables["signature"]["scalar"], "2");
+
+        let request = send_request(&signed);
+        assert_eq!(request["query"], SEND_PAYMENT_MUTATION);
+        assert_eq!(request["variables"], variables);
+
+        let sent: SendPaymentData =
+            parse_response(r#"{"data":{"sendPayment":{"payment":{"hash":"5Jv..."}}}}"#).unwrap();
+        assert_eq!(sent.send_payment.payment.hash, "5Jv...");
+    }
+
+    #[test]
+    fn test_parse_graphql_errors() {
+        let text = r#"{"data":null,"errors":[{"message":"Invalid public key"}]}"#;
+        assert!(matches!(
+            parse_response::<AccountData>(text),
+            Err(NetworkError::GraphQl(messages)) if messages == ["Invalid public key"]
+        ));
+    }
+}
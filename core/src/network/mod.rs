@@ -0,0 +1,148 @@
+//! Network access to Mina nodes
+//!
+//! Everything in this module is behind the `network` feature, so the default
+//! build of the core crate stays a purely offline signing library. Requests
+//! are made with `reqwest`, which uses `fetch` when compiled to WebAssembly.
+//!
+//! - `accounting` - CSV and OFX exports of history, valued in fiat
+//! - `archive` - Transaction history from an archive indexer
+//! - `backend` - Selection between the GraphQL and Rosetta APIs
+//! - `failover` - Retries and failover across several endpoints
+//! - `fees` - Fee suggestions from the transaction pool
+//! - `graphql` - Typed client for the Mina daemon GraphQL API
+//! - `pending` - Pending transactions, nonce gaps and stuck fees
+//! - `rosetta` - Client for the Rosetta Data API
+//! - `staking` - Delegation and staking ledger queries
+//! - `status` - Node health and sync status
+//! - `subscription` - Block and account subscriptions over websockets
+//! - `tracker` - Transaction confirmation tracking
+//! - `watch` - Polling for new transactions of an address
+//! - `webhook` - Signed notifications of watched transactions
+//! - `zkapp` - zkApp account state and permissions
+
+pub mod accounting;
+pub mod archive;
+pub mod backend;
+pub mod failover;
+pub mod fees;
+pub mod graphql;
+pub mod pending;
+pub mod rosetta;
+pub mod staking;
+pub mod status;
+pub mod subscription;
+pub mod tracker;
+pub mod watch;
+pub mod webhook;
+pub mod zkapp;
+
+use mina_signer::NetworkId;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub use accounting::{AccountingRow, PriceHistory};
+pub use archive::{ArchiveClient, HistoryEntry, HistoryPage};
+pub use backend::{Backend, BackendKind};
+pub use failover::{EndpointHealth, RetryPolicy};
+pub use fees::FeeSuggestion;
+pub use graphql::{Balance, GraphQlClient};
+pub use pending::{PendingIssue, PendingReport};
+pub use rosetta::RosettaClient;
+pub use staking::{PoolInfo, StakingInfo, StakingLedgerEntry};
+pub use status::{NodeStatus, node_status};
+pub use subscription::{SubscriptionClient, SubscriptionEvent, Topic};
+pub use tracker::{TxStatus, TxTracker};
+pub use watch::{AccountWatcher, WatchEvent};
+pub use webhook::{Webhook, WebhookPayload};
+pub use zkapp::{ZkappField, ZkappState};
+
+/// Public GraphQL endpoint used by default for mainnet
+pub const DEFAULT_MAINNET_ENDPOINT: &str = "https://api.minascan.io/node/mainnet/v1/graphql";
+
+/// Public GraphQL endpoint used by default for testnet (devnet)
+pub const DEFAULT_TESTNET_ENDPOINT: &str = "https://api.minascan.io/node/devnet/v1/graphql";
+
+/// Default GraphQL endpoint for a network
+pub fn default_endpoint(network: &NetworkId) -> &'static str {
+    match network {
+        NetworkId::MAINNET => DEFAULT_MAINNET_ENDPOINT,
+        NetworkId::TESTNET => DEFAULT_TESTNET_ENDPOINT,
+    }
+}
+
+/// Errors that can occur while talking to a node
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    #[error("HTTP request failed: {0}")]
+    Http(String),
+    #[error("GraphQL error: {}", .0.join("; "))]
+    GraphQl(Vec<String>),
+    #[error("Account not found: {0}")]
+    AccountNotFound(String),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Rosetta error {code}: {message}")]
+    Rosetta { code: i64, message: String },
+    #[error("Node is on chain {chain_id}, which is not {network}")]
+    ChainMismatch { network: String, chain_id: String },
+}
+
+impl NetworkError {
+    /// Whether the error is a connectivity problem that may go away on retry
+    ///
+    /// Errors reported by a node, such as a rejected transaction, are not.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            NetworkError::Http(_) | NetworkError::InvalidResponse(_)
+        )
+    }
+}
+
+impl From<reqwest::Error> for NetworkError {
+    fn from(e: reqwest::Error) -> Self {
+        NetworkError::Http(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, NetworkError>;
+
+/// Deserialize an integer sent either as a JSON number or as a decimal string
+///
+/// The daemon encodes `UInt32`/`UInt64` scalars as strings.
+pub(crate) fn from_str_or_number<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw<T> {
+        Number(T),
+        String(String),
+    }
+
+    match Raw::<T>::deserialize(deserializer)? {
+        Raw::Number(n) => Ok(n),
+        Raw::String(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Like `from_str_or_number`, for optional fields
+pub(crate) fn opt_from_str_or_number<'de, D, T>(
+    deserializer: D,
+) -> std::result::Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(bound(deserialize = "T: Deserialize<'de> + FromStr, T::Err: fmt::Display"))]
+    struct Wrapper<T>(#[serde(deserialize_with = "from_str_or_number")] T);
+
+    Ok(Option::<Wrapper<T>>::deserialize(deserializer)?.map(|Wrapper(n)| n))
+}
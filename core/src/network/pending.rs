@@ -0,0 +1,206 @@
+//! Pending transactions of an address
+//!
+//! Transactions from an account are applied in nonce order, starting at the
+//! account's ledger nonce. A pending transaction can be stuck because:
+//! - an earlier nonce is missing from the pool (a nonce gap), so nothing
+//!   after it can be included until it is filled
+//! - its fee is low compared to the rest of the pool; it can be replaced by
+//!   the same transaction re-signed with a higher fee at the same nonce
+//! - its nonce is below the ledger nonce, so it can never be included and
+//!   will be dropped
+
+use serde::{Deserialize, Serialize};
+
+use super::fees::FeeSuggestion;
+use super::graphql::{GraphQlClient, PooledUserCommand};
+use super::{NetworkError, Result};
+use crate::amount::Amount;
+use crate::transaction::MINIMUM_FEE;
+
+/// A problem with the pending transactions of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PendingIssue {
+    /// No pending transaction uses this nonce, blocking the later ones
+    #[serde(rename_all = "camelCase")]
+    NonceGap { nonce: u32 },
+    /// The fee is below the pool median; re-sign at the same nonce with `suggested_fee`
+    #[serde(rename_all = "camelCase")]
+    LowFee {
+        nonce: u32,
+        hash: String,
+        fee: Amount,
+        suggested_fee: Amount,
+    },
+    /// The nonce was already used on chain; the transaction will be dropped
+    #[serde(rename_all = "camelCase")]
+    StaleNonce { nonce: u32, hash: String },
+}
+
+impl PendingIssue {
+    /// What to do about the issue
+    pub fn suggestion(&self) -> String {
+        match self {
+            PendingIssue::NonceGap { nonce } => format!(
+                "Nonce {} is missing: send a transaction with nonce {} to unblock the later ones",
+                nonce, nonce
+            ),
+            PendingIssue::LowFee {
+                nonce,
+                fee,
+                suggested_fee,
+                ..
+            } => format!(
+                "Nonce {} pays {} MINA, below the pool median: re-sign it with nonce {} and a fee of {} MINA to replace it",
+                nonce, fee, nonce, suggested_fee
+            ),
+            PendingIssue::StaleNonce { nonce, .. } => format!(
+                "Nonce {} was already used on chain: this transaction will never be included",
+                nonce
+            ),
+        }
+    }
+}
+
+/// Pending transactions of an address, with the issues found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReport {
+    pub address: String,
+    /// Nonce of the next transaction the ledger expects
+    pub ledger_nonce: u32,
+    /// Pending transactions, by nonce
+    pub transactions: Vec<PooledUserCommand>,
+    pub issues: Vec<PendingIssue>,
+}
+
+impl PendingReport {
+    /// Inspect the pending transactions of `address`, given its ledger nonce
+    /// and the fees currently paid in the pool
+    pub fn inspect(
+        address: &str,
+        ledger_nonce: u32,
+        mut transactions: Vec<PooledUserCommand>,
+        pool_fees: &FeeSuggestion,
+    ) -> Self {
+        transactions.retain(|tx| tx.from == address);
+        transactions.sort_by_key(|tx| tx.nonce);
+
+        let mut issues = Vec::new();
+        let mut expected = ledger_nonce;
+        for tx in &transactions {
+            if tx.nonce < ledger_nonce {
+                issues.push(PendingIssue::StaleNonce {
+                    nonce: tx.nonce,
+                    hash: tx.hash.clone(),
+                });
+                continue;
+            }
+            issues.extend((expected..tx.nonce).map(|nonce| PendingIssue::NonceGap { nonce }));
+            expected = expected.max(tx.nonce.saturating_add(1));
+
+            if tx.fee < pool_fees.standard {
+                // A replacement must pay more than the transaction it replaces
+                let bumped = tx.fee.checked_add(MINIMUM_FEE).unwrap_or(tx.fee);
+                issues.push(PendingIssue::LowFee {
+                    nonce: tx.nonce,
+                    hash: tx.hash.clone(),
+                    fee: tx.fee,
+                    suggested_fee: pool_fees.standard.max(bumped),
+                });
+            }
+        }
+
+        Self {
+            address: address.to_string(),
+            ledger_nonce,
+            transactions,
+            issues,
+        }
+    }
+}
+
+impl GraphQlClient {
+    /// List the pending transactions of an address and look for nonce gaps,
+    /// low fees and stale nonces
+    pub async fn pending_transactions(&self, address: &str) -> Result<PendingReport> {
+        let account = self
+            .account(address)
+            .await?
+            .ok_or_else(|| NetworkError::AccountNotFound(address.to_string()))?;
+        let pool = self.pooled_user_commands(None).await?;
+        let fees: Vec<Amount> = pool.iter().map(|c| c.fee).collect();
+        Ok(PendingReport::inspect(
+            address,
+            account.nonce,
+            pool,
+            &FeeSuggestion::from_fees(&fees),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qkzhDdtnW3sz2Sp7P6nm9xC5fYx1Ff8W7i6CQEBdRzqJ7jhPz8QK";
+
+    fn pooled(from: &str, nonce: u32, fee: &str) -> PooledUserCommand {
+        PooledUserCommand {
+            id: format!("id{}", nonce),
+            hash: format!("hash{}", nonce),
+            kind: "PAYMENT".to_string(),
+            nonce,
+            from: from.to_string(),
+            to: ADDRESS.to_string(),
+            amount: "1".parse().unwrap(),
+            fee: fee.parse().unwrap(),
+            memo: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_inspect_gaps_and_stale_nonces() {
+        let fees = FeeSuggestion::from_fees(&["0.01".parse().unwrap()]);
+        let pool = vec![
+            pooled(ADDRESS, 7, "0.1"),
+            pooled(ADDRESS, 4, "0.1"),
+            pooled(ADDRESS, 5, "0.1"),
+            pooled("B62qother", 5, "0.1"),
+        ];
+        let report = PendingReport::inspect(ADDRESS, 5, pool, &fees);
+
+        assert_eq!(report.transactions.len(), 3);
+        assert_eq!(report.transactions[0].nonce, 4);
+        assert_eq!(
+            report.issues,
+            vec![
+                PendingIssue::StaleNonce {
+                    nonce: 4,
+                    hash: "hash4".to_string()
+                },
+                PendingIssue::NonceGap { nonce: 6 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inspect_low_fee() {
+        let pool_fees: Vec<Amount> = ["0.05", "0.1", "0.2"]
+            .iter()
+            .map(|f| f.parse().unwrap())
+            .collect();
+        let fees = FeeSuggestion::from_fees(&pool_fees);
+        let report = PendingReport::inspect(ADDRESS, 0, vec![pooled(ADDRESS, 0, "0.01")], &fees);
+
+        match &report.issues[..] {
+            [PendingIssue::LowFee { suggested_fee, .. }] => {
+                assert_eq!(*suggested_fee, "0.1".parse().unwrap())
+            }
+            other => panic!("unexpected issues: {:?}", other),
+        }
+        assert!(report.issues[0].suggestion().contains("nonce 0"));
+    }
+}
@@ -0,0 +1,392 @@
+//! Client for the Rosetta Data API
+//!
+//! Some infrastructure only exposes the Rosetta API of a Mina node, so the
+//! wallet can read balances, blocks and the mempool through it instead of
+//! GraphQL. Only the Data API is modelled; transactions are still signed
+//! locally and broadcast through GraphQL.
+//!
+//! Every endpoint is a `POST` with a JSON body naming the network. Errors
+//! are returned as `{code, message, retriable}` objects.
+
+use mina_signer::NetworkId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::failover::{EndpointHealth, EndpointPool, RetryPolicy};
+use super::graphql::Balance;
+use super::{NetworkError, Result, from_str_or_number, opt_from_str_or_number};
+use crate::amount::Amount;
+
+/// Token ID of MINA, the default token
+pub const MINA_TOKEN_ID: &str = "wSHV2S4qX9jFsLjQo8r1BsMLH2ZRKsZx6EJd1sbozGPieEC4Jf";
+
+/// Identifier of a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockIdentifier {
+    #[serde(deserialize_with = "from_str_or_number")]
+    pub index: u64,
+    pub hash: String,
+}
+
+/// Balance of an account at a given block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountBalance {
+    pub block: BlockIdentifier,
+    pub balance: Amount,
+    /// Nonce of the account, if the node reports it
+    pub nonce: Option<u32>,
+}
+
+/// A balance change in a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    /// e.g. `fee_payment`, `payment_source_dec`, `payment_receiver_inc`
+    pub kind: String,
+    pub status: Option<String>,
+    pub account: Option<String>,
+    /// Signed change of the balance, in nanomina
+    pub amount: Option<i64>,
+}
+
+/// A transaction of a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RosettaTransaction {
+    pub hash: String,
+    pub operations: Vec<Operation>,
+}
+
+/// A block with its transactions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RosettaBlock {
+    pub block: BlockIdentifier,
+    pub parent: BlockIdentifier,
+    /// Block time, as a Unix timestamp in seconds
+    pub timestamp: u64,
+    pub transactions: Vec<RosettaTransaction>,
+}
+
+/// Client for one or more equivalent Rosetta endpoints
+#[derive(Debug, Clone)]
+pub struct RosettaClient {
+    pool: EndpointPool,
+    retry: RetryPolicy,
+    network: &'static str,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct RosettaError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct NetworkStatusResponse {
+    current_block_identifier: BlockIdentifier,
+}
+
+#[derive(Deserialize)]
+struct AccountBalanceResponse {
+    block_identifier: BlockIdentifier,
+    balances: Vec<RawAmount>,
+    #[serde(default)]
+    metadata: Option<AccountMetadata>,
+}
+
+#[derive(Deserialize)]
+struct AccountMetadata {
+    #[serde(default, deserialize_with = "opt_from_str_or_number")]
+    nonce: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawAmount {
+    value: String,
+    currency: Currency,
+}
+
+#[derive(Deserialize)]
+struct Currency {
+    symbol: String,
+}
+
+#[derive(Deserialize)]
+struct BlockResponse {
+    block: Option<RawBlock>,
+}
+
+#[derive(Deserialize)]
+struct RawBlock {
+    block_identifier: BlockIdentifier,
+    parent_block_identifier: BlockIdentifier,
+    /// Milliseconds since the Unix epoch
+    timestamp: u64,
+    transactions: Vec<RawTransaction>,
+}
+
+#[derive(Deserialize)]
+struct RawTransaction {
+    transaction_identifier: TransactionIdentifier,
+    operations: Vec<RawOperation>,
+}
+
+#[derive(Deserialize)]
+struct TransactionIdentifier {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct RawOperation {
+    #[serde(rename = "type")]
+    kind: String,
+    status: Option<String>,
+    account: Option<RawAccount>,
+    amount: Option<RawAmount>,
+}
+
+#[derive(Deserialize)]
+struct RawAccount {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct MempoolResponse {
+    transaction_identifiers: Vec<TransactionIdentifier>,
+}
+
+impl RosettaClient {
+    /// Create a client for the Rosetta API at `base_url`
+    pub fn new(base_url: &str, network: &NetworkId) -> Self {
+        Self::with_endpoints(vec![base_url.to_string()], network)
+    }
+
+    /// Create a client failing over between several base URLs, in order of preference
+    pub fn with_endpoints(base_urls: Vec<String>, network: &NetworkId) -> Self {
+        let base_urls = base_urls
+            .into_iter()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .collect();
+        Self {
+            pool: EndpointPool::new(base_urls),
+            retry: RetryPolicy::default(),
+            network: match network {
+                NetworkId::MAINNET => "mainnet",
+                NetworkId::TESTNET => "devnet",
+            },
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The preferred base URL of this client
+    pub fn endpoint(&self) -> &str {
+        self.pool.endpoints().first().map_or("", String::as_str)
+    }
+
+    /// Health of the endpoints, as observed by the requests made so far
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.pool.health()
+    }
+
+    /// Send a request to a Data API path, adding the network identifier
+    pub async fn request<T: DeserializeOwned>(&self, path: &str, mut body: Value) -> Result<T> {
+        body["network_identifier"] = json!({ "blockchain": "mina", "network": self.network });
+        self.pool
+            .run(&self.retry, |base_url| {
+                let body = &body;
+                async move {
+                    let response = self
+                        .http
+                        .post(format!("{}{}", base_url, path))
+                        .json(body)
+                        .send()
+                        .await?;
+                    // Rosetta reports errors with a 500 status and a JSON body
+                    let text = response.text().await?;
+                    parse_response(&text)
+                }
+            })
+            .await
+    }
+
+    /// Fetch the tip of the chain
+    pub async fn network_status(&self) -> Result<BlockIdentifier> {
+        let status: NetworkStatusResponse = self.request("/network/status", json!({})).await?;
+        Ok(status.current_block_identifier)
+    }
+
+    /// Fetch the MINA balance and nonce of an address
+    pub async fn account_balance(&self, address: &str) -> Result<AccountBalance> {
+        let response: AccountBalanceResponse = self
+            .request(
+                "/account/balance",
+                json!({
+                    "account_identifier": {
+                        "address": address,
+                        "metadata": { "token_id": MINA_TOKEN_ID },
+                    },
+                }),
+            )
+            .await?;
+        account_balance_from(response)
+    }
+
+    /// Fetch the balance of an address, in the same form as `GraphQlClient::get_balance`
+    ///
+    /// Rosetta only reports the total balance.
+    pub async fn get_balance(&self, address: &str) -> Result<Balance> {
+        let balance = self.account_balance(address).await?;
+        Ok(Balance {
+            total: balance.balance,
+            liquid: None,
+            locked: None,
+        })
+    }
+
+    /// Fetch a block by height, or the latest block if `index` is `None`
+    pub async fn block(&self, index: Option<u64>) -> Result<RosettaBlock> {
+        let index = match index {
+            Some(index) => index,
+            None => self.network_status().await?.index,
+        };
+        let response: BlockResponse = self
+            .request("/block", json!({ "block_identifier": { "index": index } }))
+            .await?;
+        response
+            .block
+            .map(block_from)
+            .transpose()?
+            .ok_or_else(|| NetworkError::InvalidResponse(format!("block {} not found", index)))
+    }
+
+    /// List the hashes of the transactions in the mempool
+    pub async fn mempool(&self) -> Result<Vec<String>> {
+        let response: MempoolResponse = self.request("/mempool", json!({})).await?;
+        Ok(response
+            .transaction_identifiers
+            .into_iter()
+            .map(|t| t.hash)
+            .collect())
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(text: &str) -> Result<T> {
+    if let Ok(error) = serde_json::from_str::<RosettaError>(text) {
+        return Err(NetworkError::Rosetta {
+            code: error.code,
+            message: error.message,
+        });
+    }
+    serde_json::from_str(text).map_err(|e| NetworkError::InvalidResponse(e.to_string()))
+}
+
+fn account_balance_from(response: AccountBalanceResponse) -> Result<AccountBalance> {
+    let value = response
+        .balances
+        .iter()
+        .find(|b| b.currency.symbol == "MINA")
+        .ok_or_else(|| NetworkError::InvalidResponse("no MINA balance".to_string()))?;
+    let balance = Amount::from_nanomina_str(&value.value)
+        .map_err(|e| NetworkError::InvalidResponse(e.to_string()))?;
+    Ok(AccountBalance {
+        block: response.block_identifier,
+        balance,
+        nonce: response.metadata.and_then(|m| m.nonce),
+    })
+}
+
+fn block_from(raw: RawBlock) -> Result<RosettaBlock> {
+    let transactions = raw
+        .transactions
+        .into_iter()
+        .map(|tx| {
+            let operations = tx
+                .operations
+                .into_iter()
+                .map(|op| {
+                    let amount = op
+                        .amount
+                        .map(|a| a.value.parse::<i64>())
+                        .transpose()
+                        .map_err(|e| NetworkError::InvalidResponse(e.to_string()))?;
+                    Ok(Operation {
+                        kind: op.kind,
+                        status: op.status,
+                        account: op.account.map(|a| a.address),
+                        amount,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(RosettaTransaction {
+                hash: tx.transaction_identifier.hash,
+                operations,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RosettaBlock {
+        block: raw.block_identifier,
+        parent: raw.parent_block_identifier,
+        timestamp: raw.timestamp / 1000,
+        transactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_balance() {
+        let text = r#"{
+            "block_identifier": {"index": 350000, "hash": "3NKa"},
+            "balances": [{"value": "1500000000", "currency": {"symbol": "MINA", "decimals": 9}}],
+            "metadata": {"created_via_historical_lookup": false, "nonce": "7"}
+        }"#;
+        let balance = account_balance_from(parse_response(text).unwrap()).unwrap();
+        assert_eq!(balance.block.index, 350000);
+        assert_eq!(balance.balance, Amount::from_nanomina(1_500_000_000));
+        assert_eq!(balance.nonce, Some(7));
+
+        let error = parse_response::<AccountBalanceResponse>(
+            r#"{"code": 12, "message": "Account not found", "retriable": false}"#,
+        );
+        assert!(matches!(error, Err(NetworkError::Rosetta { code: 12, .. })));
+    }
+
+    #[test]
+    fn test_block() {
+        let text = r#"{"block": {
+            "block_identifier": {"index": 10, "hash": "3NKb"},
+            "parent_block_identifier": {"index": 9, "hash": "3NKa"},
+            "timestamp": 1704067200000,
+            "transactions": [{
+                "transaction_identifier": {"hash": "5Ja"},
+                "operations": [
+                    {"operation_identifier": {"index": 0}, "type": "fee_payment",
+                     "status": "Success", "account": {"address": "B62qa"},
+                     "amount": {"value": "-10000000", "currency": {"symbol": "MINA", "decimals": 9}}},
+                    {"operation_identifier": {"index": 1}, "type": "payment_receiver_inc",
+                     "status": "Success", "account": {"address": "B62qb"},
+                     "amount": {"value": "1000000000", "currency": {"symbol": "MINA", "decimals": 9}}}
+                ]
+            }]
+        }}"#;
+        let response: BlockResponse = parse_response(text).unwrap();
+        let block = block_from(response.block.unwrap()).unwrap();
+        assert_eq!(block.parent.index, 9);
+        assert_eq!(block.timestamp, 1_704_067_200);
+        let operations = &block.transactions[0].operations;
+        assert_eq!(operations[0].amount, Some(-10_000_000));
+        assert_eq!(operations[1].account.as_deref(), Some("B62qb"));
+    }
+}
@@ -0,0 +1,425 @@
+//! Delegation and staking queries
+//!
+//! A delegation only counts for block production once it is part of a
+//! staking ledger, which is snapshotted at epoch boundaries. To check that a
+//! delegation took effect, compare:
+//! - the delegate in the current account state (`GraphQlClient::staking_info`)
+//! - the delegate in the staking ledger of the current epoch
+//!   (`ArchiveClient::staking_ledger_entry`)
+//!
+//! Before delegating, the target can be looked up in a pool directory
+//! (`fetch_pool_directory`), a JSON list of known block producers.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Value, json};
+
+use super::archive::ArchiveClient;
+use super::graphql::GraphQlClient;
+use super::{NetworkError, Result, from_str_or_number};
+use crate::amount::{Amount, NANOMINA_PER_MINA};
+
+const STAKING_INFO_QUERY: &str = r#"query StakingInfo($publicKey: PublicKey!) {
+  account(publicKey: $publicKey) {
+    delegate
+    delegators { publicKey balance { total } }
+  }
+  bestChain(maxLength: 1) {
+    protocolState {
+      consensusState {
+        epoch
+        stakingEpochData { ledger { hash } }
+        nextEpochData { ledger { hash } }
+      }
+    }
+  }
+}"#;
+
+const TOTAL_STAKE_QUERY: &str = r#"query TotalStake {
+  bestChain(maxLength: 1) {
+    protocolState {
+      consensusState { stakingEpochData { ledger { totalCurrency } } }
+    }
+  }
+}"#;
+
+const STAKING_LEDGER_QUERY: &str = r#"query StakingLedger($query: StakeQueryInput!) {
+  stakes(query: $query, limit: 1) {
+    public_key
+    delegate
+    balance
+    epoch
+    ledgerHash
+  }
+}"#;
+
+/// An account delegating to an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Delegator {
+    pub public_key: String,
+    pub balance: Amount,
+}
+
+/// Current delegation state of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct StakingInfo {
+    pub address: String,
+    /// Current delegate; the address itself when it is not delegating
+    pub delegate: Option<String>,
+    /// Accounts currently delegating to this address
+    pub delegators: Vec<Delegator>,
+    /// Current epoch
+    pub epoch: u64,
+    /// Hash of the staking ledger used in the current epoch
+    pub staking_ledger_hash: String,
+    /// Hash of the ledger that will be used in the next epoch
+    pub next_ledger_hash: String,
+}
+
+impl StakingInfo {
+    /// Total balance delegated to this address
+    pub fn delegated_balance(&self) -> Option<Amount> {
+        self.delegators
+            .iter()
+            .try_fold(Amount::ZERO, |total, d| total.checked_add(d.balance))
+    }
+}
+
+/// An entry of an epoch staking ledger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct StakingLedgerEntry {
+    pub public_key: String,
+    /// Delegate that produces blocks with this stake during the epoch
+    pub delegate: String,
+    pub balance: Amount,
+    pub epoch: u64,
+    pub ledger_hash: String,
+}
+
+/// A block producer listed in a pool directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolInfo {
+    pub address: String,
+    pub name: String,
+    #[serde(default)]
+    pub website: Option<String>,
+}
+
+/// Whether the current delegate is already the one in the staking ledger
+pub fn delegation_is_active(info: &StakingInfo, entry: &StakingLedgerEntry) -> bool {
+    let current = info.delegate.as_deref().unwrap_or(&info.address);
+    entry.epoch == info.epoch && entry.delegate == current
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StakingInfoData {
+    account: Option<StakingAccount>,
+    best_chain: Option<Vec<StakingBlock>>,
+}
+
+#[derive(Deserialize)]
+struct StakingAccount {
+    delegate: Option<String>,
+    #[serde(default)]
+    delegators: Option<Vec<RawDelegator>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawDelegator {
+    public_key: String,
+    balance: RawBalance,
+}
+
+#[derive(Deserialize)]
+struct RawBalance {
+    total: Amount,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StakingBlock {
+    protocol_state: StakingProtocolState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StakingProtocolState {
+    consensus_state: StakingConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StakingConsensusState {
+    #[serde(deserialize_with = "from_str_or_number")]
+    epoch: u64,
+    staking_epoch_data: EpochData,
+    next_epoch_data: EpochData,
+}
+
+#[derive(Deserialize)]
+struct EpochData {
+    ledger: EpochLedger,
+}
+
+#[derive(Deserialize)]
+struct EpochLedger {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalStakeData {
+    best_chain: Option<Vec<TotalStakeBlock>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalStakeBlock {
+    protocol_state: TotalStakeProtocolState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalStakeProtocolState {
+    consensus_state: TotalStakeConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalStakeConsensusState {
+    staking_epoch_data: TotalStakeEpochData,
+}
+
+#[derive(Deserialize)]
+struct TotalStakeEpochData {
+    ledger: TotalStakeLedger,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalStakeLedger {
+    total_currency: Amount,
+}
+
+#[derive(Deserialize)]
+struct StakingLedgerData {
+    stakes: Vec<RawStake>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawStake {
+    #[serde(rename = "public_key")]
+    public_key: String,
+    delegate: String,
+    /// The indexer reports staking ledger balances in MINA
+    #[serde(deserialize_with = "mina_from_number")]
+    balance: Amount,
+    #[serde(deserialize_with = "from_str_or_number")]
+    epoch: u64,
+    ledger_hash: String,
+}
+
+impl GraphQlClient {
+    /// Fetch the current delegate and delegators of an address
+    pub async fn staking_info(&self, address: &str) -> Result<StakingInfo> {
+        let data: StakingInfoData = self
+            .query(STAKING_INFO_QUERY, json!({ "publicKey": address }))
+            .await?;
+        staking_info_from(data, address)
+    }
+
+    /// Fetch the total currency of the staking ledger of the current epoch,
+    /// the total stake competing for slots
+    pub async fn total_stake(&self) -> Result<Amount> {
+        let data: TotalStakeData = self.query(TOTAL_STAKE_QUERY, json!({})).await?;
+        data.best_chain
+            .and_then(|blocks| blocks.into_iter().last())
+            .map(|block| {
+                block
+                    .protocol_state
+                    .consensus_state
+                    .staking_epoch_data
+                    .ledger
+                    .total_currency
+            })
+            .ok_or_else(|| NetworkError::InvalidResponse("node returned no blocks".to_string()))
+    }
+}
+
+impl ArchiveClient {
+    /// Fetch the entry of an address in the staking ledger of an epoch
+    pub async fn staking_ledger_entry(
+        &self,
+        address: &str,
+        epoch: u64,
+    ) -> Result<Option<StakingLedgerEntry>> {
+        let data: StakingLedgerData = self
+            .query(
+                STAKING_LEDGER_QUERY,
+                json!({ "query": { "public_key": address, "epoch": epoch } }),
+            )
+            .await?;
+        Ok(data
+            .stakes
+            .into_iter()
+            .next()
+            .map(|raw| StakingLedgerEntry {
+                public_key: raw.public_key,
+                delegate: raw.delegate,
+                balance: raw.balance,
+                epoch: raw.epoch,
+                ledger_hash: raw.ledger_hash,
+            }))
+    }
+}
+
+/// Fetch a pool directory: a JSON array of `{"address", "name", "website"}`
+/// objects served at `url`
+pub async fn fetch_pool_directory(url: &str) -> Result<Vec<PoolInfo>> {
+    let text = reqwest::get(url).await?.error_for_status()?.text().await?;
+    parse_pool_directory(&text)
+}
+
+fn parse_pool_directory(text: &str) -> Result<Vec<PoolInfo>> {
+    serde_json::from_str(text)
+        .map_err(|e| NetworkError::InvalidResponse(format!("invalid pool directory: {}", e)))
+}
+
+fn staking_info_from(data: StakingInfoData, address: &str) -> Result<StakingInfo> {
+    let account = data
+        .account
+        .ok_or_else(|| NetworkError::AccountNotFound(address.to_string()))?;
+    let consensus = data
+        .best_chain
+        .and_then(|blocks| blocks.into_iter().last())
+        .ok_or_else(|| NetworkError::InvalidResponse("node returned no blocks".to_string()))?
+        .protocol_state
+        .consensus_state;
+    Ok(StakingInfo {
+        address: address.to_string(),
+        delegate: account.delegate,
+        delegators: account
+            .delegators
+            .unwrap_or_default()
+            .into_iter()
+            .map(|d| Delegator {
+                public_key: d.public_key,
+                balance: d.balance.total,
+            })
+            .collect(),
+        epoch: consensus.epoch,
+        staking_ledger_hash: consensus.staking_epoch_data.ledger.hash,
+        next_ledger_hash: consensus.next_epoch_data.ledger.hash,
+    })
+}
+
+/// Deserialize a MINA amount sent as a JSON number or decimal string
+///
+/// Numbers are read through their shortest decimal representation, so that
+/// `1.1` becomes exactly 1.1 MINA. Only exponent forms fall back to rounding.
+fn mina_from_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Amount, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    let text = match &value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        other => {
+            return Err(serde::de::Error::custom(format!(
+                "invalid amount {}",
+                other
+            )));
+        }
+    };
+    if let Ok(amount) = text.parse::<Amount>() {
+        return Ok(amount);
+    }
+    match value.as_f64() {
+        Some(mina) if (0.0..(u64::MAX / NANOMINA_PER_MINA) as f64).contains(&mina) => Ok(
+            Amount::from_nanomina((mina * NANOMINA_PER_MINA as f64).round() as u64),
+        ),
+        _ => Err(serde::de::Error::custom(format!("invalid amount {}", text))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+    const DELEGATE: &str = "B62qrQiw9JhUumq457sMxicgQ94Z1WD9JChzFu5vKLPdbmJnWx4VC3r";
+
+    fn info() -> StakingInfo {
+        let text = format!(
+            r#"{{"account":{{"delegate":"{}","delegators":[
+                {{"publicKey":"B62qa","balance":{{"total":"1000000000"}}}},
+                {{"publicKey":"B62qb","balance":{{"total":"500000000"}}}}]}},
+              "bestChain":[{{"protocolState":{{"consensusState":{{"epoch":"74",
+                "stakingEpochData":{{"ledger":{{"hash":"jxA"}}}},
+                "nextEpochData":{{"ledger":{{"hash":"jxB"}}}}}}}}}}]}}"#,
+            DELEGATE
+        );
+        staking_info_from(serde_json::from_str(&text).unwrap(), ADDRESS).unwrap()
+    }
+
+    #[test]
+    fn test_staking_info() {
+        let info = info();
+        assert_eq!(info.delegate.as_deref(), Some(DELEGATE));
+        assert_eq!(info.epoch, 74);
+        assert_eq!(
+            info.delegated_balance(),
+            Some(Amount::from_nanomina(1_500_000_000))
+        );
+    }
+
+    #[test]
+    fn test_delegation_is_active() {
+        let info = info();
+        let text = format!(
+            r#"{{"public_key":"{}","delegate":"{}","balance":1.1,"epoch":74,"ledgerHash":"jxA"}}"#,
+            ADDRESS, DELEGATE
+        );
+        let raw: RawStake = serde_json::from_str(&text).unwrap();
+        assert_eq!(raw.balance, Amount::from_nanomina(1_100_000_000));
+
+        let mut entry = StakingLedgerEntry {
+            public_key: raw.public_key,
+            delegate: raw.delegate,
+            balance: raw.balance,
+            epoch: raw.epoch,
+            ledger_hash: raw.ledger_hash,
+        };
+        assert!(delegation_is_active(&info, &entry));
+
+        // Delegation changed during the epoch: not active until a later one
+        entry.delegate = ADDRESS.to_string();
+        assert!(!delegation_is_active(&info, &entry));
+    }
+
+    #[test]
+    fn test_parse_pool_directory() {
+        let text = format!(
+            r#"[{{"address":"{}","name":"Pool A","website":"https://pool-a.example"}},
+               {{"address":"{}","name":"Pool B"}}]"#,
+            DELEGATE, ADDRESS
+        );
+        let pools = parse_pool_directory(&text).unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].website.as_deref(), Some("https://pool-a.example"));
+        assert_eq!(pools[1].website, None);
+        assert!(matches!(
+            parse_pool_directory(r#"{"pools":[]}"#),
+            Err(NetworkError::InvalidResponse(_))
+        ));
+    }
+}
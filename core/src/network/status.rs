@@ -0,0 +1,234 @@
+//! Node health checks
+//!
+//! Before broadcasting, a wallet should make sure the node it talks to is
+//! synced: a node that is bootstrapping or lagging behind the network may
+//! report stale balances and nonces, and may not relay transactions.
+//!
+//! It should also make sure the node follows the chain the wallet signs
+//! for: `GraphQlClient::verify_chain_id` compares the node's chain ID with
+//! the known chain IDs of the configured network.
+
+use mina_signer::NetworkId;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use web_time::Instant;
+
+use super::graphql::GraphQlClient;
+use super::{NetworkError, Result, from_str_or_number, opt_from_str_or_number};
+use crate::wallet::network_name;
+
+/// Blocks a node may lag behind the highest block it received and still be healthy
+pub const MAX_BLOCKS_BEHIND: u64 = 2;
+
+/// Chain IDs of mainnet, before and after the Berkeley hard fork
+pub const MAINNET_CHAIN_IDS: &[&str] = &[
+    "a7351abc7ddf2ea92d1b38cc8e636c271c1dfd2c081c637f62ebc2af34eb7cc1",
+    "5f704cc0c82e0ed70e873f0893d7e06f148524e3f0bdae2afb02e7819a0c24d1",
+];
+
+/// Chain IDs of the public testnet (devnet)
+pub const TESTNET_CHAIN_IDS: &[&str] =
+    &["29936104443aaf264a7f0192ac64b1c7173198c1ed404c1bcff5e562e05eb7f6"];
+
+/// Known chain IDs of a network
+pub fn chain_ids(network: &NetworkId) -> &'static [&'static str] {
+    match network {
+        NetworkId::MAINNET => MAINNET_CHAIN_IDS,
+        NetworkId::TESTNET => TESTNET_CHAIN_IDS,
+    }
+}
+
+/// Whether `chain_id` is a known chain ID of `network`
+pub fn chain_id_matches(network: &NetworkId, chain_id: &str) -> bool {
+    chain_ids(network).contains(&chain_id)
+}
+
+const CHAIN_ID_QUERY: &str = r#"query ChainId {
+  daemonStatus { chainId }
+}"#;
+
+const DAEMON_STATUS_QUERY: &str = r#"query DaemonStatus {
+  daemonStatus {
+    syncStatus
+    chainId
+    blockchainLength
+    highestBlockLengthReceived
+  }
+}"#;
+
+/// Health of a node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct NodeStatus {
+    pub endpoint: String,
+    /// `SYNCED`, `CATCHUP`, `BOOTSTRAP`, `CONNECTING`, `LISTENING` or `OFFLINE`
+    pub sync_status: String,
+    /// Hash identifying the chain the node follows
+    pub chain_id: String,
+    /// Height of the node's best tip
+    pub block_height: u64,
+    /// Highest block height the node heard about from peers
+    pub highest_block_received: u64,
+    /// Round-trip time of the status query, in milliseconds
+    pub latency_ms: u64,
+}
+
+impl NodeStatus {
+    /// Whether the node reports being synced
+    pub fn is_synced(&self) -> bool {
+        self.sync_status == "SYNCED"
+    }
+
+    /// How many blocks the node's tip is behind the highest block it received
+    pub fn blocks_behind(&self) -> u64 {
+        self.highest_block_received
+            .saturating_sub(self.block_height)
+    }
+
+    /// Whether the node is synced and close to the network tip
+    pub fn is_healthy(&self) -> bool {
+        self.is_synced() && self.blocks_behind() <= MAX_BLOCKS_BEHIND
+    }
+
+    /// Human-readable problems with the node, empty if it is healthy
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.is_synced() {
+            warnings.push(format!("node is not synced ({})", self.sync_status));
+        }
+        if self.blocks_behind() > MAX_BLOCKS_BEHIND {
+            warnings.push(format!(
+                "node is {} blocks behind the network",
+                self.blocks_behind()
+            ));
+        }
+        warnings
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainIdData {
+    daemon_status: ChainIdStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainIdStatus {
+    chain_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DaemonStatusData {
+    daemon_status: DaemonStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DaemonStatus {
+    sync_status: String,
+    chain_id: String,
+    #[serde(default, deserialize_with = "opt_from_str_or_number")]
+    blockchain_length: Option<u64>,
+    #[serde(deserialize_with = "from_str_or_number")]
+    highest_block_length_received: u64,
+}
+
+impl GraphQlClient {
+    /// Query the sync state, chain ID and height of the node, and time the request
+    ///
+    /// With several endpoints, this is the status of the first one that answers.
+    pub async fn node_status(&self) -> Result<NodeStatus> {
+        let start = Instant::now();
+        let (data, endpoint): (DaemonStatusData, String) =
+            self.query_from(DAEMON_STATUS_QUERY, json!({})).await?;
+        let latency_ms = start.elapsed().as_millis() as u64;
+        Ok(node_status_from(data.daemon_status, &endpoint, latency_ms))
+    }
+
+    /// Fetch the chain ID of the node
+    pub async fn chain_id(&self) -> Result<String> {
+        let data: ChainIdData = self.query(CHAIN_ID_QUERY, json!({})).await?;
+        Ok(data.daemon_status.chain_id)
+    }
+
+    /// Check that the node follows the chain of `network`, returning its chain ID
+    ///
+    /// Fails with `NetworkError::ChainMismatch` otherwise, so that nothing is
+    /// signed or broadcast for the wrong chain.
+    pub async fn verify_chain_id(&self, network: &NetworkId) -> Result<String> {
+        let chain_id = self.chain_id().await?;
+        if chain_id_matches(network, &chain_id) {
+            Ok(chain_id)
+        } else {
+            Err(NetworkError::ChainMismatch {
+                network: network_name(network).to_string(),
+                chain_id,
+            })
+        }
+    }
+}
+
+/// Check the health of the node at `endpoint`
+pub async fn node_status(endpoint: &str) -> Result<NodeStatus> {
+    GraphQlClient::new(endpoint).node_status().await
+}
+
+fn node_status_from(status: DaemonStatus, endpoint: &str, latency_ms: u64) -> NodeStatus {
+    NodeStatus {
+        endpoint: endpoint.to_string(),
+        sync_status: status.sync_status,
+        chain_id: status.chain_id,
+        // Bootstrapping nodes have no best tip yet
+        block_height: status.blockchain_length.unwrap_or(0),
+        highest_block_received: status.highest_block_length_received,
+        latency_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_status(text: &str) -> NodeStatus {
+        node_status_from(serde_json::from_str(text).unwrap(), "http://node", 42)
+    }
+
+    #[test]
+    fn test_healthy_node() {
+        let status = parse_status(
+            r#"{"syncStatus":"SYNCED","chainId":"a735","blockchainLength":100,
+                "highestBlockLengthReceived":101}"#,
+        );
+        assert!(status.is_healthy());
+        assert!(status.warnings().is_empty());
+        assert_eq!(status.latency_ms, 42);
+    }
+
+    #[test]
+    fn test_stale_node() {
+        let status = parse_status(
+            r#"{"syncStatus":"CATCHUP","chainId":"a735","blockchainLength":90,
+                "highestBlockLengthReceived":100}"#,
+        );
+        assert!(!status.is_healthy());
+        assert_eq!(status.blocks_behind(), 10);
+        assert_eq!(status.warnings().len(), 2);
+
+        let bootstrapping = parse_status(
+            r#"{"syncStatus":"BOOTSTRAP","chainId":"a735","blockchainLength":null,
+                "highestBlockLengthReceived":100}"#,
+        );
+        assert_eq!(bootstrapping.block_height, 0);
+    }
+
+    #[test]
+    fn test_chain_id_matches() {
+        assert!(chain_id_matches(&NetworkId::MAINNET, MAINNET_CHAIN_IDS[0]));
+        assert!(!chain_id_matches(&NetworkId::TESTNET, MAINNET_CHAIN_IDS[0]));
+        assert!(chain_id_matches(&NetworkId::TESTNET, TESTNET_CHAIN_IDS[0]));
+        assert!(!chain_id_matches(&NetworkId::MAINNET, "a735"));
+    }
+}
@@ -0,0 +1,366 @@
+//! Block and account subscriptions over GraphQL websockets
+//!
+//! The daemon pushes `newBlock` events over a websocket speaking the
+//! `graphql-ws` protocol (`connection_init`, `start`, `data`, `stop`, ...).
+//! `SubscriptionClient` implements the protocol without owning the socket:
+//! the caller sends the messages it produces and feeds it every message
+//! received, and the client dispatches events to the registered callbacks.
+//! This keeps the same logic usable with browser and native websockets.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+use super::{NetworkError, Result, from_str_or_number};
+use crate::amount::Amount;
+use crate::transaction;
+
+/// Websocket subprotocol spoken by the daemon
+pub const SUBPROTOCOL: &str = "graphql-ws";
+
+const NEW_BLOCK_SUBSCRIPTION: &str = r#"subscription NewBlock($publicKey: PublicKey) {
+  newBlock(publicKey: $publicKey) {
+    stateHash
+    protocolState { consensusState { blockHeight } }
+    transactions {
+      userCommands { hash kind from to amount fee nonce memo }
+    }
+  }
+}"#;
+
+/// What to subscribe to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Topic {
+    /// Every new block
+    NewBlocks,
+    /// New blocks containing transactions from or to an address
+    Account(String),
+}
+
+/// A user command included in a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockCommand {
+    pub hash: String,
+    pub kind: String,
+    pub from: String,
+    pub to: String,
+    pub amount: Amount,
+    pub fee: Amount,
+    #[serde(deserialize_with = "from_str_or_number")]
+    pub nonce: u32,
+    pub memo: String,
+}
+
+/// A new block pushed by the node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockEvent {
+    pub state_hash: String,
+    pub block_height: u64,
+    pub commands: Vec<BlockCommand>,
+}
+
+impl BlockEvent {
+    /// Payments in this block received by `address`
+    pub fn incoming_payments(&self, address: &str) -> Vec<&BlockCommand> {
+        self.commands
+            .iter()
+            .filter(|c| c.to == address && c.from != address && c.kind == "PAYMENT")
+            .collect()
+    }
+}
+
+/// An event delivered to a subscription callback
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SubscriptionEvent {
+    Block(BlockEvent),
+    Error {
+        message: String,
+    },
+    /// The server ended the subscription
+    Completed,
+}
+
+/// Callback invoked for every event of a subscription
+pub type Callback = Box<dyn FnMut(SubscriptionEvent)>;
+
+/// Protocol state of a websocket connection to a node
+#[derive(Default)]
+pub struct SubscriptionClient {
+    callbacks: HashMap<String, Callback>,
+    next_id: u64,
+}
+
+#[derive(Deserialize)]
+struct ServerMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    id: Option<String>,
+    payload: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewBlockData {
+    new_block: RawBlock,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBlock {
+    state_hash: String,
+    protocol_state: RawProtocolState,
+    transactions: RawTransactions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawProtocolState {
+    consensus_state: RawConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConsensusState {
+    #[serde(deserialize_with = "from_str_or_number")]
+    block_height: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTransactions {
+    user_commands: Vec<BlockCommand>,
+}
+
+impl SubscriptionClient {
+    /// Create a client with no subscriptions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// First message to send once the socket is open
+    pub fn init_message() -> String {
+        json!({ "type": "connection_init", "payload": {} }).to_string()
+    }
+
+    /// Register a subscription, returning its id and the message to send
+    pub fn subscribe(&mut self, topic: &Topic, callback: Callback) -> (String, String) {
+        self.next_id += 1;
+        let id = self.next_id.to_string();
+        let public_key = match topic {
+            Topic::NewBlocks => Value::Null,
+            Topic::Account(address) => json!(address),
+        };
+        let message = json!({
+            "id": id,
+            "type": "start",
+            "payload": {
+                "query": NEW_BLOCK_SUBSCRIPTION,
+                "variables": { "publicKey": public_key },
+            },
+        })
+        .to_string();
+        self.callbacks.insert(id.clone(), callback);
+        (id, message)
+    }
+
+    /// Remove a subscription, returning the message to send, if it existed
+    pub fn unsubscribe(&mut self, id: &str) -> Option<String> {
+        self.callbacks
+            .remove(id)
+            .map(|_| json!({ "id": id, "type": "stop" }).to_string())
+    }
+
+    /// Number of active subscriptions
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Whether there are no active subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Handle a message received from the server
+    ///
+    /// Keep-alives and acknowledgements are ignored; data, errors and
+    /// completions are dispatched to the callback of their subscription.
+    pub fn handle_message(&mut self, text: &str) -> Result<()> {
+        let message: ServerMessage =
+            serde_json::from_str(text).map_err(|e| NetworkError::InvalidResponse(e.to_string()))?;
+        match message.kind.as_str() {
+            "connection_ack" | "ka" => Ok(()),
+            "connection_error" => {
+                let event = SubscriptionEvent::Error {
+                    message: payload_message(message.payload),
+                };
+                for callback in self.callbacks.values_mut() {
+                    callback(event.clone());
+                }
+                Ok(())
+            }
+            // `next` is the name used by the newer graphql-transport-ws protocol
+            "data" | "next" => {
+                let event = parse_data(message.payload);
+                self.dispatch(message.id, event);
+                Ok(())
+            }
+            "error" => {
+                let event = SubscriptionEvent::Error {
+                    message: payload_message(message.payload),
+                };
+                self.dispatch(message.id, event);
+                Ok(())
+            }
+            "complete" => {
+                if let Some(mut callback) = message.id.and_then(|id| self.callbacks.remove(&id)) {
+                    callback(SubscriptionEvent::Completed);
+                }
+                Ok(())
+            }
+            other => Err(NetworkError::InvalidResponse(format!(
+                "unexpected message type '{}'",
+                other
+            ))),
+        }
+    }
+
+    fn dispatch(&mut self, id: Option<String>, event: SubscriptionEvent) {
+        if let Some(callback) = id.and_then(|id| self.callbacks.get_mut(&id)) {
+            callback(event);
+        }
+    }
+}
+
+fn parse_data(payload: Option<Value>) -> SubscriptionEvent {
+    let payload = payload.unwrap_or_default();
+    if let Some(errors) = payload.get("errors").and_then(Value::as_array)
+        && !errors.is_empty()
+    {
+        let messages: Vec<&str> = errors
+            .iter()
+            .filter_map(|e| e.get("message").and_then(Value::as_str))
+            .collect();
+        return SubscriptionEvent::Error {
+            message: messages.join("; "),
+        };
+    }
+
+    match serde_json::from_value::<NewBlockData>(payload["data"].clone()) {
+        Ok(data) => {
+            let block = data.new_block;
+            let commands = block
+                .transactions
+                .user_commands
+                .into_iter()
+                .map(|mut c| {
+                    if let Some(memo) = transaction::decode_memo_base58(&c.memo) {
+                        c.memo = memo;
+                    }
+                    c
+                })
+                .collect();
+            SubscriptionEvent::Block(BlockEvent {
+                state_hash: block.state_hash,
+                block_height: block.protocol_state.consensus_state.block_height,
+                commands,
+            })
+        }
+        Err(e) => SubscriptionEvent::Error {
+            message: format!("invalid block: {}", e),
+        },
+    }
+}
+
+fn payload_message(payload: Option<Value>) -> String {
+    match payload {
+        Some(Value::Object(map)) if map.contains_key("message") => {
+            map["message"].as_str().unwrap_or_default().to_string()
+        }
+        Some(other) => other.to_string(),
+        None => "unknown error".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn block_message(id: &str) -> String {
+        format!(
+            r#"{{"type":"data","id":"{}","payload":{{"data":{{"newBlock":{{
+                "stateHash":"3NKa","protocolState":{{"consensusState":{{"blockHeight":"42"}}}},
+                "transactions":{{"userCommands":[{{"hash":"5Ja","kind":"PAYMENT",
+                "from":"B62qother","to":"{}","amount":"1000000000","fee":"10000000",
+                "nonce":"3","memo":"E4YM2vTHhWEg66xpj52JErHUBU4pZ1yageL4TVDDpTTSsv8mK6YaH"}}]}}}}}}}}}}"#,
+            id, ADDRESS
+        )
+    }
+
+    #[test]
+    fn test_dispatch_block_events() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut client = SubscriptionClient::new();
+        let sink = events.clone();
+        let (id, start) = client.subscribe(
+            &Topic::Account(ADDRESS.to_string()),
+            Box::new(move |event| sink.borrow_mut().push(event)),
+        );
+        assert!(start.contains("\"start\""));
+
+        client
+            .handle_message(r#"{"type":"connection_ack"}"#)
+            .unwrap();
+        client.handle_message(&block_message(&id)).unwrap();
+        // Events for unknown subscriptions are dropped
+        client.handle_message(&block_message("99")).unwrap();
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        let SubscriptionEvent::Block(block) = &events[0] else {
+            panic!("expected a block event");
+        };
+        assert_eq!(block.block_height, 42);
+        assert_eq!(block.commands[0].memo, "");
+        assert_eq!(block.incoming_payments(ADDRESS).len(), 1);
+    }
+
+    #[test]
+    fn test_errors_and_completion() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut client = SubscriptionClient::new();
+        let sink = events.clone();
+        let (id, _) = client.subscribe(
+            &Topic::NewBlocks,
+            Box::new(move |event| sink.borrow_mut().push(event)),
+        );
+
+        let error = format!(
+            r#"{{"type":"data","id":"{}","payload":{{"data":null,"errors":[{{"message":"boom"}}]}}}}"#,
+            id
+        );
+        client.handle_message(&error).unwrap();
+        client
+            .handle_message(&format!(r#"{{"type":"complete","id":"{}"}}"#, id))
+            .unwrap();
+        assert!(client.is_empty());
+        assert!(client.unsubscribe(&id).is_none());
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                SubscriptionEvent::Error {
+                    message: "boom".to_string()
+                },
+                SubscriptionEvent::Completed,
+            ]
+        );
+        assert!(client.handle_message("not json").is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! Transaction confirmation tracking
+//!
+//! `TxTracker` looks a transaction hash up in the transaction pool and in the
+//! recent blocks of the best chain, and reports it as:
+//! - `Pending` - waiting in the transaction pool
+//! - `Included` - in a block with fewer than the configured confirmations
+//! - `Finalized` - buried under at least the configured confirmations
+//! - `Unknown` - neither in the pool nor in the searched blocks
+//!
+//! The tracker does not depend on an async runtime: `TxTracker::track` takes
+//! the sleep function to use between polls.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::time::Duration;
+
+use super::graphql::GraphQlClient;
+use super::{Result, from_str_or_number};
+
+/// Confirmations after which a transaction is reported as finalized
+pub const DEFAULT_CONFIRMATIONS: u32 = 15;
+
+/// Number of recent blocks searched for a transaction (the consensus `k`)
+pub const DEFAULT_SEARCH_DEPTH: u32 = 290;
+
+/// Delay between two polls
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Consecutive `Unknown` polls after which `track` gives up
+const MAX_UNKNOWN_POLLS: u32 = 3;
+
+const RECENT_BLOCKS_QUERY: &str = r#"query RecentBlocks($maxLength: Int) {
+  bestChain(maxLength: $maxLength) {
+    stateHash
+    protocolState { consensusState { blockHeight } }
+    transactions { userCommands { hash } }
+  }
+}"#;
+
+const POOLED_HASH_QUERY: &str = r#"query PooledHash($hashes: [String!]) {
+  pooledUserCommands(hashes: $hashes) { hash }
+}"#;
+
+/// Status of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum TxStatus {
+    Pending,
+    #[serde(rename_all = "camelCase")]
+    Included {
+        block_height: u64,
+        state_hash: String,
+        confirmations: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Finalized {
+        block_height: u64,
+        state_hash: String,
+        confirmations: u64,
+    },
+    Unknown,
+}
+
+/// Polls a node for the status of transactions
+#[derive(Debug, Clone)]
+pub struct TxTracker {
+    client: GraphQlClient,
+    confirmations: u32,
+    search_depth: u32,
+    poll_interval: Duration,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentBlocksData {
+    best_chain: Option<Vec<RecentBlock>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentBlock {
+    state_hash: String,
+    protocol_state: RecentProtocolState,
+    transactions: RecentTransactions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentProtocolState {
+    consensus_state: RecentConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentConsensusState {
+    #[serde(deserialize_with = "from_str_or_number")]
+    block_height: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecentTransactions {
+    user_commands: Vec<HashOnly>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PooledHashData {
+    pooled_user_commands: Vec<HashOnly>,
+}
+
+#[derive(Deserialize)]
+struct HashOnly {
+    hash: String,
+}
+
+impl TxTracker {
+    /// Create a tracker with the default confirmation depth and poll interval
+    pub fn new(client: GraphQlClient) -> Self {
+        Self {
+            client,
+            confirmations: DEFAULT_CONFIRMATIONS,
+            search_depth: DEFAULT_SEARCH_DEPTH,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Set the number of confirmations after which a transaction is final
+    pub fn with_confirmations(mut self, confirmations: u32) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// Set how many recent blocks are searched for the transaction
+    pub fn with_search_depth(mut self, search_depth: u32) -> Self {
+        self.search_depth = search_depth;
+        self
+    }
+
+    /// Set the delay between two polls
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Look up the current status of a transaction
+    pub async fn status(&self, hash: &str) -> Result<TxStatus> {
+        let pooled: PooledHashData = self
+            .client
+            .query(POOLED_HASH_QUERY, json!({ "hashes": [hash] }))
+            .await?;
+        if pooled.pooled_user_commands.iter().any(|c| c.hash == hash) {
+            return Ok(TxStatus::Pending);
+        }
+
+        let blocks: RecentBlocksData = self
+            .client
+            .query(
+                RECENT_BLOCKS_QUERY,
+                json!({ "maxLength": self.search_depth }),
+            )
+            .await?;
+        Ok(status_in_blocks(
+            &blocks.best_chain.unwrap_or_default(),
+            hash,
+            self.confirmations,
+        ))
+    }
+
+    /// Poll until the transaction is finalized, calling `on_update` on every change
+    ///
+    /// Returns `TxStatus::Unknown` if the transaction cannot be found several
+    /// times in a row, e.g. because it was dropped from the pool.
+    pub async fn track<S, F>(
+        &self,
+        hash: &str,
+        mut sleep: S,
+        mut on_update: impl FnMut(&TxStatus),
+    ) -> Result<TxStatus>
+    where
+        S: FnMut(Duration) -> F,
+        F: Future<Output = ()>,
+    {
+        let mut last = None;
+        let mut unknown_polls = 0;
+        loop {
+            let status = self.status(hash).await?;
+            if last.as_ref() != Some(&status) {
+                on_update(&status);
+            }
+            match status {
+                TxStatus::Finalized { .. } => return Ok(status),
+                TxStatus::Unknown => {
+                    unknown_polls += 1;
+                    if unknown_polls >= MAX_UNKNOWN_POLLS {
+                        return Ok(status);
+                    }
+                }
+                _ => unknown_polls = 0,
+            }
+            last = Some(status);
+            sleep(self.poll_interval).await;
+        }
+    }
+}
+
+fn status_in_blocks(blocks: &[RecentBlock], hash: &str, confirmations: u32) -> TxStatus {
+    let Some(tip) = blocks
+        .iter()
+        .map(|b| b.protocol_state.consensus_state.block_height)
+        .max()
+    else {
+        return TxStatus::Unknown;
+    };
+
+    let Some(block) = blocks
+        .iter()
+        .find(|b| b.transactions.user_commands.iter().any(|c| c.hash == hash))
+    else {
+        return TxStatus::Unknown;
+    };
+
+    let block_height = block.protocol_state.consensus_state.block_height;
+    let state_hash = block.state_hash.clone();
+    let depth = tip - block_height;
+    if depth >= u64::from(confirmations) {
+        TxStatus::Finalized {
+            block_height,
+            state_hash,
+            confirmations: depth,
+        }
+    } else {
+        TxStatus::Included {
+            block_height,
+            state_hash,
+            confirmations: depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks() -> Vec<RecentBlock> {
+        let text = r#"[
+            {"stateHash":"3NKa","protocolState":{"consensusState":{"blockHeight":"100"}},
+             "transactions":{"userCommands":[{"hash":"5Ja"}]}},
+            {"stateHash":"3NKb","protocolState":{"consensusState":{"blockHeight":"110"}},
+             "transactions":{"userCommands":[{"hash":"5Jb"}]}}
+        ]"#;
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_status_in_blocks() {
+        let blocks = blocks();
+        assert_eq!(
+            status_in_blocks(&blocks, "5Ja", 10),
+            TxStatus::Finalized {
+                block_height: 100,
+                state_hash: "3NKa".to_string(),
+                confirmations: 10,
+            }
+        );
+        assert_eq!(
+            status_in_blocks(&blocks, "5Ja", 15),
+            TxStatus::Included {
+                block_height: 100,
+                state_hash: "3NKa".to_string(),
+                confirmations: 10,
+            }
+        );
+        assert_eq!(status_in_blocks(&blocks, "5Jc", 15), TxStatus::Unknown);
+        assert_eq!(status_in_blocks(&[], "5Ja", 15), TxStatus::Unknown);
+    }
+
+    #[test]
+    fn test_status_json() {
+        let json = serde_json::to_value(TxStatus::Included {
+            block_height: 1,
+            state_hash: "3NK".to_string(),
+            confirmations: 0,
+        })
+        .unwrap();
+        assert_eq!(json["status"], "included");
+        assert_eq!(json["blockHeight"], 1);
+        assert_eq!(
+            serde_json::to_value(TxStatus::Pending).unwrap()["status"],
+            "pending"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Watching an address for new transactions
+//!
+//! `AccountWatcher` polls the recent blocks of the best chain and reports the
+//! transactions from or to an address in blocks it has not seen before. The
+//! first poll only records the blocks already on chain, so that watching
+//! starts from the next block rather than replaying history.
+//!
+//! Polling works with any node, unlike `subscription` which needs a websocket.
+//! After a chain reorganization, a transaction can be reported again in the
+//! block that replaced its original block.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::archive::Direction;
+use super::graphql::GraphQlClient;
+use super::subscription::{BlockCommand, BlockEvent};
+use super::{Result, from_str_or_number};
+use crate::transaction;
+
+/// Number of recent blocks fetched on every poll
+pub const DEFAULT_WATCH_DEPTH: u32 = 10;
+
+/// Delay between two polls; blocks come about every 3 minutes
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+const WATCH_BLOCKS_QUERY: &str = r#"query WatchBlocks($maxLength: Int) {
+  bestChain(maxLength: $maxLength) {
+    stateHash
+    protocolState { consensusState { blockHeight } }
+    transactions { userCommands { hash kind from to amount fee nonce memo } }
+  }
+}"#;
+
+/// A transaction from or to the watched address, in a new block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchEvent {
+    pub direction: Direction,
+    pub block_height: u64,
+    pub state_hash: String,
+    #[serde(flatten)]
+    pub command: BlockCommand,
+}
+
+/// Polls a node for new transactions of an address
+#[derive(Debug, Clone)]
+pub struct AccountWatcher {
+    client: GraphQlClient,
+    address: String,
+    depth: u32,
+    poll_interval: Duration,
+    /// State hashes of the blocks fetched by the last poll, `None` before the first
+    seen: Option<HashSet<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WatchBlocksData {
+    best_chain: Option<Vec<RawBlock>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBlock {
+    state_hash: String,
+    protocol_state: RawProtocolState,
+    transactions: RawTransactions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawProtocolState {
+    consensus_state: RawConsensusState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConsensusState {
+    #[serde(deserialize_with = "from_str_or_number")]
+    block_height: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawTransactions {
+    user_commands: Vec<BlockCommand>,
+}
+
+impl From<RawBlock> for BlockEvent {
+    fn from(block: RawBlock) -> Self {
+        let commands = block
+            .transactions
+            .user_commands
+            .into_iter()
+            .map(|mut c| {
+                if let Some(memo) = transaction::decode_memo_base58(&c.memo) {
+                    c.memo = memo;
+                }
+                c
+            })
+            .collect();
+        BlockEvent {
+            state_hash: block.state_hash,
+            block_height: block.protocol_state.consensus_state.block_height,
+            commands,
+        }
+    }
+}
+
+impl AccountWatcher {
+    /// Watch `address` with the default depth and poll interval
+    pub fn new(client: GraphQlClient, address: &str) -> Self {
+        Self {
+            client,
+            address: address.to_string(),
+            depth: DEFAULT_WATCH_DEPTH,
+            poll_interval: DEFAULT_WATCH_INTERVAL,
+            seen: None,
+        }
+    }
+
+    /// Set how many recent blocks are fetched on every poll
+    ///
+    /// It must cover the blocks produced between two polls.
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    /// Set the delay between two polls
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Delay to wait between two calls to `poll`
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Fetch the recent blocks and return the transactions of the address in
+    /// blocks not seen before, oldest first
+    pub async fn poll(&mut self) -> Result<Vec<WatchEvent>> {
+        let data: WatchBlocksData = self
+            .client
+            .query(WATCH_BLOCKS_QUERY, json!({ "maxLength": self.depth }))
+            .await?;
+        let blocks = data
+            .best_chain
+            .unwrap_or_default()
+            .into_iter()
+            .map(BlockEvent::from)
+            .collect();
+        Ok(self.process(blocks))
+    }
+
+    fn process(&mut self, mut blocks: Vec<BlockEvent>) -> Vec<WatchEvent> {
+        blocks.sort_by_key(|b| b.block_height);
+        let events = match &self.seen {
+            None => Vec::new(),
+            Some(seen) => blocks
+                .iter()
+                .filter(|b| !seen.contains(&b.state_hash))
+                .flat_map(|b| events_in_block(b, &self.address))
+                .collect(),
+        };
+        // Blocks older than the fetched ones cannot come back
+        self.seen = Some(blocks.into_iter().map(|b| b.state_hash).collect());
+        events
+    }
+}
+
+fn events_in_block(block: &BlockEvent, address: &str) -> Vec<WatchEvent> {
+    block
+        .commands
+        .iter()
+        .filter_map(|c| {
+            let direction = match (c.from == address, c.to == address) {
+                (true, true) => Direction::SelfTransfer,
+                (true, false) => Direction::Outgoing,
+                (false, true) => Direction::Incoming,
+                (false, false) => return None,
+            };
+            Some(WatchEvent {
+                direction,
+                block_height: block.block_height,
+                state_hash: block.state_hash.clone(),
+                command: c.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn command(hash: &str, from: &str, to: &str) -> BlockCommand {
+        BlockCommand {
+            hash: hash.to_string(),
+            kind: "PAYMENT".to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            amount: "1".parse().unwrap(),
+            fee: "0.1".parse().unwrap(),
+            nonce: 0,
+            memo: String::new(),
+        }
+    }
+
+    fn block(height: u64, state_hash: &str, commands: Vec<BlockCommand>) -> BlockEvent {
+        BlockEvent {
+            state_hash: state_hash.to_string(),
+            block_height: height,
+            commands,
+        }
+    }
+
+    fn watcher() -> AccountWatcher {
+        AccountWatcher::new(GraphQlClient::new("http://localhost:3085/graphql"), ADDRESS)
+    }
+
+    #[test]
+    fn test_reports_new_blocks_only() {
+        let mut watcher = watcher();
+        let old = block(10, "3NKa", vec![command("5Ja", "B62qother", ADDRESS)]);
+        assert!(watcher.process(vec![old.clone()]).is_empty());
+        assert!(watcher.process(vec![old.clone()]).is_empty());
+
+        let new = vec![
+            block(12, "3NKc", vec![command("5Jc", ADDRESS, ADDRESS)]),
+            block(
+                11,
+                "3NKb",
+                vec![
+                    command("5Jb", ADDRESS, "B62qother"),
+                    command("5Jx", "B62qother", "B62qanother"),
+                ],
+            ),
+            old,
+        ];
+        let events = watcher.process(new);
+        let summary: Vec<(&str, Direction, u64)> = events
+            .iter()
+            .map(|e| (e.command.hash.as_str(), e.direction, e.block_height))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("5Jb", Direction::Outgoing, 11),
+                ("5Jc", Direction::SelfTransfer, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reorganization_and_serialization() {
+        let mut watcher = watcher();
+        watcher.process(vec![block(10, "3NKa", vec![])]);
+
+        // A block replacing the one at the same height is new
+        let events = watcher.process(vec![block(
+            10,
+            "3NKz",
+            vec![command("5Ja", "B62qother", ADDRESS)],
+        )]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].direction, Direction::Incoming);
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["direction"], "incoming");
+        assert_eq!(json["blockHeight"], 10);
+        assert_eq!(json["hash"], "5Ja");
+    }
+}
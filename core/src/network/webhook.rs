@@ -0,0 +1,285 @@
+//! Webhook notifications of watched transactions
+//!
+//! Merchants receive a signed HTTP POST when an address they watch gets a
+//! payment, and again when a transaction of the address is finalized, instead
+//! of polling a node themselves. The body is a JSON `WebhookPayload`, sent with
+//! the headers:
+//! - `X-Mina-Wallet-Event` - `payment.received` or `transaction.confirmed`
+//! - `X-Mina-Wallet-Timestamp` - Unix time of the delivery, in seconds
+//! - `X-Mina-Wallet-Signature` - `sha256=` and the hex HMAC-SHA256 of
+//!   `<timestamp>.<body>` under the secret shared with the receiver
+//!
+//! Receivers should check the signature with `verify_signature` or its
+//! equivalent, and reject old timestamps to stop replays. A delivery can be
+//! repeated, e.g. after a chain reorganization, so receivers should key on the
+//! transaction hash and the event.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use web_time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use super::archive::Direction;
+use super::subscription::BlockCommand;
+use super::tracker::TxStatus;
+use super::watch::WatchEvent;
+use super::{NetworkError, Result};
+
+/// Header naming the event of a delivery
+pub const EVENT_HEADER: &str = "X-Mina-Wallet-Event";
+
+/// Header holding the Unix time of a delivery
+pub const TIMESTAMP_HEADER: &str = "X-Mina-Wallet-Timestamp";
+
+/// Header holding the signature of a delivery
+pub const SIGNATURE_HEADER: &str = "X-Mina-Wallet-Signature";
+
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+/// What a webhook delivery reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEventKind {
+    /// A payment to the watched address was included in a block
+    #[serde(rename = "payment.received")]
+    PaymentReceived,
+    /// A transaction of the watched address has enough confirmations
+    #[serde(rename = "transaction.confirmed")]
+    TransactionConfirmed,
+}
+
+impl WebhookEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PaymentReceived => "payment.received",
+            Self::TransactionConfirmed => "transaction.confirmed",
+        }
+    }
+}
+
+/// Body of a webhook delivery
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub event: WebhookEventKind,
+    /// The watched address
+    pub address: String,
+    pub direction: Direction,
+    pub block_height: u64,
+    pub state_hash: String,
+    /// Blocks on top of the transaction, 0 when it was just included
+    pub confirmations: u64,
+    pub transaction: BlockCommand,
+}
+
+impl WebhookPayload {
+    /// The `payment.received` payload of a watch event, if it is a payment to
+    /// `address` from another address
+    pub fn payment_received(address: &str, event: &WatchEvent) -> Option<Self> {
+        if event.direction != Direction::Incoming || event.command.kind != "PAYMENT" {
+            return None;
+        }
+        Some(Self {
+            event: WebhookEventKind::PaymentReceived,
+            address: address.to_string(),
+            direction: event.direction,
+            block_height: event.block_height,
+            state_hash: event.state_hash.clone(),
+            confirmations: 0,
+            transaction: event.command.clone(),
+        })
+    }
+
+    /// The `transaction.confirmed` payload of a watch event, if `status` is
+    /// finalized
+    ///
+    /// The block comes from `status`, as a reorganization may have moved the
+    /// transaction since it was watched.
+    pub fn transaction_confirmed(
+        address: &str,
+        event: &WatchEvent,
+        status: &TxStatus,
+    ) -> Option<Self> {
+        let TxStatus::Finalized {
+            block_height,
+            state_hash,
+            confirmations,
+        } = status
+        else {
+            return None;
+        };
+        Some(Self {
+            event: WebhookEventKind::TransactionConfirmed,
+            address: address.to_string(),
+            direction: event.direction,
+            block_height: *block_height,
+            state_hash: state_hash.clone(),
+            confirmations: *confirmations,
+            transaction: event.command.clone(),
+        })
+    }
+}
+
+/// An endpoint receiving signed webhook deliveries
+#[derive(Clone)]
+pub struct Webhook {
+    url: String,
+    secret: Zeroizing<Vec<u8>>,
+    http: reqwest::Client,
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Webhook {
+    /// Deliver to `url`, signing with `secret`
+    pub fn new(url: &str, secret: &[u8]) -> Self {
+        Self {
+            url: url.to_string(),
+            secret: Zeroizing::new(secret.to_vec()),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Value of the signature header for a body sent at `timestamp`
+    pub fn sign(&self, timestamp: u64, body: &str) -> String {
+        let mac = mac(&self.secret, timestamp, body);
+        format!(
+            "{}{}",
+            SIGNATURE_PREFIX,
+            hex::encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// POST a payload, failing unless the receiver answers with a 2xx status
+    pub async fn send(&self, payload: &WebhookPayload) -> Result<()> {
+        let body = serde_json::to_string(payload)
+            .map_err(|e| NetworkError::InvalidResponse(e.to_string()))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.http
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .header(EVENT_HEADER, payload.event.as_str())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, self.sign(timestamp, &body))
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Check the signature header of a delivery, in constant time
+pub fn verify_signature(secret: &[u8], timestamp: u64, body: &str, signature: &str) -> bool {
+    let Some(tag) = signature
+        .strip_prefix(SIGNATURE_PREFIX)
+        .and_then(|hex_tag| hex::decode(hex_tag).ok())
+    else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&tag).is_ok()
+}
+
+fn mac(secret: &[u8], timestamp: u64, body: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    fn event(direction: Direction, kind: &str) -> WatchEvent {
+        WatchEvent {
+            direction,
+            block_height: 100,
+            state_hash: "3NKa".to_string(),
+            command: BlockCommand {
+                hash: "5Ja".to_string(),
+                kind: kind.to_string(),
+                from: "B62qother".to_string(),
+                to: ADDRESS.to_string(),
+                amount: "2".parse().unwrap(),
+                fee: "0.01".parse().unwrap(),
+                nonce: 3,
+                memo: "order 42".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_payloads() {
+        let received =
+            WebhookPayload::payment_received(ADDRESS, &event(Direction::Incoming, "PAYMENT"))
+                .unwrap();
+        let json = serde_json::to_value(&received).unwrap();
+        assert_eq!(json["event"], "payment.received");
+        assert_eq!(json["address"], ADDRESS);
+        assert_eq!(json["confirmations"], 0);
+        assert_eq!(json["transaction"]["memo"], "order 42");
+        assert!(
+            WebhookPayload::payment_received(ADDRESS, &event(Direction::Outgoing, "PAYMENT"))
+                .is_none()
+        );
+        assert!(
+            WebhookPayload::payment_received(ADDRESS, &event(Direction::Incoming, "DELEGATION"))
+                .is_none()
+        );
+
+        let outgoing = event(Direction::Outgoing, "PAYMENT");
+        assert!(
+            WebhookPayload::transaction_confirmed(ADDRESS, &outgoing, &TxStatus::Pending).is_none()
+        );
+        let finalized = TxStatus::Finalized {
+            block_height: 101,
+            state_hash: "3NKb".to_string(),
+            confirmations: 15,
+        };
+        let confirmed =
+            WebhookPayload::transaction_confirmed(ADDRESS, &outgoing, &finalized).unwrap();
+        assert_eq!(confirmed.event, WebhookEventKind::TransactionConfirmed);
+        assert_eq!(confirmed.block_height, 101);
+        assert_eq!(confirmed.state_hash, "3NKb");
+        assert_eq!(confirmed.confirmations, 15);
+    }
+
+    #[test]
+    fn test_signature() {
+        let webhook = Webhook::new("https://shop.example/hooks/mina", b"whsec");
+        let body = r#"{"event":"payment.received"}"#;
+        let signature = webhook.sign(1_704_067_200, body);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), 7 + 64);
+        assert!(verify_signature(b"whsec", 1_704_067_200, body, &signature));
+
+        assert!(!verify_signature(b"other", 1_704_067_200, body, &signature));
+        assert!(!verify_signature(b"whsec", 1_704_067_201, body, &signature));
+        assert!(!verify_signature(b"whsec", 1_704_067_200, "{}", &signature));
+        assert!(!verify_signature(
+            b"whsec",
+            1_704_067_200,
+            body,
+            &signature[7..]
+        ));
+        assert!(!format!("{:?}", webhook).contains("whsec"));
+    }
+}
@@ -0,0 +1,263 @@
+//! zkApp account state
+//!
+//! A zkApp account carries, on top of its balance and nonce:
+//! - 8 field elements of on-chain state (`appState`)
+//! - the URI of its source (`zkappUri`)
+//! - the permissions that control which account updates need a proof or a signature
+//! - the hash of its verification key
+//!
+//! The daemon returns state fields as decimal strings; `ZkappField` also
+//! gives their big-endian hex form, as displayed by explorers.
+
+use ark_ff::{BigInteger, PrimeField};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+
+use super::graphql::GraphQlClient;
+use super::{NetworkError, Result};
+use crate::fields::Fp;
+
+/// Number of on-chain state fields of a zkApp account
+pub const ZKAPP_STATE_LENGTH: usize = 8;
+
+const ZKAPP_ACCOUNT_QUERY: &str = r#"query ZkappAccount($publicKey: PublicKey!) {
+  account(publicKey: $publicKey) {
+    publicKey
+    zkappState
+    zkappUri
+    provedState
+    verificationKey { hash }
+    permissions {
+      editState
+      send
+      receive
+      access
+      setDelegate
+      setPermissions
+      setVerificationKey { auth txnVersion }
+      setZkappUri
+      editActionState
+      setTokenSymbol
+      incrementNonce
+      setVotingFor
+      setTiming
+    }
+  }
+}"#;
+
+/// A field element of zkApp state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+pub struct ZkappField {
+    /// Decimal representation, as returned by the daemon
+    pub decimal: String,
+    /// Big-endian hex representation, `0x`-prefixed
+    pub hex: String,
+}
+
+impl FromStr for ZkappField {
+    type Err = NetworkError;
+
+    /// Decode a field element from its decimal representation
+    fn from_str(s: &str) -> Result<Self> {
+        let field = Fp::from_str(s)
+            .map_err(|_| NetworkError::InvalidResponse(format!("Invalid field element: {}", s)))?;
+        Ok(Self {
+            decimal: field.to_string(),
+            hex: format!("0x{}", hex::encode(field.into_bigint().to_bytes_be())),
+        })
+    }
+}
+
+/// Authorization required for an account update, e.g. `Proof` or `Signature`
+pub type AuthRequired = String;
+
+#[cfg(feature = "tsify")]
+#[wasm_bindgen::prelude::wasm_bindgen(typescript_custom_section)]
+const TS_AUTH_REQUIRED: &str = r#"/** Authorization required for an account update, e.g. "Proof" or "Signature" */
+export type AuthRequired = string;"#;
+
+/// Permission to change the verification key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationKeyPermission {
+    pub auth: AuthRequired,
+    /// Transaction version the permission applies to; after a hard fork
+    /// changing it, the key can be set with a signature
+    pub txn_version: String,
+}
+
+/// Authorizations required to update each part of the account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Permissions {
+    pub edit_state: AuthRequired,
+    pub send: AuthRequired,
+    pub receive: AuthRequired,
+    pub access: AuthRequired,
+    pub set_delegate: AuthRequired,
+    pub set_permissions: AuthRequired,
+    pub set_verification_key: VerificationKeyPermission,
+    pub set_zkapp_uri: AuthRequired,
+    pub edit_action_state: AuthRequired,
+    pub set_token_symbol: AuthRequired,
+    pub increment_nonce: AuthRequired,
+    pub set_voting_for: AuthRequired,
+    pub set_timing: AuthRequired,
+}
+
+/// zkApp part of an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct ZkappState {
+    pub address: String,
+    /// The 8 state fields; empty if no zkApp is deployed to the account
+    pub app_state: Vec<ZkappField>,
+    pub zkapp_uri: Option<String>,
+    /// Whether all state fields were last set by a proof
+    pub proved_state: bool,
+    pub verification_key_hash: Option<String>,
+    pub permissions: Option<Permissions>,
+}
+
+impl ZkappState {
+    /// Whether a zkApp is deployed to the account
+    pub fn is_zkapp(&self) -> bool {
+        self.verification_key_hash.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+struct ZkappAccountData {
+    account: Option<RawZkappAccount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawZkappAccount {
+    public_key: String,
+    zkapp_state: Option<Vec<String>>,
+    zkapp_uri: Option<String>,
+    proved_state: Option<bool>,
+    verification_key: Option<RawVerificationKey>,
+    permissions: Option<Permissions>,
+}
+
+#[derive(Deserialize)]
+struct RawVerificationKey {
+    hash: String,
+}
+
+impl TryFrom<RawZkappAccount> for ZkappState {
+    type Error = NetworkError;
+
+    fn try_from(raw: RawZkappAccount) -> Result<Self> {
+        let app_state = raw
+            .zkapp_state
+            .unwrap_or_default()
+            .iter()
+            .map(|field| field.parse())
+            .collect::<Result<Vec<ZkappField>>>()?;
+        if !app_state.is_empty() && app_state.len() != ZKAPP_STATE_LENGTH {
+            return Err(NetworkError::InvalidResponse(format!(
+                "Expected {} zkApp state fields, got {}",
+                ZKAPP_STATE_LENGTH,
+                app_state.len()
+            )));
+        }
+        Ok(Self {
+            address: raw.public_key,
+            app_state,
+            zkapp_uri: raw.zkapp_uri.filter(|uri| !uri.is_empty()),
+            proved_state: raw.proved_state.unwrap_or(false),
+            verification_key_hash: raw.verification_key.map(|vk| vk.hash),
+            permissions: raw.permissions,
+        })
+    }
+}
+
+impl GraphQlClient {
+    /// Fetch the zkApp state, URI and permissions of an account
+    ///
+    /// Fails with `NetworkError::AccountNotFound` if the account does not
+    /// exist; use `ZkappState::is_zkapp` to tell whether a zkApp is deployed.
+    pub async fn zkapp_state(&self, address: &str) -> Result<ZkappState> {
+        let data: ZkappAccountData = self
+            .query(ZKAPP_ACCOUNT_QUERY, json!({ "publicKey": address }))
+            .await?;
+        data.account
+            .ok_or_else(|| NetworkError::AccountNotFound(address.to_string()))?
+            .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_field() {
+        let field: ZkappField = "255".parse().unwrap();
+        assert_eq!(field.decimal, "255");
+        assert_eq!(field.hex, format!("0x{}ff", "0".repeat(62)));
+
+        let zero: ZkappField = "0".parse().unwrap();
+        assert_eq!(zero.hex, format!("0x{}", "0".repeat(64)));
+
+        assert!("not a number".parse::<ZkappField>().is_err());
+    }
+
+    #[test]
+    fn test_decode_zkapp_account() {
+        let data: ZkappAccountData = serde_json::from_value(json!({
+            "account": {
+                "publicKey": "B62qkzhDdtnW3sz2Sp7P6nm9xC5fYx1Ff8W7i6CQEBdRzqJ7jhPz8QK",
+                "zkappState": ["1", "2", "0", "0", "0", "0", "0", "0"],
+                "zkappUri": "https://example.com/zkapp",
+                "provedState": true,
+                "verificationKey": { "hash": "12345" },
+                "permissions": {
+                    "editState": "Proof",
+                    "send": "Signature",
+                    "receive": "None",
+                    "access": "None",
+                    "setDelegate": "Signature",
+                    "setPermissions": "Signature",
+                    "setVerificationKey": { "auth": "Signature", "txnVersion": "3" },
+                    "setZkappUri": "Signature",
+                    "editActionState": "Proof",
+                    "setTokenSymbol": "Signature",
+                    "incrementNonce": "Signature",
+                    "setVotingFor": "Signature",
+                    "setTiming": "Signature"
+                }
+            }
+        }))
+        .unwrap();
+        let state = ZkappState::try_from(data.account.unwrap()).unwrap();
+        assert!(state.is_zkapp());
+        assert_eq!(state.app_state.len(), ZKAPP_STATE_LENGTH);
+        assert_eq!(state.app_state[1].decimal, "2");
+        assert_eq!(state.permissions.unwrap().edit_state, "Proof");
+
+        // A plain account has no zkApp part
+        let data: ZkappAccountData = serde_json::from_value(json!({
+            "account": {
+                "publicKey": "B62qkzhDdtnW3sz2Sp7P6nm9xC5fYx1Ff8W7i6CQEBdRzqJ7jhPz8QK",
+                "zkappState": null,
+                "zkappUri": null,
+                "provedState": false,
+                "verificationKey": null,
+                "permissions": null
+            }
+        }))
+        .unwrap();
+        let state = ZkappState::try_from(data.account.unwrap()).unwrap();
+        assert!(!state.is_zkapp());
+        assert!(state.app_state.is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+//! Nonce management
+//!
+//! Every transaction from an account must carry the next unused nonce. When
+//! several transactions are signed before any is included in a block, the
+//! chain nonce alone is not enough: `NonceManager` remembers which nonces were
+//! already handed out, per address, and never reuses one.
+//!
+//! With the `network` feature, `NonceManager::fetch_next_nonce` syncs with a
+//! node's inferred nonce (chain nonce plus pending transactions) first.
+//!
+//! The manager can be persisted through a `Storage` backend, so that nonces
+//! handed out by one run of a program are still known to the next.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::storage::{self, Storage, StorageError};
+
+/// Storage key of the nonce manager
+pub const NONCE_STORAGE_KEY: &str = "nonces";
+
+/// Tracks the next nonce to use for each address
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NonceManager {
+    next: BTreeMap<String, u32>,
+}
+
+impl NonceManager {
+    /// Create an empty manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the manager from storage, or an empty one if none was saved
+    pub fn load(storage: &dyn Storage) -> Result<Self, StorageError> {
+        Ok(storage::load_json(storage, NONCE_STORAGE_KEY)?.unwrap_or_default())
+    }
+
+    /// Save the manager to storage
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<(), StorageError> {
+        storage::save_json(storage, NONCE_STORAGE_KEY, self)
+    }
+
+    /// Record the next nonce reported by a node for an address
+    ///
+    /// Nonces already reserved locally are kept if they are ahead of the node,
+    /// which happens while reserved transactions are not yet in its pool.
+    pub fn observe(&mut self, address: &str, next_nonce: u32) {
+        let next = self.next.entry(address.to_string()).or_insert(next_nonce);
+        *next = (*next).max(next_nonce);
+    }
+
+    /// The nonce that `reserve` would return, without reserving it
+    pub fn peek(&self, address: &str) -> Option<u32> {
+        self.next.get(address).copied()
+    }
+
+    /// Hand out the next nonce for an address, or `None` if it was never observed
+    pub fn reserve(&mut self, address: &str) -> Option<u32> {
+        let next = self.next.get_mut(address)?;
+        let nonce = *next;
+        *next = next.checked_add(1)?;
+        Some(nonce)
+    }
+
+    /// Set the next nonce of an address, even below the one already known
+    ///
+    /// This is an override for recovering from transactions that were never
+    /// included, or that were signed elsewhere.
+    pub fn set(&mut self, address: &str, next_nonce: u32) {
+        self.next.insert(address.to_string(), next_nonce);
+    }
+
+    /// Forget everything known about an address
+    pub fn reset(&mut self, address: &str) {
+        self.next.remove(address);
+    }
+}
+
+#[cfg(feature = "network")]
+impl NonceManager {
+    /// Sync an address with a node, then reserve its next nonce
+    pub async fn fetch_next_nonce(
+        &mut self,
+        client: &crate::network::GraphQlClient,
+        address: &str,
+    ) -> crate::network::Result<u32> {
+        let inferred = client.get_inferred_nonce(address).await?;
+        self.observe(address, inferred);
+        self.reserve(address).ok_or_else(|| {
+            crate::network::NetworkError::InvalidResponse("nonce overflow".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    const ADDRESS: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    #[test]
+    fn test_reserve_sequential_nonces() {
+        let mut manager = NonceManager::new();
+        assert_eq!(manager.reserve(ADDRESS), None);
+
+        manager.observe(ADDRESS, 5);
+        assert_eq!(manager.reserve(ADDRESS), Some(5));
+        assert_eq!(manager.reserve(ADDRESS), Some(6));
+        assert_eq!(manager.peek(ADDRESS), Some(7));
+    }
+
+    #[test]
+    fn test_observe_does_not_go_backwards() {
+        let mut manager = NonceManager::new();
+        manager.observe(ADDRESS, 5);
+        manager.reserve(ADDRESS);
+        // The node has not seen the reserved transaction yet
+        manager.observe(ADDRESS, 5);
+        assert_eq!(manager.peek(ADDRESS), Some(6));
+        // The node is ahead, e.g. transactions were sent from another wallet
+        manager.observe(ADDRESS, 9);
+        assert_eq!(manager.peek(ADDRESS), Some(9));
+
+        manager.reset(ADDRESS);
+        assert_eq!(manager.peek(ADDRESS), None);
+    }
+
+    #[test]
+    fn test_set_and_persist() {
+        let mut manager = NonceManager::new();
+        manager.observe(ADDRESS, 9);
+        manager.set(ADDRESS, 4);
+        assert_eq!(manager.peek(ADDRESS), Some(4));
+
+        let mut storage = MemoryStorage::new();
+        manager.save(&mut storage).unwrap();
+        let mut loaded = NonceManager::load(&storage).unwrap();
+        assert_eq!(loaded, manager);
+        assert_eq!(loaded.reserve(ADDRESS), Some(4));
+        assert_eq!(
+            NonceManager::load(&MemoryStorage::new()).unwrap(),
+            NonceManager::new()
+        );
+    }
+}
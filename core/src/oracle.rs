@@ -0,0 +1,272 @@
+//! Oracle data signing compatible with o1js
+//!
+//! zkApps that consume HTTP oracles verify responses in-circuit with
+//! `Signature.fromBase58(signature).verify(oraclePublicKey, fields)`. This
+//! module produces exactly that:
+//! - JSON values are encoded as field elements the way o1js does
+//!   (`Field(n)`, `Bool.toField()`, decimal strings)
+//! - Fields are signed with the kimchi scheme and the network prefix
+//! - Responses carry the data, the encoded fields, a Base58 signature and the public key
+
+use mina_signer::{Hashable, NetworkId, PubKey, ROInput, Signature};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::fields::Fp;
+use crate::signing::{self, SignatureError, SignatureJson, SignatureScheme};
+use crate::wallet::Wallet;
+
+/// Errors that can occur while encoding or signing oracle data
+#[derive(Error, Debug)]
+pub enum OracleError {
+    #[error("Invalid oracle data: {0}")]
+    InvalidData(String),
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+}
+
+pub type Result<T> = std::result::Result<T, OracleError>;
+
+/// An array of field elements, hashed as o1js `signFields` does
+#[derive(Debug, Clone)]
+pub struct FieldElements(pub Vec<Fp>);
+
+impl Hashable for FieldElements {
+    type D = NetworkId;
+
+    fn to_roinput(&self) -> ROInput {
+        self.0
+            .iter()
+            .fold(ROInput::new(), |roi, field| roi.append_field(*field))
+    }
+
+    fn domain_string(network_id: NetworkId) -> Option<String> {
+        signing::domain_string(network_id)
+    }
+}
+
+/// A signed oracle response, as consumed by o1js zkApps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OracleResponse {
+    /// The original data
+    pub data: Value,
+    /// The signed field elements, as decimal strings
+    pub fields: Vec<String>,
+    /// Base58 signature, for `Signature.fromBase58`
+    pub signature: String,
+    /// Address of the oracle
+    pub public_key: String,
+}
+
+impl Wallet {
+    /// Sign field elements, as o1js `Signature.create(privateKey, fields)`
+    pub fn sign_fields(&self, fields: &[Fp]) -> Signature {
+        self.sign(&FieldElements(fields.to_vec()), SignatureScheme::Kimchi)
+    }
+
+    /// Encode oracle data as fields and sign it
+    pub fn sign_oracle_data(&self, data: &Value) -> Result<OracleResponse> {
+        let fields = encode_data(data)?;
+        let signature = SignatureJson::from(&self.sign_fields(&fields));
+        Ok(OracleResponse {
+            data: data.clone(),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            signature: signature.to_base58()?,
+            public_key: self.address(),
+        })
+    }
+}
+
+/// Verify a field signature, as o1js `signature.verify(publicKey, fields)`
+pub fn verify_fields(
+    public_key: &PubKey,
+    fields: &[Fp],
+    signature: &Signature,
+    network: NetworkId,
+) -> bool {
+    signing::verify(
+        public_key,
+        &FieldElements(fields.to_vec()),
+        signature,
+        network,
+        SignatureScheme::Kimchi,
+    )
+}
+
+/// Verify a signed oracle response, re-encoding its data
+pub fn verify_oracle_response(response: &OracleResponse, network: NetworkId) -> Result<bool> {
+    let public_key = PubKey::from_address(&response.public_key)
+        .map_err(|e| OracleError::InvalidPublicKey(format!("{:?}", e)))?;
+    let fields = encode_data(&response.data)?;
+    let signature = Signature::try_from(&SignatureJson::from_base58(&response.signature)?)?;
+    Ok(verify_fields(&public_key, &fields, &signature, network))
+}
+
+/// Encode oracle data as field elements
+///
+/// Arrays and objects are flattened depth-first, object values in the order
+/// their keys appear. Scalars are encoded with `encode_value`.
+pub fn encode_data(data: &Value) -> Result<Vec<Fp>> {
+    let mut fields = Vec::new();
+    flatten(data, &mut fields)?;
+    if fields.is_empty() {
+        return Err(OracleError::InvalidData("no values to sign".to_string()));
+    }
+    Ok(fields)
+}
+
+/// Encode a single JSON scalar as a field element
+///
+/// - Integers map to `Field(n)`; negative integers wrap modulo the field order
+/// - Booleans map to `0` or `1`
+/// - Strings must be decimal integers smaller than the field order
+pub fn encode_value(value: &Value) -> Result<Fp> {
+    match value {
+        Value::Bool(b) => Ok(Fp::from(*b as u64)),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Ok(Fp::from(u))
+            } else if let Some(i) = n.as_i64() {
+                Ok(-Fp::from(i.unsigned_abs()))
+            } else {
+                Err(OracleError::InvalidData(format!(
+                    "{} is not an integer; scale decimals before signing",
+                    n
+                )))
+            }
+        }
+        Value::String(s) => parse_field(s)
+            .ok_or_else(|| OracleError::InvalidData(format!("'{}' is not a field element", s))),
+        other => Err(OracleError::InvalidData(format!(
+            "cannot encode {} as a field element",
+            other
+        ))),
+    }
+}
+
+/// Parse a canonical decimal field element, rejecting values >= the field order
+pub fn parse_field(s: &str) -> Option<Fp> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let normalized = match s.trim_start_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    };
+    let field = Fp::from_str(normalized).ok()?;
+    // `from_str` reduces modulo the field order; reject anything that wrapped
+    (field.to_string() == normalized).then_some(field)
+}
+
+fn flatten(value: &Value, out: &mut Vec<Fp>) -> Result<()> {
+    match value {
+        Value::Array(items) => items.iter().try_for_each(|v| flatten(v, out)),
+        Value::Object(map) => map.values().try_for_each(|v| flatten(v, out)),
+        scalar => {
+            out.push(encode_value(scalar)?);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_data() {
+        let fields = encode_data(&json!({ "id": 1, "creditScore": "787", "ok": true })).unwrap();
+        assert_eq!(
+            fields,
+            vec![Fp::from(1u64), Fp::from(787u64), Fp::from(1u64)]
+        );
+
+        assert_eq!(encode_value(&json!(-1)).unwrap(), -Fp::from(1u64));
+        assert!(encode_value(&json!(1.5)).is_err());
+        assert!(encode_value(&json!("abc")).is_err());
+        assert!(encode_data(&json!([])).is_err());
+    }
+
+    #[test]
+    fn test_parse_field_rejects_overflow() {
+        assert_eq!(parse_field("007"), Some(Fp::from(7u64)));
+        // The Pallas base field order itself is not a valid element
+        let order = "28948022309329048855892746252171976963363056481941560715954676764349967630337";
+        assert_eq!(parse_field(order), None);
+    }
+
+    #[test]
+    fn test_sign_and_verify_oracle_data() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let response = wallet
+            .sign_oracle_data(&json!({ "id": 1, "creditScore": 787 }))
+            .unwrap();
+        assert_eq!(response.public_key, wallet.address());
+        assert!(verify_oracle_response(&response, NetworkId::TESTNET).unwrap());
+
+        let mut tampered = response.clone();
+        tampered.data = json!({ "id": 1, "creditScore": 800 });
+        assert!(!verify_oracle_response(&tampered, NetworkId::TESTNET).unwrap());
+    }
+
+    #[test]
+    #[ignore = "needs tests/fixtures/o1js-vectors.json, from scripts/o1js-vectors.mjs"]
+    fn test_sign_oracle_data_o1js_vectors() {
+        let vectors = crate::test_vectors::o1js_vectors();
+        let vectors = vectors["oracle"].as_array().unwrap();
+        assert!(!vectors.is_empty());
+        for vector in vectors {
+            let wallet = Wallet::from_secret_key_strict(
+                vector["privateKey"].as_str().unwrap(),
+                NetworkId::TESTNET,
+            )
+            .unwrap();
+            let response = wallet.sign_oracle_data(&vector["data"]).unwrap();
+            assert_eq!(
+                serde_json::to_value(&response.fields).unwrap(),
+                vector["fields"],
+                "{}",
+                vector
+            );
+            assert_eq!(response.public_key, vector["publicKey"].as_str().unwrap());
+            assert_eq!(response.signature, vector["signature"].as_str().unwrap());
+        }
+    }
+
+    #[test]
+    #[ignore = "needs tests/fixtures/o1js-vectors.json, from scripts/o1js-vectors.mjs"]
+    fn test_sign_fields_o1js_vectors() {
+        let vectors = crate::test_vectors::o1js_vectors();
+        let vectors = vectors["signFields"].as_array().unwrap();
+        assert!(!vectors.is_empty());
+        for vector in vectors {
+            let network = match vector["network"].as_str().unwrap() {
+                "mainnet" => NetworkId::MAINNET,
+                _ => NetworkId::TESTNET,
+            };
+            let wallet =
+                Wallet::from_secret_key_strict(vector["privateKey"].as_str().unwrap(), network)
+                    .unwrap();
+            assert_eq!(wallet.address(), vector["publicKey"].as_str().unwrap());
+            let fields: Vec<Fp> = vector["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| parse_field(field.as_str().unwrap()).unwrap())
+                .collect();
+            let signature = SignatureJson::from(&wallet.sign_fields(&fields));
+            assert_eq!(
+                signature.to_base58().unwrap(),
+                vector["signature"].as_str().unwrap(),
+                "{}",
+                vector
+            );
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Paper wallet export
+//!
+//! This module produces a printable paper-wallet bundle as structured data:
+//! - The address and a QR payload for receiving funds
+//! - The Base58 secret key split into short numbered chunks for transcription
+//! - A QR payload of the secret key for restoring
+//! - Checksum words to confirm a hand-copied secret was transcribed correctly
+//!
+//! Rendering (HTML, PDF, terminal) is left to the caller.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::wallet::{Wallet, WalletInfo};
+
+/// Number of characters in each secret key chunk
+pub const CHUNK_LEN: usize = 4;
+
+/// Number of checksum words printed on a paper wallet
+pub const CHECKSUM_WORD_COUNT: usize = 4;
+
+/// A printable paper wallet
+///
+/// This contains the secret key, so it intentionally does not implement `Debug`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PaperWallet {
+    /// The Mina address
+    pub address: String,
+    /// The network the wallet was created for
+    pub network: String,
+    /// The Base58 secret key split into chunks of `CHUNK_LEN` characters
+    pub secret_chunks: Vec<String>,
+    /// QR payload for receiving funds (`mina:<address>`)
+    pub address_qr: String,
+    /// QR payload for restoring the wallet (Base58 secret key)
+    pub secret_qr: String,
+    /// Words derived from the secret key and address, used to check transcription
+    pub checksum_words: Vec<String>,
+}
+
+impl PaperWallet {
+    /// Build the paper wallet bundle for a wallet
+    pub fn new(wallet: &Wallet) -> Self {
+        let info = WalletInfo::from(wallet);
+        let secret = wallet.secret_key_base58();
+        Self {
+            address_qr: format!("mina:{}", info.address),
+            secret_chunks: chunk_secret(&secret),
+            checksum_words: checksum_words(&secret, &info.address),
+            secret_qr: secret.to_string(),
+            address: info.address,
+            network: info.network,
+        }
+    }
+}
+
+/// Split a Base58 secret key into chunks of `CHUNK_LEN` characters
+pub fn chunk_secret(secret_b58: &str) -> Vec<String> {
+    secret_b58
+        .as_bytes()
+        .chunks(CHUNK_LEN)
+        .map(|c| String::from_utf8_lossy(c).into_owned())
+        .collect()
+}
+
+/// Join transcribed chunks back into a Base58 secret key
+///
+/// Whitespace inside or around chunks is ignored.
+pub fn join_chunks<S: AsRef<str>>(chunks: &[S]) -> String {
+    chunks
+        .iter()
+        .flat_map(|c| c.as_ref().chars().filter(|ch| !ch.is_whitespace()))
+        .collect()
+}
+
+/// Derive the checksum words for a secret key and its address
+///
+/// Words are taken from the PGP word list (even positions) using the first
+/// `CHECKSUM_WORD_COUNT` bytes of `SHA-256(secret || address)`.
+pub fn checksum_words(secret_b58: &str, address: &str) -> Vec<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(secret_b58.as_bytes());
+    hasher.update(address.as_bytes());
+    let digest = hasher.finalize();
+
+    digest[..CHECKSUM_WORD_COUNT]
+        .iter()
+        .map(|&b| WORDLIST[b as usize].to_string())
+        .collect()
+}
+
+/// PGP word list, even positions (two-syllable words)
+const WORDLIST: [&str; 256] = [
+    "aardvark",
+    "absurd",
+    "accrue",
+    "acme",
+    "adrift",
+    "adult",
+    "afflict",
+    "ahead",
+    "aimless",
+    "Algol",
+    "allow",
+    "alone",
+    "ammo",
+    "ancient",
+    "apple",
+    "artist",
+    "assume",
+    "Athens",
+    "atlas",
+    "Aztec",
+    "baboon",
+    "backfield",
+    "backward",
+    "banjo",
+    "beaming",
+    "bedlamp",
+    "beehive",
+    "beeswax",
+    "befriend",
+    "Belfast",
+    "berserk",
+    "billiard",
+    "bison",
+    "blackjack",
+    "blockade",
+    "blowtorch",
+    "bluebird",
+    "bombast",
+    "bookshelf",
+    "brackish",
+    "breadline",
+    "breakup",
+    "brickyard",
+    "briefcase",
+    "Burbank",
+    "button",
+    "buzzard",
+    "cement",
+    "chairlift",
+    "chatter",
+    "checkup",
+    "chisel",
+    "choking",
+    "chopper",
+    "Christmas",
+    "clamshell",
+    "classic",
+    "classroom",
+    "cleanup",
+    "clockwork",
+    "cobra",
+    "commence",
+    "concert",
+    "cowbell",
+    "crackdown",
+    "cranky",
+    "crowfoot",
+    "crucial",
+    "crumpled",
+    "crusade",
+    "cubic",
+    "dashboard",
+    "deadbolt",
+    "deckhand",
+    "dogsled",
+    "dragnet",
+    "drainage",
+    "dreadful",
+    "drifter",
+    "dropper",
+    "drumbeat",
+    "drunken",
+    "Dupont",
+    "dwelling",
+    "eating",
+    "edict",
+    "egghead",
+    "eightball",
+    "endorse",
+    "endow",
+    "enlist",
+    "erase",
+    "escape",
+    "exceed",
+    "eyeglass",
+    "eyetooth",
+    "facial",
+    "fallout",
+    "flagpole",
+    "flatfoot",
+    "flytrap",
+    "fracture",
+    "framework",
+    "freedom",
+    "frighten",
+    "gazelle",
+    "Geiger",
+    "glitter",
+    "glucose",
+    "goggles",
+    "goldfish",
+    "gremlin",
+    "guidance",
+    "hamlet",
+    "highchair",
+    "hockey",
+    "indoors",
+    "indulge",
+    "inverse",
+    "involve",
+    "island",
+    "jawbone",
+    "keyboard",
+    "kickoff",
+    "kiwi",
+    "klaxon",
+    "locale",
+    "lockup",
+    "merit",
+    "minnow",
+    "miser",
+    "Mohawk",
+    "mural",
+    "music",
+    "necklace",
+    "Neptune",
+    "newborn",
+    "nightbird",
+    "Oakland",
+    "obtuse",
+    "offload",
+    "optic",
+    "orca",
+    "payday",
+    "peachy",
+    "pheasant",
+    "physique",
+    "playhouse",
+    "Pluto",
+    "preclude",
+    "prefer",
+    "preshrunk",
+    "printer",
+    "prowler",
+    "pupil",
+    "puppy",
+    "python",
+    "quadrant",
+    "quiver",
+    "quota",
+    "ragtime",
+    "ratchet",
+    "rebirth",
+    "reform",
+    "regain",
+    "reindeer",
+    "rematch",
+    "repay",
+    "retouch",
+    "revenge",
+    "reward",
+    "rhythm",
+    "ribcage",
+    "ringbolt",
+    "robust",
+    "rocker",
+    "ruffled",
+    "sailboat",
+    "sawdust",
+    "scallion",
+    "scenic",
+    "scorecard",
+    "Scotland",
+    "seabird",
+    "select",
+    "sentence",
+    "shadow",
+    "shamrock",
+    "showgirl",
+    "skullcap",
+    "skydive",
+    "slingshot",
+    "slowdown",
+    "snapline",
+    "snapshot",
+    "snowcap",
+    "snowslide",
+    "solo",
+    "southward",
+    "soybean",
+    "spaniel",
+    "spearhead",
+    "spellbind",
+    "spheroid",
+    "spigot",
+    "spindle",
+    "spyglass",
+    "stagehand",
+    "stagnate",
+    "stairway",
+    "standard",
+    "stapler",
+    "steamship",
+    "sterling",
+    "stockman",
+    "stopwatch",
+    "stormy",
+    "sugar",
+    "surmount",
+    "suspense",
+    "sweatband",
+    "swelter",
+    "tactics",
+    "talon",
+    "tapeworm",
+    "tempest",
+    "tiger",
+    "tissue",
+    "tonic",
+    "topmost",
+    "tracker",
+    "transit",
+    "trauma",
+    "treadmill",
+    "Trojan",
+    "trouble",
+    "tumor",
+    "tunnel",
+    "tycoon",
+    "uncut",
+    "unearth",
+    "unwind",
+    "uproot",
+    "upset",
+    "upshot",
+    "vapor",
+    "village",
+    "virus",
+    "Vulcan",
+    "waffle",
+    "wallet",
+    "watchword",
+    "wayside",
+    "willow",
+    "woodlark",
+    "Zulu",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mina_signer::NetworkId;
+
+    #[test]
+    fn test_paper_wallet() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let paper = PaperWallet::new(&wallet);
+
+        assert_eq!(paper.address, wallet.address());
+        assert_eq!(paper.network, "mainnet");
+        assert_eq!(paper.address_qr, format!("mina:{}", wallet.address()));
+        assert_eq!(paper.secret_qr, *wallet.secret_key_base58());
+        assert_eq!(paper.checksum_words.len(), CHECKSUM_WORD_COUNT);
+        assert!(paper.secret_chunks.iter().all(|c| c.len() <= CHUNK_LEN));
+    }
+
+    #[test]
+    fn test_chunks_roundtrip() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let secret = wallet.secret_key_base58();
+        let chunks = chunk_secret(&secret);
+
+        // Transcription with extra whitespace still reassembles
+        let transcribed: Vec<String> = chunks.iter().map(|c| format!(" {} ", c)).collect();
+        assert_eq!(join_chunks(&transcribed), *secret);
+        assert_eq!(
+            checksum_words(&join_chunks(&transcribed), &wallet.address()),
+            PaperWallet::new(&wallet).checksum_words
+        );
+    }
+}
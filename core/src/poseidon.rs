@@ -0,0 +1,142 @@
+//! Poseidon hashing compatible with o1js
+//!
+//! - `hash` is o1js `Poseidon.hash(fields)`: the kimchi Poseidon sponge, from
+//!   the zero state, absorbing the fields in order
+//! - `hash_with_prefix` is o1js `Poseidon.hashWithPrefix(prefix, fields)`: the
+//!   sponge starts from the state after absorbing the prefix, padded with `*`
+//!   to 20 characters, as for signatures and verification keys
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use mina_hasher::{DomainParameter, Hashable, Hasher, ROInput};
+use thiserror::Error;
+
+use crate::fields::Fp;
+
+/// Longest domain prefix, in bytes
+pub const MAX_PREFIX_LEN: usize = 20;
+
+/// Errors of Poseidon hashing
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PoseidonError {
+    #[error("Prefix longer than {MAX_PREFIX_LEN} bytes: {0}")]
+    PrefixTooLong(String),
+}
+
+pub type Result<T> = core::result::Result<T, PoseidonError>;
+
+/// Domain prefix of a hash, none for `Poseidon.hash`
+#[derive(Debug, Clone)]
+struct Prefix(Option<String>);
+
+impl DomainParameter for Prefix {
+    fn into_bytes(self) -> Vec<u8> {
+        self.0.map(String::into_bytes).unwrap_or_default()
+    }
+}
+
+/// Fields hashed as they are
+#[derive(Debug, Clone)]
+struct Fields<'a>(&'a [Fp]);
+
+impl Hashable for Fields<'_> {
+    type D = Prefix;
+
+    fn to_roinput(&self) -> ROInput {
+        self.0
+            .iter()
+            .fold(ROInput::new(), |roi, field| roi.append_field(*field))
+    }
+
+    fn domain_string(prefix: Self::D) -> Option<String> {
+        prefix.0
+    }
+}
+
+/// Hash fields as o1js `Poseidon.hash`
+pub fn hash(fields: &[Fp]) -> Fp {
+    mina_hasher::create_kimchi::<Fields>(Prefix(None)).hash(&Fields(fields))
+}
+
+/// Hash fields as o1js `Poseidon.hashWithPrefix`
+pub fn hash_with_prefix(prefix: &str, fields: &[Fp]) -> Result<Fp> {
+    if prefix.len() > MAX_PREFIX_LEN {
+        return Err(PoseidonError::PrefixTooLong(prefix.to_string()));
+    }
+    let prefix = Prefix(Some(prefix.to_string()));
+    Ok(mina_hasher::create_kimchi::<Fields>(prefix).hash(&Fields(fields)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification_key::{Commitment, VerificationKey, WRAP_INDEX_COMMITMENTS};
+    use ark_ec::AffineRepr;
+    use mina_curves::pasta::Pallas;
+
+    #[test]
+    fn test_hash() {
+        let fields = [Fp::from(1u64), Fp::from(2u64)];
+        assert_eq!(hash(&fields), hash(&fields));
+        assert_ne!(hash(&fields), hash(&[Fp::from(2u64), Fp::from(1u64)]));
+        assert_ne!(hash(&fields), hash_with_prefix("prefix", &fields).unwrap());
+        assert!(matches!(
+            hash_with_prefix("a prefix longer than twenty", &fields),
+            Err(PoseidonError::PrefixTooLong(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs tests/fixtures/o1js-vectors.json, from scripts/o1js-vectors.mjs"]
+    fn test_hash_o1js_vectors() {
+        let vectors = crate::test_vectors::o1js_vectors();
+        let vectors = vectors["poseidon"].as_array().unwrap();
+        // The empty input, one field and several, each without a prefix
+        assert!(
+            vectors
+                .iter()
+                .any(|v| v["fields"].as_array().unwrap().is_empty())
+        );
+        for vector in vectors {
+            let fields: Vec<Fp> = vector["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| crate::oracle::parse_field(field.as_str().unwrap()).unwrap())
+                .collect();
+            let hash = match vector["prefix"].as_str() {
+                Some(prefix) => hash_with_prefix(prefix, &fields).unwrap(),
+                None => hash(&fields),
+            };
+            assert_eq!(
+                hash.to_string(),
+                vector["hash"].as_str().unwrap(),
+                "{}",
+                vector
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_verification_key_hash() {
+        let generator = Pallas::generator();
+        let vk = VerificationKey {
+            max_proofs_verified: 1,
+            actual_wrap_domain_size: 2,
+            commitments: vec![
+                Commitment {
+                    x: generator.x,
+                    y: generator.y,
+                };
+                WRAP_INDEX_COMMITMENTS
+            ],
+        };
+        let mut fields: Vec<Fp> = vk.commitments.iter().flat_map(|c| [c.x, c.y]).collect();
+        // One-hot encodings of 1 and 2, packed
+        fields.push(Fp::from(0b010_001u64));
+        assert_eq!(
+            hash_with_prefix("MinaSideLoadedVk", &fields).unwrap(),
+            vk.hash()
+        );
+    }
+}
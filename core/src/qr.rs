@@ -0,0 +1,182 @@
+//! QR codes of addresses, payment URIs and signed transactions
+//!
+//! Data is encoded here once, at the same error correction level, and
+//! rendered as SVG, PNG or terminal text, so that the web wallet and the CLI
+//! show identical codes for the same payload.
+
+use std::fmt;
+use std::str::FromStr;
+
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, EcLevel, QrCode};
+use thiserror::Error;
+
+/// Error correction level of every code: 15% of the modules may be damaged
+pub const EC_LEVEL: EcLevel = EcLevel::M;
+
+/// Size of a module, in pixels for PNG and user units for SVG
+pub const MODULE_SIZE: u32 = 8;
+
+/// Width of the light border around a code, in modules
+pub const QUIET_ZONE: u32 = 4;
+
+/// Errors of QR code generation
+#[derive(Debug, Error)]
+pub enum QrError {
+    #[error("Data does not fit in a QR code: {0}")]
+    DataTooLong(String),
+    #[error("Unknown QR code format: {0} (expected svg or png)")]
+    UnknownFormat(String),
+    #[error("PNG encoding failed: {0}")]
+    Png(String),
+}
+
+pub type Result<T> = std::result::Result<T, QrError>;
+
+/// Image format of a rendered code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrFormat {
+    /// SVG document, as UTF-8 bytes
+    Svg,
+    /// Grayscale PNG image
+    Png,
+}
+
+impl QrFormat {
+    /// MIME type of the rendered bytes
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Svg => "image/svg+xml",
+            Self::Png => "image/png",
+        }
+    }
+}
+
+impl FromStr for QrFormat {
+    type Err = QrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "svg" => Ok(Self::Svg),
+            "png" => Ok(Self::Png),
+            _ => Err(QrError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for QrFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Svg => "svg",
+            Self::Png => "png",
+        })
+    }
+}
+
+/// Render `data` as a QR code image
+///
+/// # Arguments
+/// * `data` - An address, a `mina:` payment URI, a signed transaction JSON, ...
+/// * `format` - SVG or PNG
+pub fn generate_qr(data: &str, format: QrFormat) -> Result<Vec<u8>> {
+    let code = encode(data)?;
+    match format {
+        QrFormat::Svg => Ok(render_svg(&code).into_bytes()),
+        QrFormat::Png => render_png(&code),
+    }
+}
+
+/// Render `data` as a QR code of Unicode half blocks, for light text on a
+/// dark terminal
+pub fn generate_terminal_qr(data: &str) -> Result<String> {
+    let code = encode(data)?;
+    Ok(code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+fn encode(data: &str) -> Result<QrCode> {
+    QrCode::with_error_correction_level(data, EC_LEVEL)
+        .map_err(|e| QrError::DataTooLong(e.to_string()))
+}
+
+fn render_svg(code: &QrCode) -> String {
+    code.render::<svg::Color>()
+        .quiet_zone(true)
+        .module_dimensions(MODULE_SIZE, MODULE_SIZE)
+        .build()
+}
+
+fn render_png(code: &QrCode) -> Result<Vec<u8>> {
+    let modules = code.width() as u32;
+    let size = (modules + 2 * QUIET_ZONE) * MODULE_SIZE;
+    let colors = code.to_colors();
+
+    let mut pixels = vec![0xff_u8; (size * size) as usize];
+    for (index, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x = (index as u32 % modules + QUIET_ZONE) * MODULE_SIZE;
+        let y = (index as u32 / modules + QUIET_ZONE) * MODULE_SIZE;
+        for row in y..y + MODULE_SIZE {
+            let start = (row * size + x) as usize;
+            pixels[start..start + MODULE_SIZE as usize].fill(0);
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, size, size);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| QrError::Png(e.to_string()))?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| QrError::Png(e.to_string()))?;
+    writer.finish().map_err(|e| QrError::Png(e.to_string()))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "B62qnzbXmRNo9q32n4SNu2mpB8e7FYYLH8NmaX6oFCBYjjQ8SbD7uzV";
+
+    #[test]
+    fn test_generate_qr() {
+        let svg = generate_qr(ADDRESS, QrFormat::Svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("<svg"));
+
+        let png = generate_qr(ADDRESS, QrFormat::Png).unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let modules = encode(ADDRESS).unwrap().width() as u32;
+        let size = (modules + 2 * QUIET_ZONE) * MODULE_SIZE;
+        assert_eq!(reader.info().width, size);
+        assert_eq!(reader.info().height, size);
+
+        // Same data, same code
+        assert_eq!(generate_qr(ADDRESS, QrFormat::Png).unwrap(), png);
+    }
+
+    #[test]
+    fn test_qr_errors() {
+        assert_eq!("SVG".parse::<QrFormat>().unwrap(), QrFormat::Svg);
+        assert!(matches!(
+            "gif".parse::<QrFormat>(),
+            Err(QrError::UnknownFormat(_))
+        ));
+        let too_long = "x".repeat(4000);
+        assert!(matches!(
+            generate_qr(&too_long, QrFormat::Svg),
+            Err(QrError::DataTooLong(_))
+        ));
+    }
+}
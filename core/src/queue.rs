@@ -0,0 +1,368 @@
+//! Offline broadcast queue
+//!
+//! Transactions signed while offline (or while the node is unreachable) are
+//! kept in a `BroadcastQueue`, persisted through a `Storage` backend, and
+//! submitted later with `BroadcastQueue::flush` (`network` feature).
+//!
+//! Before submitting, the nonce of each queued transaction is compared with
+//! the sender's next nonce on the node:
+//! - lower: another transaction already used the nonce, the queued one can
+//!   never be included and is dropped as a conflict
+//! - higher: an earlier transaction is missing, the queued one waits
+//! - equal: the transaction is broadcast
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::storage::{self, Storage, StorageError};
+use crate::transaction::SignedTransaction;
+
+/// Storage key of the queue
+pub const QUEUE_STORAGE_KEY: &str = "broadcast_queue";
+
+/// Errors that can occur while managing the queue
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QueueError {
+    #[error("A transaction from {from} with nonce {nonce} is already queued")]
+    DuplicateNonce { from: String, nonce: u32 },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// A signed transaction waiting to be broadcast
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTransaction {
+    pub transaction: SignedTransaction,
+    /// When the transaction was queued, as a Unix timestamp in seconds
+    pub queued_at: u64,
+    /// Failed broadcast attempts so far
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+impl QueuedTransaction {
+    /// Address of the sender
+    pub fn from(&self) -> &str {
+        &self.transaction.data.from
+    }
+
+    /// Nonce of the transaction
+    pub fn nonce(&self) -> u32 {
+        self.transaction.data.nonce
+    }
+}
+
+/// What happened to a queued transaction during a flush
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(tag = "outcome", rename_all = "camelCase")]
+pub enum FlushOutcome {
+    /// Broadcast, and removed from the queue
+    Sent { hash: String },
+    /// The nonce was already used; removed from the queue
+    #[serde(rename_all = "camelCase")]
+    Conflict { next_nonce: u32 },
+    /// Kept in the queue: an earlier nonce is missing, or the node is unreachable
+    Waiting,
+    /// Rejected by the node; kept in the queue
+    Failed { error: String },
+}
+
+/// Outcome of one queued transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct FlushResult {
+    pub from: String,
+    pub nonce: u32,
+    #[serde(flatten)]
+    pub outcome: FlushOutcome,
+}
+
+/// Summary of a flush
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct FlushReport {
+    pub results: Vec<FlushResult>,
+    /// Whether the flush stopped because the node could not be reached
+    pub offline: bool,
+}
+
+/// Signed transactions waiting for connectivity
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BroadcastQueue {
+    entries: Vec<QueuedTransaction>,
+}
+
+impl BroadcastQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the queue from storage, or an empty one if none was saved
+    pub fn load(storage: &dyn Storage) -> Result<Self, QueueError> {
+        Ok(storage::load_json(storage, QUEUE_STORAGE_KEY)?.unwrap_or_default())
+    }
+
+    /// Save the queue to storage
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<(), QueueError> {
+        Ok(storage::save_json(storage, QUEUE_STORAGE_KEY, self)?)
+    }
+
+    /// Add a signed transaction, refusing a second one with the same sender and nonce
+    pub fn push(
+        &mut self,
+        transaction: SignedTransaction,
+        queued_at: u64,
+    ) -> Result<(), QueueError> {
+        let (from, nonce) = (&transaction.data.from, transaction.data.nonce);
+        if self.find(from, nonce).is_some() {
+            return Err(QueueError::DuplicateNonce {
+                from: from.clone(),
+                nonce,
+            });
+        }
+        self.entries.push(QueuedTransaction {
+            transaction,
+            queued_at,
+            attempts: 0,
+            last_error: None,
+        });
+        self.sort();
+        Ok(())
+    }
+
+    /// Remove the transaction of a sender with a given nonce
+    pub fn remove(&mut self, from: &str, nonce: u32) -> Option<QueuedTransaction> {
+        let index = self.find(from, nonce)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Queued transactions, by sender then nonce
+    pub fn transactions(&self) -> &[QueuedTransaction] {
+        &self.entries
+    }
+
+    /// Number of queued transactions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Senders with queued transactions
+    pub fn senders(&self) -> Vec<String> {
+        let mut senders: Vec<String> = self.entries.iter().map(|e| e.from().to_string()).collect();
+        senders.dedup();
+        senders
+    }
+
+    /// Decide what to do with the queued transactions of `from`, given its next nonce
+    ///
+    /// Conflicting transactions are removed. Returns the conflicts, and the
+    /// nonce of the transaction to broadcast next, if it is queued.
+    fn resolve(&mut self, from: &str, next_nonce: u32) -> (Vec<FlushResult>, Option<u32>) {
+        let mut conflicts = Vec::new();
+        self.entries.retain(|e| {
+            if e.from() == from && e.nonce() < next_nonce {
+                conflicts.push(FlushResult {
+                    from: from.to_string(),
+                    nonce: e.nonce(),
+                    outcome: FlushOutcome::Conflict { next_nonce },
+                });
+                false
+            } else {
+                true
+            }
+        });
+        let ready = self.find(from, next_nonce).map(|_| next_nonce);
+        (conflicts, ready)
+    }
+
+    fn find(&self, from: &str, nonce: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.from() == from && e.nonce() == nonce)
+    }
+
+    fn sort(&mut self) {
+        self.entries
+            .sort_by(|a, b| (a.from(), a.nonce()).cmp(&(b.from(), b.nonce())));
+    }
+
+    fn waiting(&self, from: &str) -> Vec<FlushResult> {
+        self.entries
+            .iter()
+            .filter(|e| e.from() == from)
+            .map(|e| FlushResult {
+                from: from.to_string(),
+                nonce: e.nonce(),
+                outcome: FlushOutcome::Waiting,
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "network")]
+impl BroadcastQueue {
+    /// Broadcast every queued transaction whose nonce is next for its sender
+    ///
+    /// Stops at the first transport error and reports `offline`; the queue
+    /// keeps everything that was not sent. Save it afterwards.
+    pub async fn flush(&mut self, client: &crate::network::GraphQlClient) -> FlushReport {
+        let mut report = FlushReport::default();
+        for from in self.senders() {
+            if report.offline {
+                report.results.extend(self.waiting(&from));
+                continue;
+            }
+
+            let mut next_nonce = match client.get_inferred_nonce(&from).await {
+                Ok(nonce) => nonce,
+                Err(e) => {
+                    report.offline = e.is_transient();
+                    for mut result in self.waiting(&from) {
+                        if !e.is_transient() {
+                            result.outcome = FlushOutcome::Failed {
+                                error: e.to_string(),
+                            };
+                        }
+                        report.results.push(result);
+                    }
+                    continue;
+                }
+            };
+
+            loop {
+                let (conflicts, ready) = self.resolve(&from, next_nonce);
+                report.results.extend(conflicts);
+                let Some(index) = ready.and_then(|nonce| self.find(&from, nonce)) else {
+                    break;
+                };
+
+                match client.broadcast(&self.entries[index].transaction).await {
+                    Ok(hash) => {
+                        self.entries.remove(index);
+                        report.results.push(FlushResult {
+                            from: from.clone(),
+                            nonce: next_nonce,
+                            outcome: FlushOutcome::Sent { hash },
+                        });
+                        next_nonce += 1;
+                    }
+                    Err(e) => {
+                        let entry = &mut self.entries[index];
+                        entry.attempts += 1;
+                        entry.last_error = Some(e.to_string());
+                        if e.is_transient() {
+                            report.offline = true;
+                        } else {
+                            report.results.push(FlushResult {
+                                from: from.clone(),
+                                nonce: next_nonce,
+                                outcome: FlushOutcome::Failed {
+                                    error: e.to_string(),
+                                },
+                            });
+                        }
+                        break;
+                    }
+                }
+            }
+
+            // Whatever is left for this sender waits for an earlier nonce
+            let reported: Vec<u32> = report
+                .results
+                .iter()
+                .filter(|r| r.from == from)
+                .map(|r| r.nonce)
+                .collect();
+            report.results.extend(
+                self.waiting(&from)
+                    .into_iter()
+                    .filter(|r| !reported.contains(&r.nonce)),
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::transaction::Transaction;
+    use crate::{Amount, NetworkId, Wallet};
+
+    fn signed(wallet: &Wallet, nonce: u32) -> SignedTransaction {
+        let tx = Transaction::payment(
+            &wallet.address(),
+            "B62qrQiw9JhUumq457sMxicgQ94Z1WD9JChzFu5vKLPdbmJnWx4VC3r",
+            Amount::from_nanomina(1_000_000_000),
+            Amount::from_nanomina(10_000_000),
+            nonce,
+        );
+        wallet.sign_transaction(&tx).unwrap()
+    }
+
+    #[test]
+    fn test_push_and_persist() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let mut queue = BroadcastQueue::new();
+        queue.push(signed(&wallet, 4), 100).unwrap();
+        queue.push(signed(&wallet, 3), 101).unwrap();
+        assert_eq!(
+            queue.push(signed(&wallet, 3), 102),
+            Err(QueueError::DuplicateNonce {
+                from: wallet.address(),
+                nonce: 3
+            })
+        );
+        assert_eq!(queue.transactions()[0].nonce(), 3);
+
+        let mut storage = MemoryStorage::new();
+        queue.save(&mut storage).unwrap();
+        let loaded = BroadcastQueue::load(&storage).unwrap();
+        assert_eq!(loaded, queue);
+        assert!(
+            BroadcastQueue::load(&MemoryStorage::new())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_resolve_nonce_conflicts() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let from = wallet.address();
+        let mut queue = BroadcastQueue::new();
+        for nonce in [2, 3, 5] {
+            queue.push(signed(&wallet, nonce), 0).unwrap();
+        }
+
+        // Nonce 2 was used by a transaction sent from elsewhere
+        let (conflicts, ready) = queue.resolve(&from, 3);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].outcome,
+            FlushOutcome::Conflict { next_nonce: 3 }
+        );
+        assert_eq!(ready, Some(3));
+        assert_eq!(queue.len(), 2);
+
+        // Nonce 4 is missing: 5 has to wait
+        queue.remove(&from, 3);
+        let (conflicts, ready) = queue.resolve(&from, 4);
+        assert!(conflicts.is_empty());
+        assert_eq!(ready, None);
+        assert_eq!(queue.waiting(&from)[0].outcome, FlushOutcome::Waiting);
+    }
+}
@@ -0,0 +1,218 @@
+//! Staking reward estimates
+//!
+//! A block producer wins a slot with a probability proportional to its share
+//! of the staking ledger, and about `ACTIVE_SLOT_BPS` of slots get a block.
+//! Each block earns the coinbase; a delegate keeps its fee and passes the
+//! rest on to its delegators. The expected reward of a stake is therefore:
+//!
+//! `slots per epoch * active slot rate * stake / total stake * coinbase * (1 - fee)`
+//!
+//! This is an expectation: actual rewards vary with slot luck and with the
+//! uptime of the delegate. Arithmetic is done on integers, in nanomina and
+//! basis points (1% = 100 bps).
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::amount::{Amount, NANOMINA_PER_MINA};
+use crate::consensus::{SLOT_DURATION_SECS, SLOTS_PER_EPOCH};
+
+/// Coinbase of a block (720 MINA)
+pub const COINBASE: Amount = Amount::from_nanomina(720 * NANOMINA_PER_MINA);
+
+/// Share of slots that get a block, in basis points (the consensus `f` of 0.75)
+pub const ACTIVE_SLOT_BPS: u64 = 7500;
+
+/// Basis points in 100%
+pub const BPS_PER_UNIT: u64 = 10_000;
+
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 3600;
+
+/// Errors that can occur while estimating rewards
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RewardsError {
+    #[error("Invalid percentage '{0}', expected 0 to 100 with at most 2 decimals")]
+    InvalidPercent(String),
+    #[error("Delegate fee of {0} bps is above 100%")]
+    InvalidFee(u32),
+    #[error("Total stake must be positive and at least the stake")]
+    InvalidTotalStake,
+}
+
+pub type Result<T> = std::result::Result<T, RewardsError>;
+
+/// Network parameters of an estimate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardParameters {
+    /// Total stake of the staking ledger
+    pub total_stake: Amount,
+    pub coinbase: Amount,
+    /// Whether the network doubles the coinbase for unlocked stake
+    /// (mainnet before the Berkeley upgrade)
+    pub supercharged: bool,
+    pub slots_per_epoch: u32,
+    pub active_slot_bps: u64,
+}
+
+impl RewardParameters {
+    /// Current mainnet parameters, for a given total stake
+    pub fn new(total_stake: Amount) -> Self {
+        Self {
+            total_stake,
+            coinbase: COINBASE,
+            supercharged: false,
+            slots_per_epoch: SLOTS_PER_EPOCH,
+            active_slot_bps: ACTIVE_SLOT_BPS,
+        }
+    }
+
+    /// Enable or disable supercharged coinbase
+    pub fn with_supercharged(mut self, supercharged: bool) -> Self {
+        self.supercharged = supercharged;
+        self
+    }
+}
+
+/// Expected rewards of a stake
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardEstimate {
+    pub stake: Amount,
+    /// Delegate fee, in basis points
+    pub delegate_fee_bps: u32,
+    /// Coinbase per block won by this stake
+    pub coinbase: Amount,
+    /// Blocks the stake is expected to win per epoch
+    pub expected_blocks_per_epoch: f64,
+    /// Rewards per epoch, after the delegate fee
+    pub per_epoch: Amount,
+    /// Rewards per year, after the delegate fee
+    pub per_year: Amount,
+    /// Yearly rewards relative to the stake, in basis points
+    pub yearly_yield_bps: u64,
+}
+
+/// Estimate the rewards of `stake` delegated with a fee of `delegate_fee_bps`
+///
+/// `locked` tells whether the stake is still under a vesting schedule, which
+/// forgoes the supercharged coinbase.
+pub fn estimate(
+    stake: Amount,
+    delegate_fee_bps: u32,
+    locked: bool,
+    params: &RewardParameters,
+) -> Result<RewardEstimate> {
+    if u64::from(delegate_fee_bps) > BPS_PER_UNIT {
+        return Err(RewardsError::InvalidFee(delegate_fee_bps));
+    }
+    if params.total_stake == Amount::ZERO || stake > params.total_stake {
+        return Err(RewardsError::InvalidTotalStake);
+    }
+
+    let coinbase = if params.supercharged && !locked {
+        params.coinbase.as_nanomina() * 2
+    } else {
+        params.coinbase.as_nanomina()
+    };
+
+    // Expected blocks per epoch, in basis points of a block
+    let blocks_bps = u128::from(stake.as_nanomina())
+        * u128::from(params.slots_per_epoch)
+        * u128::from(params.active_slot_bps)
+        / u128::from(params.total_stake.as_nanomina());
+    let gross = blocks_bps * u128::from(coinbase) / u128::from(BPS_PER_UNIT);
+    let per_epoch =
+        gross * u128::from(BPS_PER_UNIT - u64::from(delegate_fee_bps)) / u128::from(BPS_PER_UNIT);
+
+    let epoch_secs = u128::from(params.slots_per_epoch) * u128::from(SLOT_DURATION_SECS);
+    let per_year = per_epoch * u128::from(SECONDS_PER_YEAR) / epoch_secs;
+    let yearly_yield_bps = match stake.as_nanomina() {
+        0 => 0,
+        stake => per_year * u128::from(BPS_PER_UNIT) / u128::from(stake),
+    };
+
+    Ok(RewardEstimate {
+        stake,
+        delegate_fee_bps,
+        coinbase: Amount::from_nanomina(coinbase),
+        expected_blocks_per_epoch: blocks_bps as f64 / BPS_PER_UNIT as f64,
+        per_epoch: Amount::from_nanomina(saturate(per_epoch)),
+        per_year: Amount::from_nanomina(saturate(per_year)),
+        yearly_yield_bps: saturate(yearly_yield_bps),
+    })
+}
+
+/// Parse a percentage such as `5` or `7.25` into basis points
+pub fn parse_percent(s: &str) -> Result<u32> {
+    let invalid = || RewardsError::InvalidPercent(s.to_string());
+    let s = s.trim().trim_end_matches('%');
+    let (whole, frac) = match s.split_once('.') {
+        Some((_, "")) => return Err(invalid()),
+        Some(parts) => parts,
+        None => (s, ""),
+    };
+    let digits = |part: &str| part.bytes().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || frac.len() > 2 || !digits(whole) || !digits(frac) {
+        return Err(invalid());
+    }
+    let whole: u32 = whole.parse().map_err(|_| invalid())?;
+    let frac: u32 = format!("{:0<2}", frac).parse().map_err(|_| invalid())?;
+    let bps = whole
+        .checked_mul(100)
+        .and_then(|bps| bps.checked_add(frac))
+        .ok_or_else(invalid)?;
+    if u64::from(bps) > BPS_PER_UNIT {
+        return Err(invalid());
+    }
+    Ok(bps)
+}
+
+fn saturate(value: u128) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mina(n: u64) -> Amount {
+        Amount::from_mina(n).unwrap()
+    }
+
+    #[test]
+    fn test_estimate() {
+        // 1/7140 of the stake wins 0.75 blocks per epoch
+        let params = RewardParameters::new(mina(714_000_000));
+        let estimate = estimate(mina(100_000), 0, false, &params).unwrap();
+        assert_eq!(estimate.expected_blocks_per_epoch, 0.75);
+        assert_eq!(estimate.per_epoch, mina(540));
+
+        let with_fee = super::estimate(mina(100_000), 500, false, &params).unwrap();
+        assert_eq!(with_fee.per_epoch, mina(513));
+        assert!(with_fee.yearly_yield_bps < estimate.yearly_yield_bps);
+
+        // Supercharged coinbase only goes to unlocked stake
+        let supercharged = params.with_supercharged(true);
+        let unlocked = super::estimate(mina(100_000), 0, false, &supercharged).unwrap();
+        let locked = super::estimate(mina(100_000), 0, true, &supercharged).unwrap();
+        assert_eq!(unlocked.per_epoch, mina(1080));
+        assert_eq!(locked.per_epoch, mina(540));
+
+        assert_eq!(
+            super::estimate(mina(2), 0, false, &RewardParameters::new(mina(1))),
+            Err(RewardsError::InvalidTotalStake)
+        );
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("5"), Ok(500));
+        assert_eq!(parse_percent("7.25%"), Ok(725));
+        assert_eq!(parse_percent("0.5"), Ok(50));
+        assert_eq!(parse_percent("100"), Ok(10_000));
+        for invalid in ["", "101", "1.234", "-1", "5.", "abc"] {
+            assert!(parse_percent(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
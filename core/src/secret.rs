@@ -0,0 +1,178 @@
+//! Strict secret key parsing
+//!
+//! The lenient parsers used by the CLI and the web interface try every
+//! format in turn. Server-side users of the native crate usually want the
+//! opposite: reject anything that is not exactly a well-formed key, and say
+//! precisely why. This module provides:
+//! - Strict parsing of hex (64 characters) and Base58 (52 characters) keys
+//! - Specific error variants for whitespace, mixed case and wrong lengths
+//! - Constant-time comparison of secret material
+
+use alloc::format;
+use alloc::string::String;
+use mina_signer::SecKey;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Length of a hex-encoded secret key
+pub const SECRET_HEX_LEN: usize = 64;
+
+/// Length of a Base58-encoded secret key
+pub const SECRET_BASE58_LEN: usize = 52;
+
+/// Errors returned by the strict secret key parser
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SecretParseError {
+    #[error("secret key is empty")]
+    Empty,
+    #[error("secret key contains whitespace at position {0}")]
+    Whitespace(usize),
+    #[error("hex secret key mixes upper and lower case letters")]
+    MixedCase,
+    #[error("invalid hex secret key length: expected {len} characters, got {0}", len = SECRET_HEX_LEN)]
+    InvalidHexLength(usize),
+    #[error(
+        "invalid Base58 secret key length: expected {len} characters, got {0}",
+        len = SECRET_BASE58_LEN
+    )]
+    InvalidBase58Length(usize),
+    #[error("invalid character '{0}' at position {1}")]
+    InvalidCharacter(char, usize),
+    #[error("unrecognized secret key format: {0} characters is neither hex nor Base58")]
+    UnknownFormat(usize),
+    #[error("secret key is not valid: {0}")]
+    InvalidKey(String),
+}
+
+pub type Result<T> = core::result::Result<T, SecretParseError>;
+
+/// Encoding of a secret key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretFormat {
+    Hex,
+    Base58,
+}
+
+/// Parse a hex secret key, rejecting anything but exactly 64 hex characters
+pub fn parse_hex_strict(input: &str) -> Result<SecKey> {
+    check_common(input)?;
+    if input.len() != SECRET_HEX_LEN {
+        return Err(SecretParseError::InvalidHexLength(input.len()));
+    }
+    if let Some((i, c)) = input.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(SecretParseError::InvalidCharacter(c, i));
+    }
+    let has_upper = input.bytes().any(|b| b.is_ascii_uppercase());
+    let has_lower = input.bytes().any(|b| b.is_ascii_lowercase());
+    if has_upper && has_lower {
+        return Err(SecretParseError::MixedCase);
+    }
+
+    // The lowercase copy holds the secret too: wipe it once parsed
+    let lowercase = Zeroizing::new(input.to_ascii_lowercase());
+    SecKey::from_hex(&lowercase).map_err(|e| SecretParseError::InvalidKey(format!("{:?}", e)))
+}
+
+/// Parse a Base58 secret key, rejecting anything but exactly 52 Base58 characters
+pub fn parse_base58_strict(input: &str) -> Result<SecKey> {
+    check_common(input)?;
+    if input.len() != SECRET_BASE58_LEN {
+        return Err(SecretParseError::InvalidBase58Length(input.len()));
+    }
+    if let Some((i, c)) = input
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_alphanumeric() || matches!(c, '0' | 'O' | 'I' | 'l'))
+    {
+        return Err(SecretParseError::InvalidCharacter(c, i));
+    }
+
+    SecKey::from_base58(input).map_err(|e| SecretParseError::InvalidKey(format!("{:?}", e)))
+}
+
+/// Parse a secret key strictly, detecting the format from its length
+pub fn parse_strict(input: &str) -> Result<(SecKey, SecretFormat)> {
+    check_common(input)?;
+    match input.len() {
+        SECRET_HEX_LEN => parse_hex_strict(input).map(|k| (k, SecretFormat::Hex)),
+        SECRET_BASE58_LEN => parse_base58_strict(input).map(|k| (k, SecretFormat::Base58)),
+        len => Err(SecretParseError::UnknownFormat(len)),
+    }
+}
+
+/// Compare two secrets in constant time
+///
+/// Inputs of different lengths compare unequal; only the length is leaked.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Checks shared by every format: non-empty and free of whitespace
+fn check_common(input: &str) -> Result<()> {
+    if input.is_empty() {
+        return Err(SecretParseError::Empty);
+    }
+    if let Some((i, _)) = input.char_indices().find(|(_, c)| c.is_whitespace()) {
+        return Err(SecretParseError::Whitespace(i));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Wallet;
+    use mina_signer::NetworkId;
+
+    #[test]
+    fn test_parse_strict_roundtrip() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+
+        let (key, format) = parse_strict(&wallet.secret_key_hex()).unwrap();
+        assert_eq!(format, SecretFormat::Hex);
+        assert_eq!(key.to_hex(), *wallet.secret_key_hex());
+
+        let (key, format) = parse_strict(&wallet.secret_key_base58()).unwrap();
+        assert_eq!(format, SecretFormat::Base58);
+        assert_eq!(key.to_base58(), *wallet.secret_key_base58());
+
+        // A consistently upper-case hex key is accepted
+        let upper = wallet.secret_key_hex().to_ascii_uppercase();
+        assert!(parse_hex_strict(&upper).is_ok());
+    }
+
+    #[test]
+    fn test_parse_strict_errors() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let hex = wallet.secret_key_hex();
+
+        assert!(matches!(parse_strict(""), Err(SecretParseError::Empty)));
+        assert!(matches!(
+            parse_strict(&format!(" {}", hex.as_str())),
+            Err(SecretParseError::Whitespace(0))
+        ));
+        assert!(matches!(
+            parse_hex_strict(&hex.as_str()[..63]),
+            Err(SecretParseError::InvalidHexLength(63))
+        ));
+        assert!(matches!(
+            parse_strict(&hex.as_str()[..40]),
+            Err(SecretParseError::UnknownFormat(40))
+        ));
+
+        let mut mixed = hex.to_ascii_lowercase();
+        mixed.replace_range(0..1, "A");
+        mixed.replace_range(1..2, "b");
+        assert!(matches!(
+            parse_hex_strict(&mixed),
+            Err(SecretParseError::MixedCase)
+        ));
+    }
+
+    #[test]
+    fn test_ct_eq() {
+        assert!(ct_eq(b"secret", b"secret"));
+        assert!(!ct_eq(b"secret", b"secreT"));
+        assert!(!ct_eq(b"secret", b"secrets"));
+    }
+}
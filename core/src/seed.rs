@@ -0,0 +1,76 @@
+//! Deterministic keys from raw seed bytes
+//!
+//! Integrators that manage their own seeds (HSM, KMS) can derive any number
+//! of Mina keys from one seed, without a mnemonic nor BIP32. Key `index` is:
+//! - HKDF-SHA256 with the salt `mina-web-wallet seed v1`, the seed as input
+//!   key material and `index` as 4 big-endian bytes as info, expanded to 64
+//!   bytes
+//! - The 64 bytes, read little-endian, reduced modulo the scalar field
+//!
+//! These are not the BIP44 keys of Ledger or Auro for the same bytes.
+
+use ark_ff::PrimeField;
+use hkdf::Hkdf;
+use mina_signer::{NetworkId, SecKey};
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::fields::Fq;
+use crate::wallet::{Wallet, WalletError};
+
+/// Shortest seed accepted, in bytes
+pub const MIN_SEED_LEN: usize = 16;
+
+const HKDF_SALT: &[u8] = b"mina-web-wallet seed v1";
+
+/// Errors of seed derivation
+#[derive(Error, Debug)]
+pub enum SeedError {
+    #[error("Seed too short: {0} bytes, at least {MIN_SEED_LEN} are needed")]
+    TooShort(usize),
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+pub type Result<T> = std::result::Result<T, SeedError>;
+
+/// The wallet of key `index` of `seed`
+pub fn derive_wallet(seed: &[u8], index: u32, network: NetworkId) -> Result<Wallet> {
+    if seed.len() < MIN_SEED_LEN {
+        return Err(SeedError::TooShort(seed.len()));
+    }
+    let hkdf = Hkdf::<Sha256>::new(Some(HKDF_SALT), seed);
+    let mut okm = Zeroizing::new([0u8; 64]);
+    hkdf.expand(&index.to_be_bytes(), okm.as_mut())
+        .expect("64 bytes is a valid HKDF-SHA256 length");
+    let secret = Zeroizing::new(SecKey::new(Fq::from_le_bytes_mod_order(okm.as_ref())).to_hex());
+    Ok(Wallet::from_secret_key_hex(&secret, network)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_wallet() {
+        let seed = [7u8; 32];
+        let first = derive_wallet(&seed, 0, NetworkId::MAINNET).unwrap();
+        let again = derive_wallet(&seed, 0, NetworkId::MAINNET).unwrap();
+        assert_eq!(first.address(), again.address());
+
+        let second = derive_wallet(&seed, 1, NetworkId::MAINNET).unwrap();
+        assert_ne!(first.address(), second.address());
+        let other = derive_wallet(&[8u8; 32], 0, NetworkId::MAINNET).unwrap();
+        assert_ne!(first.address(), other.address());
+    }
+
+    #[test]
+    fn test_short_seed() {
+        assert!(matches!(
+            derive_wallet(&[0u8; 15], 0, NetworkId::MAINNET),
+            Err(SeedError::TooShort(15))
+        ));
+        assert!(derive_wallet(&[0u8; MIN_SEED_LEN], 0, NetworkId::MAINNET).is_ok());
+    }
+}
@@ -0,0 +1,215 @@
+//! Schnorr signing and verification
+//!
+//! This module is the single place where the wallet talks to the
+//! `mina-signer` signing contexts:
+//! - `SignatureScheme` selects legacy (transactions, strings) or kimchi (fields) hashing
+//! - `Wallet::sign` and `verify` work on any `Hashable` input
+//! - `SignatureJson` is the `{ field, scalar }` shape used by o1js and Auro
+
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use core::str::FromStr;
+use mina_signer::{Hashable, NetworkId, PubKey, Signature, Signer};
+use o1_utils::field_helpers::FieldHelpers;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::base58::{self, SIGNATURE_VERSION};
+use crate::fields::{Fp, Fq};
+use crate::wallet::Wallet;
+
+/// Binary format version of signatures, prepended before Base58Check encoding
+const SIGNATURE_BINARY_VERSION: u8 = 1;
+
+/// Errors that can occur while decoding a signature
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("Invalid signature field element: {0}")]
+    InvalidField(String),
+    #[error("Invalid signature scalar: {0}")]
+    InvalidScalar(String),
+    #[error("Invalid signature encoding: {0}")]
+    InvalidEncoding(String),
+}
+
+/// Poseidon variant used to hash the signed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    /// Legacy Poseidon, used for payments, delegations and string messages
+    Legacy,
+    /// Kimchi Poseidon, used for field elements (o1js `signFields`) and zkApps
+    Kimchi,
+}
+
+impl FromStr for SignatureScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(Self::Legacy),
+            "kimchi" => Ok(Self::Kimchi),
+            _ => Err(format!(
+                "Invalid signature scheme '{}'. Use 'legacy' or 'kimchi'.",
+                s
+            )),
+        }
+    }
+}
+
+/// Signature in the `{ field, scalar }` decimal form used by o1js and Auro
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+pub struct SignatureJson {
+    pub field: String,
+    pub scalar: String,
+}
+
+impl From<&Signature> for SignatureJson {
+    fn from(signature: &Signature) -> Self {
+        Self {
+            field: signature.rx.to_string(),
+            scalar: signature.s.to_string(),
+        }
+    }
+}
+
+impl TryFrom<&SignatureJson> for Signature {
+    type Error = SignatureError;
+
+    fn try_from(json: &SignatureJson) -> Result<Self, Self::Error> {
+        let rx = Fp::from_str(&json.field)
+            .map_err(|_| SignatureError::InvalidField(json.field.clone()))?;
+        let s = Fq::from_str(&json.scalar)
+            .map_err(|_| SignatureError::InvalidScalar(json.scalar.clone()))?;
+        Ok(Signature::new(rx, s))
+    }
+}
+
+impl SignatureJson {
+    /// Encode as 64 bytes of hex: `rx || s`, both little-endian
+    pub fn to_hex(&self) -> Result<String, SignatureError> {
+        let signature = Signature::try_from(self)?;
+        let mut bytes = signature.rx.to_bytes();
+        bytes.extend_from_slice(&signature.s.to_bytes());
+        Ok(hex::encode(bytes))
+    }
+
+    /// Encode in the Base58 form used by o1js `Signature.toBase58()` and the daemon
+    pub fn to_base58(&self) -> Result<String, SignatureError> {
+        let signature = Signature::try_from(self)?;
+        let mut payload = vec![SIGNATURE_BINARY_VERSION];
+        payload.extend_from_slice(&signature.rx.to_bytes());
+        payload.extend_from_slice(&signature.s.to_bytes());
+        Ok(base58::encode_check(SIGNATURE_VERSION, &payload))
+    }
+
+    /// Decode from the Base58 form produced by `to_base58`
+    pub fn from_base58(encoded: &str) -> Result<Self, SignatureError> {
+        let payload = base58::decode_check(encoded.trim(), SIGNATURE_VERSION)
+            .map_err(|e| SignatureError::InvalidEncoding(e.to_string()))?;
+        if payload.len() != 65 || payload[0] != SIGNATURE_BINARY_VERSION {
+            return Err(SignatureError::InvalidEncoding(
+                "unexpected signature payload".to_string(),
+            ));
+        }
+        Self::from_hex(&hex::encode(&payload[1..]))
+    }
+
+    /// Decode from the hex form produced by `to_hex`
+    pub fn from_hex(encoded: &str) -> Result<Self, SignatureError> {
+        let bytes = hex::decode(encoded.trim())
+            .map_err(|e| SignatureError::InvalidEncoding(e.to_string()))?;
+        if bytes.len() != 64 {
+            return Err(SignatureError::InvalidEncoding(format!(
+                "expected 64 bytes, got {}",
+                bytes.len()
+            )));
+        }
+        let rx = Fp::from_bytes(&bytes[..32])
+            .map_err(|e| SignatureError::InvalidField(format!("{:?}", e)))?;
+        let s = Fq::from_bytes(&bytes[32..])
+            .map_err(|e| SignatureError::InvalidScalar(format!("{:?}", e)))?;
+        Ok(Self::from(&Signature::new(rx, s)))
+    }
+}
+
+impl Wallet {
+    /// Sign any hashable input with this wallet's key and network
+    pub fn sign<H>(&self, input: &H, scheme: SignatureScheme) -> Signature
+    where
+        H: 'static + Hashable<D = NetworkId>,
+    {
+        match scheme {
+            SignatureScheme::Legacy => {
+                let mut ctx = mina_signer::create_legacy::<H>(self.network().clone());
+                ctx.sign(self.keypair(), input, false)
+            }
+            SignatureScheme::Kimchi => {
+                // Packed nonce derivation matches o1js for field signatures
+                let mut ctx = mina_signer::create_kimchi::<H>(self.network().clone());
+                ctx.sign(self.keypair(), input, true)
+            }
+        }
+    }
+}
+
+/// Verify a signature over any hashable input
+pub fn verify<H>(
+    public_key: &PubKey,
+    input: &H,
+    signature: &Signature,
+    network: NetworkId,
+    scheme: SignatureScheme,
+) -> bool
+where
+    H: 'static + Hashable<D = NetworkId>,
+{
+    match scheme {
+        SignatureScheme::Legacy => {
+            mina_signer::create_legacy::<H>(network).verify(signature, public_key, input)
+        }
+        SignatureScheme::Kimchi => {
+            mina_signer::create_kimchi::<H>(network).verify(signature, public_key, input)
+        }
+    }
+}
+
+/// Domain string used when hashing signed inputs, per network
+pub(crate) fn domain_string(network: NetworkId) -> Option<String> {
+    // Domain strings must have length <= 20
+    match network {
+        NetworkId::MAINNET => "MinaSignatureMainnet",
+        NetworkId::TESTNET => "CodaSignature",
+    }
+    .to_string()
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_json_hex_roundtrip() {
+        let json = SignatureJson {
+            field: "12345".to_string(),
+            scalar: "67890".to_string(),
+        };
+        let encoded = json.to_hex().unwrap();
+        assert_eq!(encoded.len(), 128);
+        assert_eq!(SignatureJson::from_hex(&encoded).unwrap(), json);
+
+        let encoded = json.to_base58().unwrap();
+        assert_eq!(SignatureJson::from_base58(&encoded).unwrap(), json);
+    }
+
+    #[test]
+    fn test_invalid_signature_json() {
+        let json = SignatureJson {
+            field: "not a number".to_string(),
+            scalar: "1".to_string(),
+        };
+        assert!(Signature::try_from(&json).is_err());
+    }
+}
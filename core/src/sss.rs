@@ -0,0 +1,316 @@
+//! Shamir secret sharing for secret key backups
+//!
+//! This module splits a secret key into `N` shares such that any `K` of them
+//! reconstruct the key, while fewer than `K` reveal nothing about it:
+//! - Arithmetic is performed byte-wise over GF(2^8) (AES polynomial)
+//! - Each share carries its index, the threshold, and a checksum
+//! - Shares are exchanged as hex strings
+
+use mina_signer::NetworkId;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::wallet::Wallet;
+
+/// Version byte prefixed to every encoded share
+const SHARE_VERSION: u8 = 1;
+
+/// Number of checksum bytes appended to every encoded share
+const CHECKSUM_LEN: usize = 4;
+
+/// Errors that can occur while splitting or recovering a secret
+#[derive(Error, Debug)]
+pub enum SssError {
+    #[error("Invalid threshold: {0}")]
+    InvalidThreshold(String),
+    #[error("Invalid share: {0}")]
+    InvalidShare(String),
+    #[error("Not enough shares: got {got}, need {needed}")]
+    NotEnoughShares { got: usize, needed: usize },
+    #[error("Recovered secret is not a valid secret key: {0}")]
+    InvalidSecret(String),
+}
+
+pub type Result<T> = std::result::Result<T, SssError>;
+
+/// A single share of a split secret
+///
+/// Share data is wiped from memory when the share is dropped.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct Share {
+    /// Evaluation point of the share (1..=255)
+    pub index: u8,
+    /// Number of shares required to recover the secret
+    pub threshold: u8,
+    /// Share payload, same length as the secret
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// Encode the share as a hex string (version, threshold, index, data, checksum)
+    pub fn to_hex(&self) -> String {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(3 + self.data.len() + CHECKSUM_LEN));
+        bytes.push(SHARE_VERSION);
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.extend_from_slice(&self.data);
+        let checksum = Sha256::digest(&*bytes);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        hex::encode(&*bytes)
+    }
+
+    /// Decode a share from its hex representation, verifying its checksum
+    pub fn from_hex(encoded: &str) -> Result<Self> {
+        let bytes = Zeroizing::new(
+            hex::decode(encoded.trim())
+                .map_err(|e| SssError::InvalidShare(format!("not valid hex: {}", e)))?,
+        );
+        if bytes.len() <= 3 + CHECKSUM_LEN {
+            return Err(SssError::InvalidShare("share is too short".to_string()));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if Sha256::digest(body)[..CHECKSUM_LEN] != *checksum {
+            return Err(SssError::InvalidShare("checksum mismatch".to_string()));
+        }
+        if body[0] != SHARE_VERSION {
+            return Err(SssError::InvalidShare(format!(
+                "unsupported version {}",
+                body[0]
+            )));
+        }
+        check_threshold(body[1])?;
+        if body[2] == 0 {
+            return Err(SssError::InvalidShare(
+                "share index must be non-zero".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            threshold: body[1],
+            index: body[2],
+            data: body[3..].to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't expose share data in debug output
+        f.debug_struct("Share")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+/// Split a secret into `count` shares, any `threshold` of which recover it
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>> {
+    check_threshold(threshold)?;
+    if count < threshold {
+        return Err(SssError::InvalidThreshold(format!(
+            "share count ({}) must be at least the threshold ({})",
+            count, threshold
+        )));
+    }
+
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share {
+            index,
+            threshold,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    // One random polynomial of degree `threshold - 1` per secret byte
+    let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize]);
+    for &byte in secret {
+        coefficients[0] = byte;
+        rand::rngs::OsRng.fill_bytes(&mut coefficients[1..]);
+        for share in shares.iter_mut() {
+            share.data.push(eval_polynomial(&coefficients, share.index));
+        }
+    }
+
+    Ok(shares)
+}
+
+/// A threshold below 2 would make every share, or none, the secret
+fn check_threshold(threshold: u8) -> Result<()> {
+    if threshold < 2 {
+        return Err(SssError::InvalidThreshold(
+            "threshold must be at least 2".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Recover a secret from at least `threshold` shares
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>> {
+    let first = shares
+        .first()
+        .ok_or(SssError::NotEnoughShares { got: 0, needed: 2 })?;
+    check_threshold(first.threshold)?;
+    let threshold = first.threshold as usize;
+    let len = first.data.len();
+
+    for (i, share) in shares.iter().enumerate() {
+        if share.threshold != first.threshold || share.data.len() != len {
+            return Err(SssError::InvalidShare(
+                "shares belong to different secrets".to_string(),
+            ));
+        }
+        if shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(SssError::InvalidShare(format!(
+                "duplicate share index {}",
+                share.index
+            )));
+        }
+    }
+    if shares.len() < threshold {
+        return Err(SssError::NotEnoughShares {
+            got: shares.len(),
+            needed: threshold,
+        });
+    }
+
+    // Lagrange interpolation at x = 0, using exactly `threshold` shares
+    let shares = &shares[..threshold];
+    let mut secret = Zeroizing::new(vec![0u8; len]);
+    for (i, share) in shares.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, other) in shares.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+            }
+        }
+        for (out, &y) in secret.iter_mut().zip(share.data.iter()) {
+            *out ^= gf_mul(basis, y);
+        }
+    }
+
+    Ok(secret)
+}
+
+/// Split the secret key of a wallet into hex-encoded shares
+pub fn split_wallet(wallet: &Wallet, threshold: u8, count: u8) -> Result<Vec<String>> {
+    let secret = Zeroizing::new(
+        hex::decode(wallet.secret_key_hex().as_bytes())
+            .map_err(|e| SssError::InvalidSecret(e.to_string()))?,
+    );
+    let shares = split(&secret, threshold, count)?;
+    Ok(shares.iter().map(Share::to_hex).collect())
+}
+
+/// Recover a wallet from hex-encoded shares
+pub fn recover_wallet<S: AsRef<str>>(shares: &[S], network: NetworkId) -> Result<Wallet> {
+    let shares = shares
+        .iter()
+        .map(|s| Share::from_hex(s.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+    let secret = combine(&shares)?;
+    let secret_hex = Zeroizing::new(hex::encode(&*secret));
+    Wallet::from_secret_key_hex(&secret_hex, network)
+        .map_err(|e| SssError::InvalidSecret(e.to_string()))
+}
+
+/// Evaluate a polynomial (lowest coefficient first) at `x` using Horner's rule
+fn eval_polynomial(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, &c| gf_mul(acc, x) ^ c)
+}
+
+/// Multiply two elements of GF(2^8) without secret-dependent branches
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Divide two elements of GF(2^8), computing `b^-1` as `b^254`
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    let mut base = b;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            inverse = gf_mul(inverse, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_combine() {
+        let secret: Vec<u8> = (0u8..32).collect();
+        let shares = split(&secret, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        // Any subset of 3 shares recovers the secret
+        assert_eq!(*combine(&shares[..3]).unwrap(), secret);
+        assert_eq!(*combine(&shares[2..]).unwrap(), secret);
+        let subset = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(*combine(&subset).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_not_enough_shares() {
+        let shares = split(&[42u8; 32], 3, 5).unwrap();
+        assert!(matches!(
+            combine(&shares[..2]),
+            Err(SssError::NotEnoughShares { got: 2, needed: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_share_hex_roundtrip() {
+        let shares = split(&[7u8; 32], 2, 3).unwrap();
+        let encoded = shares[1].to_hex();
+        assert_eq!(Share::from_hex(&encoded).unwrap(), shares[1]);
+
+        // Flipping a character breaks the checksum
+        let mut corrupted = encoded.into_bytes();
+        corrupted[10] = if corrupted[10] == b'0' { b'1' } else { b'0' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert!(Share::from_hex(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_threshold_below_two() {
+        for threshold in [0, 1] {
+            let mut share = split(&[7u8; 32], 2, 3).unwrap().remove(0);
+            share.threshold = threshold;
+            assert!(matches!(
+                Share::from_hex(&share.to_hex()),
+                Err(SssError::InvalidThreshold(_))
+            ));
+            assert!(matches!(
+                combine(&[share]),
+                Err(SssError::InvalidThreshold(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_wallet_roundtrip() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let shares = split_wallet(&wallet, 2, 3).unwrap();
+        let recovered = recover_wallet(&shares[1..], NetworkId::MAINNET).unwrap();
+        assert_eq!(recovered.address(), wallet.address());
+    }
+}
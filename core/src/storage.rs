@@ -0,0 +1,190 @@
+//! Key-value persistence for wallet state
+//!
+//! State that must survive restarts (e.g. the broadcast queue) is stored as
+//! JSON strings under fixed keys, through the `Storage` trait:
+//! - `MemoryStorage` - in memory, for tests and for front-ends that load and
+//!   save their state asynchronously (IndexedDB in the WASM module)
+//! - `FileStorage` - one file per key in a directory, for native front-ends
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while reading or writing stored state
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    #[error("Storage I/O error: {0}")]
+    Io(String),
+    #[error("Invalid stored data under '{key}': {reason}")]
+    Corrupted { key: String, reason: String },
+    #[error("Invalid storage key: {0}")]
+    InvalidKey(String),
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// A string key-value store
+pub trait Storage {
+    /// Read the value stored under `key`, if any
+    fn get(&self, key: &str) -> Result<Option<String>>;
+
+    /// Store `value` under `key`, replacing any previous value
+    fn set(&mut self, key: &str, value: &str) -> Result<()>;
+
+    /// Delete the value stored under `key`, if any
+    fn remove(&mut self, key: &str) -> Result<()>;
+}
+
+/// Read and deserialize the JSON value stored under `key`
+pub fn load_json<T: DeserializeOwned>(storage: &dyn Storage, key: &str) -> Result<Option<T>> {
+    storage
+        .get(key)?
+        .map(|text| {
+            serde_json::from_str(&text).map_err(|e| StorageError::Corrupted {
+                key: key.to_string(),
+                reason: e.to_string(),
+            })
+        })
+        .transpose()
+}
+
+/// Serialize `value` as JSON and store it under `key`
+pub fn save_json<T: Serialize>(storage: &mut dyn Storage, key: &str, value: &T) -> Result<()> {
+    let text = serde_json::to_string(value).map_err(|e| StorageError::Corrupted {
+        key: key.to_string(),
+        reason: e.to_string(),
+    })?;
+    storage.set(key, &text)
+}
+
+/// Storage kept in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    values: HashMap<String, String>,
+}
+
+impl MemoryStorage {
+    /// Create an empty storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.values.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.values.remove(key);
+        Ok(())
+    }
+}
+
+/// Storage in a directory, with one `<key>.json` file per key
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    /// Use `dir` for storage; it is created on the first write
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory files are stored in
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> Result<std::path::PathBuf> {
+        // Keys become file names: refuse anything that could escape the directory
+        let valid = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(StorageError::InvalidKey(key.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", key)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.path(key)?) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StorageError::Io(e.to_string())),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let path = self.path(key)?;
+        std::fs::create_dir_all(&self.dir).map_err(|e| StorageError::Io(e.to_string()))?;
+        // Write then rename, so a crash never leaves a truncated file
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, value).map_err(|e| StorageError::Io(e.to_string()))?;
+        std::fs::rename(&tmp, &path).map_err(|e| StorageError::Io(e.to_string()))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(key)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(StorageError::Io(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_storage_json() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(load_json::<Vec<u32>>(&storage, "numbers"), Ok(None));
+
+        save_json(&mut storage, "numbers", &vec![1u32, 2, 3]).unwrap();
+        assert_eq!(load_json(&storage, "numbers"), Ok(Some(vec![1u32, 2, 3])));
+
+        storage.set("numbers", "not json").unwrap();
+        assert!(matches!(
+            load_json::<Vec<u32>>(&storage, "numbers"),
+            Err(StorageError::Corrupted { .. })
+        ));
+
+        storage.remove("numbers").unwrap();
+        assert_eq!(storage.get("numbers"), Ok(None));
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let dir = std::env::temp_dir().join(format!("mina-storage-test-{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir);
+
+        assert_eq!(storage.get("queue"), Ok(None));
+        storage.set("queue", "[]").unwrap();
+        assert_eq!(storage.get("queue"), Ok(Some("[]".to_string())));
+        storage.remove("queue").unwrap();
+        storage.remove("queue").unwrap();
+        assert_eq!(storage.get("queue"), Ok(None));
+
+        assert!(matches!(
+            storage.set("../escape", "x"),
+            Err(StorageError::InvalidKey(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+//! Deterministic test vectors for other Mina signers
+//!
+//! Implementations in other languages (o1js, mina-signer, Go) can check that
+//! they derive the same addresses and signatures as this crate. From a seed,
+//! vector `i` holds:
+//! - A keypair whose secret key is `SHA-256(seed || i)`, `i` as 4 big-endian
+//!   bytes, read little-endian and reduced modulo the scalar field
+//! - A message signed with the scheme: a string for legacy, fields for kimchi
+//! - A payment to the address of vector `i + 1`, and a delegation to it,
+//!   signed with the scheme
+//!
+//! The secret keys are public: never send funds to their addresses.
+
+use ark_ff::PrimeField;
+use mina_signer::{NetworkId, SecKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::amount::Amount;
+use crate::fields::{Fp, Fq};
+use crate::message::Message;
+use crate::oracle::FieldElements;
+use crate::signing::{SignatureError, SignatureJson, SignatureScheme};
+use crate::transaction::{SignedTransaction, Transaction, TransactionError};
+use crate::wallet::{Wallet, WalletError, network_name};
+
+/// Errors that can occur while generating test vectors
+#[derive(Error, Debug)]
+pub enum TestVectorError {
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+}
+
+pub type Result<T> = std::result::Result<T, TestVectorError>;
+
+/// Test vectors generated from one seed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestVectors {
+    pub seed: String,
+    pub scheme: SignatureScheme,
+    /// `mainnet` or `testnet`, the network the signatures are made for
+    pub network: String,
+    pub vectors: Vec<TestVector>,
+}
+
+/// A keypair and what it signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestVector {
+    pub index: u32,
+    /// Secret key in Base58
+    pub secret_key: String,
+    pub secret_key_hex: String,
+    pub public_key: String,
+    pub message: MessageVector,
+    pub payment: SignedTransaction,
+    pub delegation: SignedTransaction,
+}
+
+/// A signed message: a string with the legacy scheme, fields with kimchi
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageVector {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Field elements, as decimal strings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    pub signature: SignatureJson,
+    /// The signature in the Base58 form of o1js `Signature.toBase58()`
+    pub signature_base58: String,
+}
+
+/// Generate `count` test vectors from `seed`
+pub fn generate(
+    seed: &str,
+    count: u32,
+    scheme: SignatureScheme,
+    network: NetworkId,
+) -> Result<TestVectors> {
+    let vectors = (0..count)
+        .map(|index| test_vector(seed, index, scheme, &network))
+        .collect::<Result<_>>()?;
+    Ok(TestVectors {
+        seed: seed.to_string(),
+        scheme,
+        network: network_name(&network).to_string(),
+        vectors,
+    })
+}
+
+/// Wallet of vector `index`
+pub fn seeded_wallet(seed: &str, index: u32, network: NetworkId) -> Result<Wallet> {
+    let digest = Sha256::new()
+        .chain_update(seed.as_bytes())
+        .chain_update(index.to_be_bytes())
+        .finalize();
+    let secret = Zeroizing::new(SecKey::new(Fq::from_le_bytes_mod_order(&digest)).to_hex());
+    Ok(Wallet::from_secret_key_hex(&secret, network)?)
+}
+
+fn test_vector(
+    seed: &str,
+    index: u32,
+    scheme: SignatureScheme,
+    network: &NetworkId,
+) -> Result<TestVector> {
+    let wallet = seeded_wallet(seed, index, network.clone())?;
+    let receiver = seeded_wallet(seed, index + 1, network.clone())?.address();
+    let n = u64::from(index);
+
+    let message = match scheme {
+        SignatureScheme::Legacy => {
+            let text = format!("Test vector {} of {}", index, seed);
+            let signature = SignatureJson::from(&wallet.sign(&Message(text.clone()), scheme));
+            MessageVector {
+                text: Some(text),
+                fields: None,
+                signature_base58: signature.to_base58()?,
+                signature,
+            }
+        }
+        SignatureScheme::Kimchi => {
+            let fields: Vec<Fp> = (0..3).map(|k| Fp::from(3 * n + k)).collect();
+            let signature =
+                SignatureJson::from(&wallet.sign(&FieldElements(fields.clone()), scheme));
+            MessageVector {
+                text: None,
+                fields: Some(fields.iter().map(|f| f.to_string()).collect()),
+                signature_base58: signature.to_base58()?,
+                signature,
+            }
+        }
+    };
+
+    let fee = Amount::from_nanomina(10_000_000 * (n + 1));
+    let payment = Transaction::payment(
+        &wallet.address(),
+        &receiver,
+        Amount::from_nanomina(1_000_000_000 * (n + 1) + n),
+        fee,
+        index,
+    )
+    .with_memo(&format!("vector {}", index))
+    .with_valid_until(if index % 2 == 0 {
+        u32::MAX
+    } else {
+        100_000 + index
+    });
+    let delegation = Transaction::delegation(&wallet.address(), &receiver, fee, index + 1);
+
+    Ok(TestVector {
+        index,
+        secret_key: wallet.secret_key_base58().to_string(),
+        secret_key_hex: wallet.secret_key_hex().to_string(),
+        public_key: wallet.address(),
+        message,
+        payment: sign(&wallet, payment, scheme)?,
+        delegation: sign(&wallet, delegation, scheme)?,
+    })
+}
+
+fn sign(
+    wallet: &Wallet,
+    transaction: Transaction,
+    scheme: SignatureScheme,
+) -> Result<SignedTransaction> {
+    let signature = wallet.sign(&transaction.to_payload()?, scheme);
+    Ok(SignedTransaction {
+        public_key: wallet.address(),
+        data: transaction,
+        signature: SignatureJson::from(&signature),
+    })
+}
+
+/// Known answers from o1js and mina-signer, written by
+/// `scripts/o1js-vectors.mjs` to `tests/fixtures/o1js-vectors.json`
+#[cfg(test)]
+pub(crate) fn o1js_vectors() -> serde_json::Value {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/o1js-vectors.json"
+    );
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("{}: {}; run scripts/o1js-vectors.mjs", path, e));
+    serde_json::from_str(&json).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{SignedMessage, verify_message};
+    use crate::oracle::verify_fields;
+    use crate::signing::verify;
+    use crate::transaction::verify_transaction;
+    use mina_signer::{PubKey, Signature};
+
+    #[test]
+    fn test_deterministic() {
+        let first = generate("seed", 3, SignatureScheme::Legacy, NetworkId::TESTNET).unwrap();
+        let again = generate("seed", 3, SignatureScheme::Legacy, NetworkId::TESTNET).unwrap();
+        assert_eq!(first, again);
+        assert_eq!(first.vectors.len(), 3);
+        assert_eq!(
+            first.vectors[1].payment.data.to,
+            first.vectors[2].public_key
+        );
+
+        let other = generate("other", 1, SignatureScheme::Legacy, NetworkId::TESTNET).unwrap();
+        assert_ne!(other.vectors[0].public_key, first.vectors[0].public_key);
+
+        let json = serde_json::to_string(&first).unwrap();
+        assert_eq!(serde_json::from_str::<TestVectors>(&json).unwrap(), first);
+    }
+
+    #[test]
+    fn test_signatures_verify() {
+        let legacy = generate("seed", 2, SignatureScheme::Legacy, NetworkId::MAINNET).unwrap();
+        for vector in &legacy.vectors {
+            assert!(verify_transaction(&vector.payment, NetworkId::MAINNET).unwrap());
+            assert!(verify_transaction(&vector.delegation, NetworkId::MAINNET).unwrap());
+            let message = SignedMessage {
+                public_key: vector.public_key.clone(),
+                data: vector.message.text.clone().unwrap(),
+                signature: vector.message.signature.clone(),
+            };
+            assert!(verify_message(&message, NetworkId::MAINNET).unwrap());
+        }
+
+        let kimchi = generate("seed", 2, SignatureScheme::Kimchi, NetworkId::MAINNET).unwrap();
+        // Same keys, different hashing
+        assert_eq!(kimchi.vectors[0].public_key, legacy.vectors[0].public_key);
+        assert_ne!(
+            kimchi.vectors[0].payment.signature,
+            legacy.vectors[0].payment.signature
+        );
+        let vector = &kimchi.vectors[1];
+        let public_key = PubKey::from_address(&vector.public_key).unwrap();
+        let signature = Signature::try_from(&vector.payment.signature).unwrap();
+        assert!(verify(
+            &public_key,
+            &vector.payment.data.to_payload().unwrap(),
+            &signature,
+            NetworkId::MAINNET,
+            SignatureScheme::Kimchi,
+        ));
+        let fields: Vec<Fp> = (3..6u64).map(Fp::from).collect();
+        let signature = Signature::try_from(&vector.message.signature).unwrap();
+        assert!(verify_fields(
+            &public_key,
+            &fields,
+            &signature,
+            NetworkId::MAINNET
+        ));
+    }
+}
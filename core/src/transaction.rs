@@ -0,0 +1,616 @@
+//! Payments and stake delegations
+//!
+//! Transactions use the same JSON shape as the `mina-signer` JavaScript
+//! package: a payment has an `amount`, a delegation does not. They are hashed
+//! with the legacy scheme, in the field order of the daemon's legacy
+//! `Signed_command_payload` input:
+//! - common: fee payer, source and receiver x-coordinates, fee, fee token,
+//!   fee payer parity, nonce, valid until, memo
+//! - body: tag bits, source and receiver parities, token id, amount, token locked
+
+use mina_signer::{CompressedPubKey, Hashable, NetworkId, PubKey, ROInput, Signature};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::amount::Amount;
+use crate::base58::{self, MEMO_VERSION};
+use crate::signing::{self, SignatureError, SignatureJson, SignatureScheme};
+use crate::wallet::Wallet;
+
+/// Length of an encoded memo, in bytes
+pub const MEMO_BYTES: usize = 34;
+
+/// Maximum length of a memo, in bytes
+pub const MAX_MEMO_LEN: usize = MEMO_BYTES - 2;
+
+/// Smallest fee accepted by the daemon (0.001 MINA)
+pub const MINIMUM_FEE: Amount = Amount::from_nanomina(1_000_000);
+
+/// Fee used when none is specified (0.1 MINA)
+pub const DEFAULT_FEE: Amount = Amount::from_nanomina(100_000_000);
+
+/// Deducted from a payment that creates the receiver's account (1 MINA)
+pub const ACCOUNT_CREATION_FEE: Amount = Amount::from_nanomina(1_000_000_000);
+
+/// Payments above this amount are flagged for a second look (10,000 MINA)
+pub const HIGH_AMOUNT: Amount = Amount::from_nanomina(10_000_000_000_000);
+
+/// Default token id, for MINA
+const DEFAULT_TOKEN_ID: u64 = 1;
+
+/// Tag bits of a payment
+const PAYMENT_TAG: [bool; 3] = [false, false, false];
+
+/// Tag bits of a stake delegation
+const DELEGATION_TAG: [bool; 3] = [false, false, true];
+
+/// Errors that can occur while building, signing or verifying a transaction
+#[derive(Error, Debug)]
+pub enum TransactionError {
+    #[error("Invalid {field} address: {reason}")]
+    InvalidAddress { field: &'static str, reason: String },
+    #[error("Memo is {0} bytes long, the maximum is {MAX_MEMO_LEN}")]
+    MemoTooLong(usize),
+    #[error("Fee {0} MINA is below the minimum of {MINIMUM_FEE} MINA")]
+    FeeTooLow(Amount),
+    #[error("Transaction is from {from}, but the wallet address is {wallet}")]
+    SenderMismatch { from: String, wallet: String },
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+}
+
+pub type Result<T> = std::result::Result<T, TransactionError>;
+
+impl TransactionError {
+    /// Field of the transaction the error is about, as named in JSON
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidAddress { field, .. } => Some(*field),
+            Self::MemoTooLong(_) => Some("memo"),
+            Self::FeeTooLow(_) => Some("fee"),
+            Self::SenderMismatch { .. } => Some("from"),
+            Self::Signature(_) => Some("signature"),
+        }
+    }
+}
+
+/// Kind of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    Payment,
+    Delegation,
+}
+
+/// An unsigned payment or stake delegation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    /// Receiver of the payment, or new delegate
+    pub to: String,
+    /// Sender and fee payer
+    pub from: String,
+    pub fee: Amount,
+    /// Amount sent; absent for delegations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    pub nonce: u32,
+    #[serde(default)]
+    pub memo: String,
+    /// Last global slot at which the transaction can be included
+    #[serde(default = "default_valid_until")]
+    pub valid_until: u32,
+}
+
+fn default_valid_until() -> u32 {
+    u32::MAX
+}
+
+/// A transaction with its signature, as returned by `mina-signer`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct SignedTransaction {
+    /// Address of the signer
+    pub public_key: String,
+    /// The signed transaction
+    pub data: Transaction,
+    /// The signature
+    pub signature: SignatureJson,
+}
+
+/// What a payment costs the sender and credits the receiver
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tsify", derive(tsify::Tsify))]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentPreview {
+    pub amount: Amount,
+    pub fee: Amount,
+    /// Amount plus fee, debited from the sender
+    pub total_cost: Amount,
+    /// Whether the payment creates the receiver's account; `None` if unknown
+    pub creates_account: Option<bool>,
+    /// Fee taken from the amount when the account is created
+    pub account_creation_fee: Amount,
+    /// Amount credited to the receiver; `None` if it does not cover the
+    /// account creation fee, in which case the payment fails
+    pub credited: Option<Amount>,
+}
+
+/// Something worth a second look before signing or broadcasting a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TransactionWarning {
+    /// The fee is below `MINIMUM_FEE`, so nodes reject the transaction
+    FeeTooLow { fee: Amount },
+    /// The amount is above `HIGH_AMOUNT`
+    HighAmount { amount: Amount },
+    /// The payment creates the receiver's account; `credited` is `None` if
+    /// the amount does not cover the account creation fee
+    NewAccount { credited: Option<Amount> },
+    /// The payment is sent to its own sender
+    SelfTransfer,
+    /// The last slot at which the transaction could be included has passed
+    #[serde(rename_all = "camelCase")]
+    Expired { valid_until: u32, current_slot: u32 },
+}
+
+impl TransactionWarning {
+    /// Explanation of the warning
+    pub fn message(&self) -> String {
+        match self {
+            TransactionWarning::FeeTooLow { fee } => format!(
+                "The fee of {} MINA is below the minimum of {} MINA: nodes will reject the transaction",
+                fee, MINIMUM_FEE
+            ),
+            TransactionWarning::HighAmount { amount } => format!(
+                "The amount of {} MINA is unusually high: check it twice",
+                amount
+            ),
+            TransactionWarning::NewAccount {
+                credited: Some(credited),
+            } => format!(
+                "The receiver account does not exist yet: {} MINA of the amount pays the account creation fee, {} MINA will be credited",
+                ACCOUNT_CREATION_FEE, credited
+            ),
+            TransactionWarning::NewAccount { credited: None } => format!(
+                "The receiver account does not exist yet, and the amount does not cover the {} MINA account creation fee: the payment will fail",
+                ACCOUNT_CREATION_FEE
+            ),
+            TransactionWarning::SelfTransfer => "The payment is sent to its own sender".to_string(),
+            TransactionWarning::Expired {
+                valid_until,
+                current_slot,
+            } => format!(
+                "The transaction expired at slot {} and the current slot is {}: it can no longer be included",
+                valid_until, current_slot
+            ),
+        }
+    }
+}
+
+/// Transaction fields in the form they are hashed in
+#[derive(Debug, Clone)]
+pub(crate) struct LegacyPayload {
+    fee: u64,
+    fee_payer_pk: CompressedPubKey,
+    nonce: u32,
+    valid_until: u32,
+    memo: [u8; MEMO_BYTES],
+    tag: [bool; 3],
+    receiver_pk: CompressedPubKey,
+    amount: u64,
+}
+
+impl Hashable for LegacyPayload {
+    type D = NetworkId;
+
+    fn to_roinput(&self) -> ROInput {
+        // The source is always the fee payer
+        let mut roi = ROInput::new()
+            .append_field(self.fee_payer_pk.x)
+            .append_field(self.fee_payer_pk.x)
+            .append_field(self.receiver_pk.x)
+            .append_u64(self.fee)
+            .append_u64(DEFAULT_TOKEN_ID)
+            .append_bool(self.fee_payer_pk.is_odd)
+            .append_u32(self.nonce)
+            .append_u32(self.valid_until)
+            .append_bytes(&self.memo);
+        for bit in self.tag {
+            roi = roi.append_bool(bit);
+        }
+        roi.append_bool(self.fee_payer_pk.is_odd)
+            .append_bool(self.receiver_pk.is_odd)
+            .append_u64(DEFAULT_TOKEN_ID)
+            .append_u64(self.amount)
+            .append_bool(false)
+    }
+
+    fn domain_string(network_id: NetworkId) -> Option<String> {
+        signing::domain_string(network_id)
+    }
+}
+
+impl Transaction {
+    /// Create a payment of `amount` from `from` to `to`
+    pub fn payment(from: &str, to: &str, amount: Amount, fee: Amount, nonce: u32) -> Self {
+        Self {
+            to: to.to_string(),
+            from: from.to_string(),
+            fee,
+            amount: Some(amount),
+            nonce,
+            memo: String::new(),
+            valid_until: u32::MAX,
+        }
+    }
+
+    /// Create a delegation of the stake of `from` to `to`
+    pub fn delegation(from: &str, to: &str, fee: Amount, nonce: u32) -> Self {
+        Self {
+            to: to.to_string(),
+            from: from.to_string(),
+            fee,
+            amount: None,
+            nonce,
+            memo: String::new(),
+            valid_until: u32::MAX,
+        }
+    }
+
+    /// Set the memo
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.memo = memo.to_string();
+        self
+    }
+
+    /// Set the last global slot at which the transaction can be included
+    pub fn with_valid_until(mut self, valid_until: u32) -> Self {
+        self.valid_until = valid_until;
+        self
+    }
+
+    /// Whether this is a payment or a delegation
+    pub fn kind(&self) -> TransactionKind {
+        match self.amount {
+            Some(_) => TransactionKind::Payment,
+            None => TransactionKind::Delegation,
+        }
+    }
+
+    /// Summarize the cost of a payment, given whether the receiver account
+    /// is new (`None` if unknown)
+    ///
+    /// Delegations are previewed as payments of 0.
+    pub fn preview(&self, creates_account: Option<bool>) -> PaymentPreview {
+        let amount = self.amount.unwrap_or(Amount::ZERO);
+        let account_creation_fee = match creates_account {
+            Some(true) => ACCOUNT_CREATION_FEE,
+            _ => Amount::ZERO,
+        };
+        PaymentPreview {
+            amount,
+            fee: self.fee,
+            total_cost: Amount::from_nanomina(
+                amount.as_nanomina().saturating_add(self.fee.as_nanomina()),
+            ),
+            creates_account,
+            account_creation_fee,
+            credited: amount.checked_sub(account_creation_fee),
+        }
+    }
+
+    /// Warnings about the transaction, given whether it creates the receiver
+    /// account and the current global slot (`None` when unknown)
+    pub fn warnings(
+        &self,
+        creates_account: Option<bool>,
+        current_slot: Option<u32>,
+    ) -> Vec<TransactionWarning> {
+        let mut warnings = Vec::new();
+        if self.fee < MINIMUM_FEE {
+            warnings.push(TransactionWarning::FeeTooLow { fee: self.fee });
+        }
+        // Delegating to oneself is common; paying oneself is not
+        if let Some(amount) = self.amount {
+            if amount > HIGH_AMOUNT {
+                warnings.push(TransactionWarning::HighAmount { amount });
+            }
+            if self.from == self.to {
+                warnings.push(TransactionWarning::SelfTransfer);
+            }
+            if creates_account == Some(true) {
+                warnings.push(TransactionWarning::NewAccount {
+                    credited: self.preview(creates_account).credited,
+                });
+            }
+        }
+        if let Some(current_slot) = current_slot
+            && current_slot > self.valid_until
+        {
+            warnings.push(TransactionWarning::Expired {
+                valid_until: self.valid_until,
+                current_slot,
+            });
+        }
+        warnings
+    }
+
+    /// Check addresses, memo length and fee
+    pub fn validate(&self) -> Result<()> {
+        self.to_payload().map(|_| ())
+    }
+
+    /// Every error `validate` can find, instead of the first one, so that
+    /// forms can flag all invalid fields at once
+    pub fn field_errors(&self) -> Vec<TransactionError> {
+        let mut errors = Vec::new();
+        if self.fee < MINIMUM_FEE {
+            errors.push(TransactionError::FeeTooLow(self.fee));
+        }
+        if let Err(e) = parse_address("from", &self.from) {
+            errors.push(e);
+        }
+        if let Err(e) = parse_address("to", &self.to) {
+            errors.push(e);
+        }
+        if let Err(e) = encode_memo(&self.memo) {
+            errors.push(e);
+        }
+        errors
+    }
+
+    pub(crate) fn to_payload(&self) -> Result<LegacyPayload> {
+        if self.fee < MINIMUM_FEE {
+            return Err(TransactionError::FeeTooLow(self.fee));
+        }
+        let (tag, amount) = match self.amount {
+            Some(amount) => (PAYMENT_TAG, amount.as_nanomina()),
+            None => (DELEGATION_TAG, 0),
+        };
+        Ok(LegacyPayload {
+            fee: self.fee.as_nanomina(),
+            fee_payer_pk: parse_address("from", &self.from)?,
+            nonce: self.nonce,
+            valid_until: self.valid_until,
+            memo: encode_memo(&self.memo)?,
+            tag,
+            receiver_pk: parse_address("to", &self.to)?,
+            amount,
+        })
+    }
+}
+
+impl Wallet {
+    /// Sign a payment or delegation sent from this wallet
+    pub fn sign_transaction(&self, transaction: &Transaction) -> Result<SignedTransaction> {
+        if transaction.from != self.address() {
+            return Err(TransactionError::SenderMismatch {
+                from: transaction.from.clone(),
+                wallet: self.address(),
+            });
+        }
+        let payload = transaction.to_payload()?;
+        let signature = self.sign(&payload, SignatureScheme::Legacy);
+        Ok(SignedTransaction {
+            public_key: self.address(),
+            data: transaction.clone(),
+            signature: SignatureJson::from(&signature),
+        })
+    }
+}
+
+/// Verify a signed transaction against its sender
+pub fn verify_transaction(signed: &SignedTransaction, network: NetworkId) -> Result<bool> {
+    let public_key =
+        PubKey::from_address(&signed.data.from).map_err(|e| TransactionError::InvalidAddress {
+            field: "from",
+            reason: format!("{:?}", e),
+        })?;
+    let payload = signed.data.to_payload()?;
+    let signature = Signature::try_from(&signed.signature)?;
+    Ok(signing::verify(
+        &public_key,
+        &payload,
+        &signature,
+        network,
+        SignatureScheme::Legacy,
+    ))
+}
+
+/// Encode a memo as `0x01 || length || bytes`, zero padded to `MEMO_BYTES`
+pub fn encode_memo(memo: &str) -> Result<[u8; MEMO_BYTES]> {
+    let bytes = memo.as_bytes();
+    if bytes.len() > MAX_MEMO_LEN {
+        return Err(TransactionError::MemoTooLong(bytes.len()));
+    }
+    let mut encoded = [0u8; MEMO_BYTES];
+    encoded[0] = 0x01;
+    encoded[1] = bytes.len() as u8;
+    encoded[2..2 + bytes.len()].copy_from_slice(bytes);
+    Ok(encoded)
+}
+
+/// Encode a memo in the Base58 form used by the daemon and explorers
+pub fn encode_memo_base58(memo: &str) -> Result<String> {
+    Ok(base58::encode_check(MEMO_VERSION, &encode_memo(memo)?))
+}
+
+/// Decode a Base58 memo back to its text, or `None` if it is malformed
+///
+/// Non-UTF-8 memo bytes are replaced with U+FFFD.
+pub fn decode_memo_base58(encoded: &str) -> Option<String> {
+    let bytes = base58::decode_check(encoded, MEMO_VERSION).ok()?;
+    if bytes.len() != MEMO_BYTES || bytes[0] != 0x01 {
+        return None;
+    }
+    let len = usize::from(bytes[1]);
+    let text = bytes.get(2..2 + len)?;
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+fn parse_address(field: &'static str, address: &str) -> Result<CompressedPubKey> {
+    CompressedPubKey::from_address(address).map_err(|e| TransactionError::InvalidAddress {
+        field,
+        reason: format!("{:?}", e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECEIVER: &str = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+
+    #[test]
+    fn test_sign_and_verify_payment() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address(),
+            RECEIVER,
+            "1.5".parse().unwrap(),
+            DEFAULT_FEE,
+            0,
+        )
+        .with_memo("hello");
+        let signed = wallet.sign_transaction(&payment).unwrap();
+        assert!(verify_transaction(&signed, NetworkId::TESTNET).unwrap());
+        assert!(!verify_transaction(&signed, NetworkId::MAINNET).unwrap());
+
+        let mut tampered = signed.clone();
+        tampered.data.nonce = 1;
+        assert!(!verify_transaction(&tampered, NetworkId::TESTNET).unwrap());
+    }
+
+    #[test]
+    fn test_delegation_json_shape() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let delegation = Transaction::delegation(&wallet.address(), RECEIVER, DEFAULT_FEE, 3);
+        assert_eq!(delegation.kind(), TransactionKind::Delegation);
+
+        let signed = wallet.sign_transaction(&delegation).unwrap();
+        let json = serde_json::to_value(&signed).unwrap();
+        assert!(json["data"].get("amount").is_none());
+        assert_eq!(json["data"]["validUntil"], u32::MAX);
+        assert!(verify_transaction(&signed, NetworkId::TESTNET).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_transactions() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let payment = |fee, memo: &str| {
+            Transaction::payment(&wallet.address(), RECEIVER, Amount::ZERO, fee, 0).with_memo(memo)
+        };
+
+        assert!(matches!(
+            payment(Amount::ZERO, "").validate(),
+            Err(TransactionError::FeeTooLow(_))
+        ));
+        assert!(matches!(
+            payment(DEFAULT_FEE, &"x".repeat(33)).validate(),
+            Err(TransactionError::MemoTooLong(33))
+        ));
+        assert!(payment(DEFAULT_FEE, &"x".repeat(32)).validate().is_ok());
+        assert!(payment(DEFAULT_FEE, "").field_errors().is_empty());
+
+        let mut invalid = payment(Amount::ZERO, &"x".repeat(33));
+        invalid.to = "B62qnot".to_string();
+        let fields: Vec<_> = invalid
+            .field_errors()
+            .iter()
+            .filter_map(|e| e.field())
+            .collect();
+        assert_eq!(fields, ["fee", "to", "memo"]);
+
+        let other = Transaction::payment(RECEIVER, RECEIVER, Amount::ZERO, DEFAULT_FEE, 0);
+        assert!(matches!(
+            wallet.sign_transaction(&other),
+            Err(TransactionError::SenderMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_memo() {
+        let memo = encode_memo("hello").unwrap();
+        assert_eq!(&memo[..7], &[0x01, 5, b'h', b'e', b'l', b'l', b'o']);
+        assert!(memo[7..].iter().all(|&b| b == 0));
+
+        // Empty memo as shown by explorers
+        assert_eq!(
+            encode_memo_base58("").unwrap(),
+            "E4YM2vTHhWEg66xpj52JErHUBU4pZ1yageL4TVDDpTTSsv8mK6YaH"
+        );
+        let encoded = encode_memo_base58("hello").unwrap();
+        assert_eq!(decode_memo_base58(&encoded).as_deref(), Some("hello"));
+        assert_eq!(decode_memo_base58("hello"), None);
+    }
+
+    #[test]
+    fn test_payment_preview() {
+        let payment =
+            Transaction::payment(RECEIVER, RECEIVER, "1.5".parse().unwrap(), DEFAULT_FEE, 0);
+
+        let preview = payment.preview(Some(true));
+        assert_eq!(preview.total_cost, "1.6".parse().unwrap());
+        assert_eq!(preview.account_creation_fee, ACCOUNT_CREATION_FEE);
+        assert_eq!(preview.credited, Some("0.5".parse().unwrap()));
+
+        assert_eq!(payment.preview(None).credited, Some(preview.amount));
+        assert_eq!(
+            payment.preview(Some(false)).account_creation_fee,
+            Amount::ZERO
+        );
+
+        let small =
+            Transaction::payment(RECEIVER, RECEIVER, "0.5".parse().unwrap(), DEFAULT_FEE, 0);
+        assert_eq!(small.preview(Some(true)).credited, None);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let sender = Wallet::new(NetworkId::TESTNET).unwrap().address();
+        let payment =
+            Transaction::payment(&sender, RECEIVER, "1.5".parse().unwrap(), DEFAULT_FEE, 0)
+                .with_valid_until(100);
+        assert!(payment.warnings(Some(false), Some(100)).is_empty());
+        assert_eq!(
+            payment.warnings(Some(true), Some(101)),
+            vec![
+                TransactionWarning::NewAccount {
+                    credited: Some("0.5".parse().unwrap())
+                },
+                TransactionWarning::Expired {
+                    valid_until: 100,
+                    current_slot: 101
+                },
+            ]
+        );
+
+        let careless = Transaction::payment(
+            RECEIVER,
+            RECEIVER,
+            "20000".parse().unwrap(),
+            Amount::ZERO,
+            0,
+        );
+        let warnings = careless.warnings(None, None);
+        assert_eq!(
+            warnings,
+            vec![
+                TransactionWarning::FeeTooLow { fee: Amount::ZERO },
+                TransactionWarning::HighAmount {
+                    amount: "20000".parse().unwrap()
+                },
+                TransactionWarning::SelfTransfer,
+            ]
+        );
+        assert!(warnings[0].message().contains("0.001 MINA"));
+
+        // Delegating to oneself is not worth a warning
+        let delegation = Transaction::delegation(RECEIVER, RECEIVER, DEFAULT_FEE, 0);
+        assert!(delegation.warnings(None, None).is_empty());
+    }
+}
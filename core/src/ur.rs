@@ -0,0 +1,382 @@
+//! Uniform Resources (BC-UR) for QR codes across an air gap
+//!
+//! A transaction envelope is too large for one QR code that a camera reads
+//! reliably. UR (Blockchain Commons BCR-2020-005) splits it into parts that
+//! are shown one after the other as an animated QR code:
+//! - `ur:mina-tx/<bytewords>` when the payload fits in one part
+//! - `ur:mina-tx/<seq>-<count>/<bytewords>` for part `seq` of `count`
+//!
+//! The payload is the envelope JSON as a CBOR byte string, in minimal
+//! bytewords (two letters a byte, CRC32 appended). Parts are the "pure"
+//! fragments of the UR fountain encoder, which other UR decoders read as is;
+//! fountain-mixed parts, past `count`, are neither produced nor decoded.
+
+use thiserror::Error;
+
+/// UR type of transaction envelopes
+pub const UR_TYPE: &str = "mina-tx";
+
+/// Fragment length that keeps each QR code easy to scan
+pub const DEFAULT_FRAGMENT_LEN: usize = 200;
+
+/// The 256 bytewords, in byte order
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// CBOR major types used by UR
+const CBOR_UNSIGNED: u8 = 0;
+const CBOR_BYTES: u8 = 2;
+const CBOR_ARRAY: u8 = 4;
+
+/// Errors that can occur while decoding a UR
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum UrError {
+    #[error("Not a UR: {0}")]
+    InvalidUr(String),
+    #[error("Expected a ur:{UR_TYPE}, got ur:{0}")]
+    UnexpectedType(String),
+    #[error("Invalid bytewords: {0}")]
+    InvalidBytewords(String),
+    #[error("Checksum mismatch: the QR code was misread")]
+    BadChecksum,
+    #[error("Invalid CBOR: {0}")]
+    InvalidCbor(String),
+    #[error("Part {0} does not belong to the same payload as the previous parts")]
+    InconsistentPart(u32),
+    #[error("Part {0} is fountain-coded, which is not supported: keep scanning")]
+    UnsupportedPart(u32),
+}
+
+pub type Result<T> = std::result::Result<T, UrError>;
+
+/// Encode `payload` as UR parts of at most `max_fragment_len` bytes each
+///
+/// A single part has no sequence; several parts are meant to be shown in a
+/// loop until the other side has read all of them.
+pub fn encode(payload: &[u8], max_fragment_len: usize) -> Vec<String> {
+    let message = cbor_bytes(payload);
+    let max_fragment_len = max_fragment_len.max(1);
+    if message.len() <= max_fragment_len {
+        return vec![format!("ur:{}/{}", UR_TYPE, bytewords(&message))];
+    }
+
+    let fragment_len = message
+        .len()
+        .div_ceil(message.len().div_ceil(max_fragment_len));
+    let count = message.len().div_ceil(fragment_len);
+    let checksum = crc32(&message);
+    message
+        .chunks(fragment_len)
+        .enumerate()
+        .map(|(index, fragment)| {
+            let mut fragment = fragment.to_vec();
+            fragment.resize(fragment_len, 0);
+
+            let mut part = Vec::new();
+            cbor_head(&mut part, CBOR_ARRAY, 5);
+            cbor_head(&mut part, CBOR_UNSIGNED, index as u64 + 1);
+            cbor_head(&mut part, CBOR_UNSIGNED, count as u64);
+            cbor_head(&mut part, CBOR_UNSIGNED, message.len() as u64);
+            cbor_head(&mut part, CBOR_UNSIGNED, u64::from(checksum));
+            part.extend(cbor_bytes(&fragment));
+            format!(
+                "ur:{}/{}-{}/{}",
+                UR_TYPE,
+                index + 1,
+                count,
+                bytewords(&part)
+            )
+        })
+        .collect()
+}
+
+/// Decode a single-part UR
+pub fn decode(ur: &str) -> Result<Vec<u8>> {
+    let mut decoder = UrDecoder::new();
+    match decoder.receive(ur)? {
+        Some(payload) => Ok(payload),
+        None => Err(UrError::InvalidUr(
+            "this is one part of several; scan all of them".to_string(),
+        )),
+    }
+}
+
+/// Reassembles a payload from its parts, read in any order
+#[derive(Debug, Clone, Default)]
+pub struct UrDecoder {
+    /// Message length and checksum of the first part read
+    header: Option<(usize, u32)>,
+    fragments: Vec<Option<Vec<u8>>>,
+}
+
+impl UrDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a scanned part; returns the payload once every part was read
+    ///
+    /// Parts read twice are ignored. Scanners upper-case the text of QR
+    /// codes, which UR allows.
+    pub fn receive(&mut self, ur: &str) -> Result<Option<Vec<u8>>> {
+        let ur = ur.trim().to_ascii_lowercase();
+        let rest = ur
+            .strip_prefix("ur:")
+            .ok_or_else(|| UrError::InvalidUr("missing the ur: prefix".to_string()))?;
+        let components: Vec<&str> = rest.split('/').collect();
+        if components[0] != UR_TYPE {
+            return Err(UrError::UnexpectedType(components[0].to_string()));
+        }
+        match components[1..] {
+            [body] => {
+                let message = decode_bytewords(body)?;
+                unwrap_cbor_bytes(&message).map(Some)
+            }
+            [sequence, body] => self.receive_part(sequence, body),
+            _ => Err(UrError::InvalidUr("unexpected path".to_string())),
+        }
+    }
+
+    /// Parts read so far, and the number of parts (0 until a part is read)
+    pub fn progress(&self) -> (usize, usize) {
+        let read = self.fragments.iter().filter(|f| f.is_some()).count();
+        (read, self.fragments.len())
+    }
+
+    fn receive_part(&mut self, sequence: &str, body: &str) -> Result<Option<Vec<u8>>> {
+        let invalid = || UrError::InvalidUr(format!("invalid sequence '{}'", sequence));
+        let (seq, count) = sequence.split_once('-').ok_or_else(invalid)?;
+        let seq: u32 = seq.parse().map_err(|_| invalid())?;
+        let count: u32 = count.parse().map_err(|_| invalid())?;
+
+        let part = decode_bytewords(body)?;
+        let mut pos = 0;
+        if cbor_read_head(&part, &mut pos)? != (CBOR_ARRAY, 5) {
+            return Err(UrError::InvalidCbor("expected an array of 5".to_string()));
+        }
+        let mut unsigned = || match cbor_read_head(&part, &mut pos)? {
+            (CBOR_UNSIGNED, value) => Ok(value),
+            _ => Err(UrError::InvalidCbor("expected an integer".to_string())),
+        };
+        let (part_seq, part_count, message_len, checksum) =
+            (unsigned()?, unsigned()?, unsigned()?, unsigned()?);
+        let fragment = unwrap_cbor_bytes(&part[pos..])?;
+
+        if part_seq != u64::from(seq) || part_count != u64::from(count) || seq == 0 || count == 0 {
+            return Err(UrError::InconsistentPart(seq));
+        }
+        if seq > count {
+            return Err(UrError::UnsupportedPart(seq));
+        }
+        let message_len =
+            usize::try_from(message_len).map_err(|_| UrError::InconsistentPart(seq))?;
+        let checksum = u32::try_from(checksum).map_err(|_| UrError::InconsistentPart(seq))?;
+        let count = count as usize;
+        if fragment.len() * count < message_len || fragment.len() * (count - 1) >= message_len {
+            return Err(UrError::InconsistentPart(seq));
+        }
+
+        match self.header {
+            None => {
+                self.header = Some((message_len, checksum));
+                self.fragments = vec![None; count];
+            }
+            Some(header) => {
+                let fragment_len = self.fragments.iter().flatten().next().map(Vec::len);
+                if header != (message_len, checksum)
+                    || self.fragments.len() != count
+                    || fragment_len.is_some_and(|len| len != fragment.len())
+                {
+                    return Err(UrError::InconsistentPart(seq));
+                }
+            }
+        }
+        self.fragments[seq as usize - 1].get_or_insert(fragment);
+
+        if self.fragments.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+        let mut message: Vec<u8> = self.fragments.iter().flatten().flatten().copied().collect();
+        message.truncate(message_len);
+        if crc32(&message) != checksum {
+            *self = Self::new();
+            return Err(UrError::BadChecksum);
+        }
+        unwrap_cbor_bytes(&message).map(Some)
+    }
+}
+
+/// Minimal bytewords of `data` followed by its CRC32
+fn bytewords(data: &[u8]) -> String {
+    data.iter()
+        .chain(&crc32(data).to_be_bytes())
+        .flat_map(|&byte| {
+            let word = BYTEWORDS[usize::from(byte)].as_bytes();
+            [char::from(word[0]), char::from(word[3])]
+        })
+        .collect()
+}
+
+fn decode_bytewords(text: &str) -> Result<Vec<u8>> {
+    let letters = text.as_bytes();
+    if !letters.len().is_multiple_of(2) || letters.len() < 10 {
+        return Err(UrError::InvalidBytewords("wrong length".to_string()));
+    }
+    let mut bytes = letters
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|w| w.as_bytes()[0] == pair[0] && w.as_bytes()[3] == pair[1])
+                .map(|index| index as u8)
+                .ok_or_else(|| {
+                    UrError::InvalidBytewords(format!("'{}'", String::from_utf8_lossy(pair)))
+                })
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if crc32(&bytes).to_be_bytes()[..] != checksum[..] {
+        return Err(UrError::BadChecksum);
+    }
+    Ok(bytes)
+}
+
+/// CRC-32 (IEEE), as used by bytewords and UR parts
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 9);
+    cbor_head(&mut out, CBOR_BYTES, data.len() as u64);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Major type and argument of the CBOR item at `pos`, moving `pos` past them
+fn cbor_read_head(data: &[u8], pos: &mut usize) -> Result<(u8, u64)> {
+    let truncated = || UrError::InvalidCbor("truncated".to_string());
+    let initial = *data.get(*pos).ok_or_else(truncated)?;
+    *pos += 1;
+    let length = match initial & 0x1f {
+        info @ 0..=23 => return Ok((initial >> 5, u64::from(info))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(UrError::InvalidCbor("unsupported length".to_string())),
+    };
+    let bytes = data.get(*pos..*pos + length).ok_or_else(truncated)?;
+    *pos += length;
+    let value = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+    Ok((initial >> 5, value))
+}
+
+/// Content of a CBOR byte string that spans all of `data`
+fn unwrap_cbor_bytes(data: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    match cbor_read_head(data, &mut pos)? {
+        (CBOR_BYTES, length) if data.len() - pos == length as usize => Ok(data[pos..].to_vec()),
+        _ => Err(UrError::InvalidCbor("expected a byte string".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytewords_vector() {
+        // Test vector of BCR-2020-012
+        assert_eq!(bytewords(&[0, 1, 2, 128, 255]), "aeadaolazmjendeoti");
+        assert_eq!(
+            decode_bytewords("aeadaolazmjendeoti").unwrap(),
+            [0, 1, 2, 128, 255]
+        );
+        assert_eq!(
+            decode_bytewords("aeadaolazmjendeota"),
+            Err(UrError::BadChecksum)
+        );
+
+        let payload = br#"{"version":1}"#;
+        let single = encode(payload, DEFAULT_FRAGMENT_LEN);
+        assert_eq!(single.len(), 1);
+        assert!(single[0].starts_with("ur:mina-tx/"));
+        assert_eq!(decode(&single[0].to_uppercase()).unwrap(), payload);
+        assert!(matches!(
+            decode("ur:bytes/aeadaolazmjendeoti"),
+            Err(UrError::UnexpectedType(_))
+        ));
+    }
+
+    #[test]
+    fn test_multi_part_roundtrip() {
+        let payload: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let parts = encode(&payload, 100);
+        assert_eq!(parts.len(), 11);
+        assert!(parts[0].starts_with("ur:mina-tx/1-11/"));
+        assert!(decode(&parts[0]).is_err());
+
+        // In any order, with repeats, as an animated QR code is read
+        let mut decoder = UrDecoder::new();
+        for index in [3, 0, 3, 10, 1, 2, 4, 5, 6, 7, 8] {
+            assert_eq!(decoder.receive(&parts[index]).unwrap(), None);
+        }
+        assert_eq!(decoder.progress(), (10, 11));
+        assert_eq!(decoder.receive(&parts[9]).unwrap(), Some(payload));
+
+        let other = encode(&[1u8; 500], 100);
+        let mut decoder = UrDecoder::new();
+        decoder.receive(&parts[0]).unwrap();
+        assert_eq!(
+            decoder.receive(&other[1]),
+            Err(UrError::InconsistentPart(2))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Vanity address generation
+//!
+//! This module grinds random keypairs until the Base58 address matches a
+//! desired pattern:
+//! - Prefix patterns are matched right after the fixed `B62q` header
+//! - Suffix patterns are matched at the end of the address
+//! - Progress is reported through a callback and the search can be cancelled
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use mina_signer::NetworkId;
+use thiserror::Error;
+
+use crate::wallet::Wallet;
+
+/// Header shared by every Mina address
+pub const ADDRESS_HEADER: &str = "B62q";
+
+/// Number of attempts between two progress callbacks
+pub const PROGRESS_INTERVAL: u64 = 1_000;
+
+/// Characters of the Base58 alphabet used by Mina
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Errors that can occur during vanity generation
+#[derive(Error, Debug)]
+pub enum VanityError {
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Search cancelled after {attempts} attempts")]
+    Cancelled { attempts: u64 },
+    #[error("Keypair generation failed: {0}")]
+    KeypairGenerationFailed(String),
+}
+
+pub type Result<T> = std::result::Result<T, VanityError>;
+
+/// Where the pattern must appear in the address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VanityPosition {
+    /// Right after the `B62q` header
+    Prefix,
+    /// At the end of the address
+    Suffix,
+}
+
+impl std::str::FromStr for VanityPosition {
+    type Err = VanityError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "prefix" => Ok(Self::Prefix),
+            "suffix" => Ok(Self::Suffix),
+            _ => Err(VanityError::InvalidPattern(format!(
+                "invalid position '{}', use 'prefix' or 'suffix'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Shared flag used to stop a running search from another thread or callback
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new, non-cancelled token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of every search using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Normalize and validate a pattern, stripping a leading `B62q` from prefixes
+pub fn normalize_pattern(pattern: &str, position: VanityPosition) -> Result<String> {
+    let pattern = match position {
+        VanityPosition::Prefix => pattern.strip_prefix(ADDRESS_HEADER).unwrap_or(pattern),
+        VanityPosition::Suffix => pattern,
+    };
+
+    if pattern.is_empty() {
+        return Err(VanityError::InvalidPattern(
+            "pattern must not be empty".to_string(),
+        ));
+    }
+    if let Some(c) = pattern.chars().find(|c| !BASE58_ALPHABET.contains(*c)) {
+        return Err(VanityError::InvalidPattern(format!(
+            "'{}' is not a Base58 character (0, O, I and l are excluded)",
+            c
+        )));
+    }
+
+    Ok(pattern.to_string())
+}
+
+/// Expected number of attempts to find a match for a normalized pattern
+pub fn expected_attempts(pattern: &str) -> f64 {
+    58f64.powi(pattern.chars().count() as i32)
+}
+
+/// Whether an address matches a normalized pattern at the given position
+pub fn matches(address: &str, pattern: &str, position: VanityPosition) -> bool {
+    match position {
+        VanityPosition::Prefix => address
+            .strip_prefix(ADDRESS_HEADER)
+            .is_some_and(|rest| rest.starts_with(pattern)),
+        VanityPosition::Suffix => address.ends_with(pattern),
+    }
+}
+
+/// Generate random wallets until the address matches `pattern`
+///
+/// `on_progress` is called with the total number of attempts every
+/// `PROGRESS_INTERVAL` attempts. The search stops with
+/// `VanityError::Cancelled` as soon as `cancel` is triggered.
+pub fn generate_vanity<F: FnMut(u64)>(
+    pattern: &str,
+    position: VanityPosition,
+    network: NetworkId,
+    cancel: &CancellationToken,
+    mut on_progress: F,
+) -> Result<Wallet> {
+    let pattern = normalize_pattern(pattern, position)?;
+    let mut attempts = 0u64;
+
+    loop {
+        if cancel.is_cancelled() {
+            return Err(VanityError::Cancelled { attempts });
+        }
+
+        let wallet = Wallet::new(network.clone())
+            .map_err(|e| VanityError::KeypairGenerationFailed(e.to_string()))?;
+        attempts += 1;
+
+        if matches(&wallet.address(), &pattern, position) {
+            return Ok(wallet);
+        }
+        if attempts % PROGRESS_INTERVAL == 0 {
+            on_progress(attempts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_pattern() {
+        assert_eq!(
+            normalize_pattern("B62qab", VanityPosition::Prefix).unwrap(),
+            "ab"
+        );
+        assert_eq!(
+            normalize_pattern("B62q", VanityPosition::Suffix).unwrap(),
+            "B62q"
+        );
+        assert!(normalize_pattern("B62q", VanityPosition::Prefix).is_err());
+        assert!(normalize_pattern("0x", VanityPosition::Suffix).is_err());
+    }
+
+    #[test]
+    fn test_generate_suffix() {
+        let cancel = CancellationToken::new();
+        let wallet = generate_vanity(
+            "a",
+            VanityPosition::Suffix,
+            NetworkId::MAINNET,
+            &cancel,
+            |_| {},
+        )
+        .unwrap();
+        assert!(wallet.address().ends_with('a'));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let result = generate_vanity(
+            "zzzzzzzz",
+            VanityPosition::Suffix,
+            NetworkId::MAINNET,
+            &cancel,
+            |_| token.cancel(),
+        );
+        assert!(matches!(
+            result,
+            Err(VanityError::Cancelled {
+                attempts: PROGRESS_INTERVAL
+            })
+        ));
+    }
+}
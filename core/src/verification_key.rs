@@ -0,0 +1,282 @@
+//! zkApp verification keys
+//!
+//! A zkApp account stores the verification key of its circuit, and the
+//! account updates that deploy or call it refer to the key by its hash. This
+//! module decodes the base64 form produced by o1js (`VerificationKey.data`)
+//! and recomputes that hash, so a deployed key can be checked against a
+//! local build.
+//!
+//! The base64 data is the binary encoding of a Pickles side-loaded key:
+//! - max proofs verified: 1 byte, 0 to 2
+//! - actual wrap domain size: 1 byte, 0 to 2
+//! - 28 commitments of the wrap index, each a Pallas point `(x, y)` with
+//!   coordinates as 32-byte little-endian field elements, in the order
+//!   sigma (7), coefficients (15), generic, poseidon, complete add, mul,
+//!   endomul and endomul scalar
+//!
+//! The hash is the kimchi Poseidon hash, with the `MinaSideLoadedVk` prefix,
+//! of the commitment coordinates followed by the two values one-hot encoded
+//! and packed into a single field element.
+
+use ark_ff::{BigInteger, PrimeField};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use mina_curves::pasta::Pallas;
+use mina_hasher::{Hashable, Hasher, ROInput};
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::fields::Fp;
+
+/// Number of sigma commitments (permutation columns)
+pub const SIGMA_COMMITMENTS: usize = 7;
+
+/// Number of coefficient commitments
+pub const COEFFICIENT_COMMITMENTS: usize = 15;
+
+/// Total number of commitments in the wrap index
+pub const WRAP_INDEX_COMMITMENTS: usize = SIGMA_COMMITMENTS + COEFFICIENT_COMMITMENTS + 6;
+
+/// Length of the binary encoding of a verification key
+pub const VERIFICATION_KEY_BYTES: usize = 2 + WRAP_INDEX_COMMITMENTS * 2 * FIELD_BYTES;
+
+const FIELD_BYTES: usize = 32;
+
+/// Largest value of max proofs verified and of the wrap domain size
+const MAX_PROOFS_VERIFIED: u8 = 2;
+
+/// Errors that can occur while decoding a verification key
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VerificationKeyError {
+    #[error("Invalid base64: {0}")]
+    InvalidBase64(String),
+    #[error("Verification key is {0} bytes long, expected {VERIFICATION_KEY_BYTES}")]
+    InvalidLength(usize),
+    #[error("Invalid {field}: {value}, expected 0 to {MAX_PROOFS_VERIFIED}")]
+    InvalidProofsVerified { field: &'static str, value: u8 },
+    #[error("Commitment {0} has a coordinate outside of the field")]
+    InvalidFieldElement(usize),
+    #[error("Commitment {0} is not a point of the Pallas curve")]
+    NotOnCurve(usize),
+    #[error("Invalid verification key hash: {0}")]
+    InvalidHash(String),
+}
+
+pub type Result<T> = std::result::Result<T, VerificationKeyError>;
+
+/// A commitment of the wrap index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Commitment {
+    pub x: Fp,
+    pub y: Fp,
+}
+
+/// A decoded side-loaded verification key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationKey {
+    /// Number of proofs the circuit verifies recursively, 0 to 2
+    pub max_proofs_verified: u8,
+    /// Wrap domain size, as the number of proofs verified it corresponds to
+    pub actual_wrap_domain_size: u8,
+    /// Commitments of the wrap index, in encoding order
+    pub commitments: Vec<Commitment>,
+}
+
+impl VerificationKey {
+    /// Decode a verification key from its base64 form
+    pub fn from_base64(data: &str) -> Result<Self> {
+        let bytes = BASE64
+            .decode(data.trim())
+            .map_err(|e| VerificationKeyError::InvalidBase64(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Decode a verification key from its binary form, checking that every
+    /// commitment is a point of the Pallas curve
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != VERIFICATION_KEY_BYTES {
+            return Err(VerificationKeyError::InvalidLength(bytes.len()));
+        }
+        let max_proofs_verified = proofs_verified("max proofs verified", bytes[0])?;
+        let actual_wrap_domain_size = proofs_verified("wrap domain size", bytes[1])?;
+
+        let commitments = bytes[2..]
+            .chunks_exact(2 * FIELD_BYTES)
+            .enumerate()
+            .map(|(i, point)| {
+                let (x, y) = point.split_at(FIELD_BYTES);
+                let commitment = Commitment {
+                    x: field(x).ok_or(VerificationKeyError::InvalidFieldElement(i))?,
+                    y: field(y).ok_or(VerificationKeyError::InvalidFieldElement(i))?,
+                };
+                if !Pallas::new_unchecked(commitment.x, commitment.y).is_on_curve() {
+                    return Err(VerificationKeyError::NotOnCurve(i));
+                }
+                Ok(commitment)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            max_proofs_verified,
+            actual_wrap_domain_size,
+            commitments,
+        })
+    }
+
+    /// Encode the verification key in its binary form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(VERIFICATION_KEY_BYTES);
+        bytes.push(self.max_proofs_verified);
+        bytes.push(self.actual_wrap_domain_size);
+        for commitment in &self.commitments {
+            for coordinate in [commitment.x, commitment.y] {
+                let mut le = coordinate.into_bigint().to_bytes_le();
+                le.resize(FIELD_BYTES, 0);
+                bytes.extend_from_slice(&le);
+            }
+        }
+        bytes
+    }
+
+    /// Encode the verification key in the base64 form used by o1js
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.to_bytes())
+    }
+
+    /// Hash of the verification key, as stored in zkApp accounts
+    pub fn hash(&self) -> Fp {
+        let mut hasher = mina_hasher::create_kimchi::<Self>(());
+        hasher.hash(self)
+    }
+
+    /// Whether the key hashes to `expected`, a decimal field element as
+    /// returned by the daemon
+    pub fn matches_hash(&self, expected: &str) -> Result<bool> {
+        let expected = Fp::from_str(expected.trim())
+            .map_err(|_| VerificationKeyError::InvalidHash(expected.to_string()))?;
+        Ok(self.hash() == expected)
+    }
+}
+
+impl Hashable for VerificationKey {
+    type D = ();
+
+    fn to_roinput(&self) -> ROInput {
+        let roi = self.commitments.iter().fold(ROInput::new(), |roi, c| {
+            roi.append_field(c.x).append_field(c.y)
+        });
+        roi.append_field(pack_one_hot(&[
+            self.max_proofs_verified,
+            self.actual_wrap_domain_size,
+        ]))
+    }
+
+    fn domain_string(_: Self::D) -> Option<String> {
+        Some("MinaSideLoadedVk".to_string())
+    }
+}
+
+fn proofs_verified(field: &'static str, value: u8) -> Result<u8> {
+    if value > MAX_PROOFS_VERIFIED {
+        return Err(VerificationKeyError::InvalidProofsVerified { field, value });
+    }
+    Ok(value)
+}
+
+/// Decode a little-endian field element, refusing values above the modulus
+fn field(bytes: &[u8]) -> Option<Fp> {
+    let value = Fp::from_le_bytes_mod_order(bytes);
+    let mut canonical = value.into_bigint().to_bytes_le();
+    canonical.resize(FIELD_BYTES, 0);
+    (canonical == bytes).then_some(value)
+}
+
+/// One-hot encode values of 0 to 2 on 3 bits each, and pack the bits into a
+/// field element, first bit most significant
+fn pack_one_hot(values: &[u8]) -> Fp {
+    let packed = values
+        .iter()
+        .flat_map(|&value| (0..=MAX_PROOFS_VERIFIED).map(move |i| i == value))
+        .fold(0u64, |acc, bit| (acc << 1) | u64::from(bit));
+    Fp::from(packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::AffineRepr;
+
+    fn sample_key() -> VerificationKey {
+        let generator = Pallas::generator();
+        VerificationKey {
+            max_proofs_verified: 1,
+            actual_wrap_domain_size: 2,
+            commitments: vec![
+                Commitment {
+                    x: generator.x,
+                    y: generator.y,
+                };
+                WRAP_INDEX_COMMITMENTS
+            ],
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_and_hash() {
+        let vk = sample_key();
+        let encoded = vk.to_base64();
+        let decoded = VerificationKey::from_base64(&encoded).unwrap();
+        assert_eq!(decoded, vk);
+
+        let hash = vk.hash();
+        assert!(vk.matches_hash(&hash.to_string()).unwrap());
+        assert!(!vk.matches_hash("1").unwrap());
+        assert!(vk.matches_hash("not a field").is_err());
+
+        let mut other = vk.clone();
+        other.max_proofs_verified = 0;
+        assert_ne!(other.hash(), hash);
+    }
+
+    #[test]
+    fn test_pack_one_hot() {
+        // 1 -> 010, 2 -> 001
+        assert_eq!(pack_one_hot(&[1, 2]), Fp::from(0b010_001u64));
+        assert_eq!(pack_one_hot(&[0, 0]), Fp::from(0b100_100u64));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let mut bytes = sample_key().to_bytes();
+        assert_eq!(
+            VerificationKey::from_bytes(&bytes[1..]),
+            Err(VerificationKeyError::InvalidLength(
+                VERIFICATION_KEY_BYTES - 1
+            ))
+        );
+
+        bytes[0] = 3;
+        assert!(matches!(
+            VerificationKey::from_bytes(&bytes),
+            Err(VerificationKeyError::InvalidProofsVerified { value: 3, .. })
+        ));
+
+        bytes[0] = 0;
+        bytes[2] ^= 1;
+        assert_eq!(
+            VerificationKey::from_bytes(&bytes),
+            Err(VerificationKeyError::NotOnCurve(0))
+        );
+
+        bytes[2..2 + FIELD_BYTES].fill(0xff);
+        assert_eq!(
+            VerificationKey::from_bytes(&bytes),
+            Err(VerificationKeyError::InvalidFieldElement(0))
+        );
+
+        assert!(matches!(
+            VerificationKey::from_base64("not base64!"),
+            Err(VerificationKeyError::InvalidBase64(_))
+        ));
+    }
+}
@@ -4,10 +4,19 @@
 //! - Creating new wallets
 //! - Importing existing wallets from secret keys
 //! - Signing messages and transactions
+//! - Wiping secret key material from memory when a wallet is dropped
 
+use alloc::format;
+use alloc::string::{String, ToString};
+use ark_ff::Zero;
+use core::fmt;
+use mina_curves::pasta::Fq;
 use mina_signer::{Keypair, NetworkId, PubKey, SecKey};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::secret::{self, SecretParseError};
 
 /// Errors that can occur during wallet operations
 #[derive(Error, Debug)]
@@ -20,12 +29,17 @@ pub enum WalletError {
     SigningFailed(String),
     #[error("Keypair generation failed: {0}")]
     KeypairGenerationFailed(String),
+    #[error("Invalid secret key format: {0}")]
+    InvalidSecretFormat(#[from] SecretParseError),
 }
 
-pub type Result<T> = std::result::Result<T, WalletError>;
+pub type Result<T> = core::result::Result<T, WalletError>;
 
 /// A Mina wallet containing a keypair and associated metadata
-#[derive(Clone)]
+///
+/// The secret key is wiped from memory when the wallet is dropped. The wallet
+/// intentionally does not implement `Clone`, so that secrets are not copied
+/// implicitly.
 pub struct Wallet {
     /// The keypair (secret + public key)
     keypair: Keypair,
@@ -34,7 +48,8 @@ pub struct Wallet {
 }
 
 impl Wallet {
-    /// Create a new random wallet
+    /// Create a new random wallet, from the OS random number generator
+    #[cfg(feature = "std")]
     pub fn new(network: NetworkId) -> Result<Self> {
         let keypair = Keypair::rand(&mut rand::rngs::OsRng)
             .map_err(|e| WalletError::KeypairGenerationFailed(format!("{:?}", e)))?;
@@ -59,6 +74,17 @@ impl Wallet {
         Ok(Self { keypair, network })
     }
 
+    /// Create a wallet from a secret key, rejecting anything but an exact hex or Base58 key
+    ///
+    /// Unlike `from_secret_key_hex` and `from_secret_key_base58`, this rejects
+    /// whitespace, mixed-case hex and wrong lengths with a specific error.
+    pub fn from_secret_key_strict(secret: &str, network: NetworkId) -> Result<Self> {
+        let (secret, _) = secret::parse_strict(secret)?;
+        let keypair = Keypair::from_secret_key(secret)
+            .map_err(|e| WalletError::InvalidSecretKey(format!("{:?}", e)))?;
+        Ok(Self { keypair, network })
+    }
+
     /// Get the public key
     pub fn public_key(&self) -> &PubKey {
         &self.keypair.public
@@ -70,13 +96,13 @@ impl Wallet {
     }
 
     /// Get the secret key in hex format
-    pub fn secret_key_hex(&self) -> String {
-        self.keypair.secret.to_hex()
+    pub fn secret_key_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.keypair.secret.to_hex())
     }
 
     /// Get the secret key in Base58 format
-    pub fn secret_key_base58(&self) -> String {
-        self.keypair.secret.to_base58()
+    pub fn secret_key_base58(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.keypair.secret.to_base58())
     }
 
     /// Get the network
@@ -88,6 +114,74 @@ impl Wallet {
     pub fn keypair(&self) -> &Keypair {
         &self.keypair
     }
+
+    /// Whether two wallets hold the same secret key, compared in constant time
+    pub fn secret_eq(&self, other: &Wallet) -> bool {
+        secret::ct_eq(
+            self.secret_key_hex().as_bytes(),
+            other.secret_key_hex().as_bytes(),
+        )
+    }
+
+    /// Drop the secret key, keeping only a watch-only view of the wallet
+    pub fn forget_secret(self) -> WatchOnlyWallet {
+        WatchOnlyWallet {
+            public: self.keypair.public.clone(),
+            network: self.network.clone(),
+        }
+        // `self` is dropped here, wiping the secret key
+    }
+}
+
+impl Zeroize for Wallet {
+    fn zeroize(&mut self) {
+        // SAFETY: `secret` is valid, aligned and exclusively borrowed, and `SecKey`
+        // has no drop glue, so overwriting it without dropping the old value is
+        // sound. The volatile write keeps the compiler from eliding the store.
+        unsafe { core::ptr::write_volatile(&mut self.keypair.secret, SecKey::new(Fq::zero())) };
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl Drop for Wallet {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Wallet {}
+
+/// A wallet without its secret key
+///
+/// It can derive the address and verify signatures, but never sign.
+#[derive(Clone)]
+pub struct WatchOnlyWallet {
+    public: PubKey,
+    network: NetworkId,
+}
+
+impl WatchOnlyWallet {
+    /// Create a watch-only wallet from a Mina address
+    pub fn from_address(address: &str, network: NetworkId) -> Result<Self> {
+        let public = PubKey::from_address(address)
+            .map_err(|e| WalletError::InvalidAddress(format!("{:?}", e)))?;
+        Ok(Self { public, network })
+    }
+
+    /// Get the public key
+    pub fn public_key(&self) -> &PubKey {
+        &self.public
+    }
+
+    /// Get the Mina address
+    pub fn address(&self) -> String {
+        self.public.into_address()
+    }
+
+    /// Get the network
+    pub fn network(&self) -> &NetworkId {
+        &self.network
+    }
 }
 
 /// Wallet information that can be safely serialized (no secret key)
@@ -101,16 +195,30 @@ impl From<&Wallet> for WalletInfo {
     fn from(wallet: &Wallet) -> Self {
         Self {
             address: wallet.address(),
-            network: match wallet.network() {
-                NetworkId::MAINNET => "mainnet".to_string(),
-                NetworkId::TESTNET => "testnet".to_string(),
-            },
+            network: network_name(wallet.network()).to_string(),
+        }
+    }
+}
+
+impl From<&WatchOnlyWallet> for WalletInfo {
+    fn from(wallet: &WatchOnlyWallet) -> Self {
+        Self {
+            address: wallet.address(),
+            network: network_name(wallet.network()).to_string(),
         }
     }
 }
 
-impl std::fmt::Debug for Wallet {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Lowercase name of a network, as used in serialized wallet information
+pub fn network_name(network: &NetworkId) -> &'static str {
+    match network {
+        NetworkId::MAINNET => "mainnet",
+        NetworkId::TESTNET => "testnet",
+    }
+}
+
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // Don't expose secret key in debug output
         f.debug_struct("Wallet")
             .field("address", &self.address())
@@ -119,8 +227,23 @@ impl std::fmt::Debug for Wallet {
     }
 }
 
-impl std::fmt::Display for Wallet {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address())
+    }
+}
+
+impl fmt::Debug for WatchOnlyWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchOnlyWallet")
+            .field("address", &self.address())
+            .field("network", self.network())
+            .finish()
+    }
+}
+
+impl fmt::Display for WatchOnlyWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.address())
     }
 }
@@ -152,6 +275,26 @@ mod tests {
         assert_eq!(wallet1.address(), wallet3.address());
     }
 
+    #[test]
+    fn test_wallet_from_secret_key_strict() {
+        let wallet1 = Wallet::new(NetworkId::MAINNET).expect("Failed to create wallet");
+        let wallet2 =
+            Wallet::from_secret_key_strict(&wallet1.secret_key_base58(), NetworkId::MAINNET)
+                .unwrap();
+        assert!(wallet1.secret_eq(&wallet2));
+
+        let padded = format!("{}\n", wallet1.secret_key_hex().as_str());
+        assert!(matches!(
+            Wallet::from_secret_key_strict(&padded, NetworkId::MAINNET),
+            Err(WalletError::InvalidSecretFormat(
+                SecretParseError::Whitespace(64)
+            ))
+        ));
+
+        let other = Wallet::new(NetworkId::MAINNET).expect("Failed to create wallet");
+        assert!(!wallet1.secret_eq(&other));
+    }
+
     #[test]
     fn test_wallet_info() {
         let wallet = Wallet::new(NetworkId::TESTNET).expect("Failed to create wallet");
@@ -159,4 +302,25 @@ mod tests {
         assert_eq!(info.address, wallet.address());
         assert_eq!(info.network, "testnet");
     }
+
+    #[test]
+    fn test_zeroize_wipes_secret() {
+        let mut wallet = Wallet::new(NetworkId::MAINNET).expect("Failed to create wallet");
+        assert!(!wallet.keypair().secret.scalar().is_zero());
+
+        wallet.zeroize();
+        assert!(wallet.keypair().secret.scalar().is_zero());
+    }
+
+    #[test]
+    fn test_forget_secret() {
+        let wallet = Wallet::new(NetworkId::TESTNET).expect("Failed to create wallet");
+        let address = wallet.address();
+
+        let watch_only = wallet.forget_secret();
+        assert_eq!(watch_only.address(), address);
+
+        let info: WalletInfo = (&watch_only).into();
+        assert_eq!(info.network, "testnet");
+    }
 }
@@ -0,0 +1,984 @@
+//! WalletConnect v2 sign client, wallet side
+//!
+//! dApps reach a wallet through a WalletConnect relay:
+//! - The dApp shows a pairing URI (`wc:{topic}@2?relay-protocol=irn&symKey={key}`);
+//!   the wallet subscribes to its topic and receives a session proposal
+//! - Once the user approves it, an X25519 key agreement with the dApp gives
+//!   the session key, and the dApp sends its requests on the session topic
+//! - Every message is a JSON-RPC payload encrypted with ChaCha20-Poly1305
+//!   under the key of its topic; topics are the SHA-256 of their key
+//!
+//! `WalletConnectClient` implements the protocol without owning the socket,
+//! like `SubscriptionClient`: the caller connects to `relay_url`, sends the
+//! messages of `take_outgoing`, and feeds it every message received. Requests
+//! of the `mina` namespace are signed with `sign_request` once the user has
+//! approved them.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Signer, SigningKey};
+use hkdf::Hkdf;
+use mina_signer::NetworkId;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use thiserror::Error;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+use zeroize::Zeroizing;
+
+use crate::oracle;
+use crate::signing::SignatureJson;
+use crate::transaction::Transaction;
+use crate::wallet::Wallet;
+
+/// Relay of the WalletConnect network
+pub const RELAY_URL: &str = "wss://relay.walletconnect.com";
+
+/// Namespace of Mina chains, e.g. `mina:mainnet`
+pub const MINA_NAMESPACE: &str = "mina";
+
+/// Methods a session accepts
+pub const MINA_METHODS: &[&str] = &[
+    "mina_signMessage",
+    "mina_signFields",
+    "mina_signTransaction",
+];
+
+/// Lifetime of a session, in seconds
+const SESSION_TTL: u64 = 7 * 86_400;
+
+/// Lifetime of the relay authentication token, in seconds
+const JWT_TTL: u64 = 86_400;
+
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+
+/// Envelope of messages encrypted with the key of their topic
+const ENVELOPE_TYPE_0: u8 = 0;
+
+/// Tag and time to live, in seconds, of a published message
+struct Publish {
+    tag: u32,
+    ttl: u64,
+}
+
+const PAIRING_DELETE_RESPONSE: Publish = Publish {
+    tag: 1001,
+    ttl: 86_400,
+};
+const PAIRING_PING_RESPONSE: Publish = Publish { tag: 1003, ttl: 30 };
+const SESSION_PROPOSE_RESPONSE: Publish = Publish {
+    tag: 1101,
+    ttl: 300,
+};
+const SESSION_SETTLE: Publish = Publish {
+    tag: 1102,
+    ttl: 300,
+};
+const SESSION_REQUEST_RESPONSE: Publish = Publish {
+    tag: 1109,
+    ttl: 300,
+};
+const SESSION_DELETE: Publish = Publish {
+    tag: 1112,
+    ttl: 86_400,
+};
+const SESSION_DELETE_RESPONSE: Publish = Publish {
+    tag: 1113,
+    ttl: 86_400,
+};
+const SESSION_PING_RESPONSE: Publish = Publish { tag: 1115, ttl: 30 };
+
+/// Error code of a request or proposal rejected by the user
+const USER_REJECTED: i64 = 5000;
+/// Error code of a session closed by the user
+const USER_DISCONNECTED: i64 = 6000;
+/// JSON-RPC error code of invalid parameters
+const INVALID_PARAMS: i64 = -32602;
+
+/// Errors that can occur in a WalletConnect session
+#[derive(Error, Debug)]
+pub enum WalletConnectError {
+    #[error("Invalid pairing URI: {0}")]
+    InvalidUri(String),
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+    #[error("Message on unknown topic {0}")]
+    UnknownTopic(String),
+    #[error("No pending proposal or request with id {0}")]
+    UnknownId(u64),
+    #[error("Decryption failed: wrong key or tampered message")]
+    DecryptionFailed,
+    #[error("Unsupported method: {0}")]
+    UnsupportedMethod(String),
+    #[error("Chain {chain} does not match the network of the wallet")]
+    WrongChain { chain: String },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+}
+
+pub type Result<T> = std::result::Result<T, WalletConnectError>;
+
+/// Name, description, URL and icons of a wallet or dApp
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    #[serde(default)]
+    pub icons: Vec<String>,
+}
+
+/// A pairing URI shown by a dApp
+pub struct PairingUri {
+    pub topic: String,
+    pub sym_key: Zeroizing<[u8; KEY_LEN]>,
+    pub relay_protocol: String,
+    /// Unix timestamp in seconds after which the pairing is void
+    pub expiry: Option<u64>,
+}
+
+impl PairingUri {
+    /// Parse `wc:{topic}@2?relay-protocol=irn&symKey={key}[&expiryTimestamp={t}]`
+    pub fn parse(uri: &str) -> Result<Self> {
+        let invalid = |reason: &str| WalletConnectError::InvalidUri(reason.to_string());
+        let rest = uri
+            .trim()
+            .strip_prefix("wc:")
+            .ok_or_else(|| invalid("no wc: prefix"))?;
+        let (topic, rest) = rest.split_once('@').ok_or_else(|| invalid("no version"))?;
+        let (version, query) = rest
+            .split_once('?')
+            .ok_or_else(|| invalid("no parameters"))?;
+        if version != "2" {
+            return Err(invalid("only version 2 is supported"));
+        }
+        if topic.len() != 2 * KEY_LEN || !topic.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid("invalid topic"));
+        }
+
+        let mut sym_key = None;
+        let mut relay_protocol = None;
+        let mut expiry = None;
+        for (name, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+            match name {
+                "symKey" => {
+                    sym_key = Some(decode_key(value).ok_or_else(|| invalid("invalid symKey"))?)
+                }
+                "relay-protocol" => relay_protocol = Some(value.to_string()),
+                "expiryTimestamp" => {
+                    expiry = Some(
+                        value
+                            .parse()
+                            .map_err(|_| invalid("invalid expiryTimestamp"))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        let sym_key = sym_key.ok_or_else(|| invalid("no symKey"))?;
+        if topic_of(&sym_key) != topic {
+            return Err(invalid("the topic does not match symKey"));
+        }
+        Ok(Self {
+            topic: topic.to_string(),
+            sym_key,
+            relay_protocol: relay_protocol.ok_or_else(|| invalid("no relay-protocol"))?,
+            expiry,
+        })
+    }
+}
+
+/// Something for the user to approve, or a session closed by the dApp
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WalletConnectEvent {
+    /// A dApp asks for a session; answer with `approve_session` or `reject_session`
+    #[serde(rename_all = "camelCase")]
+    Proposal {
+        id: u64,
+        proposer: Metadata,
+        required_namespaces: Value,
+        optional_namespaces: Value,
+    },
+    /// A dApp asks for a signature; answer with `sign_request` or `reject_request`
+    #[serde(rename_all = "camelCase")]
+    Request {
+        id: u64,
+        topic: String,
+        peer: Metadata,
+        chain_id: String,
+        method: String,
+        params: Value,
+    },
+    /// The dApp closed the session
+    SessionDeleted { topic: String },
+}
+
+struct Proposal {
+    pairing_topic: String,
+    proposer_key: [u8; KEY_LEN],
+    proposer: Metadata,
+    required_namespaces: Value,
+    optional_namespaces: Value,
+}
+
+struct Session {
+    sym_key: Zeroizing<[u8; KEY_LEN]>,
+    peer: Metadata,
+    expiry: u64,
+}
+
+struct PendingRequest {
+    topic: String,
+    chain_id: String,
+    method: String,
+    params: Value,
+}
+
+/// Message from the relay
+#[derive(Deserialize)]
+struct RelayMessage {
+    id: Value,
+    method: Option<String>,
+    params: Option<SubscriptionParams>,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionParams {
+    data: SubscriptionData,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionData {
+    topic: String,
+    message: String,
+}
+
+/// Decrypted JSON-RPC request or response of a dApp
+#[derive(Deserialize)]
+struct Payload {
+    id: u64,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProposeParams {
+    proposer: Participant,
+    #[serde(default)]
+    required_namespaces: Value,
+    #[serde(default)]
+    optional_namespaces: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Participant {
+    public_key: String,
+    metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestParams {
+    request: MethodCall,
+    chain_id: String,
+}
+
+#[derive(Deserialize)]
+struct MethodCall {
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Protocol state of the wallet side of WalletConnect
+pub struct WalletConnectClient {
+    /// Identity of the client on the relay
+    relay_key: SigningKey,
+    metadata: Metadata,
+    pairings: HashMap<String, Zeroizing<[u8; KEY_LEN]>>,
+    proposals: HashMap<u64, Proposal>,
+    sessions: HashMap<String, Session>,
+    requests: HashMap<u64, PendingRequest>,
+    outgoing: Vec<String>,
+    next_id: u64,
+}
+
+impl WalletConnectClient {
+    /// Create a client presenting the wallet to dApps with `metadata`
+    pub fn new(metadata: Metadata) -> Self {
+        Self {
+            relay_key: SigningKey::generate(&mut rand::rngs::OsRng),
+            metadata,
+            pairings: HashMap::new(),
+            proposals: HashMap::new(),
+            sessions: HashMap::new(),
+            requests: HashMap::new(),
+            outgoing: Vec::new(),
+            // Ids are only compared, but must stay exact as JavaScript numbers
+            next_id: u64::from(rand::random::<u32>()),
+        }
+    }
+
+    /// URL of the relay websocket, authenticated for a day from `now`
+    pub fn relay_url(&self, project_id: &str, now: u64) -> Result<String> {
+        if project_id.is_empty() || !project_id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(WalletConnectError::InvalidRequest(format!(
+                "invalid project id '{}'",
+                project_id
+            )));
+        }
+        Ok(format!(
+            "{}/?auth={}&projectId={}",
+            RELAY_URL,
+            self.relay_jwt(now),
+            project_id
+        ))
+    }
+
+    /// Pair with a dApp from the URI it shows
+    pub fn pair(&mut self, uri: &str, now: u64) -> Result<()> {
+        let uri = PairingUri::parse(uri)?;
+        if uri.relay_protocol != "irn" {
+            return Err(WalletConnectError::InvalidUri(format!(
+                "unsupported relay protocol {}",
+                uri.relay_protocol
+            )));
+        }
+        if uri.expiry.is_some_and(|expiry| expiry <= now) {
+            return Err(WalletConnectError::InvalidUri(
+                "the pairing expired".to_string(),
+            ));
+        }
+        self.subscribe(&uri.topic);
+        self.pairings.insert(uri.topic, uri.sym_key);
+        Ok(())
+    }
+
+    /// Messages to send to the relay, in order
+    pub fn take_outgoing(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Open sessions, by topic, with the dApp on the other side
+    pub fn sessions(&self) -> impl Iterator<Item = (&str, &Metadata)> {
+        self.sessions
+            .iter()
+            .map(|(topic, session)| (topic.as_str(), &session.peer))
+    }
+
+    /// Handle a message received from the relay
+    ///
+    /// Pings and deletions are answered directly; proposals and requests are
+    /// returned for the user to approve.
+    pub fn handle_message(&mut self, text: &str, now: u64) -> Result<Option<WalletConnectEvent>> {
+        let message: RelayMessage = serde_json::from_str(text)
+            .map_err(|e| WalletConnectError::InvalidMessage(e.to_string()))?;
+        // Acknowledgements of our own messages
+        let (Some("irn_subscription"), Some(params)) = (message.method.as_deref(), message.params)
+        else {
+            return Ok(None);
+        };
+        self.outgoing
+            .push(json!({ "id": message.id, "jsonrpc": "2.0", "result": true }).to_string());
+
+        let SubscriptionData { topic, message } = params.data;
+        self.sessions.retain(|_, session| session.expiry > now);
+        let key = self
+            .pairings
+            .get(&topic)
+            .or_else(|| self.sessions.get(&topic).map(|s| &s.sym_key))
+            .ok_or_else(|| WalletConnectError::UnknownTopic(topic.clone()))?;
+        let payload = decrypt(key, &message)?;
+        let payload: Payload = serde_json::from_slice(&payload)
+            .map_err(|e| WalletConnectError::InvalidMessage(e.to_string()))?;
+        // Responses of the dApp, e.g. to the session settlement
+        let Some(method) = payload.method else {
+            return Ok(None);
+        };
+
+        match method.as_str() {
+            "wc_sessionPropose" => {
+                let params: ProposeParams = from_params(payload.params)?;
+                let proposer_key = decode_key(&params.proposer.public_key).ok_or_else(|| {
+                    WalletConnectError::InvalidMessage("invalid public key".to_string())
+                })?;
+                let event = WalletConnectEvent::Proposal {
+                    id: payload.id,
+                    proposer: params.proposer.metadata.clone(),
+                    required_namespaces: params.required_namespaces.clone(),
+                    optional_namespaces: params.optional_namespaces.clone(),
+                };
+                self.proposals.insert(
+                    payload.id,
+                    Proposal {
+                        pairing_topic: topic,
+                        proposer_key: *proposer_key,
+                        proposer: params.proposer.metadata,
+                        required_namespaces: params.required_namespaces,
+                        optional_namespaces: params.optional_namespaces,
+                    },
+                );
+                Ok(Some(event))
+            }
+            "wc_sessionRequest" => {
+                let params: RequestParams = from_params(payload.params)?;
+                let peer = self
+                    .sessions
+                    .get(&topic)
+                    .map(|s| s.peer.clone())
+                    .ok_or_else(|| WalletConnectError::UnknownTopic(topic.clone()))?;
+                if !MINA_METHODS.contains(&params.request.method.as_str()) {
+                    let error = WalletConnectError::UnsupportedMethod(params.request.method);
+                    self.respond(
+                        &topic,
+                        payload.id,
+                        Err((10001, error.to_string())),
+                        &SESSION_REQUEST_RESPONSE,
+                    )?;
+                    return Ok(None);
+                }
+                self.requests.insert(
+                    payload.id,
+                    PendingRequest {
+                        topic: topic.clone(),
+                        chain_id: params.chain_id.clone(),
+                        method: params.request.method.clone(),
+                        params: params.request.params.clone(),
+                    },
+                );
+                Ok(Some(WalletConnectEvent::Request {
+                    id: payload.id,
+                    topic,
+                    peer,
+                    chain_id: params.chain_id,
+                    method: params.request.method,
+                    params: params.request.params,
+                }))
+            }
+            "wc_sessionPing" => {
+                self.respond(&topic, payload.id, Ok(json!(true)), &SESSION_PING_RESPONSE)?;
+                Ok(None)
+            }
+            "wc_pairingPing" => {
+                self.respond(&topic, payload.id, Ok(json!(true)), &PAIRING_PING_RESPONSE)?;
+                Ok(None)
+            }
+            "wc_sessionDelete" => {
+                self.respond(
+                    &topic,
+                    payload.id,
+                    Ok(json!(true)),
+                    &SESSION_DELETE_RESPONSE,
+                )?;
+                self.sessions.remove(&topic);
+                self.requests.retain(|_, request| request.topic != topic);
+                Ok(Some(WalletConnectEvent::SessionDeleted { topic }))
+            }
+            "wc_pairingDelete" => {
+                self.respond(
+                    &topic,
+                    payload.id,
+                    Ok(json!(true)),
+                    &PAIRING_DELETE_RESPONSE,
+                )?;
+                self.pairings.remove(&topic);
+                self.proposals
+                    .retain(|_, proposal| proposal.pairing_topic != topic);
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Approve a session proposal for the given accounts, e.g.
+    /// `mina:mainnet:B62...`, returning the topic of the session
+    pub fn approve_session(&mut self, id: u64, accounts: &[String], now: u64) -> Result<String> {
+        let proposal = self
+            .proposals
+            .remove(&id)
+            .ok_or(WalletConnectError::UnknownId(id))?;
+        for account in accounts {
+            let valid = account
+                .strip_prefix("mina:")
+                .and_then(|rest| rest.split_once(':'))
+                .is_some_and(|(chain, address)| !chain.is_empty() && address.starts_with("B62"));
+            if !valid {
+                return Err(WalletConnectError::InvalidRequest(format!(
+                    "invalid account '{}'",
+                    account
+                )));
+            }
+        }
+
+        let secret = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let public_key = PublicKey::from(&secret);
+        let shared = secret.diffie_hellman(&PublicKey::from(proposal.proposer_key));
+        let mut sym_key = Zeroizing::new([0u8; KEY_LEN]);
+        Hkdf::<Sha256>::new(None, shared.as_bytes())
+            .expand(&[], sym_key.as_mut())
+            .map_err(|_| WalletConnectError::InvalidRequest("key derivation failed".to_string()))?;
+        let session_topic = topic_of(&sym_key);
+        let public_key = hex::encode(public_key.as_bytes());
+
+        self.respond(
+            &proposal.pairing_topic,
+            id,
+            Ok(json!({ "relay": { "protocol": "irn" }, "responderPublicKey": public_key })),
+            &SESSION_PROPOSE_RESPONSE,
+        )?;
+
+        let expiry = now + SESSION_TTL;
+        self.subscribe(&session_topic);
+        self.sessions.insert(
+            session_topic.clone(),
+            Session {
+                sym_key,
+                peer: proposal.proposer,
+                expiry,
+            },
+        );
+        let settle = json!({
+            "relay": { "protocol": "irn" },
+            "namespaces": {
+                MINA_NAMESPACE: {
+                    "accounts": accounts,
+                    "methods": MINA_METHODS,
+                    "events": [],
+                },
+            },
+            "requiredNamespaces": proposal.required_namespaces,
+            "optionalNamespaces": proposal.optional_namespaces,
+            "pairingTopic": proposal.pairing_topic,
+            "controller": { "publicKey": public_key, "metadata": self.metadata },
+            "expiry": expiry,
+        });
+        let id = self.next_id();
+        self.publish_request(
+            &session_topic,
+            id,
+            "wc_sessionSettle",
+            settle,
+            &SESSION_SETTLE,
+        )?;
+        Ok(session_topic)
+    }
+
+    /// Reject a session proposal
+    pub fn reject_session(&mut self, id: u64) -> Result<()> {
+        let proposal = self
+            .proposals
+            .remove(&id)
+            .ok_or(WalletConnectError::UnknownId(id))?;
+        self.respond(
+            &proposal.pairing_topic,
+            id,
+            Err((USER_REJECTED, "User rejected.".to_string())),
+            &SESSION_PROPOSE_RESPONSE,
+        )
+    }
+
+    /// Sign an approved request with `wallet` and send the result to the dApp
+    ///
+    /// A request the wallet cannot sign is answered with an error, which is
+    /// also returned.
+    pub fn sign_request(&mut self, id: u64, wallet: &Wallet) -> Result<()> {
+        let request = self
+            .requests
+            .remove(&id)
+            .ok_or(WalletConnectError::UnknownId(id))?;
+        let result = if chain_id(wallet.network()) == request.chain_id {
+            sign_mina_request(wallet, &request.method, &request.params)
+        } else {
+            Err(WalletConnectError::WrongChain {
+                chain: request.chain_id.clone(),
+            })
+        };
+        match result {
+            Ok(value) => self.respond(&request.topic, id, Ok(value), &SESSION_REQUEST_RESPONSE),
+            Err(e) => {
+                self.respond(
+                    &request.topic,
+                    id,
+                    Err((INVALID_PARAMS, e.to_string())),
+                    &SESSION_REQUEST_RESPONSE,
+                )?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Reject a request
+    pub fn reject_request(&mut self, id: u64) -> Result<()> {
+        let request = self
+            .requests
+            .remove(&id)
+            .ok_or(WalletConnectError::UnknownId(id))?;
+        self.respond(
+            &request.topic,
+            id,
+            Err((USER_REJECTED, "User rejected.".to_string())),
+            &SESSION_REQUEST_RESPONSE,
+        )
+    }
+
+    /// Close a session
+    pub fn disconnect(&mut self, topic: &str) -> Result<()> {
+        if !self.sessions.contains_key(topic) {
+            return Err(WalletConnectError::UnknownTopic(topic.to_string()));
+        }
+        let id = self.next_id();
+        let params = json!({ "code": USER_DISCONNECTED, "message": "User disconnected." });
+        self.publish_request(topic, id, "wc_sessionDelete", params, &SESSION_DELETE)?;
+        self.sessions.remove(topic);
+        self.requests.retain(|_, request| request.topic != topic);
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn subscribe(&mut self, topic: &str) {
+        let id = self.next_id();
+        self.outgoing.push(
+            json!({
+                "id": id,
+                "jsonrpc": "2.0",
+                "method": "irn_subscribe",
+                "params": { "topic": topic },
+            })
+            .to_string(),
+        );
+    }
+
+    fn publish_request(
+        &mut self,
+        topic: &str,
+        id: u64,
+        method: &str,
+        params: Value,
+        publish: &Publish,
+    ) -> Result<()> {
+        let payload = json!({ "id": id, "jsonrpc": "2.0", "method": method, "params": params });
+        self.publish(topic, &payload, publish)
+    }
+
+    fn respond(
+        &mut self,
+        topic: &str,
+        id: u64,
+        result: std::result::Result<Value, (i64, String)>,
+        publish: &Publish,
+    ) -> Result<()> {
+        let payload = match result {
+            Ok(result) => json!({ "id": id, "jsonrpc": "2.0", "result": result }),
+            Err((code, message)) => json!({
+                "id": id,
+                "jsonrpc": "2.0",
+                "error": { "code": code, "message": message },
+            }),
+        };
+        self.publish(topic, &payload, publish)
+    }
+
+    fn publish(&mut self, topic: &str, payload: &Value, publish: &Publish) -> Result<()> {
+        let key = self
+            .pairings
+            .get(topic)
+            .or_else(|| self.sessions.get(topic).map(|s| &s.sym_key))
+            .ok_or_else(|| WalletConnectError::UnknownTopic(topic.to_string()))?;
+        let message = encrypt(key, payload.to_string().as_bytes())?;
+        let id = self.next_id();
+        self.outgoing.push(
+            json!({
+                "id": id,
+                "jsonrpc": "2.0",
+                "method": "irn_publish",
+                "params": {
+                    "topic": topic,
+                    "message": message,
+                    "ttl": publish.ttl,
+                    "tag": publish.tag,
+                    "prompt": false,
+                },
+            })
+            .to_string(),
+        );
+        Ok(())
+    }
+
+    /// JWT signed with the relay key, as the relay requires
+    fn relay_jwt(&self, now: u64) -> String {
+        let mut sub = [0u8; KEY_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut sub);
+        let mut did = vec![0xed, 0x01];
+        did.extend_from_slice(self.relay_key.verifying_key().as_bytes());
+
+        let header = json!({ "alg": "EdDSA", "typ": "JWT" });
+        let claims = json!({
+            "iss": format!("did:key:z{}", bs58::encode(did).into_string()),
+            "sub": hex::encode(sub),
+            "aud": RELAY_URL,
+            "iat": now,
+            "exp": now + JWT_TTL,
+        });
+        let data = format!(
+            "{}.{}",
+            BASE64_URL.encode(header.to_string()),
+            BASE64_URL.encode(claims.to_string())
+        );
+        let signature = self.relay_key.sign(data.as_bytes());
+        format!("{}.{}", data, BASE64_URL.encode(signature.to_bytes()))
+    }
+}
+
+/// CAIP-2 chain ID of a network, as used in sessions
+pub fn chain_id(network: &NetworkId) -> &'static str {
+    match network {
+        NetworkId::MAINNET => "mina:mainnet",
+        NetworkId::TESTNET => "mina:devnet",
+    }
+}
+
+/// Sign a request of the `mina` namespace
+///
+/// - `mina_signMessage`: `{ message }`, answered with a `SignedMessage`
+/// - `mina_signFields`: `{ message: [fields] }`, answered with
+///   `{ data, signature }` and a Base58 signature
+/// - `mina_signTransaction`: `{ transaction }`, a payment or delegation,
+///   answered with a `SignedTransaction`
+pub fn sign_mina_request(wallet: &Wallet, method: &str, params: &Value) -> Result<Value> {
+    let invalid = WalletConnectError::InvalidRequest;
+    match method {
+        "mina_signMessage" => {
+            let message = params["message"]
+                .as_str()
+                .ok_or_else(|| invalid("no message".to_string()))?;
+            to_json(&wallet.sign_message(message))
+        }
+        "mina_signFields" => {
+            let message = params["message"]
+                .as_array()
+                .ok_or_else(|| invalid("no fields".to_string()))?;
+            let fields = message
+                .iter()
+                .map(oracle::encode_value)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| invalid(e.to_string()))?;
+            let signature = SignatureJson::from(&wallet.sign_fields(&fields))
+                .to_base58()
+                .map_err(|e| invalid(e.to_string()))?;
+            Ok(json!({ "data": message, "signature": signature }))
+        }
+        "mina_signTransaction" => {
+            let transaction: Transaction = serde_json::from_value(params["transaction"].clone())
+                .map_err(|e| invalid(format!("invalid transaction: {}", e)))?;
+            let signed = wallet
+                .sign_transaction(&transaction)
+                .map_err(|e| invalid(e.to_string()))?;
+            to_json(&signed)
+        }
+        _ => Err(WalletConnectError::UnsupportedMethod(method.to_string())),
+    }
+}
+
+/// Topic of a symmetric key: its SHA-256, in hex
+pub fn topic_of(sym_key: &[u8; KEY_LEN]) -> String {
+    hex::encode(Sha256::digest(sym_key))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Value> {
+    serde_json::to_value(value).map_err(|e| WalletConnectError::InvalidRequest(e.to_string()))
+}
+
+fn from_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T> {
+    serde_json::from_value(params).map_err(|e| WalletConnectError::InvalidMessage(e.to_string()))
+}
+
+fn decode_key(hex_key: &str) -> Option<Zeroizing<[u8; KEY_LEN]>> {
+    let bytes = Zeroizing::new(hex::decode(hex_key).ok()?);
+    let key: [u8; KEY_LEN] = bytes.as_slice().try_into().ok()?;
+    Some(Zeroizing::new(key))
+}
+
+/// Encrypt into a type 0 envelope: `0x00 || iv || ciphertext`, in base64
+fn encrypt(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<String> {
+    let mut iv = [0u8; IV_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut iv);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(&iv), plaintext)
+        .map_err(|_| WalletConnectError::InvalidRequest("encryption failed".to_string()))?;
+    let mut envelope = Vec::with_capacity(1 + IV_LEN + ciphertext.len());
+    envelope.push(ENVELOPE_TYPE_0);
+    envelope.extend_from_slice(&iv);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(envelope))
+}
+
+fn decrypt(key: &[u8; KEY_LEN], message: &str) -> Result<Vec<u8>> {
+    let envelope = BASE64
+        .decode(message)
+        .map_err(|e| WalletConnectError::InvalidMessage(format!("invalid base64: {}", e)))?;
+    match envelope.split_first() {
+        Some((&ENVELOPE_TYPE_0, rest)) if rest.len() > IV_LEN => {
+            let (iv, ciphertext) = rest.split_at(IV_LEN);
+            ChaCha20Poly1305::new(Key::from_slice(key))
+                .decrypt(Nonce::from_slice(iv), ciphertext)
+                .map_err(|_| WalletConnectError::DecryptionFailed)
+        }
+        _ => Err(WalletConnectError::InvalidMessage(
+            "unsupported envelope".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{self, SignedMessage};
+
+    /// A message of the relay delivering `payload` on `topic`
+    fn subscription(topic: &str, key: &[u8; KEY_LEN], payload: Value) -> String {
+        let message = encrypt(key, payload.to_string().as_bytes()).unwrap();
+        json!({
+            "id": 1,
+            "jsonrpc": "2.0",
+            "method": "irn_subscription",
+            "params": { "id": "1", "data": { "topic": topic, "message": message } },
+        })
+        .to_string()
+    }
+
+    /// Payloads published by the client on `topic`
+    fn published(outgoing: &[String], topic: &str, key: &[u8; KEY_LEN]) -> Vec<Value> {
+        outgoing
+            .iter()
+            .map(|m| serde_json::from_str::<Value>(m).unwrap())
+            .filter(|m| m["method"] == "irn_publish" && m["params"]["topic"] == topic)
+            .map(|m| {
+                let payload = decrypt(key, m["params"]["message"].as_str().unwrap()).unwrap();
+                serde_json::from_slice(&payload).unwrap()
+            })
+            .collect()
+    }
+
+    fn pairing_uri(key: &[u8; KEY_LEN]) -> String {
+        format!(
+            "wc:{}@2?relay-protocol=irn&symKey={}&expiryTimestamp=100",
+            topic_of(key),
+            hex::encode(key)
+        )
+    }
+
+    #[test]
+    fn test_pairing_uri() {
+        let key = [3u8; KEY_LEN];
+        let uri = PairingUri::parse(&pairing_uri(&key)).unwrap();
+        assert_eq!(uri.topic, topic_of(&key));
+        assert_eq!(*uri.sym_key, key);
+        assert_eq!(uri.relay_protocol, "irn");
+        assert_eq!(uri.expiry, Some(100));
+
+        assert!(PairingUri::parse(&pairing_uri(&key).replace("@2", "@1")).is_err());
+        assert!(
+            PairingUri::parse(&pairing_uri(&key).replace(&topic_of(&key), &"0".repeat(64)))
+                .is_err()
+        );
+        assert!(PairingUri::parse("https://example.com").is_err());
+
+        let mut client = WalletConnectClient::new(Metadata::default());
+        assert!(client.pair(&pairing_uri(&key), 100).is_err());
+        client.pair(&pairing_uri(&key), 99).unwrap();
+        assert!(client.take_outgoing()[0].contains("irn_subscribe"));
+        assert!(
+            client
+                .relay_url("abc123", 0)
+                .unwrap()
+                .starts_with(RELAY_URL)
+        );
+        assert!(client.relay_url("abc&x=1", 0).is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        let wallet = Wallet::new(NetworkId::TESTNET).unwrap();
+        let pairing_key = [5u8; KEY_LEN];
+        let pairing_topic = topic_of(&pairing_key);
+        let mut client = WalletConnectClient::new(Metadata::default());
+        client.pair(&pairing_uri(&pairing_key), 0).unwrap();
+
+        // The dApp proposes a session
+        let dapp_secret = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let dapp_public = PublicKey::from(&dapp_secret);
+        let propose = json!({
+            "id": 7,
+            "jsonrpc": "2.0",
+            "method": "wc_sessionPropose",
+            "params": {
+                "proposer": {
+                    "publicKey": hex::encode(dapp_public.as_bytes()),
+                    "metadata": { "name": "dApp", "description": "", "url": "https://dapp.example" },
+                },
+                "requiredNamespaces": { "mina": { "chains": ["mina:devnet"] } },
+            },
+        });
+        let event = client
+            .handle_message(&subscription(&pairing_topic, &pairing_key, propose), 0)
+            .unwrap();
+        assert!(matches!(
+            event,
+            Some(WalletConnectEvent::Proposal { id: 7, .. })
+        ));
+
+        let account = format!("{}:{}", chain_id(wallet.network()), wallet.address());
+        let session_topic = client.approve_session(7, &[account], 0).unwrap();
+        let outgoing = client.take_outgoing();
+        let response = &published(&outgoing, &pairing_topic, &pairing_key)[0];
+        let responder =
+            decode_key(response["result"]["responderPublicKey"].as_str().unwrap()).unwrap();
+        let shared = dapp_secret.diffie_hellman(&PublicKey::from(*responder));
+        let mut session_key = [0u8; KEY_LEN];
+        Hkdf::<Sha256>::new(None, shared.as_bytes())
+            .expand(&[], &mut session_key)
+            .unwrap();
+        assert_eq!(topic_of(&session_key), session_topic);
+        let settle = &published(&outgoing, &session_topic, &session_key)[0];
+        assert_eq!(settle["method"], "wc_sessionSettle");
+
+        // The dApp asks for a signature
+        let request = json!({
+            "id": 8,
+            "jsonrpc": "2.0",
+            "method": "wc_sessionRequest",
+            "params": {
+                "chainId": "mina:devnet",
+                "request": { "method": "mina_signMessage", "params": { "message": "hello" } },
+            },
+        });
+        let event = client
+            .handle_message(&subscription(&session_topic, &session_key, request), 1)
+            .unwrap();
+        assert!(matches!(
+            event,
+            Some(WalletConnectEvent::Request { id: 8, .. })
+        ));
+        client.sign_request(8, &wallet).unwrap();
+        let response = &published(&client.take_outgoing(), &session_topic, &session_key)[0];
+        let signed: SignedMessage = serde_json::from_value(response["result"].clone()).unwrap();
+        assert!(message::verify_message(&signed, NetworkId::TESTNET).unwrap());
+        assert!(matches!(
+            client.sign_request(8, &wallet),
+            Err(WalletConnectError::UnknownId(8))
+        ));
+    }
+}
@@ -7,13 +7,30 @@
 
 // Import WASM module
 import init, {
+    environment_check,
     generate_wallet,
+    generate_wallet_with_entropy,
     import_wallet_from_hex,
     import_wallet_from_base58,
     validate_address,
     version
 } from './pkg/mina_web_wallet_wasm.js';
 
+// Pointer positions and timings, mixed into the OS randomness of new keys in
+// case the browser RNG is compromised
+const entropyPool = new Uint8Array(1024);
+let entropyCollected = 0;
+
+function collectEntropy(event) {
+    const sample = new Uint8Array(new Float64Array([event.clientX, event.clientY, event.timeStamp]).buffer);
+    for (const byte of sample) {
+        entropyPool[entropyCollected % entropyPool.length] ^= byte;
+        entropyCollected++;
+    }
+}
+
+document.addEventListener('pointermove', collectEntropy, { passive: true });
+
 // Theme management
 const THEME_KEY = 'mina-wallet-theme';
 
@@ -78,6 +95,9 @@ async function initWasm() {
         wasmLoaded = true;
         document.getElementById('version-info').textContent = `v${version()}`;
         console.log('WASM module loaded successfully');
+        for (const warning of environment_check().warnings) {
+            console.warn(warning);
+        }
     } catch (error) {
         console.error('Failed to load WASM module:', error);
         document.getElementById('version-info').textContent = 'WASM load failed';
@@ -202,7 +222,11 @@ async function handleGenerate() {
 
     try {
         const network = document.getElementById('generate-network').value;
-        const result = generate_wallet(network);
+        const result = entropyCollected > 0
+            ? generate_wallet_with_entropy(entropyPool, network)
+            : generate_wallet(network);
+        entropyPool.fill(0);
+        entropyCollected = 0;
 
         if (result.success) {
             renderWalletResult('generate-result', result.data);
@@ -0,0 +1,143 @@
+/**
+ * Mina Web Wallet - Passkey unlock
+ *
+ * Unlocks a `WasmKeystore` with a platform authenticator instead of its
+ * passphrase. The authenticator provides a 32-byte secret, through the WebAuthn
+ * PRF extension or, where PRF is not supported, a random secret stored in the
+ * largeBlob of the credential. The WASM module wraps the keystore key with
+ * that secret; the secret itself is never stored.
+ *
+ *     await keystore.unlock(passphrase);
+ *     const registration = await registerPasskey(keystore, { userName: 'alice' });
+ *     // persist `registration` next to `keystore.exportEncrypted()`
+ *
+ *     const result = await unlockWithPasskey(keystore, registration);
+ *
+ * Both resolve to the `{ success, data, error, code }` result of the WASM
+ * module, and reject if the authenticator refused or supports neither
+ * extension.
+ */
+
+const SECRET_LEN = 32;
+
+function randomBytes(length) {
+    return crypto.getRandomValues(new Uint8Array(length));
+}
+
+function toBase64Url(bytes) {
+    const binary = String.fromCharCode(...new Uint8Array(bytes));
+    return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+}
+
+function fromBase64Url(text) {
+    const binary = atob(text.replace(/-/g, '+').replace(/_/g, '/'));
+    return Uint8Array.from(binary, (c) => c.charCodeAt(0));
+}
+
+/** Run a WebAuthn assertion on the credential, with the given extensions */
+async function assert(credentialId, extensions) {
+    const credential = await navigator.credentials.get({
+        publicKey: {
+            challenge: randomBytes(32),
+            allowCredentials: [{ type: 'public-key', id: fromBase64Url(credentialId) }],
+            userVerification: 'required',
+            extensions
+        }
+    });
+    return credential.getClientExtensionResults();
+}
+
+/** Get the secret of the authenticator for a registration */
+async function authenticatorSecret(registration) {
+    const { credentialId, method, salt } = registration;
+    let secret;
+    if (method === 'prf') {
+        const results = await assert(credentialId, {
+            prf: { eval: { first: fromBase64Url(salt) } }
+        });
+        secret = results.prf?.results?.first;
+    } else if (method === 'largeBlob') {
+        const results = await assert(credentialId, { largeBlob: { read: true } });
+        secret = results.largeBlob?.blob;
+    }
+    if (!secret || secret.byteLength !== SECRET_LEN) {
+        throw new Error('The authenticator did not return its secret');
+    }
+    return new Uint8Array(secret);
+}
+
+/**
+ * Create a passkey for an unlocked keystore, and wrap its key
+ *
+ * @returns {Promise<object>} `{ success, data: { credentialId, method, salt, wrappedKey } }`,
+ *     where `data` is to be persisted, or the error of `wrapKey`
+ */
+export async function registerPasskey(keystore, { rpName = 'Mina Web Wallet', userName }) {
+    if (keystore.isLocked) {
+        return { success: false, error: 'The keystore is locked', code: 'LOCKED' };
+    }
+    const credential = await navigator.credentials.create({
+        publicKey: {
+            challenge: randomBytes(32),
+            rp: { name: rpName },
+            user: {
+                id: randomBytes(16),
+                name: userName ?? keystore.address,
+                displayName: userName ?? keystore.address
+            },
+            pubKeyCredParams: [
+                { type: 'public-key', alg: -7 },
+                { type: 'public-key', alg: -257 }
+            ],
+            authenticatorSelection: {
+                authenticatorAttachment: 'platform',
+                residentKey: 'required',
+                userVerification: 'required'
+            },
+            extensions: { prf: {}, largeBlob: { support: 'preferred' } }
+        }
+    });
+    const results = credential.getClientExtensionResults();
+    const credentialId = toBase64Url(credential.rawId);
+
+    let registration;
+    let secret;
+    if (results.prf?.enabled) {
+        registration = { credentialId, method: 'prf', salt: toBase64Url(randomBytes(32)) };
+        secret = await authenticatorSecret(registration);
+    } else if (results.largeBlob?.supported) {
+        registration = { credentialId, method: 'largeBlob' };
+        secret = randomBytes(SECRET_LEN);
+        const written = await assert(credentialId, { largeBlob: { write: secret } });
+        if (!written.largeBlob?.written) {
+            secret.fill(0);
+            throw new Error('The authenticator did not store the secret');
+        }
+    } else {
+        throw new Error('The authenticator supports neither PRF nor largeBlob');
+    }
+
+    try {
+        const result = keystore.wrapKey(secret);
+        if (!result.success) {
+            return result;
+        }
+        return { success: true, data: { ...registration, wrappedKey: result.data } };
+    } finally {
+        secret.fill(0);
+    }
+}
+
+/**
+ * Unlock a keystore with the passkey of `registerPasskey`
+ *
+ * @returns {Promise<object>} the result of `unlockWithAuthenticator`
+ */
+export async function unlockWithPasskey(keystore, registration) {
+    const secret = await authenticatorSecret(registration);
+    try {
+        return keystore.unlockWithAuthenticator(registration.wrappedKey, secret);
+    } finally {
+        secret.fill(0);
+    }
+}
@@ -0,0 +1,39 @@
+/**
+ * Mina Web Wallet - dApp provider
+ *
+ * Installs a `MinaProvider` of the WASM module as `window.mina`, the object
+ * that dApps written for the Auro wallet talk to:
+ *
+ *     const wallet = new WasmWallet(secretKey, 'mainnet');
+ *     installProvider(wallet, {
+ *         endpoint: 'https://api.minascan.io/node/mainnet/v1/graphql',
+ *         approve: (method, params) => window.confirm(`${method}?\n${JSON.stringify(params)}`)
+ *     });
+ *
+ * `approve` is called for every connection and signature request, with the
+ * message, fields or transaction to sign, and returns or resolves to whether
+ * the user accepted it.
+ */
+
+import { MinaProvider } from './pkg/mina_web_wallet_wasm.js';
+
+export function installProvider(wallet, { endpoint, approve }) {
+    const provider = new MinaProvider(wallet, endpoint, approve);
+    uninstallProvider();
+    Object.defineProperty(window, 'mina', {
+        value: provider,
+        configurable: true,
+        enumerable: false,
+        writable: false
+    });
+    return provider;
+}
+
+/** Remove `window.mina`, e.g. before disposing of its wallet */
+export function uninstallProvider() {
+    const provider = window.mina;
+    if (provider instanceof MinaProvider) {
+        delete window.mina;
+        provider.free();
+    }
+}
@@ -0,0 +1,117 @@
+/**
+ * Mina Web Wallet - Web Worker client
+ *
+ * Promise-based access to the WASM module running in `worker.js`, so that key
+ * generation, vanity searches and batch signing do not freeze the page:
+ *
+ *     const worker = new WalletWorker();
+ *     const controller = new AbortController();
+ *     const result = await worker.generateVanityWallet('abc', 'prefix', 'mainnet', 1e7,
+ *         ({ attempts, rate }) => console.log(`${attempts} attempts, ${rate.toFixed(0)}/s`),
+ *         controller.signal
+ *     );
+ *
+ * Each method resolves to the `{ success, data, error, code }` result of the
+ * WASM module, and rejects if the worker failed to run the call.
+ */
+
+export class WalletWorker {
+    constructor(url = new URL('./worker.js', import.meta.url)) {
+        this.worker = new Worker(url, { type: 'module' });
+        this.pending = new Map();
+        this.nextId = 1;
+        this.worker.onmessage = (event) => this.handleMessage(event.data);
+        this.worker.onerror = (event) => this.rejectAll(event.message || 'The worker failed');
+    }
+
+    call(method, args, onProgress, signal) {
+        const id = this.nextId++;
+        return new Promise((resolve, reject) => {
+            this.pending.set(id, { resolve, reject, onProgress });
+            this.worker.postMessage({ id, method, args });
+            signal?.addEventListener('abort', () => this.worker.postMessage({ cancel: id }), {
+                once: true
+            });
+        });
+    }
+
+    handleMessage({ id, result, error, progress }) {
+        const call = this.pending.get(id);
+        if (!call) {
+            return;
+        }
+        if (progress !== undefined) {
+            call.onProgress?.(progress);
+            return;
+        }
+        this.pending.delete(id);
+        if (error !== undefined) {
+            call.reject(new Error(error));
+        } else {
+            call.resolve(result);
+        }
+    }
+
+    rejectAll(message) {
+        for (const call of this.pending.values()) {
+            call.reject(new Error(message));
+        }
+        this.pending.clear();
+    }
+
+    generateWallet(network) {
+        return this.call('generateWallet', [network]);
+    }
+
+    /**
+     * Search a vanity address; `onProgress` receives `{ attempts, rate,
+     * expectedAttempts }`, and aborting `signal` resolves to a `CANCELLED` error
+     */
+    generateVanityWallet(pattern, position, network, maxAttempts, onProgress, signal) {
+        return this.call(
+            'generateVanityWallet',
+            [pattern, position, network, maxAttempts],
+            onProgress,
+            signal
+        );
+    }
+
+    /** Generate `count` wallets; a `seed` makes them deterministic, for tests only */
+    generateWallets(count, network, seed) {
+        return this.call('generateWallets', [count, network, seed]);
+    }
+
+    /** Search a vanity address on every core; fails unless `isThreaded()` */
+    generateVanityWalletParallel(pattern, position, network, maxAttempts) {
+        return this.call('generateVanityWalletParallel', [pattern, position, network, maxAttempts]);
+    }
+
+    verifyTransactions(signed, network) {
+        return this.call('verifyTransactions', [signed, network]);
+    }
+
+    /** Resolves to `{ data: true }` when the worker runs the threaded build */
+    isThreaded() {
+        return this.call('isThreaded', []);
+    }
+
+    /** Open a wallet in the worker; resolves to `{ data: { handle, address } }` */
+    openWallet(secretKey, network) {
+        return this.call('openWallet', [secretKey, network]);
+    }
+
+    signBatch(handle, transactions) {
+        return this.call('signBatch', [handle, transactions]);
+    }
+
+    /** Wipe the secret key of a wallet opened with `openWallet` */
+    disposeWallet(handle) {
+        return this.call('disposeWallet', [handle]);
+    }
+
+    /** Stop the worker, discarding the wallets it holds */
+    terminate() {
+        this.worker.terminate();
+        this.rejectAll('The worker was terminated');
+    }
+}
@@ -0,0 +1,116 @@
+/**
+ * Mina Web Wallet - Web Worker
+ *
+ * Runs the heavy operations of the WASM module off the UI thread. Messages are
+ * `{ id, method, args }`; the worker answers `{ id, result }` with the result
+ * of the call, `{ id, error }` if it threw, and `{ id, progress }` while a
+ * vanity search runs. `{ cancel: id }` aborts the vanity search of call `id`.
+ *
+ * Wallets opened with `openWallet` stay in the memory of the worker until
+ * `disposeWallet`: only their handle goes back to the page.
+ *
+ * On a cross-origin isolated page, the worker loads the threaded build from
+ * `pkg-threads/` when it exists (`make build-wasm-threads`), so that batch
+ * generation, batch verification and parallel vanity searches use every core.
+ */
+
+let wasm;
+
+// The threaded build needs SharedArrayBuffer, so a cross-origin isolated page
+async function load() {
+    if (self.crossOriginIsolated) {
+        try {
+            wasm = await import('./pkg-threads/mina_web_wallet_wasm.js');
+            await wasm.default();
+            await wasm.initThreadPool(navigator.hardwareConcurrency);
+            return;
+        } catch (error) {
+            console.warn('Threaded WASM module unavailable, using the single-threaded one', error);
+        }
+    }
+    wasm = await import('./pkg/mina_web_wallet_wasm.js');
+    await wasm.default();
+}
+
+const ready = load();
+
+const wallets = new Map();
+const searches = new Map();
+let nextHandle = 1;
+
+function walletOf(handle) {
+    const wallet = wallets.get(handle);
+    if (!wallet) {
+        throw new Error(`No open wallet with handle ${handle}`);
+    }
+    return wallet;
+}
+
+const methods = {
+    generateWallet: (id, network) => wasm.generate_wallet_async(network),
+
+    generateVanityWallet: async (id, pattern, position, network, maxAttempts) => {
+        const controller = new AbortController();
+        searches.set(id, controller);
+        try {
+            return await wasm.generate_vanity_wallet_async(
+                pattern,
+                position,
+                network,
+                maxAttempts,
+                (progress) => self.postMessage({ id, progress }),
+                controller.signal
+            );
+        } finally {
+            searches.delete(id);
+        }
+    },
+
+    generateWallets: (id, count, network, seed) => wasm.generate_wallets(count, network, seed),
+
+    /** Only in the threaded build: without progress nor cancellation, but on every core */
+    generateVanityWalletParallel: (id, pattern, position, network, maxAttempts) => {
+        if (!wasm.generate_vanity_wallet_parallel) {
+            throw new Error('The threaded WASM module is not loaded');
+        }
+        return wasm.generate_vanity_wallet_parallel(pattern, position, network, maxAttempts);
+    },
+
+    verifyTransactions: (id, signed, network) => wasm.verify_transactions(signed, network),
+
+    isThreaded: () => ({ success: true, data: typeof wasm.initThreadPool === 'function' }),
+
+    openWallet: (id, secretKey, network) => {
+        const wallet = new wasm.WasmWallet(secretKey, network);
+        const handle = nextHandle++;
+        wallets.set(handle, wallet);
+        return { success: true, data: { handle, address: wallet.address() } };
+    },
+
+    signBatch: (id, handle, transactions) => wasm.sign_batch(walletOf(handle), transactions),
+
+    disposeWallet: (id, handle) => {
+        walletOf(handle).free();
+        wallets.delete(handle);
+        return { success: true };
+    }
+};
+
+self.onmessage = async (event) => {
+    const { id, method, args, cancel } = event.data;
+    if (cancel !== undefined) {
+        searches.get(cancel)?.abort();
+        return;
+    }
+    try {
+        await ready;
+        const call = methods[method];
+        if (!call) {
+            throw new Error(`Unknown method ${method}`);
+        }
+        const result = await call(id, ...args);
+        self.postMessage({ id, result });
+    } catch (error) {
+        self.postMessage({ id, error: error?.message ?? String(error) });
+    }
+};
@@ -0,0 +1,432 @@
+//! Mina Web Wallet - Node.js addon
+//!
+//! This crate exposes the signing and verification of the core crate to
+//! Node.js as a native addon, built with napi-rs, so that backend services
+//! sign without loading the WebAssembly module.
+//!
+//! Transactions and signatures use the same JSON shapes as the WebAssembly
+//! module and `mina-signer`: `{ publicKey, data, signature: { field, scalar } }`.
+//! Errors are thrown as JavaScript errors, with the status `InvalidArg` for bad
+//! input.
+//!
+//! Every `network` argument is a name, "mainnet" or "testnet", or a
+//! `NetworkConfig` `{ network, chainId?, endpoints? }`, as in the WebAssembly
+//! module. A custom chain signs with the domain of `network`, and its nodes
+//! are checked against `chainId` instead of the known chain IDs.
+//!
+//! A `MinaWallet` keeps its secret key in native memory, and wipes it when it
+//! is garbage collected. The functions talking to a node return Promises, and
+//! run on the thread pool of the addon instead of the event loop.
+
+use mina_signer::{NetworkId, PubKey};
+use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::message::{self, SignedMessage};
+use mina_web_wallet_core::network::{GraphQlClient, NetworkError, default_endpoint};
+use mina_web_wallet_core::transaction::{self, SignedTransaction, Transaction};
+use mina_web_wallet_core::wallet::network_name;
+use napi::bindgen_prelude::Either;
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+/// A network, the nodes to query and, for a custom chain, its chain ID
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// "mainnet" or "testnet": the signature domain of the chain
+    pub network: String,
+    /// Chain ID the nodes must follow, for a custom chain
+    pub chain_id: Option<String>,
+    /// GraphQL endpoints of the nodes, tried in order
+    pub endpoints: Option<Vec<String>>,
+}
+
+/// A `network` argument: a name or a `NetworkConfig`
+type NetworkArg = Either<String, NetworkConfig>;
+
+/// A validated `network` argument
+struct Network {
+    id: NetworkId,
+    chain_id: Option<String>,
+    endpoints: Vec<String>,
+}
+
+/// A wallet holding its secret key in native memory
+#[napi]
+pub struct MinaWallet {
+    wallet: Wallet,
+}
+
+#[napi]
+impl MinaWallet {
+    /// Generate a new wallet
+    ///
+    /// # Arguments
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    #[napi(factory)]
+    pub fn generate(network: NetworkArg) -> Result<Self> {
+        let wallet = Wallet::new(parse_network(network)?.id)
+            .map_err(|e| Error::from_reason(format!("Failed to generate wallet: {}", e)))?;
+        Ok(Self { wallet })
+    }
+
+    /// Import a wallet from its secret key
+    ///
+    /// # Arguments
+    /// * `secret_key` - The secret key, hex or Base58
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    #[napi(factory)]
+    pub fn from_secret_key(secret_key: String, network: NetworkArg) -> Result<Self> {
+        let secret_key = Zeroizing::new(secret_key);
+        let network = parse_network(network)?.id;
+        let wallet = Wallet::from_secret_key_strict(secret_key.trim(), network)
+            .map_err(|e| invalid(format!("Invalid secret key: {}", e)))?;
+        Ok(Self { wallet })
+    }
+
+    /// The address of the wallet
+    #[napi(getter)]
+    pub fn address(&self) -> String {
+        self.wallet.address()
+    }
+
+    /// The network of the wallet, "mainnet" or "testnet"
+    #[napi(getter)]
+    pub fn network(&self) -> String {
+        network_name(self.wallet.network()).to_string()
+    }
+
+    /// Export the secret key in Base58, e.g. to store it in a secret manager
+    #[napi]
+    pub fn export_secret_key(&self) -> String {
+        self.wallet.secret_key_base58().to_string()
+    }
+
+    /// Sign a payment or delegation sent from this wallet
+    ///
+    /// # Arguments
+    /// * `transaction` - The transaction, in the `mina-signer` JSON shape
+    ///
+    /// # Returns
+    /// `{ publicKey, data, signature: { field, scalar } }`
+    #[napi]
+    pub fn sign_payment(&self, transaction: Value) -> Result<Value> {
+        let transaction: Transaction = from_json("transaction", transaction)?;
+        let signed = self
+            .wallet
+            .sign_transaction(&transaction)
+            .map_err(|e| invalid(format!("Failed to sign: {}", e)))?;
+        to_json(&signed)
+    }
+
+    /// Sign a string message
+    ///
+    /// # Returns
+    /// `{ publicKey, data, signature: { field, scalar } }`
+    #[napi]
+    pub fn sign_message(&self, message: String) -> Result<Value> {
+        to_json(&self.wallet.sign_message(&message))
+    }
+}
+
+/// Verify a signed payment or delegation against its sender
+///
+/// # Arguments
+/// * `signed` - `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+#[napi]
+pub fn verify_transaction(signed: Value, network: NetworkArg) -> Result<bool> {
+    let network = parse_network(network)?.id;
+    let signed: SignedTransaction = from_json("signed transaction", signed)?;
+    transaction::verify_transaction(&signed, network).map_err(invalid)
+}
+
+/// Verify a signed message against the address it claims to come from
+///
+/// # Arguments
+/// * `signed` - `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+#[napi]
+pub fn verify_message(signed: Value, network: NetworkArg) -> Result<bool> {
+    let network = parse_network(network)?.id;
+    let signed: SignedMessage = from_json("signed message", signed)?;
+    message::verify_message(&signed, network).map_err(invalid)
+}
+
+/// Check that a string is a valid Mina address
+#[napi]
+pub fn validate_address(address: String) -> bool {
+    PubKey::from_address(&address).is_ok()
+}
+
+/// Fetch the balance of an address
+///
+/// # Arguments
+/// * `address` - The Mina address
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - GraphQL endpoint URL of the node, or several separated by
+///   commas; those of the `NetworkConfig`, or else the public node of the
+///   network, if omitted
+///
+/// # Returns
+/// Promise of `{ total, liquid, locked }`, in nanomina strings
+#[napi]
+pub async fn fetch_balance(
+    address: String,
+    network: NetworkArg,
+    endpoint: Option<String>,
+) -> Result<Value> {
+    let balance = parse_network(network)?
+        .client(endpoint.as_deref())
+        .get_balance(&address)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+    to_json(&balance)
+}
+
+/// Fetch the next nonce of an address, counting its pending transactions
+///
+/// # Arguments
+/// As for `fetchBalance`
+#[napi]
+pub async fn fetch_nonce(
+    address: String,
+    network: NetworkArg,
+    endpoint: Option<String>,
+) -> Result<u32> {
+    parse_network(network)?
+        .client(endpoint.as_deref())
+        .get_inferred_nonce(&address)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+/// Broadcast a signed payment or delegation, once the node is checked to
+/// follow the chain of `network`
+///
+/// # Arguments
+/// * `signed` - `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetchBalance`
+///
+/// # Returns
+/// Promise of the hash of the transaction
+#[napi]
+pub async fn broadcast(
+    signed: Value,
+    network: NetworkArg,
+    endpoint: Option<String>,
+) -> Result<String> {
+    let signed: SignedTransaction = from_json("signed transaction", signed)?;
+    let network = parse_network(network)?;
+    let client = network.client(endpoint.as_deref());
+    network
+        .check_chain_id(&client)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))?;
+    client
+        .broadcast(&signed)
+        .await
+        .map_err(|e| Error::from_reason(e.to_string()))
+}
+
+/// Resolve a network name or a `NetworkConfig`
+fn parse_network(network: NetworkArg) -> Result<Network> {
+    let config = match network {
+        Either::A(network) => NetworkConfig {
+            network,
+            chain_id: None,
+            endpoints: None,
+        },
+        Either::B(config) => config,
+    };
+    let id = match config.network.to_lowercase().as_str() {
+        "mainnet" => NetworkId::MAINNET,
+        "testnet" => NetworkId::TESTNET,
+        _ => return Err(invalid("Invalid network. Use 'mainnet' or 'testnet'.")),
+    };
+    if let Some(chain_id) = &config.chain_id
+        && (chain_id.len() != 64 || !chain_id.bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        return Err(invalid(format!(
+            "Invalid chain ID, expected 64 hex characters: {}",
+            chain_id
+        )));
+    }
+    Ok(Network {
+        id,
+        chain_id: config.chain_id,
+        endpoints: config
+            .endpoints
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|e| split_endpoints(e))
+            .collect(),
+    })
+}
+
+impl Network {
+    /// A client for the endpoints given to a call, or else those of the
+    /// configuration, or else the public node of the network
+    fn client(&self, endpoint: Option<&str>) -> GraphQlClient {
+        match split_endpoints(endpoint.unwrap_or_default()) {
+            endpoints if !endpoints.is_empty() => GraphQlClient::with_endpoints(endpoints),
+            _ if !self.endpoints.is_empty() => {
+                GraphQlClient::with_endpoints(self.endpoints.clone())
+            }
+            _ => GraphQlClient::new(default_endpoint(&self.id)),
+        }
+    }
+
+    /// Check that the node of `client` follows this chain: the `chainId` of
+    /// the configuration, or else the known chain of the network
+    async fn check_chain_id(
+        &self,
+        client: &GraphQlClient,
+    ) -> std::result::Result<String, NetworkError> {
+        let Some(expected) = &self.chain_id else {
+            return client.verify_chain_id(&self.id).await;
+        };
+        let chain_id = client.chain_id().await?;
+        if chain_id == *expected {
+            Ok(chain_id)
+        } else {
+            Err(NetworkError::ChainMismatch {
+                network: expected.clone(),
+                chain_id,
+            })
+        }
+    }
+}
+
+/// Endpoints separated by commas, trimmed, without empty ones
+fn split_endpoints(endpoints: &str) -> Vec<String> {
+    endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn from_json<T: DeserializeOwned>(what: &str, value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| invalid(format!("Invalid {}: {}", what, e)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Value> {
+    serde_json::to_value(value).map_err(|e| Error::from_reason(e.to_string()))
+}
+
+fn invalid(reason: impl ToString) -> Error {
+    Error::new(Status::InvalidArg, reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use mina_web_wallet_core::Amount;
+    use mina_web_wallet_core::transaction::MINIMUM_FEE;
+
+    use super::*;
+
+    fn name(network: &str) -> NetworkArg {
+        Either::A(network.to_string())
+    }
+
+    #[test]
+    fn test_sign_verify_round_trip() {
+        let wallet = MinaWallet::generate(name("testnet")).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address(),
+            &wallet.address(),
+            Amount::from_nanomina(1_000_000_000),
+            MINIMUM_FEE,
+            0,
+        );
+        let signed = wallet
+            .sign_payment(serde_json::to_value(&payment).unwrap())
+            .unwrap();
+        assert!(verify_transaction(signed.clone(), name("testnet")).unwrap());
+        // A signature for testnet does not verify on mainnet
+        assert!(!verify_transaction(signed, name("mainnet")).unwrap());
+
+        let signed = wallet.sign_message("hello".to_string()).unwrap();
+        assert!(verify_message(signed.clone(), name("testnet")).unwrap());
+        assert!(!verify_message(signed, name("mainnet")).unwrap());
+
+        // The secret key survives an export and an import
+        let imported =
+            MinaWallet::from_secret_key(wallet.export_secret_key(), name("testnet")).unwrap();
+        assert_eq!(imported.address(), wallet.address());
+        assert_eq!(imported.network(), "testnet");
+    }
+
+    #[test]
+    fn test_parse_network() {
+        assert!(matches!(
+            parse_network(name("Mainnet")).unwrap().id,
+            NetworkId::MAINNET
+        ));
+        assert!(parse_network(name("devnet")).is_err());
+
+        let chain_id = "a".repeat(64);
+        let network = parse_network(Either::B(NetworkConfig {
+            network: "testnet".to_string(),
+            chain_id: Some(chain_id.clone()),
+            endpoints: Some(vec![" https://a.example/graphql ".to_string()]),
+        }))
+        .unwrap();
+        assert!(matches!(network.id, NetworkId::TESTNET));
+        assert_eq!(network.chain_id, Some(chain_id));
+        assert_eq!(network.endpoints, ["https://a.example/graphql"]);
+
+        assert!(
+            parse_network(Either::B(NetworkConfig {
+                network: "testnet".to_string(),
+                chain_id: Some("not hex".to_string()),
+                endpoints: None,
+            }))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_client_endpoints() {
+        let network = parse_network(name("mainnet")).unwrap();
+        let client = network.client(Some(
+            " https://a.example/graphql, ,https://b.example/graphql",
+        ));
+        assert_eq!(
+            client.endpoints(),
+            ["https://a.example/graphql", "https://b.example/graphql"]
+        );
+        assert_eq!(
+            network.client(None).endpoints(),
+            [default_endpoint(&NetworkId::MAINNET)]
+        );
+        assert_eq!(
+            network.client(Some(" , ")).endpoints(),
+            [default_endpoint(&NetworkId::MAINNET)]
+        );
+
+        // The endpoints of a configuration, unless a call overrides them
+        let network = parse_network(Either::B(NetworkConfig {
+            network: "testnet".to_string(),
+            chain_id: None,
+            endpoints: Some(vec!["https://c.example/graphql".to_string()]),
+        }))
+        .unwrap();
+        assert_eq!(
+            network.client(None).endpoints(),
+            ["https://c.example/graphql"]
+        );
+        assert_eq!(
+            network
+                .client(Some("https://d.example/graphql"))
+                .endpoints(),
+            ["https://d.example/graphql"]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// Known-answer vectors from o1js and mina-signer, for the tests of the core
+// crate that check it against them.
+//
+//   npm install --no-save o1js mina-signer
+//   node scripts/o1js-vectors.mjs > core/tests/fixtures/o1js-vectors.json
+//
+// Until the file exists, the tests reading it are ignored; run them with
+// `cargo test -p mina-web-wallet-core -- --ignored`.
+
+import Client from 'mina-signer';
+import { Bool, Field, Poseidon, PrivateKey, Signature } from 'o1js';
+import { readFileSync } from 'node:fs';
+import { createRequire } from 'node:module';
+
+const require = createRequire(import.meta.url);
+const version = (name) =>
+    JSON.parse(readFileSync(require.resolve(`${name}/package.json`), 'utf8')).version;
+
+// Fixed keys, so that the vectors do not change between runs
+const keys = [1n, 123456789n, 2n ** 200n + 7n].map((n) => PrivateKey.fromBigInt(n));
+
+const fieldInputs = [
+    [],
+    ['0'],
+    ['1', '2'],
+    [
+        '42',
+        '0',
+        '28948022309329048855892746252171976963363056481941560715954676764349967630336'
+    ]
+];
+
+// `Poseidon.hash`, and `Poseidon.hashWithPrefix` with an empty prefix, one
+// padded to 20 characters and one of exactly 20
+function poseidon() {
+    const vectors = [];
+    for (const fields of fieldInputs) {
+        const input = fields.map(Field);
+        vectors.push({ fields, hash: Poseidon.hash(input).toString() });
+        for (const prefix of ['', 'CodaSignature', 'MinaSignatureMainnet']) {
+            const hash = Poseidon.hashWithPrefix(prefix, input).toString();
+            vectors.push({ prefix, fields, hash });
+        }
+    }
+    return vectors;
+}
+
+// `Signature.create` signs with the testnet prefix; mina-signer signs with
+// that of its network
+function signFields() {
+    const vectors = [];
+    for (const key of keys) {
+        const privateKey = key.toBase58();
+        const publicKey = key.toPublicKey().toBase58();
+        for (const fields of fieldInputs.filter((fields) => fields.length > 0)) {
+            const signature = Signature.create(key, fields.map(Field)).toBase58();
+            vectors.push({
+                source: 'o1js',
+                network: 'testnet',
+                privateKey,
+                publicKey,
+                fields,
+                signature
+            });
+            for (const network of ['mainnet', 'testnet']) {
+                const signed = new Client({ network }).signFields(
+                    fields.map(BigInt),
+                    privateKey
+                );
+                vectors.push({
+                    source: 'mina-signer',
+                    network,
+                    privateKey,
+                    publicKey,
+                    fields,
+                    signature: signed.signature
+                });
+            }
+        }
+    }
+    return vectors;
+}
+
+// Oracle responses, encoded as a zkApp reads them: integers as `Field(n)`,
+// booleans as `Bool(b).toField()`, decimal strings as `Field(s)`, object
+// values in key order, arrays flattened
+const oracleData = [
+    { id: 1, creditScore: 787 },
+    { price: '12345678901234567890', ok: true, history: [1, 2, 3] },
+    [false, { nested: { value: 0 } }]
+];
+
+function encode(value) {
+    if (Array.isArray(value)) return value.flatMap(encode);
+    if (typeof value === 'object') return Object.values(value).flatMap(encode);
+    if (typeof value === 'boolean') return [Bool(value).toField()];
+    return [Field(value)];
+}
+
+function oracle() {
+    const key = keys[1];
+    return oracleData.map((data) => {
+        const fields = encode(data);
+        return {
+            network: 'testnet',
+            privateKey: key.toBase58(),
+            publicKey: key.toPublicKey().toBase58(),
+            data,
+            fields: fields.map((field) => field.toString()),
+            signature: Signature.create(key, fields).toBase58()
+        };
+    });
+}
+
+const vectors = {
+    o1js: version('o1js'),
+    minaSigner: version('mina-signer'),
+    poseidon: poseidon(),
+    signFields: signFields(),
+    oracle: oracle()
+};
+
+console.log(JSON.stringify(vectors, null, 2));
@@ -0,0 +1,454 @@
+//! Accounts and contacts persisted in IndexedDB
+//!
+//! The keystore and the address book of the core crate, stored through
+//! `IndexedDbStorage` under their core storage keys, so that front-ends keep
+//! them across sessions without serializing them themselves. Pending
+//! transactions are persisted the same way by the broadcast queue functions.
+//!
+//! Contact pickers use `search_contacts`, which lists favorites and recently
+//! paid contacts first; `mark_contact_used` records the payments.
+//!
+//! `export_backup` seals both in the backup archive of the core crate, the
+//! format of `backup export` in the CLI, so that a wallet moves between
+//! browsers and machines; `import_backup` merges an archive into them.
+
+use mina_signer::NetworkId;
+use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::address_book::{
+    ADDRESS_BOOK_STORAGE_KEY, AddressBook, AddressBookError, Contact,
+};
+use mina_web_wallet_core::backup::{BackupArchive, BackupContents, BackupError};
+use mina_web_wallet_core::keystore::{
+    KEYSTORE_STORAGE_KEY, KdfCost, KeyEntry, Keystore, KeystoreError, MergeReport,
+};
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::storage::IndexedDbStorage;
+use crate::{ErrorCode, WasmResult, WasmWallet, resolve_network};
+
+/// Outcome of `import_backup`
+#[derive(Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct BackupImport {
+    keys: MergeReport,
+    contacts_added: usize,
+    /// Labels of the contacts left out, used here for another address
+    contact_conflicts: Vec<String>,
+}
+
+/// A key of the keystore, without its encrypted secret
+#[derive(Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct AccountData {
+    address: String,
+    label: Option<String>,
+    /// When the key was added, as a Unix timestamp in seconds
+    created_at: u64,
+    is_default: bool,
+}
+
+impl AccountData {
+    fn new(entry: &KeyEntry, keystore: &Keystore) -> Self {
+        Self {
+            address: entry.address.clone(),
+            label: entry.label.clone(),
+            created_at: entry.created_at,
+            is_default: keystore
+                .default_key()
+                .is_some_and(|default| default.address == entry.address),
+        }
+    }
+}
+
+/// List the keys of the keystore
+///
+/// # Returns
+/// Promise of a JSON array of `{ address, label, createdAt, isDefault }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<AccountData[]>")]
+pub async fn list_accounts() -> JsValue {
+    match load_keystore().await {
+        Ok((keystore, _)) => WasmResult::ok(
+            keystore
+                .keys()
+                .iter()
+                .map(|entry| AccountData::new(entry, &keystore))
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => WasmResult::<Vec<AccountData>>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Encrypt a secret key with a passphrase and add it to the keystore
+///
+/// # Arguments
+/// * `secret_key` - The secret key, hex or Base58
+/// * `passphrase` - Passphrase protecting the key
+/// * `label` - Optional name of the key
+///
+/// # Returns
+/// Promise of a JSON object `{ address, label, createdAt, isDefault }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<AccountData>")]
+pub async fn add_account(secret_key: String, passphrase: String, label: Option<String>) -> JsValue {
+    if passphrase.is_empty() {
+        return WasmResult::<AccountData>::err(
+            ErrorCode::InvalidInput,
+            "The passphrase is empty".to_string(),
+        );
+    }
+    // The address, and so the stored key, is the same on every network
+    let wallet = match Wallet::from_secret_key_strict(&secret_key, NetworkId::MAINNET) {
+        Ok(w) => w,
+        Err(e) => {
+            return WasmResult::<AccountData>::err(
+                ErrorCode::InvalidSecretHex,
+                format!("Invalid secret key: {}", e),
+            );
+        }
+    };
+    let (mut keystore, mut storage) = match load_keystore().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<AccountData>::err(ErrorCode::StorageError, e),
+    };
+    let created_at = (js_sys::Date::now() / 1000.0) as u64;
+    if let Err(e) = keystore.add(&wallet, &passphrase, label.as_deref(), created_at) {
+        return WasmResult::<AccountData>::err(keystore_error_code(&e), e.to_string());
+    }
+    if let Err(e) = commit(&mut storage, |storage| {
+        keystore.save(storage).map_err(|e| e.to_string())
+    })
+    .await
+    {
+        return WasmResult::<AccountData>::err(ErrorCode::StorageError, e);
+    }
+    match keystore.get(&wallet.address()) {
+        Some(entry) => WasmResult::ok(AccountData::new(entry, &keystore)),
+        None => WasmResult::<AccountData>::err(
+            ErrorCode::StorageError,
+            "The key was not saved".to_string(),
+        ),
+    }
+}
+
+/// Remove a key from the keystore
+///
+/// # Arguments
+/// * `name` - Label or address of the key
+///
+/// # Returns
+/// Promise of the address of the removed key
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn remove_account(name: String) -> JsValue {
+    let (mut keystore, mut storage) = match load_keystore().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<String>::err(ErrorCode::StorageError, e),
+    };
+    let entry = match keystore.remove(&name) {
+        Ok(entry) => entry,
+        Err(e) => return WasmResult::<String>::err(keystore_error_code(&e), e.to_string()),
+    };
+    match commit(&mut storage, |storage| {
+        keystore.save(storage).map_err(|e| e.to_string())
+    })
+    .await
+    {
+        Ok(()) => WasmResult::ok(entry.address),
+        Err(e) => WasmResult::<String>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Decrypt a key of the keystore into a wallet
+///
+/// # Arguments
+/// * `name` - Label or address of the key
+/// * `passphrase` - Passphrase of the key
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// Promise of a `WasmWallet`, rejected on a wrong passphrase
+#[wasm_bindgen]
+pub async fn unlock_account(
+    name: String,
+    passphrase: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> Result<WasmWallet, JsValue> {
+    let network_id = resolve_network(&network)?;
+    let (keystore, _) = load_keystore().await?;
+    keystore
+        .unlock(&name, &passphrase, network_id)
+        .map(WasmWallet::from)
+        .map_err(|e| JsValue::from_str(&format!("Failed to unlock {}: {}", name, e)))
+}
+
+/// List the contacts of the address book, sorted by label
+///
+/// # Returns
+/// Promise of a JSON array of `{ label, address, favorite?, lastUsed? }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact[]>")]
+pub async fn list_contacts() -> JsValue {
+    match load_address_book().await {
+        Ok((book, _)) => WasmResult::ok(book.contacts().to_vec()),
+        Err(e) => WasmResult::<Vec<Contact>>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Add a contact to the address book, after checking its label and address
+///
+/// # Arguments
+/// * `label` - Name of the contact, used as `@label` in place of the address
+/// * `address` - Mina address of the contact
+///
+/// # Returns
+/// Promise of the new contact
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact>")]
+pub async fn add_contact(label: String, address: String) -> JsValue {
+    edit_address_book(|book| book.add(&label, &address).cloned()).await
+}
+
+/// Change the label and address of a contact
+///
+/// # Arguments
+/// * `label` - Current name of the contact
+/// * `new_label` - New name, or the current one
+/// * `address` - New Mina address, or the current one
+///
+/// # Returns
+/// Promise of the updated contact
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact>")]
+pub async fn update_contact(label: String, new_label: String, address: String) -> JsValue {
+    edit_address_book(|book| book.update(&label, &new_label, &address).cloned()).await
+}
+
+/// Mark or unmark a contact as a favorite, listed first by `search_contacts`
+///
+/// # Returns
+/// Promise of the updated contact
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact>")]
+pub async fn set_favorite_contact(label: String, favorite: bool) -> JsValue {
+    edit_address_book(|book| book.set_favorite(&label, favorite).cloned()).await
+}
+
+/// Record a payment to an address, for the recently used contacts
+///
+/// Call it once a payment is broadcast; addresses of no contact are ignored.
+///
+/// # Returns
+/// Promise of whether a contact has the address
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+pub async fn mark_contact_used(address: String) -> JsValue {
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    edit_address_book(|book| Ok(book.mark_used(&address, now))).await
+}
+
+/// Find contacts for a contact picker
+///
+/// # Arguments
+/// * `query` - Part of the label, ignoring case, or start of the address;
+///   empty to list every contact
+/// * `limit` - Most contacts to return
+///
+/// # Returns
+/// Promise of a JSON array of contacts, favorites first, then the most
+/// recently used, then by label
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact[]>")]
+pub async fn search_contacts(query: String, limit: Option<u32>) -> JsValue {
+    match load_address_book().await {
+        Ok((book, _)) => {
+            let mut found: Vec<Contact> = book.search(&query).into_iter().cloned().collect();
+            if let Some(limit) = limit {
+                found.truncate(limit as usize);
+            }
+            WasmResult::ok(found)
+        }
+        Err(e) => WasmResult::<Vec<Contact>>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Remove a contact from the address book
+///
+/// # Arguments
+/// * `label` - Name of the contact
+///
+/// # Returns
+/// Promise of the removed contact `{ label, address }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Contact>")]
+pub async fn remove_contact(label: String) -> JsValue {
+    edit_address_book(|book| book.remove(&label)).await
+}
+
+/// Seal the keystore and the address book in an encrypted backup archive
+///
+/// Keys stay encrypted with their own passphrases inside the archive.
+///
+/// # Arguments
+/// * `passphrase` - Passphrase of the archive
+///
+/// # Returns
+/// Promise of the archive JSON, as written by `backup export` in the CLI
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn export_backup(passphrase: String) -> JsValue {
+    if passphrase.is_empty() {
+        return WasmResult::<String>::err(
+            ErrorCode::InvalidInput,
+            "The passphrase is empty".to_string(),
+        );
+    }
+    let storage =
+        match IndexedDbStorage::open(&[KEYSTORE_STORAGE_KEY, ADDRESS_BOOK_STORAGE_KEY]).await {
+            Ok(storage) => storage,
+            Err(e) => {
+                return WasmResult::<String>::err(
+                    ErrorCode::StorageError,
+                    format!("Failed to read the wallet state: {:?}", e),
+                );
+            }
+        };
+    let contents = match (Keystore::load(&storage), AddressBook::load(&storage)) {
+        (Ok(keystore), Ok(address_book)) => BackupContents {
+            keystore,
+            address_book,
+        },
+        (Err(e), _) => return WasmResult::<String>::err(ErrorCode::StorageError, e.to_string()),
+        (_, Err(e)) => return WasmResult::<String>::err(ErrorCode::StorageError, e.to_string()),
+    };
+    if contents.keystore.is_empty() && contents.address_book.is_empty() {
+        return WasmResult::<String>::err(
+            ErrorCode::NotFound,
+            "The keystore and the address book are empty, nothing to back up".to_string(),
+        );
+    }
+    let created_at = (js_sys::Date::now() / 1000.0) as u64;
+    match BackupArchive::seal(&contents, &passphrase, KdfCost::default(), created_at) {
+        Ok(archive) => WasmResult::ok(archive.to_json()),
+        Err(e) => WasmResult::<String>::err(ErrorCode::EncryptionFailed, e.to_string()),
+    }
+}
+
+/// Restore a backup archive, keeping the keys and contacts already stored
+///
+/// # Arguments
+/// * `blob` - Archive JSON from `export_backup` or `backup export` in the CLI
+/// * `passphrase` - Passphrase of the archive
+///
+/// # Returns
+/// Promise of a JSON object `{ keys: { added, skipped, droppedLabels },
+/// contactsAdded, contactConflicts }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<BackupImport>")]
+pub async fn import_backup(blob: String, passphrase: String) -> JsValue {
+    let contents = match BackupArchive::from_json(&blob).and_then(|a| a.open(&passphrase)) {
+        Ok(contents) => contents,
+        Err(e) => return WasmResult::<BackupImport>::err(backup_error_code(&e), e.to_string()),
+    };
+    let mut storage =
+        match IndexedDbStorage::open(&[KEYSTORE_STORAGE_KEY, ADDRESS_BOOK_STORAGE_KEY]).await {
+            Ok(storage) => storage,
+            Err(e) => {
+                return WasmResult::<BackupImport>::err(
+                    ErrorCode::StorageError,
+                    format!("Failed to read the wallet state: {:?}", e),
+                );
+            }
+        };
+    let (mut keystore, mut book) = match (Keystore::load(&storage), AddressBook::load(&storage)) {
+        (Ok(keystore), Ok(book)) => (keystore, book),
+        (Err(e), _) => {
+            return WasmResult::<BackupImport>::err(ErrorCode::StorageError, e.to_string());
+        }
+        (_, Err(e)) => {
+            return WasmResult::<BackupImport>::err(ErrorCode::StorageError, e.to_string());
+        }
+    };
+
+    let keys = keystore.merge(contents.keystore);
+    let contacts_before = book.len();
+    let contact_conflicts = book.merge(contents.address_book);
+    let contacts_added = book.len() - contacts_before;
+    if let Err(e) = commit(&mut storage, |storage| {
+        keystore.save(storage).map_err(|e| e.to_string())?;
+        book.save(storage).map_err(|e| e.to_string())
+    })
+    .await
+    {
+        return WasmResult::<BackupImport>::err(ErrorCode::StorageError, e);
+    }
+    WasmResult::ok(BackupImport {
+        keys,
+        contacts_added,
+        contact_conflicts,
+    })
+}
+
+/// Apply `edit` to the address book and save it if it succeeds
+async fn edit_address_book<T: Serialize>(
+    edit: impl FnOnce(&mut AddressBook) -> Result<T, AddressBookError>,
+) -> JsValue {
+    let (mut book, mut storage) = match load_address_book().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<T>::err(ErrorCode::StorageError, e),
+    };
+    let result = match edit(&mut book) {
+        Ok(result) => result,
+        Err(e) => return WasmResult::<T>::err(address_book_error_code(&e), e.to_string()),
+    };
+    match commit(&mut storage, |storage| {
+        book.save(storage).map_err(|e| e.to_string())
+    })
+    .await
+    {
+        Ok(()) => WasmResult::ok(result),
+        Err(e) => WasmResult::<T>::err(ErrorCode::StorageError, e),
+    }
+}
+
+pub(crate) async fn load_keystore() -> Result<(Keystore, IndexedDbStorage), String> {
+    let storage = IndexedDbStorage::open(&[KEYSTORE_STORAGE_KEY])
+        .await
+        .map_err(|e| format!("Failed to read the keystore: {:?}", e))?;
+    let keystore = Keystore::load(&storage).map_err(|e| e.to_string())?;
+    Ok((keystore, storage))
+}
+
+async fn load_address_book() -> Result<(AddressBook, IndexedDbStorage), String> {
+    let storage = IndexedDbStorage::open(&[ADDRESS_BOOK_STORAGE_KEY])
+        .await
+        .map_err(|e| format!("Failed to read the address book: {:?}", e))?;
+    let book = AddressBook::load(&storage).map_err(|e| e.to_string())?;
+    Ok((book, storage))
+}
+
+/// Apply `save` to the storage and write the result to IndexedDB
+pub(crate) async fn commit(
+    storage: &mut IndexedDbStorage,
+    save: impl FnOnce(&mut IndexedDbStorage) -> Result<(), String>,
+) -> Result<(), String> {
+    save(storage)?;
+    storage
+        .commit()
+        .await
+        .map_err(|e| format!("Failed to save: {:?}", e))
+}
+
+pub(crate) fn keystore_error_code(error: &KeystoreError) -> ErrorCode {
+    match error {
+        KeystoreError::KeyNotFound(_) => ErrorCode::NotFound,
+        KeystoreError::DecryptionFailed => ErrorCode::DecryptionFailed,
+        KeystoreError::Storage(_) => ErrorCode::StorageError,
+        _ => ErrorCode::InvalidInput,
+    }
+}
+
+fn backup_error_code(error: &BackupError) -> ErrorCode {
+    match error {
+        BackupError::Keystore(e) => keystore_error_code(e),
+        _ => ErrorCode::InvalidInput,
+    }
+}
+
+fn address_book_error_code(error: &AddressBookError) -> ErrorCode {
+    match error {
+        AddressBookError::ContactNotFound(_) => ErrorCode::NotFound,
+        AddressBookError::InvalidAddress { .. } => ErrorCode::InvalidAddress,
+        _ => ErrorCode::InvalidInput,
+    }
+}
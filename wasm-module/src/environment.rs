@@ -0,0 +1,99 @@
+//! Checks of the page before it handles secrets
+//!
+//! `environment_check` reports what the page runs in, so that the frontend can
+//! refuse to create or unlock keys, or pick the threaded build, before any
+//! secret reaches WebAssembly memory. It reads the global object, so it works
+//! on the main thread and in Web Workers alike.
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+/// What the page offers to, and exposes of, the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentReport {
+    /// Served over HTTPS or from localhost, as WebCrypto and WebAuthn require
+    pub secure_context: bool,
+    /// Served with COOP and COEP headers, as the threaded build requires
+    pub cross_origin_isolated: bool,
+    /// `crypto.getRandomValues` and `crypto.subtle` are available
+    pub web_crypto: bool,
+    /// Embedded in a frame of another page
+    pub framed: bool,
+    /// The WebAssembly memory can be read by scripts of the page, including
+    /// extensions injecting into it: true on the main thread, false in a Web
+    /// Worker
+    pub memory_exposed: bool,
+    /// The WebAssembly memory is a `SharedArrayBuffer`, seen by every worker
+    /// of the thread pool
+    pub shared_memory: bool,
+    /// Problems found, in English; none on a page fit to handle secrets
+    pub warnings: Vec<String>,
+}
+
+/// Check the page before handling secrets
+///
+/// # Returns
+/// JSON object `{ secureContext, crossOriginIsolated, webCrypto, framed,
+/// memoryExposed, sharedMemory, warnings }`
+#[wasm_bindgen(unchecked_return_type = "EnvironmentReport")]
+pub fn environment_check() -> JsValue {
+    let global = js_sys::global();
+    let flag = |name: &str| {
+        js_sys::Reflect::get(&global, &name.into())
+            .map(|value| value.is_truthy())
+            .unwrap_or(false)
+    };
+    let crypto = js_sys::Reflect::get(&global, &"crypto".into()).unwrap_or(JsValue::UNDEFINED);
+    let web_crypto = !crypto.is_undefined()
+        && ["getRandomValues", "subtle"].iter().all(|name| {
+            js_sys::Reflect::get(&crypto, &(*name).into())
+                .map(|value| value.is_truthy())
+                .unwrap_or(false)
+        });
+    let window = web_sys::window();
+    let framed = window.as_ref().is_some_and(|window| {
+        window
+            .top()
+            .ok()
+            .flatten()
+            .is_some_and(|top| top != *window)
+    });
+    let shared_memory = js_sys::Reflect::get(&wasm_bindgen::memory(), &"buffer".into())
+        .map(|buffer| buffer.is_instance_of::<js_sys::SharedArrayBuffer>())
+        .unwrap_or(false);
+
+    let mut report = EnvironmentReport {
+        secure_context: flag("isSecureContext"),
+        cross_origin_isolated: flag("crossOriginIsolated"),
+        web_crypto,
+        framed,
+        memory_exposed: window.is_some(),
+        shared_memory,
+        warnings: Vec::new(),
+    };
+    report.warnings = warnings(&report);
+    serde_wasm_bindgen::to_value(&report).unwrap_or(JsValue::NULL)
+}
+
+fn warnings(report: &EnvironmentReport) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !report.secure_context {
+        warnings.push("The page is not a secure context: serve it over HTTPS".to_string());
+    }
+    if !report.web_crypto {
+        warnings.push("WebCrypto is not available".to_string());
+    }
+    if report.framed {
+        warnings.push("The page is embedded in a frame of another page".to_string());
+    }
+    if report.memory_exposed {
+        warnings.push(
+            "Secrets are handled on the main thread, where extensions injecting scripts into \
+             the page can read the WebAssembly memory: use a Web Worker"
+                .to_string(),
+        );
+    }
+    warnings
+}
@@ -0,0 +1,87 @@
+//! Events of the stateful wallet
+//!
+//! A `WasmKeystore` calls the listeners registered with `onLocked`,
+//! `onUnlocked`, `onTransactionSigned`, `onBroadcast` and `onIncomingPayment`
+//! as its state changes, so that a page reacts to an auto-lock or an incoming
+//! payment without polling. Each registration returns an id for `off(id)`.
+//!
+//! Listeners are called in a microtask, once the call that changed the state
+//! has returned, so that they may call the keystore themselves. They run in
+//! the order they were registered; one throwing does not stop the others.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+/// What a listener is registered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WalletEvent {
+    /// The key was wiped, by `lock()`, the auto-lock or a hidden page
+    Locked,
+    Unlocked,
+    /// Called with the signed transaction
+    TransactionSigned,
+    /// Called with `{ hash, signed }`
+    Broadcast,
+    /// Called with the payment, as in the blocks of `subscribe_blocks`
+    IncomingPayment,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u32,
+    listeners: Vec<(u32, WalletEvent, js_sys::Function)>,
+}
+
+/// Listeners of a wallet, shared with its timers and subscriptions
+#[derive(Clone, Default)]
+pub(crate) struct Listeners(Rc<RefCell<Registry>>);
+
+impl Listeners {
+    /// Register a listener, returning its id
+    pub(crate) fn add(&self, event: WalletEvent, callback: js_sys::Function) -> u32 {
+        let mut registry = self.0.borrow_mut();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.listeners.push((id, event, callback));
+        id
+    }
+
+    /// Remove a listener, returning whether it was registered
+    pub(crate) fn remove(&self, id: u32) -> bool {
+        let mut registry = self.0.borrow_mut();
+        let before = registry.listeners.len();
+        registry.listeners.retain(|(i, _, _)| *i != id);
+        registry.listeners.len() != before
+    }
+
+    /// Remove every listener
+    pub(crate) fn clear(&self) {
+        self.0.borrow_mut().listeners.clear();
+    }
+
+    /// Call the listeners of `event` with `data`, in a microtask
+    pub(crate) fn emit(&self, event: WalletEvent, data: &JsValue) {
+        let callbacks: Vec<js_sys::Function> = self
+            .0
+            .borrow()
+            .listeners
+            .iter()
+            .filter(|(_, e, _)| *e == event)
+            .map(|(_, _, callback)| callback.clone())
+            .collect();
+        if callbacks.is_empty() {
+            return;
+        }
+        let data = data.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            for callback in callbacks {
+                if let Err(e) = callback.call1(&JsValue::NULL, &data) {
+                    web_sys::console::warn_1(
+                        &format!("{:?} listener failed: {:?}", event, e).into(),
+                    );
+                }
+            }
+        });
+    }
+}
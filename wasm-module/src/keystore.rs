@@ -0,0 +1,567 @@
+//! Passphrase-encrypted keystore for the browser
+//!
+//! A `WasmKeystore` holds one secret key encrypted with the keystore scheme of
+//! the core crate (Argon2id and ChaCha20-Poly1305, run in WebAssembly). While
+//! unlocked it keeps the key derived from the passphrase, not the secret key:
+//! each signature decrypts the secret key in WebAssembly memory, signs, and
+//! wipes it. `lock()` wipes the derived key.
+//!
+//! The keystore also locks itself: after `setAutoLock(minutes)` without an
+//! unlock or a signature, and, after `lockWhenHidden()`, when the page is
+//! hidden (`visibilitychange`).
+//!
+//! With a passkey, the derived key can also be wrapped by a secret held by a
+//! platform authenticator (`wrapKey`), so that `unlockWithAuthenticator`
+//! unlocks without the passphrase. `frontend/passkey.js` gets that secret
+//! through WebAuthn, from the PRF or largeBlob extension.
+//!
+//! Pages register listeners for its events (`onLocked`, `onUnlocked`,
+//! `onTransactionSigned`, `onBroadcast`, `onIncomingPayment`) instead of
+//! polling `isLocked`; see the `events` module.
+//!
+//! `exportEncrypted()` returns the JSON to persist, e.g. in IndexedDB; it holds
+//! nothing that can be used without the passphrase.
+
+use gloo_timers::callback::Timeout;
+use mina_signer::NetworkId;
+use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::keystore::{EncryptedSecret, KEY_LEN, KdfCost, WrappedKey};
+use mina_web_wallet_core::message::SignedMessage;
+#[cfg(feature = "network")]
+use mina_web_wallet_core::network::{GraphQlClient, SubscriptionEvent, Topic};
+use mina_web_wallet_core::transaction::{SignedTransaction, Transaction};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::events::{Listeners, WalletEvent};
+#[cfg(feature = "network")]
+use crate::{BlockSubscription, split_endpoints, subscribe};
+use crate::{ErrorCode, WasmResult, network_name, parse_network, resolve_network, secrets};
+
+const LOCKED: &str = "The keystore is locked";
+
+/// Key derived from the passphrase, shared with the auto-lock callbacks
+type SharedKey = Rc<RefCell<Option<Zeroizing<[u8; KEY_LEN]>>>>;
+
+/// The JSON of `exportEncrypted()`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedKeystore {
+    address: String,
+    network: String,
+    secret: EncryptedSecret,
+}
+
+/// A secret key encrypted with a passphrase, decrypted only to sign
+#[wasm_bindgen]
+pub struct WasmKeystore {
+    address: String,
+    network: NetworkId,
+    secret: EncryptedSecret,
+    /// Key derived from the passphrase, while unlocked
+    key: SharedKey,
+    /// Idle time after which the keystore locks itself
+    auto_lock_ms: Option<u32>,
+    /// Pending auto-lock; dropping it cancels it
+    timer: Option<Timeout>,
+    /// `visibilitychange` listener registered by `lockWhenHidden()`
+    on_hidden: Option<Closure<dyn FnMut()>>,
+    /// Listeners of the keystore events
+    events: Listeners,
+}
+
+#[wasm_bindgen]
+impl WasmKeystore {
+    /// Encrypt a secret key with a passphrase
+    ///
+    /// The keystore is locked when created.
+    ///
+    /// # Arguments
+    /// * `secret_key` - The secret key, hex or Base58
+    /// * `passphrase` - Passphrase protecting the key
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    #[wasm_bindgen(js_name = createKeystore)]
+    pub fn create(
+        secret_key: &str,
+        passphrase: &str,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> Result<WasmKeystore, JsValue> {
+        let network_id = resolve_network(&network)?;
+        if passphrase.is_empty() {
+            return Err(JsValue::from_str("The passphrase is empty"));
+        }
+        let wallet = Wallet::from_secret_key_strict(secret_key, network_id.clone())
+            .map_err(|e| JsValue::from_str(&format!("Invalid secret key: {}", e)))?;
+        let secret = EncryptedSecret::from_wallet(&wallet, passphrase, KdfCost::default())
+            .map_err(|e| JsValue::from_str(&format!("Failed to encrypt: {}", e)))?;
+        Ok(Self::locked(wallet.address(), network_id, secret))
+    }
+
+    /// Load a keystore from the JSON of `exportEncrypted()`, locked
+    #[wasm_bindgen(js_name = fromEncrypted)]
+    pub fn from_encrypted(json: &str) -> Result<WasmKeystore, JsValue> {
+        let encrypted: EncryptedKeystore = serde_json::from_str(json)
+            .map_err(|e| JsValue::from_str(&format!("Invalid keystore: {}", e)))?;
+        let network = parse_network(&encrypted.network)?;
+        mina_web_wallet_core::address_to_pubkey(&encrypted.address)
+            .map_err(|e| JsValue::from_str(&format!("Invalid address: {:?}", e)))?;
+        Ok(Self::locked(encrypted.address, network, encrypted.secret))
+    }
+
+    /// Derive the key from the passphrase, and keep it until `lock()` or the
+    /// auto-lock
+    ///
+    /// # Returns
+    /// JSON object, failing with `DECRYPTION_FAILED` on a wrong passphrase
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<null>")]
+    pub fn unlock(&mut self, passphrase: &str) -> JsValue {
+        let key = match self.secret.derive_key(passphrase) {
+            Ok(k) => k,
+            Err(e) => return WasmResult::<()>::err(ErrorCode::DecryptionFailed, e.to_string()),
+        };
+        // Check the passphrase now rather than at the first signature
+        if let Err(e) = self.decrypt(&key) {
+            return WasmResult::<()>::err(ErrorCode::DecryptionFailed, e);
+        }
+        *self.key.borrow_mut() = Some(key);
+        self.restart_timer();
+        self.events.emit(WalletEvent::Unlocked, &JsValue::NULL);
+        WasmResult::ok(())
+    }
+
+    /// Wrap the key derived from the passphrase with a secret of an
+    /// authenticator, e.g. the output of the WebAuthn PRF extension
+    ///
+    /// # Arguments
+    /// * `authenticator_secret` - The 32-byte secret of the authenticator
+    ///
+    /// # Returns
+    /// JSON object with the wrapped key as JSON, to persist next to the
+    /// keystore; it holds nothing usable without the authenticator
+    #[wasm_bindgen(js_name = wrapKey, unchecked_return_type = "WasmResult<string>")]
+    pub fn wrap_key(&self, authenticator_secret: Vec<u8>) -> JsValue {
+        let wrapping_key = match authenticator_key(authenticator_secret) {
+            Ok(k) => k,
+            Err(e) => return WasmResult::<String>::err(ErrorCode::InvalidInput, e),
+        };
+        let key = self.key.borrow();
+        let Some(key) = key.as_ref() else {
+            return WasmResult::<String>::err(ErrorCode::Locked, LOCKED.to_string());
+        };
+        match WrappedKey::wrap(key, &wrapping_key, self.address.as_bytes()) {
+            Ok(wrapped) => {
+                WasmResult::ok(serde_json::to_string(&wrapped).expect("keys serialize to JSON"))
+            }
+            Err(e) => WasmResult::<String>::err(ErrorCode::EncryptionFailed, e.to_string()),
+        }
+    }
+
+    /// Unlock with a key wrapped by `wrapKey`, instead of the passphrase
+    ///
+    /// # Arguments
+    /// * `wrapped_key` - The JSON returned by `wrapKey`
+    /// * `authenticator_secret` - The 32-byte secret of the authenticator
+    ///
+    /// # Returns
+    /// JSON object, failing with `DECRYPTION_FAILED` on another authenticator
+    #[wasm_bindgen(
+        js_name = unlockWithAuthenticator,
+        unchecked_return_type = "WasmResult<null>"
+    )]
+    pub fn unlock_with_authenticator(
+        &mut self,
+        wrapped_key: &str,
+        authenticator_secret: Vec<u8>,
+    ) -> JsValue {
+        let wrapping_key = match authenticator_key(authenticator_secret) {
+            Ok(k) => k,
+            Err(e) => return WasmResult::<()>::err(ErrorCode::InvalidInput, e),
+        };
+        let wrapped: WrappedKey = match serde_json::from_str(wrapped_key) {
+            Ok(w) => w,
+            Err(e) => {
+                return WasmResult::<()>::err(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid wrapped key: {}", e),
+                );
+            }
+        };
+        let key = match wrapped.unwrap(&wrapping_key, self.address.as_bytes()) {
+            Ok(k) => k,
+            Err(e) => return WasmResult::<()>::err(ErrorCode::DecryptionFailed, e.to_string()),
+        };
+        if let Err(e) = self.decrypt(&key) {
+            return WasmResult::<()>::err(ErrorCode::DecryptionFailed, e);
+        }
+        *self.key.borrow_mut() = Some(key);
+        self.restart_timer();
+        self.events.emit(WalletEvent::Unlocked, &JsValue::NULL);
+        WasmResult::ok(())
+    }
+
+    /// Wipe the key derived from the passphrase
+    pub fn lock(&mut self) {
+        wipe(&self.key, &self.events);
+        self.timer = None;
+    }
+
+    /// Lock the keystore for good, stop watching the page visibility and
+    /// remove the listeners
+    ///
+    /// The encrypted key stays, for `exportEncrypted()`; `free()` releases it.
+    pub fn dispose(&mut self) {
+        self.lock();
+        self.auto_lock_ms = None;
+        self.stop_watching_visibility();
+        self.events.clear();
+    }
+
+    /// Whether `unlock()` must be called before signing
+    #[wasm_bindgen(getter, js_name = isLocked)]
+    pub fn is_locked(&self) -> bool {
+        self.key.borrow().is_none()
+    }
+
+    /// Lock the keystore after some minutes without an unlock or a signature
+    ///
+    /// # Arguments
+    /// * `minutes` - Idle time before locking, 0 to disable the auto-lock
+    #[wasm_bindgen(js_name = setAutoLock)]
+    pub fn set_auto_lock(&mut self, minutes: f64) -> Result<(), JsValue> {
+        let ms = minutes * 60_000.0;
+        if !ms.is_finite() || ms < 0.0 || ms > f64::from(u32::MAX) {
+            return Err(JsValue::from_str(&format!(
+                "Invalid auto-lock delay: {} minutes",
+                minutes
+            )));
+        }
+        self.auto_lock_ms = (ms >= 1.0).then_some(ms as u32);
+        self.restart_timer();
+        Ok(())
+    }
+
+    /// Lock the keystore whenever the page is hidden, e.g. when the user
+    /// switches tabs or minimizes the browser
+    #[wasm_bindgen(js_name = lockWhenHidden)]
+    pub fn lock_when_hidden(&mut self) -> Result<(), JsValue> {
+        if self.on_hidden.is_some() {
+            return Ok(());
+        }
+        let document = document()?;
+        let key = Rc::clone(&self.key);
+        let events = self.events.clone();
+        let hidden = document.clone();
+        let on_hidden = Closure::<dyn FnMut()>::new(move || {
+            if hidden.hidden() {
+                wipe(&key, &events);
+            }
+        });
+        document.add_event_listener_with_callback(
+            "visibilitychange",
+            on_hidden.as_ref().unchecked_ref(),
+        )?;
+        self.on_hidden = Some(on_hidden);
+        Ok(())
+    }
+
+    /// The address of the key
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// JSON of the encrypted key, to persist and load with `fromEncrypted`
+    #[wasm_bindgen(js_name = exportEncrypted)]
+    pub fn export_encrypted(&self) -> String {
+        let encrypted = EncryptedKeystore {
+            address: self.address.clone(),
+            network: network_name(&self.network).to_string(),
+            secret: self.secret.clone(),
+        };
+        serde_json::to_string(&encrypted).expect("the keystore serializes to JSON")
+    }
+
+    /// Sign a payment or delegation sent from the key
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signPayment, unchecked_return_type = "WasmResult<SignedTransaction>")]
+    pub fn sign_payment(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+    ) -> JsValue {
+        let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+            Ok(t) => t,
+            Err(e) => {
+                return WasmResult::<SignedTransaction>::err(
+                    ErrorCode::InvalidTransaction,
+                    format!("Invalid transaction: {}", e),
+                );
+            }
+        };
+        let wallet = match self.unlocked_wallet() {
+            Ok(w) => w,
+            Err((code, e)) => return WasmResult::<SignedTransaction>::err(code, e),
+        };
+        match wallet.sign_transaction(&transaction) {
+            Ok(signed) => {
+                if let Ok(value) = serde_wasm_bindgen::to_value(&signed) {
+                    self.events.emit(WalletEvent::TransactionSigned, &value);
+                }
+                WasmResult::ok(signed)
+            }
+            Err(e) => {
+                WasmResult::<SignedTransaction>::transaction_err(&transaction, "Failed to sign", e)
+            }
+        }
+    }
+
+    /// Broadcast a transaction signed by the key to a node
+    ///
+    /// # Arguments
+    /// * `signed` - JSON object returned by `signPayment`
+    /// * `endpoint` - GraphQL endpoint URL of the node, or several separated by commas
+    ///
+    /// # Returns
+    /// Promise of a JSON object with the transaction hash
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(unchecked_return_type = "Promise<WasmResult<string>>")]
+    pub fn broadcast(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "SignedTransaction")] signed: JsValue,
+        endpoint: String,
+    ) -> js_sys::Promise {
+        let parsed = serde_wasm_bindgen::from_value::<SignedTransaction>(signed.clone());
+        let address = self.address.clone();
+        let events = self.events.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let parsed = match parsed {
+                Ok(s) => s,
+                Err(e) => {
+                    return Ok(WasmResult::<String>::err(
+                        ErrorCode::InvalidTransaction,
+                        format!("Invalid signed transaction: {}", e),
+                    ));
+                }
+            };
+            if parsed.public_key != address {
+                return Ok(WasmResult::<String>::err(
+                    ErrorCode::InvalidTransaction,
+                    format!("The transaction is not signed by {}", address),
+                ));
+            }
+            match GraphQlClient::with_endpoints(split_endpoints(&endpoint))
+                .broadcast(&parsed)
+                .await
+            {
+                Ok(hash) => {
+                    let data = js_sys::Object::new();
+                    js_sys::Reflect::set(&data, &"hash".into(), &hash.as_str().into())?;
+                    js_sys::Reflect::set(&data, &"signed".into(), &signed)?;
+                    events.emit(WalletEvent::Broadcast, &data);
+                    Ok(WasmResult::ok(hash))
+                }
+                Err(e) => Ok(WasmResult::<String>::err(
+                    ErrorCode::BroadcastFailed,
+                    format!("Failed to broadcast transaction: {}", e),
+                )),
+            }
+        })
+    }
+
+    /// Watch the blocks of a node for payments received by the key, calling
+    /// the `onIncomingPayment` listeners with each
+    ///
+    /// # Arguments
+    /// * `endpoint` - Websocket endpoint URL of the node
+    ///
+    /// # Returns
+    /// The subscription, to `close()` when done
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(js_name = watchIncomingPayments)]
+    pub fn watch_incoming_payments(&self, endpoint: &str) -> Result<BlockSubscription, JsValue> {
+        let address = self.address.clone();
+        let events = self.events.clone();
+        subscribe(endpoint, Topic::Account(address.clone()), move |event| {
+            if let SubscriptionEvent::Block(block) = event {
+                for payment in block.incoming_payments(&address) {
+                    if let Ok(value) = serde_wasm_bindgen::to_value(payment) {
+                        events.emit(WalletEvent::IncomingPayment, &value);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Call `callback` whenever the keystore locks, on `lock()`, the
+    /// auto-lock or a hidden page
+    ///
+    /// # Returns
+    /// The id of the listener, for `off`
+    #[wasm_bindgen(js_name = onLocked)]
+    pub fn on_locked(&self, callback: js_sys::Function) -> u32 {
+        self.events.add(WalletEvent::Locked, callback)
+    }
+
+    /// Call `callback` whenever the keystore is unlocked
+    #[wasm_bindgen(js_name = onUnlocked)]
+    pub fn on_unlocked(&self, callback: js_sys::Function) -> u32 {
+        self.events.add(WalletEvent::Unlocked, callback)
+    }
+
+    /// Call `callback` with every transaction signed by `signPayment`
+    #[wasm_bindgen(js_name = onTransactionSigned)]
+    pub fn on_transaction_signed(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "(signed: SignedTransaction) => void")]
+        callback: js_sys::Function,
+    ) -> u32 {
+        self.events.add(WalletEvent::TransactionSigned, callback)
+    }
+
+    /// Call `callback` with `{ hash, signed }` for every transaction broadcast
+    /// by `broadcast`
+    #[wasm_bindgen(js_name = onBroadcast)]
+    pub fn on_broadcast(
+        &self,
+        #[wasm_bindgen(
+            unchecked_param_type = "(event: { hash: string; signed: SignedTransaction }) => void"
+        )]
+        callback: js_sys::Function,
+    ) -> u32 {
+        self.events.add(WalletEvent::Broadcast, callback)
+    }
+
+    /// Call `callback` with every payment received, once
+    /// `watchIncomingPayments` is called
+    #[wasm_bindgen(js_name = onIncomingPayment)]
+    pub fn on_incoming_payment(&self, callback: js_sys::Function) -> u32 {
+        self.events.add(WalletEvent::IncomingPayment, callback)
+    }
+
+    /// Remove a listener
+    ///
+    /// # Returns
+    /// Whether the listener was registered
+    pub fn off(&self, id: u32) -> bool {
+        self.events.remove(id)
+    }
+
+    /// Sign a string message with the key
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signMessage, unchecked_return_type = "WasmResult<SignedMessage>")]
+    pub fn sign_message(&mut self, message: &str) -> JsValue {
+        match self.unlocked_wallet() {
+            Ok(wallet) => WasmResult::ok(wallet.sign_message(message)),
+            Err((code, e)) => WasmResult::<SignedMessage>::err(code, e),
+        }
+    }
+}
+
+impl WasmKeystore {
+    fn locked(address: String, network: NetworkId, secret: EncryptedSecret) -> Self {
+        let key = SharedKey::default();
+        secrets::register("keystore", &key, |key| key.borrow().is_some());
+        Self {
+            address,
+            network,
+            secret,
+            key,
+            auto_lock_ms: None,
+            timer: None,
+            on_hidden: None,
+            events: Listeners::default(),
+        }
+    }
+
+    /// Remove the `visibilitychange` listener of `lockWhenHidden()`
+    fn stop_watching_visibility(&mut self) {
+        if let (Some(on_hidden), Ok(document)) = (self.on_hidden.take(), document()) {
+            let _ = document.remove_event_listener_with_callback(
+                "visibilitychange",
+                on_hidden.as_ref().unchecked_ref(),
+            );
+        }
+    }
+
+    /// Decrypt the secret key for one operation; dropping the wallet wipes it
+    ///
+    /// Using the key postpones the auto-lock.
+    fn unlocked_wallet(&mut self) -> Result<Wallet, (ErrorCode, String)> {
+        let wallet = {
+            let key = self.key.borrow();
+            let key = key
+                .as_ref()
+                .ok_or_else(|| (ErrorCode::Locked, LOCKED.to_string()))?;
+            self.decrypt(key)
+                .map_err(|e| (ErrorCode::DecryptionFailed, e))?
+        };
+        self.restart_timer();
+        Ok(wallet)
+    }
+
+    /// Schedule the auto-lock from now, replacing the pending one
+    fn restart_timer(&mut self) {
+        self.timer = match self.auto_lock_ms {
+            Some(ms) if !self.is_locked() => {
+                let key = Rc::clone(&self.key);
+                let events = self.events.clone();
+                Some(Timeout::new(ms, move || wipe(&key, &events)))
+            }
+            _ => None,
+        };
+    }
+
+    fn decrypt(&self, key: &[u8; KEY_LEN]) -> Result<Wallet, String> {
+        let secret = self
+            .secret
+            .decrypt_with_key(key, self.address.as_bytes())
+            .map_err(|e| e.to_string())?;
+        let secret =
+            std::str::from_utf8(&secret).map_err(|_| "The secret key is not hex".to_string())?;
+        let wallet =
+            Wallet::from_secret_key_hex(secret, self.network.clone()).map_err(|e| e.to_string())?;
+        if wallet.address() != self.address {
+            return Err("The secret key does not match the address".to_string());
+        }
+        Ok(wallet)
+    }
+}
+
+impl Drop for WasmKeystore {
+    fn drop(&mut self) {
+        self.stop_watching_visibility();
+    }
+}
+
+/// Wipe the key, telling the listeners if it was unlocked
+fn wipe(key: &SharedKey, events: &Listeners) {
+    let wiped = key.borrow_mut().take().is_some();
+    if wiped {
+        events.emit(WalletEvent::Locked, &JsValue::NULL);
+    }
+}
+
+/// Check the length of an authenticator secret, and wipe the copy passed in
+fn authenticator_key(secret: Vec<u8>) -> Result<Zeroizing<[u8; KEY_LEN]>, String> {
+    let secret = Zeroizing::new(secret);
+    let key: [u8; KEY_LEN] = secret.as_slice().try_into().map_err(|_| {
+        format!(
+            "The authenticator secret must be {} bytes, got {}",
+            KEY_LEN,
+            secret.len()
+        )
+    })?;
+    Ok(Zeroizing::new(key))
+}
+
+fn document() -> Result<web_sys::Document, JsValue> {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| JsValue::from_str("No document available"))
+}
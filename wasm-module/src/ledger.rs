@@ -0,0 +1,278 @@
+//! Ledger hardware wallets over WebHID
+//!
+//! The Mina app of a Ledger device signs payments with keys that never leave
+//! the device, once the user confirms them on its screen. The messages are
+//! built, framed and checked by `mina_web_wallet_core::ledger`, as in the CLI;
+//! this module only carries the HID reports through `navigator.hid`, and needs
+//! the `ledger` feature.
+//!
+//! WebHID is available in Chromium browsers, on secure pages. The first call
+//! asks the user to pick the device, so it must follow a user gesture such as
+//! a click; later calls reuse the device the page was granted. The device
+//! answers one request at a time, so calls wait for the previous one to
+//! settle, and fail if the device does not answer in time.
+
+use gloo_timers::callback::Timeout;
+use js_sys::{Array, Promise, Uint8Array};
+use mina_web_wallet_core::ledger::{self, Deframer, LEDGER_VENDOR_ID, LedgerError};
+use mina_web_wallet_core::transaction::{SignedTransaction, Transaction};
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{ErrorCode, WasmResult, resolve_network};
+
+/// Time the device has to answer a request that needs no confirmation, in
+/// milliseconds
+const ANSWER_TIMEOUT_MS: u32 = 30_000;
+
+/// Time the user has to confirm a payment on the device, in milliseconds
+const CONFIRM_TIMEOUT_MS: u32 = 300_000;
+
+thread_local! {
+    /// Settles once the last exchange started so far is over
+    static LAST_EXCHANGE: RefCell<Option<Promise>> = const { RefCell::new(None) };
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// `navigator.hid`
+    type Hid;
+
+    #[wasm_bindgen(method, js_name = getDevices)]
+    fn get_devices(this: &Hid) -> Promise;
+
+    #[wasm_bindgen(method, js_name = requestDevice)]
+    fn request_device(this: &Hid, options: &JsValue) -> Promise;
+
+    /// A HID device the page was granted
+    type HidDevice;
+
+    #[wasm_bindgen(method, getter)]
+    fn opened(this: &HidDevice) -> bool;
+
+    #[wasm_bindgen(method)]
+    fn open(this: &HidDevice) -> Promise;
+
+    #[wasm_bindgen(method)]
+    fn close(this: &HidDevice) -> Promise;
+
+    #[wasm_bindgen(method, js_name = sendReport)]
+    fn send_report(this: &HidDevice, report_id: u8, data: &[u8]) -> Promise;
+
+    #[wasm_bindgen(method, setter)]
+    fn set_oninputreport(this: &HidDevice, listener: Option<&js_sys::Function>);
+
+    /// The `inputreport` event of a device
+    type HidInputReportEvent;
+
+    #[wasm_bindgen(method, getter)]
+    fn data(this: &HidInputReportEvent) -> js_sys::DataView;
+}
+
+/// Read the address of a Ledger account
+///
+/// # Arguments
+/// * `index` - Account index N of the path `m/44'/12586'/N'/0/0`
+///
+/// # Returns
+/// Promise of a JSON object with the address
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn ledger_get_address(index: u32) -> JsValue {
+    let response = match exchange(&ledger::get_address_apdu(index), ANSWER_TIMEOUT_MS).await {
+        Ok(response) => response,
+        Err(e) => return WasmResult::<String>::err(ErrorCode::DeviceError, e),
+    };
+    match ledger::parse_address(&response) {
+        Ok(address) => WasmResult::ok(address),
+        Err(e) => WasmResult::<String>::err(error_code(&e), e.to_string()),
+    }
+}
+
+/// Sign a payment or delegation on a Ledger device, once confirmed there
+///
+/// The signature is checked against the transaction before it is returned.
+///
+/// # Arguments
+/// * `transaction` - The transaction, in the `mina-signer` JSON shape
+/// * `index` - Account index of the sender
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// Promise of a JSON object `{ publicKey, data, signature: { field, scalar } }`,
+/// failing with `CANCELLED` when rejected on the device
+#[wasm_bindgen(unchecked_return_type = "WasmResult<SignedTransaction>")]
+pub async fn ledger_sign_payment(
+    #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+    index: u32,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<SignedTransaction>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+        Ok(t) => t,
+        Err(e) => {
+            return WasmResult::<SignedTransaction>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid transaction: {}", e),
+            );
+        }
+    };
+    let apdu = match ledger::sign_transaction_apdu(&transaction, index, &network) {
+        Ok(apdu) => apdu,
+        Err(LedgerError::Transaction(e)) => {
+            return WasmResult::<SignedTransaction>::transaction_err(
+                &transaction,
+                "Invalid transaction",
+                e,
+            );
+        }
+        Err(e) => return WasmResult::<SignedTransaction>::err(error_code(&e), e.to_string()),
+    };
+    let response = match exchange(&apdu, CONFIRM_TIMEOUT_MS).await {
+        Ok(response) => response,
+        Err(e) => return WasmResult::<SignedTransaction>::err(ErrorCode::DeviceError, e),
+    };
+    match ledger::parse_signature(&response, &transaction, network) {
+        Ok(signed) => WasmResult::ok(signed),
+        Err(e) => WasmResult::<SignedTransaction>::err(error_code(&e), e.to_string()),
+    }
+}
+
+/// Send an APDU to the device and wait at most `timeout_ms` for the answer,
+/// once the exchanges started before are over
+///
+/// Exchanges are serialized, as each one listens to the input reports of the
+/// device in turn: two at once would take each other's answers.
+async fn exchange(apdu: &[u8], timeout_ms: u32) -> Result<Vec<u8>, String> {
+    let mut release = None;
+    let done = Promise::new(&mut |resolve, _| release = Some(resolve));
+    let release = release.expect("the executor has run");
+    let previous = LAST_EXCHANGE.with(|last| last.borrow_mut().replace(done));
+    if let Some(previous) = previous {
+        // Only the end of the previous exchange matters, not its outcome
+        let _ = JsFuture::from(previous).await;
+    }
+    let response = exchange_now(apdu, timeout_ms).await;
+    let _ = release.call0(&JsValue::NULL);
+    response
+}
+
+async fn exchange_now(apdu: &[u8], timeout_ms: u32) -> Result<Vec<u8>, String> {
+    let device = device().await?;
+    if !device.opened() {
+        JsFuture::from(device.open())
+            .await
+            .map_err(|e| format!("Cannot open the Ledger device: {:?}", e))?;
+    }
+
+    // The executor of a promise runs right away
+    let mut settle = None;
+    let answer = Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+    let (resolve, reject) = settle.expect("the executor has run");
+    let timeout = {
+        let reject = reject.clone();
+        Timeout::new(timeout_ms, move || {
+            let _ = reject.call1(
+                &JsValue::NULL,
+                &"The Ledger device did not answer in time".into(),
+            );
+        })
+    };
+    let mut deframer = Deframer::new();
+    let on_report =
+        Closure::<dyn FnMut(HidInputReportEvent)>::new(move |event: HidInputReportEvent| {
+            let data = event.data();
+            let packet = Uint8Array::new_with_byte_offset_and_length(
+                &data.buffer(),
+                data.byte_offset() as u32,
+                data.byte_length() as u32,
+            )
+            .to_vec();
+            let _ = match deframer.push(&packet) {
+                Ok(Some(response)) => {
+                    resolve.call1(&JsValue::NULL, &Uint8Array::from(&response[..]))
+                }
+                Ok(None) => Ok(JsValue::UNDEFINED),
+                Err(e) => reject.call1(
+                    &JsValue::NULL,
+                    &format!("Invalid answer from the Ledger device: {}", e).into(),
+                ),
+            };
+        });
+    // Listen before sending, so that no report is missed
+    device.set_oninputreport(Some(on_report.as_ref().unchecked_ref()));
+
+    let response = async {
+        for packet in ledger::frame(apdu) {
+            // Ledger devices use no report IDs
+            JsFuture::from(device.send_report(0, &packet))
+                .await
+                .map_err(|e| format!("Cannot write to the Ledger device: {:?}", e))?;
+        }
+        JsFuture::from(answer)
+            .await
+            .map(|response| Uint8Array::new(&response).to_vec())
+            .map_err(|e| e.as_string().unwrap_or_else(|| format!("{:?}", e)))
+    }
+    .await;
+    // Dropping the timeout cancels it
+    drop(timeout);
+    device.set_oninputreport(None);
+    if response.is_err() {
+        // A late answer must not be read as that of the next exchange
+        let _ = JsFuture::from(device.close()).await;
+    }
+    response
+}
+
+/// The Ledger device granted to the page, or else the one the user picks
+async fn device() -> Result<HidDevice, String> {
+    let navigator = js_sys::Reflect::get(&js_sys::global(), &"navigator".into())
+        .map_err(|_| "No navigator available".to_string())?;
+    let hid = js_sys::Reflect::get(&navigator, &"hid".into()).unwrap_or(JsValue::UNDEFINED);
+    if hid.is_undefined() {
+        return Err("WebHID is not available: use a Chromium browser on a secure page".to_string());
+    }
+    let hid: Hid = hid.unchecked_into();
+
+    let granted = JsFuture::from(hid.get_devices())
+        .await
+        .map_err(|e| format!("Cannot list HID devices: {:?}", e))?;
+    let ledger = |devices: &JsValue| {
+        Array::from(devices).iter().find(|device| {
+            js_sys::Reflect::get(device, &"vendorId".into())
+                .ok()
+                .and_then(|id| id.as_f64())
+                == Some(f64::from(LEDGER_VENDOR_ID))
+        })
+    };
+    if let Some(device) = ledger(&granted) {
+        return Ok(device.unchecked_into());
+    }
+
+    let filter = js_sys::Object::new();
+    js_sys::Reflect::set(&filter, &"vendorId".into(), &LEDGER_VENDOR_ID.into())
+        .map_err(|e| format!("{:?}", e))?;
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &"filters".into(), &Array::of1(&filter))
+        .map_err(|e| format!("{:?}", e))?;
+    let picked = JsFuture::from(hid.request_device(&options))
+        .await
+        .map_err(|e| format!("Cannot request a HID device: {:?}", e))?;
+    ledger(&picked).map(JsCast::unchecked_into).ok_or_else(|| {
+        "No Ledger device selected: connect and unlock it, and open the Mina app".to_string()
+    })
+}
+
+fn error_code(error: &LedgerError) -> ErrorCode {
+    match error {
+        LedgerError::Rejected => ErrorCode::Cancelled,
+        LedgerError::Locked => ErrorCode::Locked,
+        LedgerError::InvalidSignature => ErrorCode::InvalidSignature,
+        LedgerError::Transaction(_) => ErrorCode::InvalidTransaction,
+        _ => ErrorCode::SigningFailed,
+    }
+}
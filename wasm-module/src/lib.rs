@@ -2,12 +2,134 @@
 //!
 //! This module exposes the Mina wallet functionality to JavaScript/TypeScript
 //! through WebAssembly bindings.
+//!
+//! Signing goes through `WasmWallet`, which keeps the secret key in
+//! WebAssembly memory from its construction until `dispose()`, instead of
+//! passing it from JavaScript on every call. `WasmKeystore` goes further:
+//! it keeps the key encrypted with a passphrase, and decrypts it only to sign.
+//! Every object holding secrets has a `dispose()` wiping them, and
+//! `assert_no_resident_secrets()` checks that none is left.
+//!
+//! Results are plain objects, declared in the generated TypeScript as
+//! `WasmResult<T>` over the JSON types of the core crate (`Transaction`,
+//! `SignedTransaction`, `SignedMessage`, ...), so that TypeScript callers
+//! get them checked instead of `any`.
+//!
+//! Heavy operations have Promise-based variants (`generate_wallet_async`,
+//! `generate_vanity_wallet_async`, `sign_batch`) that yield to the event loop
+//! while they run. `frontend/worker.js` runs them in a Web Worker, behind the
+//! `WalletWorker` client of `frontend/wallet-worker.js`.
+//!
+//! The `threads` feature builds a variant backed by a rayon thread pool on Web
+//! Workers, started with `initThreadPool`, for pages served cross-origin
+//! isolated. `generate_wallets`, `verify_transactions` and
+//! `generate_vanity_wallet_parallel` then use every core.
+//!
+//! Accounts, contacts and the broadcast queue persist in IndexedDB, through
+//! the `Storage` trait of the core crate (`list_accounts`, `add_contact`, ...).
+//! `export_backup` and `import_backup` move accounts and contacts between
+//! devices, in the encrypted backup format of the CLI. `WasmAccountManager`
+//! keeps several of those accounts unlocked at once, each with its own lock.
+//!
+//! `import_watch_only` tracks an address without its key: balances, history,
+//! unsigned payments and signature checks, but no signing.
+//!
+//! Every `network` argument takes "mainnet", "testnet" or a `NetworkConfig`
+//! from `network_config`, which also carries the endpoints of the nodes and
+//! the chain ID of a custom chain. Functions talking to a node take such a
+//! `network` and an optional endpoint overriding those of the configuration.
+//! They are behind the `network` feature, on by default: without it, the
+//! module only signs and verifies, and never talks to a node.
+//!
+//! `MinaProvider` answers the `window.mina` requests of dApps written for the
+//! Auro wallet. `WalletConnect` does the same for dApps on other devices,
+//! over a WalletConnect v2 relay.
 
-use mina_signer::NetworkId;
-use mina_web_wallet_core::Wallet;
+use mina_signer::{NetworkId, PubKey, Signature};
+use mina_web_wallet_core::base58;
+use mina_web_wallet_core::consensus::ConsensusConstants;
+use mina_web_wallet_core::convert;
+use mina_web_wallet_core::encryption;
+use mina_web_wallet_core::entropy;
+use mina_web_wallet_core::envelope::TransactionEnvelope;
+use mina_web_wallet_core::fields::Fp;
+use mina_web_wallet_core::inspect::{self, TransactionPreview};
+use mina_web_wallet_core::keystore::Keystore;
+use mina_web_wallet_core::login::{self, LoginChallenge};
+use mina_web_wallet_core::message::{self, SignedMessage};
+#[cfg(feature = "network")]
+use mina_web_wallet_core::network::{
+    ArchiveClient, Backend, BackendKind, Balance, FeeSuggestion, GraphQlClient, HistoryPage,
+    NetworkError, NodeStatus, PendingReport, StakingInfo, StakingLedgerEntry, SubscriptionClient,
+    SubscriptionEvent, Topic, TxStatus, TxTracker, ZkappState, graphql, staking, subscription,
+};
+use mina_web_wallet_core::oracle::{self, FieldElements};
+use mina_web_wallet_core::paper::PaperWallet;
+use mina_web_wallet_core::poseidon;
+use mina_web_wallet_core::qr::{self, QrFormat};
+#[cfg(feature = "network")]
+use mina_web_wallet_core::queue::FlushReport;
+use mina_web_wallet_core::queue::{BroadcastQueue, QUEUE_STORAGE_KEY};
+use mina_web_wallet_core::rewards::{self, RewardEstimate, RewardParameters};
+use mina_web_wallet_core::seed;
+use mina_web_wallet_core::signing::{self, SignatureJson, SignatureScheme};
+use mina_web_wallet_core::test_vectors;
+use mina_web_wallet_core::transaction::{
+    self, PaymentPreview, SignedTransaction, Transaction, TransactionError, TransactionKind,
+};
+use mina_web_wallet_core::ur::{self, UrDecoder};
+use mina_web_wallet_core::vanity::{self, CancellationToken, VanityError, VanityPosition};
+use mina_web_wallet_core::verification_key::VerificationKey;
+use mina_web_wallet_core::{Amount, Wallet, sss};
 use o1_utils::field_helpers::FieldHelpers;
+#[cfg(feature = "threads")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "network")]
+use std::cell::RefCell;
+use std::rc::Rc;
+use tsify::Tsify;
 use wasm_bindgen::prelude::*;
+#[cfg(feature = "network")]
+use web_sys::{MessageEvent, WebSocket};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+mod accounts;
+mod environment;
+mod events;
+mod keystore;
+#[cfg(feature = "ledger")]
+mod ledger;
+mod manager;
+mod network_config;
+#[cfg(feature = "network")]
+mod provider;
+mod secrets;
+mod storage;
+#[cfg(feature = "threads")]
+mod threads;
+mod walletconnect;
+mod watch_only;
+
+pub use environment::{EnvironmentReport, environment_check};
+pub use keystore::WasmKeystore;
+#[cfg(feature = "ledger")]
+pub use ledger::{ledger_get_address, ledger_sign_payment};
+pub use manager::WasmAccountManager;
+#[cfg(feature = "network")]
+use network_config::NO_ENDPOINT;
+use network_config::{Network, resolve_network};
+pub use network_config::{NetworkConfig, network_config};
+#[cfg(feature = "network")]
+pub use provider::MinaProvider;
+pub use secrets::{assert_no_resident_secrets, resident_secrets};
+use storage::IndexedDbStorage;
+#[cfg(feature = "threads")]
+pub use threads::generate_vanity_wallet_parallel;
+pub use walletconnect::WalletConnect;
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+pub use watch_only::{WasmWatchOnly, import_watch_only};
 
 /// Initialize panic hook for better error messages in browser console
 #[wasm_bindgen(start)]
@@ -16,11 +138,63 @@ pub fn init() {
 }
 
 /// Result type for WASM operations
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Tsify)]
 pub struct WasmResult<T> {
     pub success: bool,
     pub data: Option<T>,
+    /// Message of the failure, in English
     pub error: Option<String>,
+    /// Kind of the failure, for callers to branch on or localize
+    pub code: Option<ErrorCode>,
+    /// Invalid inputs, when the failure comes from their validation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// Machine-readable kind of a failed WASM operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Tsify)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The network is neither "mainnet" nor "testnet"
+    InvalidNetwork,
+    InvalidSecretHex,
+    InvalidSecretBase58,
+    InvalidAddress,
+    InvalidAmount,
+    /// The transaction is malformed or fails validation; see `details`
+    InvalidTransaction,
+    /// The signature is malformed, as opposed to not matching
+    InvalidSignature,
+    /// Any other malformed argument
+    InvalidInput,
+    KeyGenerationFailed,
+    SigningFailed,
+    VerificationFailed,
+    EncryptionFailed,
+    DecryptionFailed,
+    /// `dispose()` was called on the wallet
+    WalletDisposed,
+    /// The keystore must be unlocked first
+    Locked,
+    /// The node could not be reached or answered with an error
+    NetworkError,
+    /// The node refused the transaction
+    BroadcastFailed,
+    /// The browser storage could not be read or written
+    StorageError,
+    NotFound,
+    /// The caller aborted the operation
+    Cancelled,
+    /// The hardware wallet could not be reached or answered with an error
+    DeviceError,
+}
+
+/// An invalid input and why it is invalid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Tsify)]
+pub struct FieldError {
+    /// Name of the input, e.g. `fee` or `to` for a transaction
+    pub field: String,
+    pub message: String,
 }
 
 impl<T: Serialize> WasmResult<T> {
@@ -29,22 +203,73 @@ impl<T: Serialize> WasmResult<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
+            details: Vec::new(),
         };
         serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
     }
 
-    fn err(error: String) -> JsValue {
+    fn err(code: ErrorCode, error: String) -> JsValue {
+        Self::err_with_details(code, error, Vec::new())
+    }
+
+    fn err_with_details(code: ErrorCode, error: String, details: Vec<FieldError>) -> JsValue {
         let result: WasmResult<()> = WasmResult {
             success: false,
             data: None,
             error: Some(error),
+            code: Some(code),
+            details,
         };
         serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
     }
+
+    /// Failure of a transaction that could not be signed or verified, with
+    /// every invalid field of the transaction
+    fn transaction_err(
+        transaction: &Transaction,
+        context: &str,
+        error: TransactionError,
+    ) -> JsValue {
+        let Some(field) = error.field() else {
+            return Self::err(
+                ErrorCode::InvalidTransaction,
+                format!("{}: {}", context, error),
+            );
+        };
+        let mut details: Vec<FieldError> = transaction
+            .field_errors()
+            .iter()
+            .filter_map(|e| {
+                e.field().map(|field| FieldError {
+                    field: field.to_string(),
+                    message: e.to_string(),
+                })
+            })
+            .collect();
+        // Errors `field_errors` does not look for, e.g. a sender mismatch
+        if !details.iter().any(|d| d.message == error.to_string()) {
+            details.insert(
+                0,
+                FieldError {
+                    field: field.to_string(),
+                    message: error.to_string(),
+                },
+            );
+        }
+        Self::err_with_details(
+            ErrorCode::InvalidTransaction,
+            format!("{}: {}", context, error),
+            details,
+        )
+    }
 }
 
 /// Wallet data that can be exported to JavaScript
-#[derive(Serialize, Deserialize)]
+///
+/// The copies of the secret key in WebAssembly memory are wiped once it is
+/// serialized; those in JavaScript strings cannot be.
+#[derive(Serialize, Deserialize, Tsify, Zeroize, ZeroizeOnDrop)]
 pub struct WalletData {
     pub address: String,
     pub secret_key_hex: String,
@@ -55,33 +280,174 @@ pub struct WalletData {
 /// Generate a new random wallet
 ///
 /// # Arguments
-/// * `network` - Either "mainnet" or "testnet"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
 ///
 /// # Returns
 /// JSON object with wallet data including address and secret keys
-#[wasm_bindgen]
-pub fn generate_wallet(network: &str) -> JsValue {
-    let network_id = match network.to_lowercase().as_str() {
-        "mainnet" => NetworkId::MAINNET,
-        "testnet" => NetworkId::TESTNET,
-        _ => {
-            return WasmResult::<WalletData>::err(
-                "Invalid network. Use 'mainnet' or 'testnet'.".to_string(),
-            );
-        }
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn generate_wallet(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
     };
 
     match Wallet::new(network_id) {
         Ok(wallet) => {
             let data = WalletData {
                 address: wallet.address(),
-                secret_key_hex: wallet.secret_key_hex(),
-                secret_key_base58: wallet.secret_key_base58(),
-                network: network.to_lowercase(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
             };
             WasmResult::ok(data)
         }
-        Err(e) => WasmResult::<WalletData>::err(format!("Failed to generate wallet: {}", e)),
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::KeyGenerationFailed,
+            format!("Failed to generate wallet: {}", e),
+        ),
+    }
+}
+
+/// Generate a new random wallet without blocking the caller
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// Promise of the result of `generate_wallet`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub async fn generate_wallet_async(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    yield_now().await;
+    generate_wallet(network)
+}
+
+/// Generate a new random wallet, mixing caller entropy into the OS randomness
+///
+/// The extra bytes, e.g. mouse movements collected by the page, add to the
+/// OS randomness through HKDF: the key stays secret if either is
+/// unpredictable, even if the browser RNG is compromised.
+///
+/// # Arguments
+/// * `extra_entropy` - Bytes collected by the caller, wiped once mixed
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with wallet data including address and secret keys
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn generate_wallet_with_entropy(
+    extra_entropy: Vec<u8>,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let extra_entropy = Zeroizing::new(extra_entropy);
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
+    };
+    if extra_entropy.is_empty() {
+        return WasmResult::<WalletData>::err(
+            ErrorCode::InvalidInput,
+            "The extra entropy is empty".to_string(),
+        );
+    }
+
+    match entropy::generate_with_extra(&extra_entropy, network_id) {
+        Ok(wallet) => WasmResult::ok(WalletData {
+            address: wallet.address(),
+            secret_key_hex: wallet.secret_key_hex().to_string(),
+            secret_key_base58: wallet.secret_key_base58().to_string(),
+            network: network_name(wallet.network()).to_string(),
+        }),
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::KeyGenerationFailed,
+            format!("Failed to generate wallet: {}", e),
+        ),
+    }
+}
+
+/// Derive a wallet from raw seed bytes, e.g. held by an HSM or a KMS
+///
+/// The same seed and index always give the same key; see the `seed` module
+/// of the core crate for the derivation.
+///
+/// # Arguments
+/// * `seed` - At least 16 bytes, wiped once used
+/// * `index` - Index of the key
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+#[wasm_bindgen]
+pub fn wallet_from_seed(
+    seed: Vec<u8>,
+    index: u32,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> Result<WasmWallet, JsValue> {
+    let seed = Zeroizing::new(seed);
+    let network_id = resolve_network(&network)?;
+    seed::derive_wallet(&seed, index, network_id)
+        .map(WasmWallet::from)
+        .map_err(|e| JsValue::from_str(&format!("Failed to derive wallet: {}", e)))
+}
+
+/// Largest number of wallets generated by one `generate_wallets` call
+const MAX_GENERATED_WALLETS: u32 = 10_000;
+
+/// Generate several wallets in one call
+///
+/// This crosses the JavaScript boundary once, instead of once per wallet as
+/// with `generate_wallet`. The threaded build spreads the wallets over its
+/// thread pool.
+///
+/// # Arguments
+/// * `count` - Number of wallets, at most 10000
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `seed` - Derive the keys from this seed, as the test vectors do, instead
+///   of the OS RNG; anyone knowing the seed knows the keys, so use it for
+///   tests only
+///
+/// # Returns
+/// JSON array of wallet data
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData[]>")]
+pub fn generate_wallets(
+    count: u32,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    seed: Option<String>,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<Vec<WalletData>>::err(ErrorCode::InvalidNetwork, e),
+    };
+    if count > MAX_GENERATED_WALLETS {
+        return WasmResult::<Vec<WalletData>>::err(
+            ErrorCode::InvalidInput,
+            format!("At most {} wallets per call", MAX_GENERATED_WALLETS),
+        );
+    }
+
+    let generate = |index: u32| {
+        let wallet = match &seed {
+            Some(seed) => test_vectors::seeded_wallet(seed, index, network_id.clone())
+                .map_err(|e| e.to_string()),
+            None => Wallet::new(network_id.clone()).map_err(|e| e.to_string()),
+        };
+        wallet
+            .map(|wallet| WalletData {
+                address: wallet.address(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
+            })
+            .map_err(|e| format!("Failed to generate wallet {}: {}", index + 1, e))
+    };
+    #[cfg(feature = "threads")]
+    let wallets: Result<Vec<_>, _> = (0..count).into_par_iter().map(generate).collect();
+    #[cfg(not(feature = "threads"))]
+    let wallets: Result<Vec<_>, _> = (0..count).map(generate).collect();
+
+    match wallets {
+        Ok(wallets) => WasmResult::ok(wallets),
+        Err(e) => WasmResult::<Vec<WalletData>>::err(ErrorCode::KeyGenerationFailed, e),
     }
 }
 
@@ -89,33 +455,34 @@ pub fn generate_wallet(network: &str) -> JsValue {
 ///
 /// # Arguments
 /// * `secret_hex` - The secret key in hexadecimal format (64 characters)
-/// * `network` - Either "mainnet" or "testnet"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
 ///
 /// # Returns
 /// JSON object with wallet data
-#[wasm_bindgen]
-pub fn import_wallet_from_hex(secret_hex: &str, network: &str) -> JsValue {
-    let network_id = match network.to_lowercase().as_str() {
-        "mainnet" => NetworkId::MAINNET,
-        "testnet" => NetworkId::TESTNET,
-        _ => {
-            return WasmResult::<WalletData>::err(
-                "Invalid network. Use 'mainnet' or 'testnet'.".to_string(),
-            );
-        }
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn import_wallet_from_hex(
+    secret_hex: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
     };
 
     match Wallet::from_secret_key_hex(secret_hex, network_id) {
         Ok(wallet) => {
             let data = WalletData {
                 address: wallet.address(),
-                secret_key_hex: wallet.secret_key_hex(),
-                secret_key_base58: wallet.secret_key_base58(),
-                network: network.to_lowercase(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
             };
             WasmResult::ok(data)
         }
-        Err(e) => WasmResult::<WalletData>::err(format!("Failed to import wallet: {}", e)),
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::InvalidSecretHex,
+            format!("Failed to import wallet: {}", e),
+        ),
     }
 }
 
@@ -123,36 +490,714 @@ pub fn import_wallet_from_hex(secret_hex: &str, network: &str) -> JsValue {
 ///
 /// # Arguments
 /// * `secret_base58` - The secret key in Base58 format (52 characters)
-/// * `network` - Either "mainnet" or "testnet"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
 ///
 /// # Returns
 /// JSON object with wallet data
-#[wasm_bindgen]
-pub fn import_wallet_from_base58(secret_base58: &str, network: &str) -> JsValue {
-    let network_id = match network.to_lowercase().as_str() {
-        "mainnet" => NetworkId::MAINNET,
-        "testnet" => NetworkId::TESTNET,
-        _ => {
-            return WasmResult::<WalletData>::err(
-                "Invalid network. Use 'mainnet' or 'testnet'.".to_string(),
-            );
-        }
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn import_wallet_from_base58(
+    secret_base58: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
     };
 
     match Wallet::from_secret_key_base58(secret_base58, network_id) {
         Ok(wallet) => {
             let data = WalletData {
                 address: wallet.address(),
-                secret_key_hex: wallet.secret_key_hex(),
-                secret_key_base58: wallet.secret_key_base58(),
-                network: network.to_lowercase(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
             };
             WasmResult::ok(data)
         }
-        Err(e) => WasmResult::<WalletData>::err(format!("Failed to import wallet: {}", e)),
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::InvalidSecretBase58,
+            format!("Failed to import wallet: {}", e),
+        ),
+    }
+}
+
+/// Error of the methods of a disposed `WasmWallet`
+const DISPOSED: &str = "The wallet was disposed";
+
+/// A wallet whose secret key stays in WebAssembly memory
+///
+/// Construct it once, from a secret key or a keystore entry, then sign with
+/// its methods: the secret key does not go back and forth as JavaScript
+/// strings, which cannot be wiped. `dispose()` zeroizes the key; call it as
+/// soon as the wallet is no longer needed, rather than waiting for the
+/// garbage collector.
+#[wasm_bindgen]
+pub struct WasmWallet {
+    /// `None` once disposed
+    wallet: Option<Rc<Wallet>>,
+    /// The network the wallet was opened for, with its nodes
+    network: Network,
+}
+
+#[wasm_bindgen]
+impl WasmWallet {
+    /// Import a secret key, in hex (64 characters) or Base58 (52 characters)
+    ///
+    /// # Arguments
+    /// * `secret_key` - The secret key
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        secret_key: &str,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> Result<WasmWallet, JsValue> {
+        let network = Network::from_js(&network)?;
+        Wallet::from_secret_key_strict(secret_key, network.id.clone())
+            .map(|wallet| Self::from(wallet).with_network(network))
+            .map_err(|e| JsValue::from_str(&format!("Invalid secret key: {}", e)))
+    }
+
+    /// Generate a new random wallet
+    pub fn generate(
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> Result<WasmWallet, JsValue> {
+        let network = Network::from_js(&network)?;
+        Wallet::new(network.id.clone())
+            .map(|wallet| Self::from(wallet).with_network(network))
+            .map_err(|e| JsValue::from_str(&format!("Failed to generate wallet: {}", e)))
+    }
+
+    /// Decrypt a key of a keystore
+    ///
+    /// # Arguments
+    /// * `keystore` - JSON of the keystore, as in the `keystore.json` file of
+    ///   `mina-wallet`
+    /// * `name` - Label or address of the key
+    /// * `passphrase` - Passphrase of the key
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    #[wasm_bindgen(js_name = fromKeystore)]
+    pub fn from_keystore(
+        keystore: &str,
+        name: &str,
+        passphrase: &str,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> Result<WasmWallet, JsValue> {
+        let network = Network::from_js(&network)?;
+        let keystore: Keystore = serde_json::from_str(keystore)
+            .map_err(|e| JsValue::from_str(&format!("Invalid keystore: {}", e)))?;
+        keystore
+            .unlock(name, passphrase, network.id.clone())
+            .map(|wallet| Self::from(wallet).with_network(network))
+            .map_err(|e| JsValue::from_str(&format!("Failed to unlock {}: {}", name, e)))
+    }
+
+    /// The address of the wallet
+    pub fn address(&self) -> Result<String, JsValue> {
+        Ok(self.wallet()?.address())
+    }
+
+    /// The network the wallet signs for, "mainnet" or "testnet"
+    pub fn network(&self) -> Result<String, JsValue> {
+        Ok(network_name(self.wallet()?.network()).to_string())
+    }
+
+    /// The network the wallet was opened for, with the endpoints and chain
+    /// ID of its `NetworkConfig`
+    #[wasm_bindgen(js_name = networkConfig, unchecked_return_type = "NetworkConfig")]
+    pub fn network_config(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.network.config()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Sign a payment or delegation sent from this wallet
+    ///
+    /// # Arguments
+    /// * `transaction` - The transaction, in the `mina-signer` JSON shape
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signPayment, unchecked_return_type = "WasmResult<SignedTransaction>")]
+    pub fn sign_payment(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+    ) -> JsValue {
+        let wallet = match self.wallet() {
+            Ok(w) => w,
+            Err(e) => {
+                return WasmResult::<SignedTransaction>::err(
+                    ErrorCode::WalletDisposed,
+                    e.to_string(),
+                );
+            }
+        };
+        let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+            Ok(t) => t,
+            Err(e) => {
+                return WasmResult::<SignedTransaction>::err(
+                    ErrorCode::InvalidTransaction,
+                    format!("Invalid transaction: {}", e),
+                );
+            }
+        };
+        match wallet.sign_transaction(&transaction) {
+            Ok(signed) => WasmResult::ok(signed),
+            Err(e) => {
+                WasmResult::<SignedTransaction>::transaction_err(&transaction, "Failed to sign", e)
+            }
+        }
+    }
+
+    /// Sign a message
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signMessage, unchecked_return_type = "WasmResult<SignedMessage>")]
+    pub fn sign_message(&self, message: &str) -> JsValue {
+        match self.wallet() {
+            Ok(wallet) => WasmResult::ok(wallet.sign_message(message)),
+            Err(e) => WasmResult::<SignedMessage>::err(ErrorCode::WalletDisposed, e.to_string()),
+        }
+    }
+
+    /// Sign a sign-in challenge, as created by `create_login_challenge`
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signLoginChallenge, unchecked_return_type = "WasmResult<SignedMessage>")]
+    pub fn sign_login_challenge(&self, message: &str) -> JsValue {
+        let wallet = match self.wallet() {
+            Ok(w) => w,
+            Err(e) => {
+                return WasmResult::<SignedMessage>::err(ErrorCode::WalletDisposed, e.to_string());
+            }
+        };
+        let result = LoginChallenge::parse(message).and_then(|c| wallet.sign_login_challenge(&c));
+        match result {
+            Ok(signed) => WasmResult::ok(signed),
+            Err(e) => WasmResult::<SignedMessage>::err(
+                ErrorCode::InvalidInput,
+                format!("Failed to sign login: {}", e),
+            ),
+        }
+    }
+
+    /// Sign field elements, as o1js `Signature.create(privateKey, fields)`
+    /// with the kimchi scheme
+    ///
+    /// # Arguments
+    /// * `fields` - Array of field elements, as decimal strings or integers
+    /// * `scheme` - "kimchi" (o1js) or "legacy"
+    ///
+    /// # Returns
+    /// JSON object `{ field, scalar }`, as o1js `Signature.toJSON()`
+    #[wasm_bindgen(js_name = signFields, unchecked_return_type = "WasmResult<SignatureJson>")]
+    pub fn sign_fields(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "(string | number)[]")] fields: JsValue,
+        scheme: &str,
+    ) -> JsValue {
+        let wallet = match self.wallet() {
+            Ok(w) => w,
+            Err(e) => {
+                return WasmResult::<SignatureJson>::err(ErrorCode::WalletDisposed, e.to_string());
+            }
+        };
+        let scheme: SignatureScheme = match scheme.parse() {
+            Ok(s) => s,
+            Err(e) => return WasmResult::<SignatureJson>::err(ErrorCode::InvalidInput, e),
+        };
+        match parse_fields(fields) {
+            Ok(fields) => WasmResult::ok(SignatureJson::from(
+                &wallet.sign(&FieldElements(fields), scheme),
+            )),
+            Err(e) => WasmResult::<SignatureJson>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid fields: {}", e),
+            ),
+        }
+    }
+
+    /// Encrypt a message so that only the owner of `address` can read it
+    ///
+    /// The recipient decrypts it with `decryptMessage` on their own wallet.
+    ///
+    /// # Returns
+    /// JSON object with the hex-encoded ciphertext
+    #[wasm_bindgen(js_name = encryptMessage, unchecked_return_type = "WasmResult<string>")]
+    pub fn encrypt_message(&self, address: &str, plaintext: &str) -> JsValue {
+        if let Err(e) = self.wallet() {
+            return WasmResult::<String>::err(ErrorCode::WalletDisposed, e.to_string());
+        }
+        encrypt_to_address(address, plaintext)
+    }
+
+    /// Decrypt a message encrypted to the address of the wallet
+    ///
+    /// # Returns
+    /// JSON object with the decrypted message
+    #[wasm_bindgen(js_name = decryptMessage, unchecked_return_type = "WasmResult<string>")]
+    pub fn decrypt_message(&self, ciphertext: &str) -> JsValue {
+        let wallet = match self.wallet() {
+            Ok(w) => w,
+            Err(e) => return WasmResult::<String>::err(ErrorCode::WalletDisposed, e.to_string()),
+        };
+        match wallet.decrypt(ciphertext) {
+            Ok(plaintext) => WasmResult::ok(String::from_utf8_lossy(&plaintext).into_owned()),
+            Err(e) => WasmResult::<String>::err(
+                ErrorCode::DecryptionFailed,
+                format!("Failed to decrypt: {}", e),
+            ),
+        }
+    }
+
+    /// Zeroize the secret key; every method fails afterwards
+    pub fn dispose(&mut self) {
+        // Dropping the wallet wipes its secret key, once the batches being
+        // signed with it are done
+        self.wallet = None;
+    }
+
+    /// Whether `dispose()` was called
+    #[wasm_bindgen(getter, js_name = isDisposed)]
+    pub fn is_disposed(&self) -> bool {
+        self.wallet.is_none()
+    }
+}
+
+impl WasmWallet {
+    fn wallet(&self) -> Result<&Wallet, &'static str> {
+        self.wallet.as_deref().ok_or(DISPOSED)
+    }
+
+    /// A handle on the wallet for asynchronous operations
+    fn shared_wallet(&self) -> Result<Rc<Wallet>, &'static str> {
+        self.wallet.clone().ok_or(DISPOSED)
+    }
+
+    /// Keep the endpoints and chain ID of the configuration it was opened with
+    fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+}
+
+impl From<Wallet> for WasmWallet {
+    fn from(wallet: Wallet) -> Self {
+        let network = Network {
+            id: wallet.network().clone(),
+            chain_id: None,
+            endpoints: Vec::new(),
+            archive_endpoints: Vec::new(),
+        };
+        let wallet = Rc::new(wallet);
+        secrets::register("wallet", &wallet, |_| true);
+        Self {
+            wallet: Some(wallet),
+            network,
+        }
+    }
+}
+
+/// Parse "mainnet" or "testnet", in any case
+fn parse_network(network: &str) -> Result<NetworkId, String> {
+    match network.to_lowercase().as_str() {
+        "mainnet" => Ok(NetworkId::MAINNET),
+        "testnet" => Ok(NetworkId::TESTNET),
+        _ => Err("Invalid network. Use 'mainnet' or 'testnet'.".to_string()),
+    }
+}
+
+/// Name of a network, as `parse_network` accepts it
+fn network_name(network: &NetworkId) -> &'static str {
+    match network {
+        NetworkId::MAINNET => "mainnet",
+        NetworkId::TESTNET => "testnet",
+    }
+}
+
+/// A signed transaction, with the GraphQL request that broadcasts it
+#[derive(Serialize, Tsify)]
+struct SignedTransactionData {
+    /// `{ publicKey, data, signature: { field, scalar } }`, as o1js and
+    /// `mina-signer` produce it
+    signed: SignedTransaction,
+    /// `{ query, variables }` of the `sendPayment` or `sendDelegation` mutation
+    #[cfg(feature = "network")]
+    #[tsify(type = "{ query: string; variables: Record<string, unknown> }")]
+    graphql: serde_json::Value,
+}
+
+/// Parameters of `build_payment`
+#[derive(Deserialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct PaymentParams {
+    from: String,
+    to: String,
+    /// Amount, in MINA, e.g. "1.5"
+    amount: String,
+    /// Fee, in MINA; 0.1 MINA if absent
+    #[serde(default)]
+    fee: Option<String>,
+    nonce: u32,
+    #[serde(default)]
+    memo: Option<String>,
+    /// Minutes the payment stays valid for; no expiry if absent
+    #[serde(default)]
+    valid_for_minutes: Option<u32>,
+    /// "mainnet" or "testnet", or a `NetworkConfig`: the network whose
+    /// slots `validForMinutes` counts in
+    #[tsify(type = "Network")]
+    network: Network,
+}
+
+/// An unsigned payment, checked and ready to be shown then signed
+#[derive(Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct UnsignedPayment {
+    /// The payment, to pass as is to `sign_payment`
+    transaction: Transaction,
+    /// The memo as the daemon and explorers show it
+    memo_base58: String,
+    preview: PaymentPreview,
+    /// Explanations of what is worth a second look before signing
+    warnings: Vec<String>,
+}
+
+/// Build an unsigned payment from amounts in MINA, without signing it
+///
+/// The payment is validated as `sign_payment` would, so that a dApp can show
+/// it and ask for approval before the signature step.
+///
+/// # Arguments
+/// * `params` - `{ from, to, amount, fee?, nonce, memo?, validForMinutes?, network }`
+///
+/// # Returns
+/// JSON object `{ transaction, memoBase58, preview, warnings }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<UnsignedPayment>")]
+pub fn build_payment(
+    #[wasm_bindgen(unchecked_param_type = "PaymentParams")] params: JsValue,
+) -> JsValue {
+    let params: PaymentParams = match serde_wasm_bindgen::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return WasmResult::<UnsignedPayment>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid payment parameters: {}", e),
+            );
+        }
+    };
+    let network_id = params.network.id;
+    let parse_mina = |field: &str, mina: &str| {
+        mina.parse::<Amount>().map_err(|e| {
+            let message = format!("Invalid {}: {}", field, e);
+            WasmResult::<UnsignedPayment>::err_with_details(
+                ErrorCode::InvalidAmount,
+                message.clone(),
+                vec![FieldError {
+                    field: field.to_string(),
+                    message,
+                }],
+            )
+        })
+    };
+    let amount = match parse_mina("amount", &params.amount) {
+        Ok(a) => a,
+        Err(e) => return e,
+    };
+    let fee = match params.fee.as_deref().map(|fee| parse_mina("fee", fee)) {
+        None => transaction::DEFAULT_FEE,
+        Some(Ok(fee)) => fee,
+        Some(Err(e)) => return e,
+    };
+
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    let mut payment = Transaction::payment(&params.from, &params.to, amount, fee, params.nonce)
+        .with_memo(params.memo.as_deref().unwrap_or_default());
+    if let Some(minutes) = params.valid_for_minutes {
+        let constants = ConsensusConstants::for_network(&network_id);
+        match constants.valid_until(now, u64::from(minutes) * 60) {
+            Some(slot) => payment = payment.with_valid_until(slot),
+            None => {
+                return WasmResult::<UnsignedPayment>::err(
+                    ErrorCode::InvalidInput,
+                    format!("{} minutes from now is out of range", minutes),
+                );
+            }
+        }
+    }
+    if let Err(e) = payment.validate() {
+        return WasmResult::<UnsignedPayment>::transaction_err(&payment, "Invalid payment", e);
+    }
+
+    let current_slot = ConsensusConstants::for_network(&network_id).slot_at(now);
+    WasmResult::ok(UnsignedPayment {
+        memo_base58: transaction::encode_memo_base58(&payment.memo)
+            .expect("the memo was validated"),
+        preview: payment.preview(None),
+        warnings: payment
+            .warnings(None, current_slot)
+            .iter()
+            .map(|w| w.message())
+            .collect(),
+        transaction: payment,
+    })
+}
+
+/// Format nanomina as decimal MINA, without floating point
+///
+/// # Arguments
+/// * `nanomina` - Amount in nanomina, as a decimal string
+///
+/// # Returns
+/// JSON object with the amount in MINA without trailing zeros, e.g. "1.5"
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn format_mina(nanomina: &str) -> JsValue {
+    match Amount::from_nanomina_str(nanomina.trim()) {
+        Ok(amount) => WasmResult::ok(amount.to_string()),
+        Err(e) => {
+            WasmResult::<String>::err(ErrorCode::InvalidAmount, format!("Invalid amount: {}", e))
+        }
+    }
+}
+
+/// Parse decimal MINA to nanomina, without floating point
+///
+/// # Arguments
+/// * `mina` - Amount in MINA, e.g. "1.5", with at most 9 decimals
+///
+/// # Returns
+/// JSON object with the amount in nanomina, as a decimal string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn parse_mina(mina: &str) -> JsValue {
+    match mina.parse::<Amount>() {
+        Ok(amount) => WasmResult::ok(amount.as_nanomina().to_string()),
+        Err(e) => {
+            WasmResult::<String>::err(ErrorCode::InvalidAmount, format!("Invalid amount: {}", e))
+        }
+    }
+}
+
+/// Base58Check-encode a payload under a version byte, as Mina encodes
+/// addresses (0xcb), secret keys (0x5a), signatures (0x9a) and memos (0x14)
+///
+/// # Arguments
+/// * `version` - The version byte
+/// * `payload_hex` - The payload, in hex
+///
+/// # Returns
+/// JSON object with the Base58 string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn base58_encode(version: u8, payload_hex: &str) -> JsValue {
+    match hex::decode(payload_hex.trim()) {
+        Ok(payload) => WasmResult::ok(base58::encode_check(version, &payload)),
+        Err(e) => WasmResult::<String>::err(ErrorCode::InvalidInput, format!("Invalid hex: {}", e)),
+    }
+}
+
+/// A Base58Check string, decoded
+#[derive(Serialize, Deserialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct Base58Data {
+    version: u8,
+    /// "address", "secretKey", "signature" or "memo"; absent for other
+    /// version bytes
+    kind: Option<String>,
+    payload_hex: String,
+}
+
+/// Decode any Base58Check string, checking its checksum
+///
+/// # Returns
+/// JSON object `{ version, kind, payloadHex }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Base58Data>")]
+pub fn base58_decode(encoded: &str) -> JsValue {
+    match base58::decode_check_any(encoded.trim()) {
+        Ok((version, payload)) => WasmResult::ok(Base58Data {
+            version,
+            kind: base58::version_name(version).map(str::to_string),
+            payload_hex: hex::encode(payload),
+        }),
+        Err(e) => WasmResult::<Base58Data>::err(ErrorCode::InvalidInput, e.to_string()),
+    }
+}
+
+/// Decode a signed or unsigned transaction for a confirmation dialog
+///
+/// Accepts the shapes of this wallet, Auro, `mina-signer`, the daemon's
+/// GraphQL mutations and `tx` envelopes, with or without a signature.
+///
+/// # Arguments
+/// * `json` - The transaction JSON
+///
+/// # Returns
+/// JSON object `{ kind, from, to, amount, fee, totalCost, nonce, memo,
+/// validUntil, signed, network, warnings }`, amounts in MINA
+#[wasm_bindgen(unchecked_return_type = "WasmResult<TransactionPreview>")]
+pub fn decode_transaction(json: &str) -> JsValue {
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    match inspect::preview(json, Some(now)) {
+        Ok(preview) => WasmResult::ok(preview),
+        Err(e) => WasmResult::<TransactionPreview>::err(
+            ErrorCode::InvalidTransaction,
+            format!("Invalid transaction: {}", e),
+        ),
+    }
+}
+
+/// Sign a payment, ready to be broadcast
+///
+/// # Arguments
+/// * `wallet` - The wallet sending the payment
+/// * `transaction` - The payment, in the `mina-signer` JSON shape, amounts in nanomina
+///
+/// # Returns
+/// JSON object `{ signed, graphql }`: `signed` can be passed to
+/// `broadcast_transaction` or `queue_transaction`, and `graphql` posted as is
+/// to the GraphQL endpoint of a node; `graphql` needs the `network` feature
+#[wasm_bindgen(unchecked_return_type = "WasmResult<SignedTransactionData>")]
+pub fn sign_payment(
+    wallet: &WasmWallet,
+    #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+) -> JsValue {
+    sign_transaction_of_kind(wallet, transaction, TransactionKind::Payment)
+}
+
+/// Sign a delegation of the stake of a wallet, ready to be broadcast
+///
+/// # Arguments
+/// * `wallet` - The delegating wallet
+/// * `transaction` - The delegation, in the `mina-signer` JSON shape, without `amount`
+///
+/// # Returns
+/// JSON object `{ signed, graphql }`, as `sign_payment`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<SignedTransactionData>")]
+pub fn sign_delegation(
+    wallet: &WasmWallet,
+    #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+) -> JsValue {
+    sign_transaction_of_kind(wallet, transaction, TransactionKind::Delegation)
+}
+
+fn sign_transaction_of_kind(
+    wallet: &WasmWallet,
+    transaction: JsValue,
+    kind: TransactionKind,
+) -> JsValue {
+    let wallet = match wallet.wallet() {
+        Ok(w) => w,
+        Err(e) => {
+            return WasmResult::<SignedTransactionData>::err(
+                ErrorCode::WalletDisposed,
+                e.to_string(),
+            );
+        }
+    };
+    let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+        Ok(t) => t,
+        Err(e) => {
+            return WasmResult::<SignedTransactionData>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid transaction: {}", e),
+            );
+        }
+    };
+    if transaction.kind() != kind {
+        let message = match kind {
+            TransactionKind::Payment => "Not a payment: the amount is missing",
+            TransactionKind::Delegation => "Not a delegation: a delegation has no amount",
+        };
+        return WasmResult::<SignedTransactionData>::err_with_details(
+            ErrorCode::InvalidTransaction,
+            message.to_string(),
+            vec![FieldError {
+                field: "amount".to_string(),
+                message: message.to_string(),
+            }],
+        );
+    }
+    match wallet.sign_transaction(&transaction) {
+        Ok(signed) => WasmResult::ok(SignedTransactionData {
+            #[cfg(feature = "network")]
+            graphql: graphql::send_request(&signed),
+            signed,
+        }),
+        Err(e) => {
+            WasmResult::<SignedTransactionData>::transaction_err(&transaction, "Failed to sign", e)
+        }
     }
 }
 
+/// Sign payments and delegations, yielding to the event loop between chunks
+///
+/// # Arguments
+/// * `wallet` - The wallet sending the transactions
+/// * `transactions` - Array of transactions, in the `mina-signer` JSON shape
+///
+/// # Returns
+/// Promise of a JSON array of `{ signed, graphql }`, in the order of
+/// `transactions`; nothing is returned if one of them cannot be signed
+#[wasm_bindgen(unchecked_return_type = "Promise<WasmResult<SignedTransactionData[]>>")]
+pub fn sign_batch(
+    wallet: &WasmWallet,
+    #[wasm_bindgen(unchecked_param_type = "Transaction[]")] transactions: JsValue,
+) -> js_sys::Promise {
+    // The future outlives the borrow of `wallet`, so it holds its own handle
+    let wallet = wallet.shared_wallet();
+    let transactions = serde_wasm_bindgen::from_value::<Vec<Transaction>>(transactions);
+    wasm_bindgen_futures::future_to_promise(async move {
+        let wallet = match wallet {
+            Ok(w) => w,
+            Err(e) => {
+                return Ok(WasmResult::<Vec<SignedTransactionData>>::err(
+                    ErrorCode::WalletDisposed,
+                    e.to_string(),
+                ));
+            }
+        };
+        let transactions = match transactions {
+            Ok(t) => t,
+            Err(e) => {
+                return Ok(WasmResult::<Vec<SignedTransactionData>>::err(
+                    ErrorCode::InvalidTransaction,
+                    format!("Invalid transactions: {}", e),
+                ));
+            }
+        };
+        let mut signed = Vec::with_capacity(transactions.len());
+        for (i, chunk) in transactions.chunks(BATCH_CHUNK).enumerate() {
+            if i > 0 {
+                yield_now().await;
+            }
+            for (j, transaction) in chunk.iter().enumerate() {
+                match wallet.sign_transaction(transaction) {
+                    Ok(s) => signed.push(SignedTransactionData {
+                        #[cfg(feature = "network")]
+                        graphql: graphql::send_request(&s),
+                        signed: s,
+                    }),
+                    Err(e) => {
+                        let number = i * BATCH_CHUNK + j + 1;
+                        return Ok(WasmResult::<Vec<SignedTransactionData>>::transaction_err(
+                            transaction,
+                            &format!("Failed to sign transaction {}", number),
+                            e,
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(WasmResult::ok(signed))
+    })
+}
+
+/// Transactions signed between two yields to the event loop in `sign_batch`
+const BATCH_CHUNK: usize = 16;
+
+/// Let the event loop run, so that the page stays responsive, or the worker
+/// receives its messages, during a long computation
+async fn yield_now() {
+    gloo_timers::future::TimeoutFuture::new(0).await;
+}
+
 /// Validate a Mina address
 ///
 /// # Arguments
@@ -203,36 +1248,2323 @@ pub fn address_to_pubkey(address: &str) -> JsValue {
                 is_odd: pubkey.is_odd,
             })
         }
-        Err(e) => WasmResult::<PubKeyComponents>::err(format!("Invalid address: {:?}", e)),
+        Err(e) => WasmResult::<PubKeyComponents>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        ),
     }
 }
 
-/// Get the library version
-#[wasm_bindgen]
-pub fn version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
-}
+/// Generate a wallet whose address matches a pattern
+///
+/// This runs synchronously, so `max_attempts` bounds how long the caller is blocked.
+///
+/// # Arguments
+/// * `pattern` - Base58 pattern to look for
+/// * `position` - Either "prefix" (after the B62q header) or "suffix"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `max_attempts` - Number of keypairs to try before giving up
+///
+/// # Returns
+/// JSON object with wallet data
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn generate_vanity_wallet(
+    pattern: &str,
+    position: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    max_attempts: u32,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
+    };
 
-#[cfg(test)]
+    let position: VanityPosition = match position.parse() {
+        Ok(p) => p,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidInput, format!("{}", e)),
+    };
+
+    let cancel = CancellationToken::new();
+    let result = vanity::generate_vanity(pattern, position, network_id, &cancel, |attempts| {
+        if attempts >= u64::from(max_attempts) {
+            cancel.cancel();
+        }
+    });
+
+    match result {
+        Ok(wallet) => {
+            let data = WalletData {
+                address: wallet.address(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
+            };
+            WasmResult::ok(data)
+        }
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::KeyGenerationFailed,
+            format!("Failed to generate vanity wallet: {}", e),
+        ),
+    }
+}
+
+/// Progress of a vanity search, reported between two yields to the event loop
+#[derive(Serialize, Deserialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct VanityProgress {
+    /// Keypairs tried so far
+    pub attempts: u64,
+    /// Keypairs tried per second, since the start of the search
+    pub rate: f64,
+    /// Keypairs a search for this pattern tries on average
+    pub expected_attempts: f64,
+}
+
+/// Generate a vanity wallet without blocking the caller
+///
+/// The search yields to the event loop every thousand attempts, and reports
+/// its progress, so that it can run in the page or in a Web Worker. Aborting
+/// `signal` stops it at the next yield, with the `CANCELLED` error code.
+///
+/// # Arguments
+/// * `pattern` - Base58 characters the address must contain
+/// * `position` - "prefix" (after "B62q") or "suffix"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `max_attempts` - Give up after this many attempts
+/// * `on_progress` - Called with the `VanityProgress` of the search
+/// * `signal` - Signal of an `AbortController` cancelling the search
+///
+/// # Returns
+/// Promise of JSON object with wallet data
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub async fn generate_vanity_wallet_async(
+    pattern: String,
+    position: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    max_attempts: u32,
+    #[wasm_bindgen(unchecked_param_type = "((progress: VanityProgress) => void) | undefined")]
+    on_progress: Option<js_sys::Function>,
+    signal: Option<web_sys::AbortSignal>,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let position: VanityPosition = match position.parse() {
+        Ok(p) => p,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidInput, format!("{}", e)),
+    };
+    let expected_attempts = match vanity::normalize_pattern(&pattern, position) {
+        Ok(p) => vanity::expected_attempts(&p),
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidInput, e.to_string()),
+    };
+
+    let started = js_sys::Date::now();
+    let mut attempts = 0u64;
+    loop {
+        if signal.as_ref().is_some_and(|s| s.aborted()) {
+            return WasmResult::<WalletData>::err(
+                ErrorCode::Cancelled,
+                format!("Cancelled after {} attempts", attempts),
+            );
+        }
+        // Search until the next progress report, then yield
+        let cancel = CancellationToken::new();
+        let result =
+            vanity::generate_vanity(&pattern, position, network_id.clone(), &cancel, |_| {
+                cancel.cancel()
+            });
+        match result {
+            Ok(wallet) => {
+                return WasmResult::ok(WalletData {
+                    address: wallet.address(),
+                    secret_key_hex: wallet.secret_key_hex().to_string(),
+                    secret_key_base58: wallet.secret_key_base58().to_string(),
+                    network: network_name(wallet.network()).to_string(),
+                });
+            }
+            Err(VanityError::Cancelled { attempts: chunk }) => {
+                attempts += chunk;
+                if attempts >= u64::from(max_attempts) {
+                    return WasmResult::<WalletData>::err(
+                        ErrorCode::KeyGenerationFailed,
+                        format!("No match after {} attempts", attempts),
+                    );
+                }
+                if let Some(on_progress) = &on_progress {
+                    let seconds = (js_sys::Date::now() - started) / 1000.0;
+                    let progress = VanityProgress {
+                        attempts,
+                        rate: if seconds > 0.0 {
+                            attempts as f64 / seconds
+                        } else {
+                            0.0
+                        },
+                        expected_attempts,
+                    };
+                    if let Ok(progress) = serde_wasm_bindgen::to_value(&progress) {
+                        let _ = on_progress.call1(&JsValue::NULL, &progress);
+                    }
+                }
+                yield_now().await;
+            }
+            Err(e @ VanityError::InvalidPattern(_)) => {
+                return WasmResult::<WalletData>::err(ErrorCode::InvalidInput, e.to_string());
+            }
+            Err(e) => {
+                return WasmResult::<WalletData>::err(
+                    ErrorCode::KeyGenerationFailed,
+                    format!("Failed to generate vanity wallet: {}", e),
+                );
+            }
+        }
+    }
+}
+
+/// Build a printable paper wallet from a secret key
+///
+/// # Arguments
+/// * `secret_hex` - The secret key in hexadecimal format (64 characters)
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with the address, secret key chunks, QR payloads and checksum words
+#[wasm_bindgen]
+pub fn export_paper_wallet(
+    secret_hex: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<PaperWallet>::err(ErrorCode::InvalidNetwork, e),
+    };
+
+    match Wallet::from_secret_key_hex(secret_hex, network_id) {
+        Ok(wallet) => WasmResult::ok(PaperWallet::new(&wallet)),
+        Err(e) => WasmResult::<PaperWallet>::err(
+            ErrorCode::InvalidSecretHex,
+            format!("Failed to import wallet: {}", e),
+        ),
+    }
+}
+
+/// Render data as a QR code image
+///
+/// # Arguments
+/// * `data` - An address, a `mina:` payment URI, a signed transaction JSON, ...
+/// * `format` - Either "svg" or "png"
+///
+/// # Returns
+/// The bytes of the SVG document or PNG image
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Uint8Array>")]
+pub fn generate_qr(
+    data: &str,
+    #[wasm_bindgen(unchecked_param_type = "\"svg\" | \"png\"")] format: &str,
+) -> JsValue {
+    let format = match format.parse::<QrFormat>() {
+        Ok(f) => f,
+        Err(e) => return WasmResult::<()>::err(ErrorCode::InvalidInput, e.to_string()),
+    };
+    match qr::generate_qr(data, format) {
+        Ok(bytes) => WasmResult::ok(Bytes(bytes)),
+        Err(e) => WasmResult::<()>::err(ErrorCode::InvalidInput, e.to_string()),
+    }
+}
+
+/// Serialized as a `Uint8Array` instead of an array of numbers
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+/// Split a secret key into Shamir backup shares
+///
+/// # Arguments
+/// * `secret_hex` - The secret key in hexadecimal format (64 characters)
+/// * `threshold` - Number of shares required to recover the key
+/// * `count` - Total number of shares to generate
+///
+/// # Returns
+/// JSON array of hex-encoded shares
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string[]>")]
+pub fn split_secret_key(secret_hex: &str, threshold: u8, count: u8) -> JsValue {
+    let wallet = match Wallet::from_secret_key_hex(secret_hex, NetworkId::MAINNET) {
+        Ok(w) => w,
+        Err(e) => {
+            return WasmResult::<Vec<String>>::err(
+                ErrorCode::InvalidSecretHex,
+                format!("Invalid secret key: {}", e),
+            );
+        }
+    };
+
+    match sss::split_wallet(&wallet, threshold, count) {
+        Ok(shares) => WasmResult::ok(shares),
+        Err(e) => WasmResult::<Vec<String>>::err(
+            ErrorCode::InvalidInput,
+            format!("Failed to split secret key: {}", e),
+        ),
+    }
+}
+
+/// Recover a wallet from Shamir backup shares
+///
+/// # Arguments
+/// * `shares` - Hex-encoded shares, at least as many as the threshold
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with wallet data
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn recover_secret_key(
+    shares: Vec<String>,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
+    };
+
+    match sss::recover_wallet(&shares, network_id) {
+        Ok(wallet) => {
+            let data = WalletData {
+                address: wallet.address(),
+                secret_key_hex: wallet.secret_key_hex().to_string(),
+                secret_key_base58: wallet.secret_key_base58().to_string(),
+                network: network_name(wallet.network()).to_string(),
+            };
+            WasmResult::ok(data)
+        }
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::InvalidInput,
+            format!("Failed to recover wallet: {}", e),
+        ),
+    }
+}
+
+/// Encrypt a message so that only the owner of an address can read it
+///
+/// # Arguments
+/// * `address` - The recipient Mina address
+/// * `plaintext` - The message to encrypt
+///
+/// # Returns
+/// JSON object with the hex-encoded ciphertext
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn encrypt_to_address(address: &str, plaintext: &str) -> JsValue {
+    match encryption::encrypt_to_address(address, plaintext.as_bytes()) {
+        Ok(ciphertext) => WasmResult::ok(ciphertext),
+        Err(e) => WasmResult::<String>::err(
+            ErrorCode::EncryptionFailed,
+            format!("Failed to encrypt: {}", e),
+        ),
+    }
+}
+
+/// Decrypt a message encrypted to the address of a secret key
+///
+/// Prefer `WasmWallet.decryptMessage`, which does not take the secret key on every
+/// call.
+///
+/// # Arguments
+/// * `secret_hex` - The recipient secret key in hexadecimal format (64 characters)
+/// * `ciphertext` - The hex-encoded ciphertext
+///
+/// # Returns
+/// JSON object with the decrypted message
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn decrypt_with_secret(secret_hex: &str, ciphertext: &str) -> JsValue {
+    let wallet = match Wallet::from_secret_key_hex(secret_hex, NetworkId::MAINNET) {
+        Ok(w) => w,
+        Err(e) => {
+            return WasmResult::<String>::err(
+                ErrorCode::InvalidSecretHex,
+                format!("Invalid secret key: {}", e),
+            );
+        }
+    };
+
+    match wallet.decrypt(ciphertext) {
+        Ok(plaintext) => WasmResult::ok(String::from_utf8_lossy(&plaintext).into_owned()),
+        Err(e) => WasmResult::<String>::err(
+            ErrorCode::DecryptionFailed,
+            format!("Failed to decrypt: {}", e),
+        ),
+    }
+}
+
+/// Create a sign-in challenge for an address (server or dApp side)
+///
+/// # Arguments
+/// * `domain` - Domain requesting the sign-in
+/// * `address` - The Mina address signing in
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `statement` - Optional statement shown to the user
+/// * `ttl_seconds` - Validity period of the challenge
+///
+/// # Returns
+/// JSON object with the challenge fields and the `message` text to sign
+#[wasm_bindgen]
+pub fn create_login_challenge(
+    domain: &str,
+    address: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    statement: Option<String>,
+    ttl_seconds: u32,
+) -> JsValue {
+    #[derive(Serialize)]
+    struct ChallengeData {
+        challenge: LoginChallenge,
+        message: String,
+    }
+
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(address) {
+        return WasmResult::<ChallengeData>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<ChallengeData>::err(ErrorCode::InvalidNetwork, e),
+    };
+
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    let mut challenge = LoginChallenge::new(domain, address, network_name(&network_id), now)
+        .with_ttl(ttl_seconds.into());
+    if let Some(statement) = statement {
+        challenge = challenge.with_statement(&statement);
+    }
+    let message = challenge.to_message();
+    WasmResult::ok(ChallengeData { challenge, message })
+}
+
+/// Sign a sign-in challenge
+///
+/// Prefer `WasmWallet.signLoginChallenge`, which does not take the secret key
+/// on every call.
+///
+/// # Arguments
+/// * `secret_hex` - The secret key in hexadecimal format (64 characters)
+/// * `message` - The challenge text
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object `{ publicKey, data, signature: { field, scalar } }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<SignedMessage>")]
+pub fn sign_login_challenge(
+    secret_hex: &str,
+    message: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<SignedMessage>::err(ErrorCode::InvalidNetwork, e),
+    };
+
+    let wallet = match Wallet::from_secret_key_hex(secret_hex, network_id) {
+        Ok(w) => w,
+        Err(e) => {
+            return WasmResult::<SignedMessage>::err(
+                ErrorCode::InvalidSecretHex,
+                format!("Invalid secret key: {}", e),
+            );
+        }
+    };
+
+    let result = LoginChallenge::parse(message).and_then(|c| wallet.sign_login_challenge(&c));
+    match result {
+        Ok(signed) => WasmResult::ok(signed),
+        Err(e) => WasmResult::<SignedMessage>::err(
+            ErrorCode::InvalidInput,
+            format!("Failed to sign login: {}", e),
+        ),
+    }
+}
+
+/// Verify a signed sign-in challenge
+///
+/// # Arguments
+/// * `signed` - JSON object `{ publicKey, data, signature: { field, scalar } }`
+/// * `domain` - The expected domain
+/// * `nonce` - The nonce issued with the challenge
+///
+/// # Returns
+/// JSON object with the verified challenge fields
+#[wasm_bindgen]
+pub fn verify_login(signed: JsValue, domain: &str, nonce: &str) -> JsValue {
+    let signed: SignedMessage = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<LoginChallenge>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid signed login: {}", e),
+            );
+        }
+    };
+
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    match login::verify_login(&signed, domain, nonce, now) {
+        Ok(challenge) => WasmResult::ok(challenge),
+        Err(e) => WasmResult::<LoginChallenge>::err(
+            ErrorCode::VerificationFailed,
+            format!("Login verification failed: {}", e),
+        ),
+    }
+}
+
+/// Verify a signature over field elements, as o1js `Signature.verify`
+///
+/// # Arguments
+/// * `address` - The address of the signer
+/// * `fields` - Array of field elements, as decimal strings or integers
+/// * `signature` - `{ field, scalar }` or a Base58 signature
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with `true` if the signature is valid
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+pub fn verify_signature(
+    address: &str,
+    #[wasm_bindgen(unchecked_param_type = "(string | number)[]")] fields: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "SignatureJson | string")] signature: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    verify_fields(address, fields, signature, "kimchi", network)
+}
+
+/// Verify a signature over field elements, as made by `signFields`
+///
+/// # Arguments
+/// * `address` - The address of the signer
+/// * `fields` - Array of field elements, as decimal strings or integers
+/// * `signature` - `{ field, scalar }` or a Base58 signature
+/// * `scheme` - "kimchi" (o1js) or "legacy"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with `true` if the signature is valid
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+pub fn verify_fields(
+    address: &str,
+    #[wasm_bindgen(unchecked_param_type = "(string | number)[]")] fields: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "SignatureJson | string")] signature: JsValue,
+    scheme: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<bool>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let scheme: SignatureScheme = match scheme.parse() {
+        Ok(s) => s,
+        Err(e) => return WasmResult::<bool>::err(ErrorCode::InvalidInput, e),
+    };
+    let public_key = match PubKey::from_address(address) {
+        Ok(p) => p,
+        Err(e) => {
+            return WasmResult::<bool>::err(
+                ErrorCode::InvalidAddress,
+                format!("Invalid address: {:?}", e),
+            );
+        }
+    };
+    let fields = match parse_fields(fields) {
+        Ok(f) => f,
+        Err(e) => {
+            return WasmResult::<bool>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid fields: {}", e),
+            );
+        }
+    };
+    let signature = match signature.as_string() {
+        Some(base58) => SignatureJson::from_base58(&base58).map_err(|e| e.to_string()),
+        None => {
+            serde_wasm_bindgen::from_value::<SignatureJson>(signature).map_err(|e| e.to_string())
+        }
+    }
+    .and_then(|json| Signature::try_from(&json).map_err(|e| e.to_string()));
+    match signature {
+        Ok(signature) => WasmResult::ok(signing::verify(
+            &public_key,
+            &FieldElements(fields),
+            &signature,
+            network_id,
+            scheme,
+        )),
+        Err(e) => WasmResult::<bool>::err(
+            ErrorCode::InvalidSignature,
+            format!("Invalid signature: {}", e),
+        ),
+    }
+}
+
+/// Verify a signed message against the address it claims to come from
+///
+/// # Arguments
+/// * `signed` - JSON object `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with `true` if the signature is valid
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+pub fn verify_message(
+    #[wasm_bindgen(unchecked_param_type = "SignedMessage")] signed: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<bool>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let signed: SignedMessage = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<bool>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid signed message: {}", e),
+            );
+        }
+    };
+    match message::verify_message(&signed, network_id) {
+        Ok(valid) => WasmResult::ok(valid),
+        Err(e) => WasmResult::<bool>::err(
+            ErrorCode::InvalidSignature,
+            format!("Invalid signature: {}", e),
+        ),
+    }
+}
+
+/// Verify a signed payment or delegation against its sender
+///
+/// # Arguments
+/// * `signed` - JSON object `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON object with `true` if the signature is valid
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+pub fn verify_transaction(
+    #[wasm_bindgen(unchecked_param_type = "SignedTransaction")] signed: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<bool>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let signed: SignedTransaction = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<bool>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid signed transaction: {}", e),
+            );
+        }
+    };
+    match transaction::verify_transaction(&signed, network_id) {
+        Ok(valid) => WasmResult::ok(valid),
+        Err(e) => WasmResult::<bool>::transaction_err(&signed.data, "Invalid transaction", e),
+    }
+}
+
+/// Verify signed payments and delegations against their senders
+///
+/// The threaded build spreads the transactions over its thread pool.
+///
+/// # Arguments
+/// * `signed` - Array of `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+///
+/// # Returns
+/// JSON array with `true` for each valid signature, in the order of `signed`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<boolean[]>")]
+pub fn verify_transactions(
+    #[wasm_bindgen(unchecked_param_type = "SignedTransaction[]")] signed: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<Vec<bool>>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let signed: Vec<SignedTransaction> = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<Vec<bool>>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid signed transactions: {}", e),
+            );
+        }
+    };
+
+    let verify = |(index, signed): (usize, &SignedTransaction)| {
+        transaction::verify_transaction(signed, network_id.clone()).map_err(|e| (index, e))
+    };
+    #[cfg(feature = "threads")]
+    let valid: Result<Vec<bool>, _> = signed.par_iter().enumerate().map(verify).collect();
+    #[cfg(not(feature = "threads"))]
+    let valid: Result<Vec<bool>, _> = signed.iter().enumerate().map(verify).collect();
+
+    match valid {
+        Ok(valid) => WasmResult::ok(valid),
+        Err((index, e)) => WasmResult::<Vec<bool>>::transaction_err(
+            &signed[index].data,
+            &format!("Invalid transaction {}", index + 1),
+            e,
+        ),
+    }
+}
+
+/// Decode a zkApp verification key and compute its hash
+///
+/// Compare the hash with `verificationKeyHash` from `fetch_zkapp_state` to
+/// check that a deployed zkApp runs the expected build.
+///
+/// # Arguments
+/// * `data` - Base64 verification key, as in o1js `VerificationKey.data`
+///
+/// # Returns
+/// JSON object `{ hash, maxProofsVerified, actualWrapDomainSize }`
+#[wasm_bindgen]
+pub fn verification_key_hash(data: &str) -> JsValue {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct VerificationKeyData {
+        hash: String,
+        max_proofs_verified: u8,
+        actual_wrap_domain_size: u8,
+    }
+
+    match VerificationKey::from_base64(data) {
+        Ok(vk) => WasmResult::ok(VerificationKeyData {
+            hash: vk.hash().to_string(),
+            max_proofs_verified: vk.max_proofs_verified,
+            actual_wrap_domain_size: vk.actual_wrap_domain_size,
+        }),
+        Err(e) => WasmResult::<VerificationKeyData>::err(
+            ErrorCode::InvalidInput,
+            format!("Invalid verification key: {}", e),
+        ),
+    }
+}
+
+/// Parse an array of field elements, as decimal strings or integers
+fn parse_fields(fields: JsValue) -> Result<Vec<Fp>, String> {
+    serde_wasm_bindgen::from_value::<Vec<serde_json::Value>>(fields)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|v| oracle::encode_value(v).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Hash field elements with Poseidon, as o1js `Poseidon.hash`
+///
+/// # Arguments
+/// * `fields` - Array of field elements, as decimal strings or integers
+/// * `prefix` - Optional domain prefix of at most 20 characters, as o1js
+///   `Poseidon.hashWithPrefix`
+///
+/// # Returns
+/// JSON object with the hash, as a decimal string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn poseidon_hash(
+    #[wasm_bindgen(unchecked_param_type = "(string | number)[]")] fields: JsValue,
+    prefix: Option<String>,
+) -> JsValue {
+    let fields = match parse_fields(fields) {
+        Ok(f) => f,
+        Err(e) => {
+            return WasmResult::<String>::err(
+                ErrorCode::InvalidInput,
+                format!("Invalid fields: {}", e),
+            );
+        }
+    };
+    let hash = match prefix {
+        Some(prefix) => poseidon::hash_with_prefix(&prefix, &fields),
+        None => Ok(poseidon::hash(&fields)),
+    };
+    match hash {
+        Ok(hash) => WasmResult::ok(hash.to_string()),
+        Err(e) => WasmResult::<String>::err(ErrorCode::InvalidInput, e.to_string()),
+    }
+}
+
+/// Parse a big-endian hex field element, `0x` prefix optional
+///
+/// # Returns
+/// JSON object with the field element, as a decimal string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn field_from_hex(hex: &str) -> JsValue {
+    match convert::parse_field_hex(hex) {
+        Ok(field) => WasmResult::ok(field.to_string()),
+        Err(e) => WasmResult::<String>::err(ErrorCode::InvalidInput, e.to_string()),
+    }
+}
+
+/// Encode a decimal field element as `0x` and 64 big-endian hex digits
+///
+/// # Returns
+/// JSON object with the hex string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn field_to_hex(decimal: &str) -> JsValue {
+    match field_from_decimal_str(decimal) {
+        Ok(field) => WasmResult::ok(convert::field_to_hex(&field)),
+        Err(e) => WasmResult::<String>::err(ErrorCode::InvalidInput, e),
+    }
+}
+
+/// Parse a decimal field element, rejecting values not below the field order
+///
+/// # Returns
+/// JSON object with the canonical decimal string, without leading zeros
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn field_from_decimal(decimal: &str) -> JsValue {
+    match field_from_decimal_str(decimal) {
+        Ok(field) => WasmResult::ok(field.to_string()),
+        Err(e) => WasmResult::<String>::err(ErrorCode::InvalidInput, e),
+    }
+}
+
+/// Encode a hex field element, `0x` prefix optional, as a decimal string
+///
+/// # Returns
+/// JSON object with the decimal string
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub fn field_to_decimal(hex: &str) -> JsValue {
+    field_from_hex(hex)
+}
+
+fn field_from_decimal_str(decimal: &str) -> Result<Fp, String> {
+    oracle::parse_field(decimal).ok_or_else(|| format!("'{}' is not a field element", decimal))
+}
+
+/// Encode a `tx` envelope as the UR parts of an animated QR code
+///
+/// # Arguments
+/// * `envelope` - JSON of an unsigned or signed envelope
+///
+/// # Returns
+/// Array of `ur:mina-tx/...` strings, one per QR code; show them in a loop
+/// when there is more than one
+#[wasm_bindgen]
+pub fn encode_envelope_ur(envelope: &str) -> JsValue {
+    match TransactionEnvelope::from_json(envelope) {
+        Ok(envelope) => WasmResult::ok(ur::encode(
+            envelope.to_json().as_bytes(),
+            ur::DEFAULT_FRAGMENT_LEN,
+        )),
+        Err(e) => WasmResult::<Vec<String>>::err(
+            ErrorCode::InvalidInput,
+            format!("Invalid envelope: {}", e),
+        ),
+    }
+}
+
+/// Reads the UR parts of an animated QR code, as the camera scans them
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct UrScanner {
+    decoder: UrDecoder,
+}
+
+#[wasm_bindgen]
+impl UrScanner {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the text of a scanned QR code
+    ///
+    /// # Returns
+    /// JSON object `{ received, total, envelope }`; `envelope` is the JSON of
+    /// the transaction envelope once every part was read, and null before
+    pub fn receive(&mut self, part: &str) -> JsValue {
+        #[derive(Serialize)]
+        struct ScanProgress {
+            received: usize,
+            total: usize,
+            envelope: Option<String>,
+        }
+
+        let envelope = match self.decoder.receive(part) {
+            Ok(Some(payload)) => match String::from_utf8(payload)
+                .map_err(|e| e.to_string())
+                .and_then(|json| TransactionEnvelope::from_json(&json).map_err(|e| e.to_string()))
+            {
+                Ok(envelope) => Some(envelope.to_json()),
+                Err(e) => {
+                    return WasmResult::<ScanProgress>::err(
+                        ErrorCode::InvalidInput,
+                        format!("Invalid envelope: {}", e),
+                    );
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                return WasmResult::<ScanProgress>::err(ErrorCode::InvalidInput, e.to_string());
+            }
+        };
+        let (received, total) = self.decoder.progress();
+        WasmResult::ok(ScanProgress {
+            received: if envelope.is_some() {
+                total.max(1)
+            } else {
+                received
+            },
+            total: total.max(1),
+            envelope,
+        })
+    }
+}
+
+/// Estimate the staking rewards of a stake delegated to a block producer
+///
+/// # Arguments
+/// * `stake` - Stake, in MINA
+/// * `delegate_fee` - Fee of the delegate, in percent (e.g. "5")
+/// * `total_stake` - Total stake of the staking ledger, in MINA (see `fetch_total_stake`)
+/// * `locked` - Whether the stake is still under a vesting schedule
+/// * `supercharged` - Whether unlocked stake earns a doubled coinbase
+///
+/// # Returns
+/// JSON object `{ stake, delegateFeeBps, coinbase, expectedBlocksPerEpoch, perEpoch, perYear, yearlyYieldBps }`,
+/// amounts in nanomina strings
+#[wasm_bindgen]
+pub fn estimate_staking_rewards(
+    stake: &str,
+    delegate_fee: &str,
+    total_stake: &str,
+    locked: bool,
+    supercharged: bool,
+) -> JsValue {
+    let stake: Amount = match stake.parse() {
+        Ok(a) => a,
+        Err(e) => {
+            return WasmResult::<RewardEstimate>::err(
+                ErrorCode::InvalidAmount,
+                format!("Invalid stake: {}", e),
+            );
+        }
+    };
+    let total_stake: Amount = match total_stake.parse() {
+        Ok(a) => a,
+        Err(e) => {
+            return WasmResult::<RewardEstimate>::err(
+                ErrorCode::InvalidAmount,
+                format!("Invalid total stake: {}", e),
+            );
+        }
+    };
+    let fee_bps = match rewards::parse_percent(delegate_fee) {
+        Ok(bps) => bps,
+        Err(e) => return WasmResult::<RewardEstimate>::err(ErrorCode::InvalidInput, e.to_string()),
+    };
+
+    let params = RewardParameters::new(total_stake).with_supercharged(supercharged);
+    match rewards::estimate(stake, fee_bps, locked, &params) {
+        Ok(estimate) => WasmResult::ok(estimate),
+        Err(e) => WasmResult::<RewardEstimate>::err(ErrorCode::InvalidInput, e.to_string()),
+    }
+}
+
+/// Split a comma-separated list of endpoints, used for failover
+#[cfg(feature = "network")]
+fn split_endpoints(endpoints: &str) -> Vec<String> {
+    endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// The network of a call to a node, and a client for its endpoints
+#[cfg(feature = "network")]
+fn node_client(
+    network: &JsValue,
+    endpoint: Option<&str>,
+) -> Result<(Network, GraphQlClient), (ErrorCode, String)> {
+    let network = Network::from_js(network).map_err(|e| (ErrorCode::InvalidNetwork, e))?;
+    let client = network
+        .client(endpoint)
+        .map_err(|e| (ErrorCode::InvalidInput, e))?;
+    Ok((network, client))
+}
+
+/// Fetch the balance of an address from a node, using the browser `fetch`
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - GraphQL endpoint URL of the node, or several separated by
+///   commas; those of the `NetworkConfig` if omitted
+///
+/// # Returns
+/// Promise of a JSON object `{ total, liquid, locked }`, in nanomina strings
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Balance>")]
+pub async fn fetch_balance(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<Balance>::err(code, e),
+    };
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<Balance>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    match client.get_balance(&address).await {
+        Ok(balance) => WasmResult::ok(balance),
+        Err(e) => WasmResult::<Balance>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch balance: {}", e),
+        ),
+    }
+}
+
+/// Fetch the next nonce of an address from a node, counting its pending
+/// transactions
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object with the nonce to sign the next transaction with
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<number>")]
+pub async fn fetch_nonce(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<u32>::err(code, e),
+    };
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<u32>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    match client.get_inferred_nonce(&address).await {
+        Ok(nonce) => WasmResult::ok(nonce),
+        Err(e) => WasmResult::<u32>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch nonce: {}", e),
+        ),
+    }
+}
+
+/// Fetch the balance of an address through a configurable backend
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `backend` - Either "graphql" or "rosetta"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - Endpoint URL of the node, or several separated by commas;
+///   those of the `NetworkConfig` if omitted
+///
+/// # Returns
+/// Promise of a JSON object `{ total, liquid, locked }`, in nanomina strings
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<Balance>")]
+pub async fn fetch_balance_from(
+    address: String,
+    backend: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let network = match Network::from_js(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<Balance>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let kind = match backend.parse::<BackendKind>() {
+        Ok(kind) => kind,
+        Err(e) => return WasmResult::<Balance>::err(ErrorCode::InvalidInput, e),
+    };
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<Balance>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    let endpoints = network.endpoints_or(endpoint.as_deref().unwrap_or_default());
+    if endpoints.is_empty() {
+        return WasmResult::<Balance>::err(ErrorCode::InvalidInput, NO_ENDPOINT.to_string());
+    }
+    match Backend::with_endpoints(kind, endpoints, &network.id)
+        .get_balance(&address)
+        .await
+    {
+        Ok(balance) => WasmResult::ok(balance),
+        Err(e) => WasmResult::<Balance>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch balance: {}", e),
+        ),
+    }
+}
+
+/// Suggest transaction fees from the node's transaction pool
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object `{ slow, standard, fast, poolSize }`, fees in
+/// nanomina strings
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<FeeSuggestion>")]
+pub async fn suggest_fee(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<FeeSuggestion>::err(code, e),
+    };
+    match client.suggest_fee().await {
+        Ok(suggestion) => WasmResult::ok(suggestion),
+        Err(e) => WasmResult::<FeeSuggestion>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to suggest a fee: {}", e),
+        ),
+    }
+}
+
+/// Broadcast a signed payment or delegation to a node
+///
+/// The node is first checked to follow the chain of `network`, as
+/// `verify_chain_id` does, so that the transaction is not replayed on
+/// another chain.
+///
+/// # Arguments
+/// * `signed` - JSON object `{ publicKey, data, signature: { field, scalar } }`
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+/// * `force` - Broadcast even if the node follows another chain
+///
+/// # Returns
+/// Promise of a JSON object with the transaction hash
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn broadcast_transaction(
+    #[wasm_bindgen(unchecked_param_type = "SignedTransaction")] signed: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+    force: Option<bool>,
+) -> JsValue {
+    let (network, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<String>::err(code, e),
+    };
+    let signed: SignedTransaction = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<String>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid signed transaction: {}", e),
+            );
+        }
+    };
+
+    match network.check_chain_id(&client).await {
+        Ok(_) => {}
+        Err(NetworkError::ChainMismatch { .. }) if force.unwrap_or(false) => {}
+        Err(e) => {
+            return WasmResult::<String>::err(
+                ErrorCode::NetworkError,
+                format!("Failed to verify chain ID: {}", e),
+            );
+        }
+    }
+
+    match client.broadcast(&signed).await {
+        Ok(hash) => WasmResult::ok(hash),
+        Err(e) => WasmResult::<String>::err(
+            ErrorCode::BroadcastFailed,
+            format!("Failed to broadcast transaction: {}", e),
+        ),
+    }
+}
+
+/// Summarize what a payment costs the sender and credits the receiver
+///
+/// A payment to an address without an account pays the 1 MINA account
+/// creation fee out of its amount. Whether the receiver account is new is
+/// taken from `new_account` if given, otherwise looked up on the node, if
+/// there is an endpoint to ask.
+///
+/// # Arguments
+/// * `transaction` - The unsigned payment, in the `mina-signer` JSON shape
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+/// * `new_account` - Whether the receiver account does not exist yet
+///
+/// # Returns
+/// Promise of a JSON object `{ amount, fee, totalCost, createsAccount, accountCreationFee, credited }`;
+/// `credited` is null if the amount does not cover the account creation fee
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<PaymentPreview>")]
+pub async fn preview_payment(
+    #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+    new_account: Option<bool>,
+) -> JsValue {
+    let network = match Network::from_js(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<PaymentPreview>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+        Ok(t) => t,
+        Err(e) => {
+            return WasmResult::<PaymentPreview>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid transaction: {}", e),
+            );
+        }
+    };
+
+    let creates_account = match (new_account, network.client(endpoint.as_deref())) {
+        (Some(new_account), _) => Some(new_account),
+        (None, Ok(client)) => match client.account_exists(&transaction.to).await {
+            Ok(exists) => Some(!exists),
+            Err(e) => {
+                return WasmResult::<PaymentPreview>::err(
+                    ErrorCode::NetworkError,
+                    format!("Failed to look up the receiver account: {}", e),
+                );
+            }
+        },
+        (None, Err(_)) => None,
+    };
+
+    WasmResult::ok(transaction.preview(creates_account))
+}
+
+/// Check that a node is on the chain of the given network
+///
+/// Call it before signing with a nonce from the node or broadcasting, so that
+/// nothing is sent to a node of another network. A `NetworkConfig` with a
+/// `chainId` checks the node against it instead of the known chain IDs.
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object with the chain ID of the node, or an error on mismatch
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn verify_chain_id(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (network, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<String>::err(code, e),
+    };
+    match network.check_chain_id(&client).await {
+        Ok(chain_id) => WasmResult::ok(chain_id),
+        Err(e) => WasmResult::<String>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to verify chain ID: {}", e),
+        ),
+    }
+}
+
+/// List the pending transactions of an address, with nonce gaps, low fees
+/// and stale nonces that keep them from being included
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object `{ address, ledgerNonce, transactions, issues }`;
+/// each issue has a `type` of "nonceGap", "lowFee" or "staleNonce"
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<PendingReport>")]
+pub async fn fetch_pending_transactions(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<PendingReport>::err(code, e),
+    };
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<PendingReport>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    match client.pending_transactions(&address).await {
+        Ok(report) => WasmResult::ok(report),
+        Err(e) => WasmResult::<PendingReport>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch pending transactions: {}", e),
+        ),
+    }
+}
+
+/// Look up the status of a transaction on a node
+///
+/// Call it periodically to track a transaction until it is finalized.
+///
+/// # Arguments
+/// * `hash` - The transaction hash
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `confirmations` - Confirmations after which the transaction is final
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object `{ status: "pending" | "included" | "finalized" | "unknown", ... }`
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<TxStatus>")]
+pub async fn fetch_transaction_status(
+    hash: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    confirmations: u32,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<TxStatus>::err(code, e),
+    };
+    let tracker = TxTracker::new(client).with_confirmations(confirmations);
+    match tracker.status(&hash).await {
+        Ok(status) => WasmResult::ok(status),
+        Err(e) => WasmResult::<TxStatus>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch status: {}", e),
+        ),
+    }
+}
+
+/// Fetch a page of the transaction history of an address from an archive indexer
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `limit` - Number of transactions per page
+/// * `before` - `nextCursor` of the previous page, or undefined for the newest page
+/// * `endpoint` - GraphQL endpoint URL of the indexer, or several separated
+///   by commas; the `archiveEndpoints` of the `NetworkConfig` if omitted
+///
+/// # Returns
+/// Promise of a JSON object `{ transactions, nextCursor }`, newest first
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<HistoryPage>")]
+pub async fn fetch_history(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    limit: u32,
+    before: Option<u32>,
+    endpoint: Option<String>,
+) -> JsValue {
+    let network = match Network::from_js(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<HistoryPage>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let endpoints = network.archive_endpoints_or(endpoint.as_deref());
+    if endpoints.is_empty() {
+        return WasmResult::<HistoryPage>::err(
+            ErrorCode::InvalidInput,
+            "No indexer endpoint: pass one, or set the archiveEndpoints of the NetworkConfig"
+                .to_string(),
+        );
+    }
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<HistoryPage>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    let client = ArchiveClient::with_endpoints(endpoints);
+    match client.history(&address, limit, before.map(u64::from)).await {
+        Ok(page) => WasmResult::ok(page),
+        Err(e) => WasmResult::<HistoryPage>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch history: {}", e),
+        ),
+    }
+}
+
+/// Fetch the total stake of the current staking ledger, for `estimate_staking_rewards`
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object with the total stake, in MINA
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<string>")]
+pub async fn fetch_total_stake(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<String>::err(code, e),
+    };
+    match client.total_stake().await {
+        Ok(total) => WasmResult::ok(total.to_string()),
+        Err(e) => WasmResult::<String>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch total stake: {}", e),
+        ),
+    }
+}
+
+/// Fetch the state, URI and permissions of a zkApp account
+///
+/// # Arguments
+/// * `address` - The zkApp address
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object `{ address, appState: [{ decimal, hex }], zkappUri, provedState, verificationKeyHash, permissions }`;
+/// `appState` is empty if no zkApp is deployed to the account
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<ZkappState>")]
+pub async fn fetch_zkapp_state(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<ZkappState>::err(code, e),
+    };
+    if let Err(e) = mina_web_wallet_core::address_to_pubkey(&address) {
+        return WasmResult::<ZkappState>::err(
+            ErrorCode::InvalidAddress,
+            format!("Invalid address: {:?}", e),
+        );
+    }
+
+    match client.zkapp_state(&address).await {
+        Ok(state) => WasmResult::ok(state),
+        Err(e) => WasmResult::<ZkappState>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to fetch zkApp state: {}", e),
+        ),
+    }
+}
+
+/// The delegation state of an address, with its entry in the staking ledger
+#[cfg(feature = "network")]
+#[derive(Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct StakingData {
+    info: StakingInfo,
+    staking_ledger: Option<StakingLedgerEntry>,
+    /// Whether the delegation counts in the current epoch
+    delegation_active: Option<bool>,
+}
+
+/// Fetch the delegation state of an address
+///
+/// # Arguments
+/// * `address` - The Mina address to query
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+/// * `archive_endpoint` - Indexer endpoint, to look up the staking ledger;
+///   the `archiveEndpoints` of the `NetworkConfig` if omitted, and no lookup
+///   without either
+///
+/// # Returns
+/// Promise of a JSON object `{ info, stakingLedger, delegationActive }`
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<StakingData>")]
+pub async fn fetch_staking_info(
+    address: String,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+    archive_endpoint: Option<String>,
+) -> JsValue {
+    let (network, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<StakingData>::err(code, e),
+    };
+    let info = match client.staking_info(&address).await {
+        Ok(i) => i,
+        Err(e) => {
+            return WasmResult::<StakingData>::err(
+                ErrorCode::NetworkError,
+                format!("Failed to fetch staking info: {}", e),
+            );
+        }
+    };
+
+    let archive_endpoints = network.archive_endpoints_or(archive_endpoint.as_deref());
+    let staking_ledger = if archive_endpoints.is_empty() {
+        None
+    } else {
+        match ArchiveClient::with_endpoints(archive_endpoints)
+            .staking_ledger_entry(&address, info.epoch)
+            .await
+        {
+            Ok(entry) => entry,
+            Err(e) => {
+                return WasmResult::<StakingData>::err(
+                    ErrorCode::NetworkError,
+                    format!("Failed to fetch staking ledger: {}", e),
+                );
+            }
+        }
+    };
+
+    let delegation_active = staking_ledger
+        .as_ref()
+        .map(|entry| staking::delegation_is_active(&info, entry));
+    WasmResult::ok(StakingData {
+        info,
+        staking_ledger,
+        delegation_active,
+    })
+}
+
+/// The status of a node, with the verdict of `is_healthy` and `warnings`
+#[cfg(feature = "network")]
+#[derive(Serialize, Tsify)]
+struct NodeStatusData {
+    #[serde(flatten)]
+    status: NodeStatus,
+    healthy: bool,
+    warnings: Vec<String>,
+}
+
+/// Check the health of a node before using it
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object with sync status, chain ID, block height,
+/// latency, `healthy` and human-readable `warnings`
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<NodeStatusData>")]
+pub async fn fetch_node_status(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (_, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<NodeStatusData>::err(code, e),
+    };
+    match client.node_status().await {
+        Ok(status) => WasmResult::ok(NodeStatusData {
+            healthy: status.is_healthy(),
+            warnings: status.warnings(),
+            status,
+        }),
+        Err(e) => WasmResult::<NodeStatusData>::err(
+            ErrorCode::NetworkError,
+            format!("Node is unreachable: {}", e),
+        ),
+    }
+}
+
+async fn load_queue() -> Result<(BroadcastQueue, IndexedDbStorage), String> {
+    let storage = IndexedDbStorage::open(&[QUEUE_STORAGE_KEY])
+        .await
+        .map_err(|e| format!("Failed to read the queue: {:?}", e))?;
+    let queue = BroadcastQueue::load(&storage).map_err(|e| e.to_string())?;
+    Ok((queue, storage))
+}
+
+async fn save_queue(queue: &BroadcastQueue, mut storage: IndexedDbStorage) -> Result<(), String> {
+    queue.save(&mut storage).map_err(|e| e.to_string())?;
+    storage
+        .commit()
+        .await
+        .map_err(|e| format!("Failed to save the queue: {:?}", e))
+}
+
+/// Store a signed transaction in IndexedDB, to broadcast it when the node is reachable
+///
+/// # Arguments
+/// * `signed` - JSON object `{ publicKey, data, signature: { field, scalar } }`
+///
+/// # Returns
+/// Promise of the number of queued transactions
+#[wasm_bindgen(unchecked_return_type = "WasmResult<number>")]
+pub async fn queue_transaction(
+    #[wasm_bindgen(unchecked_param_type = "SignedTransaction")] signed: JsValue,
+) -> JsValue {
+    let signed: SignedTransaction = match serde_wasm_bindgen::from_value(signed) {
+        Ok(s) => s,
+        Err(e) => {
+            return WasmResult::<usize>::err(
+                ErrorCode::InvalidTransaction,
+                format!("Invalid signed transaction: {}", e),
+            );
+        }
+    };
+    let (mut queue, storage) = match load_queue().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<usize>::err(ErrorCode::StorageError, e),
+    };
+    let queued_at = (js_sys::Date::now() / 1000.0) as u64;
+    if let Err(e) = queue.push(signed, queued_at) {
+        return WasmResult::<usize>::err(ErrorCode::InvalidTransaction, e.to_string());
+    }
+    match save_queue(&queue, storage).await {
+        Ok(()) => WasmResult::ok(queue.len()),
+        Err(e) => WasmResult::<usize>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// List the transactions waiting in the broadcast queue
+///
+/// # Returns
+/// Promise of an array of `{ transaction, queuedAt, attempts, lastError }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<QueuedTransaction[]>")]
+pub async fn list_queued_transactions() -> JsValue {
+    match load_queue().await {
+        Ok((queue, _)) => WasmResult::ok(queue),
+        Err(e) => WasmResult::<BroadcastQueue>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Remove a transaction from the broadcast queue
+///
+/// # Returns
+/// Promise of the number of queued transactions left
+#[wasm_bindgen(unchecked_return_type = "WasmResult<number>")]
+pub async fn remove_queued_transaction(from: String, nonce: u32) -> JsValue {
+    let (mut queue, storage) = match load_queue().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<usize>::err(ErrorCode::StorageError, e),
+    };
+    if queue.remove(&from, nonce).is_none() {
+        return WasmResult::<usize>::err(
+            ErrorCode::NotFound,
+            format!("No queued transaction from {} with nonce {}", from, nonce),
+        );
+    }
+    match save_queue(&queue, storage).await {
+        Ok(()) => WasmResult::ok(queue.len()),
+        Err(e) => WasmResult::<usize>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// Broadcast the queued transactions whose nonce is next for their sender
+///
+/// Transactions whose nonce was already used are dropped; the others stay
+/// queued until they can be sent. Nothing is sent to a node of another
+/// chain than `network`.
+///
+/// # Arguments
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `endpoint` - As for `fetch_balance`
+///
+/// # Returns
+/// Promise of a JSON object `{ results: [{ from, nonce, outcome, ... }], offline }`
+#[cfg(feature = "network")]
+#[wasm_bindgen(unchecked_return_type = "WasmResult<FlushReport>")]
+pub async fn flush_broadcast_queue(
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    endpoint: Option<String>,
+) -> JsValue {
+    let (network, client) = match node_client(&network, endpoint.as_deref()) {
+        Ok(c) => c,
+        Err((code, e)) => return WasmResult::<FlushReport>::err(code, e),
+    };
+    if let Err(e) = network.check_chain_id(&client).await {
+        return WasmResult::<FlushReport>::err(
+            ErrorCode::NetworkError,
+            format!("Failed to verify chain ID: {}", e),
+        );
+    }
+    let (mut queue, storage) = match load_queue().await {
+        Ok(loaded) => loaded,
+        Err(e) => return WasmResult::<FlushReport>::err(ErrorCode::StorageError, e),
+    };
+    let report = queue.flush(&client).await;
+    match save_queue(&queue, storage).await {
+        Ok(()) => WasmResult::ok(report),
+        Err(e) => WasmResult::<FlushReport>::err(ErrorCode::StorageError, e),
+    }
+}
+
+/// A live block subscription, closed with `close()`
+#[cfg(feature = "network")]
+#[wasm_bindgen]
+pub struct BlockSubscription {
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[cfg(feature = "network")]
+#[wasm_bindgen]
+impl BlockSubscription {
+    /// Close the websocket and stop receiving events
+    pub fn close(&self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+}
+
+/// Subscribe to new blocks through the websocket endpoint of a node
+///
+/// With an `address`, only blocks with transactions from or to it are
+/// reported. `callback` is called with every event: `{type: "block", ...,
+/// incomingPayments}`, `{type: "error", message}` or `{type: "completed"}`.
+#[cfg(feature = "network")]
+#[wasm_bindgen]
+pub fn subscribe_blocks(
+    endpoint: &str,
+    address: Option<String>,
+    callback: js_sys::Function,
+) -> Result<BlockSubscription, JsValue> {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct EventData {
+        #[serde(flatten)]
+        event: SubscriptionEvent,
+        incoming_payments: Vec<subscription::BlockCommand>,
+    }
+
+    let topic = match &address {
+        Some(address) => Topic::Account(address.clone()),
+        None => Topic::NewBlocks,
+    };
+    subscribe(endpoint, topic, move |event| {
+        let incoming_payments = match (&event, &address) {
+            (SubscriptionEvent::Block(block), Some(address)) => block
+                .incoming_payments(address)
+                .into_iter()
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+        let data = EventData {
+            event,
+            incoming_payments,
+        };
+        if let Ok(value) = serde_wasm_bindgen::to_value(&data) {
+            let _ = callback.call1(&JsValue::NULL, &value);
+        }
+    })
+}
+
+/// Open a websocket to a node and subscribe to `topic`
+#[cfg(feature = "network")]
+pub(crate) fn subscribe(
+    endpoint: &str,
+    topic: Topic,
+    on_event: impl Fn(SubscriptionEvent) + 'static,
+) -> Result<BlockSubscription, JsValue> {
+    let socket = WebSocket::new_with_str(endpoint, subscription::SUBPROTOCOL)?;
+    let client = Rc::new(RefCell::new(SubscriptionClient::new()));
+    let on_event = Rc::new(on_event);
+
+    let on_open = {
+        let socket = socket.clone();
+        let client = client.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let on_event = on_event.clone();
+            let (_, start) = client
+                .borrow_mut()
+                .subscribe(&topic, Box::new(move |event| on_event(event)));
+            let _ = socket.send_with_str(&SubscriptionClient::init_message());
+            let _ = socket.send_with_str(&start);
+        })
+    };
+
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        if let Some(text) = event.data().as_string()
+            && let Err(e) = client.borrow_mut().handle_message(&text)
+        {
+            web_sys::console::warn_1(&format!("Ignoring subscription message: {}", e).into());
+        }
+    });
+
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+    Ok(BlockSubscription {
+        socket,
+        _on_open: on_open,
+        _on_message: on_message,
+    })
+}
+
+/// Get the library version
+#[wasm_bindgen]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
     use wasm_bindgen_test::*;
 
     wasm_bindgen_test_configure!(run_in_browser);
 
     #[wasm_bindgen_test]
     fn test_generate_wallet() {
-        let result = generate_wallet("mainnet");
+        let result = generate_wallet("mainnet".into());
         // Result should be a valid JsValue
         assert!(!result.is_null());
     }
 
+    #[wasm_bindgen_test]
+    fn test_generate_wallets() {
+        let result: WasmResult<Vec<WalletData>> =
+            serde_wasm_bindgen::from_value(generate_wallets(3, "testnet".into(), None)).unwrap();
+        assert_eq!(result.data.unwrap().len(), 3);
+
+        let seeded = |seed: &str| -> Vec<String> {
+            let result: WasmResult<Vec<WalletData>> = serde_wasm_bindgen::from_value(
+                generate_wallets(2, "testnet".into(), Some(seed.to_string())),
+            )
+            .unwrap();
+            result
+                .data
+                .unwrap()
+                .into_iter()
+                .map(|w| w.address.clone())
+                .collect()
+        };
+        assert_eq!(seeded("seed"), seeded("seed"));
+        assert_ne!(seeded("seed"), seeded("other"));
+    }
+
     #[wasm_bindgen_test]
     fn test_validate_address() {
         let result = validate_address("B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg");
         assert!(!result.is_null());
     }
 
+    #[wasm_bindgen_test]
+    fn test_wasm_wallet_dispose() {
+        let mut wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let address = wallet.address().unwrap();
+        assert!(address.starts_with("B62q"));
+        assert_eq!(wallet.network().unwrap(), "testnet");
+        assert!(!wallet.sign_message("hello").is_null());
+
+        wallet.dispose();
+        assert!(wallet.is_disposed());
+        assert!(wallet.address().is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_message() {
+        let wallet = WasmWallet::generate("mainnet".into()).unwrap();
+        let signed: WasmResult<SignedMessage> =
+            serde_wasm_bindgen::from_value(wallet.sign_message("hello")).unwrap();
+        let data = serde_wasm_bindgen::to_value(&signed.data.unwrap()).unwrap();
+        let valid = verify_message(data.clone(), "mainnet".into());
+        let valid: WasmResult<bool> = serde_wasm_bindgen::from_value(valid).unwrap();
+        assert_eq!(valid.data, Some(true));
+
+        // A signature for mainnet does not verify on testnet
+        let valid = verify_message(data, "testnet".into());
+        let valid: WasmResult<bool> = serde_wasm_bindgen::from_value(valid).unwrap();
+        assert_eq!(valid.data, Some(false));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_error_codes() {
+        let result: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet("devnet".into())).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.code, Some(ErrorCode::InvalidNetwork));
+
+        let wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address().unwrap(),
+            "B62qnot",
+            Amount::from_nanomina(1_000_000_000),
+            Amount::ZERO,
+            0,
+        );
+        let payment = serde_wasm_bindgen::to_value(&payment).unwrap();
+        let result: WasmResult<serde_json::Value> =
+            serde_wasm_bindgen::from_value(sign_payment(&wallet, payment)).unwrap();
+        assert_eq!(result.code, Some(ErrorCode::InvalidTransaction));
+        let fields: Vec<_> = result.details.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["fee", "to"]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_keystore_lock() {
+        let wallet: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet("testnet".into())).unwrap();
+        let secret_key = wallet.data.unwrap().secret_key_hex.clone();
+        let mut keystore =
+            WasmKeystore::create(&secret_key, "correct horse", "testnet".into()).unwrap();
+        assert!(keystore.is_locked());
+
+        let wrong: WasmResult<()> =
+            serde_wasm_bindgen::from_value(keystore.unlock("wrong")).unwrap();
+        assert_eq!(wrong.code, Some(ErrorCode::DecryptionFailed));
+        let signed: WasmResult<SignedMessage> =
+            serde_wasm_bindgen::from_value(keystore.sign_message("hello")).unwrap();
+        assert_eq!(signed.code, Some(ErrorCode::Locked));
+
+        // The export unlocks with the same passphrase
+        let mut keystore = WasmKeystore::from_encrypted(&keystore.export_encrypted()).unwrap();
+        let unlocked: WasmResult<()> =
+            serde_wasm_bindgen::from_value(keystore.unlock("correct horse")).unwrap();
+        assert!(unlocked.success);
+        assert!(!keystore.sign_message("hello").is_null());
+        keystore.lock();
+        assert!(keystore.is_locked());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_keystore_authenticator() {
+        let wallet: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet("testnet".into())).unwrap();
+        let secret_key = wallet.data.unwrap().secret_key_hex.clone();
+        let mut keystore =
+            WasmKeystore::create(&secret_key, "correct horse", "testnet".into()).unwrap();
+        let locked: WasmResult<String> =
+            serde_wasm_bindgen::from_value(keystore.wrap_key(vec![1; 32])).unwrap();
+        assert_eq!(locked.code, Some(ErrorCode::Locked));
+
+        keystore.unlock("correct horse");
+        let wrapped: WasmResult<String> =
+            serde_wasm_bindgen::from_value(keystore.wrap_key(vec![1; 32])).unwrap();
+        let wrapped = wrapped.data.unwrap();
+        keystore.lock();
+
+        let short: WasmResult<()> = serde_wasm_bindgen::from_value(
+            keystore.unlock_with_authenticator(&wrapped, vec![1; 16]),
+        )
+        .unwrap();
+        assert_eq!(short.code, Some(ErrorCode::InvalidInput));
+        let wrong: WasmResult<()> = serde_wasm_bindgen::from_value(
+            keystore.unlock_with_authenticator(&wrapped, vec![2; 32]),
+        )
+        .unwrap();
+        assert_eq!(wrong.code, Some(ErrorCode::DecryptionFailed));
+        let unlocked: WasmResult<()> = serde_wasm_bindgen::from_value(
+            keystore.unlock_with_authenticator(&wrapped, vec![1; 32]),
+        )
+        .unwrap();
+        assert!(unlocked.success);
+        assert!(!keystore.is_locked());
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_provider() {
+        let wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let approve = js_sys::Function::new_with_args("method", "return method !== 'signFields'");
+        let provider = MinaProvider::new(&wallet, String::new(), approve).unwrap();
+        let call = |promise: js_sys::Promise| async move {
+            wasm_bindgen_futures::JsFuture::from(promise).await.unwrap()
+        };
+
+        let error: serde_json::Value = serde_wasm_bindgen::from_value(
+            call(provider.sign_message(JsValue::from_str("not an object"))).await,
+        )
+        .unwrap();
+        assert_eq!(error["code"].as_f64(), Some(20003.0));
+        let args = js_sys::JSON::parse(r#"{"message":"hello"}"#).unwrap();
+        let error: serde_json::Value =
+            serde_wasm_bindgen::from_value(call(provider.sign_message(args.clone())).await)
+                .unwrap();
+        assert_eq!(error["code"].as_f64(), Some(1001.0));
+
+        let accounts: Vec<String> =
+            serde_wasm_bindgen::from_value(call(provider.request_accounts()).await).unwrap();
+        assert_eq!(accounts, vec![wallet.address().unwrap()]);
+        let signed: SignedMessage =
+            serde_wasm_bindgen::from_value(call(provider.sign_message(args)).await).unwrap();
+        assert!(message::verify_message(&signed, NetworkId::TESTNET).unwrap());
+
+        let fields = js_sys::JSON::parse(r#"{"message":["1","2"]}"#).unwrap();
+        let error: serde_json::Value =
+            serde_wasm_bindgen::from_value(call(provider.sign_fields(fields)).await).unwrap();
+        assert_eq!(error["code"].as_f64(), Some(1002.0));
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_keystore_auto_lock() {
+        let wallet: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet("testnet".into())).unwrap();
+        let secret_key = wallet.data.unwrap().secret_key_hex.clone();
+        let mut keystore =
+            WasmKeystore::create(&secret_key, "correct horse", "testnet".into()).unwrap();
+        assert!(keystore.set_auto_lock(-1.0).is_err());
+
+        // 0.001 minute is 60 ms
+        keystore.set_auto_lock(0.001).unwrap();
+        let unlocked: WasmResult<()> =
+            serde_wasm_bindgen::from_value(keystore.unlock("correct horse")).unwrap();
+        assert!(unlocked.success);
+        assert!(!keystore.is_locked());
+        gloo_timers::future::TimeoutFuture::new(200).await;
+        assert!(keystore.is_locked());
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_keystore_events() {
+        let wallet: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet("testnet".into())).unwrap();
+        let secret_key = wallet.data.unwrap().secret_key_hex.clone();
+        let mut keystore =
+            WasmKeystore::create(&secret_key, "correct horse", "testnet".into()).unwrap();
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let listener = |name: &'static str| {
+            let seen = seen.clone();
+            Closure::<dyn FnMut(JsValue)>::new(move |_| seen.borrow_mut().push(name))
+                .into_js_value()
+                .unchecked_into::<js_sys::Function>()
+        };
+        let unlocked = keystore.on_unlocked(listener("unlocked"));
+        keystore.on_locked(listener("locked"));
+
+        keystore.unlock("correct horse");
+        keystore.lock();
+        // Locking a locked keystore is not an event
+        keystore.lock();
+        gloo_timers::future::TimeoutFuture::new(0).await;
+        assert_eq!(*seen.borrow(), ["unlocked", "locked"]);
+
+        assert!(keystore.off(unlocked));
+        assert!(!keystore.off(unlocked));
+        keystore.unlock("correct horse");
+        keystore.dispose();
+        gloo_timers::future::TimeoutFuture::new(0).await;
+        assert_eq!(*seen.borrow(), ["unlocked", "locked", "locked"]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_generate_qr() {
+        let address = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+        let result = generate_qr(address, "png");
+        let data = js_sys::Reflect::get(&result, &"data".into()).unwrap();
+        let png = js_sys::Uint8Array::new(&data).to_vec();
+        assert_eq!(png, qr::generate_qr(address, QrFormat::Png).unwrap());
+
+        let result: WasmResult<()> =
+            serde_wasm_bindgen::from_value(generate_qr(address, "gif")).unwrap();
+        assert_eq!(result.code, Some(ErrorCode::InvalidInput));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_poseidon_hash() {
+        let fields = serde_wasm_bindgen::to_value(&["1", "2"]).unwrap();
+        let result: WasmResult<String> =
+            serde_wasm_bindgen::from_value(poseidon_hash(fields, None)).unwrap();
+        let expected = poseidon::hash(&[Fp::from(1u64), Fp::from(2u64)]);
+        assert_eq!(result.data.unwrap(), expected.to_string());
+
+        let hex: WasmResult<String> = serde_wasm_bindgen::from_value(field_to_hex("255")).unwrap();
+        let hex = hex.data.unwrap();
+        assert_eq!(hex, format!("0x{}ff", "00".repeat(31)));
+        let decimal: WasmResult<String> =
+            serde_wasm_bindgen::from_value(field_from_hex(&hex)).unwrap();
+        assert_eq!(decimal.data.unwrap(), "255");
+        let invalid: WasmResult<String> =
+            serde_wasm_bindgen::from_value(field_from_decimal("-1")).unwrap();
+        assert_eq!(invalid.code, Some(ErrorCode::InvalidInput));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sign_fields() {
+        // The same signature as the core crate, whose tests check it against
+        // the o1js vectors of scripts/o1js-vectors.mjs
+        let vectors =
+            test_vectors::generate("o1js", 1, SignatureScheme::Kimchi, NetworkId::TESTNET).unwrap();
+        let vector = &vectors.vectors[0];
+        let fields = vector.message.fields.clone().unwrap();
+        let wallet = WasmWallet::new(&vector.secret_key, "testnet".into()).unwrap();
+
+        let js_fields = serde_wasm_bindgen::to_value(&fields).unwrap();
+        let signed: WasmResult<SignatureJson> =
+            serde_wasm_bindgen::from_value(wallet.sign_fields(js_fields, "kimchi")).unwrap();
+        let signature = signed.data.unwrap();
+        assert_eq!(signature, vector.message.signature);
+
+        let js_fields = serde_wasm_bindgen::to_value(&fields).unwrap();
+        let js_signature = serde_wasm_bindgen::to_value(&signature).unwrap();
+        let verified: WasmResult<bool> = serde_wasm_bindgen::from_value(verify_fields(
+            &vector.public_key,
+            js_fields,
+            js_signature,
+            "kimchi",
+            "testnet".into(),
+        ))
+        .unwrap();
+        assert_eq!(verified.data, Some(true));
+
+        let js_fields = serde_wasm_bindgen::to_value(&["1"]).unwrap();
+        let verified: WasmResult<bool> = serde_wasm_bindgen::from_value(verify_fields(
+            &vector.public_key,
+            js_fields,
+            JsValue::from_str(&vector.message.signature_base58),
+            "kimchi",
+            "testnet".into(),
+        ))
+        .unwrap();
+        assert_eq!(verified.data, Some(false));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_encrypt_message() {
+        let alice = WasmWallet::generate("testnet".into()).unwrap();
+        let bob = WasmWallet::generate("testnet".into()).unwrap();
+        let bob_address = bob.address().unwrap();
+
+        let encrypted: WasmResult<String> =
+            serde_wasm_bindgen::from_value(alice.encrypt_message(&bob_address, "gm")).unwrap();
+        let ciphertext = encrypted.data.unwrap();
+        let decrypted: WasmResult<String> =
+            serde_wasm_bindgen::from_value(bob.decrypt_message(&ciphertext)).unwrap();
+        assert_eq!(decrypted.data.unwrap(), "gm");
+
+        let failed: WasmResult<String> =
+            serde_wasm_bindgen::from_value(alice.decrypt_message(&ciphertext)).unwrap();
+        assert_eq!(failed.code, Some(ErrorCode::DecryptionFailed));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_build_payment() {
+        let wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let address = wallet.address().unwrap();
+        let params = serde_json::json!({
+            "from": address,
+            "to": address,
+            "amount": "1.5",
+            "nonce": 3,
+            "memo": "rent",
+            "validForMinutes": 60,
+            "network": "testnet",
+        });
+        let params = serde_wasm_bindgen::to_value(&params).unwrap();
+        let built = build_payment(params);
+        let transaction = js_sys::Reflect::get(
+            &js_sys::Reflect::get(&built, &"data".into()).unwrap(),
+            &"transaction".into(),
+        )
+        .unwrap();
+        let payment: Transaction = serde_wasm_bindgen::from_value(transaction.clone()).unwrap();
+        assert_eq!(payment.amount, Some(Amount::from_nanomina(1_500_000_000)));
+        assert_eq!(payment.fee, transaction::DEFAULT_FEE);
+        assert!(payment.valid_until < u32::MAX);
+
+        let signed = sign_payment(&wallet, transaction);
+        let success = js_sys::Reflect::get(&signed, &"success".into()).unwrap();
+        assert_eq!(success.as_bool(), Some(true));
+
+        let params = serde_json::json!({
+            "from": address,
+            "to": "B62qinvalid",
+            "amount": "1",
+            "nonce": 0,
+            "network": { "network": "testnet", "endpoints": ["https://node.example.com/graphql"] },
+        });
+        let result: WasmResult<()> = serde_wasm_bindgen::from_value(build_payment(
+            serde_wasm_bindgen::to_value(&params).unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(result.code, Some(ErrorCode::InvalidTransaction));
+        assert_eq!(result.details[0].field, "to");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_decode_transaction() {
+        let wallet = Wallet::new(NetworkId::MAINNET).unwrap();
+        let payment = Transaction::payment(
+            &wallet.address(),
+            &wallet.address(),
+            Amount::from_nanomina(2_000_000_000),
+            transaction::DEFAULT_FEE,
+            0,
+        );
+        let signed = wallet.sign_transaction(&payment).unwrap();
+        let result: WasmResult<TransactionPreview> = serde_wasm_bindgen::from_value(
+            decode_transaction(&serde_json::to_string(&signed).unwrap()),
+        )
+        .unwrap();
+        let preview = result.data.unwrap();
+        assert!(preview.signed);
+        assert_eq!(preview.amount.as_deref(), Some("2"));
+        assert_eq!(preview.network.as_deref(), Some("mainnet"));
+        // Paying oneself
+        assert_eq!(preview.warnings.len(), 1);
+
+        let result: WasmResult<()> =
+            serde_wasm_bindgen::from_value(decode_transaction("{}")).unwrap();
+        assert_eq!(result.code, Some(ErrorCode::InvalidTransaction));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_mina_amounts() {
+        let parsed: WasmResult<String> = serde_wasm_bindgen::from_value(parse_mina("1.5")).unwrap();
+        assert_eq!(parsed.data.unwrap(), "1500000000");
+        let formatted: WasmResult<String> =
+            serde_wasm_bindgen::from_value(format_mina("100000001")).unwrap();
+        assert_eq!(formatted.data.unwrap(), "0.100000001");
+
+        for result in [parse_mina("0.1234567891"), format_mina("1.5")] {
+            let result: WasmResult<String> = serde_wasm_bindgen::from_value(result).unwrap();
+            assert_eq!(result.code, Some(ErrorCode::InvalidAmount));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_base58() {
+        let address = "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg";
+        let decoded: WasmResult<Base58Data> =
+            serde_wasm_bindgen::from_value(base58_decode(address)).unwrap();
+        let decoded = decoded.data.unwrap();
+        assert_eq!(decoded.version, 0xcb);
+        assert_eq!(decoded.kind.as_deref(), Some("address"));
+
+        let encoded: WasmResult<String> =
+            serde_wasm_bindgen::from_value(base58_encode(decoded.version, &decoded.payload_hex))
+                .unwrap();
+        assert_eq!(encoded.data.unwrap(), address);
+
+        let mut tampered = address.to_string();
+        tampered.replace_range(10..11, "z");
+        let result: WasmResult<Base58Data> =
+            serde_wasm_bindgen::from_value(base58_decode(&tampered)).unwrap();
+        assert_eq!(result.code, Some(ErrorCode::InvalidInput));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_network_config() {
+        let endpoints = vec!["https://node.example.com/graphql".to_string()];
+        let config: WasmResult<NetworkConfig> = serde_wasm_bindgen::from_value(network_config(
+            "testnet",
+            Some(endpoints.clone()),
+            None,
+            None,
+        ))
+        .unwrap();
+        let config = config.data.unwrap();
+        assert_eq!(config.endpoints, endpoints);
+
+        let js_config = serde_wasm_bindgen::to_value(&config).unwrap();
+        let wallet = WasmWallet::generate(js_config.clone()).unwrap();
+        assert_eq!(wallet.network().unwrap(), "testnet");
+        let stored: NetworkConfig =
+            serde_wasm_bindgen::from_value(wallet.network_config()).unwrap();
+        assert_eq!(stored, config);
+        let data: WasmResult<WalletData> =
+            serde_wasm_bindgen::from_value(generate_wallet(js_config)).unwrap();
+        assert_eq!(data.data.unwrap().network, "testnet");
+
+        let invalid: WasmResult<NetworkConfig> = serde_wasm_bindgen::from_value(network_config(
+            "testnet",
+            Some(vec!["node.example.com".to_string()]),
+            None,
+            None,
+        ))
+        .unwrap();
+        assert!(invalid.error.is_some());
+        let invalid: WasmResult<NetworkConfig> = serde_wasm_bindgen::from_value(network_config(
+            "testnet",
+            None,
+            Some("abc".to_string()),
+            None,
+        ))
+        .unwrap();
+        assert!(invalid.error.is_some());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_environment_check() {
+        let report: EnvironmentReport =
+            serde_wasm_bindgen::from_value(environment_check()).unwrap();
+        // The test runner serves the page from localhost, on the main thread
+        assert!(report.secure_context);
+        assert!(report.web_crypto);
+        assert!(report.memory_exposed);
+        assert!(!report.warnings.is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_watch_only() {
+        let wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let address = wallet.address().unwrap();
+        let watch_only = import_watch_only(&address, "testnet".into()).unwrap();
+        assert_eq!(watch_only.address(), address);
+        assert!(import_watch_only("B62qnotanaddress", "testnet".into()).is_err());
+
+        let signed: WasmResult<SignedMessage> =
+            serde_wasm_bindgen::from_value(wallet.sign_message("hello")).unwrap();
+        let data = serde_wasm_bindgen::to_value(&signed.data.unwrap()).unwrap();
+        let valid: WasmResult<bool> =
+            serde_wasm_bindgen::from_value(watch_only.verify_message(data.clone())).unwrap();
+        assert_eq!(valid.data, Some(true));
+        // A valid signature from another address is not one of this account
+        let other = WasmWallet::generate("testnet".into()).unwrap();
+        let other = import_watch_only(&other.address().unwrap(), "testnet".into()).unwrap();
+        let valid: WasmResult<bool> =
+            serde_wasm_bindgen::from_value(other.verify_message(data)).unwrap();
+        assert_eq!(valid.data, Some(false));
+
+        let params = serde_wasm_bindgen::to_value(&serde_json::json!({
+            "to": "B62qiy32p8kAKnny8ZFwoMhYpBppM1DWVCqAPBYNcXnsAHhnfAAuXgg",
+            "amount": "1.5",
+            "nonce": 0,
+        }))
+        .unwrap();
+        let built = watch_only.build_payment(params);
+        let success = js_sys::Reflect::get(&built, &"success".into()).unwrap();
+        assert_eq!(success.as_bool(), Some(true));
+
+        let refused: WasmResult<()> =
+            serde_wasm_bindgen::from_value(watch_only.sign_message("hello")).unwrap();
+        assert_eq!(refused.code, Some(ErrorCode::SigningFailed));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_resident_secrets() {
+        let before = resident_secrets().len();
+        let mut wallet = WasmWallet::generate("testnet".into()).unwrap();
+        let secret_key = wallet.wallet().unwrap().secret_key_hex();
+        let mut keystore =
+            WasmKeystore::create(&secret_key, "correct horse", "testnet".into()).unwrap();
+        // A locked keystore holds no secret
+        assert_eq!(resident_secrets().len(), before + 1);
+
+        wallet.dispose();
+        keystore.dispose();
+        assert_eq!(resident_secrets().len(), before);
+    }
+
     #[wasm_bindgen_test]
     fn test_version() {
         let v = version();
@@ -0,0 +1,360 @@
+//! Several accounts open at once
+//!
+//! `WasmAccountManager` wraps the `WalletManager` of the core crate over the
+//! keystore persisted in IndexedDB: it adds and removes accounts, sets the
+//! default one, and locks or unlocks each account on its own. Signing methods
+//! take the label or address of the account, or nothing for the default one.
+//!
+//! The manager keeps its own copy of the keystore and writes it back after
+//! every change: use it instead of `add_account` and `remove_account`, not
+//! alongside them. A change only shows in the manager once it is saved.
+
+use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::keystore::{KEYSTORE_STORAGE_KEY, Keystore};
+use mina_web_wallet_core::manager::{AccountStatus, ManagerError, WalletManager};
+use mina_web_wallet_core::message::SignedMessage;
+#[cfg(feature = "network")]
+use mina_web_wallet_core::network::Balance;
+use mina_web_wallet_core::transaction::{SignedTransaction, Transaction};
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::accounts::{commit, keystore_error_code, load_keystore};
+#[cfg(feature = "network")]
+use crate::node_client;
+use crate::storage::IndexedDbStorage;
+use crate::{ErrorCode, WasmResult, resolve_network, secrets};
+
+/// The balance of an account, or why it could not be fetched
+#[cfg(feature = "network")]
+#[derive(Serialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+struct AccountBalance {
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance: Option<Balance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The accounts of the keystore, each locked or unlocked
+#[wasm_bindgen]
+pub struct WasmAccountManager {
+    manager: Rc<RefCell<WalletManager>>,
+    /// Whether a change is being saved; the next one waits for it
+    saving: Rc<Cell<bool>>,
+}
+
+#[wasm_bindgen]
+impl WasmAccountManager {
+    /// Open the keystore saved in IndexedDB, every account locked
+    pub async fn load() -> Result<WasmAccountManager, JsValue> {
+        let (keystore, _) = load_keystore().await.map_err(|e| JsValue::from_str(&e))?;
+        let manager = Rc::new(RefCell::new(WalletManager::new(keystore)));
+        secrets::register("accounts", &manager, |manager| {
+            manager.borrow().any_unlocked()
+        });
+        Ok(Self {
+            manager,
+            saving: Rc::new(Cell::new(false)),
+        })
+    }
+
+    /// List the accounts
+    ///
+    /// # Returns
+    /// JSON array of `{ address, label, createdAt, isDefault, unlocked }`
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<AccountStatus[]>")]
+    pub fn accounts(&self) -> JsValue {
+        WasmResult::ok(self.manager.borrow().accounts())
+    }
+
+    /// Encrypt a secret key into the keystore, and keep the account unlocked
+    ///
+    /// # Arguments
+    /// * `secret_key` - The secret key, hex or Base58
+    /// * `passphrase` - Passphrase protecting the key
+    /// * `label` - Optional name of the account
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    ///
+    /// # Returns
+    /// Promise of the new account, once saved
+    #[wasm_bindgen(
+        js_name = addAccount,
+        unchecked_return_type = "Promise<WasmResult<AccountStatus>>"
+    )]
+    pub fn add_account(
+        &self,
+        secret_key: &str,
+        passphrase: &str,
+        label: Option<String>,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> js_sys::Promise {
+        let mut staged = self.manager.borrow().staged();
+        let result = (|| {
+            if passphrase.is_empty() {
+                return Err((
+                    ErrorCode::InvalidInput,
+                    "The passphrase is empty".to_string(),
+                ));
+            }
+            let network = resolve_network(&network).map_err(|e| (ErrorCode::InvalidNetwork, e))?;
+            let wallet = Wallet::from_secret_key_strict(secret_key, network).map_err(|e| {
+                (
+                    ErrorCode::InvalidSecretHex,
+                    format!("Invalid secret key: {}", e),
+                )
+            })?;
+            let created_at = (js_sys::Date::now() / 1000.0) as u64;
+            staged
+                .add(wallet, passphrase, label.as_deref(), created_at)
+                .map_err(|e| (manager_error_code(&e), e.to_string()))
+        })();
+        self.save_then(result.map(|status| (staged, status)), |_, status| status)
+    }
+
+    /// Remove an account from the keystore, locking it first
+    ///
+    /// # Returns
+    /// Promise of the address of the removed account, once saved
+    #[wasm_bindgen(
+        js_name = removeAccount,
+        unchecked_return_type = "Promise<WasmResult<string>>"
+    )]
+    pub fn remove_account(&self, name: &str) -> js_sys::Promise {
+        let mut staged = self.manager.borrow().staged();
+        let result = staged
+            .remove(name)
+            .map(|entry| (staged, entry.address))
+            .map_err(|e| (manager_error_code(&e), e.to_string()));
+        self.save_then(result, |_, address| address)
+    }
+
+    /// Make an account the default one
+    ///
+    /// # Returns
+    /// Promise of the accounts, once saved
+    #[wasm_bindgen(
+        js_name = setDefault,
+        unchecked_return_type = "Promise<WasmResult<AccountStatus[]>>"
+    )]
+    pub fn set_default(&self, name: &str) -> js_sys::Promise {
+        let mut staged = self.manager.borrow().staged();
+        let result = staged
+            .set_default(name)
+            .map(|()| (staged, ()))
+            .map_err(|e| (manager_error_code(&e), e.to_string()));
+        self.save_then(result, |manager, ()| manager.accounts())
+    }
+
+    /// Decrypt the key of an account and keep it until `lock`
+    ///
+    /// # Arguments
+    /// * `name` - Label or address of the account, or undefined for the default one
+    /// * `passphrase` - Passphrase of the key
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    ///
+    /// # Returns
+    /// JSON object with the account, failing with `DECRYPTION_FAILED` on a
+    /// wrong passphrase
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<AccountStatus>")]
+    pub fn unlock(
+        &self,
+        name: Option<String>,
+        passphrase: &str,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    ) -> JsValue {
+        let network = match resolve_network(&network) {
+            Ok(n) => n,
+            Err(e) => return WasmResult::<AccountStatus>::err(ErrorCode::InvalidNetwork, e),
+        };
+        match self
+            .manager
+            .borrow_mut()
+            .unlock(name.as_deref(), passphrase, network)
+        {
+            Ok(status) => WasmResult::ok(status),
+            Err(e) => WasmResult::<AccountStatus>::err(manager_error_code(&e), e.to_string()),
+        }
+    }
+
+    /// Wipe the key of an account
+    ///
+    /// # Returns
+    /// JSON object with whether the account was unlocked
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<boolean>")]
+    pub fn lock(&self, name: Option<String>) -> JsValue {
+        match self.manager.borrow_mut().lock(name.as_deref()) {
+            Ok(was_unlocked) => WasmResult::ok(was_unlocked),
+            Err(e) => WasmResult::<bool>::err(manager_error_code(&e), e.to_string()),
+        }
+    }
+
+    /// Wipe the keys of every account
+    #[wasm_bindgen(js_name = lockAll)]
+    pub fn lock_all(&self) {
+        self.manager.borrow_mut().lock_all();
+    }
+
+    /// Sign a payment or delegation with an unlocked account
+    ///
+    /// # Arguments
+    /// * `name` - Label or address of the account, or undefined for the default one
+    /// * `transaction` - The transaction, in the `mina-signer` JSON shape
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signPayment, unchecked_return_type = "WasmResult<SignedTransaction>")]
+    pub fn sign_payment(
+        &self,
+        name: Option<String>,
+        #[wasm_bindgen(unchecked_param_type = "Transaction")] transaction: JsValue,
+    ) -> JsValue {
+        let transaction: Transaction = match serde_wasm_bindgen::from_value(transaction) {
+            Ok(t) => t,
+            Err(e) => {
+                return WasmResult::<SignedTransaction>::err(
+                    ErrorCode::InvalidTransaction,
+                    format!("Invalid transaction: {}", e),
+                );
+            }
+        };
+        let manager = self.manager.borrow();
+        let wallet = match manager.wallet(name.as_deref()) {
+            Ok(w) => w,
+            Err(e) => {
+                return WasmResult::<SignedTransaction>::err(manager_error_code(&e), e.to_string());
+            }
+        };
+        match wallet.sign_transaction(&transaction) {
+            Ok(signed) => WasmResult::ok(signed),
+            Err(e) => {
+                WasmResult::<SignedTransaction>::transaction_err(&transaction, "Failed to sign", e)
+            }
+        }
+    }
+
+    /// Sign a message with an unlocked account
+    ///
+    /// # Returns
+    /// JSON object `{ publicKey, data, signature: { field, scalar } }`
+    #[wasm_bindgen(js_name = signMessage, unchecked_return_type = "WasmResult<SignedMessage>")]
+    pub fn sign_message(&self, name: Option<String>, message: &str) -> JsValue {
+        match self.manager.borrow().wallet(name.as_deref()) {
+            Ok(wallet) => WasmResult::ok(wallet.sign_message(message)),
+            Err(e) => WasmResult::<SignedMessage>::err(manager_error_code(&e), e.to_string()),
+        }
+    }
+
+    /// Fetch the balances of every account, locked or not
+    ///
+    /// # Arguments
+    /// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+    /// * `endpoint` - GraphQL endpoint URL of the node, or several separated
+    ///   by commas; those of the `NetworkConfig` if omitted
+    ///
+    /// # Returns
+    /// Promise of a JSON array of `{ address, balance?, error? }`, in the
+    /// order of `accounts()`; an account that fails has an `error`
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(
+        js_name = refreshBalances,
+        unchecked_return_type = "Promise<WasmResult<AccountBalance[]>>"
+    )]
+    pub fn refresh_balances(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+        endpoint: Option<String>,
+    ) -> js_sys::Promise {
+        let client = match node_client(&network, endpoint.as_deref()) {
+            Ok((_, client)) => client,
+            Err((code, e)) => {
+                return js_sys::Promise::resolve(&WasmResult::<Vec<AccountBalance>>::err(code, e));
+            }
+        };
+        let addresses: Vec<String> = self
+            .manager
+            .borrow()
+            .accounts()
+            .into_iter()
+            .map(|account| account.address)
+            .collect();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut balances = Vec::with_capacity(addresses.len());
+            for address in addresses {
+                let (balance, error) = match client.get_balance(&address).await {
+                    Ok(balance) => (Some(balance), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                balances.push(AccountBalance {
+                    address,
+                    balance,
+                    error,
+                });
+            }
+            Ok(WasmResult::ok(balances))
+        })
+    }
+
+    /// Wipe the keys of every account; the manager can still unlock them
+    pub fn dispose(&self) {
+        self.lock_all();
+    }
+}
+
+impl WasmAccountManager {
+    /// Save the keystore of a staged change, then merge the change into the
+    /// manager and resolve to `then` of the merged manager; a failed save
+    /// leaves the manager as it was
+    ///
+    /// One change is saved at a time: a change staged while another is being
+    /// saved would undo it when merged, so it is refused.
+    fn save_then<S: 'static, T: Serialize + 'static>(
+        &self,
+        result: Result<(WalletManager, S), (ErrorCode, String)>,
+        then: impl FnOnce(&WalletManager, S) -> T + 'static,
+    ) -> js_sys::Promise {
+        let (staged, data) = match result {
+            Ok(staged) => staged,
+            Err((code, e)) => return js_sys::Promise::resolve(&WasmResult::<T>::err(code, e)),
+        };
+        if self.saving.replace(true) {
+            return js_sys::Promise::resolve(&WasmResult::<T>::err(
+                ErrorCode::StorageError,
+                "Another change to the accounts is being saved".to_string(),
+            ));
+        }
+        let manager = Rc::clone(&self.manager);
+        let saving = Rc::clone(&self.saving);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let keystore: &Keystore = staged.keystore();
+            let saved = match IndexedDbStorage::open(&[KEYSTORE_STORAGE_KEY]).await {
+                Ok(mut storage) => {
+                    commit(&mut storage, |storage| {
+                        keystore.save(storage).map_err(|e| e.to_string())
+                    })
+                    .await
+                }
+                Err(e) => Err(format!("Failed to read the keystore: {:?}", e)),
+            };
+            saving.set(false);
+            if let Err(e) = saved {
+                return Ok(WasmResult::<T>::err(ErrorCode::StorageError, e));
+            }
+            let mut manager = manager.borrow_mut();
+            manager.merge(staged);
+            Ok(WasmResult::ok(then(&manager, data)))
+        })
+    }
+}
+
+fn manager_error_code(error: &ManagerError) -> ErrorCode {
+    match error {
+        ManagerError::NoDefault => ErrorCode::NotFound,
+        ManagerError::Locked(_) => ErrorCode::Locked,
+        ManagerError::Keystore(e) => keystore_error_code(e),
+    }
+}
@@ -0,0 +1,224 @@
+//! Network configuration shared by the WASM functions
+//!
+//! Every function taking a `network` accepts its name, "mainnet" or
+//! "testnet", or a `NetworkConfig` built once with `network_config` and
+//! passed as is from then on. A configuration also carries the GraphQL
+//! endpoints of the network and, for a custom chain, its chain ID: a custom
+//! chain signs with the domain of `network`, and its nodes are checked
+//! against `chainId` instead of the known chain IDs.
+//!
+//! Functions talking to a node take a `network` and an optional endpoint:
+//! the endpoint overrides those of the configuration, and one of the two is
+//! needed. Indexer queries use the `archiveEndpoints` of the configuration.
+//!
+//! Configurations are plain objects, so that they can be posted to Web
+//! Workers and saved with the settings of the page.
+
+use mina_signer::NetworkId;
+#[cfg(feature = "network")]
+use mina_web_wallet_core::network::{GraphQlClient, NetworkError};
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "network")]
+use crate::split_endpoints;
+use crate::{ErrorCode, WasmResult, network_name, parse_network};
+
+#[cfg(feature = "network")]
+pub(crate) const NO_ENDPOINT: &str =
+    "No endpoint: pass one, or set the endpoints of the NetworkConfig";
+
+#[wasm_bindgen(typescript_custom_section)]
+const NETWORK_TYPE: &str = r#"
+/** A network name, or a configuration built by `network_config` */
+export type Network = "mainnet" | "testnet" | NetworkConfig;
+"#;
+
+/// A network, the nodes to query and, for a custom chain, its chain ID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkConfig {
+    /// "mainnet" or "testnet": the signature domain and slots of the chain
+    pub network: String,
+    /// Chain ID the nodes must follow, for a custom chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<String>,
+    /// GraphQL endpoints of the nodes, tried in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<String>,
+    /// GraphQL endpoints of the archive indexers, for history and staking ledgers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archive_endpoints: Vec<String>,
+}
+
+/// A `network` argument as it is deserialized, before validation
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum NetworkArg {
+    Name(String),
+    Config(NetworkConfig),
+}
+
+/// A validated `network` argument
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "NetworkArg")]
+pub(crate) struct Network {
+    pub id: NetworkId,
+    pub chain_id: Option<String>,
+    pub endpoints: Vec<String>,
+    pub archive_endpoints: Vec<String>,
+}
+
+impl TryFrom<NetworkArg> for Network {
+    type Error = String;
+
+    fn try_from(network: NetworkArg) -> Result<Self, String> {
+        let config = match network {
+            NetworkArg::Name(name) => NetworkConfig {
+                network: name,
+                chain_id: None,
+                endpoints: Vec::new(),
+                archive_endpoints: Vec::new(),
+            },
+            NetworkArg::Config(config) => config,
+        };
+        Ok(Self {
+            id: parse_network(&config.network)?,
+            chain_id: config.chain_id,
+            endpoints: config.endpoints,
+            archive_endpoints: config.archive_endpoints,
+        })
+    }
+}
+
+impl Network {
+    /// Resolve a network name or a `NetworkConfig`
+    pub(crate) fn from_js(network: &JsValue) -> Result<Self, String> {
+        let network: NetworkArg = serde_wasm_bindgen::from_value(network.clone())
+            .map_err(|_| "Invalid network: expected a name or a NetworkConfig".to_string())?;
+        Self::try_from(network)
+    }
+
+    pub(crate) fn config(&self) -> NetworkConfig {
+        NetworkConfig {
+            network: network_name(&self.id).to_string(),
+            chain_id: self.chain_id.clone(),
+            endpoints: self.endpoints.clone(),
+            archive_endpoints: self.archive_endpoints.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "network")]
+impl Network {
+    /// The endpoints given to a call, or else those of the configuration
+    pub(crate) fn endpoints_or(&self, endpoint: &str) -> Vec<String> {
+        match split_endpoints(endpoint) {
+            endpoints if endpoints.is_empty() => self.endpoints.clone(),
+            endpoints => endpoints,
+        }
+    }
+
+    /// A client for the endpoints given to a call, or else those of the
+    /// configuration
+    pub(crate) fn client(&self, endpoint: Option<&str>) -> Result<GraphQlClient, String> {
+        match self.endpoints_or(endpoint.unwrap_or_default()) {
+            endpoints if endpoints.is_empty() => Err(NO_ENDPOINT.to_string()),
+            endpoints => Ok(GraphQlClient::with_endpoints(endpoints)),
+        }
+    }
+
+    /// The indexer endpoints given to a call, or else those of the configuration
+    pub(crate) fn archive_endpoints_or(&self, endpoint: Option<&str>) -> Vec<String> {
+        match split_endpoints(endpoint.unwrap_or_default()) {
+            endpoints if endpoints.is_empty() => self.archive_endpoints.clone(),
+            endpoints => endpoints,
+        }
+    }
+
+    /// Check that the node of `client` follows this chain, returning its
+    /// chain ID: the `chainId` of the configuration, or else the known chain
+    /// of the network
+    pub(crate) async fn check_chain_id(
+        &self,
+        client: &GraphQlClient,
+    ) -> Result<String, NetworkError> {
+        let Some(expected) = &self.chain_id else {
+            return client.verify_chain_id(&self.id).await;
+        };
+        let chain_id = client.chain_id().await?;
+        if chain_id == *expected {
+            Ok(chain_id)
+        } else {
+            Err(NetworkError::ChainMismatch {
+                network: expected.clone(),
+                chain_id,
+            })
+        }
+    }
+}
+
+/// The network id of a `network` argument
+pub(crate) fn resolve_network(network: &JsValue) -> Result<NetworkId, String> {
+    Network::from_js(network).map(|network| network.id)
+}
+
+/// Build a network configuration, to pass as the `network` of other calls
+///
+/// # Arguments
+/// * `network` - "mainnet" or "testnet"; custom chains use the domain of one
+/// * `endpoints` - GraphQL endpoint URLs of the nodes
+/// * `chain_id` - Chain ID of a custom chain, 64 hex characters
+/// * `archive_endpoints` - GraphQL endpoint URLs of the archive indexers
+///
+/// # Returns
+/// JSON object `{ network, chainId?, endpoints?, archiveEndpoints? }`
+#[wasm_bindgen(unchecked_return_type = "WasmResult<NetworkConfig>")]
+pub fn network_config(
+    network: &str,
+    endpoints: Option<Vec<String>>,
+    chain_id: Option<String>,
+    archive_endpoints: Option<Vec<String>>,
+) -> JsValue {
+    let id = match parse_network(network) {
+        Ok(id) => id,
+        Err(e) => return WasmResult::<NetworkConfig>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let trim = |endpoints: Option<Vec<String>>| -> Vec<String> {
+        endpoints
+            .unwrap_or_default()
+            .iter()
+            .map(|e| e.trim().to_string())
+            .collect()
+    };
+    let endpoints = trim(endpoints);
+    let archive_endpoints = trim(archive_endpoints);
+    if let Some(endpoint) = endpoints
+        .iter()
+        .chain(&archive_endpoints)
+        .find(|e| !e.starts_with("https://") && !e.starts_with("http://"))
+    {
+        return WasmResult::<NetworkConfig>::err(
+            ErrorCode::InvalidInput,
+            format!("Invalid endpoint, expected an http(s) URL: {}", endpoint),
+        );
+    }
+    if let Some(chain_id) = &chain_id
+        && (chain_id.len() != 64 || hex::decode(chain_id).is_err())
+    {
+        return WasmResult::<NetworkConfig>::err(
+            ErrorCode::InvalidInput,
+            format!("Invalid chain ID, expected 64 hex characters: {}", chain_id),
+        );
+    }
+    WasmResult::ok(
+        Network {
+            id,
+            chain_id,
+            endpoints,
+            archive_endpoints,
+        }
+        .config(),
+    )
+}
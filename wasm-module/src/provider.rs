@@ -0,0 +1,433 @@
+//! Auro-compatible `window.mina` provider
+//!
+//! dApps written against the Auro wallet call `window.mina.requestAccounts()`,
+//! `signMessage({ message })`, `sendPayment({ to, amount })`, ... and get
+//! either the result or a `{ code, message }` error, with code 1001 when the
+//! dApp is not connected and 1002 when the user rejected the request.
+//! `MinaProvider` answers those calls with a `WasmWallet`, and asks the page
+//! to approve each request through an `approve(method, params)` callback.
+//! `frontend/provider.js` installs it as `window.mina`.
+//!
+//! zkApp transactions (`sendTransaction`) are refused: the core crate does not
+//! build or sign zkApp commands.
+
+use js_sys::{Function, Promise};
+use mina_web_wallet_core::network::GraphQlClient;
+use mina_web_wallet_core::signing::SignatureJson;
+use mina_web_wallet_core::transaction::{DEFAULT_FEE, Transaction};
+use mina_web_wallet_core::{Amount, Wallet, oracle};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::Cell;
+use std::future::Future;
+use std::rc::{Rc, Weak};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
+
+use crate::{WasmWallet, network_name, split_endpoints};
+
+/// The dApp did not call `requestAccounts`, or the wallet is gone
+const NOT_CONNECTED: u32 = 1001;
+/// The user, or the `approve` callback, rejected the request
+const USER_REJECTED: u32 = 1002;
+const INVALID_PARAMS: u32 = 20003;
+const UNSUPPORTED_METHOD: u32 = 20004;
+/// The node could not be reached or refused the transaction
+const NETWORK_ERROR: u32 = 20005;
+
+/// Error of a provider request, as returned by Auro
+#[derive(Debug, Serialize, Tsify)]
+struct ProviderError {
+    code: u32,
+    message: String,
+}
+
+impl ProviderError {
+    fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SignMessageArgs {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SignFieldsArgs {
+    /// Field elements, as numbers or decimal strings
+    message: Vec<Value>,
+}
+
+#[derive(Serialize)]
+struct SignedFields {
+    data: Vec<Value>,
+    /// Base58 signature, for o1js `Signature.fromBase58`
+    signature: String,
+}
+
+/// MINA amount given by a dApp, as a number or a decimal string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Mina {
+    Number(f64),
+    Text(String),
+}
+
+impl Mina {
+    fn parse(&self, field: &str) -> Result<Amount, ProviderError> {
+        let text = match self {
+            // `f64` formats without an exponent, e.g. 1e-7 as "0.0000001"
+            Self::Number(n) => n.to_string(),
+            Self::Text(s) => s.clone(),
+        };
+        text.parse()
+            .map_err(|e| ProviderError::new(INVALID_PARAMS, format!("Invalid {}: {}", field, e)))
+    }
+}
+
+#[derive(Deserialize)]
+struct SendPaymentArgs {
+    to: String,
+    amount: Mina,
+    fee: Option<Mina>,
+    memo: Option<String>,
+    nonce: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct SendDelegationArgs {
+    to: String,
+    fee: Option<Mina>,
+    memo: Option<String>,
+    nonce: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SendResult {
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct NetworkInfo {
+    #[serde(rename = "networkID")]
+    network_id: String,
+}
+
+/// The `window.mina` provider of Auro, backed by a `WasmWallet`
+///
+/// Every method returns a Promise of the result, or of a `{ code, message }`
+/// error.
+#[wasm_bindgen]
+pub struct MinaProvider {
+    inner: Rc<Provider>,
+}
+
+struct Provider {
+    /// Not kept alive by the provider: `dispose()` on the wallet still wipes it
+    wallet: Weak<Wallet>,
+    address: String,
+    network: &'static str,
+    endpoint: String,
+    approve: Function,
+    connected: Cell<bool>,
+}
+
+#[wasm_bindgen]
+impl MinaProvider {
+    /// Create a provider for a wallet
+    ///
+    /// # Arguments
+    /// * `wallet` - The wallet answering the requests
+    /// * `endpoint` - GraphQL endpoint URL of the node, or several separated by commas
+    /// * `approve` - Called with the method and its parameters, e.g. the
+    ///   transaction to send; returns, or resolves to, whether the user approved
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        wallet: &WasmWallet,
+        endpoint: String,
+        #[wasm_bindgen(
+            unchecked_param_type = "(method: string, params: unknown) => boolean | Promise<boolean>"
+        )]
+        approve: Function,
+    ) -> Result<MinaProvider, JsValue> {
+        let wallet = wallet.shared_wallet().map_err(JsValue::from_str)?;
+        Ok(Self {
+            inner: Rc::new(Provider {
+                address: wallet.address(),
+                network: network_name(wallet.network()),
+                wallet: Rc::downgrade(&wallet),
+                endpoint,
+                approve,
+                connected: Cell::new(false),
+            }),
+        })
+    }
+
+    /// Ask the user to connect the dApp; resolves to the address of the wallet
+    #[wasm_bindgen(js_name = requestAccounts, unchecked_return_type = "Promise<string[] | ProviderError>")]
+    pub fn request_accounts(&self) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            if !provider.connected.get() {
+                provider.approve("requestAccounts", &JsValue::NULL).await?;
+                provider.connected.set(true);
+            }
+            Ok(vec![provider.address.clone()])
+        })
+    }
+
+    /// The address of the wallet once connected, no address otherwise
+    #[wasm_bindgen(js_name = getAccounts, unchecked_return_type = "Promise<string[]>")]
+    pub fn get_accounts(&self) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            if provider.connected.get() {
+                Ok(vec![provider.address.clone()])
+            } else {
+                Ok(Vec::new())
+            }
+        })
+    }
+
+    /// The network of the wallet, as `{ networkID: "mina:mainnet" }`
+    #[wasm_bindgen(js_name = requestNetwork, unchecked_return_type = "Promise<{ networkID: string }>")]
+    pub fn request_network(&self) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            let network_id = match provider.network {
+                "mainnet" => "mina:mainnet",
+                _ => "mina:devnet",
+            };
+            Ok(NetworkInfo {
+                network_id: network_id.to_string(),
+            })
+        })
+    }
+
+    /// Sign a string message, after approval
+    ///
+    /// # Arguments
+    /// * `args` - `{ message }`
+    #[wasm_bindgen(js_name = signMessage, unchecked_return_type = "Promise<SignedMessage | ProviderError>")]
+    pub fn sign_message(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "{ message: string }")] args: JsValue,
+    ) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            let args: SignMessageArgs = parse_args(args)?;
+            provider.check_connected()?;
+            provider
+                .approve("signMessage", &JsValue::from_str(&args.message))
+                .await?;
+            Ok(provider.wallet()?.sign_message(&args.message))
+        })
+    }
+
+    /// Sign field elements as o1js `Signature.create`, after approval
+    ///
+    /// # Arguments
+    /// * `args` - `{ message }`, the fields as numbers or decimal strings
+    #[wasm_bindgen(
+        js_name = signFields,
+        unchecked_return_type = "Promise<{ data: (string | number)[]; signature: string } | ProviderError>"
+    )]
+    pub fn sign_fields(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "{ message: (string | number)[] }")] args: JsValue,
+    ) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            let args: SignFieldsArgs = parse_args(args)?;
+            let fields = args
+                .message
+                .iter()
+                .map(oracle::encode_value)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ProviderError::new(INVALID_PARAMS, e.to_string()))?;
+            provider.check_connected()?;
+            provider
+                .approve("signFields", &to_js(&args.message))
+                .await?;
+            let signature = provider.wallet()?.sign_fields(&fields);
+            let signature = SignatureJson::from(&signature)
+                .to_base58()
+                .map_err(|e| ProviderError::new(INVALID_PARAMS, e.to_string()))?;
+            Ok(SignedFields {
+                data: args.message,
+                signature,
+            })
+        })
+    }
+
+    /// Sign and broadcast a payment, after approval
+    ///
+    /// The nonce is taken from the node, and the fee defaults to 0.1 MINA.
+    ///
+    /// # Arguments
+    /// * `args` - `{ to, amount, fee?, memo?, nonce? }`, amounts in MINA
+    #[wasm_bindgen(js_name = sendPayment, unchecked_return_type = "Promise<{ hash: string } | ProviderError>")]
+    pub fn send_payment(
+        &self,
+        #[wasm_bindgen(
+            unchecked_param_type = "{ to: string; amount: number | string; fee?: number | string; memo?: string; nonce?: number }"
+        )]
+        args: JsValue,
+    ) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            let args: SendPaymentArgs = parse_args(args)?;
+            provider.check_connected()?;
+            let amount = args.amount.parse("amount")?;
+            let fee = parse_fee(args.fee.as_ref())?;
+            let nonce = provider.nonce(args.nonce).await?;
+            let transaction = Transaction::payment(&provider.address, &args.to, amount, fee, nonce)
+                .with_memo(args.memo.as_deref().unwrap_or_default());
+            provider.send("sendPayment", transaction).await
+        })
+    }
+
+    /// Sign and broadcast a stake delegation, after approval
+    ///
+    /// # Arguments
+    /// * `args` - `{ to, fee?, memo?, nonce? }`, the fee in MINA
+    #[wasm_bindgen(
+        js_name = sendStakeDelegation,
+        unchecked_return_type = "Promise<{ hash: string } | ProviderError>"
+    )]
+    pub fn send_stake_delegation(
+        &self,
+        #[wasm_bindgen(
+            unchecked_param_type = "{ to: string; fee?: number | string; memo?: string; nonce?: number }"
+        )]
+        args: JsValue,
+    ) -> Promise {
+        let provider = Rc::clone(&self.inner);
+        spawn(async move {
+            let args: SendDelegationArgs = parse_args(args)?;
+            provider.check_connected()?;
+            let fee = parse_fee(args.fee.as_ref())?;
+            let nonce = provider.nonce(args.nonce).await?;
+            let transaction = Transaction::delegation(&provider.address, &args.to, fee, nonce)
+                .with_memo(args.memo.as_deref().unwrap_or_default());
+            provider.send("sendStakeDelegation", transaction).await
+        })
+    }
+
+    /// zkApp transactions are not supported; always fails
+    #[wasm_bindgen(js_name = sendTransaction, unchecked_return_type = "Promise<ProviderError>")]
+    pub fn send_transaction(&self, _args: JsValue) -> Promise {
+        spawn(async move {
+            Err::<(), _>(ProviderError::new(
+                UNSUPPORTED_METHOD,
+                "zkApp transactions are not supported by this wallet",
+            ))
+        })
+    }
+
+    /// Disconnect the dApp; `requestAccounts` must be approved again
+    pub fn disconnect(&self) {
+        self.inner.connected.set(false);
+    }
+}
+
+impl Provider {
+    fn check_connected(&self) -> Result<(), ProviderError> {
+        if self.connected.get() {
+            Ok(())
+        } else {
+            Err(ProviderError::new(
+                NOT_CONNECTED,
+                "Call requestAccounts first",
+            ))
+        }
+    }
+
+    /// The wallet, taken only once the request is approved
+    fn wallet(&self) -> Result<Rc<Wallet>, ProviderError> {
+        self.wallet
+            .upgrade()
+            .ok_or_else(|| ProviderError::new(NOT_CONNECTED, "The wallet was disposed"))
+    }
+
+    async fn approve(&self, method: &str, params: &JsValue) -> Result<(), ProviderError> {
+        let rejected = || ProviderError::new(USER_REJECTED, "User rejected the request");
+        let answer = self
+            .approve
+            .call2(&JsValue::NULL, &JsValue::from_str(method), params)
+            .map_err(|_| rejected())?;
+        let answer = JsFuture::from(Promise::resolve(&answer))
+            .await
+            .map_err(|_| rejected())?;
+        if answer.is_truthy() {
+            Ok(())
+        } else {
+            Err(rejected())
+        }
+    }
+
+    async fn nonce(&self, nonce: Option<u32>) -> Result<u32, ProviderError> {
+        if let Some(nonce) = nonce {
+            return Ok(nonce);
+        }
+        GraphQlClient::with_endpoints(split_endpoints(&self.endpoint))
+            .get_inferred_nonce(&self.address)
+            .await
+            .map_err(|e| {
+                ProviderError::new(NETWORK_ERROR, format!("Failed to get the nonce: {}", e))
+            })
+    }
+
+    /// Approve, sign and broadcast a payment or delegation
+    async fn send(
+        &self,
+        method: &str,
+        transaction: Transaction,
+    ) -> Result<SendResult, ProviderError> {
+        transaction
+            .validate()
+            .map_err(|e| ProviderError::new(INVALID_PARAMS, e.to_string()))?;
+        self.approve(method, &to_js(&transaction)).await?;
+        let signed = self
+            .wallet()?
+            .sign_transaction(&transaction)
+            .map_err(|e| ProviderError::new(INVALID_PARAMS, e.to_string()))?;
+        GraphQlClient::with_endpoints(split_endpoints(&self.endpoint))
+            .broadcast(&signed)
+            .await
+            .map(|hash| SendResult { hash })
+            .map_err(|e| ProviderError::new(NETWORK_ERROR, format!("Failed to broadcast: {}", e)))
+    }
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(args: JsValue) -> Result<T, ProviderError> {
+    serde_wasm_bindgen::from_value(args)
+        .map_err(|e| ProviderError::new(INVALID_PARAMS, format!("Invalid parameters: {}", e)))
+}
+
+fn parse_fee(fee: Option<&Mina>) -> Result<Amount, ProviderError> {
+    fee.map_or(Ok(DEFAULT_FEE), |fee| fee.parse("fee"))
+}
+
+fn to_js<T: Serialize>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_or(JsValue::NULL)
+}
+
+/// Run a request; the Promise resolves to its result or its `ProviderError`
+fn spawn<T: Serialize>(
+    request: impl Future<Output = Result<T, ProviderError>> + 'static,
+) -> Promise {
+    future_to_promise(async move {
+        Ok(match request.await {
+            Ok(result) => to_js(&result),
+            Err(e) => to_js(&e),
+        })
+    })
+}
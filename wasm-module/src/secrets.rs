@@ -0,0 +1,69 @@
+//! Registry of the objects holding secret material
+//!
+//! Wallets, keystores and WalletConnect clients register here when created,
+//! through weak references that do not keep them alive. `resident_secrets`
+//! lists those still holding a secret, so that a page can check, e.g. after
+//! logging out or in its tests, that every one was disposed.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+
+/// Whether a registered object still holds a secret; `None` once it is gone
+type Probe = Box<dyn Fn() -> Option<bool>>;
+
+thread_local! {
+    static REGISTRY: RefCell<Vec<(&'static str, Probe)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Register an object, resident while `holds` returns true on it
+pub(crate) fn register<T: 'static>(kind: &'static str, object: &Rc<T>, holds: fn(&T) -> bool) {
+    let object: Weak<T> = Rc::downgrade(object);
+    let probe: Probe = Box::new(move || object.upgrade().map(|object| holds(&object)));
+    REGISTRY.with(|registry| registry.borrow_mut().push((kind, probe)));
+}
+
+/// Kinds of the objects still holding secrets, forgetting the dropped ones
+fn resident() -> Vec<&'static str> {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let mut kinds = Vec::new();
+        registry.retain(|(kind, probe)| match probe() {
+            Some(holds) => {
+                if holds {
+                    kinds.push(*kind);
+                }
+                true
+            }
+            None => false,
+        });
+        kinds
+    })
+}
+
+/// List the objects still holding secret material in WebAssembly memory
+///
+/// These are the wallets not disposed, including those kept alive by a
+/// pending batch, the unlocked keystores, the account managers with an
+/// unlocked account and the WalletConnect clients not disposed.
+///
+/// # Returns
+/// One entry per object: "wallet", "keystore", "accounts" or "walletconnect"
+#[wasm_bindgen]
+pub fn resident_secrets() -> Vec<String> {
+    resident().into_iter().map(str::to_string).collect()
+}
+
+/// Throw if any object still holds secret material, e.g. after logging out
+#[wasm_bindgen]
+pub fn assert_no_resident_secrets() -> Result<(), JsValue> {
+    let kinds = resident();
+    if kinds.is_empty() {
+        return Ok(());
+    }
+    Err(JsValue::from_str(&format!(
+        "{} objects still hold secrets: {}",
+        kinds.len(),
+        kinds.join(", ")
+    )))
+}
@@ -0,0 +1,163 @@
+//! IndexedDB persistence for wallet state
+//!
+//! IndexedDB is asynchronous while the core `Storage` trait is not, so state
+//! is loaded into a `MemoryStorage`, used by core, then written back. Values
+//! are strings in a single object store, keyed like the core storage keys.
+//! `IndexedDbStorage` wraps both steps behind the `Storage` trait.
+
+use js_sys::Promise;
+use mina_web_wallet_core::storage::{
+    MemoryStorage, Result as StorageResult, Storage, StorageError,
+};
+use std::collections::BTreeSet;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, IdbDatabase, IdbRequest, IdbTransactionMode};
+
+const DB_NAME: &str = "mina-web-wallet";
+const DB_VERSION: u32 = 1;
+const STORE_NAME: &str = "state";
+
+/// Load the given keys from IndexedDB
+pub async fn load(keys: &[&str]) -> Result<MemoryStorage, JsValue> {
+    let db = open().await?;
+    let store = db
+        .transaction_with_str(STORE_NAME)?
+        .object_store(STORE_NAME)?;
+    let mut storage = MemoryStorage::new();
+    for key in keys {
+        let value = wait(&store.get(&JsValue::from_str(key))?).await?;
+        if let Some(value) = value.as_string() {
+            storage
+                .set(key, &value)
+                .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        }
+    }
+    Ok(storage)
+}
+
+/// Write the given keys back to IndexedDB, deleting those without a value
+pub async fn save(storage: &MemoryStorage, keys: &[&str]) -> Result<(), JsValue> {
+    let db = open().await?;
+    let store = db
+        .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)?
+        .object_store(STORE_NAME)?;
+    for key in keys {
+        let value = storage
+            .get(key)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let request = match value {
+            Some(value) => {
+                store.put_with_key(&JsValue::from_str(&value), &JsValue::from_str(key))?
+            }
+            None => store.delete(&JsValue::from_str(key))?,
+        };
+        wait(&request).await?;
+    }
+    Ok(())
+}
+
+/// The core `Storage` over IndexedDB
+///
+/// `open` reads a snapshot of some keys, which core then reads and writes
+/// synchronously; `commit` writes the keys that changed back to IndexedDB.
+/// Other keys are refused, so that they are never overwritten unread.
+pub struct IndexedDbStorage {
+    keys: Vec<String>,
+    memory: MemoryStorage,
+    changed: BTreeSet<String>,
+}
+
+impl IndexedDbStorage {
+    /// Read the given keys from IndexedDB
+    pub async fn open(keys: &[&str]) -> Result<Self, JsValue> {
+        Ok(Self {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            memory: load(keys).await?,
+            changed: BTreeSet::new(),
+        })
+    }
+
+    /// Write the keys changed since `open` or the last commit
+    pub async fn commit(&mut self) -> Result<(), JsValue> {
+        let changed: Vec<&str> = self.changed.iter().map(String::as_str).collect();
+        save(&self.memory, &changed).await?;
+        self.changed.clear();
+        Ok(())
+    }
+
+    fn check_key(&self, key: &str) -> StorageResult<()> {
+        if self.keys.iter().any(|k| k == key) {
+            Ok(())
+        } else {
+            Err(StorageError::InvalidKey(format!("{} was not opened", key)))
+        }
+    }
+}
+
+impl Storage for IndexedDbStorage {
+    fn get(&self, key: &str) -> StorageResult<Option<String>> {
+        self.check_key(key)?;
+        self.memory.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> StorageResult<()> {
+        self.check_key(key)?;
+        self.changed.insert(key.to_string());
+        self.memory.set(key, value)
+    }
+
+    fn remove(&mut self, key: &str) -> StorageResult<()> {
+        self.check_key(key)?;
+        self.changed.insert(key.to_string());
+        self.memory.remove(key)
+    }
+}
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    let factory = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("No window available"))?
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from_str("IndexedDB is not available"))?;
+    let request = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+    let on_upgrade = Closure::once_into_js({
+        let request = request.clone();
+        move |_: Event| {
+            if let Ok(db) = request.result().and_then(|db| db.dyn_into::<IdbDatabase>())
+                && !db.object_store_names().contains(STORE_NAME)
+            {
+                let _ = db.create_object_store(STORE_NAME);
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+    wait(&request).await?.dyn_into::<IdbDatabase>()
+}
+
+/// Wait for an IndexedDB request to complete, returning its result
+async fn wait(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let on_success = Closure::once_into_js({
+            let request = request.clone();
+            move |_: Event| {
+                let result = request.result().unwrap_or(JsValue::UNDEFINED);
+                let _ = resolve.call1(&JsValue::NULL, &result);
+            }
+        });
+        let on_error = Closure::once_into_js({
+            let request = request.clone();
+            move |_: Event| {
+                let error = match request.error() {
+                    Ok(Some(error)) => JsValue::from(error),
+                    _ => JsValue::from_str("IndexedDB request failed"),
+                };
+                let _ = reject.call1(&JsValue::NULL, &error);
+            }
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
@@ -0,0 +1,85 @@
+//! Work spread over the thread pool of the threaded build
+//!
+//! The pool runs on Web Workers sharing the WebAssembly memory, and is started
+//! with `initThreadPool(navigator.hardwareConcurrency)`. Its functions block
+//! the calling thread until every worker is done, which browsers forbid on
+//! the main thread: call them from a Web Worker.
+
+use mina_web_wallet_core::vanity::{self, CancellationToken, VanityError, VanityPosition};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use wasm_bindgen::prelude::*;
+
+use crate::{ErrorCode, WalletData, WasmResult, network_name, resolve_network};
+
+/// Generate a vanity wallet on every thread of the pool
+///
+/// # Arguments
+/// * `pattern` - Base58 characters the address must contain
+/// * `position` - "prefix" (after "B62q") or "suffix"
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+/// * `max_attempts` - Give up after this many attempts, over all threads
+///
+/// # Returns
+/// JSON object with wallet data
+#[wasm_bindgen(unchecked_return_type = "WasmResult<WalletData>")]
+pub fn generate_vanity_wallet_parallel(
+    pattern: &str,
+    position: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+    max_attempts: u32,
+) -> JsValue {
+    let network_id = match resolve_network(&network) {
+        Ok(n) => n,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidNetwork, e),
+    };
+    let position: VanityPosition = match position.parse() {
+        Ok(p) => p,
+        Err(e) => return WasmResult::<WalletData>::err(ErrorCode::InvalidInput, format!("{}", e)),
+    };
+
+    let cancel = CancellationToken::new();
+    let attempts = AtomicU64::new(0);
+    let result = (0..rayon::current_num_threads())
+        .into_par_iter()
+        .map(|_| {
+            let result =
+                vanity::generate_vanity(pattern, position, network_id.clone(), &cancel, |_| {
+                    let total = attempts.fetch_add(vanity::PROGRESS_INTERVAL, Ordering::Relaxed)
+                        + vanity::PROGRESS_INTERVAL;
+                    if total >= u64::from(max_attempts) {
+                        cancel.cancel();
+                    }
+                });
+            // The first match stops the other threads
+            if result.is_ok() {
+                cancel.cancel();
+            }
+            result
+        })
+        .reduce_with(|a, b| if a.is_ok() { a } else { b })
+        .expect("at least one thread");
+
+    match result {
+        Ok(wallet) => WasmResult::ok(WalletData {
+            address: wallet.address(),
+            secret_key_hex: wallet.secret_key_hex().to_string(),
+            secret_key_base58: wallet.secret_key_base58().to_string(),
+            network: network_name(wallet.network()).to_string(),
+        }),
+        Err(VanityError::Cancelled { .. }) => WasmResult::<WalletData>::err(
+            ErrorCode::KeyGenerationFailed,
+            format!(
+                "No match after {} attempts",
+                attempts.load(Ordering::Relaxed)
+            ),
+        ),
+        Err(e @ VanityError::InvalidPattern(_)) => {
+            WasmResult::<WalletData>::err(ErrorCode::InvalidInput, e.to_string())
+        }
+        Err(e) => WasmResult::<WalletData>::err(
+            ErrorCode::KeyGenerationFailed,
+            format!("Failed to generate vanity wallet: {}", e),
+        ),
+    }
+}
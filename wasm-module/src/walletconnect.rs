@@ -0,0 +1,242 @@
+//! WalletConnect v2 sessions with remote dApps
+//!
+//! `WalletConnect` owns the relay websocket of a `WalletConnectClient` of the
+//! core crate, and signs with a `WasmWallet`. Session proposals and signing
+//! requests are passed to the `onEvent` callback, as `{ type: "proposal", id,
+//! proposer, ... }` or `{ type: "request", id, topic, peer, chainId, method,
+//! params }`; the page answers them with `approveSession`/`rejectSession` and
+//! `approveRequest`/`rejectRequest` once the user has decided.
+
+use mina_web_wallet_core::Wallet;
+use mina_web_wallet_core::walletconnect::{
+    Metadata, WalletConnectClient, WalletConnectError, chain_id,
+};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use web_sys::{MessageEvent, WebSocket};
+
+use crate::{ErrorCode, WasmResult, WasmWallet, secrets};
+
+/// A connection to the WalletConnect relay, closed with `close()`, and whose
+/// session keys `dispose()` wipes
+#[wasm_bindgen]
+pub struct WalletConnect {
+    /// `None` once disposed
+    client: Rc<RefCell<Option<WalletConnectClient>>>,
+    socket: WebSocket,
+    /// Not kept alive: `dispose()` on the wallet still wipes it
+    wallet: Weak<Wallet>,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl WalletConnect {
+    /// Connect to the relay
+    ///
+    /// # Arguments
+    /// * `wallet` - The wallet signing the approved requests
+    /// * `project_id` - WalletConnect Cloud project ID
+    /// * `metadata` - `{ name, description, url, icons }` shown to dApps
+    /// * `on_event` - Called with every proposal, request or deleted session
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        wallet: &WasmWallet,
+        project_id: &str,
+        #[wasm_bindgen(
+            unchecked_param_type = "{ name: string; description: string; url: string; icons?: string[] }"
+        )]
+        metadata: JsValue,
+        on_event: js_sys::Function,
+    ) -> Result<WalletConnect, JsValue> {
+        let wallet = wallet.shared_wallet().map_err(JsValue::from_str)?;
+        let metadata: Metadata = serde_wasm_bindgen::from_value(metadata)
+            .map_err(|e| JsValue::from_str(&format!("Invalid metadata: {}", e)))?;
+        let client = WalletConnectClient::new(metadata);
+        let url = client
+            .relay_url(project_id, now())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let socket = WebSocket::new(&url)?;
+        let client = Rc::new(RefCell::new(Some(client)));
+        secrets::register("walletconnect", &client, |client| client.borrow().is_some());
+
+        let on_open = {
+            let socket = socket.clone();
+            let client = client.clone();
+            Closure::<dyn FnMut()>::new(move || flush(&socket, &client))
+        };
+        let on_message = {
+            let socket = socket.clone();
+            let client = client.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let Some(text) = event.data().as_string() else {
+                    return;
+                };
+                // The callback may answer right away, so the client is released first
+                let result = match client.borrow_mut().as_mut() {
+                    Some(client) => client.handle_message(&text, now()),
+                    None => return,
+                };
+                flush(&socket, &client);
+                match result {
+                    Ok(Some(event)) => {
+                        let _ = on_event.call1(&JsValue::NULL, &to_js(&event));
+                    }
+                    Ok(None) => {}
+                    Err(e) => web_sys::console::warn_1(
+                        &format!("Ignoring WalletConnect message: {}", e).into(),
+                    ),
+                }
+            })
+        };
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            client,
+            socket,
+            wallet: Rc::downgrade(&wallet),
+            _on_open: on_open,
+            _on_message: on_message,
+        })
+    }
+
+    /// Pair with a dApp from the `wc:` URI it shows
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<null>")]
+    pub fn pair(&self, uri: &str) -> JsValue {
+        self.with_client(|client| client.pair(uri, now()))
+    }
+
+    /// Approve a session proposal with the address of the wallet
+    ///
+    /// # Returns
+    /// JSON object with the topic of the session
+    #[wasm_bindgen(js_name = approveSession, unchecked_return_type = "WasmResult<string>")]
+    pub fn approve_session(&self, id: f64) -> JsValue {
+        let Some(wallet) = self.wallet.upgrade() else {
+            return WasmResult::<String>::err(
+                ErrorCode::WalletDisposed,
+                "The wallet was disposed".to_string(),
+            );
+        };
+        let account = format!("{}:{}", chain_id(wallet.network()), wallet.address());
+        self.with_client(|client| client.approve_session(parse_id(id)?, &[account], now()))
+    }
+
+    /// Reject a session proposal
+    #[wasm_bindgen(js_name = rejectSession, unchecked_return_type = "WasmResult<null>")]
+    pub fn reject_session(&self, id: f64) -> JsValue {
+        self.with_client(|client| client.reject_session(parse_id(id)?))
+    }
+
+    /// Sign an approved request and send the result to the dApp
+    #[wasm_bindgen(js_name = approveRequest, unchecked_return_type = "WasmResult<null>")]
+    pub fn approve_request(&self, id: f64) -> JsValue {
+        let Some(wallet) = self.wallet.upgrade() else {
+            return WasmResult::<()>::err(
+                ErrorCode::WalletDisposed,
+                "The wallet was disposed".to_string(),
+            );
+        };
+        self.with_client(|client| client.sign_request(parse_id(id)?, &wallet))
+    }
+
+    /// Reject a request
+    #[wasm_bindgen(js_name = rejectRequest, unchecked_return_type = "WasmResult<null>")]
+    pub fn reject_request(&self, id: f64) -> JsValue {
+        self.with_client(|client| client.reject_request(parse_id(id)?))
+    }
+
+    /// Close a session
+    #[wasm_bindgen(unchecked_return_type = "WasmResult<null>")]
+    pub fn disconnect(&self, topic: &str) -> JsValue {
+        self.with_client(|client| client.disconnect(topic))
+    }
+
+    /// Close the relay websocket; open sessions resume on a new connection
+    /// only if the dApp pairs again
+    pub fn close(&self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+
+    /// Close the relay websocket and wipe the session keys
+    pub fn dispose(&self) {
+        self.close();
+        self.client.borrow_mut().take();
+    }
+}
+
+impl WalletConnect {
+    /// Run an operation on the client, send what it queued, and convert its
+    /// result
+    fn with_client<T: Serialize>(
+        &self,
+        operation: impl FnOnce(&mut WalletConnectClient) -> Result<T, WalletConnectError>,
+    ) -> JsValue {
+        let result = match self.client.borrow_mut().as_mut() {
+            Some(client) => operation(client),
+            None => {
+                return WasmResult::<T>::err(
+                    ErrorCode::WalletDisposed,
+                    "The WalletConnect client was disposed".to_string(),
+                );
+            }
+        };
+        flush(&self.socket, &self.client);
+        match result {
+            Ok(value) => WasmResult::ok(value),
+            Err(e) => WasmResult::<T>::err(error_code(&e), e.to_string()),
+        }
+    }
+}
+
+/// Send the queued messages, once the socket is open
+fn flush(socket: &WebSocket, client: &RefCell<Option<WalletConnectClient>>) {
+    if socket.ready_state() != WebSocket::OPEN {
+        return;
+    }
+    let messages = match client.borrow_mut().as_mut() {
+        Some(client) => client.take_outgoing(),
+        None => return,
+    };
+    for message in messages {
+        let _ = socket.send_with_str(&message);
+    }
+}
+
+fn error_code(error: &WalletConnectError) -> ErrorCode {
+    match error {
+        WalletConnectError::UnknownId(_) | WalletConnectError::UnknownTopic(_) => {
+            ErrorCode::NotFound
+        }
+        WalletConnectError::DecryptionFailed => ErrorCode::DecryptionFailed,
+        WalletConnectError::WrongChain { .. } => ErrorCode::InvalidNetwork,
+        WalletConnectError::InvalidRequest(_) => ErrorCode::SigningFailed,
+        _ => ErrorCode::InvalidInput,
+    }
+}
+
+/// JSON-RPC ids are integers, exact as JavaScript numbers
+fn parse_id(id: f64) -> Result<u64, WalletConnectError> {
+    if id.fract() != 0.0 || !(0.0..=9_007_199_254_740_991.0).contains(&id) {
+        return Err(WalletConnectError::InvalidMessage(format!(
+            "invalid id {}",
+            id
+        )));
+    }
+    Ok(id as u64)
+}
+
+fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+fn to_js<T: Serialize>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap_or(JsValue::NULL)
+}
@@ -0,0 +1,201 @@
+//! Watch-only accounts
+//!
+//! A `WasmWatchOnly` tracks an address without its secret key, e.g. for a
+//! portfolio or a cold wallet: it fetches balances and history (with the
+//! `network` feature), builds unsigned payments for another device to sign,
+//! and verifies signatures from the address. Its signing methods exist so that it can stand in for a
+//! `WasmWallet`, and always fail: there is no key to leak.
+
+use mina_web_wallet_core::WatchOnlyWallet;
+use mina_web_wallet_core::message::SignedMessage;
+use mina_web_wallet_core::transaction::SignedTransaction;
+use wasm_bindgen::prelude::*;
+
+use crate::network_config::Network;
+use crate::{
+    ErrorCode, WasmResult, build_payment, network_name, verify_message, verify_transaction,
+};
+#[cfg(feature = "network")]
+use crate::{fetch_balance, fetch_history, fetch_nonce};
+
+const WATCH_ONLY: &str = "This account is watch-only: it has no secret key to sign with";
+
+/// An address tracked without its secret key
+#[wasm_bindgen]
+pub struct WasmWatchOnly {
+    wallet: WatchOnlyWallet,
+    network: Network,
+}
+
+/// Track an address without its secret key
+///
+/// # Arguments
+/// * `address` - The Mina address to track
+/// * `network` - Either "mainnet" or "testnet", or a `NetworkConfig`
+#[wasm_bindgen]
+pub fn import_watch_only(
+    address: &str,
+    #[wasm_bindgen(unchecked_param_type = "Network")] network: JsValue,
+) -> Result<WasmWatchOnly, JsValue> {
+    let network = Network::from_js(&network)?;
+    let wallet = WatchOnlyWallet::from_address(address, network.id.clone())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(WasmWatchOnly { wallet, network })
+}
+
+#[wasm_bindgen]
+impl WasmWatchOnly {
+    /// The tracked address
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> String {
+        self.wallet.address()
+    }
+
+    /// The network of the address, "mainnet" or "testnet"
+    #[wasm_bindgen(getter)]
+    pub fn network(&self) -> String {
+        network_name(self.wallet.network()).to_string()
+    }
+
+    /// Always true, to tell it from a `WasmWallet`
+    #[wasm_bindgen(getter, js_name = isWatchOnly)]
+    pub fn is_watch_only(&self) -> bool {
+        true
+    }
+
+    /// Fetch the balance of the address
+    ///
+    /// # Arguments
+    /// * `endpoint` - GraphQL endpoint URL of the node, or several separated
+    ///   by commas; those of the `NetworkConfig` if omitted
+    ///
+    /// # Returns
+    /// Promise of a JSON object `{ total, liquid, locked }`, in nanomina strings
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(js_name = fetchBalance, unchecked_return_type = "Promise<WasmResult<Balance>>")]
+    pub fn fetch_balance(&self, endpoint: Option<String>) -> js_sys::Promise {
+        let future = fetch_balance(self.address(), self.network_js(), endpoint);
+        wasm_bindgen_futures::future_to_promise(async move { Ok(future.await) })
+    }
+
+    /// Fetch the next nonce of the address, to build a payment from it
+    ///
+    /// # Returns
+    /// Promise of a JSON object with the nonce
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(js_name = fetchNonce, unchecked_return_type = "Promise<WasmResult<number>>")]
+    pub fn fetch_nonce(&self, endpoint: Option<String>) -> js_sys::Promise {
+        let future = fetch_nonce(self.address(), self.network_js(), endpoint);
+        wasm_bindgen_futures::future_to_promise(async move { Ok(future.await) })
+    }
+
+    /// Fetch a page of the transaction history of the address
+    ///
+    /// # Arguments
+    /// * `limit` - Number of transactions per page
+    /// * `before` - `nextCursor` of the previous page, or undefined for the newest page
+    /// * `endpoint` - GraphQL endpoint URL of the archive indexer; the
+    ///   `archiveEndpoints` of the `NetworkConfig` if omitted
+    ///
+    /// # Returns
+    /// Promise of a JSON object `{ transactions, nextCursor }`, newest first
+    #[cfg(feature = "network")]
+    #[wasm_bindgen(
+        js_name = fetchHistory,
+        unchecked_return_type = "Promise<WasmResult<HistoryPage>>"
+    )]
+    pub fn fetch_history(
+        &self,
+        limit: u32,
+        before: Option<u32>,
+        endpoint: Option<String>,
+    ) -> js_sys::Promise {
+        let future = fetch_history(self.address(), self.network_js(), limit, before, endpoint);
+        wasm_bindgen_futures::future_to_promise(async move { Ok(future.await) })
+    }
+
+    /// Build an unsigned payment from the address, to sign on another device
+    ///
+    /// # Arguments
+    /// * `params` - As for `build_payment`; `from` and `network` default to
+    ///   those of the account
+    ///
+    /// # Returns
+    /// JSON object `{ transaction, memoBase58, preview, warnings }`
+    #[wasm_bindgen(js_name = buildPayment, unchecked_return_type = "WasmResult<UnsignedPayment>")]
+    pub fn build_payment(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "Partial<PaymentParams>")] params: JsValue,
+    ) -> JsValue {
+        // A copy, so that the object of the caller is left as is
+        let params = js_sys::Object::assign(&js_sys::Object::new(), params.unchecked_ref());
+        for (key, value) in [
+            ("from", JsValue::from(self.address())),
+            ("network", self.network_js()),
+        ] {
+            let missing = js_sys::Reflect::get(&params, &key.into())
+                .map(|v| v.is_undefined())
+                .unwrap_or(true);
+            if missing {
+                let _ = js_sys::Reflect::set(&params, &key.into(), &value);
+            }
+        }
+        let from = js_sys::Reflect::get(&params, &"from".into()).ok();
+        if from.and_then(|from| from.as_string()) != Some(self.address()) {
+            return WasmResult::<()>::err(
+                ErrorCode::InvalidInput,
+                format!("The payment must be sent from {}", self.address()),
+            );
+        }
+        build_payment(params.into())
+    }
+
+    /// Verify a message signed by the address
+    ///
+    /// # Returns
+    /// JSON object with `true` if the address signed the message
+    #[wasm_bindgen(js_name = verifyMessage, unchecked_return_type = "WasmResult<boolean>")]
+    pub fn verify_message(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "SignedMessage")] signed: JsValue,
+    ) -> JsValue {
+        match serde_wasm_bindgen::from_value::<SignedMessage>(signed.clone()) {
+            Ok(message) if message.public_key != self.address() => WasmResult::ok(false),
+            _ => verify_message(signed, self.network().into()),
+        }
+    }
+
+    /// Verify a payment or delegation signed by the address
+    ///
+    /// # Returns
+    /// JSON object with `true` if the address signed the transaction
+    #[wasm_bindgen(js_name = verifyTransaction, unchecked_return_type = "WasmResult<boolean>")]
+    pub fn verify_transaction(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "SignedTransaction")] signed: JsValue,
+    ) -> JsValue {
+        match serde_wasm_bindgen::from_value::<SignedTransaction>(signed.clone()) {
+            Ok(transaction) if transaction.public_key != self.address() => WasmResult::ok(false),
+            _ => verify_transaction(signed, self.network().into()),
+        }
+    }
+
+    /// Refused: a watch-only account cannot sign
+    #[wasm_bindgen(js_name = signPayment, unchecked_return_type = "WasmResult<never>")]
+    pub fn sign_payment(&self, _transaction: JsValue) -> JsValue {
+        WasmResult::<()>::err(ErrorCode::SigningFailed, WATCH_ONLY.to_string())
+    }
+
+    /// Refused: a watch-only account cannot sign
+    #[wasm_bindgen(js_name = signMessage, unchecked_return_type = "WasmResult<never>")]
+    pub fn sign_message(&self, _message: &str) -> JsValue {
+        WasmResult::<()>::err(ErrorCode::SigningFailed, WATCH_ONLY.to_string())
+    }
+}
+
+impl WasmWatchOnly {
+    /// The `NetworkConfig` the account was imported with, as a `network` argument
+    fn network_js(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.network.config()).unwrap_or(JsValue::NULL)
+    }
+}